@@ -0,0 +1,82 @@
+// Compile-only check for the generated text_processor.d.ts
+// Run with `npm run test:types` after generating bindings into pkg-types/
+import {
+    batch_justify,
+    justify_text_large,
+    justify_text_large_with_stats,
+    justify_text_with_options,
+    layout_text_lines,
+    text_stats,
+    validate_text_input_detailed,
+    type BatchId,
+    type BatchResult,
+    type BatchResultsWithTransfer,
+    type JustifyOptions,
+    type LayoutLine,
+    type TextStats,
+    type WebtoolsError,
+} from '../../pkg-types/text_processor';
+
+const options: JustifyOptions = { maxCharsPerLine: 36, lineNumbers: true };
+const justified: string = justify_text_with_options('你好 world', options);
+justify_text_with_options(justified, undefined);
+justify_text_with_options(justified, { maxOutputRatio: 0, strictOptions: true });
+// @ts-expect-error strictOptions is a boolean
+justify_text_with_options(justified, { strictOptions: 'yes' });
+
+const stats: TextStats = text_stats(justified);
+const cjkCount: number = stats.cjkCount;
+const hasCjk: boolean = stats.hasCjk;
+// @ts-expect-error misspelled stats field
+stats.cjkcount;
+
+const results: BatchResult[] = batch_justify(['a', 'b'], { numberStart: 10 });
+const firstLineCount: number = results[0].lineCount;
+const identified: BatchResult[] = batch_justify(['a', { id: 7, text: 'b' }, { id: 'c', text: 'c' }], undefined);
+const firstId: BatchId | undefined = identified[1].id;
+// @ts-expect-error ids are strings or numbers
+batch_justify([{ id: true, text: 'a' }], undefined);
+// @ts-expect-error batch input must be an array of strings
+batch_justify([1, 2], undefined);
+const reported: BatchResultsWithTransfer = batch_justify(['a', 'b'], undefined, false, true);
+const inputBytes: number = reported.transfer.inputBytes;
+const itemMs: number[] = reported.transfer.itemMs;
+
+const lines: LayoutLine[] = layout_text_lines(justified, options, 24, 1.5, 20, 'center');
+const widthPx: number = lines[0].widthPx;
+// @ts-expect-error unknown option
+justify_text_with_options(justified, { maxChars: 10 });
+
+const blocks: string[] = [];
+justify_text_large(new TextEncoder().encode(justified), 36, 65536, (block: string) => {
+    blocks.push(block);
+});
+// @ts-expect-error the sink takes the block as a string
+justify_text_large(new Uint8Array(0), 36, 65536, (block: number) => {});
+const streamedStats: string = justify_text_large_with_stats(
+    new TextEncoder().encode(justified),
+    36,
+    65536,
+    (block: string, statsSoFar: string) => {
+        blocks.push(block, statsSoFar);
+    },
+);
+
+const validation: WebtoolsError | null = validate_text_input_detailed('');
+if (validation && validation.code === 'INPUT_TOO_LARGE') {
+    const limit: number = validation.details.limit;
+}
+if (validation && validation.code === 'TOO_MANY_BATCH_ITEMS') {
+    const itemLimit: number = validation.details.limit;
+}
+if (validation && validation.code === 'BATCH_ITEM_TOO_LARGE') {
+    const itemId: BatchId | undefined = validation.details.id;
+}
+if (validation && validation.code === 'CONFLICTING_OPTIONS') {
+    const field: string = validation.details.field;
+}
+// @ts-expect-error unknown error code
+if (validation?.code === 'TOO_LARGE') {
+}
+
+export { streamedStats, cjkCount, hasCjk, firstLineCount, firstId, inputBytes, itemMs, widthPx };
@@ -0,0 +1,44 @@
+// Batch justification with and without use_cache on a batch that is 80% duplicates
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/batch-cache.cjs /tmp/pkg
+const path = require('path');
+
+const HEADER = '本报告由自动化系统生成，请勿直接回复。The quick brown fox jumps over the lazy dog. '.repeat(4);
+const FOOTER = 'Copyright notice: all rights reserved. 版权所有，未经许可不得转载。'.repeat(4);
+const BODY = 'Each item has its own body text, 每一项都有独立的正文内容。 ';
+
+// Four of every five items are one of the two boilerplate strings
+const texts = Array.from({ length: 2000 }, (_, i) => {
+  if (i % 5 === 4) return `${i}: ${BODY.repeat(6)}`;
+  return i % 2 === 0 ? HEADER : FOOTER;
+});
+const textsJson = JSON.stringify(texts);
+const ROUNDS = 20;
+
+function time(run) {
+  run(); // warm up
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    run();
+  }
+  return Number(process.hrtime.bigint() - start) / 1e6 / ROUNDS;
+}
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+if (wasm.batch_justify_text(textsJson, 36, true) !== wasm.batch_justify_text(textsJson, 36, false)) {
+  throw new Error('cached and uncached results differ');
+}
+
+const uncached = time(() => wasm.batch_justify_text(textsJson, 36, false));
+wasm.clear_cache();
+const cached = time(() => wasm.batch_justify_text(textsJson, 36, true));
+wasm.clear_cache();
+const deduped = time(() => {
+  wasm.clear_cache();
+  wasm.batch_justify_text(textsJson, 36, true);
+});
+
+console.log(`uncached:              ${uncached.toFixed(2)} ms/batch`);
+console.log(`use_cache, warm cache: ${cached.toFixed(2)} ms/batch`);
+console.log(`use_cache, cold cache: ${deduped.toFixed(2)} ms/batch (in-batch dedup only)`);
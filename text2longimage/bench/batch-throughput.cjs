@@ -0,0 +1,33 @@
+// Batch justification throughput of a nodejs-target build
+// Build both allocators, then compare:
+//   cargo build --release --target wasm32-unknown-unknown [--features wee_alloc]
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg-<name> target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/batch-throughput.cjs /tmp/pkg-dlmalloc /tmp/pkg-wee_alloc
+const path = require('path');
+
+const SAMPLE =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4);
+
+const texts = Array.from({ length: 2000 }, (_, i) => SAMPLE.slice(i % 40));
+const textsJson = JSON.stringify(texts);
+const ROUNDS = 20;
+
+for (const dir of process.argv.slice(2)) {
+  const wasm = require(path.resolve(dir, 'text_processor.js'));
+  wasm.batch_justify_text(textsJson, 36); // warm up
+
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    wasm.batch_justify_text(textsJson, 36);
+  }
+  const elapsedMs = Number(process.hrtime.bigint() - start) / 1e6;
+
+  const bytes = textsJson.length * ROUNDS;
+  console.log(
+    `${dir}: ${(elapsedMs / ROUNDS).toFixed(2)} ms/batch, ` +
+      `${(bytes / 1024 / 1024 / (elapsedMs / 1000)).toFixed(1)} MB/s, memory ${wasm.memory_stats()}`
+  );
+}
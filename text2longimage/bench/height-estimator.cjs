@@ -0,0 +1,40 @@
+// A width slider sweep on a 1 MB mixed CJK/English document: count_wrapped_lines at every
+// width vs one HeightEstimator answering the same widths
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/height-estimator.cjs /tmp/pkg
+const path = require('path');
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4) +
+  '\n';
+const DOCUMENT = PARAGRAPH.repeat(Math.ceil((1024 * 1024) / Buffer.byteLength(PARAGRAPH)));
+const WIDTHS = Array.from({ length: 101 }, (_, i) => 20 + i);
+
+function time(run) {
+  run(); // warm up
+  const start = process.hrtime.bigint();
+  const result = run();
+  return [Number(process.hrtime.bigint() - start) / 1e6, result];
+}
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+
+const [countMs, counted] = time(() => WIDTHS.map((width) => wasm.count_wrapped_lines(DOCUMENT, width)));
+const [buildMs, estimator] = time(() => new wasm.HeightEstimator(DOCUMENT));
+const [estimateMs, estimated] = time(() => WIDTHS.map((width) => estimator.estimate(width)));
+
+WIDTHS.forEach((width, i) => {
+  if (counted[i] !== estimated[i]) {
+    throw new Error(`${width} columns: count_wrapped_lines ${counted[i]}, HeightEstimator ${estimated[i]}`);
+  }
+});
+
+const sweep = `${WIDTHS.length} widths`;
+console.log(`count_wrapped_lines, ${sweep}: ${countMs.toFixed(2)} ms (${(countMs / WIDTHS.length).toFixed(3)} ms/width)`);
+console.log(`HeightEstimator build:          ${buildMs.toFixed(2)} ms`);
+console.log(`HeightEstimator, ${sweep}:    ${estimateMs.toFixed(2)} ms (${(estimateMs / WIDTHS.length).toFixed(3)} ms/width)`);
+estimator.free();
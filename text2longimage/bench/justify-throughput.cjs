@@ -0,0 +1,52 @@
+// justify_text and get_text_stats throughput on ~2 MB fixtures: mixed CJK/English, and English prose
+// Pass one or more nodejs-target pkg dirs to compare builds:
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/justify-throughput.cjs /tmp/pkg
+// Debug builds (cargo build --target wasm32-unknown-unknown) also report allocations per call
+const path = require('path');
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4) +
+  '\n';
+const PROSE =
+  'It was the best of times, it was the worst of times, it was the age of wisdom, ' +
+  'it was the age of foolishness, it was the epoch of belief, it was the epoch of incredulity.\n' +
+  '"Hello," said the stranger -- and nothing more.\n\n';
+const repeatTo = (text, bytes) => text.repeat(Math.ceil(bytes / Buffer.byteLength(text)));
+const FIXTURES = {
+  mixed: repeatTo(PARAGRAPH, 2 * 1024 * 1024),
+  english: repeatTo(PROSE, 2 * 1024 * 1024),
+};
+const ROUNDS = 10;
+
+function measure(wasm, run, fixture) {
+  run(fixture); // warm up
+  const before = JSON.parse(wasm.memory_stats());
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    run(fixture);
+  }
+  const elapsedMs = Number(process.hrtime.bigint() - start) / 1e6;
+  const after = JSON.parse(wasm.memory_stats());
+
+  const bytes = Buffer.byteLength(fixture) * ROUNDS;
+  let line =
+    `${(elapsedMs / ROUNDS).toFixed(2)} ms/call, ` +
+    `${(bytes / 1024 / 1024 / (elapsedMs / 1000)).toFixed(1)} MB/s`;
+  if (after.allocations !== undefined) {
+    line += `, ${Math.round((after.allocations - before.allocations) / ROUNDS)} allocations/call`;
+  }
+  return line;
+}
+
+for (const dir of process.argv.slice(2)) {
+  const wasm = require(path.resolve(dir, 'text_processor.js'));
+  for (const [name, fixture] of Object.entries(FIXTURES)) {
+    console.log(`${dir} ${name} justify_text: ${measure(wasm, (t) => wasm.justify_text(t, 36), fixture)}`);
+    console.log(`${dir} ${name} get_text_stats: ${measure(wasm, wasm.get_text_stats, fixture)}`);
+  }
+}
@@ -0,0 +1,41 @@
+// preview_snippet against wrapping the whole text and keeping the first lines, on ~4 MB fixtures
+// Most of what preview_snippet's time does grow with is copying the text into wasm, and for the
+// one-line fixture a scan of the line for CJK; justify_text + split wraps all of it.
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/preview.cjs /tmp/pkg
+const path = require('path');
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n';
+const repeatTo = (text, bytes) => text.repeat(Math.ceil(bytes / Buffer.byteLength(text)));
+const FIXTURES = {
+  paragraphs: repeatTo(PARAGRAPH, 4 * 1024 * 1024),
+  // No line breaks at all: the wrapper's early exit has to stop inside one source line
+  'one line': repeatTo('The quick brown fox jumps over the lazy dog. ', 4 * 1024 * 1024),
+};
+const WIDTH = 36;
+const LINES = 5;
+const ROUNDS = 20;
+
+function time(run) {
+  run(); // warm up
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    run();
+  }
+  return Number(process.hrtime.bigint() - start) / 1e6 / ROUNDS;
+}
+
+for (const [name, fixture] of Object.entries(FIXTURES)) {
+  const preview = time(() => wasm.preview_snippet(fixture, WIDTH, LINES));
+  const full = time(() => wasm.justify_text(fixture, WIDTH).split('\r\n').slice(0, LINES).join('\r\n'));
+  console.log(
+    `${name}: preview_snippet ${preview.toFixed(3)} ms, ` +
+      `justify_text + split ${full.toFixed(2)} ms (${(full / preview).toFixed(0)}x)`,
+  );
+}
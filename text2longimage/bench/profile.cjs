@@ -0,0 +1,48 @@
+// set_profiling/take_profile on a few instrumented calls; fails if the profile JSON is missing
+// a field, or if a call's stages don't roughly add up to its totalMs
+//   cargo build --release --target wasm32-unknown-unknown --features profiling
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/profile.cjs /tmp/pkg
+const path = require('path');
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+
+const STAGES = ['decode', 'classify', 'wrap', 'serialize'];
+const text = 'The quick brown fox jumps over the lazy dog. 日本語の文章も混ぜておく。\n'.repeat(2000);
+const texts = Array.from({ length: 200 }, (_, i) => `item ${i}: ${text.slice(0, 400)}`);
+// Entering and leaving a call isn't any stage; allow that much besides rounding
+const SLACK_MS = 2;
+
+const fail = (message) => {
+  console.error(message);
+  process.exit(1);
+};
+
+wasm.set_profiling(true);
+wasm.justify_text(text, 40);
+wasm.justify_text_with_options(text, { maxCharsPerLine: 40 });
+wasm.batch_justify(texts, { maxCharsPerLine: 30 });
+wasm.batch_justify_text(JSON.stringify(texts), 30);
+wasm.get_text_stats(text);
+wasm.layout_text(text, 40, 16, 1.5, 20, 'left');
+wasm.set_profiling(false);
+wasm.justify_text(text, 40);
+
+const profile = JSON.parse(wasm.take_profile());
+if (profile.calls.length !== 6) fail(`expected 6 calls, got ${profile.calls.length}`);
+const sum = (stages) => STAGES.reduce((total, stage) => total + stages[stage], 0);
+for (const call of profile.calls) {
+  if (typeof call.operation !== 'string' || typeof call.totalMs !== 'number') fail(`bad call ${JSON.stringify(call)}`);
+  for (const stage of STAGES) {
+    if (typeof call.stagesMs[stage] !== 'number') fail(`${call.operation}: no stagesMs.${stage}`);
+  }
+  const stages = sum(call.stagesMs);
+  console.log(`${call.operation}: ${call.totalMs.toFixed(2)} ms, stages ${stages.toFixed(2)} ms`);
+  if (stages > call.totalMs + 1e-6 || call.totalMs - stages > SLACK_MS) {
+    fail(`${call.operation}: stages add up to ${stages} ms of ${call.totalMs} ms`);
+  }
+}
+const totalMs = profile.calls.reduce((total, call) => total + call.totalMs, 0);
+if (Math.abs(profile.totalMs - totalMs) > 1e-6) fail(`totalMs ${profile.totalMs}, calls add up to ${totalMs}`);
+if (JSON.parse(wasm.take_profile()).calls.length !== 0) fail('take_profile should start over');
+console.log(`profile ok: ${profile.totalMs.toFixed(2)} ms over ${profile.calls.length} calls`);
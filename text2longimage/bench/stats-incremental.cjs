@@ -0,0 +1,59 @@
+// Per-keystroke stats while typing at the end of a 1 MB document: get_text_stats over the
+// whole text against stats_incremental; fails if they disagree or the incremental call
+// isn't several times cheaper
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/stats-incremental.cjs /tmp/pkg
+const path = require('path');
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4) +
+  '\n';
+const DOCUMENT = PARAGRAPH.repeat(Math.ceil((1024 * 1024) / Buffer.byteLength(PARAGRAPH)));
+const TYPED = 'Typing at the end, 一字ずつ, שלום עולם.\nAnd on the next line. ';
+const MIN_SPEEDUP = 5;
+
+function fail(message) {
+  console.error(message);
+  process.exit(1);
+}
+
+function time(run) {
+  const start = process.hrtime.bigint();
+  run();
+  return Number(process.hrtime.bigint() - start) / 1e6;
+}
+
+wasm.get_text_stats(DOCUMENT); // warm up
+
+let full;
+const fullMs = time(() => {
+  for (let typed = 1; typed <= TYPED.length; typed++) {
+    full = wasm.get_text_stats(DOCUMENT + TYPED.slice(0, typed));
+  }
+});
+
+let text = DOCUMENT;
+let stats = wasm.get_text_stats(text);
+const incrementalMs = time(() => {
+  for (let typed = 1; typed <= TYPED.length; typed++) {
+    const next = DOCUMENT + TYPED.slice(0, typed);
+    stats = wasm.stats_incremental(stats, text.length, next);
+    text = next;
+  }
+});
+
+const perKey = (ms) => `${(ms / TYPED.length).toFixed(3)} ms/keystroke`;
+console.log(`get_text_stats:    ${perKey(fullMs)}`);
+console.log(`stats_incremental: ${perKey(incrementalMs)}`);
+if (stats !== full) {
+  fail(`stats_incremental gave ${stats}, get_text_stats ${full}`);
+}
+if (incrementalMs * MIN_SPEEDUP > fullMs) {
+  fail(`expected stats_incremental to be at least ${MIN_SPEEDUP}x cheaper`);
+}
@@ -0,0 +1,28 @@
+// get_text_stats on a 1 MB mixed CJK/English document
+// Pass one or more nodejs-target pkg dirs to compare builds:
+//   node bench/text-stats.cjs /tmp/pkg-before /tmp/pkg-after
+const path = require('path');
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4) +
+  '\n';
+const DOCUMENT = PARAGRAPH.repeat(Math.ceil((1024 * 1024) / Buffer.byteLength(PARAGRAPH)));
+const ROUNDS = 50;
+
+for (const dir of process.argv.slice(2)) {
+  const wasm = require(path.resolve(dir, 'text_processor.js'));
+  const expected = wasm.get_text_stats(DOCUMENT); // warm up
+
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    if (wasm.get_text_stats(DOCUMENT) !== expected) {
+      throw new Error('unstable result');
+    }
+  }
+  const elapsedMs = Number(process.hrtime.bigint() - start) / 1e6;
+
+  console.log(`${dir}: ${(elapsedMs / ROUNDS).toFixed(2)} ms/call ${expected}`);
+}
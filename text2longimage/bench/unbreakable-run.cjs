@@ -0,0 +1,25 @@
+// justify_text_with_options on a pasted megabyte of base64 with no spaces, which
+// maxUnbreakableRun cuts into lines; fails if a line overflows or the call isn't quick
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/unbreakable-run.cjs /tmp/pkg
+const path = require('path');
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+
+const BASE64 = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/';
+const token = Array.from({ length: 1024 * 1024 }, (_, i) => BASE64[(i * 7) % 64]).join('');
+const text = `Pasted: ${token} end of paste`;
+const WIDTH = 36;
+const LIMIT_MS = 500;
+
+const start = process.hrtime.bigint();
+const lines = wasm.justify_text_with_options(text, { maxCharsPerLine: WIDTH }).split('\r\n');
+const elapsedMs = Number(process.hrtime.bigint() - start) / 1e6;
+const widest = Math.max(...lines.map((line) => line.length));
+console.log(`1 MB token: ${elapsedMs.toFixed(1)} ms, ${lines.length} lines, widest ${widest} columns`);
+console.log(`longestUnbreakableRun: ${JSON.parse(wasm.get_text_stats(text)).longestUnbreakableRun}`);
+if (widest > WIDTH || elapsedMs > LIMIT_MS) {
+  console.error(`expected lines of at most ${WIDTH} columns within ${LIMIT_MS} ms`);
+  process.exit(1);
+}
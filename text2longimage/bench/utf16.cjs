@@ -0,0 +1,45 @@
+// justify_text (JS string in and out) vs justify_text_utf16 (Uint16Array in and out)
+// on a ~2 MB Chinese document
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/utf16.cjs /tmp/pkg
+const path = require('path');
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和少量English混排。我们需要处理很长的文档，' +
+  '每一段都有几十个汉字，偶尔夹杂数字123和符号。\n\n';
+const DOCUMENT = PARAGRAPH.repeat(Math.ceil((2 * 1024 * 1024) / Buffer.byteLength(PARAGRAPH)));
+const ROUNDS = 10;
+
+function toUnits(text) {
+  const units = new Uint16Array(text.length);
+  for (let i = 0; i < text.length; i++) {
+    units[i] = text.charCodeAt(i);
+  }
+  return units;
+}
+
+function time(run) {
+  run(); // warm up
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    run();
+  }
+  return Number(process.hrtime.bigint() - start) / 1e6 / ROUNDS;
+}
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+const decoder = new TextDecoder('utf-16le');
+const units = toUnits(DOCUMENT);
+
+if (decoder.decode(wasm.justify_text_utf16(units, 36)) !== wasm.justify_text(DOCUMENT, 36)) {
+  throw new Error('UTF-16 and string results differ');
+}
+
+const stringPath = time(() => wasm.justify_text(DOCUMENT, 36));
+const unitsPath = time(() => wasm.justify_text_utf16(units, 36));
+const unitsWithConversion = time(() => decoder.decode(wasm.justify_text_utf16(toUnits(DOCUMENT), 36)));
+
+console.log(`string in/out:                   ${stringPath.toFixed(2)} ms/call`);
+console.log(`Uint16Array in/out:              ${unitsPath.toFixed(2)} ms/call`);
+console.log(`string -> Uint16Array -> string: ${unitsWithConversion.toFixed(2)} ms/call`);
@@ -0,0 +1,42 @@
+// justify_text (JS string in and out) vs justify_text_bytes (UTF-8 Uint8Array in and out)
+// on a ~5 MB mixed CJK/English document, as fetch() bytes headed for a Blob
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target nodejs --out-dir /tmp/pkg target/wasm32-unknown-unknown/release/text_processor.wasm
+//   node bench/utf8-bytes.cjs /tmp/pkg
+const path = require('path');
+
+const PARAGRAPH =
+  '这是一段用于测试的中文文本，包含标点符号和English words混排。\n' +
+  'The quick brown fox jumps over the lazy dog. '.repeat(8) +
+  '\n\n' +
+  '日本語のテキストも含まれています。'.repeat(4) +
+  '\n';
+const DOCUMENT = PARAGRAPH.repeat(Math.ceil((5 * 1024 * 1024) / Buffer.byteLength(PARAGRAPH)));
+const ROUNDS = 10;
+
+function time(run) {
+  run(); // warm up
+  const start = process.hrtime.bigint();
+  for (let round = 0; round < ROUNDS; round++) {
+    run();
+  }
+  return Number(process.hrtime.bigint() - start) / 1e6 / ROUNDS;
+}
+
+const wasm = require(path.resolve(process.argv[2] || 'pkg', 'text_processor.js'));
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+const bytes = encoder.encode(DOCUMENT);
+
+if (decoder.decode(wasm.justify_text_bytes(bytes, 36, false)) !== wasm.justify_text(DOCUMENT, 36)) {
+  throw new Error('byte and string results differ');
+}
+
+const stringPath = time(() => wasm.justify_text(DOCUMENT, 36));
+const bytesPath = time(() => wasm.justify_text_bytes(bytes, 36, false));
+const decodedPath = time(() => encoder.encode(wasm.justify_text(decoder.decode(bytes), 36)));
+
+console.log(`${(bytes.length / 1024 / 1024).toFixed(1)} MB`);
+console.log(`string in/out:                  ${stringPath.toFixed(2)} ms/call`);
+console.log(`Uint8Array in/out:              ${bytesPath.toFixed(2)} ms/call`);
+console.log(`bytes -> string -> bytes:       ${decodedPath.toFixed(2)} ms/call`);
@@ -0,0 +1,35 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Build metadata for build_info(): short git hash and a unix build timestamp
+fn main() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=TEXT_PROCESSOR_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=TEXT_PROCESSOR_BUILD_TIMESTAMP={}",
+        timestamp
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
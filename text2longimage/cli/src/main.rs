@@ -0,0 +1,217 @@
+//! Command-line front end for text_processor_core
+//! Reads a file (or stdin) and writes wrapped text or statistics JSON to stdout
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use text_processor_core::{
+    decode_utf8, get_text_stats, justify_text, paragraph_stats, strip_ansi,
+    DEFAULT_MAX_CHARS_PER_LINE,
+};
+
+const USAGE: &str = "\
+Usage: text2longimage [OPTIONS] [FILE]
+
+Wrap text the same way the text2longimage web app does.
+Reads FILE, or stdin when FILE is omitted or '-'.
+
+Options:
+  -w, --width <COLUMNS>  Line width in half-width columns (CJK characters take two) [default: 36]
+  -m, --mode <MODE>      justify (wrapped text), stats (JSON) or paragraphs (JSON) [default: justify]
+      --crlf             Keep CRLF line endings in wrapped output instead of LF
+      --strip-ansi       Remove terminal color codes and other ANSI escapes first
+  -h, --help             Print this help";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Justify,
+    Stats,
+    Paragraphs,
+}
+
+struct Args {
+    width: u32,
+    mode: Mode,
+    crlf: bool,
+    strip_ansi: bool,
+    input: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        width: DEFAULT_MAX_CHARS_PER_LINE,
+        mode: Mode::Justify,
+        crlf: false,
+        strip_ansi: false,
+        input: None,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-w" | "--width" => {
+                let value = args.next().ok_or("--width needs a value")?;
+                parsed.width = value
+                    .parse()
+                    .map_err(|_| format!("Invalid width: {}", value))?;
+            }
+            "-m" | "--mode" => {
+                let value = args.next().ok_or("--mode needs a value")?;
+                parsed.mode = match value.as_str() {
+                    "justify" => Mode::Justify,
+                    "stats" => Mode::Stats,
+                    "paragraphs" => Mode::Paragraphs,
+                    _ => return Err(format!("Unknown mode: {}", value)),
+                };
+            }
+            "--crlf" => parsed.crlf = true,
+            "--strip-ansi" => parsed.strip_ansi = true,
+            "-" => parsed.input = None,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => {
+                if parsed.input.is_some() {
+                    return Err("Only one input file is supported".to_string());
+                }
+                parsed.input = Some(arg);
+            }
+        }
+    }
+
+    Ok(Some(parsed))
+}
+
+fn read_input(path: Option<&str>) -> Result<Vec<u8>, String> {
+    match path {
+        Some(path) => fs::read(path).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("stdin: {}", e))?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// What args asks to be written for the input bytes, without the final line break
+fn render(args: &Args, bytes: &[u8]) -> Result<String, String> {
+    let mut text = decode_utf8(bytes, false).map_err(|e| e.to_string())?;
+    if args.strip_ansi {
+        text = strip_ansi(&text).into();
+    }
+
+    Ok(match args.mode {
+        Mode::Justify => {
+            let justified = justify_text(&text, args.width);
+            if args.crlf {
+                justified
+            } else {
+                justified.replace("\r\n", "\n")
+            }
+        }
+        Mode::Stats => get_text_stats(&text),
+        Mode::Paragraphs => paragraph_stats(&text, args.width),
+    })
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let bytes = read_input(args.input.as_deref())?;
+    let output = render(&args, &bytes)?;
+
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.write_all(if args.crlf { b"\r\n" } else { b"\n" }))
+        .map_err(|e| format!("stdout: {}", e))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn flags_and_defaults() {
+        let parsed = args(&[]).unwrap().unwrap();
+        assert_eq!(parsed.width, DEFAULT_MAX_CHARS_PER_LINE);
+        assert!(parsed.mode == Mode::Justify && !parsed.crlf && parsed.input.is_none());
+
+        let parsed = args(&["-w", "12", "--mode", "stats", "--crlf", "notes.txt"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.width, 12);
+        assert!(parsed.mode == Mode::Stats && parsed.crlf);
+        assert_eq!(parsed.input.as_deref(), Some("notes.txt"));
+        assert!(args(&["notes.txt", "-"]).unwrap().unwrap().input.is_none());
+        assert!(args(&["-w", "12", "--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_arguments_are_named() {
+        assert_eq!(args(&["-w"]).err().unwrap(), "--width needs a value");
+        assert_eq!(args(&["-w", "wide"]).err().unwrap(), "Invalid width: wide");
+        assert_eq!(args(&["-m", "html"]).err().unwrap(), "Unknown mode: html");
+        assert_eq!(args(&["--wrap"]).err().unwrap(), "Unknown option: --wrap");
+        assert_eq!(
+            args(&["a.txt", "b.txt"]).err().unwrap(),
+            "Only one input file is supported"
+        );
+    }
+
+    #[test]
+    fn output_is_the_core_functions() {
+        let text = "这是一段中文 mixed with English\r\n\x1b[31mred\x1b[0m";
+        let mut parsed = args(&["-w", "10"]).unwrap().unwrap();
+        let justified = justify_text(text, 10);
+        assert_eq!(
+            render(&parsed, text.as_bytes()).unwrap(),
+            justified.replace("\r\n", "\n")
+        );
+        parsed.crlf = true;
+        assert_eq!(render(&parsed, text.as_bytes()).unwrap(), justified);
+        parsed.strip_ansi = true;
+        assert_eq!(
+            render(&parsed, text.as_bytes()).unwrap(),
+            justify_text(&strip_ansi(text), 10)
+        );
+        parsed.mode = Mode::Stats;
+        assert_eq!(
+            render(&parsed, text.as_bytes()).unwrap(),
+            get_text_stats(&strip_ansi(text))
+        );
+        parsed.mode = Mode::Paragraphs;
+        assert_eq!(
+            render(&parsed, text.as_bytes()).unwrap(),
+            paragraph_stats(&strip_ansi(text), 10)
+        );
+        // Not UTF-8: the error names the offset
+        assert!(render(&parsed, b"ok\xff").unwrap_err().contains('2'));
+    }
+}
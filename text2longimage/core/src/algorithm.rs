@@ -0,0 +1,301 @@
+use crate::WebtoolsError;
+
+/// Revisions of the wrapping algorithm behind JustifyOptions
+/// A released version's output never changes, so renders can be reproduced exactly by pinning
+/// it; improvements that move wrap points go into a new version. The exceptions are
+/// JustifyOptions' safety caps, in every version: maxUnbreakableRun, which cuts words far
+/// wider than any line, maxMarksPerCluster, which drops marks piled onto one character, and
+/// maxCharRepeat, which cuts runs of one character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AlgorithmVersion {
+    /// Greedy wrapping as justify_text first did it: English words are measured by their
+    /// UTF-8 byte length, every character above U+00FF is two columns wide, and CJK lines
+    /// break before whichever character doesn't fit
+    V1,
+    /// V1, except where a CJK line has to cut through a run of printable ASCII: the break
+    /// moves back to just after punctuation within JustifyOptions' punctuationBreakWindow,
+    /// and URLs and paths break after a separator as in justify_text_cjk; and a number or
+    /// date token ("1.5e-7", "2024-03-15") or right-to-left run that doesn't fit moves to the
+    /// next line whole, as justify_text moves it
+    V2,
+    /// V2, except that astral-plane letters fonts draw narrow (Mathematical Alphanumeric
+    /// Symbols such as 𝐀𝐁𝐂, Gothic, Old Italic, Deseret) are one column wide instead of two;
+    /// Extension B and later ideographs and emoji stay two
+    V3,
+    /// V3, except that Latin words may break after the characters in JustifyOptions'
+    /// breakAfterChars (dashes, '/', middle dots) instead of overflowing, and a CJK line never
+    /// starts with one of them
+    V4,
+    /// V4, except that with JustifyOptions' markdown an inline code span (`HashMap::new()`,
+    /// backticks included) is one unbreakable token, never broken inside or next to its
+    /// backticks
+    V5,
+    /// V5, except that U+FEFF is a word joiner: zero columns wide, with no line break on
+    /// either side of it
+    V6,
+    /// V6, except that everything from the first line equal to JustifyOptions'
+    /// signatureDelimiter to the end of the text is a signature, written as it is instead of
+    /// wrapped
+    V7,
+}
+
+impl AlgorithmVersion {
+    /// Newest version, used when an options object doesn't pick one
+    pub const LATEST: AlgorithmVersion = AlgorithmVersion::V7;
+
+    /// Every version, oldest first
+    pub const ALL: &'static [AlgorithmVersion] = &[
+        AlgorithmVersion::V1,
+        AlgorithmVersion::V2,
+        AlgorithmVersion::V3,
+        AlgorithmVersion::V4,
+        AlgorithmVersion::V5,
+        AlgorithmVersion::V6,
+        AlgorithmVersion::V7,
+    ];
+
+    pub fn from_number(version: u32) -> Result<Self, WebtoolsError> {
+        AlgorithmVersion::ALL
+            .iter()
+            .copied()
+            .find(|known| known.number() == version)
+            .ok_or_else(|| {
+                WebtoolsError::invalid_argument(format!(
+                    "Unknown algorithm version: {} (latest is {})",
+                    version,
+                    AlgorithmVersion::LATEST.number()
+                ))
+            })
+    }
+
+    pub fn number(self) -> u32 {
+        match self {
+            AlgorithmVersion::V1 => 1,
+            AlgorithmVersion::V2 => 2,
+            AlgorithmVersion::V3 => 3,
+            AlgorithmVersion::V4 => 4,
+            AlgorithmVersion::V5 => 5,
+            AlgorithmVersion::V6 => 6,
+            AlgorithmVersion::V7 => 7,
+        }
+    }
+
+    /// One line for a settings panel
+    pub fn description(self) -> &'static str {
+        match self {
+            AlgorithmVersion::V1 => "Greedy wrapping; every character above U+00FF is two columns",
+            AlgorithmVersion::V2 => {
+                "Long ASCII runs in CJK lines break after punctuation and URL separators"
+            }
+            AlgorithmVersion::V3 => "Narrow astral letters such as 𝐀𝐁𝐂 are one column wide",
+            AlgorithmVersion::V4 => "Words break after dashes, '/' and middle dots",
+            AlgorithmVersion::V5 => "Markdown inline code spans are kept whole",
+            AlgorithmVersion::V6 => "U+FEFF inside text is a zero-width word joiner",
+            AlgorithmVersion::V7 => "A signature block after a \"-- \" line is kept as written",
+        }
+    }
+}
+
+/// Number of the newest wrapping algorithm version
+pub fn latest_algorithm_version() -> u32 {
+    AlgorithmVersion::LATEST.number()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JustifyOptions;
+
+    /// Inputs, the width they wrap at and their V1 output, which must never change
+    const V1_SNAPSHOTS: &[(&str, u32, &str)] = &[
+        // English words are measured in bytes: "café café" is 9 columns but 11 bytes
+        ("café café café", 10, "café\r\ncafé\r\ncafé"),
+        ("naïve résumé here", 8, "naïve\r\nrésumé\r\nhere"),
+        ("这是一段中文文本", 6, "这是一\r\n段中文\r\n文本"),
+        ("中文English混排的文本", 10, "中文Englis\r\nh混排的文\r\n本"),
+        ("漢字、。漢字", 4, "漢字\r\n、。\r\n漢字"),
+        // Number tokens break like any other characters
+        ("中文1.5e-7", 7, "中文1.5\r\ne-7"),
+        ("時刻12:30:45です", 8, "時刻12:3\r\n0:45です"),
+        // So do right-to-left runs; lines without CJK wrap them by word
+        ("中文שלום", 8, "中文של\r\nום"),
+        ("hello שלום עולם there", 8, "hello\r\nשלום\r\nעולם\r\nthere"),
+        // Two columns for every character above U+00FF, astral letters included
+        ("𝐀𝐁𝐂 𝐃𝐄𝐅", 8, "𝐀𝐁𝐂\r\n𝐃𝐄𝐅"),
+        ("well-known/path·x", 8, "well-known/path·x"),
+        (
+            "see www.example.com/path ok",
+            12,
+            "see\r\nwww.example.com/path\r\nok",
+        ),
+        ("中文www.example.com/a", 10, "中文www.ex\r\nample.com/\r\na"),
+        (
+            "中文https://example.com/path/to",
+            8,
+            "中文http\r\ns://exam\r\nple.com/\r\npath/to",
+        ),
+        // A ZWJ sequence is its characters: two columns each, the joiners included
+        (
+            "家族👨\u{200d}👩\u{200d}👧\u{200d}👦表情👩\u{200d}💻",
+            6,
+            "家族👨\r\n\u{200d}👩\u{200d}\r\n👧\u{200d}👦\r\n表情👩\r\n\u{200d}💻",
+        ),
+        (
+            "abc 👨\u{200d}👩\u{200d}👧 def",
+            5,
+            "abc\r\n👨\u{200d}👩\u{200d}👧\r\ndef",
+        ),
+        // Combining marks too, so a CJK line may break before one
+        (
+            "cafe\u{301} cafe\u{301} cafe\u{301}",
+            6,
+            "cafe\u{301}\r\ncafe\u{301}\r\ncafe\u{301}",
+        ),
+        (
+            "中文e\u{301}\u{301}字字",
+            6,
+            "中文e\r\n\u{301}\u{301}字\r\n字",
+        ),
+        (
+            "漢字\u{3099}漢字\u{3099}",
+            4,
+            "漢字\r\n\u{3099}漢\r\n字\u{3099}",
+        ),
+        ("a\u{feff}b c\u{feff}d e", 10, "a\u{feff}b\r\nc\u{feff}d e"),
+        ("中\u{feff}文中文中文", 6, "中\u{feff}文\r\n中文中\r\n文"),
+        ("`a b c` d", 6, "`a b\r\nc` d"),
+        (
+            "body\n-- \nsig line that is long",
+            10,
+            "body\r\n--\r\nsig line\r\nthat is\r\nlong",
+        ),
+    ];
+
+    fn v1(max_chars_per_line: u32) -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line,
+            algorithm_version: 1,
+            ..JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn v1_output_is_frozen() {
+        for &(text, max_chars_per_line, expected) in V1_SNAPSHOTS {
+            assert_eq!(v1(max_chars_per_line).justify(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn options_of_later_versions_leave_v1_alone() {
+        for &(text, max_chars_per_line, expected) in V1_SNAPSHOTS {
+            let options = JustifyOptions {
+                markdown: true,
+                break_after_chars: "-/·".to_string(),
+                signature_delimiter: "-- ".to_string(),
+                ..v1(max_chars_per_line)
+            };
+            assert_eq!(options.justify(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn later_versions_move_the_wrap_points() {
+        let latest = |max_chars_per_line| JustifyOptions {
+            max_chars_per_line,
+            markdown: true,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(latest(8).justify("𝐀𝐁𝐂 𝐃𝐄𝐅"), "𝐀𝐁𝐂 𝐃𝐄𝐅");
+        assert_eq!(
+            latest(8).justify("well-known/path·x"),
+            "well-\r\nknown/\r\npath·x"
+        );
+        assert_eq!(
+            latest(10).justify("中文www.example.com/a"),
+            "中文www.\r\nexample.\r\ncom/a"
+        );
+        assert_eq!(latest(6).justify("`a b c` d"), "`a b c`\r\nd");
+        assert_eq!(
+            latest(10).justify("body\n-- \nsig line that is long"),
+            "body\r\n-- \r\nsig line that is long"
+        );
+    }
+
+    /// A dash-joined compound near the margin and a ・-separated name list, as versions 3 and
+    /// 4 wrap them
+    const BREAK_AFTER_EXAMPLES: [(&str, u32, &str, &str); 3] = [
+        (
+            "The state—of—the—art approach",
+            20,
+            "The\r\nstate—of—the—art\r\napproach",
+            "The state—of—\r\nthe—art approach",
+        ),
+        (
+            "ジョン・スミス・タナカ・サトウ",
+            14,
+            "ジョン・スミス\r\n・タナカ・サト\r\nウ",
+            "ジョン・スミ\r\nス・タナカ・サ\r\nトウ",
+        ),
+        // Hyphens only between letters, and never inside a run: "--flag" stays whole
+        (
+            "x-ray and/or --flag",
+            10,
+            "x-ray\r\nand/or\r\n--flag",
+            "x-ray and/\r\nor --flag",
+        ),
+    ];
+
+    fn version(algorithm_version: u32, max_chars_per_line: u32) -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line,
+            algorithm_version,
+            ..JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn v4_breaks_after_dashes_and_middle_dots() {
+        for (text, width, v3, v4) in BREAK_AFTER_EXAMPLES {
+            assert_eq!(version(3, width).justify(text), v3, "{text:?} in V3");
+            assert_eq!(version(4, width).justify(text), v4, "{text:?} in V4");
+        }
+        // A break after a dash is marked without a space to take away
+        let text = BREAK_AFTER_EXAMPLES[0].0;
+        let marked = JustifyOptions {
+            mark_soft_breaks: "\u{200B}".into(),
+            ..version(4, 20)
+        }
+        .justify(text);
+        assert_eq!(crate::remove_soft_breaks(&marked, "\u{200B}"), text);
+    }
+
+    #[test]
+    fn version_numbers() {
+        assert_eq!(
+            latest_algorithm_version(),
+            AlgorithmVersion::LATEST.number()
+        );
+        assert_eq!(
+            AlgorithmVersion::ALL.last(),
+            Some(&AlgorithmVersion::LATEST)
+        );
+        for (index, &version) in AlgorithmVersion::ALL.iter().enumerate() {
+            assert_eq!(version.number() as usize, index + 1);
+            assert_eq!(
+                AlgorithmVersion::from_number(version.number()).unwrap(),
+                version
+            );
+        }
+        let error = AlgorithmVersion::from_number(0).unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+        let latest = format!("latest is {}", latest_algorithm_version());
+        assert!(error.to_string().contains(&latest), "{}", error);
+        // An options object with an unknown version wraps with the latest
+        let unknown = JustifyOptions {
+            algorithm_version: 99,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(unknown.algorithm(), AlgorithmVersion::LATEST);
+    }
+}
@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+
+use crate::edits::{EditRecorder, TextEdit};
+
+const ESC: u8 = 0x1B;
+const BEL: u8 = 0x07;
+
+/// Remove ANSI escape sequences, e.g. the color codes in pasted terminal output
+/// Handles CSI (`ESC [ ... final`), OSC and the other string sequences (`ESC ] ...`,
+/// `ESC P ...`) terminated by BEL or ST (`ESC \`), and two-byte escapes like `ESC 7`. A string
+/// sequence with no terminator ends at the next line break. A truncated sequence at the end
+/// of input is dropped, and an ESC that doesn't start a sequence is removed on its own.
+pub fn strip_ansi(text: &str) -> String {
+    strip_ansi_counted(text).0.into_owned()
+}
+
+/// strip_ansi plus the number of sequences (and lone escapes) removed
+/// Borrows the input when it contains no ESC
+pub(crate) fn strip_ansi_counted(text: &str) -> (Cow<'_, str>, usize) {
+    let (stripped, removed, _) = strip_ansi_recorded(text, false);
+    (stripped, removed)
+}
+
+/// strip_ansi_counted, and the edits that remove the sequences when record is set
+pub(crate) fn strip_ansi_recorded(
+    text: &str,
+    record: bool,
+) -> (Cow<'_, str>, usize, Vec<TextEdit>) {
+    let bytes = text.as_bytes();
+    let Some(first) = bytes.iter().position(|&b| b == ESC) else {
+        return (Cow::Borrowed(text), 0, Vec::new());
+    };
+
+    let mut out = EditRecorder::new(text.len(), record);
+    let mut removed = 0;
+    let mut copied = 0;
+    let mut i = first;
+    while i < bytes.len() {
+        if bytes[i] != ESC {
+            i += 1;
+            continue;
+        }
+        out.keep(&text[copied..i]);
+        let start = i;
+        i = sequence_end(bytes, i);
+        // An OSC title or hyperlink can carry non-ASCII text, so count chars, not bytes
+        out.replace(text[start..i].chars().count(), "");
+        copied = i;
+        removed += 1;
+    }
+    out.keep(&text[copied..]);
+
+    let (stripped, edits) = out.finish();
+    (Cow::Owned(stripped), removed, edits)
+}
+
+/// Index just past the escape sequence starting at bytes[start] (an ESC)
+/// Always a char boundary: a sequence ends after an ASCII byte, before a line break or ESC,
+/// or at the end of input
+fn sequence_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    match bytes.get(i) {
+        // CSI: parameter bytes, intermediate bytes, one final byte
+        Some(b'[') => {
+            i += 1;
+            while matches!(bytes.get(i), Some(0x30..=0x3F)) {
+                i += 1;
+            }
+            while matches!(bytes.get(i), Some(0x20..=0x2F)) {
+                i += 1;
+            }
+            // Anything else is malformed: stop before it and keep it as text
+            if matches!(bytes.get(i), Some(0x40..=0x7E)) {
+                i += 1;
+            }
+            i
+        }
+        // OSC, DCS, SOS, PM and APC carry a string up to BEL or ST
+        Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+            i += 1;
+            while let Some(&b) = bytes.get(i) {
+                match b {
+                    BEL => return i + 1,
+                    ESC if bytes.get(i + 1) == Some(&b'\\') => return i + 2,
+                    ESC | b'\n' | b'\r' => return i,
+                    _ => i += 1,
+                }
+            }
+            i
+        }
+        // nF / Fp / Fe / Fs: optional intermediates, then one final byte
+        Some(0x21..=0x7E) => {
+            let mut end = i;
+            while matches!(bytes.get(end), Some(0x20..=0x2F)) {
+                end += 1;
+            }
+            match bytes.get(end) {
+                Some(0x30..=0x7E) => end + 1,
+                // No final byte: only the ESC goes, the text after it stays
+                _ => i,
+            }
+        }
+        // A lone ESC (end of input, space, control character or non-ASCII next)
+        _ => i,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::apply_edits;
+
+    fn removed(text: &str) -> (String, usize) {
+        let (stripped, removed) = strip_ansi_counted(text);
+        (stripped.into_owned(), removed)
+    }
+
+    #[test]
+    fn colored_ls_output() {
+        let ls = "\x1b[0m\x1b[01;34mdocs\x1b[0m  \x1b[01;32mbuild.sh\x1b[0m*  README.md\n";
+        assert_eq!(removed(ls), ("docs  build.sh*  README.md\n".to_string(), 5));
+        // 256-color and truecolor parameters, and a cursor move with no parameters
+        assert_eq!(
+            strip_ansi("\x1b[38;5;208m橙\x1b[48;2;0;0;0m黒\x1b[K"),
+            "橙黒"
+        );
+    }
+
+    #[test]
+    fn string_sequences_end_at_bel_or_st() {
+        let title = "\x1b]0;ビルド\x07done";
+        assert_eq!(removed(title), ("done".to_string(), 1));
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ text";
+        assert_eq!(removed(link), ("link text".to_string(), 2));
+        // An unterminated one stops at the line break
+        assert_eq!(strip_ansi("\x1b]0;title\nnext"), "\nnext");
+    }
+
+    #[test]
+    fn truncated_sequences_at_the_end_are_dropped() {
+        for tail in [
+            "\x1b",
+            "\x1b[",
+            "\x1b[01;3",
+            "\x1b]0;title",
+            "\x1b]0;标题\x1b",
+        ] {
+            let text = format!("ok{}", tail);
+            assert_eq!(strip_ansi(&text), "ok", "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn a_literal_esc_goes_on_its_own() {
+        assert_eq!(removed("a\x1b b"), ("a b".to_string(), 1));
+        assert_eq!(removed("\x1b\x1b[1mx"), ("x".to_string(), 2));
+        assert_eq!(strip_ansi("\x1b漢字"), "漢字");
+        // Malformed CSI: the sequence stops before the byte that can't be in it
+        assert_eq!(strip_ansi("\x1b[12\u{3042}"), "\u{3042}");
+        assert!(matches!(
+            strip_ansi_counted("no escapes"),
+            (Cow::Borrowed(_), 0)
+        ));
+    }
+
+    #[test]
+    fn edits_remove_exactly_the_sequences() {
+        for text in [
+            "\x1b[1mbold\x1b[0m",
+            "\x1b]0;ビルド\x07日本\x1b[31m語",
+            "前\x1b]2;标题\x1b\\後",
+            "\x1b]0;タイトル",
+        ] {
+            let (stripped, _, edits) = strip_ansi_recorded(text, true);
+            assert_eq!(apply_edits(text, &edits), stripped, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn clean_text_strips_them_when_asked() {
+        let options = crate::CleanOptions {
+            strip_ansi: true,
+            ..crate::CleanOptions::default()
+        };
+        let report = crate::clean_text_with("\x1b[32mok\x1b[0m\r\n", &options);
+        assert_eq!(report.text, "ok\n");
+        assert_eq!(report.ansi_sequences_removed, 2);
+        let kept = crate::clean_text_with("\x1b[32mok", &crate::CleanOptions::default());
+        assert_eq!(kept.text, "\x1b[32mok");
+    }
+}
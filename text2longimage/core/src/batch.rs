@@ -0,0 +1,197 @@
+//! Batch items that carry the caller's id
+//! A frontend that filters or reorders its items before a batch call can't rely on array
+//! indices to match results back, so an item can be {id, text} instead of a bare string and
+//! its result (or the error it caused) repeats the id. Results still come back in input order
+//! whatever the batch does with duplicates or the cache.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_batch_size, count_wrapped_lines_of_output, justify_batch, JustifyOptions, WebtoolsError,
+};
+
+/// A caller's id for a batch item, handed back as it came
+/// Integers stay integers; any other number is kept as a float.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum BatchId {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// One item of a batch: a bare string, or {"id", "text"} with the id optional
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(from = "BatchItemInput")]
+pub struct BatchItem {
+    pub id: Option<BatchId>,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "items must be strings or {id, text} objects")]
+enum BatchItemInput {
+    Text(String),
+    Item(IdentifiedText),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentifiedText {
+    #[serde(default)]
+    id: Option<BatchId>,
+    text: String,
+}
+
+impl From<BatchItemInput> for BatchItem {
+    fn from(input: BatchItemInput) -> Self {
+        match input {
+            BatchItemInput::Text(text) => BatchItem { id: None, text },
+            BatchItemInput::Item(IdentifiedText { id, text }) => BatchItem { id, text },
+        }
+    }
+}
+
+impl AsRef<str> for BatchItem {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// The result for one batch item; id is left out when the item had none
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<BatchId>,
+    pub text: String,
+    pub line_count: u32,
+}
+
+/// check_batch_size for items; a BatchItemTooLarge error carries the item's id
+pub fn check_batch_items(items: &[BatchItem]) -> Result<(), WebtoolsError> {
+    check_batch_size(items).map_err(|error| match error {
+        WebtoolsError::BatchItemTooLarge {
+            index,
+            limit,
+            actual,
+            ..
+        } => WebtoolsError::BatchItemTooLarge {
+            index,
+            limit,
+            actual,
+            id: items[index].id.clone(),
+        },
+        error => error,
+    })
+}
+
+/// Pair each item with its justified text, which must be in input order (as justify_batch
+/// and justify_batch_with_report return them)
+pub fn batch_results(items: &[BatchItem], justified: Vec<String>) -> Vec<BatchResult> {
+    debug_assert_eq!(items.len(), justified.len());
+    items
+        .iter()
+        .zip(justified)
+        .map(|(item, text)| BatchResult {
+            id: item.id.clone(),
+            line_count: count_wrapped_lines_of_output(&text),
+            text,
+        })
+        .collect()
+}
+
+/// justify_batch over items, every result carrying its item's id
+/// Results are in input order, the nth for the nth item, with or without use_cache
+pub fn justify_batch_items(
+    items: &[BatchItem],
+    options: &JustifyOptions,
+    use_cache: bool,
+) -> Vec<BatchResult> {
+    batch_results(items, justify_batch(items, options, use_cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::lock_limits;
+    use crate::DEFAULT_MAX_INPUT_SIZE;
+
+    fn item(id: BatchId, text: &str) -> BatchItem {
+        BatchItem {
+            id: Some(id),
+            text: text.to_string(),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn bare_strings_and_objects_mix() {
+        let json = r#"["plain", {"text": "named", "id": "a"}, {"id": 7, "text": "seven"}, {"id": 1.5, "text": "x"}, {"text": "none"}]"#;
+        let parsed: Vec<BatchItem> = serde_json::from_str(json).unwrap();
+        let ids: Vec<Option<BatchId>> = parsed.into_iter().map(|item| item.id).collect();
+        let expected = [
+            None,
+            Some(BatchId::Text("a".into())),
+            Some(BatchId::Integer(7)),
+            Some(BatchId::Float(1.5)),
+            None,
+        ];
+        assert_eq!(ids, expected);
+        assert!(serde_json::from_str::<Vec<BatchItem>>(r#"[{"text": "x", "ids": 1}]"#).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn integer_ids_come_back_as_integers() {
+        let options = JustifyOptions::default();
+        let results = justify_batch_items(&[item(BatchId::Integer(7), "x")], &options, false);
+        assert_eq!(serde_json::to_string(&results[0].id).unwrap(), "7");
+        let results = justify_batch_items(&[item(BatchId::Float(7.5), "x")], &options, false);
+        assert_eq!(serde_json::to_string(&results[0].id).unwrap(), "7.5");
+    }
+
+    #[test]
+    fn results_keep_input_order_with_duplicates() {
+        let options = JustifyOptions {
+            max_chars_per_line: 4,
+            ..JustifyOptions::default()
+        };
+        let items = [
+            item(BatchId::Text("b".into()), "中文中文"),
+            item(BatchId::Integer(1), "ab cd"),
+            item(BatchId::Text("a".into()), "中文中文"),
+        ];
+        for use_cache in [false, true] {
+            let results = justify_batch_items(&items, &options, use_cache);
+            let ids: Vec<_> = results.iter().map(|result| result.id.clone()).collect();
+            let lines: Vec<_> = results.iter().map(|result| result.line_count).collect();
+            let expected: Vec<_> = items.iter().map(|item| item.id.clone()).collect();
+            assert_eq!(ids, expected);
+            assert_eq!(lines, [2, 2, 2]);
+            assert_eq!(results[1].text, "ab\r\ncd");
+        }
+    }
+
+    #[test]
+    fn an_oversized_item_is_reported_by_id() {
+        let _limits = lock_limits();
+        let oversized = [
+            item(BatchId::Text("ok".into()), "fits"),
+            item(
+                BatchId::Text("big".into()),
+                &"a".repeat(DEFAULT_MAX_INPUT_SIZE + 1),
+            ),
+        ];
+        match check_batch_items(&oversized) {
+            Err(WebtoolsError::BatchItemTooLarge {
+                index: 1,
+                id: Some(BatchId::Text(id)),
+                ..
+            }) => assert_eq!(id, "big"),
+            other => panic!("expected BatchItemTooLarge for id \"big\", got {other:?}"),
+        }
+        assert_eq!(check_batch_items(&oversized[..1]), Ok(()));
+    }
+}
@@ -0,0 +1,228 @@
+//! Batch JSON checked against the size limits while it is parsed
+//! Deserializing a batch into Vec<String> and checking its size afterwards lets a 400 MB
+//! element be copied out, or a million empty strings be allocated, before anything says no.
+//! parse_batch_json reads the array an element at a time and checks each string's length
+//! before copying it: an item over max_input_size() is a BatchItemTooLarge error as soon as it
+//! is read, and once the batch has passed max_batch_items() items or max_batch_size() bytes
+//! the rest is only measured, never copied, so the error reports the whole batch. The top level
+//! must be an array and every element a string; anything else, a nested array included, is an
+//! InvalidJson error naming the item, found before the element is read any further.
+
+use std::cell::Cell;
+use std::fmt;
+
+use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+
+use crate::limits::BatchLimits;
+use crate::WebtoolsError;
+
+/// The strings of a JSON array of strings, under the batch limits as currently set
+/// Passes the same checks check_batch_size makes, in the same order, and fails the same way.
+pub fn parse_batch_json(texts_json: &str) -> Result<Vec<String>, WebtoolsError> {
+    let limits = BatchLimits::current();
+    let reading = Cell::new(None);
+    let limit_error = Cell::new(None);
+    let mut deserializer = serde_json::Deserializer::from_str(texts_json);
+    let parsed = deserializer
+        .deserialize_seq(BatchVisitor {
+            limits,
+            reading: &reading,
+            limit_error: &limit_error,
+        })
+        .and_then(|texts| deserializer.end().map(|()| texts));
+    if let Some(error) = limit_error.take() {
+        return Err(error);
+    }
+    let (texts, count, total) = parsed.map_err(|error| match reading.get() {
+        Some(index) => WebtoolsError::invalid_json(format!("batch item {}: {}", index, error)),
+        None => WebtoolsError::invalid_json(error),
+    })?;
+    // Past a limit the texts stopped being kept, but count and total are the whole batch's
+    limits.check_totals(count, total)?;
+    Ok(texts)
+}
+
+/// Reads the top-level array into the texts kept, the item count and the total bytes
+struct BatchVisitor<'a> {
+    limits: BatchLimits,
+    /// Index of the element being read, None outside the array
+    reading: &'a Cell<Option<usize>>,
+    /// A limit error, which the deserializer's own error stands in for until parsing stops
+    limit_error: &'a Cell<Option<WebtoolsError>>,
+}
+
+impl<'de> Visitor<'de> for BatchVisitor<'_> {
+    type Value = (Vec<String>, usize, usize);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array of strings")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut texts = Vec::new();
+        let (mut count, mut total) = (0usize, 0usize);
+        // Once the batch is over a limit nothing more is kept, only measured
+        let mut keep = true;
+        loop {
+            self.reading.set(Some(count));
+            let element = ElementSeed {
+                keep,
+                item_bytes: self.limits.item_bytes,
+            };
+            let Some((text, len)) = seq.next_element_seed(element)? else {
+                break;
+            };
+            if let Err(error) = self.limits.check_item(count, len) {
+                self.limit_error.set(Some(error));
+                return Err(serde::de::Error::custom("batch item too large"));
+            }
+            count += 1;
+            total = total.saturating_add(len);
+            keep &= count <= self.limits.items && total <= self.limits.total_bytes;
+            match text {
+                Some(text) if keep => texts.push(text),
+                _ => texts = Vec::new(),
+            }
+        }
+        self.reading.set(None);
+        Ok((texts, count, total))
+    }
+}
+
+/// One element: a string, copied when kept and no longer than item_bytes, and its length
+struct ElementSeed {
+    keep: bool,
+    item_bytes: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for ElementSeed {
+    type Value = (Option<String>, usize);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ElementSeed {
+    type Value = (Option<String>, usize);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    // serde_json hands over unescaped strings as a slice of the input and escaped ones in its
+    // scratch buffer, so nothing is copied until the length is known to fit
+    fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Self::Value, E> {
+        let kept = (self.keep && text.len() <= self.item_bytes).then(|| text.to_string());
+        Ok((kept, text.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::limits::lock_limits;
+    use crate::{
+        check_batch_item_count, check_batch_size, DEFAULT_MAX_BATCH_ITEMS, DEFAULT_MAX_BATCH_SIZE,
+        DEFAULT_MAX_INPUT_SIZE,
+    };
+
+    /// parse_batch_json, which must answer a pathological batch without copying it
+    fn quickly(texts_json: &str) -> Result<Vec<String>, WebtoolsError> {
+        let started = Instant::now();
+        let result = parse_batch_json(texts_json);
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "{} bytes parsed slowly",
+            texts_json.len()
+        );
+        result
+    }
+
+    fn invalid(texts_json: &str) -> String {
+        match quickly(texts_json) {
+            Err(WebtoolsError::InvalidJson { message }) => message,
+            other => panic!("{texts_json:.40}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_million_empty_items_are_counted_not_kept() {
+        // Held for the default limits; tests that raise them restore them before letting go
+        let _limits = lock_limits();
+        let empty_items = format!("[{}\"\"]", "\"\",".repeat(999_999));
+        assert_eq!(
+            quickly(&empty_items),
+            Err(WebtoolsError::TooManyBatchItems {
+                limit: DEFAULT_MAX_BATCH_ITEMS,
+                actual: 1_000_000
+            })
+        );
+        let at_limit = format!("[{}\"\"]", "\"\",".repeat(DEFAULT_MAX_BATCH_ITEMS - 1));
+        assert_eq!(
+            quickly(&at_limit).map(|texts| texts.len()),
+            Ok(DEFAULT_MAX_BATCH_ITEMS)
+        );
+        assert_eq!(check_batch_item_count(DEFAULT_MAX_BATCH_ITEMS), Ok(()));
+        assert!(check_batch_item_count(DEFAULT_MAX_BATCH_ITEMS + 1).is_err());
+    }
+
+    #[test]
+    fn a_huge_item_is_measured_before_it_is_copied() {
+        let _limits = lock_limits();
+        let huge = 4 * DEFAULT_MAX_INPUT_SIZE;
+        // Escaped or not; "\\n" is one byte of text
+        for element in ["a".repeat(huge), "\\n".repeat(huge)] {
+            let texts_json = format!("[\"ok\",\"{element}\",[]]");
+            assert_eq!(
+                quickly(&texts_json),
+                Err(WebtoolsError::BatchItemTooLarge {
+                    index: 1,
+                    limit: DEFAULT_MAX_INPUT_SIZE,
+                    actual: huge,
+                    id: None
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn past_the_batch_size_the_rest_is_measured() {
+        let _limits = lock_limits();
+        // Each item under max_input_size, 25 of them over max_batch_size
+        let items = vec!["x".repeat(DEFAULT_MAX_BATCH_SIZE / 20); 25];
+        let too_large = Err(WebtoolsError::BatchTooLarge {
+            limit: DEFAULT_MAX_BATCH_SIZE,
+            actual: 25 * (DEFAULT_MAX_BATCH_SIZE / 20),
+        });
+        assert_eq!(quickly(&serde_json::to_string(&items).unwrap()), too_large);
+        assert_eq!(check_batch_size(&items).map(|()| items.clone()), too_large);
+    }
+
+    #[test]
+    fn anything_but_an_array_of_strings_is_invalid() {
+        let nested = "[".repeat(1_000_000);
+        assert!(invalid(&nested).starts_with("batch item 0:"));
+        for texts_json in [
+            r#"["a", ["b"]]"#,
+            r#"["a", {"id": 1, "text": "b"}]"#,
+            r#"["a", 2]"#,
+        ] {
+            assert!(
+                invalid(texts_json).starts_with("batch item 1:"),
+                "{texts_json}"
+            );
+        }
+        for texts_json in [
+            r#"{"texts": []}"#,
+            r#""a""#,
+            "",
+            r#"["a"] ["b"]"#,
+            r#"["a""#,
+        ] {
+            invalid(texts_json);
+        }
+    }
+}
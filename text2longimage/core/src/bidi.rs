@@ -0,0 +1,217 @@
+//! Right-to-left runs (Hebrew, Arabic) inside left-to-right text
+//! There is no bidi reordering; the wrappers only keep each run on one line, since a run split
+//! across lines reads in scrambled order once the canvas lays each half out right to left.
+//! JustifyOptions does so from algorithm V2 on; V1 breaks a run like any other characters.
+
+use std::ops::Range;
+
+use crate::metrics::{
+    fits_width, place_chars_with, separated_words, Separators, WidthModel, WrapSink,
+};
+
+/// Hebrew, Arabic and their presentation forms
+pub fn is_rtl_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0590..=0x05FF | 0x0600..=0x06FF | 0xFB1D..=0xFB4F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFE
+    )
+}
+
+/// Whether text contains any right-to-left character
+pub fn has_rtl(text: &str) -> bool {
+    text.chars().any(is_rtl_char)
+}
+
+/// Byte ranges of the maximal RTL runs in text
+/// Whitespace between two RTL characters belongs to the run, so a multi-word name is one run.
+pub(crate) fn rtl_runs(text: &str) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    // End of the last RTL character seen, while only whitespace has followed it
+    let mut open_run_end = None;
+
+    for (index, c) in text.char_indices() {
+        if is_rtl_char(c) {
+            let end = index + c.len_utf8();
+            match (open_run_end, runs.last_mut()) {
+                (Some(_), Some(run)) => run.end = end,
+                _ => runs.push(index..end),
+            }
+            open_run_end = Some(end);
+        } else if !c.is_whitespace() {
+            open_run_end = None;
+        }
+    }
+
+    runs
+}
+
+/// Number of RTL runs, as counted for get_text_stats
+pub fn count_rtl_runs(text: &str) -> usize {
+    rtl_runs(text).len()
+}
+
+/// wrap_chars_with that moves an RTL run to the next line whole when the rest of the line
+/// is too short for it; a run wider than a whole line breaks per character
+pub(crate) fn wrap_chars_rtl_with<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    out: &mut S,
+) {
+    let mut current_line_width = 0.0f64;
+    let mut position = 0;
+    for run in rtl_runs(text) {
+        place_chars_with(
+            &text[position..run.start],
+            max_width,
+            model,
+            &mut current_line_width,
+            out,
+        );
+
+        let run_text = &text[run.clone()];
+        let run_width: f64 = run_text.chars().map(|c| model.char_width(c)).sum();
+        if fits_width(current_line_width + run_width, max_width) {
+            out.push_text(run_text);
+            current_line_width += run_width;
+        } else if fits_width(run_width, max_width) {
+            out.line_break();
+            out.push_text(run_text);
+            current_line_width = run_width;
+        } else {
+            place_chars_with(run_text, max_width, model, &mut current_line_width, out);
+        }
+        position = run.end;
+    }
+    place_chars_with(
+        &text[position..],
+        max_width,
+        model,
+        &mut current_line_width,
+        out,
+    );
+}
+
+/// wrap_words_with that keeps consecutive words containing RTL text on one line when
+/// together they fit in one; longer groups are placed word by word
+pub(crate) fn wrap_words_rtl_with<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    keep_separators: bool,
+    out: &mut S,
+) {
+    let separators = Separators::new(keep_separators, model);
+    let mut current_line_width = 0.0f64;
+    let mut line_is_empty = true;
+    // A group joins the line after its first word's separator, and its words after theirs
+    let mut place = |out: &mut S, words: &[(&str, f64, &str)], width: f64| {
+        let (separator, separator_width, _) = words[0];
+        if line_is_empty {
+            current_line_width = width;
+            line_is_empty = false;
+        } else if fits_width(current_line_width + separator_width + width, max_width) {
+            separators.push(separator, out);
+            current_line_width += separator_width + width;
+        } else {
+            out.line_break();
+            current_line_width = width;
+        }
+        for (index, &(separator, _, word)) in words.iter().enumerate() {
+            if index > 0 {
+                separators.push(separator, out);
+            }
+            out.push_text(word);
+        }
+    };
+
+    let words: Vec<(&str, f64, &str)> = separated_words(text)
+        .map(|(separator, word)| (separator, separators.width(separator, model), word))
+        .collect();
+    let mut start = 0;
+    while start < words.len() {
+        let mut end = start + 1;
+        if has_rtl(words[start].2) {
+            while end < words.len() && has_rtl(words[end].2) {
+                end += 1;
+            }
+        }
+
+        let group = &words[start..end];
+        let group_width = group
+            .iter()
+            .map(|&(_, _, word)| model.word_width(word))
+            .sum::<f64>()
+            + group[1..].iter().map(|&(_, width, _)| width).sum::<f64>();
+        if group.len() > 1 && fits_width(group_width, max_width) {
+            place(out, group, group_width);
+        } else {
+            for word in group {
+                place(out, std::slice::from_ref(word), model.word_width(word.2));
+            }
+        }
+        start = end;
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_text_stats, justify_text, JustifyOptions};
+
+    #[test]
+    fn an_arabic_name_across_a_break_in_english() {
+        let text = "We met محمد علي yesterday";
+        // محمد علي is 15 columns: seven letters at two and the space between them
+        assert_eq!(justify_text(text, 16), "We met\r\nمحمد علي\r\nyesterday");
+        assert_eq!(justify_text(text, 24), "We met محمد علي\r\nyesterday");
+        // Narrower than the name, its words go one to a line
+        assert_eq!(justify_text(text, 12), "We met\r\nمحمد\r\nعلي\r\nyesterday");
+    }
+
+    #[test]
+    fn an_arabic_name_across_a_break_in_chinese() {
+        let text = "我们见到了محمد علي先生";
+        assert_eq!(justify_text(text, 18), "我们见到了\r\nمحمد علي先\r\n生");
+        assert_eq!(justify_text(text, 24), "我们见到了\r\nمحمد علي先生");
+        // A run wider than the line breaks per character, as before
+        assert_eq!(justify_text(text, 14), "我们见到了مح\r\nمد علي先\r\n生");
+        assert_eq!(
+            justify_text("名字ابراهيم很长", 14),
+            "名字\r\nابراهيم\r\n很长"
+        );
+    }
+
+    #[test]
+    fn hebrew_words_stay_together() {
+        assert_eq!(justify_text("Hi שלום עולם and", 12), "Hi שלום\r\nעולם and");
+        // שלום עולם is 17 columns, so with "Hi " it fills 20 exactly
+        assert_eq!(justify_text("Hi שלום עולם and", 20), "Hi שלום עולם\r\nand");
+    }
+
+    #[test]
+    fn runs_take_the_whitespace_between_rtl_characters() {
+        let text = "a שלום עולם b مرحبا";
+        let runs: Vec<&str> = rtl_runs(text).into_iter().map(|run| &text[run]).collect();
+        assert_eq!(runs, ["שלום עולם", "مرحبا"]);
+        assert_eq!(count_rtl_runs(text), 2);
+        assert_eq!(count_rtl_runs("no rtl here, 中文"), 0);
+        // Presentation forms count too
+        assert!(is_rtl_char('\u{FB1D}') && is_rtl_char('\u{FEFC}'));
+        assert!(!is_rtl_char('\u{FEFF}'));
+        assert!(get_text_stats(text).contains(r#""rtlRunCount":2"#));
+    }
+
+    #[test]
+    fn algorithm_v1_breaks_runs_as_it_always_did() {
+        let v1 = JustifyOptions {
+            max_chars_per_line: 8,
+            algorithm_version: 1,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(v1.justify("中文שלום"), "中文של\r\nום");
+        assert_eq!(justify_text("中文שלום", 8), "中文\r\nשלום");
+        // Lines without CJK wrap by word either way
+        let text = "hello שלום עולם there";
+        assert_eq!(v1.justify(text), justify_text(text, 8));
+    }
+}
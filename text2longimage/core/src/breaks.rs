@@ -0,0 +1,166 @@
+//! Where a line may break, whatever the width
+//! An editor drawing soft-wrap guides needs the positions the wrappers are allowed to break at
+//! without wrapping anything. break_opportunities lists them under the rules of justify_text,
+//! or of justify_text_cjk_locale when given kinsoku rules; the kinsoku wrapper takes its breaks
+//! from the same list.
+
+#[cfg(feature = "json")]
+use serde::Deserialize;
+
+use crate::bidi::rtl_runs;
+use crate::metrics::number_tokens;
+use crate::KinsokuRules;
+use crate::{is_cjk, is_rtl_char};
+#[cfg(feature = "json")]
+use crate::{CjkLocale, WebtoolsError};
+
+/// Zero-width space: a break opportunity and nothing else
+const ZWSP: char = '\u{200B}';
+
+/// Soft hyphen: the word may break here, with a hyphen drawn at the end of the line
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Char indices the line may break before, ascending
+/// Index k means the line can end after the first k chars; the ends of the line never count.
+/// With rules every char boundary is one, as in justify_text_cjk_locale, except where the
+/// rules forbid it (a closing bracket would start a line, an opening one end it) and inside
+/// number and date tokens. Without rules these are justify_text's: a CJK line breaks between
+/// any two chars outside number tokens; any other line breaks where a word starts after
+/// whitespace, or after a zero-width space or soft hyphen. RTL text stays together: an RTL
+/// run on a CJK line, and consecutive words with RTL in them on any other line.
+/// A wrapper only breaks elsewhere when a token or run between two of these is wider than
+/// the line.
+pub fn break_opportunities(line: &str, rules: Option<&KinsokuRules>) -> Vec<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let breaks = match rules {
+        Some(rules) => char_breaks(&chars, Some(rules)),
+        None if is_cjk(line) => {
+            let mut breaks = char_breaks(&chars, None);
+            for run in rtl_runs(line) {
+                let start = line[..run.start].chars().count();
+                let end = start + line[run].chars().count();
+                breaks[start + 1..end].fill(false);
+            }
+            breaks
+        }
+        None => word_breaks(&chars),
+    };
+    (1..chars.len()).filter(|&k| breaks[k]).collect()
+}
+
+/// Whether a character-wrapped line may break before each of chars.len() + 1 positions: any
+/// boundary rules allow that isn't inside a number token (the ends are never breaks)
+pub(crate) fn char_breaks(chars: &[char], rules: Option<&KinsokuRules>) -> Vec<bool> {
+    let mut breaks: Vec<bool> = (0..=chars.len())
+        .map(|k| {
+            (1..chars.len()).contains(&k)
+                && rules.is_none_or(|rules| rules.allows_break(chars[k - 1], chars[k]))
+        })
+        .collect();
+    for token in number_tokens(chars) {
+        breaks[token.start + 1..token.end].fill(false);
+    }
+    breaks
+}
+
+/// char_breaks' counterpart for word-wrapped lines: where a word starts after whitespace,
+/// unless it and the word before both have RTL in them, and after a zero-width space or soft
+/// hyphen inside a word without RTL
+fn word_breaks(chars: &[char]) -> Vec<bool> {
+    let mut breaks = vec![false; chars.len() + 1];
+    let mut previous_rtl = None;
+    let mut start = 0;
+    while start < chars.len() {
+        if chars[start].is_whitespace() {
+            start += 1;
+            continue;
+        }
+        let len = chars[start..]
+            .iter()
+            .take_while(|c| !c.is_whitespace())
+            .count();
+        let word = &chars[start..start + len];
+        let rtl = word.iter().any(|&c| is_rtl_char(c));
+        if let Some(previous_rtl) = previous_rtl {
+            breaks[start] = !(previous_rtl && rtl);
+        }
+        if !rtl {
+            for k in start + 1..start + len {
+                breaks[k] = matches!(chars[k - 1], ZWSP | SOFT_HYPHEN);
+            }
+        }
+        previous_rtl = Some(rtl);
+        start += len;
+    }
+    breaks
+}
+
+#[cfg(feature = "json")]
+/// Options of break_opportunities_json and edge_char_report; every field is optional
+/// JSON keys are camelCase: {"locale", "extraNoStart", "extraNoEnd"}
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub(crate) struct BreakOptions {
+    /// Kinsoku rules of this locale ("ja", "zh-Hans", "zh-Hant", "ko" or "auto"), as
+    /// justify_text_cjk_locale applies them; none for justify_text's rules
+    locale: Option<String>,
+    extra_no_start: String,
+    extra_no_end: String,
+}
+
+#[cfg(feature = "json")]
+impl BreakOptions {
+    /// Parse options_json; "" is every field's default
+    pub(crate) fn from_json(options_json: &str) -> Result<Self, WebtoolsError> {
+        if options_json.trim().is_empty() {
+            return Ok(BreakOptions::default());
+        }
+        serde_json::from_str(options_json).map_err(WebtoolsError::invalid_json)
+    }
+
+    /// The kinsoku rules asked for, "auto" resolved for text; None without a locale
+    pub(crate) fn rules(&self, text: &str) -> Result<Option<KinsokuRules>, WebtoolsError> {
+        match &self.locale {
+            Some(locale) => Ok(Some(KinsokuRules::new(
+                CjkLocale::parse(locale)?.resolve(text),
+                &self.extra_no_start,
+                &self.extra_no_end,
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+/// break_opportunities as a JSON array of char indices
+/// options_json: {locale, extraNoStart, extraNoEnd}, all optional; "" for justify_text's rules.
+/// The extra characters only count with a locale.
+pub fn break_opportunities_json(line: &str, options_json: &str) -> Result<String, WebtoolsError> {
+    let rules = BreakOptions::from_json(options_json)?.rules(line)?;
+    let breaks = break_opportunities(line, rules.as_ref());
+    Ok(serde_json::to_string(&breaks).unwrap_or_else(|_| "[]".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CjkLocale;
+
+    #[test]
+    fn opportunities_follow_words_cjk_and_kinsoku() {
+        let rules = KinsokuRules::new(CjkLocale::Ja, "", "");
+        for (line, with_rules, expected) in [
+            ("ab cd  ef", false, vec![3, 7]),
+            ("漢字。漢", false, vec![1, 2, 3]),
+            ("漢字。漢", true, vec![1, 3]),
+            ("「漢字」です", true, vec![2, 4, 5]),
+            ("co\u{00AD}op a\u{200B}b", false, vec![3, 6, 8]),
+            ("価格1,234円", false, vec![1, 2, 7]),
+            ("go مرحبا مرحبا now", false, vec![3, 15]),
+            ("  lead", false, vec![]),
+        ] {
+            let rules = with_rules.then_some(&rules);
+            assert_eq!(break_opportunities(line, rules), expected, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! How much of a length-limited caption is left, in the unit the platform counts in
+//! Platforms cap captions in different units: code points, UTF-16 code units (JavaScript's
+//! length), display columns, or Twitter's weighted count, where CJK and emoji count 2 and a
+//! URL counts 23 whatever its length. Text is measured a grapheme at a time (a character with
+//! its combining marks and selectors, an emoji sequence, a CRLF, and in the weighted count a
+//! whole URL), so truncate_to_budget never cuts through one.
+
+use serde::Deserialize;
+
+use crate::capabilities::{expected_values, find_value};
+use crate::emoji::{emoji_len, VS15, VS16, ZWJ};
+use crate::table::{in_ranges, COMBINING_RANGES};
+use crate::{width_of, OptionValue, WebtoolsError};
+
+/// Characters Twitter counts as light (1 each), as code point ranges: Latin, Greek, Cyrillic,
+/// Arabic, Hebrew, the Indic scripts and the general punctuation spaces and quotes
+const LIGHT_RANGES: &[(u32, u32)] = &[
+    (0x0000, 0x10FF),
+    (0x2000, 0x200D),
+    (0x2010, 0x201F),
+    (0x2032, 0x2037),
+];
+
+/// What each kind of grapheme costs in CountingMode::WeightedTwitter
+/// JSON keys are camelCase and all optional: {"light", "heavy", "emoji", "url"}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct TwitterWeights {
+    /// Each character in LIGHT_RANGES
+    pub light: u32,
+    /// Each other character, CJK, kana and Hangul syllables among them
+    pub heavy: u32,
+    /// Each emoji, sequences and flags included
+    pub emoji: u32,
+    /// Each "http://" or "https://" URL, as shortened links all have the same length
+    pub url: u32,
+}
+
+impl TwitterWeights {
+    /// Twitter's own weights
+    pub const DEFAULT: TwitterWeights = TwitterWeights {
+        light: 1,
+        heavy: 2,
+        emoji: 2,
+        url: 23,
+    };
+}
+
+impl Default for TwitterWeights {
+    fn default() -> Self {
+        TwitterWeights::DEFAULT
+    }
+}
+
+/// The unit a length limit counts in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountingMode {
+    /// Code points, as Rust's chars() or JavaScript's [...text] count them
+    Chars,
+    /// UTF-16 code units, as JavaScript's length counts them
+    Utf16,
+    /// Columns, as the wrappers measure lines (width_of)
+    DisplayWidth,
+    /// Twitter-style weights per character, emoji and URL
+    WeightedTwitter(TwitterWeights),
+}
+
+impl CountingMode {
+    /// Parse "chars" / "utf16" / "displayWidth" / "weightedTwitter" in any case; the weighted
+    /// count gets TwitterWeights::DEFAULT
+    pub fn parse(mode: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| mode.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown counting mode: {} (expected {})",
+                mode,
+                expected_values::<CountingMode>()
+            ))
+        })
+    }
+
+    #[cfg(feature = "json")]
+    /// parse, with the weighted count's weights as JSON {light, heavy, emoji, url}, all
+    /// optional; "" keeps the defaults, and only "weightedTwitter" takes weights
+    pub fn parse_with_weights(mode: &str, weights_json: &str) -> Result<Self, WebtoolsError> {
+        let mode = CountingMode::parse(mode)?;
+        if weights_json.trim().is_empty() {
+            return Ok(mode);
+        }
+        match mode {
+            CountingMode::WeightedTwitter(_) => serde_json::from_str(weights_json)
+                .map(CountingMode::WeightedTwitter)
+                .map_err(WebtoolsError::invalid_json),
+            _ => Err(WebtoolsError::invalid_argument(format!(
+                "Weights only apply to counting mode weightedTwitter, not {}",
+                mode.name()
+            ))),
+        }
+    }
+
+    /// What one grapheme costs
+    fn cost(self, grapheme: &str, is_url: bool) -> u64 {
+        match self {
+            CountingMode::Chars => grapheme.chars().count() as u64,
+            CountingMode::Utf16 => grapheme.encode_utf16().count() as u64,
+            CountingMode::DisplayWidth => grapheme.chars().map(|c| width_of(c) as u64).sum(),
+            CountingMode::WeightedTwitter(weights) if is_url => weights.url as u64,
+            CountingMode::WeightedTwitter(weights) => {
+                let chars: Vec<char> = grapheme.chars().collect();
+                if emoji_len(&chars, 0).is_some() {
+                    return weights.emoji as u64;
+                }
+                chars
+                    .iter()
+                    .map(|&c| {
+                        if in_ranges(LIGHT_RANGES, c) {
+                            weights.light as u64
+                        } else {
+                            weights.heavy as u64
+                        }
+                    })
+                    .sum()
+            }
+        }
+    }
+}
+
+impl OptionValue for CountingMode {
+    const KIND: &'static str = "countingMode";
+    const USED_BY: &'static [&'static str] = &["remaining_budget", "truncate_to_budget"];
+    const ALL: &'static [Self] = &[
+        CountingMode::Chars,
+        CountingMode::Utf16,
+        CountingMode::DisplayWidth,
+        CountingMode::WeightedTwitter(TwitterWeights::DEFAULT),
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CountingMode::Chars => "chars",
+            CountingMode::Utf16 => "utf16",
+            CountingMode::DisplayWidth => "displayWidth",
+            CountingMode::WeightedTwitter(_) => "weightedTwitter",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CountingMode::Chars => "Code points",
+            CountingMode::Utf16 => "UTF-16 code units, as JavaScript's length",
+            CountingMode::DisplayWidth => "Display columns, as the wrappers measure lines",
+            CountingMode::WeightedTwitter(_) => "Twitter's count: CJK and emoji 2, URLs 23",
+        }
+    }
+}
+
+/// Whether c attaches to the character before it rather than starting a grapheme
+pub(crate) fn extends(c: char) -> bool {
+    c == VS15 || c == VS16 || c == ZWJ || in_ranges(COMBINING_RANGES, c)
+}
+
+/// Char length of the "http://" or "https://" URL starting at chars[start], if one does
+/// The URL runs over printable ASCII, less the punctuation that ends a sentence after it.
+fn url_len(chars: &[char], start: usize) -> Option<usize> {
+    if !chars[start].eq_ignore_ascii_case(&'h')
+        || start > 0 && chars[start - 1].is_ascii_alphanumeric()
+    {
+        return None;
+    }
+    let run = chars[start..]
+        .iter()
+        .take_while(|c| c.is_ascii_graphic())
+        .count();
+    let token: String = chars[start..start + run].iter().collect();
+    let scheme = ["http://", "https://"].into_iter().find(|scheme| {
+        token
+            .get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })?;
+    let len = token
+        .trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"'])
+        .len();
+    (len > scheme.len()).then_some(len)
+}
+
+/// The graphemes of text with what each costs under mode, as (byte end, cost)
+pub(crate) fn graphemes(text: &str, mode: CountingMode) -> Vec<(usize, u64)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let plain: Vec<char> = chars.iter().map(|&(_, c)| c).collect();
+    let byte_at = |index: usize| chars.get(index).map_or(text.len(), |&(offset, _)| offset);
+    let weighted = matches!(mode, CountingMode::WeightedTwitter(_));
+
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < plain.len() {
+        let url = weighted.then(|| url_len(&plain, i)).flatten();
+        let mut end = match (url, plain[i]) {
+            (Some(len), _) => i + len,
+            (None, '\r') if plain.get(i + 1) == Some(&'\n') => i + 2,
+            (None, _) => i + emoji_len(&plain, i).unwrap_or(1),
+        };
+        if url.is_none() {
+            end += plain[end..].iter().take_while(|&&c| extends(c)).count();
+        }
+        let grapheme = &text[byte_at(i)..byte_at(end)];
+        result.push((byte_at(end), mode.cost(grapheme, url.is_some())));
+        i = end;
+    }
+    result
+}
+
+fn count(text: &str, mode: CountingMode) -> u64 {
+    graphemes(text, mode).iter().map(|&(_, cost)| cost).sum()
+}
+
+/// How much of limit text leaves under mode; negative by as much as text is over
+/// Saturates at i32::MIN for text that is over by more than that.
+pub fn remaining_budget(text: &str, limit: u32, mode: CountingMode) -> i32 {
+    let remaining = i64::from(limit) - count(text, mode).min(i64::MAX as u64) as i64;
+    remaining.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// text cut to fit limit under mode, with ellipsis after it when it had to be cut
+/// The cut is at a grapheme boundary (a whole URL goes in the weighted count), whitespace
+/// before the ellipsis is dropped, and the ellipsis is counted in the same mode, so
+/// remaining_budget of the result is never negative. When the ellipsis alone is over the
+/// limit, text is cut without one.
+pub fn truncate_to_budget(text: &str, limit: u32, mode: CountingMode, ellipsis: &str) -> String {
+    let limit = u64::from(limit);
+    let graphemes = graphemes(text, mode);
+    if graphemes.iter().map(|&(_, cost)| cost).sum::<u64>() <= limit {
+        return text.to_string();
+    }
+
+    let ellipsis_cost = count(ellipsis, mode);
+    let ellipsis = if ellipsis_cost <= limit { ellipsis } else { "" };
+    let budget = limit - ellipsis_cost.min(limit);
+    let mut used = 0;
+    let mut keep = graphemes
+        .iter()
+        .take_while(|&&(_, cost)| {
+            used += cost;
+            used <= budget
+        })
+        .count();
+
+    // A grapheme at the end of the kept text can merge with the ellipsis's first one (a
+    // combining mark in the ellipsis, a URL running into it), so check the whole result
+    loop {
+        let end = keep.checked_sub(1).map_or(0, |last| graphemes[last].0);
+        let result = text[..end].trim_end().to_string() + ellipsis;
+        if keep == 0 || count(&result, mode) <= limit {
+            return result;
+        }
+        keep -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A caption mixing CJK, a URL, an emoji with a skin tone and ASCII, as each counting mode
+    /// counts it: 2 + 1 + 14 + 1 + 2 + 1 code points, the emoji's two surrogate pairs, its two
+    /// wide code points, and Twitter's 4 + 1 + 23 + 1 + 2 + 1
+    const CAPTION: &str = "中文 https://t.co/x 👍🏽!";
+    const WEIGHTED: CountingMode = CountingMode::WeightedTwitter(TwitterWeights::DEFAULT);
+
+    #[test]
+    fn each_mode_counts_the_caption() {
+        for (mode, count) in [
+            (CountingMode::Chars, 21),
+            (CountingMode::Utf16, 23),
+            (CountingMode::DisplayWidth, 25),
+            (WEIGHTED, 32),
+        ] {
+            assert_eq!(remaining_budget(CAPTION, 30, mode), 30 - count, "{mode:?}");
+        }
+        let heavy_cjk = CountingMode::WeightedTwitter(TwitterWeights {
+            heavy: 3,
+            ..TwitterWeights::DEFAULT
+        });
+        assert_eq!(remaining_budget(CAPTION, 32, heavy_cjk), -2);
+    }
+
+    #[test]
+    fn truncation_keeps_urls_whole() {
+        for (mode, limit, ellipsis, expected) in [
+            (CountingMode::Chars, 20, "…", "中文 https://t.co/x…"),
+            (CountingMode::Utf16, 21, "...", "中文 https://t.co/x..."),
+            (CountingMode::DisplayWidth, 4, "…", "中…"),
+            // The URL goes whole or not at all
+            (WEIGHTED, 28, "…", "中文…"),
+            (WEIGHTED, 1, "…", ""),
+            (WEIGHTED, 32, "…", CAPTION),
+        ] {
+            assert_eq!(
+                truncate_to_budget(CAPTION, limit, mode, ellipsis),
+                expected,
+                "{mode:?} to {limit}"
+            );
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn only_the_weighted_count_takes_weights() {
+        let heavy_cjk = CountingMode::WeightedTwitter(TwitterWeights {
+            heavy: 3,
+            ..TwitterWeights::DEFAULT
+        });
+        assert_eq!(
+            CountingMode::parse_with_weights("weightedTwitter", r#"{"heavy": 3}"#),
+            Ok(heavy_cjk)
+        );
+        assert!(matches!(
+            CountingMode::parse_with_weights("chars", r#"{"heavy": 3}"#),
+            Err(WebtoolsError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use std::borrow::Cow;
+
+#[cfg(feature = "json")]
+use crate::batch_justify_text;
+use crate::{get_text_stats, justify_text, WebtoolsError};
+
+/// Decode UTF-8 input, either strictly or replacing invalid sequences with U+FFFD
+pub fn decode_utf8(bytes: &[u8], lossy: bool) -> Result<Cow<'_, str>, WebtoolsError> {
+    if lossy {
+        return Ok(String::from_utf8_lossy(bytes));
+    }
+
+    std::str::from_utf8(bytes)
+        .map(Cow::Borrowed)
+        .map_err(|e| WebtoolsError::InvalidUtf8 {
+            offset: e.valid_up_to(),
+        })
+}
+
+/// justify_text over UTF-8 bytes (e.g. file contents), returning UTF-8 bytes
+/// With lossy = false invalid input is an error naming the byte offset
+pub fn justify_text_bytes(
+    bytes: &[u8],
+    max_chars_per_line: u32,
+    lossy: bool,
+) -> Result<Vec<u8>, WebtoolsError> {
+    let text = decode_utf8(bytes, lossy)?;
+    Ok(justify_text(&text, max_chars_per_line).into_bytes())
+}
+
+/// get_text_stats over UTF-8 bytes
+pub fn get_text_stats_bytes(bytes: &[u8], lossy: bool) -> Result<String, WebtoolsError> {
+    let text = decode_utf8(bytes, lossy)?;
+    Ok(get_text_stats(&text))
+}
+
+#[cfg(feature = "json")]
+/// batch_justify_text over a UTF-8 encoded JSON array, returning the JSON result as bytes
+pub fn batch_justify_text_bytes(
+    texts_json: &[u8],
+    max_chars_per_line: u32,
+    use_cache: bool,
+) -> Result<Vec<u8>, WebtoolsError> {
+    let texts_json = decode_utf8(texts_json, false)?;
+    batch_justify_text(&texts_json, max_chars_per_line, use_cache).map(String::into_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_wrap_as_the_string_does() {
+        let text = "漢字かな交じり and some English words";
+        assert_eq!(
+            justify_text_bytes(text.as_bytes(), 10, false).unwrap(),
+            justify_text(text, 10).into_bytes()
+        );
+        assert_eq!(
+            get_text_stats_bytes(text.as_bytes(), false).unwrap(),
+            get_text_stats(text)
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_names_its_offset() {
+        // "ab", then a lone continuation byte, then 漢 cut after its first byte
+        let bytes = b"ab\x80cd\xE6";
+        assert_eq!(
+            justify_text_bytes(bytes, 10, false),
+            Err(WebtoolsError::InvalidUtf8 { offset: 2 })
+        );
+        assert_eq!(
+            decode_utf8(&bytes[3..], false),
+            Err(WebtoolsError::InvalidUtf8 { offset: 2 })
+        );
+        assert_eq!(
+            justify_text_bytes(bytes, 10, true).unwrap(),
+            "ab\u{FFFD}cd\u{FFFD}".as_bytes()
+        );
+    }
+}
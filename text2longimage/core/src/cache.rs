@@ -0,0 +1,234 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::JustifyOptions;
+
+/// Entries kept by the batch cache unless set_cache_capacity says otherwise
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// Least-recently-used map from (options, text) to the justified output
+/// Keyed by the whole JustifyOptions so every option that affects output is part of the key
+struct JustifyCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<JustifyOptions, HashMap<Rc<str>, (String, u64)>>,
+    // Last-use tick -> key, oldest first
+    order: BTreeMap<u64, (JustifyOptions, Rc<str>)>,
+}
+
+impl JustifyCache {
+    fn new(capacity: usize) -> Self {
+        JustifyCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, options: &JustifyOptions, text: &str) -> Option<String> {
+        let (value, last_used) = self.entries.get_mut(options)?.get_mut(text)?;
+        let key = self.order.remove(last_used)?;
+
+        self.tick += 1;
+        *last_used = self.tick;
+        self.order.insert(self.tick, key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, options: &JustifyOptions, text: &str, value: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.order.len() >= self.capacity {
+            self.evict_oldest();
+        }
+
+        self.tick += 1;
+        let text: Rc<str> = Rc::from(text);
+        self.entries
+            .entry(options.clone())
+            .or_default()
+            .insert(Rc::clone(&text), (value, self.tick));
+        self.order.insert(self.tick, (options.clone(), text));
+    }
+
+    fn evict_oldest(&mut self) {
+        let Some((_, (options, text))) = self.order.pop_first() else {
+            return;
+        };
+        if let Some(texts) = self.entries.get_mut(&options) {
+            texts.remove(&text);
+            if texts.is_empty() {
+                self.entries.remove(&options);
+            }
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.order.len() > capacity {
+            self.evict_oldest();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+thread_local! {
+    static CACHE: RefCell<JustifyCache> = RefCell::new(JustifyCache::new(DEFAULT_CACHE_CAPACITY));
+}
+
+/// Drop every cached batch result
+pub fn clear_cache() {
+    CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Change how many results the batch cache keeps; shrinking evicts the oldest first
+/// Zero disables caching across calls (duplicates within one batch are still wrapped once)
+pub fn set_cache_capacity(entries: usize) {
+    CACHE.with(|cache| cache.borrow_mut().set_capacity(entries));
+}
+
+/// Current batch cache capacity in entries
+pub fn cache_capacity() -> usize {
+    CACHE.with(|cache| cache.borrow().capacity)
+}
+
+/// Justify every text with the same options, in input order
+/// With use_cache each distinct text is wrapped once and results are kept in the LRU cache
+/// across calls; without it every item is wrapped independently
+pub fn justify_batch<S: AsRef<str>>(
+    texts: &[S],
+    options: &JustifyOptions,
+    use_cache: bool,
+) -> Vec<String> {
+    justify_batch_each(texts, options, use_cache, || {})
+}
+
+/// justify_batch calling after_item once each item's result is ready
+pub(crate) fn justify_batch_each<S: AsRef<str>>(
+    texts: &[S],
+    options: &JustifyOptions,
+    use_cache: bool,
+    mut after_item: impl FnMut(),
+) -> Vec<String> {
+    if !use_cache {
+        return texts
+            .iter()
+            .map(|text| {
+                let value = options.justify(text.as_ref());
+                after_item();
+                value
+            })
+            .collect();
+    }
+
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        let mut results: Vec<String> = Vec::with_capacity(texts.len());
+
+        for text in texts {
+            let text = text.as_ref();
+            if let Some(&index) = seen.get(text) {
+                let value = results[index].clone();
+                results.push(value);
+                after_item();
+                continue;
+            }
+
+            let value = cache.get(options, text).unwrap_or_else(|| {
+                let value = options.justify(text);
+                cache.insert(options, text, value.clone());
+                value
+            });
+            seen.insert(text, results.len());
+            results.push(value);
+            after_item();
+        }
+
+        results
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(max_chars_per_line: u32) -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line,
+            ..JustifyOptions::default()
+        }
+    }
+
+    fn cached(options: &JustifyOptions, text: &str) -> bool {
+        CACHE.with(|cache| cache.borrow_mut().get(options, text).is_some())
+    }
+
+    #[test]
+    fn least_recently_used_entry_goes_first() {
+        let options = width(10);
+        let mut cache = JustifyCache::new(2);
+        cache.insert(&options, "a", "A".to_string());
+        cache.insert(&options, "b", "B".to_string());
+        // Reading a makes b the oldest
+        assert_eq!(cache.get(&options, "a").as_deref(), Some("A"));
+        cache.insert(&options, "c", "C".to_string());
+        assert_eq!(cache.get(&options, "b"), None);
+        assert_eq!(cache.get(&options, "a").as_deref(), Some("A"));
+        assert_eq!(cache.get(&options, "c").as_deref(), Some("C"));
+        cache.set_capacity(1);
+        assert_eq!(cache.get(&options, "a"), None);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.order.len(), 1);
+    }
+
+    #[test]
+    fn cached_batches_match_uncached_ones() {
+        let header = "公司名称 Company Ltd. 版权所有";
+        let texts = [
+            header,
+            "第一段内容 first item",
+            header,
+            "",
+            header,
+            "second item",
+        ];
+        for options in [width(8), width(20)] {
+            let expected = justify_batch(&texts, &options, false);
+            assert_eq!(justify_batch(&texts, &options, true), expected);
+            // Again, now from the cache
+            assert_eq!(justify_batch(&texts, &options, true), expected);
+        }
+        // The options are part of the key, so the two widths were kept apart
+        assert_ne!(
+            justify_batch(&[header], &width(8), true),
+            justify_batch(&[header], &width(20), true)
+        );
+    }
+
+    #[test]
+    fn capacity_and_clearing() {
+        assert_eq!(cache_capacity(), DEFAULT_CACHE_CAPACITY);
+        let options = width(10);
+        justify_batch(&["one", "two"], &options, true);
+        assert!(cached(&options, "one") && cached(&options, "two"));
+        assert!(!cached(&width(11), "one"));
+        clear_cache();
+        assert!(!cached(&options, "one"));
+
+        // Nothing is kept across calls, but the batch is still right
+        set_cache_capacity(0);
+        assert_eq!(
+            justify_batch(&["one", "one"], &options, true),
+            justify_batch(&["one", "one"], &options, false)
+        );
+        assert!(!cached(&options, "one"));
+    }
+}
@@ -0,0 +1,539 @@
+//! Warnings for images that come close to what the browser can draw
+//! Canvases past a browser's size limits fail to render without an error, so the estimate and
+//! layout APIs compare the image with CanvasLimits and warn from LIMIT_WARNING_RATIO of a
+//! limit on, each warning with a width or a split that brings the image back under it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::narrowest_width_for_lines;
+use crate::paginate::split_blocks;
+use crate::{
+    fit_columns, image_size_of_lines, justify_text, layout_lines, wrapped_lines, Align,
+    ColumnRounding, ImageSize, LayoutLine, ScriptLineHeights, WebtoolsError, MAX_CANVAS_HEIGHT_PX,
+    MIN_CHARS_PER_LINE, PARAGRAPH_BREAK_TOLERANCE,
+};
+
+/// Widest canvas Chrome and Firefox draw
+pub const MAX_CANVAS_WIDTH_PX: f64 = 32_767.0;
+
+/// Largest canvas area Chrome and Firefox draw (16,384²); iOS Safari stops at 16,777,216
+pub const MAX_CANVAS_PIXELS: f64 = 268_435_456.0;
+
+/// Share of a limit from which an image gets a warning
+pub const LIMIT_WARNING_RATIO: f64 = 0.8;
+
+/// The canvas sizes a platform can draw; missing fields keep the Chrome and Firefox limits
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct CanvasLimits {
+    pub max_height_px: f64,
+    pub max_width_px: f64,
+    /// Width × height
+    pub max_total_pixels: f64,
+}
+
+impl Default for CanvasLimits {
+    fn default() -> Self {
+        CanvasLimits {
+            max_height_px: MAX_CANVAS_HEIGHT_PX,
+            max_width_px: MAX_CANVAS_WIDTH_PX,
+            max_total_pixels: MAX_CANVAS_PIXELS,
+        }
+    }
+}
+
+impl CanvasLimits {
+    /// Parse limits as JSON {maxHeightPx, maxWidthPx, maxTotalPixels}; an empty string is the
+    /// defaults, and every limit has to be a positive number
+    pub fn from_json(limits_json: &str) -> Result<CanvasLimits, WebtoolsError> {
+        if limits_json.trim().is_empty() {
+            return Ok(CanvasLimits::default());
+        }
+        let limits: CanvasLimits =
+            serde_json::from_str(limits_json).map_err(WebtoolsError::invalid_json)?;
+        for (name, limit) in [
+            ("maxHeightPx", limits.max_height_px),
+            ("maxWidthPx", limits.max_width_px),
+            ("maxTotalPixels", limits.max_total_pixels),
+        ] {
+            if !(limit.is_finite() && limit > 0.0) {
+                return Err(WebtoolsError::invalid_argument(format!(
+                    "{} must be a positive number, got {}",
+                    name, limit
+                )));
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Whether an image stays under LIMIT_WARNING_RATIO of every limit
+    fn clears(&self, width_px: f64, height_px: f64) -> bool {
+        height_px < self.max_height_px * LIMIT_WARNING_RATIO
+            && width_px < self.max_width_px * LIMIT_WARNING_RATIO
+            && width_px * height_px < self.max_total_pixels * LIMIT_WARNING_RATIO
+    }
+}
+
+/// An image dimension at LIMIT_WARNING_RATIO of its limit or more
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasWarning {
+    /// "CANVAS_HEIGHT", "CANVAS_WIDTH" or "CANVAS_PIXELS"
+    pub code: &'static str,
+    pub message: String,
+    /// The image's height, width or area, and the limit it comes close to
+    pub actual: f64,
+    pub limit: f64,
+    pub suggestion: CanvasSuggestion,
+}
+
+/// Ways back under LIMIT_WARNING_RATIO of every limit; a field is left out when that way
+/// doesn't get there
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasSuggestion {
+    /// The max_chars_per_line closest to the current one whose image clears every limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars_per_line: Option<u32>,
+    /// How many images paginate_for_images makes at split_height_px
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_count: Option<u32>,
+    /// The max_height_px to split at: the text height that, with the padding, keeps each
+    /// image under every limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split_height_px: Option<f64>,
+}
+
+/// How the text is drawn, for measuring it at other widths
+struct Drawing<'a> {
+    text: &'a str,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    heights: ScriptLineHeights,
+}
+
+impl<'a> Drawing<'a> {
+    fn new(text: &'a str, font_size_px: f64, line_height_factor: f64, padding_px: f64) -> Self {
+        Drawing {
+            text,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            heights: ScriptLineHeights::current(),
+        }
+    }
+}
+
+impl Drawing<'_> {
+    /// The image size, each line as tall as layout_text makes it
+    fn size(&self, max_chars_per_line: u32) -> ImageSize {
+        image_size_of_lines(
+            &wrapped_lines(self.text, max_chars_per_line),
+            max_chars_per_line,
+            self.font_size_px,
+            self.line_height_factor,
+            self.padding_px,
+            self.heights,
+        )
+    }
+
+    fn column_px(&self) -> f64 {
+        self.font_size_px / 2.0
+    }
+
+    /// Height of the tallest line the text can have, so a line count under a height holds
+    /// whichever lines they are
+    fn line_height_px(&self) -> f64 {
+        self.font_size_px * self.line_height_factor * self.heights.tallest_in(self.text)
+    }
+
+    /// Most lines whose image, padding included, stays under height_px
+    fn lines_under(&self, height_px: f64) -> Option<u32> {
+        let lines = ((height_px - 2.0 * self.padding_px) / self.line_height_px()).ceil() - 1.0;
+        (lines.is_finite() && lines >= 0.0).then(|| lines.min(u32::MAX as f64) as u32)
+    }
+
+    /// The width closest to max_chars_per_line that clears every limit
+    /// Widths the width limit allows and whose height clears form one range, because height
+    /// only shrinks as lines widen; the area isn't monotonic, so the pick is checked.
+    fn width_suggestion(&self, max_chars_per_line: u32, limits: &CanvasLimits) -> Option<u32> {
+        let width_px = limits.max_width_px * LIMIT_WARNING_RATIO;
+        let widest = ((width_px - 2.0 * self.padding_px) / self.column_px()).ceil() - 1.0;
+        if !widest.is_finite() || widest < MIN_CHARS_PER_LINE as f64 {
+            return None;
+        }
+        let widest = widest.min(u32::MAX as f64) as u32;
+        let lines = self.lines_under(limits.max_height_px * LIMIT_WARNING_RATIO)?;
+        let narrowest = narrowest_width_for_lines(self.text, lines, MIN_CHARS_PER_LINE, widest)?;
+
+        let width = max_chars_per_line.clamp(narrowest, widest);
+        let size = self.size(width);
+        (width != max_chars_per_line && limits.clears(size.width_px, size.height_px))
+            .then_some(width)
+    }
+
+    /// Images to split into at max_chars_per_line, and the text height each of them gets
+    fn split_suggestion(
+        &self,
+        max_chars_per_line: u32,
+        size: &ImageSize,
+        limits: &CanvasLimits,
+    ) -> Option<(u32, f64)> {
+        if size.width_px >= limits.max_width_px * LIMIT_WARNING_RATIO {
+            return None;
+        }
+        let image_height_px = (limits.max_height_px * LIMIT_WARNING_RATIO)
+            .min(limits.max_total_pixels * LIMIT_WARNING_RATIO / size.width_px);
+        let capacity = self
+            .lines_under(image_height_px)
+            .filter(|&lines| lines > 0)?;
+
+        let lines = wrapped_lines(self.text, max_chars_per_line);
+        let blocks = split_blocks(&lines, capacity as usize, PARAGRAPH_BREAK_TOLERANCE, false);
+        let images = u32::try_from(blocks.len()).unwrap_or(u32::MAX);
+        Some((images, capacity as f64 * self.line_height_px()))
+    }
+}
+
+/// Warnings for the image of text wrapped at max_chars_per_line, one per limit it comes
+/// within LIMIT_WARNING_RATIO of, in the order height, width, pixels
+/// A width warning only suggests a width; the others also suggest a split, at the width as it
+/// is, with paginate_for_images(text, max_chars_per_line, font_size_px, line_height_factor,
+/// splitHeightPx, false).
+pub fn canvas_warnings(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    limits: &CanvasLimits,
+) -> Vec<CanvasWarning> {
+    let drawing = Drawing::new(text, font_size_px, line_height_factor, padding_px);
+    drawing.warnings(
+        max_chars_per_line,
+        &drawing.size(max_chars_per_line),
+        limits,
+    )
+}
+
+impl Drawing<'_> {
+    /// canvas_warnings for the image size already computed at max_chars_per_line
+    fn warnings(
+        &self,
+        max_chars_per_line: u32,
+        size: &ImageSize,
+        limits: &CanvasLimits,
+    ) -> Vec<CanvasWarning> {
+        let pixels = size.width_px * size.height_px;
+        let checks = [
+            (
+                "CANVAS_HEIGHT",
+                "height",
+                size.height_px,
+                limits.max_height_px,
+            ),
+            ("CANVAS_WIDTH", "width", size.width_px, limits.max_width_px),
+            (
+                "CANVAS_PIXELS",
+                "pixel count",
+                pixels,
+                limits.max_total_pixels,
+            ),
+        ];
+        let near =
+            |&(.., actual, limit): &(&str, &str, f64, f64)| actual >= limit * LIMIT_WARNING_RATIO;
+        if !checks.iter().any(near) {
+            return Vec::new();
+        }
+
+        let width = self.width_suggestion(max_chars_per_line, limits);
+        let split = self.split_suggestion(max_chars_per_line, size, limits);
+        checks
+            .into_iter()
+            .filter(near)
+            .map(|(code, what, actual, limit)| {
+                let split = split.filter(|_| code != "CANVAS_WIDTH");
+                CanvasWarning {
+                    code,
+                    message: format!(
+                        "Image {} is {:.0}% of the {} limit",
+                        what,
+                        actual / limit * 100.0,
+                        limit
+                    ),
+                    actual,
+                    limit,
+                    suggestion: CanvasSuggestion {
+                        max_chars_per_line: width,
+                        image_count: split.map(|(images, _)| images),
+                        split_height_px: split.map(|(_, height_px)| height_px),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SizeWithWarnings {
+    #[serde(flatten)]
+    size: ImageSize,
+    warnings: Vec<CanvasWarning>,
+}
+
+/// estimate_image_size compared with limits
+/// Returns JSON {widthPx, heightPx, lineCount, exceedsSafeCanvasHeight, exceedsMaxCanvasHeight,
+/// warnings: [{code, message, actual, limit, suggestion: {maxCharsPerLine?, imageCount?,
+/// splitHeightPx?}}]}
+pub fn estimate_image_size_with_limits(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    limits: &CanvasLimits,
+) -> String {
+    let drawing = Drawing::new(text, font_size_px, line_height_factor, padding_px);
+    let size = drawing.size(max_chars_per_line);
+    let warnings = drawing.warnings(max_chars_per_line, &size, limits);
+    let report = SizeWithWarnings { size, warnings };
+    serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SizeForWidth {
+    #[serde(flatten)]
+    size: ImageSize,
+    /// The column count the pixel width was rounded to
+    columns: u32,
+    /// Pixels of the target width the image leaves over; negative when it overflows
+    slack_px: f64,
+    warnings: Vec<CanvasWarning>,
+}
+
+/// estimate_image_size_with_limits for an image of the given pixel width, rounded to
+/// columns as rounding says (see fit_columns)
+/// Returns JSON {widthPx, heightPx, lineCount, exceedsSafeCanvasHeight, exceedsMaxCanvasHeight,
+/// columns, slackPx, warnings}: widthPx is the width the columns take, and slackPx what is left
+/// of width_px to centre the text block in
+pub fn estimate_image_size_for_width(
+    text: &str,
+    width_px: f64,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    rounding: ColumnRounding,
+    limits: &CanvasLimits,
+) -> String {
+    let fit = fit_columns(width_px, font_size_px, padding_px, rounding);
+    let drawing = Drawing::new(text, font_size_px, line_height_factor, padding_px);
+    let size = drawing.size(fit.columns);
+    let warnings = drawing.warnings(fit.columns, &size, limits);
+    let report = SizeForWidth {
+        size,
+        columns: fit.columns,
+        slack_px: fit.slack_px,
+        warnings,
+    };
+    serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LayoutWithWarnings<'a> {
+    lines: Vec<LayoutLine<'a>>,
+    width_px: f64,
+    height_px: f64,
+    warnings: Vec<CanvasWarning>,
+}
+
+/// layout_text with the image size and its warnings
+/// Returns JSON {lines: [{text, xPx, yPx, widthPx}], widthPx, heightPx, warnings}, with
+/// warnings as in estimate_image_size_with_limits
+pub fn layout_text_with_limits(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    limits: &CanvasLimits,
+) -> String {
+    let drawing = Drawing::new(text, font_size_px, line_height_factor, padding_px);
+    let justified = justify_text(text, max_chars_per_line);
+    let lines = layout_lines(
+        &justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::parse(align),
+    );
+    let size = drawing.size(max_chars_per_line);
+    let report = LayoutWithWarnings {
+        lines,
+        width_px: size.width_px,
+        height_px: size.height_px,
+        warnings: drawing.warnings(max_chars_per_line, &size, limits),
+    };
+    serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paginate_for_images;
+
+    const FONT_SIZE_PX: f64 = 16.0;
+    const LINE_HEIGHT_FACTOR: f64 = 1.5;
+    const PADDING_PX: f64 = 8.0;
+
+    fn text() -> String {
+        "長い文章を画像にする。".repeat(400)
+    }
+
+    fn estimate(text: &str, max_chars_per_line: u32, limits: &CanvasLimits) -> serde_json::Value {
+        let json = estimate_image_size_with_limits(
+            text,
+            max_chars_per_line,
+            FONT_SIZE_PX,
+            LINE_HEIGHT_FACTOR,
+            PADDING_PX,
+            limits,
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// Limits with only the one named by code, set so the image is share of where it warns
+    fn only(code: &str, size: &serde_json::Value, share: f64) -> CanvasLimits {
+        let (width_px, height_px) = (
+            size["widthPx"].as_f64().unwrap(),
+            size["heightPx"].as_f64().unwrap(),
+        );
+        let limit_at = |actual: f64| actual / LIMIT_WARNING_RATIO / share;
+        let unlimited = CanvasLimits {
+            max_height_px: f64::MAX,
+            max_width_px: f64::MAX,
+            max_total_pixels: f64::MAX,
+        };
+        match code {
+            "CANVAS_HEIGHT" => CanvasLimits {
+                max_height_px: limit_at(height_px),
+                ..unlimited
+            },
+            "CANVAS_WIDTH" => CanvasLimits {
+                max_width_px: limit_at(width_px),
+                ..unlimited
+            },
+            _ => CanvasLimits {
+                max_total_pixels: limit_at(width_px * height_px),
+                ..unlimited
+            },
+        }
+    }
+
+    #[test]
+    fn each_limit_warns_just_over_and_not_just_under() {
+        let text = text();
+        let size = estimate(&text, 20, &CanvasLimits::default());
+        assert_eq!(size["warnings"], serde_json::json!([]));
+        for code in ["CANVAS_HEIGHT", "CANVAS_WIDTH", "CANVAS_PIXELS"] {
+            let under = estimate(&text, 20, &only(code, &size, 0.999));
+            assert_eq!(under["warnings"], serde_json::json!([]), "{code}");
+
+            let over = estimate(&text, 20, &only(code, &size, 1.001));
+            let warnings = over["warnings"].as_array().unwrap();
+            assert_eq!(warnings.len(), 1, "{code}: {over}");
+            assert_eq!(warnings[0]["code"], code);
+        }
+    }
+
+    #[test]
+    fn a_suggested_width_clears_every_limit() {
+        let text = text();
+        let size = estimate(&text, 20, &CanvasLimits::default());
+        for code in ["CANVAS_HEIGHT", "CANVAS_WIDTH"] {
+            let limits = only(code, &size, 1.001);
+            let over = estimate(&text, 20, &limits);
+            let suggested = over["warnings"][0]["suggestion"]["maxCharsPerLine"]
+                .as_u64()
+                .unwrap_or_else(|| panic!("{code}: no width in {over}"));
+            assert_eq!(
+                estimate(&text, suggested as u32, &limits)["warnings"],
+                serde_json::json!([]),
+                "{code}: width {suggested}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_suggested_split_gives_the_image_count_under_the_limits() {
+        let text = text();
+        let size = estimate(&text, 20, &CanvasLimits::default());
+        let width_px = size["widthPx"].as_f64().unwrap();
+        for code in ["CANVAS_HEIGHT", "CANVAS_PIXELS"] {
+            let limits = only(code, &size, 2.0);
+            let over = estimate(&text, 20, &limits);
+            let suggestion = &over["warnings"][0]["suggestion"];
+            let split_height_px = suggestion["splitHeightPx"].as_f64().unwrap();
+            let images: serde_json::Value = serde_json::from_str(&paginate_for_images(
+                &text,
+                20,
+                FONT_SIZE_PX,
+                LINE_HEIGHT_FACTOR,
+                split_height_px,
+                false,
+            ))
+            .unwrap();
+            let images = images.as_array().unwrap();
+            assert!(images.len() > 1, "{code}");
+            assert_eq!(suggestion["imageCount"], images.len(), "{code}");
+            for image in images {
+                let height_px = image["heightPx"].as_f64().unwrap() + 2.0 * PADDING_PX;
+                assert!(limits.clears(width_px, height_px), "{code}: {height_px}px");
+            }
+        }
+    }
+
+    #[test]
+    fn a_width_warning_suggests_no_split() {
+        let text = text();
+        let size = estimate(&text, 20, &CanvasLimits::default());
+        let over = estimate(&text, 20, &only("CANVAS_WIDTH", &size, 1.001));
+        let suggestion = &over["warnings"][0]["suggestion"];
+        assert!(suggestion.get("imageCount").is_none());
+        assert!(suggestion.get("splitHeightPx").is_none());
+    }
+
+    #[test]
+    fn limits_parse_with_defaults_and_reject_non_positive_values() {
+        assert_eq!(
+            CanvasLimits::from_json(" ").unwrap(),
+            CanvasLimits::default()
+        );
+        assert_eq!(
+            CanvasLimits::from_json(r#"{"maxHeightPx": 16384}"#).unwrap(),
+            CanvasLimits {
+                max_height_px: 16_384.0,
+                ..CanvasLimits::default()
+            }
+        );
+        for json in [
+            r#"{"maxWidthPx": 0}"#,
+            r#"{"maxTotalPixels": -1}"#,
+            r#"{"maxHeight": 100}"#,
+            "{",
+        ] {
+            assert!(CanvasLimits::from_json(json).is_err(), "{json}");
+        }
+    }
+}
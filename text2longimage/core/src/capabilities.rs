@@ -0,0 +1,343 @@
+//! What this build supports, for settings panels that offer choices and for feature checks
+//! Nothing here is listed by hand: option values come from the OptionValue impls the parse
+//! functions and Deserialize impls go through, versions and limits from the code that applies
+//! them, so a value can't be listed without being accepted, or accepted without being listed.
+
+use serde::Serialize;
+
+use crate::ucd::UNICODE_VERSION;
+#[cfg(feature = "json")]
+use crate::CanvasLimits;
+use crate::{
+    max_batch_items, max_batch_size, max_input_size, max_output_ratio, AlgorithmVersion, Align,
+    CaseMode, CjkIndentMode, CjkLocale, ColumnRounding, ConfusablePolicy, CountingMode,
+    IndentConversion, ListMarkerSpacing, MergeSeparator, NewlineStyle, NormalizationForm,
+    PunctuationTarget, RubySyntax, StatsLocale, TsvOverflow, MAX_MAX_CHARS_PER_LINE,
+    MIN_MAX_CHARS_PER_LINE,
+};
+
+/// An enum that options or arguments take by name
+/// ALL is the whole registry for the type: parse functions look names up in it, and for
+/// the serde enums variant_names has to give the same names in the same order.
+pub trait OptionValue: Copy + PartialEq + 'static {
+    /// What the value is called in option lists, e.g. "rubySyntax"
+    const KIND: &'static str;
+    /// The options fields and function arguments that take it
+    const USED_BY: &'static [&'static str];
+    const ALL: &'static [Self];
+
+    /// The name options and arguments give
+    fn name(self) -> &'static str;
+
+    /// One line for a settings panel
+    fn description(self) -> &'static str;
+}
+
+/// The value of T whose name matches
+pub(crate) fn find_value<T: OptionValue>(matches: impl Fn(&str) -> bool) -> Option<T> {
+    T::ALL.iter().copied().find(|value| matches(value.name()))
+}
+
+/// T's names for an error message: "a, b or c"
+pub(crate) fn expected_values<T: OptionValue>() -> String {
+    let names: Vec<&str> = T::ALL.iter().map(|value| value.name()).collect();
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// One accepted name
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ValueInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Every value of one OptionValue type
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OptionValues {
+    pub kind: &'static str,
+    pub used_by: &'static [&'static str],
+    pub values: Vec<ValueInfo>,
+}
+
+impl OptionValues {
+    pub fn of<T: OptionValue>() -> Self {
+        OptionValues {
+            kind: T::KIND,
+            used_by: T::USED_BY,
+            values: T::ALL
+                .iter()
+                .map(|value| ValueInfo {
+                    name: value.name(),
+                    description: value.description(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One algorithmVersion
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AlgorithmVersionInfo {
+    pub number: u32,
+    pub description: &'static str,
+}
+
+/// Unicode versions of the tables in use
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct UnicodeVersions {
+    /// The width, CJK and emoji tables
+    pub tables: &'static str,
+    /// The normalization tables, with the normalization feature
+    pub normalization: Option<String>,
+}
+
+/// Size limits, as currently set
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Limits {
+    /// Largest text accepted, in bytes (see set_max_input_size)
+    pub max_input_size: usize,
+    /// Largest batch accepted, in bytes (see set_max_batch_size)
+    pub max_batch_size: usize,
+    /// Most items in a batch (see set_max_batch_items)
+    pub max_batch_items: usize,
+    /// Most output bytes per input byte, 0 for no limit (see set_max_output_ratio)
+    pub max_output_ratio: u32,
+    /// Narrowest maxCharsPerLine
+    pub min_max_chars_per_line: u32,
+    /// Widest maxCharsPerLine (see MAX_MAX_CHARS_PER_LINE)
+    pub max_max_chars_per_line: u32,
+    #[cfg(feature = "json")]
+    /// The canvas limits images are checked against by default
+    pub canvas: CanvasLimits,
+}
+
+/// Everything capabilities reports
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Cargo features this build was compiled with
+    pub features: Vec<&'static str>,
+    pub options: Vec<OptionValues>,
+    pub algorithm_versions: Vec<AlgorithmVersionInfo>,
+    pub latest_algorithm_version: u32,
+    pub unicode: UnicodeVersions,
+    pub limits: Limits,
+    /// The kinds json_schema takes, each described by the functions writing that JSON; empty
+    /// without the schema feature
+    pub schemas: Vec<ValueInfo>,
+}
+
+/// Cargo features of the core crate
+const FEATURES: &[(&str, bool)] = &[
+    ("encodings", cfg!(feature = "encodings")),
+    ("json", cfg!(feature = "json")),
+    ("normalization", cfg!(feature = "normalization")),
+    ("profiling", cfg!(feature = "profiling")),
+    ("raster", cfg!(feature = "raster")),
+    ("schema", cfg!(feature = "schema")),
+    ("simd", cfg!(feature = "simd")),
+    ("strict-checks", cfg!(feature = "strict-checks")),
+];
+
+/// Unicode version of the block tables behind is_cjk_char and the widths ("15.1.0")
+pub fn unicode_tables_version() -> &'static str {
+    UNICODE_VERSION
+}
+
+/// What this build of the core supports
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        options: vec![
+            OptionValues::of::<Align>(),
+            OptionValues::of::<CaseMode>(),
+            OptionValues::of::<CjkIndentMode>(),
+            OptionValues::of::<CjkLocale>(),
+            OptionValues::of::<ColumnRounding>(),
+            OptionValues::of::<ConfusablePolicy>(),
+            OptionValues::of::<CountingMode>(),
+            OptionValues::of::<IndentConversion>(),
+            OptionValues::of::<ListMarkerSpacing>(),
+            OptionValues::of::<MergeSeparator>(),
+            OptionValues::of::<NewlineStyle>(),
+            OptionValues::of::<NormalizationForm>(),
+            OptionValues::of::<PunctuationTarget>(),
+            OptionValues::of::<RubySyntax>(),
+            OptionValues::of::<StatsLocale>(),
+            #[cfg(feature = "encodings")]
+            OptionValues::of::<crate::TextEncoding>(),
+            OptionValues::of::<TsvOverflow>(),
+        ],
+        algorithm_versions: AlgorithmVersion::ALL
+            .iter()
+            .map(|version| AlgorithmVersionInfo {
+                number: version.number(),
+                description: version.description(),
+            })
+            .collect(),
+        latest_algorithm_version: AlgorithmVersion::LATEST.number(),
+        unicode: UnicodeVersions {
+            tables: UNICODE_VERSION,
+            normalization: normalization_unicode_version(),
+        },
+        limits: Limits {
+            max_input_size: max_input_size(),
+            max_batch_size: max_batch_size(),
+            max_batch_items: max_batch_items(),
+            max_output_ratio: max_output_ratio(),
+            min_max_chars_per_line: MIN_MAX_CHARS_PER_LINE,
+            max_max_chars_per_line: MAX_MAX_CHARS_PER_LINE,
+            #[cfg(feature = "json")]
+            canvas: CanvasLimits::default(),
+        },
+        schemas: schema_kinds(),
+    }
+}
+
+#[cfg(feature = "schema")]
+fn schema_kinds() -> Vec<ValueInfo> {
+    crate::SCHEMA_KINDS
+        .iter()
+        .map(|kind| ValueInfo {
+            name: kind.name,
+            description: kind.produced_by,
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "schema"))]
+fn schema_kinds() -> Vec<ValueInfo> {
+    Vec::new()
+}
+
+#[cfg(feature = "normalization")]
+fn normalization_unicode_version() -> Option<String> {
+    let (major, minor, update) = unicode_normalization::UNICODE_VERSION;
+    Some(format!("{}.{}.{}", major, minor, update))
+}
+
+#[cfg(not(feature = "normalization"))]
+fn normalization_unicode_version() -> Option<String> {
+    None
+}
+
+impl Capabilities {
+    /// The same capabilities with another feature list, for a wrapper crate reporting its
+    /// own features (which forward to the core's)
+    pub fn with_features(self, features: Vec<&'static str>) -> Self {
+        Capabilities { features, ..self }
+    }
+
+    #[cfg(feature = "json")]
+    /// As JSON {features, options: [{kind, usedBy, values: [{name, description}]}],
+    /// algorithmVersions: [{number, description}], latestAlgorithmVersion,
+    /// unicode: {tables, normalization}, limits, schemas: [{name, description}]}
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use super::*;
+
+    /// Every value of T in capabilities is the one parse gives for its name
+    fn assert_parses<T: OptionValue + Debug>(parse: impl Fn(&str) -> Option<T>) {
+        for &value in T::ALL {
+            assert_eq!(
+                parse(value.name()),
+                Some(value),
+                "{} {:?}",
+                T::KIND,
+                value.name()
+            );
+        }
+    }
+
+    /// A serde enum's values in capabilities are exactly the names its Deserialize impl
+    /// accepts, and each deserializes to its value
+    #[cfg(feature = "json")]
+    fn assert_deserializes<T>()
+    where
+        T: OptionValue + Debug + for<'de> serde::Deserialize<'de>,
+    {
+        let names: Vec<&str> = T::ALL.iter().map(|value| value.name()).collect();
+        assert_eq!(crate::variant_names::<T>(), names, "{} names", T::KIND);
+        for &value in T::ALL {
+            let parsed: T = serde_json::from_value(serde_json::json!(value.name())).unwrap();
+            assert_eq!(parsed, value, "{} {:?}", T::KIND, value.name());
+        }
+    }
+
+    #[test]
+    fn listed_values_are_the_ones_parsed() {
+        assert_parses(|name| Some(Align::parse(name)));
+        assert_parses(|name| CaseMode::parse(name).ok());
+        assert_parses(|name| CjkIndentMode::parse(name).ok());
+        assert_parses(|name| CjkLocale::parse(name).ok());
+        assert_parses(|name| IndentConversion::parse(name).ok());
+        assert_parses(|name| ListMarkerSpacing::parse(name).ok());
+        assert_parses(|name| MergeSeparator::parse(name).ok());
+        assert_parses(|name| NormalizationForm::parse(name).ok());
+        assert_parses(|name| PunctuationTarget::parse(name).ok());
+        assert_parses(|name| RubySyntax::parse(name).ok());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn listed_values_are_the_ones_deserialized() {
+        assert_deserializes::<Align>();
+        assert_deserializes::<ListMarkerSpacing>();
+        assert_deserializes::<NewlineStyle>();
+        assert_deserializes::<NormalizationForm>();
+        assert_deserializes::<RubySyntax>();
+        assert_deserializes::<TsvOverflow>();
+    }
+
+    #[test]
+    fn every_accepted_algorithm_version_is_listed() {
+        let capabilities = capabilities();
+        let numbers: Vec<u32> = capabilities
+            .algorithm_versions
+            .iter()
+            .map(|version| version.number)
+            .collect();
+        let accepted: Vec<u32> = (0..=numbers.len() as u32 + 1)
+            .filter(|&number| AlgorithmVersion::from_number(number).is_ok())
+            .collect();
+        assert_eq!(numbers, accepted);
+        assert_eq!(numbers.last(), Some(&capabilities.latest_algorithm_version));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_lists_every_option_kind() {
+        let capabilities = capabilities();
+        let json: serde_json::Value = serde_json::from_str(&capabilities.to_json()).unwrap();
+        let kinds: Vec<&str> = json["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|values| values["kind"].as_str())
+            .collect();
+        assert_eq!(kinds.len(), capabilities.options.len());
+    }
+}
@@ -0,0 +1,218 @@
+//! Case transforms for headings, done here so the widths they change are known
+//! Unicode's full case mappings are used: fullwidth letters stay fullwidth (ａ ↔ Ａ), CJK has
+//! no case and is left alone, and a mapping like ß → SS that makes a character wider is
+//! reported so the caller can wrap the text again.
+
+use serde::Serialize;
+
+use crate::capabilities::{expected_values, find_value};
+use crate::{is_cjk_char, width_of, OptionValue, WebtoolsError};
+
+/// What transform_case does to letters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseMode {
+    /// Every letter uppercased
+    Upper,
+    /// Every letter lowercased
+    Lower,
+    /// The first letter of each word titlecased, the others left as they are
+    Title,
+}
+
+impl CaseMode {
+    /// Parse "upper" / "lower" / "title" in any case
+    pub fn parse(mode: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| mode.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown case mode: {} (expected {})",
+                mode,
+                expected_values::<CaseMode>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for CaseMode {
+    const KIND: &'static str = "caseMode";
+    const USED_BY: &'static [&'static str] = &["transform_case"];
+    const ALL: &'static [Self] = &[CaseMode::Upper, CaseMode::Lower, CaseMode::Title];
+
+    fn name(self) -> &'static str {
+        match self {
+            CaseMode::Upper => "upper",
+            CaseMode::Lower => "lower",
+            CaseMode::Title => "title",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CaseMode::Upper => "Every letter uppercased",
+            CaseMode::Lower => "Every letter lowercased",
+            CaseMode::Title => "The first letter of each word titlecased",
+        }
+    }
+}
+
+/// Transformed text, and whether any character's display width changed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CaseTransform {
+    pub text: String,
+    /// Set when a mapping made the text wider or narrower (ß → SS), so wrapped output is stale
+    pub width_changed: bool,
+}
+
+/// Change the case of text
+/// Title case only touches the first letter of each word, so acronyms survive ("USB cable" →
+/// "USB Cable"). A word is a run of letters, digits and in-word apostrophes; CJK characters end
+/// one like spaces and punctuation do, so "中文heading" → "中文Heading".
+pub fn transform_case(text: &str, mode: CaseMode) -> CaseTransform {
+    let mut result = String::with_capacity(text.len());
+    let mut width_changed = false;
+    let mut in_word = false;
+    for c in text.chars() {
+        let start = result.len();
+        match mode {
+            CaseMode::Upper => result.extend(c.to_uppercase()),
+            CaseMode::Lower => result.extend(c.to_lowercase()),
+            CaseMode::Title if !in_word && is_cased_letter(c) => push_titlecase(c, &mut result),
+            CaseMode::Title => result.push(c),
+        }
+        let width: u32 = result[start..].chars().map(width_of).sum();
+        width_changed |= width != width_of(c);
+        in_word = continues_word(c, in_word);
+    }
+    if mode == CaseMode::Lower {
+        // Same widths, but final sigma (Σ → ς at the end of a word) needs the context
+        result = text.to_lowercase();
+    }
+    CaseTransform {
+        text: result,
+        width_changed,
+    }
+}
+
+#[cfg(feature = "json")]
+/// transform_case with the mode given as "upper", "lower" or "title"
+/// Returns JSON {text, widthChanged}
+pub fn transform_case_json(text: &str, mode: &str) -> Result<String, WebtoolsError> {
+    let transformed = transform_case(text, CaseMode::parse(mode)?);
+    Ok(serde_json::to_string(&transformed).unwrap_or_else(|_| "{}".to_string()))
+}
+
+/// Letters that have case, which rules out CJK
+fn is_cased_letter(c: char) -> bool {
+    !is_cjk_char(c) && (c.is_lowercase() || c.is_uppercase())
+}
+
+/// Whether a word still goes on after c; apostrophes and combining marks stay inside one
+fn continues_word(c: char, in_word: bool) -> bool {
+    if is_cjk_char(c) {
+        false
+    } else if c.is_alphanumeric() {
+        true
+    } else {
+        in_word && matches!(c, '\'' | '’' | '\u{0300}'..='\u{036F}')
+    }
+}
+
+/// The titlecase form: the uppercase one, except for the digraphs that have a titlecase
+/// letter of their own (ǆ → ǅ) and for mappings to several letters (ß → Ss, ﬁ → Fi)
+fn push_titlecase(c: char, out: &mut String) {
+    let digraph = match c {
+        '\u{01C4}'..='\u{01C6}' => Some('\u{01C5}'),
+        '\u{01C7}'..='\u{01C9}' => Some('\u{01C8}'),
+        '\u{01CA}'..='\u{01CC}' => Some('\u{01CB}'),
+        '\u{01F1}'..='\u{01F3}' => Some('\u{01F2}'),
+        _ => None,
+    };
+    if let Some(title) = digraph {
+        out.push(title);
+        return;
+    }
+    let mut upper = c.to_uppercase();
+    out.extend(upper.next());
+    out.extend(upper.flat_map(char::to_lowercase));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transformed(text: &str, mode: CaseMode) -> (String, bool) {
+        let transformed = transform_case(text, mode);
+        (transformed.text, transformed.width_changed)
+    }
+
+    #[test]
+    fn sharp_s_uppercases_wider() {
+        assert_eq!(
+            transformed("Straße", CaseMode::Upper),
+            ("STRASSE".to_string(), true)
+        );
+        assert_eq!(
+            transformed("straße", CaseMode::Title),
+            ("Straße".to_string(), false)
+        );
+        assert_eq!(transformed("ß", CaseMode::Title), ("Ss".to_string(), true));
+    }
+
+    #[test]
+    fn fullwidth_letters_stay_fullwidth() {
+        assert_eq!(
+            transformed("ａｂｃ", CaseMode::Upper),
+            ("ＡＢＣ".to_string(), false)
+        );
+        assert_eq!(
+            transformed("ＡＢＣ", CaseMode::Lower),
+            ("ａｂｃ".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn title_case_starts_words_after_cjk() {
+        assert_eq!(
+            transformed("中文heading", CaseMode::Title),
+            ("中文Heading".to_string(), false)
+        );
+        assert_eq!(
+            transformed("USB cable, don't stop", CaseMode::Title),
+            ("USB Cable, Don't Stop".to_string(), false)
+        );
+        // A digraph has a titlecase letter of its own
+        assert_eq!(transformed("ǆungla", CaseMode::Title).0, "ǅungla");
+        assert_eq!(
+            transformed("中文", CaseMode::Upper),
+            ("中文".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn final_sigma_lowercases_at_the_end_of_a_word() {
+        assert_eq!(transformed("ΟΔΟΣ", CaseMode::Lower).0, "οδος");
+    }
+
+    #[test]
+    fn modes_parse_in_any_case() {
+        assert_eq!(CaseMode::parse("upper"), Ok(CaseMode::Upper));
+        assert_eq!(CaseMode::parse("Title"), Ok(CaseMode::Title));
+        let error = CaseMode::parse("sentence").unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+        assert!(
+            error.to_string().contains("upper"),
+            "{error} names the modes"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_form_reports_width_changed() {
+        assert_eq!(
+            transform_case_json("ß", "upper").unwrap(),
+            r#"{"text":"SS","widthChanged":true}"#
+        );
+        assert!(transform_case_json("ß", "sentence").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Chunk sizes for process_text_chunks, picked from what wrapping costs where it runs
+//! A chunk is meant to be about TARGET_CHUNK_MS of work: short enough to report progress and
+//! yield between, long enough that the seams cost nothing. The cost per byte is measured once
+//! by calibrate_chunk_cost, which wraps a small built-in sample against the caller's clock;
+//! until then (or with calibration turned off) DEFAULT_WRAP_NS_PER_BYTE stands in for it.
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::{justify_text, DEFAULT_MAX_CHARS_PER_LINE};
+
+/// Work one chunk is sized for, in milliseconds
+pub const TARGET_CHUNK_MS: f64 = 75.0;
+
+/// Wrapping cost assumed before calibration, in nanoseconds per UTF-8 byte (wasm in a
+/// current desktop browser, mixed CJK and English)
+pub const DEFAULT_WRAP_NS_PER_BYTE: f64 = 20.0;
+
+/// Most chunks a text is split into; longer texts get longer chunks instead
+const MAX_CHUNKS: usize = 64;
+
+/// Fewest full lines a chunk has room for, so a wide line width doesn't get chunks that are
+/// mostly seams
+const MIN_LINES_PER_CHUNK: usize = 64;
+
+/// The calibration run times CALIBRATION_BATCHES batches of passes over the sample, each
+/// lasting at least BATCH_MS, and keeps the fastest, so a GC pause or a tier-up compile in one
+/// batch doesn't count
+const CALIBRATION_BATCHES: usize = 5;
+const BATCH_MS: f64 = 1.0;
+
+/// Upper bound on the passes in a batch, for a clock that doesn't advance
+const MAX_BATCH_PASSES: usize = 100;
+
+/// One paragraph of the calibration sample: CJK, English and a blank line
+const CALIBRATION_PARAGRAPH: &str =
+    "这是一段用于测试的中文文本，包含标点符号和English words混排。\n\
+    The quick brown fox jumps over the lazy dog, and the lazy dog doesn't mind at all.\n\n\
+    日本語のテキストも含まれています。句読点、括弧「」も。\n";
+
+/// Paragraphs in the calibration sample (about 9 KB)
+const CALIBRATION_REPEAT: usize = 32;
+
+static WRAP_NS_PER_BYTE: AtomicU64 = AtomicU64::new(DEFAULT_WRAP_NS_PER_BYTE.to_bits());
+static CALIBRATED: AtomicBool = AtomicBool::new(false);
+static CALIBRATION_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn the calibration run off (or back on, if it hasn't run yet); off keeps
+/// DEFAULT_WRAP_NS_PER_BYTE for good
+pub fn set_chunk_calibration(enabled: bool) {
+    CALIBRATION_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether calibrate_chunk_cost still measures anything
+pub fn chunk_calibration() -> bool {
+    CALIBRATION_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Wrapping cost recommended_chunk_size works from, in nanoseconds per byte
+pub fn wrap_ns_per_byte() -> f64 {
+    f64::from_bits(WRAP_NS_PER_BYTE.load(Ordering::Relaxed))
+}
+
+/// Measure what wrapping costs, the first time it is called with calibration enabled
+/// clock is in milliseconds. The sample is wrapped for about CALIBRATION_BATCHES × BATCH_MS;
+/// later calls, and a clock that never advances, leave the cost as it is. Returns
+/// wrap_ns_per_byte.
+pub fn calibrate_chunk_cost(clock: impl Fn() -> f64) -> f64 {
+    if !chunk_calibration() || CALIBRATED.swap(true, Ordering::Relaxed) {
+        return wrap_ns_per_byte();
+    }
+
+    let sample = CALIBRATION_PARAGRAPH.repeat(CALIBRATION_REPEAT);
+    // Untimed, so a cold start (code not optimized yet, tables not paged in) isn't measured
+    black_box(justify_text(black_box(&sample), DEFAULT_MAX_CHARS_PER_LINE));
+
+    let mut fastest = f64::INFINITY;
+    for _ in 0..CALIBRATION_BATCHES {
+        let start = clock();
+        let mut elapsed = 0.0;
+        let mut passes = 0;
+        while elapsed < BATCH_MS && passes < MAX_BATCH_PASSES {
+            black_box(justify_text(black_box(&sample), DEFAULT_MAX_CHARS_PER_LINE));
+            passes += 1;
+            elapsed = clock() - start;
+        }
+        if elapsed.is_finite() && elapsed > 0.0 {
+            fastest = fastest.min(elapsed * 1e6 / (passes * sample.len()) as f64);
+        }
+    }
+    if fastest.is_finite() {
+        WRAP_NS_PER_BYTE.store(fastest.to_bits(), Ordering::Relaxed);
+    }
+    wrap_ns_per_byte()
+}
+
+/// Chunk size in bytes for process_text_chunks over text_len bytes at max_chars_per_line
+/// TARGET_CHUNK_MS of work at wrap_ns_per_byte, but never more than MAX_CHUNKS chunks, never
+/// less than MIN_LINES_PER_CHUNK lines of the width, and never more than the text: the size
+/// only grows with text_len, and a text wrapped in under TARGET_CHUNK_MS is one chunk.
+pub fn recommended_chunk_size(text_len: usize, max_chars_per_line: u32) -> u32 {
+    let target = (TARGET_CHUNK_MS * 1e6 / wrap_ns_per_byte()) as usize;
+    let size = target
+        .max(text_len.div_ceil(MAX_CHUNKS))
+        .max((max_chars_per_line as usize).saturating_mul(MIN_LINES_PER_CHUNK))
+        .min(text_len)
+        .max(1);
+    u32::try_from(size).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::lock_limits;
+    use crate::{process_text_chunks, set_max_input_size, DEFAULT_MAX_INPUT_SIZE};
+
+    #[test]
+    fn the_chunk_size_grows_with_the_text_and_stays_in_bounds() {
+        let mut text_lens: Vec<usize> = (0..37).flat_map(|e| [(1 << e) - 1, 1 << e]).collect();
+        text_lens.dedup();
+        for max_chars_per_line in [1, 20, 80, 1_000] {
+            let mut last = 0;
+            for &text_len in &text_lens {
+                let size = recommended_chunk_size(text_len, max_chars_per_line) as usize;
+                assert!(size >= last, "{text_len} at {max_chars_per_line}");
+                assert!(size >= 1 && size <= text_len.max(1), "{text_len}");
+                assert!(text_len.div_ceil(size) <= MAX_CHUNKS, "{text_len}");
+                assert!(
+                    size >= text_len.min(max_chars_per_line as usize * MIN_LINES_PER_CHUNK),
+                    "{text_len} at {max_chars_per_line}"
+                );
+                last = size;
+            }
+        }
+    }
+
+    #[test]
+    fn a_text_wrapped_within_the_target_is_one_chunk() {
+        let within = (TARGET_CHUNK_MS * 1e6 / wrap_ns_per_byte()) as usize;
+        for text_len in [1, 1_000, within] {
+            assert_eq!(recommended_chunk_size(text_len, 20) as usize, text_len);
+        }
+    }
+
+    #[test]
+    fn auto_chunks_give_justify_text_output() {
+        let paragraph = "这是一段用于测试的中文文本，包含English words混排。\r\n\
+                         The quick brown fox jumps over the lazy dog.\n\n\
+                         一行だけ。\n";
+        let small = paragraph.repeat(10);
+        assert_eq!(
+            process_text_chunks(&small, 20, 0).unwrap(),
+            justify_text(&small, 20)
+        );
+
+        // Long enough for more than one chunk, ending without a line break
+        let within = (TARGET_CHUNK_MS * 1e6 / wrap_ns_per_byte()) as usize;
+        let mut text = paragraph.repeat(within / paragraph.len());
+        while recommended_chunk_size(text.len(), 20) as usize >= text.len() {
+            text.push_str(paragraph);
+        }
+        text.push_str("最後");
+        let _limits = lock_limits();
+        set_max_input_size(text.len());
+        let chunked = process_text_chunks(&text, 20, 0);
+        set_max_input_size(DEFAULT_MAX_INPUT_SIZE);
+        assert!(chunked.unwrap() == justify_text(&text, 20));
+    }
+}
@@ -0,0 +1,160 @@
+//! Run-length encoded character classes, for renderers that color scripts differently
+//! A long image with CJK, Latin, digits and punctuation in their own colors needs every
+//! character classified; doing it here keeps the classes in step with the tables the wrapper
+//! and the stats use, and runs of one class keep the JSON small on huge documents. Lengths
+//! are in UTF-16 code units, so a renderer can slice its JavaScript strings with them.
+
+use serde::Serialize;
+
+use crate::emoji::{emoji_len, VS15, VS16, ZWJ};
+use crate::kinsoku::is_iteration_mark;
+use crate::marks::is_combining_mark;
+use crate::CharClass;
+
+/// What a run of characters is, for coloring
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum RunClass {
+    /// What get_text_stats counts as CJK: Han, kana and iteration marks (々)
+    Cjk,
+    /// Latin, Greek and Cyrillic letters, fullwidth Latin letters included
+    Latin,
+    /// ASCII and fullwidth digits
+    Digit,
+    /// ASCII, Latin-1, general and CJK punctuation (〇 aside), fullwidth forms included
+    Punct,
+    /// Whitespace and line breaks, U+3000 included
+    Space,
+    /// An emoji grapheme, as emoji_placements finds them
+    Emoji,
+    /// Everything else: other scripts (Hangul, Arabic, Thai, ...), symbols, control characters
+    Other,
+}
+
+/// One run: its class and its length in UTF-16 code units; serialized as [class, length]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ClassRun(pub RunClass, pub u32);
+
+/// Punctuation of the blocks CJK and Latin text draw on
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            c as u32,
+            0x00A1..=0x00BF // Latin-1 punctuation and signs
+                | 0x2010..=0x2027 // dashes, quotes, ellipsis
+                | 0x2030..=0x205E // per mille, primes, general punctuation
+                | 0x3001..=0x3006 // 、。〃〄
+                | 0x3008..=0x303F // CJK brackets and marks
+                | 0xFF01..=0xFF0F
+                | 0xFF1A..=0xFF20
+                | 0xFF3B..=0xFF40
+                | 0xFF5B..=0xFF65 // fullwidth and halfwidth punctuation
+        )
+}
+
+/// The class of a character outside an emoji grapheme
+fn class_of(c: char) -> RunClass {
+    if c.is_whitespace() {
+        RunClass::Space
+    } else if CharClass::of(c).cjk || is_iteration_mark(c) {
+        RunClass::Cjk
+    } else if c.is_ascii_digit() || matches!(c, '０'..='９') {
+        RunClass::Digit
+    } else if (c.is_alphabetic() && (c as u32) < 0x0530) || matches!(c, 'Ａ'..='Ｚ' | 'ａ'..='ｚ')
+    {
+        RunClass::Latin
+    } else if is_punctuation(c) {
+        RunClass::Punct
+    } else {
+        RunClass::Other
+    }
+}
+
+/// Whether c belongs to the character before it: a combining mark, a presentation selector
+/// or a joiner
+fn extends_previous(c: char) -> bool {
+    is_combining_mark(c) || c == VS15 || c == VS16 || c == ZWJ
+}
+
+/// Push length units of class onto runs, extending the last run when it is of that class
+fn push_run(runs: &mut Vec<ClassRun>, class: RunClass, length: u32) {
+    match runs.last_mut() {
+        Some(ClassRun(last, run_length)) if *last == class => *run_length += length,
+        _ => runs.push(ClassRun(class, length)),
+    }
+}
+
+/// The classes of text, run by run; the lengths add up to text's length in UTF-16 code units
+/// An emoji grapheme is one emoji run however many code points it has, and combining marks,
+/// presentation selectors and joiners take the class of the character before them.
+pub fn class_runs(text: &str) -> Vec<ClassRun> {
+    let chars: Vec<char> = text.chars().collect();
+    let units = |chars: &[char]| chars.iter().map(|c| c.len_utf16() as u32).sum::<u32>();
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(len) = emoji_len(&chars, i) {
+            push_run(&mut runs, RunClass::Emoji, units(&chars[i..i + len]));
+            i += len;
+            continue;
+        }
+        let c = chars[i];
+        let class = match runs.last() {
+            Some(&ClassRun(last, _)) if extends_previous(c) => last,
+            _ => class_of(c),
+        };
+        push_run(&mut runs, class, c.len_utf16() as u32);
+        i += 1;
+    }
+    runs
+}
+
+/// class_runs of each line of already wrapped text (CRLF or LF separated), without the line
+/// breaks, so no run crosses from one line into the next
+pub fn class_runs_by_line(wrapped_text: &str) -> Vec<Vec<ClassRun>> {
+    wrapped_text
+        .split('\n')
+        .map(|line| class_runs(line.strip_suffix('\r').unwrap_or(line)))
+        .collect()
+}
+
+#[cfg(feature = "json")]
+/// class_runs as JSON: array of [class, length] pairs, class being "cjk", "latin", "digit",
+/// "punct", "space", "emoji" or "other" and length in UTF-16 code units
+pub fn classify_runs(text: &str) -> String {
+    serde_json::to_string(&class_runs(text)).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(feature = "json")]
+/// class_runs_by_line as JSON: an array per line of [class, length] pairs
+pub fn classify_line_runs(wrapped_text: &str) -> String {
+    serde_json::to_string(&class_runs_by_line(wrapped_text)).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    /// classify_runs of a mixed-script line, with the runs it is made of
+    const CLASS_RUN_EXAMPLE: (&str, &str) = (
+        "Hello，世界！ 2024年 👨\u{200D}👩\u{200D}👧 cafe\u{0301} 한국",
+        r#"[["latin",5],["punct",1],["cjk",2],["punct",1],["space",1],["digit",4],["cjk",1],["space",1],["emoji",8],["space",1],["latin",5],["space",1],["other",2]]"#,
+    );
+
+    #[test]
+    fn runs_of_a_mixed_script_line() {
+        let (text, expected) = CLASS_RUN_EXAMPLE;
+        assert_eq!(classify_runs(text), expected);
+        assert_eq!(classify_runs(""), "[]");
+    }
+
+    #[test]
+    fn runs_of_each_wrapped_line() {
+        assert_eq!(
+            classify_line_runs("漢字 ok\r\n42!"),
+            r#"[[["cjk",2],["space",1],["latin",2]],[["digit",2],["punct",1]]]"#
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ansi::strip_ansi_recorded;
+#[cfg(feature = "json")]
+use crate::edits::edits_json;
+use crate::edits::{compose_edits, EditRecorder, TextEdit};
+use crate::emoji::{ends_emoji, is_pictographic, takes_presentation_selector, VS15, VS16, ZWJ};
+use crate::normalize::to_nfc_recorded;
+use crate::OptionValue;
+
+#[cfg(feature = "json")]
+use crate::normalize::check_normalization_available;
+#[cfg(feature = "json")]
+use crate::WebtoolsError;
+
+/// Line ending clean_text writes for \r\n, \n and lone \r
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewlineStyle {
+    /// Leave line endings as they are
+    Keep,
+    Lf,
+    Crlf,
+}
+
+impl OptionValue for NewlineStyle {
+    const KIND: &'static str = "newlineStyle";
+    const USED_BY: &'static [&'static str] = &["CleanOptions.normalizeNewlinesTo"];
+    const ALL: &'static [Self] = &[NewlineStyle::Keep, NewlineStyle::Lf, NewlineStyle::Crlf];
+
+    fn name(self) -> &'static str {
+        match self {
+            NewlineStyle::Keep => "keep",
+            NewlineStyle::Lf => "lf",
+            NewlineStyle::Crlf => "crlf",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            NewlineStyle::Keep => "Line endings are left as they are",
+            NewlineStyle::Lf => "Every line ending becomes \\n",
+            NewlineStyle::Crlf => "Every line ending becomes \\r\\n",
+        }
+    }
+}
+
+/// Toggles for clean_text; every field is optional when deserializing
+/// JSON keys are camelCase: {"stripBom", "normalizeNewlinesTo": "keep"|"lf"|"crlf",
+/// "stripZeroWidth", "stripStrayVariationSelectors", "stripDirectionalMarks", "stripAnsi",
+/// "normalizeNfc"}
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CleanOptions {
+    /// Drop a U+FEFF byte order mark at the very start
+    pub strip_bom: bool,
+    pub normalize_newlines_to: NewlineStyle,
+    /// Drop zero-width spaces, joiners and non-joiners, word joiners and stray U+FEFF;
+    /// a ZWJ that joins two emoji is kept
+    pub strip_zero_width: bool,
+    /// Drop text and emoji presentation selectors (U+FE0E, U+FE0F) after characters they do
+    /// nothing to, such as punctuation or letters; after an emoji-capable character (☺, ©,
+    /// a keycap digit) they choose how it is drawn and are kept
+    pub strip_stray_variation_selectors: bool,
+    /// Drop bidi marks, embeddings, overrides and isolates
+    pub strip_directional_marks: bool,
+    /// Remove ANSI escape sequences (terminal colors); off by default
+    pub strip_ansi: bool,
+    /// Compose the text to NFC first; off by default and needs the normalization feature
+    pub normalize_nfc: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            strip_bom: true,
+            normalize_newlines_to: NewlineStyle::Lf,
+            strip_zero_width: true,
+            strip_stray_variation_selectors: true,
+            strip_directional_marks: true,
+            strip_ansi: false,
+            normalize_nfc: false,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl CleanOptions {
+    /// Parse options JSON; an empty string means all defaults
+    pub fn from_json(options_json: &str) -> Result<CleanOptions, WebtoolsError> {
+        if options_json.trim().is_empty() {
+            return Ok(CleanOptions::default());
+        }
+        let options: CleanOptions =
+            serde_json::from_str(options_json).map_err(WebtoolsError::invalid_json)?;
+        check_normalization_available(options.normalize_nfc)?;
+        Ok(options)
+    }
+}
+
+/// Cleaned text plus what was removed or rewritten
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CleanReport {
+    pub text: String,
+    pub bom_removed: usize,
+    pub zero_width_removed: usize,
+    pub variation_selectors_removed: usize,
+    pub directional_marks_removed: usize,
+    /// Line breaks rewritten to the requested style
+    pub newlines_normalized: usize,
+    /// Escape sequences removed when strip_ansi is on
+    pub ansi_sequences_removed: usize,
+}
+
+impl CleanReport {
+    /// Invisible characters removed, for messages like "removed 14 invisible characters"
+    pub fn invisible_removed(&self) -> usize {
+        self.bom_removed
+            + self.zero_width_removed
+            + self.variation_selectors_removed
+            + self.directional_marks_removed
+    }
+}
+
+const BOM: char = '\u{FEFF}';
+
+pub(crate) fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{200C}' | ZWJ | '\u{2060}' | BOM)
+}
+
+fn is_directional_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Clean text in one pass; see CleanOptions for what each toggle does
+pub fn clean_text_with(text: &str, options: &CleanOptions) -> CleanReport {
+    clean_recorded(text, options, false).0
+}
+
+/// clean_text_with, and the edits that make the change when record is set
+/// Each pass (ANSI, NFC, the rest) records its own edits, composed into edits of text.
+fn clean_recorded(
+    text: &str,
+    options: &CleanOptions,
+    record: bool,
+) -> (CleanReport, Vec<TextEdit>) {
+    let mut report = CleanReport::default();
+
+    let (without_ansi, ansi_sequences_removed, ansi_edits) = if options.strip_ansi {
+        strip_ansi_recorded(text, record)
+    } else {
+        (text.into(), 0, Vec::new())
+    };
+    report.ansi_sequences_removed = ansi_sequences_removed;
+    let (normalized, nfc_edits) = if options.normalize_nfc {
+        to_nfc_recorded(&without_ansi, record)
+    } else {
+        (Cow::Borrowed(&*without_ansi), Vec::new())
+    };
+
+    let mut out = EditRecorder::new(normalized.len(), record);
+    let mut rest: &str = &normalized;
+    if options.strip_bom {
+        if let Some(stripped) = rest.strip_prefix(BOM) {
+            rest = stripped;
+            out.replace(1, "");
+            report.bom_removed = 1;
+        }
+    }
+
+    let mut previous: Option<char> = None;
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' | '\n' if options.normalize_newlines_to != NewlineStyle::Keep => {
+                let crlf = c == '\r' && chars.next_if_eq(&'\n').is_some();
+                let wanted = options.normalize_newlines_to == NewlineStyle::Crlf;
+                match (crlf, wanted) {
+                    (true, true) => out.keep("\r\n"),
+                    (false, false) if c == '\n' => out.keep_char(c),
+                    // A lone \r is rewritten whichever style is wanted
+                    _ => {
+                        out.replace(1 + usize::from(crlf), if wanted { "\r\n" } else { "\n" });
+                        report.newlines_normalized += 1;
+                    }
+                }
+                previous = Some('\n');
+                continue;
+            }
+            ZWJ if options.strip_zero_width => {
+                let joins_emoji = previous.is_some_and(ends_emoji)
+                    && chars.peek().copied().is_some_and(is_pictographic);
+                if !joins_emoji {
+                    out.replace(1, "");
+                    report.zero_width_removed += 1;
+                    continue;
+                }
+            }
+            c if options.strip_zero_width && is_zero_width(c) => {
+                out.replace(1, "");
+                report.zero_width_removed += 1;
+                continue;
+            }
+            VS15 | VS16
+                if options.strip_stray_variation_selectors
+                    && !previous.is_some_and(takes_presentation_selector) =>
+            {
+                out.replace(1, "");
+                report.variation_selectors_removed += 1;
+                continue;
+            }
+            c if options.strip_directional_marks && is_directional_mark(c) => {
+                out.replace(1, "");
+                report.directional_marks_removed += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        out.keep_char(c);
+        previous = Some(c);
+    }
+
+    let (cleaned, clean_edits) = out.finish();
+    report.text = cleaned;
+    let edits = compose_edits(ansi_edits, nfc_edits, &without_ansi);
+    let edits = compose_edits(edits, clean_edits, &normalized);
+    (report, edits)
+}
+
+#[cfg(feature = "json")]
+/// Strip BOM, normalize newlines and remove zero-width characters, stray variation selectors
+/// and directional marks
+/// options_json is a CleanOptions object; "" uses the defaults (see CleanOptions::default)
+pub fn clean_text(text: &str, options_json: &str) -> Result<String, WebtoolsError> {
+    let options = CleanOptions::from_json(options_json)?;
+    Ok(clean_text_with(text, &options).text)
+}
+
+#[cfg(feature = "json")]
+/// clean_text as edits of text, for an editor to apply in place
+/// Returns JSON [{start, end, replacement}] in char indices of text, ascending and not
+/// touching; applied to text (see apply_edits) they give clean_text's output.
+pub fn clean_text_edits(text: &str, options_json: &str) -> Result<String, WebtoolsError> {
+    let options = CleanOptions::from_json(options_json)?;
+    let (_, edits) = clean_recorded(text, &options, true);
+    Ok(edits_json(&edits))
+}
+
+#[cfg(feature = "json")]
+/// What clean_text_report writes: the report plus its invisible_removed total
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CleanReportJson<'a> {
+    #[serde(flatten)]
+    report: &'a CleanReport,
+    invisible_removed: usize,
+}
+
+#[cfg(feature = "json")]
+/// clean_text plus counts of what changed
+/// Returns JSON {text, bomRemoved, zeroWidthRemoved, variationSelectorsRemoved,
+/// directionalMarksRemoved, newlinesNormalized, ansiSequencesRemoved, invisibleRemoved}
+pub fn clean_text_report(text: &str, options_json: &str) -> Result<String, WebtoolsError> {
+    let options = CleanOptions::from_json(options_json)?;
+    let report = clean_text_with(text, &options);
+    let json = CleanReportJson {
+        report: &report,
+        invisible_removed: report.invisible_removed(),
+    };
+    Ok(serde_json::to_string(&json).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(text: &str) -> CleanReport {
+        clean_text_with(text, &CleanOptions::default())
+    }
+
+    const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+
+    #[test]
+    fn pasted_web_text() {
+        let report = clean("\u{FEFF}Hello\u{200B} wor\u{200D}ld\u{200E}\r\nnext\rlast\n");
+        assert_eq!(report.text, "Hello world\nnext\nlast\n");
+        assert_eq!(report.bom_removed, 1);
+        assert_eq!(report.zero_width_removed, 2);
+        assert_eq!(report.directional_marks_removed, 1);
+        // The \r\n and the lone \r; the \n was already LF
+        assert_eq!(report.newlines_normalized, 2);
+        assert_eq!(report.invisible_removed(), 4);
+    }
+
+    #[test]
+    fn joiners_inside_emoji_sequences_stay() {
+        let family = clean(FAMILY);
+        assert_eq!(family.text, FAMILY);
+        assert_eq!(family.zero_width_removed, 0);
+        // Rainbow flag: 🏳 VS16 ZWJ 🌈, the selector chosen for an emoji-capable character
+        let flag = "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}";
+        assert_eq!(clean(flag).text, flag);
+        // A joiner at either end of the sequence, or between letters, joins nothing
+        assert_eq!(clean("\u{200D}\u{1F468}\u{200D}").text, "\u{1F468}");
+        assert_eq!(clean("a\u{200D}\u{1F468}").text, "a\u{1F468}");
+        assert_eq!(clean("\u{1F468}\u{200D}a").text, "\u{1F468}a");
+    }
+
+    #[test]
+    fn stray_variation_selectors_go() {
+        let report = clean("a\u{FE0F}!\u{FE0E} \u{263A}\u{FE0F}");
+        assert_eq!(report.text, "a! \u{263A}\u{FE0F}");
+        assert_eq!(report.variation_selectors_removed, 2);
+        // A selector stays only after a character with both presentations, keycap digits
+        // included
+        for (text, cleaned) in [
+            ("\u{263A}\u{FE0E}", "\u{263A}\u{FE0E}"),
+            ("\u{2460}\u{FE0E}", "\u{2460}"),
+            ("1\u{FE0F}", "1\u{FE0F}"),
+        ] {
+            assert_eq!(clean(text).text, cleaned, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn each_toggle_off_keeps_its_characters() {
+        let text = "\u{FEFF}a\u{200B}b\u{200F}c\u{FE0F}\r\nd";
+        let off = CleanOptions {
+            strip_bom: false,
+            normalize_newlines_to: NewlineStyle::Keep,
+            strip_zero_width: false,
+            strip_stray_variation_selectors: false,
+            strip_directional_marks: false,
+            strip_ansi: false,
+            normalize_nfc: false,
+        };
+        let report = clean_text_with(text, &off);
+        assert_eq!(report.text, text);
+        assert_eq!(
+            report,
+            CleanReport {
+                text: text.to_string(),
+                ..CleanReport::default()
+            }
+        );
+
+        // A BOM after the start is zero-width junk, not a byte order mark
+        let only_bom = CleanOptions {
+            strip_bom: true,
+            ..off.clone()
+        };
+        assert_eq!(
+            clean_text_with("\u{FEFF}a\u{FEFF}", &only_bom).text,
+            "a\u{FEFF}"
+        );
+        let only_zero_width = CleanOptions {
+            strip_zero_width: true,
+            ..off
+        };
+        assert_eq!(
+            clean_text_with("\u{FEFF}a\u{FEFF}", &only_zero_width).text,
+            "a"
+        );
+    }
+
+    #[test]
+    fn newline_styles() {
+        let text = "a\r\nb\nc\rd";
+        let to = |style| CleanOptions {
+            normalize_newlines_to: style,
+            ..CleanOptions::default()
+        };
+        assert_eq!(
+            clean_text_with(text, &to(NewlineStyle::Lf)).text,
+            "a\nb\nc\nd"
+        );
+        let crlf = clean_text_with(text, &to(NewlineStyle::Crlf));
+        assert_eq!(crlf.text, "a\r\nb\r\nc\r\nd");
+        assert_eq!(crlf.newlines_normalized, 2);
+        assert_eq!(clean_text_with(text, &to(NewlineStyle::Keep)).text, text);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn report_json_counts_the_invisible_characters() {
+        let json = clean_text_report(
+            "\u{FEFF}a\u{200B}\u{200E}\r\n",
+            r#"{"normalizeNewlinesTo":"keep"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"text":"a\r\n","bomRemoved":1,"zeroWidthRemoved":1,"variationSelectorsRemoved":0,"directionalMarksRemoved":1,"newlinesNormalized":0,"ansiSequencesRemoved":0,"invisibleRemoved":3}"#
+        );
+        assert_eq!(clean_text("\u{FEFF}a\r\n", "").unwrap(), "a\n");
+        assert_eq!(clean_text("x", "{").unwrap_err().code(), "INVALID_JSON");
+    }
+}
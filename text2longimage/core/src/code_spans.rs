@@ -0,0 +1,201 @@
+//! Markdown inline code spans, kept whole by the wrapper
+//! `HashMap::new()` split across two lines leaves a backtick at a line edge, which reads as a
+//! typo in the image. With markdown set (from algorithm V5 on), a code span, from its opening
+//! backticks to its closing ones, is one unbreakable token: it moves to the next line whole
+//! and overflows like any word too wide for a line, or on a character-wrapped line is wrapped
+//! by character when wider than a whole line. A span opens with a run of backticks and closes
+//! with the next run of the same length, as in CommonMark, so ``a ` b`` holds a literal
+//! backtick; a run with no match is ordinary text. Around the spans a character-wrapped line
+//! is wrapped as a ruby line is, without the break options' adjustments. The wrapping below
+//! takes any unbreakable spans, the characters a BOM joins (see joiners.rs) among them.
+
+use std::ops::Range;
+
+use crate::metrics::{fits_width, place_chars_with, WidthModel, WrapSink};
+
+/// Byte ranges of the code spans of line, delimiters included, in order
+pub(crate) fn code_spans(line: &str) -> Vec<Range<usize>> {
+    let bytes = line.as_bytes();
+    let run_at = |start: usize| bytes[start..].iter().take_while(|&&b| b == b'`').count();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let open = run_at(i);
+        let mut j = i + open;
+        let mut close = None;
+        while j < bytes.len() {
+            if bytes[j] != b'`' {
+                j += 1;
+                continue;
+            }
+            let run = run_at(j);
+            if run == open {
+                close = Some(j + run);
+                break;
+            }
+            j += run;
+        }
+        match close {
+            Some(end) => {
+                spans.push(i..end);
+                i = end;
+            }
+            // Unmatched: the run is text, and a later, different run may still open a span
+            None => i += open,
+        }
+    }
+    spans
+}
+
+/// The words of line as separated_words gives them, except that whitespace inside one of
+/// spans doesn't split a word: each word with the whitespace before it ("" before the first)
+pub(crate) fn span_words<'a>(line: &'a str, spans: &[Range<usize>]) -> Vec<(&'a str, &'a str)> {
+    let mut words = Vec::new();
+    let mut end = 0;
+    let mut position = 0;
+    while position < line.len() {
+        let rest = &line[position..];
+        let Some(skip) = rest.find(|c: char| !c.is_whitespace()) else {
+            break;
+        };
+        let start = position + skip;
+        let mut word_end = start;
+        loop {
+            match spans.iter().find(|span| span.contains(&word_end)) {
+                Some(span) => word_end = span.end,
+                None => match line[word_end..].chars().next() {
+                    Some(c) if !c.is_whitespace() => word_end += c.len_utf8(),
+                    _ => break,
+                },
+            }
+        }
+        words.push((&line[end..start], &line[start..word_end]));
+        end = word_end;
+        position = word_end;
+    }
+    words
+}
+
+/// Whether word, a slice of line, holds (part of) one of spans
+pub(crate) fn has_span(line: &str, word: &str, spans: &[Range<usize>]) -> bool {
+    let start = word.as_ptr() as usize - line.as_ptr() as usize;
+    let range = start..start + word.len();
+    spans
+        .iter()
+        .any(|span| span.start < range.end && range.start < span.end)
+}
+
+/// Character wrapping of a line with unbreakable spans: the text between them is placed as
+/// place_chars_with places it, and each span goes on the next line when it doesn't fit on
+/// this one, unless it is wider than a whole line
+pub(crate) fn wrap_span_chars_with<M: WidthModel, S: WrapSink>(
+    line: &str,
+    spans: &[Range<usize>],
+    max_width: f64,
+    model: &M,
+    out: &mut S,
+) {
+    let mut current_line_width = 0.0f64;
+    let mut position = 0;
+    for span in spans {
+        let before = &line[position..span.start];
+        place_chars_with(before, max_width, model, &mut current_line_width, out);
+
+        let code = &line[span.clone()];
+        let code_width: f64 = code.chars().map(|c| model.char_width(c)).sum();
+        if current_line_width > 0.0 && !fits_width(current_line_width + code_width, max_width) {
+            out.line_break();
+            current_line_width = 0.0;
+        }
+        if fits_width(code_width, max_width) {
+            out.push_text(code);
+            current_line_width += code_width;
+        } else {
+            place_chars_with(code, max_width, model, &mut current_line_width, out);
+        }
+        position = span.end;
+    }
+    place_chars_with(
+        &line[position..],
+        max_width,
+        model,
+        &mut current_line_width,
+        out,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{JustifyOptions, WarningCode};
+
+    /// Markdown inline code spans as algorithm versions 4 and 5 wrap them: one that fits, one
+    /// moved to the next line (with spaces and a dash inside, and on a CJK line), ones wider than
+    /// the line, a double-backtick span holding a literal backtick and an unmatched backtick
+    const CODE_SPAN_EXAMPLES: [(&str, u32, &str, &str); 7] = [
+        ("call `f(x)` now", 20, "call `f(x)` now", "call `f(x)` now"),
+        (
+            "use `a b c` please",
+            10,
+            "use `a b\r\nc` please",
+            "use\r\n`a b c`\r\nplease",
+        ),
+        (
+            "see `a/very/long-path/name` end",
+            10,
+            "see `a/\r\nvery/long-\r\npath/name`\r\nend",
+            "see\r\n`a/very/long-path/name`\r\nend",
+        ),
+        ("一二三`code`", 8, "一二三`c\r\node`", "一二三\r\n`code`"),
+        (
+            "汉字`abcdefghijkl`汉字",
+            8,
+            "汉字`abc\r\ndefghijk\r\nl`汉字",
+            "汉字\r\n`abcdefg\r\nhijkl`汉\r\n字",
+        ),
+        (
+            "run ``a ` b`` ok",
+            8,
+            "run ``a\r\n` b`` ok",
+            "run\r\n``a ` b``\r\nok",
+        ),
+        (
+            "trailing tick `",
+            8,
+            "trailing\r\ntick `",
+            "trailing\r\ntick `",
+        ),
+    ];
+
+    fn with_version(version: u32, width: u32) -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line: width,
+            algorithm_version: version,
+            markdown: true,
+            ..JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn v5_keeps_spans_whole_where_v4_breaks_them() {
+        for (text, width, v4, v5) in CODE_SPAN_EXAMPLES {
+            assert_eq!(with_version(4, width).justify(text), v4, "{text:?} in V4");
+            assert_eq!(with_version(5, width).justify(text), v5, "{text:?} in V5");
+        }
+    }
+
+    #[test]
+    fn a_span_wider_than_the_line_overflows() {
+        let overflow = with_version(5, 10).warnings(CODE_SPAN_EXAMPLES[2].0);
+        assert_eq!(
+            overflow.iter().map(|w| w.code).collect::<Vec<_>>(),
+            [WarningCode::TokenOverflow]
+        );
+        assert!(with_version(5, 10)
+            .warnings(CODE_SPAN_EXAMPLES[1].0)
+            .is_empty());
+    }
+}
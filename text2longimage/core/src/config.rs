@@ -0,0 +1,277 @@
+//! Process-wide settings applied together from one JSON object
+//! Everything is parsed and validated before any of it is applied, so a bad value leaves the
+//! previous settings untouched; a field left out is reset to its default, not kept.
+//! The settings are global to one instance of the module: each Web Worker that instantiates
+//! it has its own, and within one they apply to every call that doesn't say otherwise. A call
+//! needn't depend on them: an options object's maxOutputRatio and strictOptions override the
+//! module's for that call, and a Document keeps the line heights it was built with. The ones
+//! nothing overrides are the size limits (maxInputSize, maxBatchSize, maxBatchItems), the batch
+//! cache, chunk
+//! size calibration, the font metrics of justify_text_measured, the log level and the panic
+//! hook; the cache and calibration change how fast a call is, never what it returns.
+//! RuntimeConfig::current reads them all back.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache_capacity, chunk_calibration, cjk_line_height_factor, emoji_line_height_factor,
+    max_batch_items, max_batch_size, max_input_size, max_output_ratio, set_cache_capacity,
+    set_chunk_calibration, set_cjk_line_height_factor, set_emoji_line_height_factor,
+    set_max_batch_items, set_max_batch_size, set_max_input_size, set_max_output_ratio,
+    set_strict_options, strict_options, FontMetrics, ScriptLineHeights, WebtoolsError,
+    DEFAULT_CACHE_CAPACITY, DEFAULT_CJK_LINE_HEIGHT_FACTOR, DEFAULT_EMOJI_LINE_HEIGHT_FACTOR,
+    DEFAULT_MAX_BATCH_ITEMS, DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_INPUT_SIZE,
+    DEFAULT_MAX_OUTPUT_RATIO,
+};
+
+/// Highest console log level (trace)
+const MAX_LOG_LEVEL: u32 = 3;
+
+/// Settings given to configure, and the effective settings it reports back
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Forward panics to console.error (only builds with the panic-hook feature can)
+    pub panic_hook: bool,
+    /// 0 off, 1 error, 2 info, 3 trace
+    pub log_level: u32,
+    pub max_input_size: usize,
+    pub max_batch_size: usize,
+    /// Most items in a batch (see set_max_batch_items)
+    pub max_batch_items: usize,
+    /// Most output bytes per input byte before wrapping stops (see set_max_output_ratio);
+    /// 0 for no limit
+    pub max_output_ratio: u32,
+    pub cache_capacity: usize,
+    /// Font metrics in the shape FontMetrics::from_json takes; null for the default widths
+    pub font_metrics: Option<serde_json::Value>,
+    /// Reject options objects with unknown fields (see set_strict_options)
+    pub strict_options: bool,
+    /// Measure what wrapping costs the first time a chunk size is picked (see
+    /// calibrate_chunk_cost); false keeps DEFAULT_WRAP_NS_PER_BYTE
+    pub calibrate_chunk_size: bool,
+    /// heightFactor of layout lines with CJK (see ScriptLineHeights)
+    pub cjk_line_height_factor: f64,
+    /// heightFactor of layout lines with emoji
+    pub emoji_line_height_factor: f64,
+    #[serde(skip)]
+    metrics: FontMetrics,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            panic_hook: true,
+            log_level: 0,
+            max_input_size: DEFAULT_MAX_INPUT_SIZE,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_batch_items: DEFAULT_MAX_BATCH_ITEMS,
+            max_output_ratio: DEFAULT_MAX_OUTPUT_RATIO,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            font_metrics: None,
+            strict_options: false,
+            calibrate_chunk_size: true,
+            cjk_line_height_factor: DEFAULT_CJK_LINE_HEIGHT_FACTOR,
+            emoji_line_height_factor: DEFAULT_EMOJI_LINE_HEIGHT_FACTOR,
+            metrics: FontMetrics::default(),
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Parse and validate a configuration, font metrics included; an empty string is the defaults
+    pub fn from_json(config_json: &str) -> Result<RuntimeConfig, WebtoolsError> {
+        if config_json.trim().is_empty() {
+            return Ok(RuntimeConfig::default());
+        }
+        let mut config: RuntimeConfig =
+            serde_json::from_str(config_json).map_err(WebtoolsError::invalid_json)?;
+
+        if config.log_level > MAX_LOG_LEVEL {
+            return Err(WebtoolsError::invalid_argument(format!(
+                "logLevel must be 0 to {}, got {}",
+                MAX_LOG_LEVEL, config.log_level
+            )));
+        }
+        if config.max_input_size == 0 {
+            return Err(WebtoolsError::invalid_argument("maxInputSize can't be 0"));
+        }
+        if config.max_batch_size == 0 {
+            return Err(WebtoolsError::invalid_argument("maxBatchSize can't be 0"));
+        }
+        if config.max_batch_items == 0 {
+            return Err(WebtoolsError::invalid_argument("maxBatchItems can't be 0"));
+        }
+        ScriptLineHeights::checked(
+            config.cjk_line_height_factor,
+            config.emoji_line_height_factor,
+        )?;
+        if let Some(metrics) = &config.font_metrics {
+            config.metrics = FontMetrics::from_json(&metrics.to_string())?;
+        }
+        Ok(config)
+    }
+
+    /// Apply the settings kept in this crate: the size limits, the batch cache capacity,
+    /// strict options, chunk size calibration and the line height factors
+    pub fn apply_limits(&self) {
+        set_max_input_size(self.max_input_size);
+        set_max_batch_size(self.max_batch_size);
+        set_max_batch_items(self.max_batch_items);
+        set_max_output_ratio(self.max_output_ratio);
+        set_cache_capacity(self.cache_capacity);
+        set_strict_options(self.strict_options);
+        set_chunk_calibration(self.calibrate_chunk_size);
+        set_cjk_line_height_factor(self.cjk_line_height_factor);
+        set_emoji_line_height_factor(self.emoji_line_height_factor);
+    }
+
+    /// The settings in effect: those kept in this crate as currently set, and the panic hook,
+    /// log level and font metrics, which the caller keeps, as given
+    pub fn current(panic_hook: bool, log_level: u32, metrics: &FontMetrics) -> RuntimeConfig {
+        RuntimeConfig {
+            panic_hook,
+            log_level,
+            max_input_size: max_input_size(),
+            max_batch_size: max_batch_size(),
+            max_batch_items: max_batch_items(),
+            max_output_ratio: max_output_ratio(),
+            cache_capacity: cache_capacity(),
+            font_metrics: metrics.to_json_value(),
+            strict_options: strict_options(),
+            calibrate_chunk_size: chunk_calibration(),
+            cjk_line_height_factor: cjk_line_height_factor(),
+            emoji_line_height_factor: emoji_line_height_factor(),
+            metrics: FontMetrics::default(),
+        }
+    }
+
+    /// The parsed font metrics, leaving the defaults in their place
+    pub fn take_font_metrics(&mut self) -> FontMetrics {
+        std::mem::take(&mut self.metrics)
+    }
+
+    /// The configuration as JSON, in the shape from_json accepts
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::lock_limits;
+
+    fn current() -> RuntimeConfig {
+        RuntimeConfig::current(true, 0, &FontMetrics::default())
+    }
+
+    #[test]
+    fn nothing_given_is_the_defaults() {
+        let defaults = RuntimeConfig::default().to_json();
+        for config_json in ["", "  ", "{}"] {
+            assert_eq!(
+                RuntimeConfig::from_json(config_json).unwrap().to_json(),
+                defaults
+            );
+        }
+        assert_eq!(
+            defaults,
+            r#"{"panicHook":true,"logLevel":0,"maxInputSize":500000,"maxBatchSize":5000000,"maxBatchItems":100000,"maxOutputRatio":10,"cacheCapacity":256,"fontMetrics":null,"strictOptions":false,"calibrateChunkSize":true,"cjkLineHeightFactor":1.15,"emojiLineHeightFactor":1.25}"#
+        );
+    }
+
+    #[test]
+    fn each_invalid_setting_is_an_error() {
+        for (config_json, code) in [
+            (r#"{"logLevel": 4}"#, "INVALID_ARGUMENT"),
+            (r#"{"maxInputSize": 0}"#, "INVALID_ARGUMENT"),
+            (r#"{"maxBatchSize": 0}"#, "INVALID_ARGUMENT"),
+            (r#"{"maxBatchItems": 0}"#, "INVALID_ARGUMENT"),
+            (r#"{"cjkLineHeightFactor": 0}"#, "INVALID_ARGUMENT"),
+            (
+                r#"{"fontMetrics": {"chars": {"i": -1}}}"#,
+                "INVALID_ARGUMENT",
+            ),
+            (r#"{"logLevel": "x"}"#, "INVALID_JSON"),
+            (r#"{"maxInputSize": 1000000, "bogus": 1}"#, "INVALID_JSON"),
+            ("not json", "INVALID_JSON"),
+        ] {
+            let error = RuntimeConfig::from_json(config_json).err();
+            assert_eq!(error.map(|e| e.code()), Some(code), "{}", config_json);
+        }
+    }
+
+    #[test]
+    fn an_invalid_setting_leaves_the_previous_ones_in_place() {
+        let _limits = lock_limits();
+        // Raised rather than lowered, so tests not holding the lock still pass meanwhile
+        let raised = r#"{"maxInputSize": 1000000, "maxBatchSize": 10000000}"#;
+        RuntimeConfig::from_json(raised).unwrap().apply_limits();
+        let before = current().to_json();
+        // The valid maxInputSize alongside the invalid logLevel isn't applied either
+        let invalid = r#"{"maxInputSize": 2000000, "logLevel": 9}"#;
+        assert!(RuntimeConfig::from_json(invalid).is_err());
+        assert_eq!(current().to_json(), before);
+        assert_eq!(max_input_size(), 1_000_000);
+
+        // A later configuration replaces the whole of the earlier one
+        RuntimeConfig::from_json(r#"{"maxInputSize": 2000000}"#)
+            .unwrap()
+            .apply_limits();
+        assert_eq!(max_input_size(), 2_000_000);
+        assert_eq!(max_batch_size(), DEFAULT_MAX_BATCH_SIZE);
+
+        RuntimeConfig::default().apply_limits();
+        assert_eq!(current().to_json(), RuntimeConfig::default().to_json());
+    }
+
+    #[test]
+    fn the_effective_configuration_reads_back_and_applies_unchanged() {
+        let _limits = lock_limits();
+        let given =
+            r#"{"logLevel": 2, "maxInputSize": 1000000, "fontMetrics": {"chars": {"i": 0.3}}}"#;
+        let mut config = RuntimeConfig::from_json(given).unwrap();
+        config.apply_limits();
+        let metrics = config.take_font_metrics();
+        assert_eq!(
+            metrics.to_json_value(),
+            Some(
+                serde_json::json!({"chars": {"i": 0.3}, "narrow": 0.5, "ranges": [], "wide": 1.0})
+            )
+        );
+        let effective = RuntimeConfig::current(true, 2, &metrics).to_json();
+
+        // Applying the configuration read back changes nothing
+        let mut again = RuntimeConfig::from_json(&effective).unwrap();
+        again.apply_limits();
+        let metrics = again.take_font_metrics();
+        assert_eq!(
+            RuntimeConfig::current(true, 2, &metrics).to_json(),
+            effective
+        );
+
+        RuntimeConfig::default().apply_limits();
+    }
+
+    #[test]
+    fn every_limit_reads_back_as_applied() {
+        let _limits = lock_limits();
+        // The cache, strict options and line heights are left alone: tests not holding the
+        // lock depend on them
+        let config_json = r#"{"maxInputSize":1000000,"maxBatchSize":10000000,"maxBatchItems":200000,"maxOutputRatio":0,"calibrateChunkSize":false}"#;
+        let config = RuntimeConfig::from_json(config_json).unwrap();
+        config.apply_limits();
+        assert_eq!(current().to_json(), config.to_json());
+        assert_eq!(
+            RuntimeConfig::from_json(&current().to_json())
+                .unwrap()
+                .to_json(),
+            config.to_json()
+        );
+
+        RuntimeConfig::default().apply_limits();
+        assert_eq!(current().to_json(), RuntimeConfig::default().to_json());
+    }
+}
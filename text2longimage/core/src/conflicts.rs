@@ -0,0 +1,307 @@
+//! Options that contradict each other, and what happens when both are set
+//! Every combination JustifyOptions knows to be nonsense is one row of OPTION_CONFLICTS: either
+//! it is rejected with a ConflictingOptions error naming both fields, or one field takes
+//! precedence and the other is ignored, which option_warnings reports.
+
+use serde::Serialize;
+
+use crate::visualize::symbol;
+use crate::{
+    JustifyOptions, ListMarkerSpacing, WebtoolsError, DEFAULT_BREAK_AFTER_CHARS,
+    DEFAULT_PUNCTUATION_BREAK_WINDOW, DEFAULT_SIGNATURE_DELIMITER,
+};
+
+/// What a conflict leads to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The options are rejected
+    Invalid,
+    /// `field` has no effect; `other` wins
+    Ignored,
+}
+
+/// One row of the precedence table
+pub struct OptionConflict {
+    /// The field the conflict is about (the ignored one, for ConflictResolution::Ignored)
+    pub field: &'static str,
+    pub other: &'static str,
+    pub resolution: ConflictResolution,
+    pub message: &'static str,
+    applies: fn(&JustifyOptions) -> bool,
+}
+
+/// Every conflict between options, in field order
+pub const OPTION_CONFLICTS: [OptionConflict; 11] = [
+    OptionConflict {
+        field: "numberStart",
+        other: "lineNumbers",
+        resolution: ConflictResolution::Ignored,
+        message: "numberStart has no effect without lineNumbers",
+        applies: |options| options.number_start != 1 && !options.line_numbers,
+    },
+    OptionConflict {
+        field: "markdown",
+        other: "trimTrailing",
+        resolution: ConflictResolution::Ignored,
+        message: "markdown only keeps hard breaks when trimTrailing is on; with trimTrailing \
+                  off all trailing whitespace is kept",
+        applies: |options| options.markdown && !options.trim_trailing,
+    },
+    OptionConflict {
+        field: "hardBreakMarker",
+        other: "softBreakMarker",
+        resolution: ConflictResolution::Invalid,
+        message: "hardBreakMarker and softBreakMarker must differ",
+        applies: |options| {
+            !options.hard_break_marker.is_empty()
+                && options.hard_break_marker == options.soft_break_marker
+        },
+    },
+    OptionConflict {
+        field: "softBreakMarker",
+        other: "hardBreakMarker",
+        resolution: ConflictResolution::Invalid,
+        message: "softBreakMarker can't contain hardBreakMarker: hard breaks are found first, \
+                  so every soft break marker would become a hard break",
+        applies: |options| {
+            !options.hard_break_marker.is_empty()
+                && options.soft_break_marker != options.hard_break_marker
+                && options
+                    .soft_break_marker
+                    .contains(&options.hard_break_marker)
+        },
+    },
+    OptionConflict {
+        field: "footerAlign",
+        other: "footerText",
+        resolution: ConflictResolution::Ignored,
+        message: "footerAlign and footerBlankLines have no effect without footerText",
+        applies: |options| {
+            options.footer_text.is_empty()
+                && (options.footer_align != JustifyOptions::default().footer_align
+                    || options.footer_blank_lines != JustifyOptions::default().footer_blank_lines)
+        },
+    },
+    OptionConflict {
+        field: "markSoftBreaks",
+        other: "visualizeWhitespace",
+        resolution: ConflictResolution::Invalid,
+        message: "visualizeWhitespace would replace the whitespace in markSoftBreaks, so \
+                  remove_soft_breaks couldn't find the marks",
+        applies: |options| {
+            options.visualize_whitespace
+                && options
+                    .mark_soft_breaks
+                    .chars()
+                    .any(|c| symbol(c).is_some())
+        },
+    },
+    OptionConflict {
+        field: "markSoftBreaks",
+        other: "escapeHtml",
+        resolution: ConflictResolution::Invalid,
+        message: "escapeHtml would write the & < > \" ' in markSoftBreaks as entities, so \
+                  remove_soft_breaks couldn't find the marks",
+        applies: |options| {
+            options.escape_html
+                && options
+                    .mark_soft_breaks
+                    .contains(['&', '<', '>', '"', '\''])
+        },
+    },
+    OptionConflict {
+        field: "punctuationBreakWindow",
+        other: "algorithmVersion",
+        resolution: ConflictResolution::Ignored,
+        message: "punctuationBreakWindow has no effect with algorithmVersion 1",
+        applies: |options| {
+            options.algorithm_version == 1
+                && options.punctuation_break_window != DEFAULT_PUNCTUATION_BREAK_WINDOW
+        },
+    },
+    OptionConflict {
+        field: "breakAfterChars",
+        other: "algorithmVersion",
+        resolution: ConflictResolution::Ignored,
+        message: "breakAfterChars has no effect before algorithmVersion 4",
+        applies: |options| {
+            options.algorithm_version < 4 && options.break_after_chars != DEFAULT_BREAK_AFTER_CHARS
+        },
+    },
+    OptionConflict {
+        field: "listMarkerSpacing",
+        other: "hangingIndent",
+        resolution: ConflictResolution::Ignored,
+        message: "listMarkerSpacing has no effect without hangingIndent",
+        applies: |options| {
+            !options.hanging_indent && options.list_marker_spacing != ListMarkerSpacing::Keep
+        },
+    },
+    OptionConflict {
+        field: "signatureDelimiter",
+        other: "algorithmVersion",
+        resolution: ConflictResolution::Ignored,
+        message: "signatureDelimiter has no effect before algorithmVersion 7",
+        applies: |options| {
+            options.algorithm_version < 7
+                && options.signature_delimiter != DEFAULT_SIGNATURE_DELIMITER
+        },
+    },
+];
+
+/// An option that was set but has no effect, and the one that overrides it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OptionWarning {
+    pub field: &'static str,
+    pub overridden_by: &'static str,
+    pub message: &'static str,
+}
+
+impl OptionConflict {
+    /// Whether these options have this conflict
+    pub fn applies_to(&self, options: &JustifyOptions) -> bool {
+        (self.applies)(options)
+    }
+}
+
+impl JustifyOptions {
+    /// A ConflictingOptions error for every invalid combination these options set
+    pub fn conflict_errors(&self) -> Vec<WebtoolsError> {
+        OPTION_CONFLICTS
+            .iter()
+            .filter(|conflict| conflict.resolution == ConflictResolution::Invalid)
+            .filter(|conflict| conflict.applies_to(self))
+            .map(|conflict| WebtoolsError::ConflictingOptions {
+                field: conflict.field,
+                other: conflict.other,
+                message: conflict.message,
+            })
+            .collect()
+    }
+
+    /// Every option these options set that another one overrides
+    pub fn option_warnings(&self) -> Vec<OptionWarning> {
+        OPTION_CONFLICTS
+            .iter()
+            .filter(|conflict| conflict.resolution == ConflictResolution::Ignored)
+            .filter(|conflict| conflict.applies_to(self))
+            .map(|conflict| OptionWarning {
+                field: conflict.field,
+                overridden_by: conflict.other,
+                message: conflict.message,
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    /// One options object per row of OPTION_CONFLICTS that has the conflict, and what the
+    /// options are without the ignored field
+    const CONFLICT_EXAMPLES: [(&str, &str, Option<&str>); 11] = [
+        (r#"{"numberStart": 5}"#, "numberStart", Some("{}")),
+        (
+            r#"{"markdown": true, "trimTrailing": false}"#,
+            "markdown",
+            Some(r#"{"trimTrailing": false}"#),
+        ),
+        (
+            r#"{"hardBreakMarker": "|", "softBreakMarker": "|"}"#,
+            "hardBreakMarker",
+            None,
+        ),
+        (
+            r#"{"hardBreakMarker": "|", "softBreakMarker": "||"}"#,
+            "softBreakMarker",
+            None,
+        ),
+        (
+            r#"{"footerAlign": "left", "footerBlankLines": 3}"#,
+            "footerAlign",
+            Some("{}"),
+        ),
+        (
+            r#"{"visualizeWhitespace": true, "markSoftBreaks": "\u200B"}"#,
+            "markSoftBreaks",
+            None,
+        ),
+        (
+            r#"{"escapeHtml": true, "markSoftBreaks": "<wbr>"}"#,
+            "markSoftBreaks",
+            None,
+        ),
+        (
+            r#"{"algorithmVersion": 1, "punctuationBreakWindow": 2}"#,
+            "punctuationBreakWindow",
+            Some(r#"{"algorithmVersion": 1}"#),
+        ),
+        (
+            r#"{"algorithmVersion": 3, "breakAfterChars": "-"}"#,
+            "breakAfterChars",
+            Some(r#"{"algorithmVersion": 3}"#),
+        ),
+        (
+            r#"{"listMarkerSpacing": "space"}"#,
+            "listMarkerSpacing",
+            Some("{}"),
+        ),
+        (
+            r#"{"algorithmVersion": 6, "signatureDelimiter": "--"}"#,
+            "signatureDelimiter",
+            Some(r#"{"algorithmVersion": 6}"#),
+        ),
+    ];
+
+    const TEXT: &str = "1. 中文 **bold** text  \n-- \nsig\nhttps://example.com/a,b";
+
+    #[test]
+    fn every_conflict_is_rejected_or_warned_about() {
+        assert_eq!(CONFLICT_EXAMPLES.len(), OPTION_CONFLICTS.len());
+        for (conflict, &(options_json, field, resolved_json)) in
+            OPTION_CONFLICTS.iter().zip(&CONFLICT_EXAMPLES)
+        {
+            assert_eq!(conflict.field, field, "CONFLICT_EXAMPLES is out of order");
+            let parsed = JustifyOptions::from_json(options_json);
+            match (conflict.resolution, resolved_json) {
+                (ConflictResolution::Invalid, None) => match parsed {
+                    Err(WebtoolsError::ConflictingOptions { field, other, .. }) => {
+                        assert_eq!((field, other), (conflict.field, conflict.other));
+                    }
+                    other => panic!("{options_json} gave {other:?}"),
+                },
+                (ConflictResolution::Ignored, Some(resolved_json)) => {
+                    let options = parsed.unwrap();
+                    let resolved = JustifyOptions::from_json(resolved_json).unwrap();
+                    assert_eq!(
+                        options.justify(TEXT),
+                        resolved.justify(TEXT),
+                        "{options_json}"
+                    );
+                    assert_eq!(
+                        options.option_warnings(),
+                        [OptionWarning {
+                            field: conflict.field,
+                            overridden_by: conflict.other,
+                            message: conflict.message,
+                        }],
+                        "{options_json}"
+                    );
+                    assert!(options.conflict_errors().is_empty(), "{options_json}");
+                    assert!(resolved.option_warnings().is_empty(), "{resolved_json}");
+                }
+                _ => panic!("{field} is {:?} in OPTION_CONFLICTS", conflict.resolution),
+            }
+        }
+    }
+
+    #[test]
+    fn default_options_have_no_conflicts() {
+        let options = JustifyOptions::default();
+        assert!(options.conflict_errors().is_empty());
+        assert!(options.option_warnings().is_empty());
+    }
+}
@@ -0,0 +1,663 @@
+//! Characters that look like ones from another script, for proofreading OCR'd and pasted text
+//! A fullwidth ０ in "2０24", a Cyrillic а in "dаta", 〇 among ASCII digits or a katakana カ
+//! between kanji renders like the character meant, so the text reads right and searches wrong.
+//! confusables_report finds them where two scripts meet in one word (or, for kana and kanji,
+//! between two neighbours) and suggests the character of the word's own script;
+//! apply_confusable_fixes rewrites them. The table is small and deliberate: only pairs that are
+//! hard to tell apart in common fonts, so a legitimately mixed word like "Ω-3" or "第3回" isn't
+//! reported.
+
+use serde::Serialize;
+
+use crate::capabilities::{expected_values, find_value};
+use crate::{OptionValue, WebtoolsError};
+
+/// Cyrillic letters and the Latin letters they pass for
+const CYRILLIC_LOOKALIKES: &[(char, char)] = &[
+    ('а', 'a'),
+    ('е', 'e'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ѕ', 's'),
+    ('ԁ', 'd'),
+    ('һ', 'h'),
+    ('А', 'A'),
+    ('В', 'B'),
+    ('Е', 'E'),
+    ('К', 'K'),
+    ('М', 'M'),
+    ('Н', 'H'),
+    ('О', 'O'),
+    ('Р', 'P'),
+    ('С', 'C'),
+    ('Т', 'T'),
+    ('У', 'Y'),
+    ('Х', 'X'),
+    ('І', 'I'),
+    ('Ј', 'J'),
+    ('Ѕ', 'S'),
+];
+
+/// Greek letters and the Latin letters they pass for
+const GREEK_LOOKALIKES: &[(char, char)] = &[
+    ('Α', 'A'),
+    ('Β', 'B'),
+    ('Ε', 'E'),
+    ('Ζ', 'Z'),
+    ('Η', 'H'),
+    ('Ι', 'I'),
+    ('Κ', 'K'),
+    ('Μ', 'M'),
+    ('Ν', 'N'),
+    ('Ο', 'O'),
+    ('Ρ', 'P'),
+    ('Τ', 'T'),
+    ('Υ', 'Y'),
+    ('Χ', 'X'),
+    ('ο', 'o'),
+    ('ν', 'v'),
+];
+
+/// Katakana and the kanji they pass for; ヘ and へ are handled on their own
+const KANA_KANJI_LOOKALIKES: &[(char, char)] = &[
+    ('カ', '力'),
+    ('エ', '工'),
+    ('ロ', '口'),
+    ('ニ', '二'),
+    ('ハ', '八'),
+    ('タ', '夕'),
+    ('ト', '卜'),
+    ('ー', '一'),
+];
+
+/// CJK numerals a 〇 is written among; a 0 or O among them is taken for one
+const CJK_NUMERALS: &str = "〇一二三四五六七八九十百千万";
+
+/// Which way apply_confusable_fixes rewrites a word where scripts meet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfusablePolicy {
+    /// ASCII letters and digits: fullwidth ０ and 〇 become 0
+    LatinPreferred,
+    /// CJK forms: 0 next to fullwidth digits becomes ０, and next to CJK numerals 〇
+    CjkPreferred,
+}
+
+impl ConfusablePolicy {
+    /// Parse "latin-preferred" / "cjk-preferred" in any case
+    pub fn parse(policy: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| policy.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown confusable policy: {} (expected {})",
+                policy,
+                expected_values::<ConfusablePolicy>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for ConfusablePolicy {
+    const KIND: &'static str = "confusablePolicy";
+    const USED_BY: &'static [&'static str] = &["apply_confusable_fixes"];
+    const ALL: &'static [Self] = &[
+        ConfusablePolicy::LatinPreferred,
+        ConfusablePolicy::CjkPreferred,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ConfusablePolicy::LatinPreferred => "latin-preferred",
+            ConfusablePolicy::CjkPreferred => "cjk-preferred",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ConfusablePolicy::LatinPreferred => {
+                "Fullwidth letters and digits and 〇 become ASCII where they meet ASCII ones"
+            }
+            ConfusablePolicy::CjkPreferred => {
+                "ASCII letters and digits become fullwidth, or 〇, where they meet CJK ones"
+            }
+        }
+    }
+}
+
+/// What two characters of a ConfusableGroup are mistaken for each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfusableClass {
+    /// Fullwidth letters or digits among ASCII ones, or the other way round
+    Fullwidth,
+    /// Cyrillic or Greek letters in a Latin word, or Latin letters in a Cyrillic or Greek one
+    CyrillicGreek,
+    /// 〇 among ASCII digits or letters, or 0 and O among CJK numerals
+    IdeographicZero,
+    /// Katakana between kanji, kanji between katakana, or ヘ and へ in the other kana
+    KanaHan,
+}
+
+/// Script of a ConfusableChar
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ConfusableScript {
+    /// ASCII letters and digits
+    Ascii,
+    /// Fullwidth letters and digits, U+FF10–U+FF5A
+    Fullwidth,
+    Cyrillic,
+    Greek,
+    /// Kanji and CJK numerals, 〇 included
+    Han,
+    Hiragana,
+    Katakana,
+}
+
+/// A character that is probably meant as another one
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ConfusableChar {
+    /// Char offset in the text
+    pub char_index: usize,
+    pub char: char,
+    pub script: ConfusableScript,
+    /// The look-alike in the script of its word or neighbours
+    pub suggestion: char,
+}
+
+/// The characters of one word (or, for kana-han, one character and its neighbours) that
+/// look like another script's
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ConfusableGroup {
+    pub class: ConfusableClass,
+    /// Char offset of the word in the text
+    pub start: usize,
+    pub word: String,
+    pub chars: Vec<ConfusableChar>,
+}
+
+/// Every ConfusableGroup of a text, in text order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ConfusablesReport {
+    /// Characters reported, over all groups
+    pub count: usize,
+    pub groups: Vec<ConfusableGroup>,
+}
+
+/// A character of a group with what each policy writes for it
+struct Member {
+    char_index: usize,
+    c: char,
+    script: ConfusableScript,
+    /// Reported, i.e. on the minority side of its word
+    flagged: bool,
+    suggestion: char,
+    /// What latin-preferred and cjk-preferred write; the suggestion for classes without a
+    /// Latin and a CJK side
+    latin: char,
+    cjk: char,
+}
+
+/// A group with the members a fix may touch, both sides of the word
+struct Finding {
+    class: ConfusableClass,
+    start: usize,
+    end: usize,
+    members: Vec<Member>,
+}
+
+/// Fullwidth form of an ASCII letter or digit, or ASCII form of a fullwidth one
+fn fullwidth_pair(c: char) -> Option<(char, char)> {
+    match c {
+        '0'..='9' | 'A'..='Z' | 'a'..='z' => Some((c, char::from_u32(c as u32 + 0xFEE0)?)),
+        '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+            Some((char::from_u32(c as u32 - 0xFEE0)?, c))
+        }
+        _ => None,
+    }
+}
+
+fn is_latin_letter(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}') && c.is_alphabetic()
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{052F}')
+}
+
+fn is_greek(c: char) -> bool {
+    matches!(c, '\u{0370}'..='\u{03FF}')
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '〇')
+}
+
+fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}')
+}
+
+fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A0}'..='\u{30FF}')
+}
+
+/// Characters of the words fullwidth, cyrillic-greek and ideographic-zero look at: letters
+/// and digits of the scripts they compare, and CJK numerals. Other kanji, kana, spaces and
+/// punctuation end a word, so "２０２４年3月" is two words, not one mixed one.
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || fullwidth_pair(c).is_some()
+        || is_latin_letter(c)
+        || (is_cyrillic(c) || is_greek(c)) && c.is_alphabetic()
+        || CJK_NUMERALS.contains(c)
+}
+
+/// Words of chars, as (start, end) char offsets
+fn words(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        if !is_word_char(chars[start]) {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < chars.len() && is_word_char(chars[end]) {
+            end += 1;
+        }
+        words.push((start, end));
+        start = end;
+    }
+    words
+}
+
+/// Fullwidth letters and digits mixed with ASCII ones in a word
+/// The side with fewer characters is reported, fullwidth on a tie.
+fn fullwidth_finding(chars: &[char], (start, end): (usize, usize)) -> Option<Finding> {
+    let word = &chars[start..end];
+    let ascii = word.iter().filter(|c| c.is_ascii_alphanumeric()).count();
+    let wide = word
+        .iter()
+        .filter(|&&c| fullwidth_pair(c).is_some() && !c.is_ascii())
+        .count();
+    if ascii == 0 || wide == 0 {
+        return None;
+    }
+    let flag_ascii = ascii < wide;
+    let members = (start..end)
+        .filter_map(|index| {
+            let c = chars[index];
+            let (latin, cjk) = fullwidth_pair(c)?;
+            let is_ascii = c.is_ascii();
+            Some(Member {
+                char_index: index,
+                c,
+                script: if is_ascii {
+                    ConfusableScript::Ascii
+                } else {
+                    ConfusableScript::Fullwidth
+                },
+                flagged: is_ascii == flag_ascii,
+                suggestion: if is_ascii { cjk } else { latin },
+                latin,
+                cjk,
+            })
+        })
+        .collect();
+    Some(Finding {
+        class: ConfusableClass::Fullwidth,
+        start,
+        end,
+        members,
+    })
+}
+
+/// Look-alikes of one script in a word of another
+/// Latin letters are weighed against Cyrillic and Greek ones together; the script with fewer
+/// letters is reported and its look-alikes get the majority script's letter. A minority
+/// letter without a look-alike, like the ж in a Latin word, isn't reported.
+fn cyrillic_greek_finding(chars: &[char], (start, end): (usize, usize)) -> Option<Finding> {
+    let word = &chars[start..end];
+    let latin = word.iter().filter(|&&c| is_latin_letter(c)).count();
+    let cyrillic = word
+        .iter()
+        .filter(|&&c| is_cyrillic(c) && c.is_alphabetic())
+        .count();
+    let greek = word
+        .iter()
+        .filter(|&&c| is_greek(c) && c.is_alphabetic())
+        .count();
+    if latin == 0 || cyrillic + greek == 0 {
+        return None;
+    }
+    let flag_latin = latin < cyrillic + greek;
+    // The foreign table to map Latin letters into, when they are the minority
+    let foreign = if cyrillic >= greek {
+        CYRILLIC_LOOKALIKES
+    } else {
+        GREEK_LOOKALIKES
+    };
+    let members: Vec<Member> = (start..end)
+        .filter_map(|index| {
+            let c = chars[index];
+            let (script, suggestion) = if flag_latin {
+                let &(other, _) = foreign.iter().find(|&&(_, l)| l == c)?;
+                (ConfusableScript::Ascii, other)
+            } else {
+                let table = if is_cyrillic(c) {
+                    CYRILLIC_LOOKALIKES
+                } else {
+                    GREEK_LOOKALIKES
+                };
+                let &(_, l) = table.iter().find(|&&(other, _)| other == c)?;
+                let script = if is_cyrillic(c) {
+                    ConfusableScript::Cyrillic
+                } else {
+                    ConfusableScript::Greek
+                };
+                (script, l)
+            };
+            Some(Member {
+                char_index: index,
+                c,
+                script,
+                flagged: true,
+                suggestion,
+                latin: suggestion,
+                cjk: suggestion,
+            })
+        })
+        .collect();
+    (!members.is_empty()).then_some(Finding {
+        class: ConfusableClass::CyrillicGreek,
+        start,
+        end,
+        members,
+    })
+}
+
+/// 〇 in a word of ASCII letters or digits, or 0, O and o in a word of CJK numerals
+/// ASCII letters and digits are weighed against the CJK numerals; the side with fewer is
+/// reported, 〇 on a tie. 〇 passes for 0 next to a digit and for O otherwise.
+fn ideographic_zero_finding(chars: &[char], (start, end): (usize, usize)) -> Option<Finding> {
+    let word = &chars[start..end];
+    let ascii = word.iter().filter(|c| c.is_ascii_alphanumeric()).count();
+    let numerals = word.iter().filter(|&&c| CJK_NUMERALS.contains(c)).count();
+    let zeros = word.iter().filter(|&&c| c == '〇').count();
+    let ascii_zeros = word
+        .iter()
+        .filter(|&&c| matches!(c, '0' | 'O' | 'o'))
+        .count();
+    if ascii == 0 || numerals == 0 || zeros + ascii_zeros == 0 {
+        return None;
+    }
+    let flag_ascii = ascii < numerals;
+    let members: Vec<Member> = (start..end)
+        .filter_map(|index| {
+            let c = chars[index];
+            let member = match c {
+                '〇' => {
+                    let digit_next = |i: Option<usize>| {
+                        i.and_then(|i| word.get(i.checked_sub(start)?))
+                            .is_some_and(char::is_ascii_digit)
+                    };
+                    let latin = if digit_next(index.checked_sub(1)) || digit_next(Some(index + 1)) {
+                        '0'
+                    } else {
+                        'O'
+                    };
+                    Member {
+                        char_index: index,
+                        c,
+                        script: ConfusableScript::Han,
+                        flagged: !flag_ascii,
+                        suggestion: latin,
+                        latin,
+                        cjk: c,
+                    }
+                }
+                '0' | 'O' | 'o' => Member {
+                    char_index: index,
+                    c,
+                    script: ConfusableScript::Ascii,
+                    flagged: flag_ascii,
+                    suggestion: '〇',
+                    latin: c,
+                    cjk: '〇',
+                },
+                _ => return None,
+            };
+            Some(member)
+        })
+        .collect();
+    members
+        .iter()
+        .any(|member| member.flagged)
+        .then_some(Finding {
+            class: ConfusableClass::IdeographicZero,
+            start,
+            end,
+            members,
+        })
+}
+
+/// Kana, hiragana and kanji that can't be told apart
+fn is_kana_or_kanji(c: char) -> bool {
+    is_kanji(c) || is_hiragana(c) || is_katakana(c)
+}
+
+/// The look-alike c is probably meant as, from its neighbours, with c's script
+/// Katakana passes for kanji between two kanji, and kanji for katakana between two katakana,
+/// so "ブランド力" and "エネルギー源" are fine, and so is the counter in "3カ所". The particle
+/// へ sits between kanji ("東京へ行く"), so a katakana ヘ with no katakana next to it is
+/// taken for it, and a へ for ヘ only when katakana follows and no kana or kanji precedes.
+fn kana_han_suggestion(
+    previous: Option<char>,
+    c: char,
+    next: Option<char>,
+) -> Option<(ConfusableScript, char)> {
+    let both = |test: fn(char) -> bool| previous.is_some_and(test) && next.is_some_and(test);
+    match c {
+        'ヘ' => {
+            let neighbours = [previous, next]
+                .into_iter()
+                .flatten()
+                .filter(|&n| is_kana_or_kanji(n));
+            let mut neighbours = neighbours.peekable();
+            (neighbours.peek().is_some() && neighbours.all(|n| !is_katakana(n)))
+                .then_some((ConfusableScript::Katakana, 'へ'))
+        }
+        'へ' => (next.is_some_and(is_katakana) && !previous.is_some_and(is_kana_or_kanji))
+            .then_some((ConfusableScript::Hiragana, 'ヘ')),
+        _ => KANA_KANJI_LOOKALIKES.iter().find_map(|&(kana, kanji)| {
+            if c == kana && both(is_kanji) {
+                Some((ConfusableScript::Katakana, kanji))
+            } else if c == kanji && both(is_katakana) {
+                Some((ConfusableScript::Han, kana))
+            } else {
+                None
+            }
+        }),
+    }
+}
+
+/// A kana-han finding for every character kana_han_suggestion takes for another
+fn kana_han_findings(chars: &[char]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, &c) in chars.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(index + 1).copied();
+        let Some((script, suggestion)) = kana_han_suggestion(previous, c, next) else {
+            continue;
+        };
+        findings.push(Finding {
+            class: ConfusableClass::KanaHan,
+            start: index.saturating_sub(1),
+            end: (index + 2).min(chars.len()),
+            members: vec![Member {
+                char_index: index,
+                c,
+                script,
+                flagged: true,
+                suggestion,
+                latin: suggestion,
+                cjk: suggestion,
+            }],
+        });
+    }
+    findings
+}
+
+/// Every finding of text, ordered by start
+fn findings(chars: &[char]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for word in words(chars) {
+        findings.extend(fullwidth_finding(chars, word));
+        findings.extend(cyrillic_greek_finding(chars, word));
+        findings.extend(ideographic_zero_finding(chars, word));
+    }
+    findings.extend(kana_han_findings(chars));
+    findings.sort_by_key(|finding| finding.start);
+    findings
+}
+
+/// Characters of text that look like another script's, grouped by word and class
+pub fn find_confusables(text: &str) -> ConfusablesReport {
+    let chars: Vec<char> = text.chars().collect();
+    let groups: Vec<ConfusableGroup> = findings(&chars)
+        .into_iter()
+        .map(|finding| ConfusableGroup {
+            class: finding.class,
+            start: finding.start,
+            word: chars[finding.start..finding.end].iter().collect(),
+            chars: finding
+                .members
+                .into_iter()
+                .filter(|member| member.flagged)
+                .map(|member| ConfusableChar {
+                    char_index: member.char_index,
+                    char: member.c,
+                    script: member.script,
+                    suggestion: member.suggestion,
+                })
+                .collect(),
+        })
+        .collect();
+    ConfusablesReport {
+        count: groups.iter().map(|group| group.chars.len()).sum(),
+        groups,
+    }
+}
+
+#[cfg(feature = "json")]
+/// find_confusables as JSON {count, groups: [{class, start, word, chars: [{charIndex, char,
+/// script, suggestion}]}]}
+pub fn confusables_report(text: &str) -> String {
+    serde_json::to_string(&find_confusables(text)).unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Rewrite what find_confusables finds
+/// Where fullwidth or 〇 meets ASCII, latin-preferred writes the whole word in ASCII and
+/// cjk-preferred in fullwidth forms or 〇, whichever side find_confusables reported. Cyrillic,
+/// Greek and kana look-alikes take their suggestion under either policy. Every replacement is
+/// one character for one, so char offsets stay valid.
+pub fn apply_confusable_fixes_with(text: &str, policy: ConfusablePolicy) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    let fixes: Vec<(usize, char)> = findings(&chars)
+        .into_iter()
+        .flat_map(|finding| finding.members)
+        .map(|member| {
+            let to = match policy {
+                ConfusablePolicy::LatinPreferred => member.latin,
+                ConfusablePolicy::CjkPreferred => member.cjk,
+            };
+            (member.char_index, to)
+        })
+        .collect();
+    if fixes.is_empty() {
+        return text.to_string();
+    }
+    for (index, to) in fixes {
+        chars[index] = to;
+    }
+    chars.into_iter().collect()
+}
+
+/// Rewrite confusables with policy "latin-preferred" or "cjk-preferred"; see
+/// apply_confusable_fixes_with
+pub fn apply_confusable_fixes(text: &str, policy: &str) -> Result<String, WebtoolsError> {
+    let policy = ConfusablePolicy::parse(policy)?;
+    Ok(apply_confusable_fixes_with(text, policy))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// Lines as OCR gives them back: one of each confusable class
+    const OCR_SCAN: &str = "Invoice Nо. 2０24-117, dated 二0二四年三月, for Cоmpany dаta.\n\
+                            Total: 1〇5 USD.\n\
+                            ア力ウント登録は第ー回の説明会で。東京ヘ行く。";
+
+    /// (char index, character, suggestion) of everything find_confusables reports in OCR_SCAN
+    const OCR_SCAN_CONFUSABLES: [(usize, char, char); 9] = [
+        (9, 'о', 'o'),
+        (13, '０', '0'),
+        (29, '0', '〇'),
+        (42, 'о', 'o'),
+        (50, 'а', 'a'),
+        (63, '〇', '0'),
+        (72, '力', 'カ'),
+        (80, 'ー', '一'),
+        (90, 'ヘ', 'へ'),
+    ];
+
+    #[test]
+    fn an_ocr_scan_has_one_of_each_class() {
+        let report = find_confusables(OCR_SCAN);
+        let found: Vec<(usize, char, char)> = report
+            .groups
+            .iter()
+            .flat_map(|group| &group.chars)
+            .map(|c| (c.char_index, c.char, c.suggestion))
+            .collect();
+        assert_eq!(found, OCR_SCAN_CONFUSABLES);
+        let classes: HashSet<ConfusableClass> =
+            report.groups.iter().map(|group| group.class).collect();
+        assert_eq!(classes.len(), 4);
+    }
+
+    #[test]
+    fn each_policy_fixes_the_scan_its_way() {
+        assert_eq!(
+            apply_confusable_fixes(OCR_SCAN, "latin-preferred").as_deref(),
+            Ok(
+                "Invoice No. 2024-117, dated 二0二四年三月, for Company data.\n\
+                Total: 105 USD.\n\
+                アカウント登録は第一回の説明会で。東京へ行く。"
+            )
+        );
+        assert_eq!(
+            apply_confusable_fixes(OCR_SCAN, "cjk-preferred").as_deref(),
+            Ok(
+                "Invoice No. ２０２４-117, dated 二〇二四年三月, for Company data.\n\
+                Total: 1〇5 USD.\n\
+                アカウント登録は第一回の説明会で。東京へ行く。"
+            )
+        );
+        assert!(apply_confusable_fixes(OCR_SCAN, "latin").is_err());
+    }
+}
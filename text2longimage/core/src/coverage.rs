@@ -0,0 +1,148 @@
+//! Characters that are likely to render as tofu in a typical web font
+//! The crate can't see the font, so this only flags risk by code point: astral-plane symbols,
+//! rare ideographs, private-use and unassigned code points, and combining marks, which many
+//! fonts lack or position badly.
+
+use serde::Serialize;
+
+use crate::table::{
+    in_ranges, is_unassigned, COMBINING_RANGES, PRIVATE_USE_RANGES, SUPPLEMENTARY_IDEOGRAPH_RANGES,
+};
+
+/// Distinct example characters listed per bucket
+pub const MAX_COVERAGE_EXAMPLES: usize = 10;
+
+/// Characters of one risk bucket
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageBucket {
+    pub count: usize,
+    /// The first distinct characters found, in text order
+    pub examples: Vec<char>,
+}
+
+impl CoverageBucket {
+    fn add(&mut self, c: char) {
+        self.count += 1;
+        if self.examples.len() < MAX_COVERAGE_EXAMPLES && !self.examples.contains(&c) {
+            self.examples.push(c);
+        }
+    }
+}
+
+/// Risky characters of a text by bucket
+/// Each character lands in the first bucket that matches, in field order, so an Extension B
+/// ideograph counts as a supplementary ideograph and not also as non-BMP.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct FontCoverage {
+    /// Sum of all bucket counts
+    pub risky_count: usize,
+    /// Characters above U+FFFF, risky or not (get_text_stats' astralCount)
+    pub astral_count: usize,
+    pub unassigned: CoverageBucket,
+    pub private_use: CoverageBucket,
+    /// CJK Extension B and later
+    pub supplementary_ideographs: CoverageBucket,
+    pub combining: CoverageBucket,
+    /// Everything else outside the BMP (emoji, historic scripts, math alphanumerics)
+    pub non_bmp: CoverageBucket,
+}
+
+impl FontCoverage {
+    pub fn of(text: &str) -> Self {
+        let mut coverage = FontCoverage::default();
+        for c in text.chars() {
+            if c as u32 > 0xFFFF {
+                coverage.astral_count += 1;
+            }
+            let bucket = if is_unassigned(c) {
+                &mut coverage.unassigned
+            } else if in_ranges(PRIVATE_USE_RANGES, c) {
+                &mut coverage.private_use
+            } else if in_ranges(SUPPLEMENTARY_IDEOGRAPH_RANGES, c) {
+                &mut coverage.supplementary_ideographs
+            } else if in_ranges(COMBINING_RANGES, c) {
+                &mut coverage.combining
+            } else if c as u32 > 0xFFFF {
+                &mut coverage.non_bmp
+            } else {
+                continue;
+            };
+            bucket.add(c);
+            coverage.risky_count += 1;
+        }
+        coverage
+    }
+}
+
+#[cfg(feature = "json")]
+/// Count characters a typical font may lack, with examples
+/// Returns JSON {riskyCount, astralCount, unassigned, privateUse, supplementaryIdeographs,
+/// combining, nonBmp}, each bucket {count, examples}; see FontCoverage
+pub fn font_coverage_report(text: &str) -> String {
+    serde_json::to_string(&FontCoverage::of(text)).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_character_from_each_bucket() {
+        // Plane 4 is unassigned, U+E000 private use, 𠀀 Extension B, U+0301 a combining acute,
+        // 😀 an emoji; 漢 and the letters are safe
+        let coverage = FontCoverage::of("漢a\u{40000}\u{E000}𠀀e\u{0301}😀b");
+        let bucket = |c: char| CoverageBucket {
+            count: 1,
+            examples: vec![c],
+        };
+        assert_eq!(coverage.unassigned, bucket('\u{40000}'));
+        assert_eq!(coverage.private_use, bucket('\u{E000}'));
+        assert_eq!(coverage.supplementary_ideographs, bucket('𠀀'));
+        assert_eq!(coverage.combining, bucket('\u{0301}'));
+        assert_eq!(coverage.non_bmp, bucket('😀'));
+        assert_eq!(coverage.risky_count, 5);
+        assert_eq!(coverage.astral_count, 3);
+    }
+
+    #[test]
+    fn the_first_matching_bucket_takes_the_character() {
+        // Plane 15 private use is astral, but counts as private use only
+        let coverage = FontCoverage::of("\u{F0000}");
+        assert_eq!(coverage.private_use.count, 1);
+        assert_eq!(coverage.non_bmp.count, 0);
+        assert_eq!((coverage.risky_count, coverage.astral_count), (1, 1));
+        assert_eq!(FontCoverage::of("plain 中文 text"), FontCoverage::default());
+        // Noncharacters are unassigned wherever they are
+        assert_eq!(
+            FontCoverage::of("\u{FDD0}\u{FFFF}\u{10FFFE}")
+                .unassigned
+                .count,
+            3
+        );
+    }
+
+    #[test]
+    fn examples_are_distinct_and_capped() {
+        let coverage = FontCoverage::of("😀😀😀😁");
+        assert_eq!(coverage.non_bmp.count, 4);
+        assert_eq!(coverage.non_bmp.examples, ['😀', '😁']);
+        let many: String = (0xE000..0xE020).filter_map(char::from_u32).collect();
+        let coverage = FontCoverage::of(&many);
+        assert_eq!(coverage.private_use.count, 32);
+        assert_eq!(coverage.private_use.examples.len(), MAX_COVERAGE_EXAMPLES);
+        assert_eq!(coverage.private_use.examples[0], '\u{E000}');
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn report_json() {
+        assert_eq!(
+            font_coverage_report("a😀"),
+            r#"{"riskyCount":1,"astralCount":1,"unassigned":{"count":0,"examples":[]},"privateUse":{"count":0,"examples":[]},"supplementaryIdeographs":{"count":0,"examples":[]},"combining":{"count":0,"examples":[]},"nonBmp":{"count":1,"examples":["😀"]}}"#
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Comparisons of wrapped text: one text under two option sets, and two versions of a text
+//! diff_wrap lets the UI show what a settings change will do before re-rendering a large
+//! image. diff_texts is for "what changed" images: the lines of the two versions are compared
+//! (see myers.rs), and within a run of changed lines their words, or a CJK text's characters,
+//! so one character changed in a long line marks that character only. The old and new text
+//! of each change are merged, deleted before inserted, and the merged text is wrapped like
+//! justify_text, every piece of a line tagged with where it came from.
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::myers::{edit_script, Edit};
+use crate::{
+    check_max_chars_per_line, is_cjk_char, width_of, JustifyOptions, WebtoolsError, WrapIter,
+};
+
+/// Most differing lines diff_wrap lists; the counts still cover the whole text
+pub const MAX_WRAP_DIFFERENCES: usize = 50;
+
+/// A line that differs; None where that side has fewer lines
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct LineDifference<'a> {
+    line_index: usize,
+    line_a: Option<&'a str>,
+    line_b: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WrapDiff<'a> {
+    line_count_a: usize,
+    line_count_b: usize,
+    /// Index of the first differing line, None when the outputs are identical
+    first_difference: Option<usize>,
+    difference_count: usize,
+    differences: Vec<LineDifference<'a>>,
+}
+
+fn output_lines(justified: &str) -> Vec<&str> {
+    if justified.is_empty() {
+        Vec::new()
+    } else {
+        justified.split("\r\n").collect()
+    }
+}
+
+/// Wrap text under two JustifyOptions objects and compare the output line by line
+/// Returns JSON {lineCountA, lineCountB, firstDifference, differenceCount, differences}, where
+/// differences lists the first MAX_WRAP_DIFFERENCES {lineIndex, lineA, lineB} (null past the
+/// end of the shorter output). "" for either options object uses the defaults.
+pub fn diff_wrap(
+    text: &str,
+    options_a_json: &str,
+    options_b_json: &str,
+) -> Result<String, WebtoolsError> {
+    let justified_a = JustifyOptions::from_json(options_a_json)?.justify(text);
+    let justified_b = JustifyOptions::from_json(options_b_json)?.justify(text);
+    let lines_a = output_lines(&justified_a);
+    let lines_b = output_lines(&justified_b);
+
+    let mut differing = (0..lines_a.len().max(lines_b.len()))
+        .map(|index| {
+            (
+                index,
+                lines_a.get(index).copied(),
+                lines_b.get(index).copied(),
+            )
+        })
+        .filter(|(_, line_a, line_b)| line_a != line_b)
+        .peekable();
+    let first_difference = differing.peek().map(|&(index, _, _)| index);
+    let mut differences = Vec::new();
+    let mut difference_count = 0;
+    for (line_index, line_a, line_b) in differing {
+        if differences.len() < MAX_WRAP_DIFFERENCES {
+            differences.push(LineDifference {
+                line_index,
+                line_a,
+                line_b,
+            });
+        }
+        difference_count += 1;
+    }
+
+    let diff = WrapDiff {
+        line_count_a: lines_a.len(),
+        line_count_b: lines_b.len(),
+        first_difference,
+        difference_count,
+        differences,
+    };
+    Ok(serde_json::to_string(&diff).unwrap_or_else(|_| "{}".to_string()))
+}
+
+/// Where a piece of diff_texts' output comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeTag {
+    /// In both texts
+    Unchanged,
+    /// Only in the new text
+    Inserted,
+    /// Only in the old text
+    Deleted,
+}
+
+/// Characters of one wrapped line with one tag, in display columns
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSegment {
+    pub line_index: usize,
+    pub start_col: u32,
+    pub end_col: u32,
+    pub change: ChangeTag,
+    pub text: String,
+}
+
+/// The merged text of two versions, wrapped, as diff_texts returns it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TextDiff {
+    /// Wrapped lines, blank ones (which have no segments) included
+    pub line_count: usize,
+    /// In reading order; a piece a line break goes through is a segment on each line
+    pub segments: Vec<DiffSegment>,
+}
+
+/// A text built from tagged pieces, with the byte range of each tag's run
+#[derive(Default)]
+struct MergedText {
+    text: String,
+    tags: Vec<(Range<usize>, ChangeTag)>,
+}
+
+impl MergedText {
+    fn push(&mut self, piece: &str, change: ChangeTag) {
+        if piece.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(piece);
+        match self.tags.last_mut() {
+            Some((range, last)) if *last == change => range.end = self.text.len(),
+            _ => self.tags.push((start..self.text.len(), change)),
+        }
+    }
+
+    /// The tag of the byte at offset
+    fn tag_at(&self, offset: usize) -> ChangeTag {
+        let index = self.tags.partition_point(|(range, _)| range.end <= offset);
+        self.tags
+            .get(index)
+            .map_or(ChangeTag::Unchanged, |&(_, change)| change)
+    }
+}
+
+/// Lines split on '\n' with a trailing '\r' removed; none for ""
+fn diff_lines(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
+
+/// The pieces changed lines are compared by: each word, or CJK character, with the whitespace
+/// after it; whitespace starting a line and each '\n' are pieces of their own
+fn diff_tokens(text: &str) -> Vec<&str> {
+    // Whitespace up to the next line break
+    let blank_len = |text: &str| {
+        text.find(|c: char| c == '\n' || !c.is_whitespace())
+            .unwrap_or(text.len())
+    };
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\n' {
+            1
+        } else if c.is_whitespace() {
+            blank_len(rest)
+        } else {
+            let word_len = if is_cjk_char(c) {
+                c.len_utf8()
+            } else {
+                rest.find(|c: char| c.is_whitespace() || is_cjk_char(c))
+                    .unwrap_or(rest.len())
+            };
+            word_len + blank_len(&rest[word_len..])
+        };
+        let (token, tail) = rest.split_at(len);
+        tokens.push(token);
+        rest = tail;
+    }
+    tokens
+}
+
+/// old and new merged line by line, and within a run of changed lines token by token
+fn merge_texts(old: &str, new: &str) -> MergedText {
+    let (old_lines, new_lines) = (diff_lines(old), diff_lines(new));
+    let mut merged = MergedText::default();
+    let (mut o, mut n) = (0, 0);
+    let script = edit_script(&old_lines, &new_lines);
+    let mut edits = script.iter().peekable();
+    while let Some(edit) = edits.next() {
+        if *edit == Edit::Keep {
+            merged.push(old_lines[o], ChangeTag::Unchanged);
+            merged.push("\n", ChangeTag::Unchanged);
+            o += 1;
+            n += 1;
+            continue;
+        }
+        // A run of changed lines: the deleted ones, then the inserted ones
+        let (deleted_start, inserted_start) = (o, n);
+        let mut edit = Some(edit);
+        while let Some(&change) = edit.filter(|&&e| e != Edit::Keep) {
+            match change {
+                Edit::Delete => o += 1,
+                _ => n += 1,
+            }
+            edit = match edits.peek() {
+                Some(&&next) if next != Edit::Keep => edits.next(),
+                _ => None,
+            };
+        }
+        let deleted = old_lines[deleted_start..o].join("\n");
+        let inserted = new_lines[inserted_start..n].join("\n");
+        if deleted_start == o || inserted_start == n {
+            let change = match deleted_start == o {
+                true => ChangeTag::Inserted,
+                false => ChangeTag::Deleted,
+            };
+            merged.push(&deleted, change);
+            merged.push(&inserted, change);
+            merged.push("\n", change);
+            continue;
+        }
+        let (old_tokens, new_tokens) = (diff_tokens(&deleted), diff_tokens(&inserted));
+        let (mut ot, mut nt) = (0, 0);
+        for token_edit in edit_script(&old_tokens, &new_tokens) {
+            match token_edit {
+                Edit::Keep => {
+                    merged.push(old_tokens[ot], ChangeTag::Unchanged);
+                    ot += 1;
+                    nt += 1;
+                }
+                Edit::Delete => {
+                    merged.push(old_tokens[ot], ChangeTag::Deleted);
+                    ot += 1;
+                }
+                Edit::Insert => {
+                    merged.push(new_tokens[nt], ChangeTag::Inserted);
+                    nt += 1;
+                }
+            }
+        }
+        merged.push("\n", ChangeTag::Unchanged);
+    }
+    // The line break after the last line
+    if let Some((range, _)) = merged.tags.last_mut() {
+        merged.text.pop();
+        range.end -= 1;
+        if range.start == range.end {
+            merged.tags.pop();
+        }
+    }
+    merged
+}
+
+impl TextDiff {
+    /// old_text and new_text merged and wrapped at max_chars_per_line, as justify_text wraps
+    pub fn of(old_text: &str, new_text: &str, max_chars_per_line: u32) -> Self {
+        let merged = merge_texts(old_text, new_text);
+        let mut segments: Vec<DiffSegment> = Vec::new();
+        let mut line_count = 0;
+        for (line_index, line) in
+            WrapIter::new(merged.text.as_str(), max_chars_per_line).enumerate()
+        {
+            line_count += 1;
+            let start = line.text_range.start;
+            let mut source = merged.text[line.text_range.clone()].char_indices();
+            let mut col = 0;
+            for c in line.text(&merged.text).chars() {
+                // The source character written as c: itself, or the first of a run of
+                // whitespace the word wrapper wrote as one space
+                let offset = source
+                    .find(|&(_, s)| s == c || c == ' ' && s.is_whitespace())
+                    .map_or(line.text_range.end, |(offset, _)| start + offset);
+                let change = merged.tag_at(offset);
+                let width = width_of(c);
+                match segments.last_mut() {
+                    Some(last)
+                        if last.line_index == line_index
+                            && last.change == change
+                            && last.end_col == col =>
+                    {
+                        last.end_col += width;
+                        last.text.push(c);
+                    }
+                    _ => segments.push(DiffSegment {
+                        line_index,
+                        start_col: col,
+                        end_col: col + width,
+                        change,
+                        text: c.to_string(),
+                    }),
+                }
+                col += width;
+            }
+        }
+        TextDiff {
+            line_count,
+            segments,
+        }
+    }
+}
+
+/// Compare old_text and new_text and wrap the merged result at max_chars_per_line
+/// Returns JSON {lineCount, segments}, segments being {lineIndex, startCol, endCol, change,
+/// text} with change "unchanged", "inserted" or "deleted"; a change that wraps onto the next
+/// line is a segment on each. Changed lines are compared by word, CJK by character, and a
+/// deletion is written before the insertion replacing it.
+pub fn diff_texts(
+    old_text: &str,
+    new_text: &str,
+    max_chars_per_line: u32,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let diff = TextDiff::of(old_text, new_text, max_chars_per_line);
+    Ok(serde_json::to_string(&diff).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FABLE: &str =
+        "The quick brown fox jumps over the lazy dog, and then it runs back across the \
+field to hide under the old oak tree near the river bank before nightfall comes.";
+
+    fn diff(text: &str, options_a_json: &str, options_b_json: &str) -> serde_json::Value {
+        serde_json::from_str(&diff_wrap(text, options_a_json, options_b_json).unwrap()).unwrap()
+    }
+
+    /// The segments of diff_texts' output with one change tag, as (line, text) pairs
+    fn segments(old: &str, new: &str, width: u32, change: &str) -> Vec<(usize, String)> {
+        let diff: serde_json::Value =
+            serde_json::from_str(&diff_texts(old, new, width).unwrap()).unwrap();
+        diff["segments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|segment| segment["change"] == change)
+            .map(|segment| {
+                (
+                    segment["lineIndex"].as_u64().unwrap() as usize,
+                    segment["text"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn width_40_against_42() {
+        let diff = diff(
+            FABLE,
+            r#"{"maxCharsPerLine":40}"#,
+            r#"{"maxCharsPerLine":42}"#,
+        );
+        assert_eq!(
+            (diff["lineCountA"].as_u64(), diff["lineCountB"].as_u64()),
+            (Some(5), Some(4))
+        );
+        // The first two lines are the same at both widths
+        assert_eq!(diff["firstDifference"], 2);
+        assert_eq!(diff["differenceCount"], 3);
+        assert_eq!(
+            diff["differences"][0],
+            serde_json::json!({
+                "lineIndex": 2,
+                "lineA": "field to hide under the old oak tree",
+                "lineB": "field to hide under the old oak tree near",
+            })
+        );
+        // Past the end of the shorter output its side is null
+        assert_eq!(
+            diff["differences"][2],
+            serde_json::json!({"lineIndex": 4, "lineA": "comes.", "lineB": null})
+        );
+    }
+
+    #[test]
+    fn a_japanese_paragraph_with_and_without_ruby() {
+        let text = "吾輩《わがはい》は猫である。名前《なまえ》はまだ無い。";
+        let diff = diff(
+            text,
+            r#"{"maxCharsPerLine":20}"#,
+            r#"{"maxCharsPerLine":20,"rubySyntax":"kanji"}"#,
+        );
+        assert_eq!(diff["firstDifference"], 0);
+        assert_eq!(diff["differences"][0]["lineA"], "吾輩《わがはい》は猫");
+        assert_eq!(
+            diff["differences"][0]["lineB"],
+            "吾輩《わがはい》は猫である。名前《なまえ》"
+        );
+    }
+
+    #[test]
+    fn identical_options_have_no_differences() {
+        // The defaults, and the default width spelled out
+        let diff = diff(FABLE, "", r#"{"maxCharsPerLine":36}"#);
+        assert_eq!(diff["firstDifference"], serde_json::Value::Null);
+        assert_eq!(diff["differenceCount"], 0);
+        assert_eq!(diff["differences"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn differences_are_capped_but_counted() {
+        let text = "x ".repeat(200);
+        let diff = diff(
+            &text,
+            r#"{"maxCharsPerLine":1}"#,
+            r#"{"maxCharsPerLine":3}"#,
+        );
+        assert_eq!(diff["lineCountA"], 200);
+        assert_eq!(diff["differenceCount"], 200);
+        assert_eq!(
+            diff["differences"].as_array().unwrap().len(),
+            MAX_WRAP_DIFFERENCES
+        );
+    }
+
+    #[test]
+    fn options_are_checked() {
+        assert_eq!(
+            diff_wrap(FABLE, "{", "").unwrap_err().code(),
+            "INVALID_JSON"
+        );
+        assert_eq!(
+            diff_wrap(FABLE, "", r#"{"rubySyntax":"pinyin"}"#)
+                .unwrap_err()
+                .code(),
+            "INVALID_JSON"
+        );
+    }
+
+    #[test]
+    fn the_word_changed_in_a_sentence_is_marked() {
+        let old = "The quick brown fox jumps over the lazy dog.\nThe second line stays.";
+        let new = "The quick red fox jumps over the lazy dog.\nThe second line stays.";
+        assert_eq!(
+            segments(old, new, 20, "deleted"),
+            [(0, "brown ".to_string())]
+        );
+        assert_eq!(segments(old, new, 20, "inserted"), [(0, "red".to_string())]);
+    }
+
+    #[test]
+    fn one_character_changed_in_a_long_chinese_line_is_marked() {
+        let old = "今天天气很好我们一起去公园散步吧然后去吃饭";
+        let new = "今天天气很好我们一起去公园跑步吧然后去吃饭";
+        assert_eq!(segments(old, new, 10, "deleted"), [(2, "散".to_string())]);
+        assert_eq!(segments(old, new, 10, "inserted"), [(2, "跑".to_string())]);
+    }
+
+    #[test]
+    fn a_change_that_wraps_is_a_segment_on_each_line() {
+        assert_eq!(
+            segments("第一行", "第一行新增加的文字", 6, "inserted"),
+            [(1, "新增加".to_string()), (2, "的文字".to_string())]
+        );
+    }
+}
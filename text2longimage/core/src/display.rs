@@ -0,0 +1,236 @@
+//! Text statistics formatted for display, in the reader's locale
+//! The stats panel, the export dialog and the share card each formatted the numbers their own
+//! way. format_stats gives every number once as is and once as display text: "32,415" in
+//! English, "3.2万" in Chinese and Japanese, which count in myriads — 万 (10⁴) and 亿/億 (10⁸),
+//! rounded to one decimal that is left out when it is 0, with numbers below 10,000 written as
+//! plain digits. Reading time assumes 200 words a minute, and 400 characters a minute for
+//! Han and kana, rounded up to whole minutes.
+
+use serde::Serialize;
+
+use crate::capabilities::{expected_values, find_value};
+use crate::error::group_thousands;
+use crate::{is_cjk_char, words, OptionValue, TextStats, WebtoolsError};
+
+/// Words of Latin, Cyrillic and the like read in a minute
+const WORDS_PER_MINUTE: usize = 200;
+
+/// CJK characters read in a minute
+const CJK_CHARS_PER_MINUTE: usize = 400;
+
+/// The locales format_stats writes numbers for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsLocale {
+    English,
+    Chinese,
+    Japanese,
+}
+
+impl StatsLocale {
+    /// Parse "en", "zh" or "ja" in any case; a region ("zh-CN", "en_US") is ignored
+    pub fn parse(locale: &str) -> Result<Self, WebtoolsError> {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        find_value(|name| language.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown stats locale: {} (expected {})",
+                locale,
+                expected_values::<StatsLocale>()
+            ))
+        })
+    }
+
+    /// count as display text
+    pub fn format_count(self, count: usize) -> String {
+        match self {
+            StatsLocale::English => group_thousands(count),
+            StatsLocale::Chinese => myriads(count, "亿"),
+            StatsLocale::Japanese => myriads(count, "億"),
+        }
+    }
+
+    /// A reading time of minutes as display text
+    pub fn format_minutes(self, minutes: usize) -> String {
+        match self {
+            StatsLocale::English => format!("{} min", group_thousands(minutes)),
+            StatsLocale::Chinese => format!("{}分钟", self.format_count(minutes)),
+            StatsLocale::Japanese => format!("{}分", self.format_count(minutes)),
+        }
+    }
+}
+
+impl OptionValue for StatsLocale {
+    const KIND: &'static str = "statsLocale";
+    const USED_BY: &'static [&'static str] = &["format_stats"];
+    const ALL: &'static [Self] = &[
+        StatsLocale::English,
+        StatsLocale::Chinese,
+        StatsLocale::Japanese,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            StatsLocale::English => "en",
+            StatsLocale::Chinese => "zh",
+            StatsLocale::Japanese => "ja",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            StatsLocale::English => "Thousands separated by commas (32,415); \"3 min\"",
+            StatsLocale::Chinese => "Counted in 万 and 亿 (3.2万); \"3分钟\"",
+            StatsLocale::Japanese => "Counted in 万 and 億 (3.2万); \"3分\"",
+        }
+    }
+}
+
+/// count in myriads: below 10⁴ as digits, then in 万 and from 10⁸ on in hundred_million, to one
+/// decimal
+fn myriads(count: usize, hundred_million: &str) -> String {
+    if count < 10_000 {
+        return count.to_string();
+    }
+    // Tenths of a 万, rounded half up; 9999.95万 rounds to 1亿, not 10000万
+    let tenths = (count + 500) / 1_000;
+    if tenths < 100_000 {
+        return tenths_with_unit(tenths, "万");
+    }
+    tenths_with_unit((count + 5_000_000) / 10_000_000, hundred_million)
+}
+
+/// 32 tenths and "万" -> "3.2万", 30 -> "3万"
+fn tenths_with_unit(tenths: usize, unit: &str) -> String {
+    match tenths % 10 {
+        0 => format!("{}{}", tenths / 10, unit),
+        decimal => format!("{}.{}{}", tenths / 10, decimal, unit),
+    }
+}
+
+/// Minutes reading text takes, rounded up: 0 for text without words
+pub fn reading_minutes(text: &str) -> usize {
+    let (mut words_read, mut cjk_chars) = (0, 0);
+    for word in words(text) {
+        if word.starts_with(is_cjk_char) {
+            cjk_chars += word.chars().count();
+        } else {
+            words_read += 1;
+        }
+    }
+    // In 400ths of a minute, so both rates add up exactly
+    let units = words_read * (CJK_CHARS_PER_MINUTE / WORDS_PER_MINUTE) + cjk_chars;
+    units.div_ceil(CJK_CHARS_PER_MINUTE)
+}
+
+/// The stats format_stats shows, each as a number and as display text
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct FormattedStats {
+    /// The locale's name, as capabilities lists it ("en", "zh" or "ja")
+    pub locale: &'static str,
+    /// TextStats' charCount
+    pub char_count_raw: usize,
+    pub char_count_display: String,
+    pub word_count_raw: usize,
+    pub word_count_display: String,
+    pub line_count_raw: usize,
+    pub line_count_display: String,
+    /// Columns, CJK characters counting two
+    pub display_width_raw: u32,
+    pub display_width_display: String,
+    /// See reading_minutes
+    pub reading_minutes_raw: usize,
+    pub reading_minutes_display: String,
+}
+
+impl FormattedStats {
+    pub fn of(text: &str, locale: StatsLocale) -> Self {
+        let stats = TextStats::of(text);
+        let minutes = reading_minutes(text);
+        FormattedStats {
+            locale: locale.name(),
+            char_count_raw: stats.char_count,
+            char_count_display: locale.format_count(stats.char_count),
+            word_count_raw: stats.word_count,
+            word_count_display: locale.format_count(stats.word_count),
+            line_count_raw: stats.line_count,
+            line_count_display: locale.format_count(stats.line_count),
+            display_width_raw: stats.display_width,
+            display_width_display: locale.format_count(stats.display_width as usize),
+            reading_minutes_raw: minutes,
+            reading_minutes_display: locale.format_minutes(minutes),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+/// Character, word and line counts, display width and reading time of text, each as a number
+/// and as display text for locale ("en", "zh" or "ja")
+/// Returns JSON {locale, charCountRaw, charCountDisplay, wordCountRaw, ...,
+/// readingMinutesRaw, readingMinutesDisplay}
+pub fn format_stats(text: &str, locale: &str) -> Result<String, WebtoolsError> {
+    let stats = FormattedStats::of(text, StatsLocale::parse(locale)?);
+    Ok(serde_json::to_string(&stats).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::capabilities;
+
+    /// The display strings of formatted_stats_text(), per locale: charCount, wordCount,
+    /// lineCount, displayWidth and readingMinutes; a changed translation or rounding shows up
+    /// here
+    const FORMATTED_STATS: [(&str, [&str; 5]); 3] = [
+        ("en", ["33,600", "14,700", "700", "44,800", "48 min"]),
+        ("zh", ["3.4万", "1.5万", "700", "4.5万", "48分钟"]),
+        ("ja", ["3.4万", "1.5万", "700", "4.5万", "48分"]),
+    ];
+
+    /// 700 lines of mixed Chinese and English
+    fn formatted_stats_text() -> String {
+        "长图文字排版需要处理中英文混排。Long images are read on phones.\n".repeat(700)
+    }
+
+    #[test]
+    fn each_locale_shows_its_own_strings() {
+        let fixture = formatted_stats_text();
+        let fields = [
+            "charCount",
+            "wordCount",
+            "lineCount",
+            "displayWidth",
+            "readingMinutes",
+        ];
+        for (locale, displays) in FORMATTED_STATS {
+            let json: serde_json::Value =
+                serde_json::from_str(&format_stats(&fixture, locale).unwrap()).unwrap();
+            let shown: Vec<&str> = fields
+                .iter()
+                .map(|field| json[format!("{field}Display")].as_str().unwrap())
+                .collect();
+            assert_eq!(shown, displays, "format_stats in {locale}");
+            assert_eq!(json["readingMinutesRaw"], 48);
+        }
+    }
+
+    #[test]
+    fn a_region_is_ignored_and_the_locales_are_listed() {
+        let fixture = formatted_stats_text();
+        assert_eq!(
+            format_stats(&fixture, "zh-CN"),
+            format_stats(&fixture, "zh")
+        );
+        assert!(matches!(
+            format_stats(&fixture, "fr"),
+            Err(WebtoolsError::InvalidArgument { .. })
+        ));
+        let locales = capabilities()
+            .options
+            .into_iter()
+            .find(|values| values.kind == "statsLocale")
+            .expect("capabilities lists statsLocale");
+        let names: Vec<&str> = locales.values.iter().map(|value| value.name).collect();
+        assert_eq!(names, ["en", "zh", "ja"]);
+    }
+}
@@ -0,0 +1,478 @@
+//! A text kept together with its options, so repeated queries share one wrap
+//! A render loop asks for the wrapped text, the line count, the layout and the pages of the
+//! same input; Document wraps on the first of those and answers the rest from the cached lines
+//! until the text or the width changes. Everything a Document's answers depend on is its own:
+//! the options it was given and the script line heights, taken from the module settings when
+//! it is built and changed only through set_line_heights, so two Documents stay independent
+//! whatever configure changes between their calls.
+
+use std::cell::OnceCell;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::layout::layout_lines_with_heights;
+use crate::layout::{line_script, LineGeometry};
+use crate::line_height::LineHeightSum;
+use crate::paginate::page_wrapped_sections;
+use crate::{Align, JustifyOptions, LayoutLine, ScriptLineHeights, TextStats};
+
+/// The wrap of the current text: the output and the byte range of each of its lines
+struct Wrapped {
+    text: String,
+    lines: Vec<Range<usize>>,
+    /// The lines of each height above every line, for layout_range
+    above: OnceCell<Vec<LineHeightSum>>,
+}
+
+impl Wrapped {
+    fn of(text: &str, options: &JustifyOptions) -> Self {
+        #[cfg(test)]
+        tests::WRAPS.with(|wraps| wraps.set(wraps.get() + 1));
+        let text = options.justify(text);
+        let mut lines = Vec::new();
+        if !text.is_empty() {
+            let mut start = 0;
+            for (index, _) in text.match_indices("\r\n") {
+                lines.push(start..index);
+                start = index + 2;
+            }
+            lines.push(start..text.len());
+        }
+        Wrapped {
+            text,
+            lines,
+            above: OnceCell::new(),
+        }
+    }
+
+    fn lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .map(|range| &self.text[range.clone()])
+            .collect()
+    }
+}
+
+/// Part of a Document's layout, as Document::layout_range returns it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutRange<'a> {
+    /// lines are wrapped lines first_line through last_line (both included); last_line is
+    /// None when the range held none
+    pub first_line: u32,
+    pub last_line: Option<u32>,
+    /// Wrapped lines in the whole text
+    pub line_count: u32,
+    pub lines: Vec<LayoutLine<'a>>,
+}
+
+/// A text and its options, wrapped at most once per text and width
+/// Every accessor gives what the matching standalone function gives for the same text and
+/// options (JustifyOptions::justify, count_wrapped_lines_of_output, layout_lines_with_ruby,
+/// TextStats::of, paginate_lines), the layouts with the Document's line heights
+pub struct Document {
+    text: String,
+    options: JustifyOptions,
+    heights: ScriptLineHeights,
+    wrapped: OnceCell<Wrapped>,
+    stats: OnceCell<TextStats>,
+}
+
+impl Document {
+    /// Nothing is wrapped until an accessor needs it; the line heights are the ones currently
+    /// set
+    pub fn new(text: String, options: JustifyOptions) -> Self {
+        Document {
+            text,
+            options,
+            heights: ScriptLineHeights::current(),
+            wrapped: OnceCell::new(),
+            stats: OnceCell::new(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn options(&self) -> &JustifyOptions {
+        &self.options
+    }
+
+    /// The heightFactors of lines with CJK and with emoji the layouts use
+    pub fn line_heights(&self) -> ScriptLineHeights {
+        self.heights
+    }
+
+    /// Lay out with other heightFactors from now on; the wrap stays
+    pub fn set_line_heights(&mut self, heights: ScriptLineHeights) {
+        self.heights = heights;
+    }
+
+    /// Replace the text; the options stay and the next accessor wraps again
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+        self.wrapped = OnceCell::new();
+        self.stats = OnceCell::new();
+    }
+
+    /// Change options.max_chars_per_line; the text (and its stats) stay, and the next accessor
+    /// that needs lines wraps again. Setting the current width keeps the cached wrap
+    pub fn set_width(&mut self, max_chars_per_line: u32) {
+        if max_chars_per_line != self.options.max_chars_per_line {
+            self.options.max_chars_per_line = max_chars_per_line;
+            self.wrapped = OnceCell::new();
+        }
+    }
+
+    /// Whether the current text is wrapped at the current width
+    pub fn is_wrapped(&self) -> bool {
+        self.wrapped.get().is_some()
+    }
+
+    fn wrapped(&self) -> &Wrapped {
+        self.wrapped
+            .get_or_init(|| Wrapped::of(&self.text, &self.options))
+    }
+
+    /// options.justify(text)
+    pub fn wrapped_text(&self) -> &str {
+        &self.wrapped().text
+    }
+
+    /// The wrapped lines, without their line breaks
+    pub fn lines(&self) -> Vec<&str> {
+        self.wrapped().lines()
+    }
+
+    /// Number of wrapped lines, as count_wrapped_lines_of_output counts them
+    pub fn line_count(&self) -> u32 {
+        self.wrapped().lines.len() as u32
+    }
+
+    /// Canvas positions of the wrapped lines, with the readings of options.ruby_syntax
+    pub fn layout(
+        &self,
+        font_size_px: f64,
+        line_height_factor: f64,
+        padding_px: f64,
+        align: Align,
+    ) -> Vec<LayoutLine<'_>> {
+        layout_lines_with_heights(
+            self.wrapped_text(),
+            self.options.max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            align,
+            self.options.ruby_syntax,
+            self.heights,
+        )
+    }
+
+    /// layout's entries for wrapped lines first_line through last_line (both included) only
+    /// A range past the end is cut to the lines there are. Each entry is the one layout gives
+    /// for its line: y positions come from the heights of the lines above, counted once per
+    /// wrap, so ranges requested one after another stitch together into the full layout.
+    pub fn layout_range(
+        &self,
+        first_line: u32,
+        last_line: u32,
+        font_size_px: f64,
+        line_height_factor: f64,
+        padding_px: f64,
+        align: Align,
+    ) -> LayoutRange<'_> {
+        let wrapped = self.wrapped();
+        let line_count = self.line_count();
+        let first_line = first_line.min(line_count);
+        let end = last_line.saturating_add(1).min(line_count).max(first_line);
+        let geometry = LineGeometry {
+            max_chars_per_line: self.options.max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            align,
+            ruby_syntax: self.options.ruby_syntax,
+            heights: self.heights,
+        };
+        let above = self.heights_above();
+        let lines = (first_line..end)
+            .map(|index| {
+                let line = &wrapped.text[wrapped.lines[index as usize].clone()];
+                geometry.place(line, &above[index as usize]).0
+            })
+            .collect();
+        LayoutRange {
+            first_line,
+            last_line: (end > first_line).then(|| end - 1),
+            line_count,
+            lines,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    /// layout_range as JSON {firstLine, lastLine, lineCount, lines: [{text, xPx, yPx, widthPx,
+    /// ruby?, heightFactor}]}; lastLine is null when no line is in the range
+    pub fn layout_range_json(
+        &self,
+        first_line: u32,
+        last_line: u32,
+        font_size_px: f64,
+        line_height_factor: f64,
+        padding_px: f64,
+        align: Align,
+    ) -> String {
+        let range = self.layout_range(
+            first_line,
+            last_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            align,
+        );
+        serde_json::to_string(&range).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// The lines of each height above every wrapped line
+    fn heights_above(&self) -> &[LineHeightSum] {
+        let wrapped = self.wrapped();
+        wrapped.above.get_or_init(|| {
+            let mut above = LineHeightSum::default();
+            wrapped
+                .lines()
+                .into_iter()
+                .map(|line| {
+                    let before = above;
+                    above.add(line_script(line, self.options.ruby_syntax));
+                    before
+                })
+                .collect()
+        })
+    }
+
+    /// Statistics of the source text; kept across set_width
+    pub fn stats(&self) -> &TextStats {
+        self.stats.get_or_init(|| TextStats::of(&self.text))
+    }
+
+    /// Pages of at most lines_per_page wrapped lines, as paginate_lines pages them
+    /// Form feeds in the text force a page break; such a text is wrapped again one section at
+    /// a time, as paginate_lines does, since the cached wrap runs across them.
+    pub fn paginate(&self, lines_per_page: u32, avoid_widows: bool) -> Vec<String> {
+        let capacity = |_| lines_per_page as usize;
+        if self.text.contains('\u{000C}') {
+            let sections: Vec<Wrapped> = self
+                .text
+                .split('\u{000C}')
+                .map(|section| Wrapped::of(section, &self.options))
+                .collect();
+            page_wrapped_sections(sections.iter().map(Wrapped::lines), capacity, avoid_widows)
+        } else {
+            page_wrapped_sections([self.wrapped().lines()], capacity, avoid_widows)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{
+        count_wrapped_lines_of_output, layout_lines_with_ruby, paginate_lines, RubySyntax,
+    };
+
+    thread_local! {
+        /// Times Wrapped::of ran on this thread
+        pub(super) static WRAPS: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn wraps() -> u32 {
+        WRAPS.with(Cell::get)
+    }
+
+    fn document(text: &str, max_chars_per_line: u32) -> Document {
+        Document::new(
+            text.to_string(),
+            JustifyOptions {
+                max_chars_per_line,
+                ..JustifyOptions::default()
+            },
+        )
+    }
+
+    /// Ranges of every length, stitched together, against the Document's own layout
+    fn assert_ranges_stitch(document: &Document) {
+        let full = document.layout(16.0, 1.5, 8.0, Align::Center);
+        let count = document.line_count();
+        assert_eq!(full.len(), count as usize);
+        for step in 1..=count.max(1) {
+            let mut stitched = Vec::new();
+            let mut first = 0;
+            while first < count {
+                stitched.extend(
+                    document
+                        .layout_range(first, first + step - 1, 16.0, 1.5, 8.0, Align::Center)
+                        .lines,
+                );
+                first += step;
+            }
+            assert_eq!(stitched, full, "ranges of {step} lines");
+        }
+    }
+
+    #[test]
+    fn layout_range_stitches_into_layout() {
+        // The current wrapper breaks the URL at its separators; justify_text V1 does not
+        assert_ranges_stitch(&document("https://example.com/path/to/x 漢字", 2));
+        assert_ranges_stitch(&document("first paragraph\n\n漢字かな交じり文です。", 5));
+    }
+
+    #[test]
+    fn a_text_that_is_all_signature_stitches_too() {
+        // The signature is laid out verbatim, so V1's trimming of "-- " doesn't apply
+        for width in [0, 1, 5] {
+            let document = document("-- \nsig", width);
+            assert_ranges_stitch(&document);
+            let lines = document.layout(16.0, 1.5, 8.0, Align::Left);
+            let texts: Vec<&str> = lines.iter().map(|line| &*line.text).collect();
+            assert_eq!(texts, ["-- ", "sig"], "at {width}");
+        }
+    }
+
+    #[test]
+    fn layout_range_past_the_end() {
+        let document = document("one two three four", 4);
+        let count = document.line_count();
+        let past = document.layout_range(count - 2, u32::MAX, 16.0, 1.5, 8.0, Align::Left);
+        assert_eq!(
+            (past.first_line, past.last_line),
+            (count - 2, Some(count - 1))
+        );
+        let beyond = document.layout_range(count + 3, count + 5, 16.0, 1.5, 8.0, Align::Left);
+        assert_eq!((beyond.first_line, beyond.last_line), (count, None));
+        assert!(beyond.lines.is_empty());
+    }
+
+    const TEXT: &str = "这是一段用于测试的中文文本，包含English words混排。\n\nThe quick brown fox jumps over the lazy dog.";
+
+    #[test]
+    fn accessors_match_the_standalone_functions() {
+        let document = document(TEXT, 12);
+        let justified = document.options().justify(TEXT);
+        assert_eq!(document.wrapped_text(), justified);
+        assert_eq!(
+            document.lines(),
+            justified.split("\r\n").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            document.line_count(),
+            count_wrapped_lines_of_output(&justified)
+        );
+        assert_eq!(
+            document.layout(16.0, 1.5, 8.0, Align::Left),
+            layout_lines_with_ruby(
+                &justified,
+                12,
+                16.0,
+                1.5,
+                8.0,
+                Align::Left,
+                RubySyntax::None
+            )
+        );
+        assert_eq!(document.stats(), &TextStats::of(TEXT));
+        for lines_per_page in [1, 3, 100] {
+            assert_eq!(
+                document.paginate(lines_per_page, false),
+                paginate_lines(TEXT, 12, lines_per_page, false)
+            );
+        }
+        let empty = Document::new(String::new(), JustifyOptions::default());
+        assert!(empty.lines().is_empty());
+        assert_eq!(empty.line_count(), 0);
+    }
+
+    #[test]
+    fn accessors_share_one_wrap() {
+        let before = wraps();
+        let document = document(TEXT, 12);
+        assert!(!document.is_wrapped());
+        assert_eq!(wraps(), before);
+        document.wrapped_text();
+        document.line_count();
+        document.layout(16.0, 1.5, 8.0, Align::Center);
+        document.layout_range(1, 2, 16.0, 1.5, 8.0, Align::Center);
+        document.paginate(3, true);
+        document.lines();
+        assert!(document.is_wrapped());
+        assert_eq!(wraps(), before + 1);
+    }
+
+    #[test]
+    fn changes_wrap_again_on_the_next_accessor() {
+        let mut document = document(TEXT, 12);
+        document.line_count();
+        let before = wraps();
+        // The same width keeps the wrap
+        document.set_width(12);
+        assert!(document.is_wrapped());
+        document.set_width(30);
+        assert!(!document.is_wrapped());
+        let stats = document.stats().clone();
+        assert_eq!(wraps(), before);
+        assert_eq!(document.wrapped_text(), justify(TEXT, 30));
+        assert_eq!(wraps(), before + 1);
+
+        document.set_text("漢字かな交じり文".to_string());
+        assert!(!document.is_wrapped());
+        assert_ne!(document.stats(), &stats);
+        assert_eq!(document.wrapped_text(), justify("漢字かな交じり文", 30));
+        assert_eq!(wraps(), before + 2);
+    }
+
+    fn justify(text: &str, max_chars_per_line: u32) -> String {
+        JustifyOptions {
+            max_chars_per_line,
+            ..JustifyOptions::default()
+        }
+        .justify(text)
+    }
+
+    #[test]
+    fn form_feeds_page_each_section_on_its_own() {
+        let text = "one two three\u{000C}four five";
+        assert_eq!(
+            document(text, 8).paginate(10, false),
+            paginate_lines(text, 8, 10, false)
+        );
+    }
+
+    #[test]
+    fn set_line_heights_changes_that_document_alone() {
+        let text = "你好世界\nHello 😀\nplain";
+        let factors = |document: &Document| -> Vec<f64> {
+            let layout = document.layout(16.0, 1.5, 8.0, Align::Left);
+            layout.iter().map(|line| line.height_factor).collect()
+        };
+        let first = document(text, 36);
+        let mut second = document(text, 36);
+        let built_with = first.line_heights();
+        assert_eq!(second.line_heights(), built_with);
+        let heights = ScriptLineHeights::checked(2.5, 3.0).unwrap();
+        second.set_line_heights(heights);
+        assert_eq!(second.line_heights(), heights);
+        assert_eq!(factors(&second), [2.5, 3.0, 1.0]);
+        assert_eq!(first.line_heights(), built_with);
+        assert_eq!(factors(&first), [built_with.cjk, built_with.emoji, 1.0]);
+        assert_eq!(
+            second
+                .layout_range(0, u32::MAX, 16.0, 1.5, 8.0, Align::Left)
+                .lines,
+            second.layout(16.0, 1.5, 8.0, Align::Left)
+        );
+    }
+}
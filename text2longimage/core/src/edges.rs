@@ -0,0 +1,232 @@
+//! Which characters the wrapper leaves at the edges of lines, for tuning kinsoku
+//! Whether a corpus needs kinsoku rules, or extra no-start characters, shows in where its soft
+//! breaks fall. edge_char_report counts the last character before every break the wrapper made
+//! and the first character after it, flagging the ones the locale's rules forbid there, so a
+//! settings panel can say "27 lines start with 。 — enable kinsoku?". Breaks at a line break in
+//! the source are left out: nothing there is the wrapper's doing.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+#[cfg(feature = "json")]
+use crate::{check_max_chars_per_line, BreakOptions, CjkLocale, WebtoolsError};
+use crate::{justify_text_cjk_with_rules, KinsokuRules, WrapIter};
+
+/// How many soft-wrapped lines start (or end) with one character
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeChar {
+    pub character: char,
+    pub count: usize,
+    /// The rules keep this character from that edge of a line
+    pub forbidden: bool,
+}
+
+/// Characters at the soft breaks of wrapped text, each histogram most frequent first
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeCharReport {
+    /// Tag of the locale whose rules forbidden is checked against
+    pub locale: &'static str,
+    /// The text was wrapped with those rules (justify_text_cjk_locale) rather than by
+    /// justify_text
+    pub kinsoku: bool,
+    /// Lines the wrapper broke
+    pub soft_breaks: usize,
+    /// Lines after a soft break starting with a forbidden character
+    pub forbidden_starts: usize,
+    /// Lines before a soft break ending with a forbidden character
+    pub forbidden_ends: usize,
+    /// First characters of the lines after a soft break
+    pub line_starts: Vec<EdgeChar>,
+    /// Last characters of the lines before a soft break
+    pub line_ends: Vec<EdgeChar>,
+}
+
+impl EdgeCharReport {
+    /// The edges of text wrapped at max_chars_per_line, by justify_text, or by
+    /// justify_text_cjk_with_rules with rules when kinsoku is set
+    pub fn of(text: &str, max_chars_per_line: u32, rules: &KinsokuRules, kinsoku: bool) -> Self {
+        let mut starts = BTreeMap::new();
+        let mut ends = BTreeMap::new();
+        let mut soft_breaks = 0;
+        let mut soft_break = |before: &str, after: &str| {
+            soft_breaks += 1;
+            if let Some(c) = before.chars().next_back() {
+                *ends.entry(c).or_insert(0) += 1;
+            }
+            if let Some(c) = after.chars().next() {
+                *starts.entry(c).or_insert(0) += 1;
+            }
+        };
+
+        if kinsoku {
+            for line in text.split('\n') {
+                let wrapped = justify_text_cjk_with_rules(line, max_chars_per_line, rules);
+                let lines: Vec<&str> = wrapped.split("\r\n").collect();
+                for pair in lines.windows(2) {
+                    soft_break(pair[0], pair[1]);
+                }
+            }
+        } else {
+            let mut previous: Option<String> = None;
+            for line in WrapIter::new(text, max_chars_per_line) {
+                let line_text = line.text(text);
+                if let Some(before) = previous.take() {
+                    soft_break(&before, &line_text);
+                }
+                if !line.hard_break {
+                    previous = Some(line_text.into_owned());
+                }
+            }
+        }
+
+        let line_starts = histogram(starts, |c| rules.forbids_start(c));
+        let line_ends = histogram(ends, |c| rules.forbids_end(c));
+        let forbidden = |edges: &[EdgeChar]| -> usize {
+            edges
+                .iter()
+                .filter(|edge| edge.forbidden)
+                .map(|edge| edge.count)
+                .sum()
+        };
+        EdgeCharReport {
+            locale: rules.locale().tag(),
+            kinsoku,
+            soft_breaks,
+            forbidden_starts: forbidden(&line_starts),
+            forbidden_ends: forbidden(&line_ends),
+            line_starts,
+            line_ends,
+        }
+    }
+}
+
+/// counts as EdgeChars, most frequent first and in code point order among equals
+fn histogram(counts: BTreeMap<char, usize>, forbidden: impl Fn(char) -> bool) -> Vec<EdgeChar> {
+    let mut edges: Vec<EdgeChar> = counts
+        .into_iter()
+        .map(|(character, count)| EdgeChar {
+            character,
+            count,
+            forbidden: forbidden(character),
+        })
+        .collect();
+    // Stable, so equal counts keep the map's code point order
+    edges.sort_by_key(|edge| std::cmp::Reverse(edge.count));
+    edges
+}
+
+#[cfg(feature = "json")]
+/// First and last characters of the lines text wraps into at max_chars_per_line around each
+/// soft break, with the ones kinsoku rules forbid there flagged
+/// options_json: {locale, extraNoStart, extraNoEnd}, all optional, as break_opportunities
+/// takes them. With a locale the text is wrapped with its rules, as justify_text_cjk_locale
+/// wraps it; without one by justify_text, and flagged against the built-in rules of the
+/// locale detect_cjk_locale finds, to show what kinsoku would change.
+/// Returns JSON {locale, kinsoku, softBreaks, forbiddenStarts, forbiddenEnds, lineStarts,
+/// lineEnds}, each histogram entry {character, count, forbidden}
+pub fn edge_char_report(
+    text: &str,
+    max_chars_per_line: u32,
+    options_json: &str,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let report = match BreakOptions::from_json(options_json)?.rules(text)? {
+        Some(rules) => EdgeCharReport::of(text, max_chars_per_line, &rules, true),
+        None => {
+            let rules = KinsokuRules::new(CjkLocale::Auto.resolve(text), "", "");
+            EdgeCharReport::of(text, max_chars_per_line, &rules, false)
+        }
+    };
+    Ok(serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{justify_text, justify_text_cjk_locale, CjkLocale};
+
+    /// Seven CJK characters to a line with kinsoku off, five at width 10
+    const EDGE_TEXT: &str = "今日は晴れ。明日も晴れ。";
+
+    fn edge(character: char, count: usize, forbidden: bool) -> EdgeChar {
+        EdgeChar {
+            character,
+            count,
+            forbidden,
+        }
+    }
+
+    #[test]
+    fn without_kinsoku_the_breaks_are_flagged_by_the_rules() {
+        // 今日は晴れ | 。明日も晴 | れ。: the second line starts with 。
+        assert_eq!(
+            justify_text(EDGE_TEXT, 10),
+            "今日は晴れ\r\n。明日も晴\r\nれ。"
+        );
+        let rules = KinsokuRules::new(CjkLocale::Ja, "", "");
+        let text = format!("{EDGE_TEXT}\n{EDGE_TEXT}");
+        let report = EdgeCharReport::of(&text, 10, &rules, false);
+        assert_eq!(
+            report,
+            EdgeCharReport {
+                locale: "ja",
+                kinsoku: false,
+                soft_breaks: 4,
+                forbidden_starts: 2,
+                forbidden_ends: 0,
+                line_starts: vec![edge('。', 2, true), edge('れ', 2, false)],
+                line_ends: vec![edge('れ', 2, false), edge('晴', 2, false)],
+            }
+        );
+    }
+
+    #[test]
+    fn with_kinsoku_the_break_moves_back_a_character() {
+        // 今日は晴 | れ。明日も | 晴れ。
+        assert_eq!(
+            justify_text_cjk_locale(EDGE_TEXT, 10, "ja", "", "").unwrap(),
+            "今日は晴\r\nれ。明日も\r\n晴れ。"
+        );
+        let rules = KinsokuRules::new(CjkLocale::Ja, "", "");
+        let report = EdgeCharReport::of(EDGE_TEXT, 10, &rules, true);
+        assert_eq!(
+            report,
+            EdgeCharReport {
+                locale: "ja",
+                kinsoku: true,
+                soft_breaks: 2,
+                forbidden_starts: 0,
+                forbidden_ends: 0,
+                line_starts: vec![edge('れ', 1, false), edge('晴', 1, false)],
+                line_ends: vec![edge('も', 1, false), edge('晴', 1, false)],
+            }
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn options_pick_the_rules_and_are_checked() {
+        let report = |options_json: &str| -> serde_json::Value {
+            serde_json::from_str(&edge_char_report(EDGE_TEXT, 10, options_json).unwrap()).unwrap()
+        };
+        // Without a locale the rules are those of the one detected
+        assert_eq!(report("")["locale"], "ja");
+        assert_eq!(report("")["kinsoku"], false);
+        assert_eq!(report(r#"{"locale":"ja"}"#)["kinsoku"], true);
+        // An extra no-end character shows as forbidden where the wrapper could not avoid it
+        let extra = report(r#"{"locale":"ja","extraNoEnd":"晴"}"#);
+        let line_ends = extra["lineEnds"].as_array().unwrap();
+        assert!(!line_ends.is_empty());
+        for edge in line_ends {
+            assert_eq!(edge["forbidden"], edge["character"] == "晴", "{edge}");
+        }
+        for options_json in [r#"{"locale":"fr"}"#, r#"{"kinsoku":true}"#] {
+            assert!(edge_char_report(EDGE_TEXT, 10, options_json).is_err());
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Transformations as lists of edits, for editors that keep cursors and undo history
+//! A collaborative editor that swaps in the whole string convert_punctuation returns loses
+//! every cursor and undo step. The `*_edits` variants return the same change as a list of
+//! {start, end, replacement} edits in char indices of the original text, ascending and never
+//! touching, so the editor can apply them one by one. The edits are recorded while the
+//! transformation runs (EditRecorder), not diffed afterwards, and the string-returning
+//! variant writes through the same recorder, so applying the edits always gives its output.
+
+use serde::Serialize;
+
+/// Replace the chars start..end of the original text with replacement
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// The output of a transformation, written piece by piece, and the edits that turn its input
+/// into it
+/// The transformation consumes its input from the start: keep copies input chars to the
+/// output, replace swaps some for other text. Edits that meet are merged into one. Without
+/// record only the output is built.
+pub(crate) struct EditRecorder {
+    pub(crate) text: String,
+    edits: Vec<TextEdit>,
+    record: bool,
+    /// Input chars consumed so far
+    position: usize,
+    /// Chars kept since the last edit, which unkeep can give back
+    kept_tail: usize,
+}
+
+impl EditRecorder {
+    pub(crate) fn new(capacity: usize, record: bool) -> Self {
+        EditRecorder {
+            text: String::with_capacity(capacity),
+            edits: Vec::new(),
+            record,
+            position: 0,
+            kept_tail: 0,
+        }
+    }
+
+    /// Copy c, the next input char, to the output
+    pub(crate) fn keep_char(&mut self, c: char) {
+        self.text.push(c);
+        self.position += 1;
+        self.kept_tail += 1;
+    }
+
+    /// Copy text, the next input chars, to the output
+    pub(crate) fn keep(&mut self, text: &str) {
+        self.text.push_str(text);
+        let chars = text.chars().count();
+        self.position += chars;
+        self.kept_tail += chars;
+    }
+
+    /// Consume the next len input chars, writing replacement in their place; len 0 inserts
+    pub(crate) fn replace(&mut self, len: usize, replacement: &str) {
+        if len == 0 && replacement.is_empty() {
+            return;
+        }
+        self.text.push_str(replacement);
+        let start = self.position;
+        self.position += len;
+        let kept_tail = std::mem::take(&mut self.kept_tail);
+        if !self.record {
+            return;
+        }
+        match self.edits.last_mut() {
+            Some(last) if last.end == start && kept_tail == 0 => {
+                last.end = self.position;
+                last.replacement.push_str(replacement);
+            }
+            _ => self.edits.push(TextEdit {
+                start,
+                end: self.position,
+                replacement: replacement.to_string(),
+            }),
+        }
+    }
+
+    /// Give back the kept c at the end of the output: it is removed, and the next replace
+    /// consumes it again; the number given back
+    pub(crate) fn unkeep(&mut self, c: char) -> usize {
+        let mut count = 0;
+        while self.kept_tail > 0 && self.text.ends_with(c) {
+            self.text.pop();
+            self.position -= 1;
+            self.kept_tail -= 1;
+            count += 1;
+        }
+        count
+    }
+
+    /// The output and, when recording, its edits
+    pub(crate) fn finish(self) -> (String, Vec<TextEdit>) {
+        (self.text, self.edits)
+    }
+}
+
+/// Byte offsets of char indices of one text, looked up in ascending order in one pass
+struct CharOffsets<'a> {
+    text: &'a str,
+    chars: usize,
+    byte: usize,
+}
+
+impl<'a> CharOffsets<'a> {
+    fn new(text: &'a str) -> Self {
+        CharOffsets {
+            text,
+            chars: 0,
+            byte: 0,
+        }
+    }
+
+    fn byte_at(&mut self, index: usize) -> usize {
+        // Lookups go forward, except that a cluster may ask twice for where it starts
+        if index < self.chars {
+            self.chars = 0;
+            self.byte = 0;
+        }
+        let skipped = self.text[self.byte..]
+            .char_indices()
+            .nth(index - self.chars)
+            .map_or(self.text.len(), |(offset, _)| self.byte + offset);
+        self.chars = index;
+        self.byte = skipped;
+        skipped
+    }
+
+    fn slice(&mut self, start: usize, end: usize) -> &'a str {
+        let start = self.byte_at(start);
+        let end = self.byte_at(end);
+        &self.text[start..end]
+    }
+}
+
+/// The edits of two transformations one after the other: first turns the original text into
+/// intermediate, second turns intermediate into the result
+/// Edits of second that meet edits of first are merged with them, so every edit is in char
+/// indices of the original text.
+pub(crate) fn compose_edits(
+    first: Vec<TextEdit>,
+    second: Vec<TextEdit>,
+    intermediate: &str,
+) -> Vec<TextEdit> {
+    if second.is_empty() {
+        return first;
+    }
+    if first.is_empty() {
+        return second;
+    }
+    // The range each edit of first covers in intermediate, and how far it shifts what follows
+    let mut shift = 0isize;
+    let first: Vec<(usize, usize, isize)> = first
+        .iter()
+        .map(|edit| {
+            let start = (edit.start as isize + shift) as usize;
+            let end = start + edit.replacement.chars().count();
+            shift += end as isize - start as isize - (edit.end - edit.start) as isize;
+            (start, end, shift)
+        })
+        .collect();
+
+    let mut offsets = CharOffsets::new(intermediate);
+    let mut composed = Vec::with_capacity(first.len() + second.len());
+    let (mut f, mut s) = (0, 0);
+    // The shift of the edits of first before the current cluster
+    let mut shift_before = 0isize;
+    while f < first.len() || s < second.len() {
+        // A cluster: edits of either list whose intermediate ranges touch
+        let starts_with_first =
+            s == second.len() || f < first.len() && first[f].0 <= second[s].start;
+        let (start, mut end) = if starts_with_first {
+            (first[f].0, first[f].1)
+        } else {
+            (second[s].start, second[s].end)
+        };
+        let (f_start, s_start) = (f, s);
+        loop {
+            if f < first.len() && first[f].0 <= end {
+                end = end.max(first[f].1);
+                f += 1;
+            } else if s < second.len() && second[s].start <= end {
+                end = end.max(second[s].end);
+                s += 1;
+            } else {
+                break;
+            }
+        }
+        let shift_after = if f > f_start {
+            first[f - 1].2
+        } else {
+            shift_before
+        };
+
+        // The cluster's intermediate text with second's edits applied
+        let mut replacement = String::new();
+        let mut copied = start;
+        for edit in &second[s_start..s] {
+            replacement.push_str(offsets.slice(copied, edit.start));
+            replacement.push_str(&edit.replacement);
+            copied = edit.end;
+        }
+        replacement.push_str(offsets.slice(copied, end));
+
+        composed.push(TextEdit {
+            start: (start as isize - shift_before) as usize,
+            end: (end as isize - shift_after) as usize,
+            replacement,
+        });
+        shift_before = shift_after;
+    }
+    composed
+}
+
+/// text with edits applied; edits are in char indices of text, ascending and not overlapping
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut offsets = CharOffsets::new(text);
+    let mut applied = String::with_capacity(text.len());
+    let mut copied = 0;
+    for edit in edits {
+        applied.push_str(offsets.slice(copied, edit.start));
+        applied.push_str(&edit.replacement);
+        copied = edit.end;
+    }
+    applied.push_str(&text[offsets.byte_at(copied)..]);
+    applied
+}
+
+#[cfg(feature = "json")]
+/// edits as JSON: array of {start, end, replacement}
+pub(crate) fn edits_json(edits: &[TextEdit]) -> String {
+    serde_json::to_string(edits).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::{clean_text, clean_text_edits, convert_punctuation, convert_punctuation_edits};
+
+    /// Texts whose edit lists cover every kind of edit: a dropped space before a conversion,
+    /// runs of conversions that merge, an inserted space, ANSI sequences and line breaks rewritten
+    const EDIT_EXAMPLES: [&str; 5] = [
+        "你好 , world",
+        "价格:100,好吗?是的!",
+        "你好，world。Next，line",
+        "a\u{FEFF}b\r\nc\x1b[31md\x1b[0m\re",
+        "cafe\u{301}  \t\n\n\n\nend",
+    ];
+
+    /// edits_json parsed back, checked ascending, never touching and inside text
+    fn parse_edits(text: &str, edits_json: &str) -> Vec<TextEdit> {
+        let edits: Vec<serde_json::Value> = serde_json::from_str(edits_json).unwrap();
+        let edits: Vec<TextEdit> = edits
+            .iter()
+            .map(|edit| TextEdit {
+                start: edit["start"].as_u64().unwrap() as usize,
+                end: edit["end"].as_u64().unwrap() as usize,
+                replacement: edit["replacement"].as_str().unwrap().to_string(),
+            })
+            .collect();
+        let char_count = text.chars().count();
+        assert!(
+            edits
+                .iter()
+                .all(|edit| edit.start <= edit.end && edit.end <= char_count)
+                && edits.windows(2).all(|pair| pair[0].end < pair[1].start),
+            "edits of {text:?}: {edits:?}"
+        );
+        edits
+    }
+
+    #[test]
+    fn punctuation_edits_give_convert_punctuation() {
+        for text in EDIT_EXAMPLES {
+            for target in ["cjk", "ascii"] {
+                let edits = parse_edits(text, &convert_punctuation_edits(text, target).unwrap());
+                assert_eq!(
+                    apply_edits(text, &edits),
+                    convert_punctuation(text, target).unwrap(),
+                    "{target} edits of {text:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clean_edits_give_clean_text() {
+        for text in EDIT_EXAMPLES {
+            for options in [
+                "",
+                r#"{"stripAnsi": true}"#,
+                r#"{"normalizeNewlinesTo": "crlf"}"#,
+                r#"{"stripAnsi": true, "normalizeNewlinesTo": "keep", "stripBom": false}"#,
+            ] {
+                let edits = parse_edits(text, &clean_text_edits(text, options).unwrap());
+                assert_eq!(
+                    apply_edits(text, &edits),
+                    clean_text(text, options).unwrap(),
+                    "{options} edits of {text:?}"
+                );
+            }
+        }
+    }
+}
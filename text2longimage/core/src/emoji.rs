@@ -0,0 +1,253 @@
+//! Where emoji sit in wrapped text, for frontends that draw them as images
+//! Canvas fillText renders emoji differently on every platform, so the frontend can skip them
+//! and draw an image instead. Each emoji grapheme (a ZWJ sequence, flag pair, keycap or an
+//! emoji with its modifiers) is one placement at the x offset char_advances gives its first
+//! character; it is as wide as the columns the wrapper measured for it.
+
+use serde::Serialize;
+
+use crate::layout::column_width_px;
+use crate::width_of;
+
+pub(crate) const ZWJ: char = '\u{200D}';
+/// Emoji presentation selector
+pub(crate) const VS16: char = '\u{FE0F}';
+/// Text presentation selector
+pub(crate) const VS15: char = '\u{FE0E}';
+const KEYCAP: char = '\u{20E3}';
+
+/// Emoji that take part in ZWJ sequences (an approximation of Extended_Pictographic)
+pub(crate) fn is_pictographic(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x00A9
+            | 0x00AE
+            | 0x203C
+            | 0x2049
+            | 0x2122
+            | 0x2139
+            | 0x2194..=0x21AA
+            | 0x2300..=0x23FF
+            | 0x25A0..=0x27BF
+            | 0x2934..=0x2935
+            | 0x2B00..=0x2BFF
+            | 0x3030
+            | 0x303D
+            | 0x3297
+            | 0x3299
+            | 0x1F000..=0x1FAFF
+    )
+}
+
+/// Characters VS15 and VS16 switch between text and emoji presentation: the pictographs, and
+/// the digits, '#' and '*' keycaps start with
+pub(crate) fn takes_presentation_selector(c: char) -> bool {
+    is_pictographic(c) || c.is_ascii_digit() || c == '#' || c == '*'
+}
+
+/// What may sit right before the ZWJ of an emoji sequence: an emoji, VS16 or a skin tone
+pub(crate) fn ends_emoji(c: char) -> bool {
+    is_pictographic(c) || c == '\u{FE0F}'
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// Skin tone modifiers
+fn is_modifier(c: char) -> bool {
+    matches!(c as u32, 0x1F3FB..=0x1F3FF)
+}
+
+/// Tag characters, which spell out subdivision flags such as England's
+fn is_tag(c: char) -> bool {
+    matches!(c as u32, 0xE0020..=0xE007F)
+}
+
+/// Length in chars of the emoji grapheme starting at chars[start], if one does
+pub(crate) fn emoji_len(chars: &[char], start: usize) -> Option<usize> {
+    let c = chars[start];
+    let next = chars.get(start + 1).copied();
+    if is_regional_indicator(c) {
+        return Some(if next.is_some_and(is_regional_indicator) {
+            2
+        } else {
+            1
+        });
+    }
+    if c.is_ascii_digit() || c == '#' || c == '*' {
+        let keycap = if next == Some(VS16) {
+            start + 2
+        } else {
+            start + 1
+        };
+        return (chars.get(keycap) == Some(&KEYCAP)).then_some(keycap + 1 - start);
+    }
+    // Older pictographs (©, ™, arrows) default to text presentation; VS16 makes them emoji
+    if !is_pictographic(c) || (c as u32) < 0x1F000 && next != Some(VS16) {
+        return None;
+    }
+
+    let mut end = start + 1;
+    loop {
+        match chars.get(end) {
+            Some(&c) if c == VS16 || c == KEYCAP || is_modifier(c) || is_tag(c) => end += 1,
+            Some(&ZWJ) if chars.get(end + 1).copied().is_some_and(is_pictographic) => end += 2,
+            _ => break,
+        }
+    }
+    Some(end - start)
+}
+
+/// Start of the emoji grapheme chars[index] is in, or index when it isn't in one
+/// Graphemes are found from the start of chars, which has to be the start of one.
+pub(crate) fn emoji_grapheme_start(chars: &[char], index: usize) -> usize {
+    let mut start = 0;
+    while start < index {
+        let len = emoji_len(chars, start).unwrap_or(1);
+        if start + len > index {
+            return start;
+        }
+        start += len;
+    }
+    index
+}
+
+/// Whether line holds an emoji grapheme, as emoji_placements finds them
+pub(crate) fn has_emoji(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().collect();
+    (0..chars.len()).any(|i| emoji_len(&chars, i).is_some())
+}
+
+/// One emoji grapheme in wrapped text
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EmojiPlacement {
+    pub line_index: usize,
+    /// Offset from the line start, as char_advances gives it
+    pub x_px: f64,
+    /// Space the layout leaves for it: two columns for a single emoji, the columns of all its
+    /// code points for a sequence
+    pub width_px: f64,
+    /// The grapheme's code points, e.g. for looking up a Twemoji image
+    pub code_points: Vec<u32>,
+}
+
+/// Every emoji grapheme of already wrapped text (CRLF or LF separated), line by line
+pub fn emoji_placements(wrapped_text: &str, font_size_px: f64) -> Vec<EmojiPlacement> {
+    let column_px = column_width_px(font_size_px);
+    let mut placements = Vec::new();
+
+    for (line_index, line) in wrapped_text.split('\n').enumerate() {
+        let chars: Vec<char> = line.strip_suffix('\r').unwrap_or(line).chars().collect();
+        let mut columns = 0u32;
+        let mut i = 0;
+        while i < chars.len() {
+            let len = emoji_len(&chars, i).unwrap_or(0);
+            let grapheme = &chars[i..i + len.max(1)];
+            let width: u32 = grapheme.iter().map(|&c| width_of(c)).sum();
+            if len > 0 {
+                placements.push(EmojiPlacement {
+                    line_index,
+                    x_px: columns as f64 * column_px,
+                    width_px: width as f64 * column_px,
+                    code_points: grapheme.iter().map(|&c| c as u32).collect(),
+                });
+            }
+            columns += width;
+            i += grapheme.len();
+        }
+    }
+
+    placements
+}
+
+#[cfg(feature = "json")]
+/// emoji_placements as JSON: array of {lineIndex, xPx, widthPx, codePoints}
+pub fn emoji_positions(wrapped_text: &str, font_size_px: f64) -> String {
+    serde_json::to_string(&emoji_placements(wrapped_text, font_size_px))
+        .unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::char_advances;
+
+    #[test]
+    fn placements_line_up_with_char_advances() {
+        let line = "Hi 😀 fam 👨\u{200D}👩\u{200D}👧 flag 🇯🇵!";
+        let placements = emoji_placements(line, 16.0);
+        let advances = char_advances(line, 16.0);
+        let first_chars: Vec<usize> = ["😀", "👨", "🇯"]
+            .iter()
+            .map(|emoji| line[..line.find(emoji).unwrap()].chars().count())
+            .collect();
+        assert_eq!(placements.len(), 3);
+        for (placement, &index) in placements.iter().zip(&first_chars) {
+            assert_eq!(placement.x_px, advances[index] as f64);
+            assert_eq!(placement.line_index, 0);
+        }
+        // Two columns of 8 px for 😀; the family's three emoji and two joiners are ten columns,
+        // the flag's two regional indicators four
+        let widths: Vec<f64> = placements.iter().map(|p| p.width_px).collect();
+        assert_eq!(widths, [16.0, 80.0, 32.0]);
+        assert_eq!(
+            placements[1].code_points,
+            [0x1F468, 0x200D, 0x1F469, 0x200D, 0x1F467]
+        );
+        assert_eq!(placements[2].code_points, [0x1F1EF, 0x1F1F5]);
+    }
+
+    #[test]
+    fn every_line_of_wrapped_text() {
+        let placements = emoji_placements("a😀\r\n\r\n中👍🏽", 10.0);
+        let found: Vec<(usize, f64, Vec<u32>)> = placements
+            .into_iter()
+            .map(|p| (p.line_index, p.x_px, p.code_points))
+            .collect();
+        assert_eq!(
+            found,
+            [(0, 5.0, vec![0x1F600]), (2, 10.0, vec![0x1F44D, 0x1F3FD])]
+        );
+    }
+
+    #[test]
+    fn text_presentation_characters_are_not_emoji() {
+        // © and digits are text unless VS16 or a keycap makes them emoji
+        assert!(emoji_placements("© 2024 #1", 16.0).is_empty());
+        let codes = |text: &str| -> Vec<Vec<u32>> {
+            emoji_placements(text, 16.0)
+                .into_iter()
+                .map(|p| p.code_points)
+                .collect()
+        };
+        assert_eq!(codes("©\u{FE0F}"), [vec![0xA9, 0xFE0F]]);
+        assert_eq!(codes("1\u{FE0F}\u{20E3}"), [vec![0x31, 0xFE0F, 0x20E3]]);
+        assert_eq!(
+            codes("🏴\u{E0067}\u{E0062}\u{E0065}\u{E006E}\u{E0067}\u{E007F}").len(),
+            1
+        );
+        // A lone regional indicator is still one placement
+        assert_eq!(codes("🇯x"), [vec![0x1F1EF]]);
+    }
+
+    #[test]
+    fn grapheme_starts() {
+        let chars: Vec<char> = "a👨\u{200D}👩b".chars().collect();
+        assert_eq!(emoji_grapheme_start(&chars, 3), 1);
+        assert_eq!(emoji_grapheme_start(&chars, 4), 4);
+        assert_eq!(emoji_grapheme_start(&chars, 0), 0);
+        assert!(has_emoji("ok 👍") && !has_emoji("ok ✓"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn positions_json() {
+        assert_eq!(
+            emoji_positions("😀", 16.0),
+            r#"[{"lineIndex":0,"xPx":0.0,"widthPx":16.0,"codePoints":[128512]}]"#
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! What the APIs do with empty and whitespace-only text
+//! Empty input is not an error anywhere but the validators: it wraps to "" and has no lines.
+//! Whitespace without a '\n' counts as empty; whitespace with line breaks is blank lines,
+//! which the wrappers keep as they would in any other text.
+//!
+//! For a text that is_empty_input:
+//! - every justify_* function, the batch items and process_text_chunks give ""
+//! - count_wrapped_lines gives 0; line_widths, layout, pagination and previews give no lines
+//! - get_text_stats has lineCount 0, and charCount, byteCount, asciiCount and displayWidth
+//!   are those of the whitespace (all zero for "")
+//! - validate_text and validate_input report EMPTY_INPUT
+//!
+//! Otherwise a whitespace-only text with n '\n's wraps to n + 1 blank lines ("\r\n" repeated
+//! n times), except in justify_text_english, which joins lines and so gives "" for any
+//! whitespace.
+
+/// No visible character and no '\n': the text every API treats like ""
+pub fn is_empty_input(text: &str) -> bool {
+    text.trim().is_empty() && !text.contains('\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        count_wrapped_lines, count_wrapped_lines_of_output, justify_text, justify_text_cjk,
+        justify_text_cjk_locale, justify_text_english, justify_text_for_image,
+        justify_text_fractional, justify_text_html, justify_text_utf16, line_widths,
+        process_text_chunks, validate_text, wrapped_lines, Document, JustifyOptions, WebtoolsError,
+    };
+
+    /// Blank texts, whether they are empty input, and what they wrap to at any width
+    const BLANK_TEXTS: &[(&str, bool, &str)] = &[
+        ("", true, ""),
+        (" ", true, ""),
+        ("\n", false, "\r\n"),
+        ("\r\n", false, "\r\n"),
+        ("\u{3000}", true, ""),
+    ];
+
+    #[test]
+    fn every_wrapper_agrees_on_blank_text() {
+        let width = 20;
+        for &(text, empty, expected) in BLANK_TEXTS {
+            assert_eq!(is_empty_input(text), empty, "{text:?}");
+            let units: Vec<u16> = text.encode_utf16().collect();
+            let document = Document::new(text.to_string(), JustifyOptions::default());
+            for (what, output) in [
+                ("justify_text", justify_text(text, width)),
+                ("justify_text_cjk", justify_text_cjk(text, width)),
+                (
+                    "justify_text_fractional",
+                    justify_text_fractional(text, 10.0),
+                ),
+                (
+                    "justify_text_cjk_locale",
+                    justify_text_cjk_locale(text, width, "ja", "", "").unwrap(),
+                ),
+                (
+                    "JustifyOptions::justify",
+                    JustifyOptions::default().justify(text),
+                ),
+                (
+                    "justify_text_for_image",
+                    justify_text_for_image(text, 400.0, 20.0, 0.0),
+                ),
+                (
+                    "justify_text_utf16",
+                    String::from_utf16_lossy(&justify_text_utf16(&units, width)),
+                ),
+                (
+                    "Document::wrapped_text",
+                    document.wrapped_text().to_string(),
+                ),
+                (
+                    "process_text_chunks",
+                    process_text_chunks(text, width, 0).unwrap(),
+                ),
+            ] {
+                assert_eq!(output, expected, "{what} of {text:?}");
+            }
+            // justify_text_english joins lines, so any whitespace gives ""
+            assert_eq!(justify_text_english(text, width), "", "{text:?}");
+            assert_eq!(
+                justify_text_html(text, width),
+                "<br>".repeat(expected.len() / 2),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_input_has_no_lines_and_blank_lines_are_kept() {
+        let width = 20;
+        for &(text, empty, expected) in BLANK_TEXTS {
+            let lines = if empty { 0 } else { expected.len() / 2 + 1 };
+            let document = Document::new(text.to_string(), JustifyOptions::default());
+            for (what, count) in [
+                (
+                    "count_wrapped_lines",
+                    count_wrapped_lines(text, width) as usize,
+                ),
+                (
+                    "count_wrapped_lines_of_output",
+                    count_wrapped_lines_of_output(expected) as usize,
+                ),
+                ("wrapped_lines", wrapped_lines(text, width).len()),
+                ("Document::line_count", document.line_count() as usize),
+                ("line_widths", line_widths(text).len()),
+            ] {
+                assert_eq!(count, lines, "{what} of {text:?}");
+            }
+            let validated = validate_text(text);
+            if empty {
+                assert_eq!(validated, Err(WebtoolsError::EmptyInput), "{text:?}");
+            } else {
+                assert_eq!(validated, Ok(()), "{text:?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_apis_agree_on_blank_text() {
+        use crate::{batch_justify_text, estimate_image_size, get_text_stats, layout_text};
+
+        let width = 20;
+        for &(text, empty, expected) in BLANK_TEXTS {
+            let lines = if empty { 0 } else { expected.len() / 2 + 1 };
+            let json = serde_json::to_string(&[text]).unwrap();
+            assert_eq!(
+                batch_justify_text(&json, width, false).unwrap(),
+                serde_json::to_string(&[expected]).unwrap(),
+                "batch of {text:?}"
+            );
+            let layout: serde_json::Value =
+                serde_json::from_str(&layout_text(text, width, 16.0, 1.5, 0.0, "left")).unwrap();
+            assert_eq!(layout.as_array().map(Vec::len), Some(lines), "{text:?}");
+            let size: serde_json::Value =
+                serde_json::from_str(&estimate_image_size(text, width, 16.0, 1.5, 0.0)).unwrap();
+            assert_eq!(size["lineCount"], lines, "{text:?}");
+            let stats: serde_json::Value = serde_json::from_str(&get_text_stats(text)).unwrap();
+            assert_eq!(stats["charCount"], text.chars().count(), "{text:?}");
+            assert_eq!(stats["wordCount"], 0, "{text:?}");
+            if empty {
+                assert_eq!(stats["lineCount"], 0, "{text:?}");
+            }
+        }
+    }
+}
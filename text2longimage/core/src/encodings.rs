@@ -0,0 +1,389 @@
+//! Legacy encodings for old .txt files: decoding, and guessing which one a file is in
+//! decode_bytes turns GBK, Big5, Shift_JIS, EUC-KR or Windows-1252 bytes into text, strictly,
+//! so a file read with the wrong label fails at a byte offset instead of turning into
+//! mojibake. detect_encoding guesses the label from the bytes themselves: valid UTF-8 is
+//! UTF-8, and otherwise each legacy encoding decodes the start of the file and is scored by
+//! how much of what it decodes looks like text in its language, from how often the script's
+//! commonest characters turn up. Bytes read in the wrong CJK encoding still decode, but to
+//! rare characters in near-random order, so the right one stands out after a sentence or so.
+
+use encoding_rs::{DecoderResult, Encoding};
+use serde::Serialize;
+
+use crate::capabilities::expected_values;
+use crate::{OptionValue, WebtoolsError};
+
+/// Bytes from the start of the input detect_encoding looks at
+pub const DETECT_SAMPLE_BYTES: usize = 16 * 1024;
+
+/// The 132 commonest characters of simplified Chinese text, in frequency order
+const COMMON_SIMPLIFIED: &str = "的一是不了在人有我他这个们中来上大为和国地到以说时要就出也得里后\
+    自会家可下而过天去能对小多然于心学么之都好看起发当没成只如事把还用第样道想作种开美总从无情己面\
+    最女但现前些所同日手又行意动方期它头经长儿回位分爱老因很给名法间知世什两次使身者被高已亲其进此\
+    话常与活正感";
+
+/// The same characters in traditional Chinese
+const COMMON_TRADITIONAL: &str = "的一是不了在人有我他這個們中來上大為和國地到以說時要就出也得裡後\
+    自會家可下而過天去能對小多然於心學麼之都好看起發當沒成只如事把還用第樣道想作種開美總從無情己面\
+    最女但現前些所同日手又行意動方期它頭經長兒回位分愛老因很給名法間知世什兩次使身者被高已親其進此\
+    話常與活正感";
+
+/// Kanji taught in the first grades of Japanese school, which most Japanese text is full of
+const COMMON_KANJI: &str = "日一国人年大十二本中長出三時行見月分後前生五間上東四今金九入学高円子\
+    外八六下来気小七山話女北午百書先名川千水半男西電校語土木聞食車何南万毎白天母火右読友左休父雨会\
+    社事自者発地業方新場員立開手力問代明動京目通言理体田主題意不作用度強公持野以思家世多正安院心界\
+    教文元重近考画海売知道集別物使品計死特私始朝運終台広住真有口少町料工建空急止送切転研足究楽起着\
+    店病質待試族銀早映親験英医仕去味写字答夜音注帰古歌買悪図週室歩風紙黒花春赤青館屋色走秋夏習駅洋\
+    旅服夕借曜飲肉貸堂鳥飯勉冬昼茶弟牛魚兄犬妹姉漢";
+
+/// The commonest Hangul syllables of Korean text
+const COMMON_HANGUL: &str = "이다는의에하고을가지기서로한도사리자수어대시나인아있들정를게해부전주\
+    일만내보요구그으과제적경우라면여상니까국성소장화거문또원방동신세비위했되것분모말생실연중미무개\
+    마각학할와오금저없년때람래떻많";
+
+/// Non-ASCII Windows-1252 characters Western text uses: accented letters and typographic
+/// punctuation
+const COMMON_WESTERN: &str = "‘’“”–—…€•°©®™«»¡¿£§·";
+
+/// The encodings decode_bytes reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// Simplified Chinese; also reads GB2312 and GB18030
+    #[serde(rename = "gbk")]
+    Gbk,
+    /// Traditional Chinese, with the HKSCS extensions
+    #[serde(rename = "big5")]
+    Big5,
+    /// Japanese, as Windows writes it (code page 932)
+    #[serde(rename = "shift_jis")]
+    ShiftJis,
+    /// Korean, as Windows writes it (code page 949)
+    #[serde(rename = "euc-kr")]
+    EucKr,
+    /// Western European; also reads ISO-8859-1 and ASCII
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// Parse an encoding label as TextDecoder takes it: "gbk", "GB2312", "sjis", "latin1", ...
+    /// Labels of encodings besides these six are rejected
+    pub fn parse(label: &str) -> Result<Self, WebtoolsError> {
+        Encoding::for_label(label.as_bytes())
+            .and_then(|encoding| {
+                TextEncoding::ALL
+                    .iter()
+                    .copied()
+                    .find(|known| known.encoding() == encoding)
+            })
+            .ok_or_else(|| {
+                WebtoolsError::invalid_argument(format!(
+                    "Unknown encoding: {} (expected {})",
+                    label,
+                    expected_values::<TextEncoding>()
+                ))
+            })
+    }
+
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            TextEncoding::Utf8 => encoding_rs::UTF_8,
+            TextEncoding::Gbk => encoding_rs::GBK,
+            TextEncoding::Big5 => encoding_rs::BIG5,
+            TextEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+            TextEncoding::EucKr => encoding_rs::EUC_KR,
+            TextEncoding::Windows1252 => encoding_rs::WINDOWS_1252,
+        }
+    }
+}
+
+impl OptionValue for TextEncoding {
+    const KIND: &'static str = "textEncoding";
+    const USED_BY: &'static [&'static str] = &["decode_bytes", "detect_encoding"];
+    const ALL: &'static [Self] = &[
+        TextEncoding::Utf8,
+        TextEncoding::Gbk,
+        TextEncoding::Big5,
+        TextEncoding::ShiftJis,
+        TextEncoding::EucKr,
+        TextEncoding::Windows1252,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Gbk => "gbk",
+            TextEncoding::Big5 => "big5",
+            TextEncoding::ShiftJis => "shift_jis",
+            TextEncoding::EucKr => "euc-kr",
+            TextEncoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "Unicode",
+            TextEncoding::Gbk => "Simplified Chinese (GBK, GB2312, GB18030)",
+            TextEncoding::Big5 => "Traditional Chinese (Big5, HKSCS)",
+            TextEncoding::ShiftJis => "Japanese (Shift_JIS, code page 932)",
+            TextEncoding::EucKr => "Korean (EUC-KR, code page 949)",
+            TextEncoding::Windows1252 => "Western European (Windows-1252, ISO-8859-1)",
+        }
+    }
+}
+
+/// Decode bytes in the encoding encoding_label names (see TextEncoding::parse)
+/// A byte order mark for the encoding is dropped. Bytes the encoding can't decode are an
+/// error naming the offset where the first such sequence starts: INVALID_UTF8 for "utf-8",
+/// INVALID_ENCODING for the others. Windows-1252 decodes every byte.
+pub fn decode_bytes(bytes: &[u8], encoding_label: &str) -> Result<String, WebtoolsError> {
+    let encoding = TextEncoding::parse(encoding_label)?;
+    let mut decoder = encoding.encoding().new_decoder_with_bom_removal();
+    let mut text = String::new();
+    let mut read = 0;
+    loop {
+        let rest = &bytes[read..];
+        let needed = decoder
+            .max_utf8_buffer_length_without_replacement(rest.len())
+            .unwrap_or(rest.len());
+        text.reserve(needed);
+        let (result, consumed) =
+            decoder.decode_to_string_without_replacement(rest, &mut text, true);
+        read += consumed;
+        match result {
+            DecoderResult::InputEmpty => return Ok(text),
+            DecoderResult::OutputFull => continue,
+            DecoderResult::Malformed(length, after) => {
+                let offset = read - usize::from(length) - usize::from(after);
+                return Err(match encoding {
+                    TextEncoding::Utf8 => WebtoolsError::InvalidUtf8 { offset },
+                    _ => WebtoolsError::InvalidEncoding {
+                        encoding: encoding.name(),
+                        offset,
+                    },
+                });
+            }
+        }
+    }
+}
+
+/// detect_encoding's answer
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EncodingGuess {
+    pub encoding: TextEncoding,
+    /// 0 to 1: 1 for valid UTF-8 (and ASCII, which reads the same in all of them); for a
+    /// legacy encoding, its score less half the runner-up's, so a close call reads low
+    pub confidence: f64,
+}
+
+/// The legacy encodings detect_encoding weighs, in the order ties go
+const LEGACY_ENCODINGS: [TextEncoding; 5] = [
+    TextEncoding::Gbk,
+    TextEncoding::Big5,
+    TextEncoding::ShiftJis,
+    TextEncoding::EucKr,
+    TextEncoding::Windows1252,
+];
+
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c as u32, 0x3000..=0x303F | 0xFF01..=0xFF60)
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c as u32, 0x3041..=0x3096 | 0x30A1..=0x30FA | 0x30FC)
+}
+
+/// Whether c is what text in encoding's language is mostly made of, and the share of a
+/// typical text's non-ASCII characters that are
+fn common_characters(encoding: TextEncoding) -> (fn(char) -> bool, f64) {
+    match encoding {
+        TextEncoding::Gbk => (
+            |c| COMMON_SIMPLIFIED.contains(c) || is_cjk_punctuation(c),
+            0.4,
+        ),
+        TextEncoding::Big5 => (
+            |c| COMMON_TRADITIONAL.contains(c) || is_cjk_punctuation(c),
+            0.45,
+        ),
+        TextEncoding::ShiftJis => (
+            |c| is_kana(c) || COMMON_KANJI.contains(c) || is_cjk_punctuation(c),
+            0.8,
+        ),
+        TextEncoding::EucKr => (|c| COMMON_HANGUL.contains(c) || is_cjk_punctuation(c), 0.6),
+        TextEncoding::Utf8 | TextEncoding::Windows1252 => (|_| false, 1.0),
+    }
+}
+
+/// How much of sample decoded as a CJK encoding looks like text in its language, 0 to 1
+fn cjk_score(sample: &[u8], encoding: TextEncoding) -> f64 {
+    let (common, typical_share) = common_characters(encoding);
+    let (text, _) = encoding.encoding().decode_without_bom_handling(sample);
+    let (mut total, mut hits) = (0usize, 0usize);
+    // U+FFFD, for bytes the encoding can't decode, is never common
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        total += 1;
+        hits += usize::from(common(c));
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    (hits as f64 / total as f64 / typical_share).min(1.0)
+}
+
+/// Share of the non-ASCII bytes of sample that read as accented letters or typographic
+/// punctuation in Windows-1252, counted only in runs of one or two between ASCII: Western text
+/// has the odd "é" or "ß" inside a word, a CJK encoding long runs of high bytes
+fn western_score(sample: &[u8]) -> f64 {
+    let is_common = |c: char| {
+        (('\u{C0}'..='\u{FF}').contains(&c) && c != '×' && c != '÷') || COMMON_WESTERN.contains(c)
+    };
+    let (mut total, mut hits) = (0usize, 0usize);
+    for run in sample.split(u8::is_ascii).filter(|run| !run.is_empty()) {
+        total += run.len();
+        if run.len() <= 2 {
+            let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(run);
+            hits += text.chars().filter(|&c| is_common(c)).count();
+        }
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    hits as f64 / total as f64
+}
+
+/// Best guess at the encoding of bytes, from the first DETECT_SAMPLE_BYTES of them
+/// Valid UTF-8 (with or without a byte order mark) is UTF-8; otherwise the legacy encoding
+/// whose decoding looks most like text in its language, Windows-1252 when none does.
+pub fn detect_encoding(bytes: &[u8]) -> EncodingGuess {
+    let sample = &bytes[..bytes.len().min(DETECT_SAMPLE_BYTES)];
+    // A sample cut inside a character ends in an incomplete sequence, with no error_len
+    let utf8 = match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none() && sample.len() < bytes.len(),
+    };
+    if utf8 || sample.starts_with(b"\xEF\xBB\xBF") {
+        return EncodingGuess {
+            encoding: TextEncoding::Utf8,
+            confidence: 1.0,
+        };
+    }
+
+    let scores = LEGACY_ENCODINGS.map(|encoding| match encoding {
+        TextEncoding::Windows1252 => western_score(sample),
+        _ => cjk_score(sample, encoding),
+    });
+    // Windows-1252, last, decodes anything, so it stands when nothing scores at all
+    let mut best = LEGACY_ENCODINGS.len() - 1;
+    for (index, &score) in scores.iter().enumerate() {
+        if score > scores[best] || (score > 0.0 && score == scores[best] && index < best) {
+            best = index;
+        }
+    }
+    let runner_up = scores
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != best)
+        .map(|(_, &score)| score)
+        .fold(0.0, f64::max);
+    let confidence = (scores[best] - runner_up / 2.0).clamp(0.0, 1.0);
+    EncodingGuess {
+        encoding: LEGACY_ENCODINGS[best],
+        confidence: (confidence * 100.0).round() / 100.0,
+    }
+}
+
+#[cfg(feature = "json")]
+/// detect_encoding as JSON {encoding, confidence}
+pub fn detect_encoding_json(bytes: &[u8]) -> String {
+    serde_json::to_string(&detect_encoding(bytes)).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Files in each encoding decode_bytes reads, under core/fixtures/encodings, with their text
+    const ENCODING_FIXTURES: [(&str, &[u8], &str); 6] = [
+        (
+            "utf-8",
+            include_bytes!("../fixtures/encodings/utf-8.txt"),
+            "UTF-8 with a byte order mark: 漢字, かな, 한글 and café.\n",
+        ),
+        (
+            "gbk",
+            include_bytes!("../fixtures/encodings/gbk.txt"),
+            "简体中文的文本：今天天气很好，我们一起去公园散步吧。\n这是一个编码检测的例子。\n",
+        ),
+        (
+            "big5",
+            include_bytes!("../fixtures/encodings/big5.txt"),
+            "繁體中文的文字：今天天氣很好，我們一起去公園散步吧。\n這是一個編碼偵測的例子。\n",
+        ),
+        (
+            "shift_jis",
+            include_bytes!("../fixtures/encodings/shift_jis.txt"),
+            "これは日本語のテキストです。\n今日はとても良い天気なので、公園を散歩しましょう。\n",
+        ),
+        (
+            "euc-kr",
+            include_bytes!("../fixtures/encodings/euc-kr.txt"),
+            "이것은 한국어 텍스트입니다.\n오늘은 날씨가 아주 좋아서 공원에 산책하러 갑시다.\n",
+        ),
+        (
+            "windows-1252",
+            include_bytes!("../fixtures/encodings/windows-1252.txt"),
+            "Café déjà vu: the naïve façade of the “Hôtel Über”\ncosts 5 €—très chic, n’est-ce pas?\n",
+        ),
+    ];
+
+    /// Bytes decode_bytes can't read, with the label, the error code and its offset
+    const UNDECODABLE_EXAMPLES: [(&[u8], &str, &str, usize); 5] = [
+        (b"ab\xFFcd", "utf-8", "INVALID_UTF8", 2),
+        // 汉 cut after its lead byte
+        (b"ok\xBA", "gbk", "INVALID_ENCODING", 2),
+        (b"\xA4\x40\xFF", "big5", "INVALID_ENCODING", 2),
+        (b"ab\x82\xA0\xFF", "sjis", "INVALID_ENCODING", 4),
+        (b"\xC7\xD1\xB1", "ks_c_5601-1987", "INVALID_ENCODING", 2),
+    ];
+
+    #[test]
+    fn each_fixture_decodes_and_is_detected() {
+        for (label, bytes, text) in ENCODING_FIXTURES {
+            assert_eq!(
+                decode_bytes(bytes, label).as_deref(),
+                Ok(text),
+                "{label} fixture"
+            );
+            let guess = detect_encoding(bytes);
+            assert_eq!(guess.encoding.name(), label, "{label} fixture detected");
+            assert!(guess.confidence >= 0.5, "{label} fixture: {guess:?}");
+        }
+    }
+
+    #[test]
+    fn undecodable_bytes_fail_at_their_offset() {
+        for (bytes, label, code, offset) in UNDECODABLE_EXAMPLES {
+            let error = decode_bytes(bytes, label).expect_err(label);
+            let at = match error {
+                WebtoolsError::InvalidUtf8 { offset }
+                | WebtoolsError::InvalidEncoding { offset, .. } => Some(offset),
+                _ => None,
+            };
+            assert_eq!(
+                (error.code(), at),
+                (code, Some(offset)),
+                "{bytes:?} in {label}"
+            );
+        }
+    }
+
+    #[test]
+    fn labels_are_whatwg_aliases() {
+        assert_eq!(decode_bytes(b"\xE9", "Latin1").as_deref(), Ok("é"));
+        assert!(TextEncoding::parse("utf-16le").is_err());
+    }
+}
@@ -0,0 +1,495 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::BatchId;
+
+/// Errors returned by the fallible core APIs
+/// Each variant has a stable `code()` so callers can branch without matching on messages
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebtoolsError {
+    /// Input that should be JSON could not be parsed
+    InvalidJson { message: String },
+    /// Input bytes are not valid UTF-8; `offset` is the length of the valid prefix
+    InvalidUtf8 { offset: usize },
+    /// Input bytes are not valid in the legacy encoding named by `encoding` (see decode_bytes);
+    /// `offset` is where the first undecodable sequence starts
+    InvalidEncoding {
+        encoding: &'static str,
+        offset: usize,
+    },
+    /// Text input is empty, or whitespace without a line break (see is_empty_input)
+    EmptyInput,
+    /// Text input is larger than the configured maximum, in bytes
+    InputTooLarge { limit: usize, actual: usize },
+    /// Text input has more characters than the given maximum
+    TooManyCharacters { limit: usize, actual: usize },
+    /// One item of a batch is larger than the per-input maximum, in bytes
+    /// `id` is the item's own id, when it was given one (see BatchItem)
+    BatchItemTooLarge {
+        index: usize,
+        limit: usize,
+        actual: usize,
+        id: Option<BatchId>,
+    },
+    /// The items of a batch add up to more than the batch maximum, in bytes
+    BatchTooLarge { limit: usize, actual: usize },
+    /// A batch has more items than max_batch_items()
+    TooManyBatchItems { limit: usize, actual: usize },
+    /// The output grew past max_output_ratio() times the input's size (see expansion.rs) and
+    /// wrapping stopped; `ratio` is output bytes per input byte when it stopped, `line_index`
+    /// the output line it had reached
+    OutputTooLarge {
+        ratio: f64,
+        limit: u32,
+        line_index: usize,
+    },
+    /// Line width below the smallest usable value
+    InvalidWidth { min: u32, actual: u32 },
+    /// Line width above MAX_MAX_CHARS_PER_LINE
+    WidthTooLarge { max: u32, actual: u32 },
+    /// A code point range whose start is after its end
+    InvalidRange { start: u32, end: u32 },
+    /// Any other argument outside the accepted range
+    InvalidArgument { message: String },
+    /// Two options that can't be used together (see OPTION_CONFLICTS)
+    ConflictingOptions {
+        field: &'static str,
+        other: &'static str,
+        message: &'static str,
+    },
+    /// The operation was cancelled before it finished
+    Cancelled,
+    /// A wrapper broke its own postcondition (see postconditions.rs); `line_index` and `line`
+    /// are the offending output line. Only strict-checks builds report it.
+    Internal {
+        message: String,
+        line_index: usize,
+        line: String,
+    },
+}
+
+impl WebtoolsError {
+    /// Stable machine-readable code, e.g. "INPUT_TOO_LARGE"
+    pub fn code(&self) -> &'static str {
+        match self {
+            WebtoolsError::InvalidJson { .. } => "INVALID_JSON",
+            WebtoolsError::InvalidUtf8 { .. } => "INVALID_UTF8",
+            WebtoolsError::InvalidEncoding { .. } => "INVALID_ENCODING",
+            WebtoolsError::EmptyInput => "EMPTY_INPUT",
+            WebtoolsError::InputTooLarge { .. } => "INPUT_TOO_LARGE",
+            WebtoolsError::TooManyCharacters { .. } => "TOO_MANY_CHARACTERS",
+            WebtoolsError::BatchItemTooLarge { .. } => "BATCH_ITEM_TOO_LARGE",
+            WebtoolsError::BatchTooLarge { .. } => "BATCH_TOO_LARGE",
+            WebtoolsError::TooManyBatchItems { .. } => "TOO_MANY_BATCH_ITEMS",
+            WebtoolsError::OutputTooLarge { .. } => "OUTPUT_TOO_LARGE",
+            WebtoolsError::InvalidWidth { .. } => "INVALID_WIDTH",
+            WebtoolsError::WidthTooLarge { .. } => "WIDTH_TOO_LARGE",
+            WebtoolsError::InvalidRange { .. } => "INVALID_RANGE",
+            WebtoolsError::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            WebtoolsError::ConflictingOptions { .. } => "CONFLICTING_OPTIONS",
+            WebtoolsError::Cancelled => "CANCELLED",
+            WebtoolsError::Internal { .. } => "INTERNAL_ERROR",
+        }
+    }
+
+    #[cfg(feature = "json")]
+    pub(crate) fn invalid_json(error: impl fmt::Display) -> Self {
+        WebtoolsError::InvalidJson {
+            message: error.to_string(),
+        }
+    }
+
+    pub(crate) fn invalid_argument(message: impl Into<String>) -> Self {
+        WebtoolsError::InvalidArgument {
+            message: message.into(),
+        }
+    }
+}
+
+/// 500000 -> "500,000"
+pub(crate) fn group_thousands(value: usize) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+impl fmt::Display for WebtoolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebtoolsError::InvalidJson { message } => write!(f, "Invalid JSON: {}", message),
+            WebtoolsError::InvalidUtf8 { offset } => {
+                write!(f, "Invalid UTF-8 at byte offset {}", offset)
+            }
+            WebtoolsError::InvalidEncoding { encoding, offset } => {
+                write!(f, "Invalid {} at byte offset {}", encoding, offset)
+            }
+            WebtoolsError::EmptyInput => f.write_str("Text cannot be empty"),
+            WebtoolsError::InputTooLarge { limit, .. } => write!(
+                f,
+                "Text too large: maximum {} bytes supported",
+                group_thousands(*limit)
+            ),
+            WebtoolsError::TooManyCharacters { limit, .. } => write!(
+                f,
+                "Text too long: maximum {} characters supported",
+                group_thousands(*limit)
+            ),
+            WebtoolsError::BatchItemTooLarge {
+                index, limit, id, ..
+            } => {
+                write!(f, "Batch item {}", index)?;
+                match id {
+                    Some(BatchId::Text(id)) => write!(f, " (id {:?})", id)?,
+                    Some(BatchId::Integer(id)) => write!(f, " (id {})", id)?,
+                    Some(BatchId::Float(id)) => write!(f, " (id {})", id)?,
+                    None => {}
+                }
+                write!(
+                    f,
+                    " too large: maximum {} bytes supported",
+                    group_thousands(*limit)
+                )
+            }
+            WebtoolsError::BatchTooLarge { limit, .. } => write!(
+                f,
+                "Batch too large: maximum {} bytes in total supported",
+                group_thousands(*limit)
+            ),
+            WebtoolsError::TooManyBatchItems { limit, .. } => write!(
+                f,
+                "Batch has too many items: maximum {} supported",
+                group_thousands(*limit)
+            ),
+            WebtoolsError::OutputTooLarge {
+                limit, line_index, ..
+            } => write!(
+                f,
+                "Output too large: over {} times the input's size by output line {}",
+                limit, line_index
+            ),
+            WebtoolsError::InvalidWidth { min, actual } => {
+                write!(f, "Invalid width: {} (minimum {})", actual, min)
+            }
+            WebtoolsError::WidthTooLarge { max, actual } => write!(
+                f,
+                "Width too large: {} (maximum {})",
+                actual,
+                group_thousands(*max as usize)
+            ),
+            WebtoolsError::InvalidRange { start, end } => {
+                write!(f, "Invalid range: {:#X}-{:#X}", start, end)
+            }
+            WebtoolsError::InvalidArgument { message } => f.write_str(message),
+            WebtoolsError::ConflictingOptions { message, .. } => f.write_str(message),
+            WebtoolsError::Cancelled => f.write_str("Operation cancelled"),
+            WebtoolsError::Internal {
+                message,
+                line_index,
+                line,
+            } => write!(
+                f,
+                "Internal error: {} (output line {}: {:?})",
+                message, line_index, line
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WebtoolsError {}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct NoDetails {}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct OffsetDetails {
+    offset: usize,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct EncodingDetails {
+    encoding: &'static str,
+    offset: usize,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct LimitDetails {
+    limit: usize,
+    actual: usize,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct ItemLimitDetails<'a> {
+    index: usize,
+    limit: usize,
+    actual: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a BatchId>,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct RatioDetails {
+    ratio: f64,
+    limit: u32,
+    line_index: usize,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct WidthDetails {
+    min: u32,
+    actual: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct WidthLimitDetails {
+    max: u32,
+    actual: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct RangeDetails {
+    start: u32,
+    end: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct ConflictDetails {
+    field: &'static str,
+    other: &'static str,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct LineDetails<'a> {
+    line_index: usize,
+    line: &'a str,
+}
+
+#[cfg(feature = "schema")]
+/// One {code, message, details} shape per code, in code() order; a new variant needs its row
+impl schemars::JsonSchema for WebtoolsError {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "WebtoolsError".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let limit = generator.subschema_for::<LimitDetails>();
+        let none = generator.subschema_for::<NoDetails>();
+        crate::schema::coded_schema(vec![
+            ("INVALID_JSON", none.clone()),
+            ("INVALID_UTF8", generator.subschema_for::<OffsetDetails>()),
+            (
+                "INVALID_ENCODING",
+                generator.subschema_for::<EncodingDetails>(),
+            ),
+            ("EMPTY_INPUT", none.clone()),
+            ("INPUT_TOO_LARGE", limit.clone()),
+            ("TOO_MANY_CHARACTERS", limit.clone()),
+            (
+                "BATCH_ITEM_TOO_LARGE",
+                generator.subschema_for::<ItemLimitDetails>(),
+            ),
+            ("BATCH_TOO_LARGE", limit.clone()),
+            ("TOO_MANY_BATCH_ITEMS", limit),
+            (
+                "OUTPUT_TOO_LARGE",
+                generator.subschema_for::<RatioDetails>(),
+            ),
+            ("INVALID_WIDTH", generator.subschema_for::<WidthDetails>()),
+            (
+                "WIDTH_TOO_LARGE",
+                generator.subschema_for::<WidthLimitDetails>(),
+            ),
+            ("INVALID_RANGE", generator.subschema_for::<RangeDetails>()),
+            ("INVALID_ARGUMENT", none.clone()),
+            (
+                "CONFLICTING_OPTIONS",
+                generator.subschema_for::<ConflictDetails>(),
+            ),
+            ("CANCELLED", none),
+            ("INTERNAL_ERROR", generator.subschema_for::<LineDetails>()),
+        ])
+    }
+}
+
+/// Serialized as {code, message, details}; details is always an object, empty when the
+/// variant carries nothing beyond its message
+impl Serialize for WebtoolsError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("WebtoolsError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        match *self {
+            WebtoolsError::InvalidUtf8 { offset } => {
+                state.serialize_field("details", &OffsetDetails { offset })?
+            }
+            WebtoolsError::InvalidEncoding { encoding, offset } => {
+                state.serialize_field("details", &EncodingDetails { encoding, offset })?
+            }
+            WebtoolsError::InputTooLarge { limit, actual }
+            | WebtoolsError::TooManyCharacters { limit, actual }
+            | WebtoolsError::BatchTooLarge { limit, actual }
+            | WebtoolsError::TooManyBatchItems { limit, actual } => {
+                state.serialize_field("details", &LimitDetails { limit, actual })?
+            }
+            WebtoolsError::BatchItemTooLarge {
+                index,
+                limit,
+                actual,
+                ref id,
+            } => state.serialize_field(
+                "details",
+                &ItemLimitDetails {
+                    index,
+                    limit,
+                    actual,
+                    id: id.as_ref(),
+                },
+            )?,
+            WebtoolsError::OutputTooLarge {
+                ratio,
+                limit,
+                line_index,
+            } => state.serialize_field(
+                "details",
+                &RatioDetails {
+                    ratio,
+                    limit,
+                    line_index,
+                },
+            )?,
+            WebtoolsError::InvalidWidth { min, actual } => {
+                state.serialize_field("details", &WidthDetails { min, actual })?
+            }
+            WebtoolsError::WidthTooLarge { max, actual } => {
+                state.serialize_field("details", &WidthLimitDetails { max, actual })?
+            }
+            WebtoolsError::InvalidRange { start, end } => {
+                state.serialize_field("details", &RangeDetails { start, end })?
+            }
+            WebtoolsError::ConflictingOptions { field, other, .. } => {
+                state.serialize_field("details", &ConflictDetails { field, other })?
+            }
+            WebtoolsError::Internal {
+                line_index,
+                ref line,
+                ..
+            } => state.serialize_field("details", &LineDetails { line_index, line })?,
+            WebtoolsError::InvalidJson { .. }
+            | WebtoolsError::EmptyInput
+            | WebtoolsError::InvalidArgument { .. }
+            | WebtoolsError::Cancelled => state.serialize_field("details", &NoDetails {})?,
+        }
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::lock_limits;
+    use crate::{
+        check_max_chars_per_line, decode_utf8, validate_text, JustifyOptions,
+        DEFAULT_MAX_INPUT_SIZE,
+    };
+
+    #[test]
+    fn each_failure_mode_has_its_code() {
+        let _limits = lock_limits();
+        let code = |result: Result<(), WebtoolsError>| result.unwrap_err().code();
+        assert_eq!(
+            code(decode_utf8(b"ok\xff", false).map(drop)),
+            "INVALID_UTF8"
+        );
+        assert_eq!(code(validate_text(" \t ")), "EMPTY_INPUT");
+        let large = "a".repeat(DEFAULT_MAX_INPUT_SIZE + 1);
+        assert_eq!(code(validate_text(&large)), "INPUT_TOO_LARGE");
+        assert_eq!(code(check_max_chars_per_line(u32::MAX)), "WIDTH_TOO_LARGE");
+        let narrow = JustifyOptions {
+            max_chars_per_line: 0,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(code(narrow.validate()), "INVALID_WIDTH");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_failure_modes_have_their_codes() {
+        let batch = |json: &str| crate::batch_justify_text(json, 10, false).unwrap_err();
+        assert_eq!(batch("not json").code(), "INVALID_JSON");
+        assert_eq!(batch(r#"["a", 1]"#).code(), "INVALID_JSON");
+        assert_eq!(
+            crate::batch_justify_text("[]", u32::MAX, false)
+                .unwrap_err()
+                .code(),
+            "WIDTH_TOO_LARGE"
+        );
+        let metrics = crate::FontMetrics::from_json(r#"{"ranges": [[200, 100, 1.0]]}"#);
+        assert_eq!(
+            metrics.err(),
+            Some(WebtoolsError::InvalidRange {
+                start: 200,
+                end: 100
+            })
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serialized_as_code_message_and_details() {
+        let error = WebtoolsError::InputTooLarge {
+            limit: 500_000,
+            actual: 612_345,
+        };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "INPUT_TOO_LARGE",
+                "message": "Text too large: maximum 500,000 bytes supported",
+                "details": {"limit": 500_000, "actual": 612_345},
+            })
+        );
+        let error = WebtoolsError::BatchItemTooLarge {
+            index: 3,
+            limit: 10,
+            actual: 12,
+            id: Some(BatchId::Text("intro".to_string())),
+        };
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            value["message"],
+            r#"Batch item 3 (id "intro") too large: maximum 10 bytes supported"#
+        );
+        assert_eq!(value["details"]["id"], "intro");
+        // Details is always an object, empty when there are none
+        let value = serde_json::to_value(WebtoolsError::Cancelled).unwrap();
+        assert_eq!(value["code"], "CANCELLED");
+        assert_eq!(value["details"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn thousands_are_grouped() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1000), "1,000");
+        assert_eq!(group_thousands(5_000_000), "5,000,000");
+    }
+}
@@ -0,0 +1,214 @@
+use std::borrow::Cow;
+
+use crate::metrics::{wrap_text_into, ColumnWidths, TrailingWhitespace, WrapSink};
+
+/// How justify_text_html writes the wrapped text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HtmlOutput {
+    /// Line breaks as "<br>" (true) or "\n" for a white-space: pre container (false)
+    pub use_br: bool,
+    /// Write the second and later spaces of a run, and a space starting a line, as &nbsp;
+    /// so HTML whitespace collapsing keeps them
+    pub preserve_spaces: bool,
+    /// Drop whitespace left at the end of wrapped lines; turn off for white-space: pre-wrap
+    pub trim_trailing: bool,
+}
+
+impl Default for HtmlOutput {
+    fn default() -> Self {
+        HtmlOutput {
+            use_br: true,
+            preserve_spaces: false,
+            trim_trailing: true,
+        }
+    }
+}
+
+/// The characters HTML output writes as entities, and their entities
+const ENTITIES: [(char, &str); 5] = [
+    ('&', "&amp;"),
+    ('<', "&lt;"),
+    ('>', "&gt;"),
+    ('"', "&quot;"),
+    ('\'', "&#39;"),
+];
+
+/// The entity c is written as in HTML output, None for a character written as it is
+fn entity(c: char) -> Option<&'static str> {
+    ENTITIES
+        .iter()
+        .find(|&&(escaped, _)| escaped == c)
+        .map(|&(_, entity)| entity)
+}
+
+/// text with & < > " ' written as entities, as justify_text_html writes them; everything else,
+/// line breaks included, is unchanged
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + text.len() / 10);
+    for c in text.chars() {
+        match entity(c) {
+            Some(entity) => escaped.push_str(entity),
+            None => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// escape_html undone: the entities it writes become their characters again
+pub(crate) fn unescape_html(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match ENTITIES.iter().find(|(_, entity)| rest.starts_with(entity)) {
+            Some(&(c, entity)) => {
+                unescaped.push(c);
+                rest = &rest[entity.len()..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    Cow::Owned(unescaped)
+}
+
+/// Escapes as the wrappers write; widths are measured before this, on the raw text
+struct HtmlSink {
+    out: String,
+    options: HtmlOutput,
+    /// Last thing written was a space or a line break
+    after_space: bool,
+}
+
+impl WrapSink for HtmlSink {
+    fn push_text(&mut self, text: &str) {
+        text.chars().for_each(|c| self.push_char(c));
+    }
+
+    fn push_char(&mut self, c: char) {
+        let after_space = self.after_space;
+        self.after_space = c == ' ';
+        match entity(c) {
+            Some(entity) => self.out.push_str(entity),
+            None if c == ' ' && self.options.preserve_spaces && after_space => {
+                self.out.push_str("&nbsp;")
+            }
+            None => self.out.push(c),
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.out
+            .push_str(if self.options.use_br { "<br>" } else { "\n" });
+        self.after_space = true;
+    }
+}
+
+/// justify_text for an HTML preview: wrapped and escaped in the same pass
+/// Lines break exactly where justify_text breaks them (escaping doesn't count toward width);
+/// & < > " ' become entities and line breaks become <br>.
+pub fn justify_text_html(text: &str, max_chars_per_line: u32) -> String {
+    justify_text_html_with(text, max_chars_per_line, &HtmlOutput::default())
+}
+
+/// justify_text_html with a choice of line break and space handling
+pub fn justify_text_html_with(text: &str, max_chars_per_line: u32, options: &HtmlOutput) -> String {
+    let mut sink = HtmlSink {
+        out: String::with_capacity(text.len() + text.len() / 10),
+        options: *options,
+        after_space: true,
+    };
+    wrap_text_into(
+        text,
+        max_chars_per_line as f64 / 2.0,
+        &ColumnWidths,
+        &if options.trim_trailing {
+            TrailingWhitespace::Trim
+        } else {
+            TrailingWhitespace::Keep
+        }
+        .into(),
+        &mut sink,
+    );
+    sink.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::justify_text;
+    use crate::tests::random_texts;
+
+    /// justify_text's output escaped afterwards, with <br> for its line breaks
+    fn escaped_afterwards(text: &str, width: u32) -> String {
+        escape_html(&justify_text(text, width)).replace("\r\n", "<br>")
+    }
+
+    #[test]
+    fn markup_at_wrap_boundaries() {
+        // "a<b" fills the 3 columns, so the break falls right after the '<'-word
+        assert_eq!(justify_text_html("a<b c&d", 3), "a&lt;b<br>c&amp;d");
+        assert_eq!(justify_text_html("<<漢", 2), "&lt;&lt;<br>漢");
+        assert_eq!(
+            justify_text_html("\"引用\"と'語'", 6),
+            "&quot;引用&quot;<br>と&#39;語&#39;"
+        );
+        assert_eq!(justify_text_html("x > y", 10), "x &gt; y");
+    }
+
+    #[test]
+    fn entities_never_count_toward_the_width() {
+        let markup = ["&", "<", ">", "\"", "'", " & ", "<b>", "&amp;"];
+        for (index, text) in random_texts(200).into_iter().enumerate() {
+            let text = format!(
+                "{}{}{}",
+                markup[index % markup.len()],
+                text,
+                markup[(index / 3) % markup.len()]
+            );
+            for width in [1, 2, 4, 7, 12, 40] {
+                assert_eq!(
+                    justify_text_html(&text, width),
+                    escaped_afterwards(&text, width),
+                    "{:?} at {}",
+                    text,
+                    width
+                );
+            }
+            assert_eq!(unescape_html(&escape_html(&text)), text, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn output_options() {
+        let pre = HtmlOutput {
+            use_br: false,
+            ..HtmlOutput::default()
+        };
+        assert_eq!(justify_text_html_with("a<b c", 3, &pre), "a&lt;b\nc");
+
+        let spaces = HtmlOutput {
+            preserve_spaces: true,
+            trim_trailing: false,
+            ..HtmlOutput::default()
+        };
+        // The first space of a run stays a space so the browser can still break there, and
+        // a space starting a wrapped line is kept
+        assert_eq!(
+            justify_text_html_with("漢   字", 10, &spaces),
+            "漢 &nbsp;&nbsp;字"
+        );
+        assert_eq!(
+            justify_text_html_with("漢  字", 3, &spaces),
+            "漢 <br>&nbsp;字"
+        );
+        assert_eq!(justify_text_html("漢   字", 10), "漢   字");
+    }
+}
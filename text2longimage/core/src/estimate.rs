@@ -0,0 +1,188 @@
+use crate::metrics::number_token_len;
+use crate::{count_wrapped_lines, has_rtl, is_cjk, is_empty_input, width_of};
+
+/// Per-source-line summary kept by HeightEstimator
+enum Segment {
+    /// Blank line or empty '\r'-separated piece: always one output line
+    Blank,
+    /// CJK-path piece whose characters all share one width: closed-form line count
+    Uniform { chars: u64, width: u64 },
+    /// CJK-path piece with mixed widths: prefix sums of character widths
+    Chars(Vec<u64>),
+    /// Word-wrapped line: prefix sums of word byte lengths
+    Words(Vec<u64>),
+    /// Line the summaries can't model (RTL runs, and number tokens on the CJK path, move
+    /// whole): counted from the text each time
+    Text(String),
+}
+
+/// Pre-summarized document that answers "how many lines at this width?" without rewrapping
+/// Results are exact (they always equal count_wrapped_lines). A query costs O(1) for
+/// uniform-width CJK lines and O(output lines × log n) for the rest, instead of touching every
+/// character again, which keeps a width slider responsive on large documents.
+pub struct HeightEstimator {
+    segments: Vec<Segment>,
+    empty: bool,
+}
+
+impl HeightEstimator {
+    pub fn new(text: &str) -> HeightEstimator {
+        let mut segments = Vec::new();
+
+        for line in text.split('\n') {
+            let trimmed_line = line.trim();
+            if trimmed_line.is_empty() {
+                segments.push(Segment::Blank);
+            } else if has_rtl(trimmed_line)
+                || is_cjk(trimmed_line) && has_number_token(trimmed_line)
+            {
+                segments.push(Segment::Text(trimmed_line.to_string()));
+            } else if is_cjk(trimmed_line) {
+                // '\r' inside a CJK line starts a fresh output line, like justify_text_cjk
+                segments.extend(trimmed_line.split('\r').map(summarize_chars));
+            } else {
+                let mut prefix = vec![0u64];
+                for word in trimmed_line.split_whitespace() {
+                    prefix.push(prefix[prefix.len() - 1] + word.len() as u64);
+                }
+                segments.push(Segment::Words(prefix));
+            }
+        }
+
+        HeightEstimator {
+            segments,
+            empty: is_empty_input(text),
+        }
+    }
+
+    /// Number of wrapped lines at max_chars_per_line
+    pub fn estimate(&self, max_chars_per_line: u32) -> u32 {
+        if self.empty {
+            return 0;
+        }
+
+        let max_width = max_chars_per_line as u64;
+        let total: u64 = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Blank => 1,
+                Segment::Uniform { chars, width } => uniform_lines(*chars, *width, max_width),
+                Segment::Chars(prefix) => {
+                    // A first character wider than the line is preceded by an empty line
+                    let leading = u64::from(prefix[1] > max_width);
+                    leading + greedy_lines(prefix, max_width, 0)
+                }
+                Segment::Words(prefix) => greedy_lines(prefix, max_width, 1),
+                Segment::Text(line) => count_wrapped_lines(line, max_chars_per_line) as u64,
+            })
+            .sum();
+
+        total.min(u32::MAX as u64) as u32
+    }
+}
+
+/// Whether the character wrapper would keep a number or date token together in line
+fn has_number_token(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    (0..bytes.len()).any(|index| number_token_len(&bytes[index..]) > 1)
+}
+
+fn summarize_chars(piece: &str) -> Segment {
+    let mut chars = piece.chars();
+    let first_width = match chars.next() {
+        Some(c) => width_of(c) as u64,
+        None => return Segment::Blank,
+    };
+
+    if chars.all(|c| width_of(c) as u64 == first_width) {
+        return Segment::Uniform {
+            chars: piece.chars().count() as u64,
+            width: first_width,
+        };
+    }
+
+    let mut prefix = vec![0u64];
+    for c in piece.chars() {
+        prefix.push(prefix[prefix.len() - 1] + width_of(c) as u64);
+    }
+    Segment::Chars(prefix)
+}
+
+/// Lines for `chars` characters of identical width in the character wrapper
+fn uniform_lines(chars: u64, width: u64, max_width: u64) -> u64 {
+    if width > max_width {
+        // Every character breaks before itself, including the first
+        chars + 1
+    } else {
+        chars.div_ceil(max_width / width)
+    }
+}
+
+/// Greedy line count over items with prefix sums of their widths
+/// `gap` is the width added between consecutive items on a line (1 for spaces between words)
+fn greedy_lines(prefix: &[u64], max_width: u64, gap: u64) -> u64 {
+    let items = prefix.len() - 1;
+    let mut lines = 0;
+    let mut start = 0;
+
+    while start < items {
+        // Largest end whose items [start, end) fit; a line always takes at least one item
+        let fits =
+            |end: usize| prefix[end] - prefix[start] + gap * (end - start - 1) as u64 <= max_width;
+        let (mut low, mut high) = (start + 1, items);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        lines += 1;
+        start = low;
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The estimate at every width from 0 to widest, against count_wrapped_lines
+    fn assert_exact(text: &str, widest: u32) {
+        let estimator = HeightEstimator::new(text);
+        for width in 0..=widest {
+            assert_eq!(
+                estimator.estimate(width),
+                count_wrapped_lines(text, width),
+                "{text:?} at {width}"
+            );
+        }
+    }
+
+    #[test]
+    fn pure_cjk_and_pure_ascii_paragraphs() {
+        assert_exact(&"汉字".repeat(37), 80);
+        assert_exact(&"ｆｕｌｌｗｉｄｔｈ".repeat(5), 30);
+        assert_exact(
+            "The quick brown fox jumps over the lazy dog, twice over.",
+            60,
+        );
+        assert_exact(&"x".repeat(101), 110);
+    }
+
+    #[test]
+    fn mixed_and_unmodelled_lines() {
+        let text = "标题 Title\n\n正文里有English words和数字2024-06-01。\r\n  indented words  \nمرحبا بالعالم hello\n\u{3000}\n\n";
+        assert_exact(text, 40);
+        assert_exact("漢字\rかな", 6);
+        assert_exact("", 4);
+        assert_exact("\n\n", 4);
+        assert_eq!(HeightEstimator::new("").estimate(10), 0);
+        // A full-width character wider than the line goes under an empty one
+        assert_eq!(HeightEstimator::new("漢字").estimate(1), 3);
+    }
+}
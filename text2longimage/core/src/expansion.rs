@@ -0,0 +1,231 @@
+//! A ceiling on how much larger than its input the wrapped output may grow
+//! Some option combinations multiply the text: a width of 1 with a line-number gutter, break
+//! markers and soft break marks on every line turns each character into dozens of bytes, and a
+//! large enough input then runs the wasm module out of memory halfway through, which traps
+//! instead of throwing. The fallible entry points (JustifyOptions::justify_checked,
+//! process_text_chunks, justify_windows) stop once the output passes max_output_ratio() times
+//! the input read so far, or MIN_OUTPUT_CEILING bytes if that is more, and return
+//! OutputTooLarge; an options object's maxOutputRatio overrides the setting for its call. The
+//! wrapper's sink stops growing past the ceiling and wrapping stops at the next source line,
+//! so the output never gets much past it; the chunked paths carry the totals from one chunk
+//! to the next.
+
+use crate::metrics::WrapSink;
+use crate::{max_output_ratio, WebtoolsError, MIN_OUTPUT_CEILING};
+
+/// The wrappers' String output, which takes nothing more once it is over ceiling bytes
+pub(crate) struct CappedOutput {
+    pub(crate) text: String,
+    ceiling: usize,
+}
+
+impl CappedOutput {
+    /// An empty output; None for no ceiling
+    pub(crate) fn new(capacity: usize, ceiling: Option<usize>) -> Self {
+        CappedOutput {
+            text: String::with_capacity(capacity),
+            ceiling: ceiling.unwrap_or(usize::MAX),
+        }
+    }
+}
+
+impl WrapSink for CappedOutput {
+    fn push_text(&mut self, text: &str) {
+        if !self.full() {
+            self.text.push_str(text);
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        if !self.full() {
+            self.text.push(c);
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.push_text("\r\n");
+    }
+
+    fn full(&self) -> bool {
+        self.text.len() > self.ceiling
+    }
+}
+
+/// Bytes read and written so far by one call, checked against max_output_ratio()
+pub(crate) struct ExpansionGuard {
+    ratio: u32,
+    input: usize,
+    output: usize,
+    line_breaks: usize,
+}
+
+impl ExpansionGuard {
+    /// A guard for a call starting now, with the ratio currently set
+    pub(crate) fn new() -> Self {
+        Self::with_ratio(max_output_ratio())
+    }
+
+    /// A guard for a call starting now with its own ratio, 0 for no ceiling
+    pub(crate) fn with_ratio(ratio: u32) -> Self {
+        ExpansionGuard {
+            ratio,
+            input: 0,
+            output: 0,
+            line_breaks: 0,
+        }
+    }
+
+    /// How many more output bytes may be written once more_input more bytes of input have
+    /// been read; None for no ceiling
+    pub(crate) fn ceiling(&self, more_input: usize) -> Option<usize> {
+        (self.ratio > 0).then(|| {
+            let allowed = (self.input + more_input).saturating_mul(self.ratio as usize);
+            allowed.max(MIN_OUTPUT_CEILING).saturating_sub(self.output)
+        })
+    }
+
+    /// Count input bytes read and the output wrapped from them; OutputTooLarge once the
+    /// output is over the ceiling
+    pub(crate) fn add(&mut self, input: usize, output: &str) -> Result<(), WebtoolsError> {
+        let allowed = self.ceiling(input);
+        self.input += input;
+        self.output += output.len();
+        self.line_breaks += output.matches("\r\n").count();
+        match allowed {
+            Some(allowed) if output.len() > allowed => Err(WebtoolsError::OutputTooLarge {
+                ratio: self.output as f64 / self.input.max(1) as f64,
+                limit: self.ratio,
+                line_index: self.line_breaks,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::limits::lock_limits;
+    use crate::{
+        justify_text, justify_windows, process_text_chunks, set_max_output_ratio, JustifyOptions,
+        DEFAULT_MAX_OUTPUT_RATIO,
+    };
+
+    use super::*;
+
+    /// A width of 1 with a line-number gutter and soft break marks, which writes dozens of
+    /// bytes for each word
+    fn numbered_and_marked() -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line: 1,
+            line_numbers: true,
+            mark_soft_breaks: "<-- soft break -->".into(),
+            ..JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn wrapping_stops_partway_and_the_next_call_wraps_as_before() {
+        let _limits = lock_limits();
+        let text = "a a a a a\n".repeat(4_000);
+        let options = numbered_and_marked();
+        let lines = options.justify(&text).matches("\r\n").count();
+        let error = options.justify_checked(&text).unwrap_err();
+        let &WebtoolsError::OutputTooLarge {
+            ratio,
+            limit,
+            line_index,
+        } = &error
+        else {
+            panic!("expected OutputTooLarge, got {error:?}");
+        };
+        assert_eq!(limit, DEFAULT_MAX_OUTPUT_RATIO);
+        assert!(ratio > f64::from(limit), "ratio {ratio}");
+        assert!(
+            line_index > 0 && line_index < lines,
+            "stopped at {line_index} of {lines}"
+        );
+        assert_eq!(
+            options.justify_checked("a b c"),
+            Ok(options.justify("a b c"))
+        );
+    }
+
+    #[test]
+    fn chunked_wrapping_carries_the_totals_across_chunks() {
+        let _limits = lock_limits();
+        // Plain wrapping only doubles blank lines, so a ratio of 1 stops it
+        let blank = "\n".repeat(100_000);
+        let windows = || {
+            let mut output = String::new();
+            let result = justify_windows::<WebtoolsError>(
+                blank.len(),
+                10,
+                1000,
+                |range, buffer| buffer.extend_from_slice(&blank.as_bytes()[range]),
+                |block| {
+                    output.push_str(block);
+                    Ok(())
+                },
+            );
+            result.map(|()| output)
+        };
+        let text = "a a a a a\n".repeat(4_000);
+        let options = numbered_and_marked();
+        set_max_output_ratio(1);
+        let chunked = process_text_chunks(&blank, 10, 1000);
+        let windowed = windows();
+        set_max_output_ratio(0);
+        let unlimited = options.justify_checked(&text);
+        set_max_output_ratio(DEFAULT_MAX_OUTPUT_RATIO);
+        for (what, result) in [
+            ("process_text_chunks", chunked),
+            ("justify_windows", windowed),
+        ] {
+            match result {
+                Err(WebtoolsError::OutputTooLarge {
+                    limit: 1,
+                    line_index,
+                    ..
+                }) => assert!(
+                    line_index < blank.len() - 1,
+                    "{what} stopped at {line_index}"
+                ),
+                other => panic!("expected OutputTooLarge from {what}, got {other:?}"),
+            }
+        }
+        assert_eq!(unlimited, Ok(options.justify(&text)));
+        assert_eq!(
+            process_text_chunks(&blank, 10, 1000),
+            Ok(justify_text(&blank, 10))
+        );
+        assert_eq!(windows(), Ok(justify_text(&blank, 10)));
+    }
+
+    #[test]
+    fn an_options_ratio_overrides_the_module_setting() {
+        let _limits = lock_limits();
+        let blank = "\n".repeat(100_000);
+        let options = JustifyOptions::default();
+        let limited = JustifyOptions {
+            max_output_ratio: Some(1),
+            ..options.clone()
+        };
+        let unlimited = JustifyOptions {
+            max_output_ratio: Some(0),
+            ..options.clone()
+        };
+        // Raised rather than lowered, so tests not holding the lock still pass meanwhile
+        for ratio in [0, DEFAULT_MAX_OUTPUT_RATIO] {
+            set_max_output_ratio(ratio);
+            assert!(matches!(
+                limited.justify_checked(&blank),
+                Err(WebtoolsError::OutputTooLarge { limit: 1, .. })
+            ));
+            assert_eq!(
+                unlimited.justify_checked(&blank),
+                Ok(unlimited.justify(&blank))
+            );
+            assert_eq!(options.output_ratio(), ratio);
+        }
+    }
+}
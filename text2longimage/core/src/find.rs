@@ -0,0 +1,231 @@
+//! Searching text as it appears after wrapping
+//! The query is matched against justify_text's output, where a break the wrapper inserted
+//! reads as the space it replaced (or as nothing inside a word or CJK run), so a match can
+//! straddle it. Whitespace runs the word wrapper collapsed to one space only match as one space.
+
+use serde::Serialize;
+
+use crate::{width_of, WrapIter};
+
+/// Part of a match on one wrapped line, in display columns
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MatchSegment {
+    pub line_index: usize,
+    pub start_col: u32,
+    pub end_col: u32,
+    /// The same match goes on at the start of the next line (reported as the next segment)
+    pub continues_on_next_line: bool,
+}
+
+/// Where a character of the searched text came from; None for the space standing in for a break
+type Origin = Option<(usize, u32, u32)>;
+
+/// Case folding that keeps one character per character, so match offsets still map back
+fn fold(c: char, case_insensitive: bool) -> char {
+    if !case_insensitive {
+        return c;
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+/// Search one run of soft-wrapped lines and append the segments of every match
+fn search_run(
+    run: &[(char, Origin)],
+    query: &[char],
+    case_insensitive: bool,
+    segments: &mut Vec<MatchSegment>,
+) {
+    if query.len() > run.len() {
+        return;
+    }
+
+    let mut start = 0;
+    while start + query.len() <= run.len() {
+        let matched = run[start..start + query.len()]
+            .iter()
+            .zip(query)
+            .all(|(&(c, _), &q)| fold(c, case_insensitive) == q);
+        if !matched {
+            start += 1;
+            continue;
+        }
+
+        let mut current: Option<MatchSegment> = None;
+        for &(line_index, col, width) in run[start..start + query.len()]
+            .iter()
+            .filter_map(|(_, origin)| origin.as_ref())
+        {
+            match &mut current {
+                Some(segment) if segment.line_index == line_index => {
+                    segment.end_col = col + width;
+                }
+                _ => {
+                    if let Some(mut segment) = current.take() {
+                        segment.continues_on_next_line = true;
+                        segments.push(segment);
+                    }
+                    current = Some(MatchSegment {
+                        line_index,
+                        start_col: col,
+                        end_col: col + width,
+                        continues_on_next_line: false,
+                    });
+                }
+            }
+        }
+        segments.extend(current);
+        start += query.len();
+    }
+}
+
+/// Every non-overlapping match of query in the wrapped text, as one segment per line it covers
+/// A match never crosses a line break of the source; a query made only of the space standing
+/// in for a break matches nothing drawable and is dropped.
+pub fn find_wrapped_segments(
+    text: &str,
+    max_chars_per_line: u32,
+    query: &str,
+    case_insensitive: bool,
+) -> Vec<MatchSegment> {
+    let mut segments = Vec::new();
+    let query: Vec<char> = query.chars().map(|c| fold(c, case_insensitive)).collect();
+    if query.is_empty() {
+        return segments;
+    }
+
+    let mut run: Vec<(char, Origin)> = Vec::new();
+    let mut previous_end = None;
+    for (line_index, line) in WrapIter::new(text, max_chars_per_line).enumerate() {
+        if let Some(end) = previous_end {
+            // The whitespace the wrapper dropped at the break reads as one space
+            if end < line.text_range.start {
+                run.push((' ', None));
+            }
+        }
+
+        let mut col = 0;
+        for c in line.text(text).chars() {
+            let width = width_of(c);
+            run.push((c, Some((line_index, col, width))));
+            col += width;
+        }
+
+        if line.hard_break {
+            search_run(&run, &query, case_insensitive, &mut segments);
+            run.clear();
+            previous_end = None;
+        } else {
+            previous_end = Some(line.text_range.end);
+        }
+    }
+
+    segments
+}
+
+#[cfg(feature = "json")]
+/// Find query in text as wrapped by justify_text(text, max_chars_per_line)
+/// Returns JSON array of {lineIndex, startCol, endCol, continuesOnNextLine}; a match over an
+/// inserted line break is two entries, the first with continuesOnNextLine set. Matching is
+/// against the wrapped output, so text the wrapper collapsed (runs of spaces) can't be found
+/// as written. case_insensitive folds case per character (ASCII, full-width Latin and others).
+pub fn find_in_wrapped(
+    text: &str,
+    max_chars_per_line: u32,
+    query: &str,
+    case_insensitive: bool,
+) -> String {
+    let segments = find_wrapped_segments(text, max_chars_per_line, query, case_insensitive);
+    serde_json::to_string(&segments).unwrap_or_else(|_| "[]".to_string())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(
+        text: &str,
+        max_chars_per_line: u32,
+        query: &str,
+        case_insensitive: bool,
+    ) -> Vec<(usize, u32, u32, bool)> {
+        find_wrapped_segments(text, max_chars_per_line, query, case_insensitive)
+            .into_iter()
+            .map(|s| {
+                (
+                    s.line_index,
+                    s.start_col,
+                    s.end_col,
+                    s.continues_on_next_line,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_match_across_a_word_wrap() {
+        // "the quick" / "brown fox": the break stands for the space between the words
+        assert_eq!(
+            find("the quick brown fox", 10, "quick brown", false),
+            [(0, 4, 9, true), (1, 0, 5, false)]
+        );
+        assert_eq!(
+            find("the quick brown fox", 10, "fox", false),
+            [(1, 6, 9, false)]
+        );
+    }
+
+    #[test]
+    fn a_match_across_a_cjk_wrap() {
+        // "这是一段" / "中文文本" / "内容": nothing stands between the lines
+        assert_eq!(
+            find("这是一段中文文本内容", 8, "一段中文", false),
+            [(0, 4, 8, true), (1, 0, 4, false)]
+        );
+        assert_eq!(
+            find("这是一段中文文本内容", 8, "文本内", false),
+            [(1, 4, 8, true), (2, 0, 2, false)]
+        );
+    }
+
+    #[test]
+    fn case_insensitive_ascii_and_full_width() {
+        assert_eq!(
+            find("Hello WORLD hello", 20, "hello", true),
+            [(0, 0, 5, false), (0, 12, 17, false)]
+        );
+        assert_eq!(
+            find("Hello WORLD hello", 20, "hello", false),
+            [(0, 12, 17, false)]
+        );
+        assert_eq!(find("ＡＢＣ abc", 20, "ａｂｃ", true), [(0, 0, 6, false)]);
+    }
+
+    #[test]
+    fn matches_are_against_the_output() {
+        // The two spaces were collapsed to one, so only the one-space query finds them
+        assert!(find("a  b c", 10, "a  b", false).is_empty());
+        assert_eq!(find("a  b c", 10, "a b", false), [(0, 0, 3, false)]);
+        // A source line break is not a space
+        assert!(find("x\ny", 10, "x y", false).is_empty());
+        assert!(find("abc", 10, "", false).is_empty());
+        // Matches don't overlap
+        assert_eq!(
+            find("aaaa", 10, "aa", false),
+            [(0, 0, 2, false), (0, 2, 4, false)]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn find_json() {
+        assert_eq!(
+            find_in_wrapped("the quick brown fox", 10, "K B", true),
+            r#"[{"lineIndex":0,"startCol":8,"endCol":9,"continuesOnNextLine":true},{"lineIndex":1,"startCol":0,"endCol":1,"continuesOnNextLine":false}]"#
+        );
+    }
+}
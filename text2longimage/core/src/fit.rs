@@ -0,0 +1,91 @@
+//! Whether a text fits on one line, for checks like "the caption must fit on the card"
+//! Wrapping the text and looking for "\r\n" does the whole job to answer a yes or no; these
+//! go through WrapIter, which wraps one source line at a time, and stop after the first
+//! line: the text fits when it is all on that line and the line is within the width.
+
+use crate::emoji::emoji_grapheme_start;
+use crate::{width_of, WrapIter};
+
+/// Char index of the first character of text that doesn't fit on one line of
+/// max_chars_per_line columns, None when it all does
+/// The line is justify_text's first, so the width model and what moves to the next line
+/// whole (words, number tokens, RTL runs) are the wrapper's: the index is where that line
+/// stops, or where its width passes the limit when it holds a word too wide for any line.
+/// A line break in the text is where it stops too. An index inside an emoji sequence moves
+/// back to the sequence's start, so highlighting from it never splits one.
+pub fn first_overflow_index(text: &str, max_chars_per_line: u32) -> Option<usize> {
+    let mut lines = WrapIter::new(text, max_chars_per_line);
+    let first = lines.next()?;
+
+    let overflow = if first.width > max_chars_per_line {
+        // A word too wide for any line: stop at the char that passes the limit
+        let line = first.text(text);
+        let mut width = 0;
+        let passing = line.chars().position(|c| {
+            width += width_of(c);
+            width > max_chars_per_line
+        })?;
+        let visible_before = line
+            .chars()
+            .take(passing)
+            .filter(|c| !c.is_whitespace())
+            .count();
+        let source = &text[first.text_range.clone()];
+        let offset = source
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .nth(visible_before)
+            .map_or(source.len(), |(offset, _)| offset);
+        first.text_range.start + offset
+    } else {
+        let next = lines.next()?;
+        if first.hard_break {
+            first.text_range.end
+        } else {
+            next.text_range.start
+        }
+    };
+
+    let index = text[..overflow].chars().count();
+    let line_start = text[..overflow].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[overflow..]
+        .find('\n')
+        .map_or(text.len(), |i| overflow + i);
+    let chars: Vec<char> = text[line_start..line_end].chars().collect();
+    let column = text[line_start..overflow].chars().count();
+    Some(index - column + emoji_grapheme_start(&chars, column))
+}
+
+/// Whether all of text fits on one line of max_chars_per_line columns, as justify_text would
+/// wrap it (see first_overflow_index)
+pub fn fits_in_width(text: &str, max_chars_per_line: u32) -> bool {
+    first_overflow_index(text, max_chars_per_line).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_character_past_the_width() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        for (text, width, expected) in [
+            ("hello world", 11, None),
+            ("hello world", 10, Some(6)),
+            ("abc漢", 5, None),
+            ("abc漢", 4, Some(3)),
+            ("abcdefgh", 4, Some(4)),
+            ("caption\nsecond line", 40, Some(7)),
+            (&format!("漢字{family}"), 14, None),
+            (&format!("漢字{family}"), 8, Some(2)),
+            (&format!("abc{family}"), 5, Some(3)),
+        ] {
+            assert_eq!(
+                first_overflow_index(text, width),
+                expected,
+                "first_overflow_index({text:?}, {width})"
+            );
+            assert_eq!(fits_in_width(text, width), expected.is_none(), "{text:?}");
+        }
+    }
+}
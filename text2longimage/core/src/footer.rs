@@ -0,0 +1,125 @@
+//! Attribution footer appended below wrapped text
+//! The footer is a single line that is never wrapped: it is aligned by padding it with spaces
+//! to the line width under the same display widths as the body, and cut with an ellipsis when
+//! it doesn't fit on its own.
+
+use crate::{
+    align_to_width, truncate_to_width, width_of, width_of_str, Align, MAX_MAX_CHARS_PER_LINE,
+};
+
+const ELLIPSIS: char = '…';
+
+/// The footer cut to max_chars_per_line, ending in an ellipsis when anything was dropped
+/// A width too narrow for the ellipsis itself gets the characters that fit and no ellipsis.
+fn truncate_footer(footer: &str, max_chars_per_line: u32) -> String {
+    if width_of_str(footer) <= max_chars_per_line as u64 {
+        return footer.to_string();
+    }
+
+    let ellipsis_width = width_of(ELLIPSIS);
+    let (budget, ellipsis) = match max_chars_per_line.checked_sub(ellipsis_width) {
+        Some(budget) => (budget, Some(ELLIPSIS)),
+        None => (max_chars_per_line, None),
+    };
+    let mut truncated = truncate_to_width(footer, budget as u64).to_string();
+    truncated.extend(ellipsis);
+    truncated
+}
+
+/// The footer line as written: truncated to fit, then padded for align
+/// Padding stops at MAX_MAX_CHARS_PER_LINE columns, so a width past it can't pad a line
+/// with billions of spaces.
+pub fn footer_line(footer: &str, max_chars_per_line: u32, align: Align) -> String {
+    let footer = truncate_footer(footer, max_chars_per_line);
+    let columns = max_chars_per_line.min(MAX_MAX_CHARS_PER_LINE);
+    align_to_width(&footer, columns as u64, align)
+}
+
+/// Append a footer line to justified text after blank_lines empty lines
+/// An empty footer leaves the text unchanged; empty text gets the footer line alone.
+pub fn append_footer(
+    justified: &str,
+    footer: &str,
+    max_chars_per_line: u32,
+    align: Align,
+    blank_lines: u32,
+) -> String {
+    if footer.is_empty() {
+        return justified.to_string();
+    }
+
+    let line = footer_line(footer, max_chars_per_line, align);
+    if justified.is_empty() {
+        return line;
+    }
+    let mut result =
+        String::with_capacity(justified.len() + line.len() + 2 * blank_lines as usize + 2);
+    result.push_str(justified);
+    for _ in 0..=blank_lines {
+        result.push_str("\r\n");
+    }
+    result.push_str(&line);
+    result
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JustifyOptions;
+
+    /// 17 columns: the dash is two, the space one and the seven ideographs two each
+    const FOOTER: &str = "— 由网页工具生成";
+
+    #[test]
+    fn a_cjk_footer_right_aligned_on_an_odd_width() {
+        assert_eq!(
+            footer_line(FOOTER, 21, Align::Right),
+            "    — 由网页工具生成"
+        );
+        assert_eq!(width_of_str(&footer_line(FOOTER, 21, Align::Right)), 21);
+        // Centred, the odd column of the four goes to the right and isn't written
+        assert_eq!(footer_line(FOOTER, 21, Align::Center), "  — 由网页工具生成");
+        assert_eq!(footer_line(FOOTER, 20, Align::Center), " — 由网页工具生成");
+        assert_eq!(footer_line(FOOTER, 17, Align::Right), FOOTER);
+    }
+
+    #[test]
+    fn a_footer_wider_than_the_line_ends_in_an_ellipsis() {
+        // Seven columns of text and two of ellipsis, padded to ten
+        assert_eq!(footer_line(FOOTER, 10, Align::Right), " — 由网…");
+        assert_eq!(footer_line(FOOTER, 2, Align::Left), "…");
+        // Too narrow for the ellipsis: what fits, which here is nothing
+        assert_eq!(footer_line(FOOTER, 1, Align::Right), " ");
+    }
+
+    #[test]
+    fn blank_lines_go_between_the_text_and_the_footer() {
+        assert_eq!(
+            append_footer("ab\r\ncd", "x", 5, Align::Right, 1),
+            "ab\r\ncd\r\n\r\n    x"
+        );
+        assert_eq!(append_footer("ab", "x", 5, Align::Left, 0), "ab\r\nx");
+        assert_eq!(append_footer("", "x", 5, Align::Right, 1), "    x");
+        assert_eq!(append_footer("ab", "", 5, Align::Right, 1), "ab");
+    }
+
+    #[test]
+    fn justify_options_never_wrap_the_footer() {
+        let options = JustifyOptions {
+            max_chars_per_line: 13,
+            footer_text: "— 网页工具".to_string(),
+            footer_align: Align::Right,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(options.footer_blank_lines, 1);
+        assert_eq!(
+            options.justify("这是一段很长的中文文本"),
+            "这是一段很长\r\n的中文文本\r\n\r\n  — 网页工具"
+        );
+        let narrow = JustifyOptions {
+            max_chars_per_line: 6,
+            ..options
+        };
+        // Four columns before the ellipsis hold "— " but not 网
+        assert_eq!(narrow.justify("中文"), "中文\r\n\r\n — …");
+    }
+}
@@ -0,0 +1,169 @@
+//! Rulers for reviewing a layout: where the margins are, a vertical guide every few columns,
+//! and how many columns each line leaves unused at its end
+//! A review tool draws them over the image to spot overly ragged lines. Slack is measured in
+//! the columns the lines were wrapped in, so a line the wrapper filled has none.
+
+use serde::Serialize;
+
+use crate::layout::column_width_px;
+#[cfg(feature = "json")]
+use crate::profile::mark;
+#[cfg(feature = "json")]
+use crate::{justify_text, layout_lines, layout_lines_with_headings, Align, Stage};
+use crate::{width_of_str, LayoutLine, Spacing};
+
+/// Columns between two guides when the caller doesn't say
+pub const DEFAULT_GUIDE_COLUMNS: u32 = 10;
+
+/// The rulers of one layout
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutGuides {
+    /// x of the text block's left edge
+    pub left_margin_px: f64,
+    /// x of the text block's right edge, max_chars_per_line columns to the right
+    pub right_margin_px: f64,
+    /// x of a guide every guide_columns columns, between the margins
+    pub column_guides_px: Vec<f64>,
+    /// Unused columns at the end of each line: max_chars_per_line less the line's width, with
+    /// its spacing and heading scale; negative for a run too long to break
+    pub slack: Vec<f64>,
+}
+
+/// The guides of lines laid out at max_chars_per_line columns (see layout_lines and
+/// layout_lines_with_headings; spacing is what they were wrapped with)
+/// A guide_columns of 0 gives no column guides.
+pub fn layout_guides(
+    lines: &[LayoutLine],
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    padding_px: f64,
+    guide_columns: u32,
+    spacing: Spacing,
+) -> LayoutGuides {
+    let column_px = column_width_px(font_size_px);
+    let column_guides_px = match guide_columns {
+        0 => Vec::new(),
+        step => (step..max_chars_per_line)
+            .step_by(step as usize)
+            .map(|column| padding_px + column as f64 * column_px)
+            .collect(),
+    };
+    let slack = lines
+        .iter()
+        .map(|line| {
+            // An em is two columns
+            let columns = (width_of_str(&line.text) as f64
+                + 2.0 * spacing.extra_em_str(&line.text))
+                * line.font_scale.unwrap_or(1.0);
+            max_chars_per_line as f64 - columns
+        })
+        .collect();
+
+    LayoutGuides {
+        left_margin_px: padding_px,
+        right_margin_px: padding_px + max_chars_per_line as f64 * column_px,
+        column_guides_px,
+        slack,
+    }
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct GuidedLayout<'a> {
+    lines: Vec<LayoutLine<'a>>,
+    guides: LayoutGuides,
+}
+
+#[cfg(feature = "json")]
+/// layout_text_with_spacing with the guides of its lines
+/// Returns JSON {lines, guides: {leftMarginPx, rightMarginPx, columnGuidesPx, slack}}, lines
+/// being exactly the layout_text_with_spacing array
+#[allow(clippy::too_many_arguments)]
+pub fn layout_text_with_guides(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    heading_scale: f64,
+    spacing: Spacing,
+    guide_columns: u32,
+) -> String {
+    let justified;
+    let lines = if heading_scale == 1.0 && spacing.is_zero() {
+        justified = justify_text(text, max_chars_per_line);
+        layout_lines(
+            &justified,
+            max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            Align::parse(align),
+        )
+    } else {
+        layout_lines_with_headings(
+            text,
+            max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            Align::parse(align),
+            heading_scale,
+            spacing,
+        )
+    };
+    let guides = layout_guides(
+        &lines,
+        max_chars_per_line,
+        font_size_px,
+        padding_px,
+        guide_columns,
+        spacing,
+    );
+    mark(Stage::Wrap);
+
+    let json =
+        serde_json::to_string(&GuidedLayout { lines, guides }).unwrap_or_else(|_| "{}".to_string());
+    mark(Stage::Serialize);
+    json
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    fn guided(text: &str, guide_columns: u32) -> serde_json::Value {
+        let json = layout_text_with_guides(
+            text,
+            10,
+            20.0,
+            1.5,
+            5.0,
+            "center",
+            1.0,
+            Spacing::default(),
+            guide_columns,
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn margins_guides_and_slack_at_ten_columns() {
+        // A line the wrapper filled and a short one after it, in ASCII and CJK
+        for (text, slack) in [("abcdefghij klm", [0.0, 7.0]), ("中文中文中文", [0.0, 8.0])] {
+            let guides = &guided(text, 4)["guides"];
+            assert_eq!(guides["slack"], serde_json::json!(slack), "{text:?}");
+            assert_eq!(guides["leftMarginPx"], 5.0);
+            assert_eq!(guides["rightMarginPx"], 105.0);
+            assert_eq!(guides["columnGuidesPx"], serde_json::json!([45.0, 85.0]));
+        }
+        assert_eq!(
+            guided("abc", 0)["guides"]["columnGuidesPx"],
+            serde_json::json!([])
+        );
+    }
+}
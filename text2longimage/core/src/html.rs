@@ -0,0 +1,517 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "json")]
+use crate::WebtoolsError;
+
+/// Toggles for strip_html; every field is optional when deserializing
+/// JSON keys are camelCase: {"keepLinkUrls"}
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HtmlOptions {
+    /// Follow the text of each <a href> with its URL in parentheses
+    pub keep_link_urls: bool,
+}
+
+#[cfg(feature = "json")]
+impl HtmlOptions {
+    /// Parse options JSON; an empty string means all defaults
+    pub fn from_json(options_json: &str) -> Result<HtmlOptions, WebtoolsError> {
+        if options_json.trim().is_empty() {
+            return Ok(HtmlOptions::default());
+        }
+        serde_json::from_str(options_json).map_err(WebtoolsError::invalid_json)
+    }
+}
+
+/// Elements that start and end a line of their own
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements whose content is never text
+const SKIPPED_TAGS: &[&str] = &["head", "script", "style", "template", "title"];
+
+const NAMED_ENTITIES: &[(&str, char)] = &[
+    ("aacute", 'á'),
+    ("acirc", 'â'),
+    ("agrave", 'à'),
+    ("amp", '&'),
+    ("apos", '\''),
+    ("Auml", 'Ä'),
+    ("auml", 'ä'),
+    ("bull", '•'),
+    ("ccedil", 'ç'),
+    ("cent", '¢'),
+    ("copy", '©'),
+    ("deg", '°'),
+    ("Eacute", 'É'),
+    ("eacute", 'é'),
+    ("ecirc", 'ê'),
+    ("egrave", 'è'),
+    ("euml", 'ë'),
+    ("euro", '€'),
+    ("gt", '>'),
+    ("hellip", '…'),
+    ("iacute", 'í'),
+    ("iuml", 'ï'),
+    ("laquo", '«'),
+    ("ldquo", '“'),
+    ("lsquo", '‘'),
+    ("lt", '<'),
+    ("mdash", '—'),
+    ("middot", '·'),
+    ("nbsp", ' '),
+    ("ndash", '–'),
+    ("ntilde", 'ñ'),
+    ("oacute", 'ó'),
+    ("Ouml", 'Ö'),
+    ("ouml", 'ö'),
+    ("pound", '£'),
+    ("quot", '"'),
+    ("raquo", '»'),
+    ("rdquo", '”'),
+    ("reg", '®'),
+    ("rsquo", '’'),
+    ("szlig", 'ß'),
+    ("times", '×'),
+    ("trade", '™'),
+    ("uacute", 'ú'),
+    ("Uuml", 'Ü'),
+    ("uuml", 'ü'),
+    ("yen", '¥'),
+];
+
+/// Appends text while collapsing whitespace the way a browser renders it
+struct Writer {
+    out: String,
+    pending_space: bool,
+    pending_breaks: usize,
+}
+
+impl Writer {
+    fn flush(&mut self) {
+        if self.out.is_empty() {
+            // Nothing before this yet: leading breaks and spaces are dropped
+        } else if self.pending_breaks > 0 {
+            while self.out.ends_with(' ') {
+                self.out.pop();
+            }
+            (0..self.pending_breaks).for_each(|_| self.out.push('\n'));
+        } else if self.pending_space && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+        self.pending_breaks = 0;
+    }
+
+    fn push(&mut self, c: char) {
+        if c.is_whitespace() {
+            self.pending_space = true;
+        } else {
+            self.flush();
+            self.out.push(c);
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        s.chars().for_each(|c| self.push(c))
+    }
+
+    /// End the current line; 2 leaves a blank line, as between paragraphs
+    fn line_break(&mut self, breaks: usize) {
+        self.pending_breaks = self.pending_breaks.max(breaks);
+    }
+}
+
+/// Markup-to-text conversion of copied web content: tags are removed (<br> and block
+/// elements become line breaks, <p> a blank line, <li> "- "), entities are decoded and
+/// whitespace is collapsed. A forgiving scanner rather than an HTML parser: a '<' that
+/// doesn't open a complete tag is kept as text, as is an unknown entity.
+pub fn strip_html_with(text: &str, options: &HtmlOptions) -> String {
+    let mut w = Writer {
+        out: String::with_capacity(text.len()),
+        pending_space: false,
+        pending_breaks: 0,
+    };
+    // Link open: (href, output length when its text started)
+    let mut link: Option<(String, usize)> = None;
+
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            if let Some(end) = comment.find("-->") {
+                i += 4 + end + 3;
+                continue;
+            }
+        } else if rest.starts_with('<') {
+            if let Some(tag) = Tag::parse(rest) {
+                i += tag.len;
+                let name = tag.name.to_ascii_lowercase();
+
+                if !tag.closing && SKIPPED_TAGS.contains(&name.as_str()) {
+                    i += skip_element(&text[i..], &name);
+                    continue;
+                }
+
+                match name.as_str() {
+                    "br" => w.line_break(1),
+                    "p" => w.line_break(2),
+                    "li" if !tag.closing => {
+                        w.line_break(1);
+                        w.flush();
+                        w.out.push_str("- ");
+                    }
+                    "li" => w.line_break(1),
+                    "td" | "th" => w.push(' '),
+                    "a" if options.keep_link_urls && !tag.closing => {
+                        link = tag.attribute("href").map(|href| {
+                            w.flush();
+                            (decode_entities(href.trim()), w.out.len())
+                        });
+                    }
+                    "a" if tag.closing => {
+                        if let Some((href, start)) = link.take() {
+                            let link_text = w.out.get(start..).unwrap_or("").trim();
+                            if !href.is_empty() && !href.starts_with('#') && link_text != href {
+                                w.push(' ');
+                                w.push('(');
+                                w.push_str(&href);
+                                w.push(')');
+                            }
+                        }
+                    }
+                    _ if BLOCK_TAGS.contains(&name.as_str()) => w.line_break(1),
+                    _ => {}
+                }
+                continue;
+            }
+        } else if rest.starts_with('&') {
+            if let Some((c, len)) = entity(rest) {
+                w.push(c);
+                i += len;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or('\u{FFFD}');
+        // A soft hyphen only marks where a word may break
+        if c != '\u{00AD}' {
+            w.push(c);
+        }
+        i += c.len_utf8();
+    }
+
+    w.out
+}
+
+#[cfg(feature = "json")]
+/// Convert pasted HTML to plain text; see strip_html_with
+/// options_json is an HtmlOptions object ({"keepLinkUrls"}); "" uses the defaults
+pub fn strip_html(text: &str, options_json: &str) -> Result<String, WebtoolsError> {
+    let options = HtmlOptions::from_json(options_json)?;
+    Ok(strip_html_with(text, &options))
+}
+
+/// One start or end tag, e.g. `<a href="x">` or `</p>`
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    /// Everything between the name and the closing '>'
+    attributes: &'a str,
+    /// Bytes from '<' through '>'
+    len: usize,
+}
+
+impl<'a> Tag<'a> {
+    /// Parse a tag at the start of `s` (which starts with '<'); None if it isn't one
+    fn parse(s: &'a str) -> Option<Tag<'a>> {
+        let bytes = s.as_bytes();
+        let mut i = 1;
+        let closing = bytes.get(i) == Some(&b'/');
+        if closing {
+            i += 1;
+        }
+        // <!DOCTYPE ...> and <?xml ...?> are dropped like tags with no name
+        let declaration = !closing && matches!(bytes.get(i), Some(b'!' | b'?'));
+        if !declaration && !bytes.get(i)?.is_ascii_alphabetic() {
+            return None;
+        }
+
+        let name_start = i;
+        while bytes
+            .get(i)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'-')
+        {
+            i += 1;
+        }
+        let name = if declaration { "" } else { &s[name_start..i] };
+
+        let end = i + tag_end(&s[i..])?;
+        Some(Tag {
+            name,
+            closing,
+            attributes: &s[i..end],
+            len: end + 1,
+        })
+    }
+
+    /// Value of an attribute, without its quotes
+    fn attribute(&self, wanted: &str) -> Option<&'a str> {
+        let mut rest = self.attributes;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+            if rest.is_empty() {
+                return None;
+            }
+            let name_len = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+                .unwrap_or(rest.len())
+                .max(1);
+            let name = &rest[..name_len];
+            rest = rest[name_len..].trim_start();
+
+            let mut value = "";
+            if let Some(after_equals) = rest.strip_prefix('=') {
+                let after_equals = after_equals.trim_start();
+                let (parsed, remaining) = match after_equals.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let inner = &after_equals[1..];
+                        match inner.find(quote) {
+                            Some(close) => (&inner[..close], &inner[close + 1..]),
+                            None => (inner, ""),
+                        }
+                    }
+                    _ => {
+                        let len = after_equals
+                            .find(char::is_whitespace)
+                            .unwrap_or(after_equals.len());
+                        (&after_equals[..len], &after_equals[len..])
+                    }
+                };
+                value = parsed;
+                rest = remaining;
+            }
+
+            if name.eq_ignore_ascii_case(wanted) {
+                return Some(value);
+            }
+        }
+    }
+}
+
+/// Offset of the '>' ending a tag body, skipping any inside quoted attribute values
+/// An unbalanced quote falls back to the first '>'
+fn tag_end(body: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, b) in body.bytes().enumerate() {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (None, b'>') => return Some(i),
+            (Some(q), _) if b == q => quote = None,
+            _ => {}
+        }
+    }
+    body.find('>')
+}
+
+/// Bytes up to and including `</name ...>`, or the rest of the input if it never closes
+fn skip_element(s: &str, name: &str) -> usize {
+    let mut from = 0;
+    while let Some(offset) = s[from..].find("</") {
+        let start = from + offset;
+        let after = &s[start + 2..];
+        let matches_name = after
+            .get(..name.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(name))
+            && !after
+                .as_bytes()
+                .get(name.len())
+                .is_some_and(u8::is_ascii_alphanumeric);
+        if matches_name {
+            return match after.find('>') {
+                Some(end) => start + 2 + end + 1,
+                None => s.len(),
+            };
+        }
+        from = start + 2;
+    }
+    s.len()
+}
+
+/// Decode an entity at the start of `s` (which starts with '&'): (char, bytes consumed)
+fn entity(s: &str) -> Option<(char, usize)> {
+    // Longest entity handled is "&#x10FFFF;"
+    let end = s.as_bytes().iter().take(12).position(|&b| b == b';')?;
+    let body = &s[1..end];
+
+    let c = if let Some(number) = body.strip_prefix('#') {
+        let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (number, 10),
+        };
+        if !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        let code_point = u32::from_str_radix(digits, radix).ok()?;
+        match char::from_u32(code_point) {
+            Some('\0') | None => char::REPLACEMENT_CHARACTER,
+            Some(c) => c,
+        }
+    } else {
+        NAMED_ENTITIES
+            .iter()
+            .find(|(name, _)| *name == body)
+            .map(|&(_, c)| c)?
+    };
+    Some((c, end + 1))
+}
+
+/// Decode every entity in an attribute value
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut i = 0;
+    while let Some(offset) = s[i..].find('&') {
+        out.push_str(&s[i..i + offset]);
+        i += offset;
+        match entity(&s[i..]) {
+            Some((c, len)) => {
+                out.push(c);
+                i += len;
+            }
+            None => {
+                out.push('&');
+                i += 1;
+            }
+        }
+    }
+    out.push_str(&s[i..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(html: &str) -> String {
+        strip_html_with(html, &HtmlOptions::default())
+    }
+
+    const ARTICLE: &str = r##"<!DOCTYPE html><head><title>News</title><style>p{}</style></head>
+<div class="story"><h1>Storm&nbsp;closes   schools</h1>
+<p>Officials said on Monday&hellip; that <b>all</b> schools<br>would close.</p>
+<p>Read <a href="https://example.com/a?x=1&amp;y=2">the report</a> or
+<a href="#top">go up</a>.</p><ul><li>Roads &amp; bridges</li><li>&#x5B66;&#26657;</li></ul>
+<script>if (a < b) { alert("</p>") }</script><!-- ad --></div>"##;
+
+    #[test]
+    fn news_article_fragment() {
+        assert_eq!(
+            strip(ARTICLE),
+            "Storm closes schools\n\n\
+             Officials said on Monday… that all schools\nwould close.\n\n\
+             Read the report or go up.\n\n\
+             - Roads & bridges\n\
+             - 学校"
+        );
+        let options = HtmlOptions {
+            keep_link_urls: true,
+        };
+        let with_urls = strip_html_with(ARTICLE, &options);
+        // In-page anchors give no URL
+        assert!(with_urls.contains("Read the report (https://example.com/a?x=1&y=2) or go up."));
+    }
+
+    #[test]
+    fn stray_angle_brackets_are_text() {
+        assert_eq!(strip("<<>>"), "<<>>");
+        assert_eq!(strip("a < b > c"), "a < b > c");
+        assert_eq!(strip("1 <2 and 3> 2"), "1 <2 and 3> 2");
+        assert_eq!(strip("< p>x"), "< p>x");
+    }
+
+    #[test]
+    fn unterminated_markup() {
+        assert_eq!(strip("text <p"), "text <p");
+        assert_eq!(strip("<a href=\"x>y"), "y");
+        assert_eq!(strip("before<script>never closed"), "before");
+        assert_eq!(strip("a<!-- open comment"), "a<!-- open comment");
+        assert_eq!(strip("</"), "</");
+        assert_eq!(strip("<"), "<");
+        assert_eq!(strip("&"), "&");
+        assert_eq!(strip("&amp"), "&amp");
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(strip("&lt;b&gt; &quot;&#39;&#x27;"), "<b> \"''");
+        // Unknown names, bad digits and out-of-range numbers
+        assert_eq!(strip("&bogus; &#xZZ; &#;"), "&bogus; &#xZZ; &#;");
+        assert_eq!(strip("&#0;&#x110000;&#xD800;"), "\u{FFFD}\u{FFFD}\u{FFFD}");
+        assert_eq!(strip("soft\u{AD}hyphen"), "softhyphen");
+    }
+
+    #[test]
+    fn never_panics_on_random_markup() {
+        let pieces = [
+            "<", ">", "</", "&", ";", "#x", "\"", "'", "a", "p", "br", "漢", "=", "!--", " ",
+        ];
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for _ in 0..2000 {
+            let mut html = String::new();
+            for _ in 0..(state % 30) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                html.push_str(pieces[(state % pieces.len() as u64) as usize]);
+            }
+            for keep_link_urls in [false, true] {
+                strip_html_with(&html, &HtmlOptions { keep_link_urls });
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn options_json() {
+        let html = r#"<a href="https://a.example">a</a>"#;
+        assert_eq!(strip_html(html, "").unwrap(), "a");
+        assert_eq!(
+            strip_html(html, r#"{"keepLinkUrls":true}"#).unwrap(),
+            "a (https://a.example)"
+        );
+        assert_eq!(
+            strip_html(html, r#"{"keepLinkUrls":1}"#)
+                .unwrap_err()
+                .code(),
+            "INVALID_JSON"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Repeated and near-empty lines in pasted input
+//! Chat logs and scraped pages repeat the same short lines over and over, which only makes the
+//! image longer. Lines are compared after trimming; blank lines are reported on their own and
+//! never count as duplicates, so paragraph breaks survive dedupe_lines.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+/// Most duplicate groups and short-line indices line_hygiene_report lists
+pub const MAX_HYGIENE_EXAMPLES: usize = 50;
+
+/// Source lines without their line break ("\r\n" or "\n")
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// One line that appears more than once
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateLine<'a> {
+    /// The line, trimmed
+    pub text: &'a str,
+    pub occurrences: usize,
+    pub first_index: usize,
+}
+
+/// What line_hygiene_report found
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LineHygiene<'a> {
+    pub line_count: usize,
+    /// Lines that repeat an earlier line (dedupe_lines with keep_first removes this many)
+    pub duplicate_line_count: usize,
+    /// Most repeated first, then by first occurrence; at most MAX_HYGIENE_EXAMPLES
+    pub duplicates: Vec<DuplicateLine<'a>>,
+    pub blank_line_count: usize,
+    /// Non-blank lines with fewer than short_line_chars characters after trimming
+    pub short_line_count: usize,
+    /// Indices of the first MAX_HYGIENE_EXAMPLES short lines
+    pub short_line_indices: Vec<usize>,
+}
+
+impl<'a> LineHygiene<'a> {
+    pub fn of(text: &'a str, short_line_chars: u32) -> Self {
+        let mut report = LineHygiene {
+            line_count: 0,
+            duplicate_line_count: 0,
+            duplicates: Vec::new(),
+            blank_line_count: 0,
+            short_line_count: 0,
+            short_line_indices: Vec::new(),
+        };
+        // Trimmed line -> index into duplicates of its group
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+
+        for (index, line) in lines(text).enumerate() {
+            report.line_count += 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                report.blank_line_count += 1;
+                continue;
+            }
+            if trimmed.chars().count() < short_line_chars as usize {
+                report.short_line_count += 1;
+                if report.short_line_indices.len() < MAX_HYGIENE_EXAMPLES {
+                    report.short_line_indices.push(index);
+                }
+            }
+
+            let group = *groups.entry(trimmed).or_insert_with(|| {
+                report.duplicates.push(DuplicateLine {
+                    text: trimmed,
+                    occurrences: 0,
+                    first_index: index,
+                });
+                report.duplicates.len() - 1
+            });
+            report.duplicates[group].occurrences += 1;
+        }
+
+        report.duplicates.retain(|group| group.occurrences > 1);
+        report.duplicate_line_count = report
+            .duplicates
+            .iter()
+            .map(|group| group.occurrences - 1)
+            .sum();
+        report.duplicates.sort_by(|a, b| {
+            b.occurrences
+                .cmp(&a.occurrences)
+                .then(a.first_index.cmp(&b.first_index))
+        });
+        report.duplicates.truncate(MAX_HYGIENE_EXAMPLES);
+        report
+    }
+}
+
+#[cfg(feature = "json")]
+/// Duplicate, blank and short lines of a text
+/// Returns JSON {lineCount, duplicateLineCount, duplicates: [{text, occurrences, firstIndex}],
+/// blankLineCount, shortLineCount, shortLineIndices}; see LineHygiene
+pub fn line_hygiene_report(text: &str, short_line_chars: u32) -> String {
+    serde_json::to_string(&LineHygiene::of(text, short_line_chars))
+        .unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Remove repeated lines, compared after trimming
+/// With keep_first every repeat of a line goes and its first occurrence stays; otherwise only
+/// runs of consecutive equal lines collapse to one. Blank lines are always kept, and kept
+/// lines keep their text and line break as written.
+pub fn dedupe_lines(text: &str, keep_first: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut previous: Option<&str> = None;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let repeat = !trimmed.is_empty()
+            && if keep_first {
+                !seen.insert(trimmed)
+            } else {
+                previous == Some(trimmed)
+            };
+        previous = Some(trimmed);
+        if !repeat {
+            out.push_str(line);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A header, then ten rounds of a question, three "ok"s and a blank line
+    fn chat_log() -> String {
+        let mut log = vec!["Team chat".to_string()];
+        for round in 0..10 {
+            log.push(format!("question {}?", round));
+            log.extend(["ok", "ok", " ok "].map(String::from));
+            log.push(String::new());
+        }
+        log.join("\n")
+    }
+
+    #[test]
+    fn thirty_oks_in_a_chat_log() {
+        let log = chat_log();
+        let report = LineHygiene::of(&log, 3);
+        assert_eq!(report.line_count, 51);
+        assert_eq!(report.blank_line_count, 10);
+        assert_eq!(
+            report.duplicates,
+            [DuplicateLine {
+                text: "ok",
+                occurrences: 30,
+                first_index: 2,
+            }]
+        );
+        assert_eq!(report.duplicate_line_count, 29);
+        // Every "ok" is under three characters; the questions aren't
+        assert_eq!(report.short_line_count, 30);
+        assert_eq!(report.short_line_indices[..4], [2, 3, 4, 7]);
+    }
+
+    #[test]
+    fn dedupe_keeps_the_first_or_collapses_runs() {
+        let log = chat_log();
+        let all = dedupe_lines(&log, true);
+        assert_eq!(all.matches("ok").count(), 1);
+        assert!(all.starts_with("Team chat\nquestion 0?\nok\n\nquestion 1?\n\n"));
+        // Consecutive repeats collapse to the first of each run, spaces and all
+        let runs = dedupe_lines(&log, false);
+        assert_eq!(runs.matches("ok").count(), 10);
+        assert!(runs.starts_with("Team chat\nquestion 0?\nok\n\nquestion 1?\nok\n\n"));
+        assert_eq!(LineHygiene::of(&all, 3).duplicate_line_count, 0);
+    }
+
+    #[test]
+    fn blank_lines_never_count_as_duplicates() {
+        let text = "a\r\n\r\n\r\nb\r\n  \r\na";
+        assert_eq!(dedupe_lines(text, true), "a\r\n\r\n\r\nb\r\n  \r\n");
+        assert_eq!(dedupe_lines(text, false), text);
+        let report = LineHygiene::of(text, 0);
+        assert_eq!(report.blank_line_count, 3);
+        assert_eq!(report.duplicate_line_count, 1);
+        assert_eq!(report.short_line_count, 0);
+    }
+
+    #[test]
+    fn most_repeated_first() {
+        let text = "b\na\nb\na\nb\nc\nc";
+        let order: Vec<(&str, usize)> = LineHygiene::of(text, 0)
+            .duplicates
+            .iter()
+            .map(|group| (group.text, group.occurrences))
+            .collect();
+        assert_eq!(order, [("b", 3), ("a", 2), ("c", 2)]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn report_json() {
+        assert_eq!(
+            line_hygiene_report("ok\nok\n", 3),
+            r#"{"lineCount":3,"duplicateLineCount":1,"duplicates":[{"text":"ok","occurrences":2,"firstIndex":0}],"blankLineCount":1,"shortLineCount":2,"shortLineIndices":[0,1]}"#
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Text statistics kept up to date while the user types at the end
+//! get_text_stats walks the whole text, which is too slow to call on every keystroke of a long
+//! document. When text only grew at the end, stats_after_append takes the stats of the text
+//! before and goes over nothing but the appended part and the old last line: words, RTL runs,
+//! unbreakable runs and repeated characters can join across the end of the old text, and
+//! never across a '\n' other than through whitespace between RTL characters.
+
+use crate::marks::is_zalgo;
+use crate::{
+    count_rtl_runs, count_words, is_empty_input, is_rtl_char, longest_char_repeat,
+    longest_unbreakable_run, TextCounts, TextStats,
+};
+#[cfg(feature = "json")]
+use crate::{stats_json, WebtoolsError};
+
+/// TextStats::of(text), given prev = TextStats::of(old) for an old text text starts with
+/// The old text is taken to be text's first prev.byte_count bytes. When it can't be (text is
+/// shorter, or that isn't a char boundary), when prev doesn't add up, or when the append may
+/// have removed the old longest unbreakable run (CJK typed onto its line) or repeat, the stats
+/// are computed over the whole text instead.
+pub fn stats_after_append(prev: &TextStats, text: &str) -> TextStats {
+    appended_stats(prev, text).unwrap_or_else(|| TextStats::of(text))
+}
+
+fn appended_stats(prev: &TextStats, text: &str) -> Option<TextStats> {
+    let old_len = prev.byte_count;
+    if !text.is_char_boundary(old_len) {
+        return None;
+    }
+    let (old, appended) = text.split_at(old_len);
+    if appended.is_empty() {
+        return Some(prev.clone());
+    }
+
+    // Nothing before the old last line changes
+    let line_start = old.rfind('\n').map_or(0, |i| i + 1);
+    let before = &old[..line_start];
+    let old_last = &old[line_start..];
+    let new_last = &text[line_start..];
+
+    // A line making it CJK drops its runs; the old maximum may have been one of them
+    let old_longest = longest_unbreakable_run(old_last);
+    let new_longest = longest_unbreakable_run(new_last);
+    if new_longest < old_longest && old_longest >= prev.longest_unbreakable_run {
+        return None;
+    }
+    // Likewise a mark typed onto the last character of the longest repeat ends it one early
+    let old_repeat = longest_char_repeat(old_last);
+    let new_repeat = longest_char_repeat(new_last);
+    if new_repeat < old_repeat && old_repeat >= prev.longest_char_repeat {
+        return None;
+    }
+
+    let rtl_run_count =
+        (prev.rtl_run_count + count_rtl_runs(new_last) + usize::from(rtl_joins(before, old_last)))
+            .checked_sub(count_rtl_runs(old_last) + usize::from(rtl_joins(before, new_last)))?;
+    let word_count =
+        (prev.word_count + count_words(new_last)).checked_sub(count_words(old_last))?;
+
+    let old_newlines = prev
+        .line_count
+        .checked_sub(usize::from(has_open_line(line_start > 0, old_last)))?;
+    let newlines = old_newlines + appended.bytes().filter(|&b| b == b'\n').count();
+    let text_last = &text[text.rfind('\n').map_or(0, |i| i + 1)..];
+
+    let counts = TextCounts::of(appended);
+    let cjk_count = prev.cjk_count + counts.cjk_count;
+    let char_count = prev.char_count + counts.char_count;
+    let combining_mark_count = prev.combining_mark_count + counts.mark_count;
+    Some(TextStats {
+        char_count,
+        byte_count: text.len(),
+        line_count: newlines + usize::from(has_open_line(newlines > 0, text_last)),
+        cjk_count,
+        ascii_count: prev.ascii_count + counts.ascii_count,
+        display_width: prev.display_width + counts.display_width,
+        has_cjk: cjk_count > 0,
+        word_count,
+        rtl_run_count,
+        astral_count: prev.astral_count + counts.astral_count,
+        longest_unbreakable_run: prev.longest_unbreakable_run.max(new_longest),
+        longest_char_repeat: prev.longest_char_repeat.max(new_repeat),
+        combining_mark_count,
+        zalgo_suspect: is_zalgo(combining_mark_count, char_count),
+        bom_count: prev.bom_count + counts.bom_count,
+    })
+}
+
+/// Whether an RTL run of before carries on into after: only whitespace between two RTL
+/// characters
+fn rtl_joins(before: &str, after: &str) -> bool {
+    before
+        .trim_end()
+        .chars()
+        .next_back()
+        .is_some_and(is_rtl_char)
+        && after.trim_start().chars().next().is_some_and(is_rtl_char)
+}
+
+/// Whether a text counts a line after its last '\n', as TextCounts does: last_line is what
+/// follows that '\n' (the whole text without one)
+fn has_open_line(has_newline: bool, last_line: &str) -> bool {
+    if has_newline {
+        !last_line.is_empty()
+    } else {
+        !is_empty_input(last_line)
+    }
+}
+
+#[cfg(feature = "json")]
+/// get_text_stats of text from prev_stats_json, get_text_stats' JSON for the text before an
+/// append at the end, and old_text_len, that text's length in UTF-16 code units (a JS
+/// string's length)
+/// An old_text_len the stats don't match means the edit wasn't an append to that text, and
+/// the stats are computed over the whole text; see stats_after_append for the other cases.
+pub fn stats_incremental_json(
+    prev_stats_json: &str,
+    old_text_len: usize,
+    text: &str,
+) -> Result<String, WebtoolsError> {
+    let prev: TextStats =
+        serde_json::from_str(prev_stats_json).map_err(WebtoolsError::invalid_json)?;
+    let stats = if old_text_len == prev.char_count + prev.bom_count + prev.astral_count {
+        stats_after_append(&prev, text)
+    } else {
+        TextStats::of(text)
+    };
+    Ok(stats_json(&stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_text_stats, stats_json};
+
+    const TEXTS: &[&str] = &[
+        "hello world",
+        "line one\r\nline two\r\n",
+        "cafe\u{301} au lait",
+        "family 👨\u{200D}👩\u{200D}👧 here",
+        "中文字符 and ASCII\n\n",
+        "שלום עולם\nשלום",
+        "aaaa bbbbbbbb\n  \n",
+        "\u{FEFF}x\u{301}\u{302}\u{303}",
+    ];
+
+    #[test]
+    fn an_append_at_any_char_boundary_gives_the_stats_of_the_whole_text() {
+        for text in TEXTS {
+            for (i, _) in text.char_indices().chain([(text.len(), ' ')]) {
+                let prev = TextStats::of(&text[..i]);
+                assert_eq!(
+                    stats_json(&stats_after_append(&prev, text)),
+                    get_text_stats(text),
+                    "{text:?} appended at byte {i}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn appends_across_a_crlf_a_mark_and_a_zwj_sequence() {
+        for (old, appended) in [
+            ("one\r", "\ntwo"),
+            ("one\r\n", "\r\n"),
+            ("cafe", "\u{301}"),
+            ("cafe\u{301}", "\u{302} noir"),
+            ("👨\u{200D}", "👩"),
+            ("👨", "\u{200D}👩\u{200D}👧"),
+        ] {
+            let text = format!("{old}{appended}");
+            assert_eq!(
+                stats_after_append(&TextStats::of(old), &text),
+                TextStats::of(&text),
+                "{old:?} + {appended:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn stats_that_are_not_of_a_prefix_are_recomputed() {
+        let prev = TextStats::of("a longer text than the new one");
+        assert_eq!(stats_after_append(&prev, "short"), TextStats::of("short"));
+        // Byte 1 is inside "é"
+        let prev = TextStats::of("a");
+        assert_eq!(stats_after_append(&prev, "éa"), TextStats::of("éa"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_form_takes_the_utf16_length() {
+        let old = "😀 word";
+        let text = "😀 words";
+        let old_len = old.encode_utf16().count();
+        assert_eq!(
+            stats_incremental_json(&get_text_stats(old), old_len, text).unwrap(),
+            get_text_stats(text)
+        );
+        // A length the stats don't match falls back to the whole text
+        assert_eq!(
+            stats_incremental_json(&get_text_stats(old), old_len + 3, text).unwrap(),
+            get_text_stats(text)
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_form_rejects_invalid_stats() {
+        for json in [
+            "",
+            "{",
+            "[]",
+            r#"{"charCount": 1}"#,
+            r#"{"charCount": "1"}"#,
+        ] {
+            let err = stats_incremental_json(json, 0, "text").unwrap_err();
+            assert_eq!(err.code(), "INVALID_JSON", "{json:?}");
+        }
+        let extra = get_text_stats("a").replace('}', r#","extra":1}"#);
+        assert!(stats_incremental_json(&extra, 1, "ab").is_err());
+    }
+}
@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::capabilities::{expected_values, find_value};
+use crate::paragraphs::{paragraph_segments, ParagraphKind};
+use crate::{OptionValue, WebtoolsError};
+
+/// Tab width dedent uses when the caller doesn't pick one
+pub const DEFAULT_TAB_WIDTH: u32 = 4;
+
+/// Columns a leading whitespace character advances to from `column`
+/// U+3000 (ideographic space) is indentation too and takes two columns; None for anything
+/// that doesn't count as indentation
+fn advance(c: char, column: u32, tab_width: u32) -> Option<u32> {
+    match c {
+        ' ' => Some(column + 1),
+        '\t' => Some((column / tab_width + 1) * tab_width),
+        '\u{3000}' => Some(column + 2),
+        _ => None,
+    }
+}
+
+/// Width of a line's indentation in columns
+fn indent_width(line: &str, tab_width: u32) -> u32 {
+    let mut column = 0;
+    for c in line.chars() {
+        match advance(c, column, tab_width) {
+            Some(next) => column = next,
+            None => break,
+        }
+    }
+    column
+}
+
+/// Lines split on '\n' with any trailing '\r' split off, so it can be put back unchanged
+fn lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split('\n').map(|line| match line.strip_suffix('\r') {
+        Some(line) => (line, "\r"),
+        None => (line, ""),
+    })
+}
+
+fn join_lines<'a>(lines: impl Iterator<Item = (String, &'a str)>, capacity: usize) -> String {
+    let mut out = String::with_capacity(capacity);
+    for (index, (line, cr)) in lines.enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        out.push_str(&line);
+        out.push_str(cr);
+    }
+    out
+}
+
+/// Remove the longest common leading indentation, measured in columns with tabs advancing to
+/// the next multiple of tab_width (a tab_width of 0 is treated as 1)
+/// Whitespace-only lines don't take part and come out empty. When a tab straddles the cut,
+/// the columns it covers past the cut are kept as spaces.
+pub fn dedent(text: &str, tab_width: u32) -> String {
+    let tab_width = tab_width.max(1);
+    let common = lines(text)
+        .filter(|(line, _)| !line.trim().is_empty())
+        .map(|(line, _)| indent_width(line, tab_width))
+        .min()
+        .unwrap_or(0);
+
+    let dedented = lines(text).map(|(line, cr)| {
+        if line.trim().is_empty() {
+            return (String::new(), cr);
+        }
+
+        let mut column = 0;
+        let mut rest = line;
+        while column < common {
+            let mut chars = rest.chars();
+            let Some(next) = chars.next().and_then(|c| advance(c, column, tab_width)) else {
+                break;
+            };
+            column = next;
+            rest = chars.as_str();
+        }
+
+        let overshoot = (column - common.min(column)) as usize;
+        (format!("{}{}", " ".repeat(overshoot), rest), cr)
+    });
+    join_lines(dedented, text.len())
+}
+
+/// Add prefix to the start of every line that has non-whitespace content, or of every line
+/// when include_empty is set
+/// The prefix may be fullwidth text; it's measured like any other text by later wrapping.
+pub fn indent(text: &str, prefix: &str, include_empty: bool) -> String {
+    let indented = lines(text).map(|(line, cr)| {
+        if include_empty || !line.trim().is_empty() {
+            (format!("{}{}", prefix, line), cr)
+        } else {
+            (line.to_string(), cr)
+        }
+    });
+    join_lines(indented, text.len() + prefix.len() * 8)
+}
+
+/// What detect_indentation found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    /// No indented lines
+    None,
+    Tabs,
+    Spaces,
+}
+
+/// Indentation style and the size of one level: spaces per level for Spaces, 1 for Tabs,
+/// 0 for None
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Indentation {
+    pub style: IndentStyle,
+    pub size: u32,
+}
+
+/// Guess how text is indented from its indented lines
+/// The style is whichever of tabs or spaces starts more lines. The space size is the most
+/// common increase in indentation between consecutive non-blank space-indented lines (ties
+/// go to the smaller size), so a file nested 4, 8, 12 deep reports 4 rather than 12.
+pub fn detect_indentation(text: &str) -> Indentation {
+    let mut tab_lines = 0usize;
+    let mut space_lines = 0usize;
+    let mut steps: HashMap<u32, usize> = HashMap::new();
+    let mut previous_spaces = 0u32;
+
+    for (line, _) in lines(text) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+
+        let spaces = (line.len() - line.trim_start_matches(' ').len()) as u32;
+        if spaces > 0 {
+            space_lines += 1;
+        }
+        if spaces > previous_spaces {
+            *steps.entry(spaces - previous_spaces).or_default() += 1;
+        }
+        previous_spaces = spaces;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return Indentation {
+            style: IndentStyle::None,
+            size: 0,
+        };
+    }
+    if tab_lines > space_lines {
+        return Indentation {
+            style: IndentStyle::Tabs,
+            size: 1,
+        };
+    }
+
+    let size = steps
+        .into_iter()
+        .max_by(|(a_step, a_count), (b_step, b_count)| {
+            a_count.cmp(b_count).then(b_step.cmp(a_step))
+        })
+        .map(|(step, _)| step)
+        .unwrap_or(0);
+    Indentation {
+        style: IndentStyle::Spaces,
+        size,
+    }
+}
+
+/// detect_indentation as JSON {style: "none"|"tabs"|"spaces", size}
+pub fn detect_indentation_json(text: &str) -> String {
+    let indentation = detect_indentation(text);
+    let style = match indentation.style {
+        IndentStyle::None => "none",
+        IndentStyle::Tabs => "tabs",
+        IndentStyle::Spaces => "spaces",
+    };
+    format!(r#"{{"style":"{}","size":{}}}"#, style, indentation.size)
+}
+
+/// Which way convert_indentation rewrites leading whitespace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentConversion {
+    TabsToSpaces,
+    SpacesToTabs,
+}
+
+impl IndentConversion {
+    /// Parse "tabs_to_spaces" / "spaces_to_tabs" in any case
+    pub fn parse(mode: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| mode.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown indentation mode: {} (expected {})",
+                mode,
+                expected_values::<IndentConversion>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for IndentConversion {
+    const KIND: &'static str = "indentConversion";
+    const USED_BY: &'static [&'static str] = &["convert_indentation"];
+    const ALL: &'static [Self] = &[
+        IndentConversion::TabsToSpaces,
+        IndentConversion::SpacesToTabs,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            IndentConversion::TabsToSpaces => "tabs_to_spaces",
+            IndentConversion::SpacesToTabs => "spaces_to_tabs",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            IndentConversion::TabsToSpaces => "Leading tabs become spaces",
+            IndentConversion::SpacesToTabs => "Leading spaces become tabs where a whole tab fits",
+        }
+    }
+}
+
+/// Length in bytes of a line's leading run of spaces and tabs
+fn leading_len(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+/// Rewrite the leading spaces and tabs of every line, keeping the column the content starts at
+/// Tabs advance to the next multiple of tab_width (0 is treated as 1). SpacesToTabs writes as
+/// many tabs as fit and spaces for the rest; whitespace after the first non-blank character,
+/// such as alignment spaces before a comment, is never touched.
+pub fn convert_indentation_with(text: &str, mode: IndentConversion, tab_width: u32) -> String {
+    let tab_width = tab_width.max(1);
+    let converted = lines(text).map(|(line, cr)| {
+        let (leading, rest) = line.split_at(leading_len(line));
+        let columns = indent_width(leading, tab_width);
+        let indent = match mode {
+            IndentConversion::TabsToSpaces => " ".repeat(columns as usize),
+            IndentConversion::SpacesToTabs => format!(
+                "{}{}",
+                "\t".repeat((columns / tab_width) as usize),
+                " ".repeat((columns % tab_width) as usize)
+            ),
+        };
+        (format!("{}{}", indent, rest), cr)
+    });
+    join_lines(converted, text.len() + text.len() / 4)
+}
+
+/// Convert leading whitespace with mode "tabs_to_spaces" or "spaces_to_tabs"; see
+/// convert_indentation_with
+pub fn convert_indentation(
+    text: &str,
+    mode: &str,
+    tab_width: u32,
+) -> Result<String, WebtoolsError> {
+    let mode = IndentConversion::parse(mode)?;
+    Ok(convert_indentation_with(text, mode, tab_width))
+}
+
+/// A line whose leading whitespace mixes tabs and spaces
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MixedIndentLine {
+    /// 1-based line number
+    pub line: usize,
+    pub tabs: usize,
+    pub spaces: usize,
+}
+
+/// Lines whose indentation has both tabs and spaces, in order
+/// Only reports: a tab may be meaningful (Makefile recipes), so nothing is rewritten here.
+/// Whitespace-only lines are ignored.
+pub fn lint_indentation(text: &str) -> Vec<MixedIndentLine> {
+    lines(text)
+        .enumerate()
+        .filter(|(_, (line, _))| !line.trim().is_empty())
+        .filter_map(|(index, (line, _))| {
+            let leading = &line[..leading_len(line)];
+            let tabs = leading.matches('\t').count();
+            let spaces = leading.len() - tabs;
+            (tabs > 0 && spaces > 0).then_some(MixedIndentLine {
+                line: index + 1,
+                tabs,
+                spaces,
+            })
+        })
+        .collect()
+}
+
+/// lint_indentation as a JSON array [{line, tabs, spaces}]
+pub fn lint_indentation_json(text: &str) -> String {
+    let entries: Vec<String> = lint_indentation(text)
+        .iter()
+        .map(|mixed| {
+            format!(
+                r#"{{"line":{},"tabs":{},"spaces":{}}}"#,
+                mixed.line, mixed.tabs, mixed.spaces
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+/// The indent Chinese paragraphs conventionally start with: two ideographic spaces
+pub const CJK_PARAGRAPH_INDENT: &str = "\u{3000}\u{3000}";
+
+/// What normalize_cjk_indent does to the start of each prose paragraph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CjkIndentMode {
+    /// Exactly two U+3000, whatever indent was there before
+    Add,
+    /// No indent
+    Remove,
+    /// Nothing changes
+    Leave,
+}
+
+impl CjkIndentMode {
+    /// Parse "add" / "remove" / "leave" in any case
+    pub fn parse(mode: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| mode.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown CJK indent mode: {} (expected {})",
+                mode,
+                expected_values::<CjkIndentMode>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for CjkIndentMode {
+    const KIND: &'static str = "cjkIndentMode";
+    const USED_BY: &'static [&'static str] = &["normalize_cjk_indent"];
+    const ALL: &'static [Self] = &[
+        CjkIndentMode::Add,
+        CjkIndentMode::Remove,
+        CjkIndentMode::Leave,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CjkIndentMode::Add => "add",
+            CjkIndentMode::Remove => "remove",
+            CjkIndentMode::Leave => "leave",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CjkIndentMode::Add => "Two ideographic spaces at the start of every paragraph",
+            CjkIndentMode::Remove => "No indent at the start of any paragraph",
+            CjkIndentMode::Leave => "Paragraph indents stay as they are",
+        }
+    }
+}
+
+/// The indent of every prose paragraph (see split_paragraphs) set by mode
+/// The leading run of U+3000, spaces and tabs on a paragraph's first line is replaced by
+/// CJK_PARAGRAPH_INDENT (Add) or dropped (Remove); the paragraph's other lines, list items,
+/// headings, code blocks and blank lines are left as they are. Applying a mode twice gives
+/// what applying it once does, and Remove after Add gives what Remove alone does.
+pub fn normalize_cjk_indent_with(text: &str, mode: CjkIndentMode) -> String {
+    let new_indent = match mode {
+        CjkIndentMode::Add => CJK_PARAGRAPH_INDENT,
+        CjkIndentMode::Remove => "",
+        CjkIndentMode::Leave => return text.to_string(),
+    };
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    let mut copied = 0;
+    for segment in paragraph_segments(text) {
+        if segment.kind != ParagraphKind::Prose {
+            continue;
+        }
+        let start = segment.text.as_ptr() as usize - text.as_ptr() as usize;
+        let body = segment.text.trim_start_matches(['\u{3000}', ' ', '\t']);
+        out.push_str(&text[copied..start]);
+        out.push_str(new_indent);
+        out.push_str(body);
+        copied = start + segment.text.len();
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Add, remove or leave the indent of every prose paragraph, mode being "add", "remove" or
+/// "leave"; see normalize_cjk_indent_with
+pub fn normalize_cjk_indent(text: &str, mode: &str) -> Result<String, WebtoolsError> {
+    let mode = CjkIndentMode::parse(mode)?;
+    Ok(normalize_cjk_indent_with(text, mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_blank_line_doesnt_defeat_the_common_prefix() {
+        // The empty line and the two-space line would make the common indent 0 if they counted
+        let snippet = "    fn main() {\n\n  \n        run();\n    }\n";
+        assert_eq!(dedent(snippet, 4), "fn main() {\n\n\n    run();\n}\n");
+    }
+
+    #[test]
+    fn tabs_and_spaces_dedent_by_columns() {
+        // A tab and four spaces are the same 4 columns
+        assert_eq!(dedent("\tif x:\n        y\n    z", 4), "if x:\n    y\nz");
+        // At tab width 8 the tab is 8 columns; the rest of it past the cut stays as spaces
+        assert_eq!(dedent("\ta\n    b", 8), "    a\nb");
+        // "  \t" reaches column 4 too
+        assert_eq!(dedent("  \ta\n\tb", 4), "a\nb");
+        assert_eq!(dedent("\u{3000}\u{3000}段落\n    续", 4), "段落\n续");
+        assert_eq!(dedent("  a\r\n  b\r\n", 0), "a\r\nb\r\n");
+        assert_eq!(dedent("no indent\n  here", 4), "no indent\n  here");
+    }
+
+    #[test]
+    fn indent_adds_a_prefix() {
+        assert_eq!(indent("a\n\nb", "> ", false), "> a\n\n> b");
+        assert_eq!(indent("a\n\nb", "> ", true), "> a\n> \n> b");
+        // A fullwidth prefix counts its own width when the result is wrapped
+        let indented = indent("字字", "\u{3000}", false);
+        assert_eq!(indented, "\u{3000}字字");
+        assert_eq!(crate::calculate_text_width(&indented), 6);
+        assert_eq!(indent("a\r\n b\r\n", "  ", false), "  a\r\n   b\r\n");
+        assert_eq!(dedent(&indent("x\n  y", "\t", false), 4), "x\n  y");
+    }
+
+    #[test]
+    fn detects_the_dominant_indentation() {
+        let four = "a\n    b\n        c\n            d\n    e\n";
+        assert_eq!(
+            detect_indentation(four),
+            Indentation {
+                style: IndentStyle::Spaces,
+                size: 4
+            }
+        );
+        let two = "a\n  b\n    c\n  d\n";
+        assert_eq!(detect_indentation(two).size, 2);
+        // Mostly tabs, with one space-indented line
+        let tabs = "a\n\tb\n\t\tc\n  d\n\te";
+        assert_eq!(
+            detect_indentation(tabs),
+            Indentation {
+                style: IndentStyle::Tabs,
+                size: 1
+            }
+        );
+        assert_eq!(
+            detect_indentation("flat\ntext\n\n"),
+            Indentation {
+                style: IndentStyle::None,
+                size: 0
+            }
+        );
+        assert_eq!(
+            detect_indentation_json(four),
+            r#"{"style":"spaces","size":4}"#
+        );
+        assert_eq!(
+            detect_indentation_json(tabs),
+            r#"{"style":"tabs","size":1}"#
+        );
+        assert_eq!(detect_indentation_json(""), r#"{"style":"none","size":0}"#);
+    }
+
+    #[test]
+    fn converts_and_lints_mixed_files() {
+        let mixed = "\tone\n  \ttwo\n    three // aligned   here";
+        assert_eq!(
+            convert_indentation_with(mixed, IndentConversion::TabsToSpaces, 4),
+            "    one\n    two\n    three // aligned   here"
+        );
+        assert_eq!(
+            convert_indentation("      x", "SPACES_TO_TABS", 4).unwrap(),
+            "\t  x"
+        );
+        assert_eq!(
+            lint_indentation(mixed),
+            [MixedIndentLine {
+                line: 2,
+                tabs: 1,
+                spaces: 2
+            }]
+        );
+        assert_eq!(
+            lint_indentation_json(mixed),
+            r#"[{"line":2,"tabs":1,"spaces":2}]"#
+        );
+        assert_eq!(
+            convert_indentation("x", "tabs", 4).unwrap_err().code(),
+            "INVALID_ARGUMENT"
+        );
+    }
+
+    #[test]
+    fn cjk_paragraph_indents() {
+        let text = "第一段。\n\n  第二段，\n续行。";
+        let added = normalize_cjk_indent_with(text, CjkIndentMode::Add);
+        assert_eq!(
+            added,
+            "\u{3000}\u{3000}第一段。\n\n\u{3000}\u{3000}第二段，\n续行。"
+        );
+        assert_eq!(normalize_cjk_indent_with(&added, CjkIndentMode::Add), added);
+        assert_eq!(
+            normalize_cjk_indent_with(&added, CjkIndentMode::Remove),
+            normalize_cjk_indent_with(text, CjkIndentMode::Remove)
+        );
+        assert_eq!(normalize_cjk_indent(text, "leave").unwrap(), text);
+    }
+
+    /// A Chinese article pasted with every kind of paragraph indent, a list and a heading
+    const MIXED_INDENT_ARTICLE: &str = "# 标题\n\n\u{3000}\u{3000}第一段有两个全角空格。\n\
+        第二行接着第一段。\n\n    第二段用了四个半角空格。\n\n第三段没有缩进。\n\n\
+        \u{3000} \t第四段混用了空白。\n\n  - 列表项保留缩进\n";
+
+    /// The article with every prose paragraph starting with two U+3000
+    const INDENTED_ARTICLE: &str = "# 标题\n\n\u{3000}\u{3000}第一段有两个全角空格。\n\
+        第二行接着第一段。\n\n\u{3000}\u{3000}第二段用了四个半角空格。\n\n\
+        \u{3000}\u{3000}第三段没有缩进。\n\n\
+        \u{3000}\u{3000}第四段混用了空白。\n\n  - 列表项保留缩进\n";
+
+    #[test]
+    fn a_pasted_article_is_indented_and_unindented() {
+        let added = normalize_cjk_indent_with(MIXED_INDENT_ARTICLE, CjkIndentMode::Add);
+        assert_eq!(added, INDENTED_ARTICLE);
+        let removed = normalize_cjk_indent_with(&added, CjkIndentMode::Remove);
+        assert_eq!(
+            removed,
+            "# 标题\n\n第一段有两个全角空格。\n第二行接着第一段。\n\n第二段用了四个半角空格。\n\n\
+             第三段没有缩进。\n\n第四段混用了空白。\n\n  - 列表项保留缩进\n"
+        );
+        assert_eq!(
+            normalize_cjk_indent_with(&removed, CjkIndentMode::Add),
+            added
+        );
+        assert_eq!(
+            normalize_cjk_indent("\u{3000}段落", "LEAVE").as_deref(),
+            Ok("\u{3000}段落")
+        );
+        assert!(matches!(
+            normalize_cjk_indent("段落", "indent"),
+            Err(WebtoolsError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn python_snippet_with_a_mixed_line() {
+        let python = "def f(x):\n    if x:\n    \treturn 1    # one\n    return 0\n";
+        assert_eq!(
+            lint_indentation(python),
+            [MixedIndentLine {
+                line: 3,
+                tabs: 1,
+                spaces: 4
+            }]
+        );
+        let spaces = convert_indentation_with(python, IndentConversion::TabsToSpaces, 4);
+        assert_eq!(
+            spaces,
+            "def f(x):\n    if x:\n        return 1    # one\n    return 0\n"
+        );
+        assert!(lint_indentation(&spaces).is_empty());
+        // The alignment spaces before the comment stay spaces
+        assert_eq!(
+            convert_indentation_with(&spaces, IndentConversion::SpacesToTabs, 4),
+            "def f(x):\n\tif x:\n\t\treturn 1    # one\n\treturn 0\n"
+        );
+    }
+
+    #[test]
+    fn makefile_recipes_are_flagged_not_fixed() {
+        let makefile = "all: build\n\nbuild:\n\tcargo build\n  \tcargo test\n";
+        assert_eq!(
+            lint_indentation_json(makefile),
+            r#"[{"line":5,"tabs":1,"spaces":2}]"#
+        );
+        // Linting leaves the recipe tabs alone; only a conversion would touch them
+        assert_eq!(
+            convert_indentation_with(makefile, IndentConversion::SpacesToTabs, 4),
+            "all: build\n\nbuild:\n\tcargo build\n\tcargo test\n"
+        );
+    }
+}
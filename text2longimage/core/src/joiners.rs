@@ -0,0 +1,161 @@
+//! U+FEFF inside text, wrapped as the word joiner it stands for
+//! Files concatenated with `cat` leave a byte order mark at the start of every section they
+//! came from. Past the start of the text U+FEFF is ZERO WIDTH NO-BREAK SPACE: it takes no
+//! room and no line breaks next to it. Before algorithm V6 the wrapper measured it as a
+//! two-column character a character-wrapped line could break on either side of; from V6 on it
+//! is zero columns wide and glues the characters on both sides of it into one unbreakable
+//! span, which moves to the next line whole (see code_spans.rs for how such spans wrap).
+//! TextStats counts BOMs as bomCount rather than in charCount, and validate_input lists where
+//! they are.
+
+use std::ops::Range;
+
+use crate::metrics::WidthModel;
+use crate::table::astral_aware_width;
+
+/// U+FEFF, a byte order mark at the start of text and a word joiner anywhere else
+pub(crate) const BOM: char = '\u{FEFF}';
+
+/// astral_aware_width, with BOMs zero columns wide
+pub(crate) fn joiner_aware_width(c: char) -> u32 {
+    if c == BOM {
+        0
+    } else {
+        astral_aware_width(c)
+    }
+}
+
+/// A width model in which BOMs take no room
+pub(crate) struct JoinerWidths<M>(pub(crate) M);
+
+impl<M: WidthModel> WidthModel for JoinerWidths<M> {
+    fn char_width(&self, c: char) -> f64 {
+        if c == BOM {
+            0.0
+        } else {
+            self.0.char_width(c)
+        }
+    }
+
+    fn word_width(&self, word: &str) -> f64 {
+        let boms = word.matches(BOM).count() as f64;
+        self.0.word_width(word) - boms * self.0.word_width("\u{FEFF}")
+    }
+
+    fn ascii_width(&self) -> Option<f64> {
+        self.0.ascii_width()
+    }
+}
+
+/// Byte ranges of line that a BOM joins, in order: each run of BOMs with the character before
+/// and the character after it, runs sharing a character merged into one range
+/// Line breaks are never joined.
+pub(crate) fn joined_spans(line: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let joinable = |c: &char| *c != '\r' && *c != '\n';
+    for (offset, _) in line.match_indices(BOM) {
+        let start = line[..offset]
+            .chars()
+            .next_back()
+            .filter(joinable)
+            .map_or(offset, |c| offset - c.len_utf8());
+        let after = offset + BOM.len_utf8();
+        let end = line[after..]
+            .chars()
+            .next()
+            .filter(joinable)
+            .map_or(after, |c| after + c.len_utf8());
+        match spans.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => spans.push(start..end),
+        }
+    }
+    spans
+}
+
+/// a and b merged into one ascending list of ranges, overlapping ranges joined
+pub(crate) fn merge_spans(a: Vec<Range<usize>>, b: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    if b.is_empty() {
+        return a;
+    }
+    let mut all = a;
+    all.extend(b);
+    all.sort_by_key(|span| span.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(all.len());
+    for span in all {
+        match merged.last_mut() {
+            Some(last) if last.end > span.start => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clean_text_with, validate_input_report, CleanOptions, JustifyOptions, TextStats,
+        ValidationWarning,
+    };
+
+    /// A BOM at the start, one joining two Latin words and one joining two CJK characters across
+    /// where the line would break, as algorithm versions 5 and 6 wrap them
+    const BOM_EXAMPLES: [(&str, u32, &str, &str); 3] = [
+        (
+            "\u{FEFF}hello world again",
+            11,
+            "\u{FEFF}hello\r\nworld again",
+            "\u{FEFF}hello world\r\nagain",
+        ),
+        (
+            "one two\u{FEFF}three four",
+            12,
+            "one\r\ntwo\u{FEFF}three\r\nfour",
+            "one two\u{FEFF}three\r\nfour",
+        ),
+        (
+            "一二三四\u{FEFF}五六",
+            8,
+            "一二三四\r\n\u{FEFF}五六",
+            "一二三\r\n四\u{FEFF}五六",
+        ),
+    ];
+
+    #[test]
+    fn v6_never_breaks_next_to_a_bom() {
+        let with_version = |version, width| JustifyOptions {
+            max_chars_per_line: width,
+            algorithm_version: version,
+            ..JustifyOptions::default()
+        };
+        for (text, width, v5, v6) in BOM_EXAMPLES {
+            assert_eq!(with_version(5, width).justify(text), v5, "{text:?} in V5");
+            assert_eq!(with_version(6, width).justify(text), v6, "{text:?} in V6");
+        }
+    }
+
+    #[test]
+    fn boms_are_counted_reported_and_cleaned() {
+        let text = "\u{FEFF}ab\u{FEFF}cd";
+        let stats = TextStats::of(text);
+        assert_eq!((stats.char_count, stats.bom_count), (4, 2));
+        let report = validate_input_report(text, 10);
+        let boms = report
+            .warnings
+            .iter()
+            .find(|warning| warning.code() == "BYTE_ORDER_MARKS");
+        assert_eq!(
+            boms,
+            Some(&ValidationWarning::ByteOrderMarks {
+                count: 2,
+                offsets: vec![0, 5]
+            })
+        );
+        assert_eq!(clean_text_with(text, &CleanOptions::default()).text, "abcd");
+        let kept = CleanOptions {
+            strip_zero_width: false,
+            ..CleanOptions::default()
+        };
+        assert_eq!(clean_text_with(text, &kept).text, "ab\u{FEFF}cd");
+    }
+}
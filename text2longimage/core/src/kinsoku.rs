@@ -0,0 +1,573 @@
+//! Line-breaking rules (kinsoku shori) for the CJK wrapper
+//! Each locale is a pair of character tables: characters that may not start a line and ones
+//! that may not end it. The tables are plain data so adding a locale is a matter of writing
+//! two more lists and one entry in LOCALE_RULES. Callers extend a locale's tables with
+//! characters of their own (extraNoStart, extraNoEnd), which KinsokuRules checks alongside the
+//! built-in ones, and kinsoku_sets lists the built-in ones for a settings panel.
+
+use serde::Serialize;
+
+use crate::breaks::char_breaks;
+use crate::capabilities::{expected_values, find_value};
+use crate::metrics::{number_tokens, TrimTrailing, WrapSink};
+use crate::{check_max_chars_per_line, width_of, OptionValue, WebtoolsError};
+
+/// Locale whose line-breaking rules justify_text_cjk_locale applies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CjkLocale {
+    /// Pick one from the text with detect_cjk_locale
+    Auto,
+    Ja,
+    ZhHans,
+    ZhHant,
+    Ko,
+}
+
+impl CjkLocale {
+    /// Parse "ja", "zh-Hans", "zh-Hant", "ko" or "auto" in any case ('_' works for '-')
+    pub fn parse(locale: &str) -> Result<Self, WebtoolsError> {
+        let normalized = locale.replace('_', "-");
+        find_value(|tag| normalized.eq_ignore_ascii_case(tag)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown locale: {} (expected {})",
+                locale,
+                expected_values::<CjkLocale>()
+            ))
+        })
+    }
+
+    /// BCP 47 tag, as accepted by parse
+    pub fn tag(self) -> &'static str {
+        self.name()
+    }
+
+    /// This locale, or the one detected from text for Auto
+    pub fn resolve(self, text: &str) -> Self {
+        match self {
+            CjkLocale::Auto => detect_cjk_locale(text),
+            locale => locale,
+        }
+    }
+}
+
+impl OptionValue for CjkLocale {
+    const KIND: &'static str = "locale";
+    const USED_BY: &'static [&'static str] = &[
+        "justify_text_cjk_locale",
+        "break_opportunities (locale)",
+        "kinsoku_sets",
+    ];
+    const ALL: &'static [Self] = &[
+        CjkLocale::Ja,
+        CjkLocale::ZhHans,
+        CjkLocale::ZhHant,
+        CjkLocale::Ko,
+        CjkLocale::Auto,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CjkLocale::Auto => "auto",
+            CjkLocale::Ja => "ja",
+            CjkLocale::ZhHans => "zh-Hans",
+            CjkLocale::ZhHant => "zh-Hant",
+            CjkLocale::Ko => "ko",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            CjkLocale::Auto => "Picked from the text, as detect_cjk_locale does",
+            CjkLocale::Ja => "Japanese: small kana, ー and ・ never start a line",
+            CjkLocale::ZhHans => "Simplified Chinese (GB/T 15834): dashes and · never start a line",
+            CjkLocale::ZhHant => "Traditional Chinese: also the small form punctuation",
+            CjkLocale::Ko => "Korean: breaks between any syllables",
+        }
+    }
+}
+
+/// Built-in rule tables for one locale
+pub struct LocaleRules {
+    pub locale: CjkLocale,
+    /// Characters that may not start a line
+    pub no_start: &'static [char],
+    /// Characters that may not end a line
+    pub no_end: &'static [char],
+}
+
+/// Closing brackets and quotes, which stay with the text they close in every locale
+const CLOSING: &[char] = &[
+    ')', ']', '}', '）', '］', '｝', '〕', '〉', '》', '」', '』', '】', '〙', '〗', '〟', '’',
+    '”', '｠', '»',
+];
+
+/// Opening brackets and quotes, which stay with the text they open in every locale
+const OPENING: &[char] = &[
+    '(', '[', '{', '（', '［', '｛', '〔', '〈', '《', '「', '『', '【', '〘', '〖', '〝', '‘',
+    '“', '｟', '«',
+];
+
+/// Punctuation that never starts a line in any locale
+const PUNCTUATION: &[char] = &[
+    ',', '.', ':', ';', '!', '?', '，', '．', '、', '。', '：', '；', '！', '？', '…', '‥', '%',
+    '％',
+];
+
+/// Iteration marks, which repeat the character before them (人々, 日々, こゝろ) and so never
+/// start a line in any locale
+const ITERATION_MARKS: &[char] = &['々', '〻', 'ゝ', 'ゞ', 'ヽ', 'ヾ'];
+
+/// Japanese adds small kana, the prolonged sound mark and the middle dot
+const JA_NO_START: &[char] = &[
+    'ぁ', 'ぃ', 'ぅ', 'ぇ', 'ぉ', 'っ', 'ゃ', 'ゅ', 'ょ', 'ゎ', 'ゕ', 'ゖ', 'ァ', 'ィ', 'ゥ', 'ェ',
+    'ォ', 'ッ', 'ャ', 'ュ', 'ョ', 'ヮ', 'ヵ', 'ヶ', 'ㇰ', 'ㇱ', 'ㇲ', 'ㇳ', 'ㇴ', 'ㇵ', 'ㇶ', 'ㇷ',
+    'ㇸ', 'ㇹ', 'ㇺ', 'ㇻ', 'ㇼ', 'ㇽ', 'ㇾ', 'ㇿ', 'ー', '・', '゠', '〜', '‐',
+];
+
+/// Simplified Chinese (GB/T 15834) keeps the dash and ellipsis pairs and the interpunct
+/// together but lets small kana and ー fall where they will
+const ZH_HANS_NO_START: &[char] = &['—', '·', '～'];
+
+/// Traditional Chinese also uses the small form variants for its centred punctuation
+const ZH_HANT_NO_START: &[char] = &['—', '·', '～', '﹐', '﹑', '﹒', '﹔', '﹕', '﹖', '﹗'];
+
+/// Korean breaks between any syllables; only punctuation is attached
+const KO_NO_START: &[char] = &[];
+
+/// Chinese keeps currency signs with the amount after them
+const ZH_NO_END: &[char] = &['￥', '＄', '$', '¥'];
+
+const NO_EXTRA: &[char] = &[];
+
+/// Rule tables by locale; no_start and no_end are added to the shared bracket and
+/// punctuation tables
+pub const LOCALE_RULES: &[LocaleRules] = &[
+    LocaleRules {
+        locale: CjkLocale::Ja,
+        no_start: JA_NO_START,
+        no_end: NO_EXTRA,
+    },
+    LocaleRules {
+        locale: CjkLocale::ZhHans,
+        no_start: ZH_HANS_NO_START,
+        no_end: ZH_NO_END,
+    },
+    LocaleRules {
+        locale: CjkLocale::ZhHant,
+        no_start: ZH_HANT_NO_START,
+        no_end: ZH_NO_END,
+    },
+    LocaleRules {
+        locale: CjkLocale::Ko,
+        no_start: KO_NO_START,
+        no_end: NO_EXTRA,
+    },
+];
+
+/// Line-breaking rules for one resolved locale, optionally extended by the caller
+pub struct KinsokuRules {
+    locale_rules: &'static LocaleRules,
+    extra_no_start: Vec<char>,
+    extra_no_end: Vec<char>,
+}
+
+impl KinsokuRules {
+    /// Rules of locale (Auto is treated as Ja; resolve it first) plus the characters in
+    /// extra_no_start and extra_no_end, any character, repeats and ones the tables already
+    /// have included
+    pub fn new(locale: CjkLocale, extra_no_start: &str, extra_no_end: &str) -> Self {
+        let locale_rules = LOCALE_RULES
+            .iter()
+            .find(|rules| rules.locale == locale)
+            .unwrap_or(&LOCALE_RULES[0]);
+        KinsokuRules {
+            locale_rules,
+            extra_no_start: extra_no_start.chars().collect(),
+            extra_no_end: extra_no_end.chars().collect(),
+        }
+    }
+
+    pub fn locale(&self) -> CjkLocale {
+        self.locale_rules.locale
+    }
+
+    /// Whether c may not start a line
+    pub fn forbids_start(&self, c: char) -> bool {
+        CLOSING.contains(&c)
+            || PUNCTUATION.contains(&c)
+            || is_iteration_mark(c)
+            || self.locale_rules.no_start.contains(&c)
+            || self.extra_no_start.contains(&c)
+    }
+
+    /// Whether c may not end a line
+    pub fn forbids_end(&self, c: char) -> bool {
+        OPENING.contains(&c)
+            || self.locale_rules.no_end.contains(&c)
+            || self.extra_no_end.contains(&c)
+    }
+
+    /// Whether a line may end with before and the next one start with after
+    pub fn allows_break(&self, before: char, after: char) -> bool {
+        !self.forbids_end(before) && !self.forbids_start(after)
+    }
+}
+
+/// The built-in rule tables of one locale, each as one string of characters without repeats
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct KinsokuSets {
+    /// The locale's tag, as parse takes it
+    pub locale: &'static str,
+    /// Characters that may not start a line: closing brackets, punctuation, iteration marks,
+    /// then the locale's own
+    pub no_start: String,
+    /// Characters that may not end a line: opening brackets, then the locale's own
+    pub no_end: String,
+}
+
+impl KinsokuSets {
+    /// The tables KinsokuRules::new(locale, "", "") checks (Auto is treated as Ja)
+    pub fn of(locale: CjkLocale) -> Self {
+        let rules = KinsokuRules::new(locale, "", "");
+        let no_start = [
+            CLOSING,
+            PUNCTUATION,
+            ITERATION_MARKS,
+            rules.locale_rules.no_start,
+        ];
+        let no_end = [OPENING, rules.locale_rules.no_end];
+        KinsokuSets {
+            locale: rules.locale().tag(),
+            no_start: without_repeats(&no_start),
+            no_end: without_repeats(&no_end),
+        }
+    }
+}
+
+/// The characters of tables in order, each only the first time
+fn without_repeats(tables: &[&[char]]) -> String {
+    let mut chars = String::new();
+    for &c in tables.iter().copied().flatten() {
+        if !chars.contains(c) {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+#[cfg(feature = "json")]
+/// The built-in kinsoku tables of locale ("ja", "zh-Hans", "zh-Hant" or "ko"), which
+/// extraNoStart and extraNoEnd add to
+/// Returns JSON {locale, noStart, noEnd}, the tables as strings of characters. "auto" is
+/// rejected: which tables it picks depends on the text.
+pub fn kinsoku_sets(locale: &str) -> Result<String, WebtoolsError> {
+    let locale = match CjkLocale::parse(locale)? {
+        CjkLocale::Auto => {
+            return Err(WebtoolsError::invalid_argument(
+                "kinsoku_sets needs a locale, not auto",
+            ))
+        }
+        locale => locale,
+    };
+    Ok(serde_json::to_string(&KinsokuSets::of(locale)).unwrap_or_else(|_| "{}".to_string()))
+}
+
+/// Whether c is an iteration mark (々, 〻, ゝ, ゞ, ヽ, ヾ), which belongs with the character
+/// before it
+pub(crate) fn is_iteration_mark(c: char) -> bool {
+    ITERATION_MARKS.contains(&c)
+}
+
+/// Common characters that only exist in one Chinese script, for telling them apart
+const SIMPLIFIED_ONLY: &str = "这们来说时个为对会经过还没发现问题么实间应该开关长东门见车书";
+const TRADITIONAL_ONLY: &str = "這們來說時個為對會經過還沒發現問題麼實間應該開關長東門見車書";
+
+/// Guess the locale of CJK text
+/// Any kana means Japanese; Hangul outnumbering Han means Korean; otherwise Chinese, Traditional
+/// when characters only Traditional Chinese uses outnumber Simplified-only ones. Text without
+/// any of these is treated as Simplified Chinese.
+pub fn detect_cjk_locale(text: &str) -> CjkLocale {
+    let mut kana = 0usize;
+    let mut hangul = 0usize;
+    let mut han = 0usize;
+    let mut simplified = 0usize;
+    let mut traditional = 0usize;
+
+    for c in text.chars() {
+        match c as u32 {
+            0x3041..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => kana += 1,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => hangul += 1,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0x20000..=0x2A6DF => {
+                han += 1;
+                if SIMPLIFIED_ONLY.contains(c) {
+                    simplified += 1;
+                } else if TRADITIONAL_ONLY.contains(c) {
+                    traditional += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if kana > 0 {
+        CjkLocale::Ja
+    } else if hangul > han {
+        CjkLocale::Ko
+    } else if traditional > simplified {
+        CjkLocale::ZhHant
+    } else {
+        CjkLocale::ZhHans
+    }
+}
+
+/// Character wrapping with line-breaking rules
+/// Breaks per character like justify_text_cjk, but when the break would put a forbidden
+/// character at the start of the next line or the end of this one, or split a number or
+/// date token, the break moves back until both rules hold, pushing the characters after it
+/// to the next line. When no break on
+/// the line satisfies them, the line breaks at the width limit anyway, though never between an
+/// iteration mark and the character it repeats.
+/// Source lines are wrapped separately ("\r\n" and "\n" both end one) and joined with "\r\n";
+/// whitespace left at the end of a wrapped line is trimmed.
+pub fn justify_text_cjk_with_rules(
+    text: &str,
+    max_chars_per_line: u32,
+    rules: &KinsokuRules,
+) -> String {
+    let mut result = String::with_capacity(text.len() + text.len() / 20);
+    let mut pending = String::new();
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            result.push_str("\r\n");
+        }
+        let chars: Vec<char> = line.strip_suffix('\r').unwrap_or(line).chars().collect();
+        wrap_line(
+            &chars,
+            max_chars_per_line,
+            rules,
+            &mut TrimTrailing::new(&mut result, &mut pending),
+        );
+    }
+    result
+}
+
+fn wrap_line<S: WrapSink>(
+    chars: &[char],
+    max_chars_per_line: u32,
+    rules: &KinsokuRules,
+    out: &mut S,
+) {
+    // The break opportunities, plus the inside of number and date tokens too wide for a line
+    let breaks = char_breaks(chars, Some(rules));
+    let wide_numbers: Vec<_> = number_tokens(chars)
+        .into_iter()
+        .filter(|token| token.len() as u32 > max_chars_per_line)
+        .collect();
+    let mut start = 0;
+    let mut width = 0u32;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let char_width = width_of(chars[i]);
+        if i == start || width + char_width <= max_chars_per_line {
+            width += char_width;
+            i += 1;
+            continue;
+        }
+
+        // chars[i] doesn't fit: break before the last position both rules allow
+        let allowed = |k: usize| {
+            breaks[k]
+                || (wide_numbers
+                    .iter()
+                    .any(|token| token.start < k && k < token.end)
+                    && rules.allows_break(chars[k - 1], chars[k]))
+        };
+        // Forced at the limit, the break still keeps an iteration mark with its base
+        let forced = if is_iteration_mark(chars[i]) && i - 1 > start {
+            i - 1
+        } else {
+            i
+        };
+        let end = (start + 1..=i)
+            .rev()
+            .find(|&k| allowed(k))
+            .unwrap_or(forced);
+        chars[start..end].iter().for_each(|&c| out.push_char(c));
+        out.line_break();
+        start = end;
+        width = chars[start..i].iter().map(|&c| width_of(c)).sum();
+    }
+    chars[start..].iter().for_each(|&c| out.push_char(c));
+}
+
+/// justify_text_cjk with the line-breaking rules of locale ("ja", "zh-Hans", "zh-Hant", "ko" or
+/// "auto"), extended by the characters in extra_no_start and extra_no_end
+pub fn justify_text_cjk_locale(
+    text: &str,
+    max_chars_per_line: u32,
+    locale: &str,
+    extra_no_start: &str,
+    extra_no_end: &str,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let locale = CjkLocale::parse(locale)?.resolve(text);
+    let rules = KinsokuRules::new(locale, extra_no_start, extra_no_end);
+    Ok(justify_text_cjk_with_rules(
+        text,
+        max_chars_per_line,
+        &rules,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(text: &str, width: u32, locale: &str) -> String {
+        justify_text_cjk_locale(text, width, locale, "", "").unwrap()
+    }
+
+    #[test]
+    fn japanese_and_simplified_chinese_differ_on_the_documented_characters() {
+        // ー and small kana can't start a Japanese line; Chinese lets them
+        assert_eq!(wrap("あいーう", 4, "ja"), "あ\r\nいー\r\nう");
+        assert_eq!(wrap("あいーう", 4, "zh-Hans"), "あい\r\nーう");
+        assert_eq!(wrap("きゃくしゃ", 4, "ja"), "きゃ\r\nく\r\nしゃ");
+        assert_eq!(wrap("きゃくしゃ", 4, "zh-Hans"), "きゃ\r\nくし\r\nゃ");
+        // A dash can't start a Chinese line; Japanese lets it
+        assert_eq!(wrap("中文—好", 4, "zh-Hans"), "中\r\n文—\r\n好");
+        assert_eq!(wrap("中文—好", 4, "ja"), "中文\r\n—好");
+        // Both keep closing punctuation off the start of a line
+        for locale in ["ja", "zh-Hans", "zh-Hant", "ko"] {
+            assert_eq!(
+                wrap("中文。好", 4, locale),
+                "中\r\n文。\r\n好",
+                "{}",
+                locale
+            );
+        }
+    }
+
+    #[test]
+    fn korean_breaks_between_syllables() {
+        assert_eq!(wrap("한국어입니다.", 6, "ko"), "한국어\r\n입니\r\n다.");
+        assert_eq!(wrap("「한국」", 6, "ko"), "「한\r\n국」");
+    }
+
+    #[test]
+    fn extra_characters_extend_a_locale() {
+        assert_eq!(
+            justify_text_cjk_locale("中文★好", 4, "zh-Hans", "★", "").unwrap(),
+            "中\r\n文★\r\n好"
+        );
+        assert_eq!(
+            justify_text_cjk_locale("中文★好", 6, "zh-Hans", "", "★").unwrap(),
+            "中文\r\n★好"
+        );
+        let rules = KinsokuRules::new(CjkLocale::Ko, "ab", "c");
+        assert!(rules.forbids_start('a') && rules.forbids_start('。') && !rules.forbids_start('c'));
+        assert!(rules.forbids_end('c') && rules.forbids_end('「'));
+        assert!(!rules.allows_break('c', 'x') && rules.allows_break('x', 'x'));
+    }
+
+    #[test]
+    fn iteration_marks_stay_with_their_base() {
+        assert_eq!(wrap("人人々", 4, "ja"), "人\r\n人々");
+        assert_eq!(wrap("日日日々", 4, "ja"), "日日\r\n日々");
+        for (text, width, locale, expected) in [
+            ("私の人々", 6, "ja", "私の\r\n人々"),
+            ("その日々が", 6, "ja", "その\r\n日々が"),
+            ("威風堂々と", 6, "ja", "威風\r\n堂々と"),
+            ("他们堂々", 6, "zh-Hans", "他们\r\n堂々"),
+            ("他們人々", 6, "zh-Hant", "他們\r\n人々"),
+            ("사람人々", 6, "ko", "사람\r\n人々"),
+            // No break before the mark fits the rules, so the line breaks after it
+            ("「人々", 4, "ja", "「\r\n人々"),
+            ("こゝろ", 2, "ja", "こ\r\nゝ\r\nろ"),
+        ] {
+            assert_eq!(
+                wrap(text, width, locale),
+                expected,
+                "{text:?} at {width} in {locale}"
+            );
+        }
+    }
+
+    #[test]
+    fn auto_detects_the_locale() {
+        assert_eq!(detect_cjk_locale("ひらがなと漢字"), CjkLocale::Ja);
+        assert_eq!(detect_cjk_locale("한국어 텍스트 韓"), CjkLocale::Ko);
+        assert_eq!(detect_cjk_locale("这是简体中文"), CjkLocale::ZhHans);
+        assert_eq!(detect_cjk_locale("這是繁體中文"), CjkLocale::ZhHant);
+        assert_eq!(detect_cjk_locale("no CJK at all"), CjkLocale::ZhHans);
+        assert_eq!(wrap("あいーう", 4, "auto"), wrap("あいーう", 4, "ja"));
+        assert_eq!(CjkLocale::parse("ZH_hant").unwrap(), CjkLocale::ZhHant);
+        assert_eq!(
+            CjkLocale::parse("fr").unwrap_err().code(),
+            "INVALID_ARGUMENT"
+        );
+        assert_eq!(
+            justify_text_cjk_locale("x", u32::MAX, "ja", "", "")
+                .unwrap_err()
+                .code(),
+            "WIDTH_TOO_LARGE"
+        );
+    }
+
+    /// A Japanese text where a character kinsoku rules attach to the one before it follows
+    /// nearly every other, to be swapped for a custom one
+    const KINSOKU_TEXT: &str = "漢字X長い文字列X次の行へXかなXです漢字X漢字XX終わりX";
+
+    #[test]
+    fn extra_characters_wrap_as_built_in_ones() {
+        let wrap = |text: &str, width, no_start: &str, no_end: &str| {
+            justify_text_cjk_locale(text, width, "ja", no_start, no_end).unwrap()
+        };
+        for width in 4..=12 {
+            let with = |c: &str| KINSOKU_TEXT.replace('X', c);
+            // '#' as the built-in '%', '𠮷' as '。', '@' as '('
+            let percent = wrap(&with("%"), width, "", "");
+            assert_eq!(wrap(&with("#"), width, "#", ""), percent.replace('%', "#"));
+            assert_eq!(wrap(&with("%"), width, "%%", ""), percent, "repeats");
+            let period = wrap(&with("。"), width, "", "");
+            assert_eq!(
+                wrap(&with("𠮷"), width, "𠮷", ""),
+                period.replace('。', "𠮷")
+            );
+            let paren = wrap(&with("("), width, "", "");
+            assert_eq!(wrap(&with("@"), width, "", "@"), paren.replace('(', "@"));
+            let custom = wrap(&with("#"), width, "#", "");
+            assert!(
+                custom.split("\r\n").all(|line| !line.starts_with('#')),
+                "{custom:?}"
+            );
+        }
+        let hashes = KINSOKU_TEXT.replace('X', "#");
+        assert!(
+            (4..=12).any(|width| wrap(&hashes, width, "", "").contains("\r\n#")),
+            "'#' never started a line without extraNoStart"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn built_in_sets() {
+        let sets: serde_json::Value = serde_json::from_str(&kinsoku_sets("ja").unwrap()).unwrap();
+        assert_eq!(sets["locale"], "ja");
+        let no_start = sets["noStart"].as_str().unwrap();
+        assert!(no_start.starts_with(")]}") && no_start.ends_with("〜‐"));
+        assert!(no_start.contains('ー') && no_start.contains('々'));
+        assert_eq!(
+            KinsokuSets::of(CjkLocale::Ko).no_end,
+            without_repeats(&[OPENING])
+        );
+        assert_eq!(kinsoku_sets("auto").unwrap_err().code(), "INVALID_ARGUMENT");
+        assert!(kinsoku_sets("fr").is_err());
+        let ja: serde_json::Value = serde_json::from_str(&kinsoku_sets("JA").unwrap()).unwrap();
+        assert!(ja["noStart"].as_str().unwrap().contains(['%', 'ー', '々']));
+        assert!(!ja["noStart"].as_str().unwrap().contains(['#', '𠮷']));
+    }
+}
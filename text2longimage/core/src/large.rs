@@ -0,0 +1,186 @@
+//! justify_text for inputs past max_input_size, read and wrapped a window at a time
+//! Source lines wrap independently, so the input is cut after the last line break of each
+//! window and every block of whole lines is wrapped on its own, as process_text_chunks does
+//! with chunk_size 0: the blocks add up to justify_text of the whole input. Memory is
+//! O(window_bytes + the longest line), not O(window_bytes): a line is only cut at a line
+//! break, so one longer than the window is held whole, up to max_input_size bytes.
+//! justify_windows_with_stats also gives the stats of the input and output so far with each
+//! block (see stream_stats.rs), so the input needn't be kept around for get_text_stats.
+
+use std::ops::Range;
+
+use crate::expansion::ExpansionGuard;
+use crate::{
+    check_max_chars_per_line, justify_text, max_input_size, StreamStats, StreamedStats,
+    WebtoolsError,
+};
+
+/// Wrap len bytes of UTF-8 input a window of window_bytes at a time
+/// read(range, buffer) appends those bytes of the input to buffer; sink gets each block of
+/// wrapped lines as it is done, the line break between two blocks ending the first, so the
+/// blocks concatenated are justify_text of the whole input. A line longer than the window is
+/// read whole, up to max_input_size bytes; past that it is an InputTooLarge error, as is a
+/// window_bytes of 0 or over max_input_size an InvalidArgument one. Invalid UTF-8 is an
+/// error naming its offset in the input; blocks before it have already gone to sink, and an
+/// error from sink stops the wrapping. So does the output growing past max_output_ratio() times
+/// the input read so far, with OutputTooLarge (see expansion.rs).
+pub fn justify_windows<E: From<WebtoolsError>>(
+    len: usize,
+    max_chars_per_line: u32,
+    window_bytes: usize,
+    read: impl FnMut(Range<usize>, &mut Vec<u8>),
+    mut sink: impl FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    justify_windows_with_stats(len, max_chars_per_line, window_bytes, read, |block, _| {
+        sink(block)
+    })
+    .map(drop)
+}
+
+/// justify_windows, giving sink with each block the stats of the input wrapped into the
+/// blocks so far and of those blocks, and returning the stats of the whole input
+/// The stats are those of get_text_stats for the bytes read so far (see StreamedStats).
+pub fn justify_windows_with_stats<E: From<WebtoolsError>>(
+    len: usize,
+    max_chars_per_line: u32,
+    window_bytes: usize,
+    mut read: impl FnMut(Range<usize>, &mut Vec<u8>),
+    mut sink: impl FnMut(&str, &StreamedStats) -> Result<(), E>,
+) -> Result<StreamedStats, E> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let limit = max_input_size();
+    if window_bytes == 0 || window_bytes > limit {
+        return Err(WebtoolsError::invalid_argument(format!(
+            "windowBytes must be 1 to {} (see maxInputSize), got {}",
+            limit, window_bytes
+        ))
+        .into());
+    }
+
+    // Bytes read but not wrapped yet, starting at input offset `wrapped`
+    let mut pending: Vec<u8> = Vec::with_capacity(window_bytes);
+    let mut wrapped = 0;
+    let mut read_to: usize = 0;
+    let mut guard = ExpansionGuard::new();
+    let mut stats = StreamStats::new();
+    while wrapped < len {
+        let next = read_to.saturating_add(window_bytes).min(len);
+        // What is left of earlier windows has no line break, so only the new bytes are searched
+        let searched = pending.len();
+        read(read_to..next, &mut pending);
+        read_to = next;
+
+        let cut = if read_to == len {
+            pending.len()
+        } else {
+            match pending[searched..].iter().rposition(|&byte| byte == b'\n') {
+                Some(newline) => searched + newline + 1,
+                None if pending.len() > limit => {
+                    return Err(WebtoolsError::InputTooLarge {
+                        limit,
+                        actual: pending.len(),
+                    }
+                    .into());
+                }
+                // The line goes on past the window: read more of it
+                None => continue,
+            }
+        };
+        let block =
+            std::str::from_utf8(&pending[..cut]).map_err(|e| WebtoolsError::InvalidUtf8 {
+                offset: wrapped + e.valid_up_to(),
+            })?;
+
+        stats.feed(block.as_bytes())?;
+
+        let mut justified = justify_text(block, max_chars_per_line);
+        // A block of nothing but spaces wraps to "" and separates nothing
+        if wrapped + cut < len && !justified.is_empty() && !justified.ends_with("\r\n") {
+            justified.push_str("\r\n");
+        }
+        guard.add(cut, &justified)?;
+        stats.feed_output(&justified);
+        if !justified.is_empty() {
+            sink(&justified, &stats.stats_so_far())?;
+        }
+        wrapped += cut;
+        pending.drain(..cut);
+    }
+
+    Ok(stats.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::lock_limits;
+
+    const TEXTS: &[&str] = &[
+        "",
+        "short",
+        "中文字符和English words混排的一行文字。\r\nThe quick brown fox.\r\n\r\n",
+        "   \n   \nwords after blank lines\n\n\n",
+        "a line much longer than any of the windows it is read through, 長い行も\nend",
+        "\u{feff}naïve cafe\u{301} 👩\u{200d}💻 𠮷野家\r\n々\r",
+    ];
+
+    /// justify_windows_with_stats' blocks concatenated, and the most bytes read but not
+    /// wrapped at once
+    fn streamed(text: &str, width: u32, window: usize) -> (String, usize) {
+        let mut output = String::new();
+        let mut held = 0;
+        justify_windows_with_stats::<WebtoolsError>(
+            text.len(),
+            width,
+            window,
+            |range, buffer| {
+                buffer.extend_from_slice(&text.as_bytes()[range]);
+                held = held.max(buffer.len());
+            },
+            |block, _| {
+                output.push_str(block);
+                Ok(())
+            },
+        )
+        .unwrap();
+        (output, held)
+    }
+
+    #[test]
+    fn windows_of_any_size_give_justify_text_output() {
+        for text in TEXTS {
+            for width in [1, 4, 20] {
+                for window in [1, 2, 3, 5, 16, 1_000] {
+                    assert_eq!(
+                        streamed(text, width, window).0,
+                        justify_text(text, width),
+                        "{text:?} at {width} in {window}-byte windows"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_more_than_a_window_and_the_longest_line_is_held() {
+        for text in TEXTS {
+            let longest_line = text.split_inclusive('\n').map(str::len).max().unwrap_or(0);
+            for window in [1, 3, 16] {
+                let (_, held) = streamed(text, 20, window);
+                assert!(
+                    held <= longest_line + window,
+                    "{held} bytes of {text:?} in {window}-byte windows"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_window_of_zero_or_past_the_input_size_is_rejected() {
+        let _limits = lock_limits();
+        for window in [0, max_input_size() + 1] {
+            let result = justify_windows::<WebtoolsError>(1, 20, window, |_, _| {}, |_| Ok(()));
+            assert!(matches!(result, Err(WebtoolsError::InvalidArgument { .. })));
+        }
+    }
+}
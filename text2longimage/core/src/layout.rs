@@ -0,0 +1,1144 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::{expected_values, find_value};
+use crate::line_height::LineHeightSum;
+use crate::paragraphs::heading_lines;
+#[cfg(feature = "json")]
+use crate::profile::mark;
+use crate::ruby::{ruby_display_text, ruby_spans};
+use crate::{
+    count_wrapped_lines, is_empty_input, justify_text, justify_text_with_spacing, width_of,
+    width_of_str, LineScript, OptionValue, RubySyntax, ScriptLineHeights, Spacing, WebtoolsError,
+};
+#[cfg(feature = "json")]
+use crate::{count_wrapped_lines_of_output, estimate_image_size_with_limits, CanvasLimits, Stage};
+
+/// Canvas height most browsers render reliably (older Safari, mobile Chrome)
+pub const SAFE_CANVAS_HEIGHT_PX: f64 = 16_384.0;
+
+/// Hard canvas height limit in Chrome and Firefox
+pub const MAX_CANVAS_HEIGHT_PX: f64 = 32_767.0;
+
+/// Narrowest line the pixel conversion will produce: one full-width character
+pub const MIN_CHARS_PER_LINE: u32 = 2;
+
+/// Pixel width of one half-width column: ASCII = font_size / 2, CJK = font_size
+pub(crate) fn column_width_px(font_size_px: f64) -> f64 {
+    font_size_px / 2.0
+}
+
+/// How a pixel width that isn't a whole number of columns becomes a column count
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnRounding {
+    /// Round down: the text never overflows the pixel width, leaving up to one column spare
+    Floor,
+    /// Round to the closest count: the closest fit, overflowing by up to half a column
+    Round,
+    /// Round up: the text fills the pixel width, overflowing by up to one column
+    Ceil,
+}
+
+impl ColumnRounding {
+    /// Parse "floor" / "round" / "ceil" in any case
+    pub fn parse(rounding: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| rounding.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown column rounding: {} (expected {})",
+                rounding,
+                expected_values::<ColumnRounding>()
+            ))
+        })
+    }
+
+    /// columns rounded to a whole count; near-whole values count as whole, so exact
+    /// multiples neither floor one column short nor ceil one over
+    fn apply(self, columns: f64) -> f64 {
+        match self {
+            ColumnRounding::Floor => (columns + 1e-9).floor(),
+            ColumnRounding::Round => columns.round(),
+            ColumnRounding::Ceil => (columns - 1e-9).ceil(),
+        }
+    }
+}
+
+impl OptionValue for ColumnRounding {
+    const KIND: &'static str = "columnRounding";
+    const USED_BY: &'static [&'static str] = &[
+        "chars_per_line_for_width",
+        "justify_text_for_image",
+        "estimate_image_size_for_width",
+        "fit_aspect_ratio",
+    ];
+    const ALL: &'static [Self] = &[
+        ColumnRounding::Floor,
+        ColumnRounding::Round,
+        ColumnRounding::Ceil,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ColumnRounding::Floor => "floor",
+            ColumnRounding::Round => "round",
+            ColumnRounding::Ceil => "ceil",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ColumnRounding::Floor => "Never overflows the pixel width (the default)",
+            ColumnRounding::Round => "Closest fit; may overflow by half a column",
+            ColumnRounding::Ceil => "Fills the pixel width; may overflow by a column",
+        }
+    }
+}
+
+/// Column count for a pixel width, with the pixels it leaves over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColumnFit {
+    pub columns: u32,
+    /// Target width minus the width of the columns and padding: the margin left to centre
+    /// the text block in, negative when the columns overflow (Round, Ceil, or a width below
+    /// MIN_CHARS_PER_LINE)
+    pub slack_px: f64,
+}
+
+/// Columns that fit in an image of the given pixel width, rounded as rounding says
+/// (target_width_px - 2 × padding_px) / (font_size_px / 2), never below MIN_CHARS_PER_LINE
+pub fn fit_columns(
+    target_width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+    rounding: ColumnRounding,
+) -> ColumnFit {
+    let column_px = column_width_px(font_size_px);
+    let exact = (target_width_px - 2.0 * padding_px) / column_px;
+    let columns = if !exact.is_finite() || exact <= MIN_CHARS_PER_LINE as f64 {
+        MIN_CHARS_PER_LINE
+    } else {
+        rounding.apply(exact).min(u32::MAX as f64) as u32
+    };
+    ColumnFit {
+        columns,
+        slack_px: target_width_px - pixel_width_for_chars(columns, font_size_px, padding_px),
+    }
+}
+
+/// Number of half-width columns that fit in an image of the given pixel width
+/// floor((target_width_px - 2 × padding_px) / (font_size_px / 2)), never below MIN_CHARS_PER_LINE
+pub fn chars_per_line_for_width(target_width_px: f64, font_size_px: f64, padding_px: f64) -> u32 {
+    chars_per_line_for_width_with(
+        target_width_px,
+        font_size_px,
+        padding_px,
+        ColumnRounding::Floor,
+    )
+}
+
+/// chars_per_line_for_width with the given rounding; only Floor guarantees the text never
+/// overflows the pixel width
+pub fn chars_per_line_for_width_with(
+    target_width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+    rounding: ColumnRounding,
+) -> u32 {
+    fit_columns(target_width_px, font_size_px, padding_px, rounding).columns
+}
+
+/// Inverse of chars_per_line_for_width: image width needed for `chars` columns
+pub fn pixel_width_for_chars(chars: u32, font_size_px: f64, padding_px: f64) -> f64 {
+    chars as f64 * column_width_px(font_size_px) + 2.0 * padding_px
+}
+
+/// Justify text for an image of the given pixel width in one call
+pub fn justify_text_for_image(
+    text: &str,
+    width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+) -> String {
+    justify_text_for_image_with(
+        text,
+        width_px,
+        font_size_px,
+        padding_px,
+        ColumnRounding::Floor,
+    )
+}
+
+/// justify_text_for_image with the pixel width rounded to columns as rounding says
+pub fn justify_text_for_image_with(
+    text: &str,
+    width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+    rounding: ColumnRounding,
+) -> String {
+    justify_text(
+        text,
+        chars_per_line_for_width_with(width_px, font_size_px, padding_px, rounding),
+    )
+}
+
+/// Pixel dimensions of the rendered image
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSize {
+    pub width_px: f64,
+    pub height_px: f64,
+    pub line_count: u32,
+    pub exceeds_safe_canvas_height: bool,
+    pub exceeds_max_canvas_height: bool,
+}
+
+/// Image dimensions for a given number of wrapped lines, each one line height tall
+pub fn image_size(
+    line_count: u32,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+) -> ImageSize {
+    sized_image(
+        line_count,
+        line_count as f64,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+    )
+}
+
+/// Image dimensions for wrapped lines, each as tall as its heightFactor makes it (see
+/// ScriptLineHeights)
+pub fn image_size_of_lines<S: AsRef<str>>(
+    lines: &[S],
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    heights: ScriptLineHeights,
+) -> ImageSize {
+    sized_image(
+        u32::try_from(lines.len()).unwrap_or(u32::MAX),
+        LineHeightSum::of_lines(lines, heights),
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+    )
+}
+
+/// Image dimensions for line_count lines adding up to height_units line heights
+fn sized_image(
+    line_count: u32,
+    height_units: f64,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+) -> ImageSize {
+    let width_px = max_chars_per_line as f64 * column_width_px(font_size_px) + 2.0 * padding_px;
+    let height_px = height_units * font_size_px * line_height_factor + 2.0 * padding_px;
+
+    ImageSize {
+        width_px,
+        height_px,
+        line_count,
+        exceeds_safe_canvas_height: height_px > SAFE_CANVAS_HEIGHT_PX,
+        exceeds_max_canvas_height: height_px > MAX_CANVAS_HEIGHT_PX,
+    }
+}
+
+#[cfg(feature = "json")]
+/// Estimate the rendered image dimensions for the wrapped text
+/// Lines are as tall as layout_text makes them (see ScriptLineHeights). Returns JSON {widthPx, heightPx, lineCount, exceedsSafeCanvasHeight, exceedsMaxCanvasHeight,
+/// warnings}, with warnings for the default CanvasLimits (see estimate_image_size_with_limits)
+pub fn estimate_image_size(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+) -> String {
+    estimate_image_size_with_limits(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        &CanvasLimits::default(),
+    )
+}
+
+/// Horizontal alignment of a line within the text block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    /// Parse "left" / "center" / "right"; anything else falls back to left
+    pub fn parse(align: &str) -> Self {
+        find_value(|name| align == name).unwrap_or(Align::Left)
+    }
+
+    fn offset(self, block_width_px: f64, line_width_px: f64) -> f64 {
+        match self {
+            Align::Left => 0.0,
+            Align::Center => (block_width_px - line_width_px) / 2.0,
+            Align::Right => block_width_px - line_width_px,
+        }
+    }
+}
+
+impl OptionValue for Align {
+    const KIND: &'static str = "align";
+    const USED_BY: &'static [&'static str] = &[
+        "JustifyOptions.footerAlign",
+        "layout_text",
+        "layout_text_lines",
+        "layout_text_with_limits",
+    ];
+    const ALL: &'static [Self] = &[Align::Left, Align::Center, Align::Right];
+
+    fn name(self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Align::Left => "Lines start at the left edge",
+            Align::Center => "Lines are centred",
+            Align::Right => "Lines end at the right edge",
+        }
+    }
+}
+
+/// One wrapped line with its position on the canvas
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutLine<'a> {
+    /// The line as drawn; ruby notation is reduced to its base
+    pub text: Cow<'a, str>,
+    pub x_px: f64,
+    pub y_px: f64,
+    pub width_px: f64,
+    /// Readings to draw above the line (only with a ruby syntax; omitted from JSON when empty)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ruby: Vec<RubyAnnotation<'a>>,
+    /// Font size multiplier for heading lines (see layout_lines_with_headings); omitted
+    /// from JSON for body lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_scale: Option<f64>,
+    /// Line height multiplier for the scripts on the line (see ScriptLineHeights): the line
+    /// box is font size × line_height_factor × height_factor, and the next line starts below it
+    pub height_factor: f64,
+}
+
+/// A ruby reading and the span of its base on the canvas
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RubyAnnotation<'a> {
+    pub base: &'a str,
+    pub reading: &'a str,
+    /// Left edge of the base, in the same coordinates as the line's xPx
+    pub x_px: f64,
+    pub width_px: f64,
+}
+
+/// Position every line of already justified text
+pub fn layout_lines(
+    justified: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: Align,
+) -> Vec<LayoutLine<'_>> {
+    layout_lines_with_ruby(
+        justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        RubySyntax::None,
+    )
+}
+
+/// layout_lines for text justified with a ruby syntax
+/// Each line's text drops the readings, and ruby lists them with the position of their base
+pub fn layout_lines_with_ruby(
+    justified: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: Align,
+    ruby_syntax: RubySyntax,
+) -> Vec<LayoutLine<'_>> {
+    layout_lines_with_heights(
+        justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        ruby_syntax,
+        ScriptLineHeights::current(),
+    )
+}
+
+/// layout_lines_with_ruby with the given heightFactors instead of the ones currently set
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn layout_lines_with_heights(
+    justified: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: Align,
+    ruby_syntax: RubySyntax,
+    heights: ScriptLineHeights,
+) -> Vec<LayoutLine<'_>> {
+    if justified.is_empty() {
+        return Vec::new();
+    }
+
+    let geometry = LineGeometry {
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        ruby_syntax,
+        heights,
+    };
+    let mut above = LineHeightSum::default();
+    justified
+        .split("\r\n")
+        .map(|line| {
+            let (placed, script) = geometry.place(line, &above);
+            above.add(script);
+            placed
+        })
+        .collect()
+}
+
+/// What layout_lines_with_heights positions every line with
+pub(crate) struct LineGeometry {
+    pub(crate) max_chars_per_line: u32,
+    pub(crate) font_size_px: f64,
+    pub(crate) line_height_factor: f64,
+    pub(crate) padding_px: f64,
+    pub(crate) align: Align,
+    pub(crate) ruby_syntax: RubySyntax,
+    pub(crate) heights: ScriptLineHeights,
+}
+
+impl LineGeometry {
+    /// One justified line laid out below the lines counted in above, and the kind of line it
+    /// is for the ones after it
+    pub(crate) fn place<'a>(
+        &self,
+        line: &'a str,
+        above: &LineHeightSum,
+    ) -> (LayoutLine<'a>, LineScript) {
+        let column_px = column_width_px(self.font_size_px);
+        let line_height_px = self.font_size_px * self.line_height_factor;
+        let block_width_px = self.max_chars_per_line as f64 * column_px;
+
+        let spans = ruby_spans(line, self.ruby_syntax);
+        let text = ruby_display_text(line, &spans);
+        let width_px = width_of_str(&text) as f64 * column_px;
+        let x_px = self.padding_px + self.align.offset(block_width_px, width_px);
+
+        let mut column = 0;
+        let mut position = 0;
+        let ruby = spans
+            .iter()
+            .map(|span| {
+                column += width_of_str(&line[position..span.range.start]);
+                let base_columns = width_of_str(span.base);
+                let annotation = RubyAnnotation {
+                    base: span.base,
+                    reading: span.reading,
+                    x_px: x_px + column as f64 * column_px,
+                    width_px: base_columns as f64 * column_px,
+                };
+                column += base_columns;
+                position = span.range.end;
+                annotation
+            })
+            .collect();
+
+        let script = LineScript::of(&text);
+        let placed = LayoutLine {
+            text,
+            x_px,
+            y_px: self.padding_px + above.total(self.heights) * line_height_px,
+            width_px,
+            ruby,
+            font_scale: None,
+            height_factor: self.heights.factor(script),
+        };
+        (placed, script)
+    }
+}
+
+/// The kind of line a justified line is for its height, as LineGeometry::place sees it
+pub(crate) fn line_script(line: &str, ruby_syntax: RubySyntax) -> LineScript {
+    LineScript::of(&ruby_display_text(line, &ruby_spans(line, ruby_syntax)))
+}
+
+/// Wrap and position text whose Markdown headings are drawn heading_scale times larger
+/// Heading lines (ATX "#" lines and setext-underlined lines) wrap at
+/// floor(max_chars_per_line / heading_scale) columns, carry fontScale, and take a line height
+/// and column width scaled to match; every later line moves down accordingly. Line heights are
+/// also scaled by each line's heightFactor, as in layout_lines. A heading_scale
+/// that isn't a positive number counts as 1. Lines wrap and measure with spacing added (see
+/// justify_text_with_spacing), in em of each line's own font size.
+#[allow(clippy::too_many_arguments)]
+pub fn layout_lines_with_headings(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: Align,
+    heading_scale: f64,
+    spacing: Spacing,
+) -> Vec<LayoutLine<'static>> {
+    if is_empty_input(text) {
+        return Vec::new();
+    }
+    let heading_scale = if heading_scale.is_finite() && heading_scale > 0.0 {
+        heading_scale
+    } else {
+        1.0
+    };
+
+    let column_px = column_width_px(font_size_px);
+    let line_height_px = font_size_px * line_height_factor;
+    let block_width_px = max_chars_per_line as f64 * column_px;
+    let heading_chars = ((max_chars_per_line as f64 / heading_scale).floor() as u32).max(1);
+
+    let heights = ScriptLineHeights::current();
+    let mut lines = Vec::new();
+    let mut body_above = LineHeightSum::default();
+    let mut headings_above = LineHeightSum::default();
+    // Source lines wrap independently in justify_text, so each can take its own width
+    for (line, heading) in text.split('\n').zip(heading_lines(text)) {
+        let (chars, scale) = if heading {
+            (heading_chars, heading_scale)
+        } else {
+            (max_chars_per_line, 1.0)
+        };
+        for wrapped in justify_text_with_spacing(line, chars, spacing).split("\r\n") {
+            let width_px = (width_of_str(wrapped) as f64 * column_px
+                + spacing.extra_em_str(wrapped) * font_size_px)
+                * scale;
+            let script = LineScript::of(wrapped);
+            let y_px = padding_px
+                + body_above.total(heights) * line_height_px
+                + headings_above.total(heights) * line_height_px * heading_scale;
+            lines.push(LayoutLine {
+                text: Cow::Owned(wrapped.to_string()),
+                x_px: padding_px + align.offset(block_width_px, width_px),
+                y_px,
+                width_px,
+                ruby: Vec::new(),
+                font_scale: (heading && heading_scale != 1.0).then_some(heading_scale),
+                height_factor: heights.factor(script),
+            });
+            if heading {
+                headings_above.add(script);
+            } else {
+                body_above.add(script);
+            }
+        }
+    }
+
+    lines
+}
+
+#[cfg(feature = "json")]
+/// Wrap text and compute canvas positions for every line
+/// Returns JSON array of {text, xPx, yPx, widthPx, heightFactor}; yPx is the top of the line
+/// box, and each line box is heightFactor line heights tall (see ScriptLineHeights)
+/// align is "left", "center" or "right" (unknown values fall back to left)
+pub fn layout_text(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+) -> String {
+    let justified = justify_text(text, max_chars_per_line);
+    let lines = layout_lines(
+        &justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::parse(align),
+    );
+    mark(Stage::Wrap);
+
+    let json = serde_json::to_string(&lines).unwrap_or_else(|_| "[]".to_string());
+    mark(Stage::Serialize);
+    json
+}
+
+#[cfg(feature = "json")]
+/// layout_text with Markdown headings drawn heading_scale times larger
+/// Heading entries add fontScale (see layout_lines_with_headings); a heading_scale of 1
+/// gives exactly the layout_text output
+pub fn layout_text_with_headings(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    heading_scale: f64,
+) -> String {
+    layout_text_with_spacing(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        heading_scale,
+        Spacing::default(),
+    )
+}
+
+#[cfg(feature = "json")]
+/// layout_text_with_headings for text drawn with letter and word spacing
+/// Lines wrap so they still fit max_chars_per_line columns once spaced, and widthPx includes
+/// the spacing; zero spacing gives exactly the layout_text_with_headings output
+#[allow(clippy::too_many_arguments)]
+pub fn layout_text_with_spacing(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    heading_scale: f64,
+    spacing: Spacing,
+) -> String {
+    if heading_scale == 1.0 && spacing.is_zero() {
+        return layout_text(
+            text,
+            max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            align,
+        );
+    }
+    let lines = layout_lines_with_headings(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::parse(align),
+        heading_scale,
+        spacing,
+    );
+    mark(Stage::Wrap);
+
+    let json = serde_json::to_string(&lines).unwrap_or_else(|_| "[]".to_string());
+    mark(Stage::Serialize);
+    json
+}
+
+/// X offset of every character in a single line under the half-width/full-width model
+/// Offsets are relative to the line start; the last offset plus that character's width
+/// equals the line's widthPx from layout_text
+pub fn char_advances(line: &str, font_size_px: f64) -> Vec<f32> {
+    char_advances_with_spacing(line, font_size_px, Spacing::default())
+}
+
+/// char_advances for a line drawn with letter and word spacing
+/// Each character starts after the spacing of the ones before it, matching the widthPx of
+/// layout_text_with_spacing
+pub fn char_advances_with_spacing(line: &str, font_size_px: f64, spacing: Spacing) -> Vec<f32> {
+    let column_px = column_width_px(font_size_px);
+    let mut advances = Vec::with_capacity(line.len());
+    let mut columns = 0u32;
+    let mut extra_em = 0.0;
+
+    for c in line.chars() {
+        advances.push((columns as f64 * column_px + extra_em * font_size_px) as f32);
+        columns += width_of(c);
+        extra_em += spacing.extra_em(c);
+    }
+
+    advances
+}
+
+#[cfg(feature = "json")]
+/// char_advances for every line of already wrapped text (CRLF or LF separated)
+/// Returns JSON array with one array of offsets per line
+pub fn char_advances_lines(wrapped_text: &str, font_size_px: f64) -> String {
+    char_advances_lines_with_spacing(wrapped_text, font_size_px, Spacing::default())
+}
+
+#[cfg(feature = "json")]
+/// char_advances_lines for text drawn with letter and word spacing
+pub fn char_advances_lines_with_spacing(
+    wrapped_text: &str,
+    font_size_px: f64,
+    spacing: Spacing,
+) -> String {
+    let lines: Vec<Vec<f32>> = wrapped_text
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            char_advances_with_spacing(line, font_size_px, spacing)
+        })
+        .collect();
+
+    serde_json::to_string(&lines).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Width:height ratio of the text block at a given column count (line height = font size)
+fn block_aspect_ratio(text: &str, chars: u32, font_size_px: f64) -> Option<f64> {
+    let line_count = count_wrapped_lines(text, chars);
+    if line_count == 0 {
+        return None;
+    }
+    Some((chars as f64 * column_width_px(font_size_px)) / (line_count as f64 * font_size_px))
+}
+
+/// Find the max_chars_per_line in [min_chars, max_chars] whose image is closest to target_ratio
+/// target_ratio is width / height (0.75 for a 3:4 card); line height is taken as font_size_px
+/// Wider lines never produce more lines, so the ratio grows with the width and can be bisected
+pub fn fit_aspect_ratio(
+    text: &str,
+    target_ratio: f64,
+    font_size_px: f64,
+    min_chars: u32,
+    max_chars: u32,
+) -> u32 {
+    fit_aspect_ratio_with(
+        text,
+        target_ratio,
+        font_size_px,
+        min_chars,
+        max_chars,
+        ColumnRounding::Round,
+    )
+}
+
+/// fit_aspect_ratio choosing between the widths either side of target_ratio as rounding says:
+/// Floor takes the widest block no wider than the ratio, Ceil the narrowest that reaches it
+/// and Round (fit_aspect_ratio's choice) the closer of the two; min_chars and max_chars still
+/// bound it when no width is on one side
+pub fn fit_aspect_ratio_with(
+    text: &str,
+    target_ratio: f64,
+    font_size_px: f64,
+    min_chars: u32,
+    max_chars: u32,
+    rounding: ColumnRounding,
+) -> u32 {
+    let (mut low, mut high) = if min_chars <= max_chars {
+        (min_chars.max(1), max_chars.max(1))
+    } else {
+        (max_chars.max(1), min_chars.max(1))
+    };
+    let (min_chars, max_chars) = (low, high);
+
+    if count_wrapped_lines(text, max_chars) == 0 {
+        return max_chars;
+    }
+
+    // Smallest width whose ratio reaches the target
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match block_aspect_ratio(text, mid, font_size_px) {
+            Some(ratio) if ratio >= target_ratio => high = mid,
+            _ => low = mid + 1,
+        }
+    }
+    if low == min_chars {
+        return low;
+    }
+
+    // The crossing point reaches the target ratio (unless low is max_chars) and the width just
+    // before it falls short
+    let ratio = |chars: u32| block_aspect_ratio(text, chars, font_size_px);
+    let distance =
+        |chars: u32| ratio(chars).map_or(f64::INFINITY, |ratio| (ratio - target_ratio).abs());
+    let over = ratio(low).is_some_and(|ratio| ratio > target_ratio);
+    let narrower = match rounding {
+        ColumnRounding::Floor => over,
+        ColumnRounding::Round => distance(low - 1) < distance(low),
+        ColumnRounding::Ceil => false,
+    };
+    if narrower {
+        low - 1
+    } else {
+        low
+    }
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LineCountFit {
+    width: u32,
+    line_count: u32,
+    fits: bool,
+    text: String,
+}
+
+#[cfg(feature = "json")]
+/// Narrowest width in [min_chars, max_chars] (either order) that wraps into at most
+/// target_lines lines, None when even the widest doesn't
+/// Wider lines never produce more lines, so the count can be bisected.
+pub(crate) fn narrowest_width_for_lines(
+    text: &str,
+    target_lines: u32,
+    min_chars: u32,
+    max_chars: u32,
+) -> Option<u32> {
+    let (mut low, mut high) = if min_chars <= max_chars {
+        (min_chars.max(1), max_chars.max(1))
+    } else {
+        (max_chars.max(1), min_chars.max(1))
+    };
+    if count_wrapped_lines(text, high) > target_lines {
+        return None;
+    }
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if count_wrapped_lines(text, mid) <= target_lines {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Some(high)
+}
+
+#[cfg(feature = "json")]
+/// Find the narrowest width in [min_chars, max_chars] that wraps into at most target_lines lines
+/// Returns JSON {width, lineCount, fits, text}. When even max_chars overflows, the wrap at
+/// max_chars is returned with fits = false. A target above the natural line count still
+/// yields the narrowest fitting width, not the widest.
+pub fn wrap_to_line_count(text: &str, target_lines: u32, min_chars: u32, max_chars: u32) -> String {
+    let fit = narrowest_width_for_lines(text, target_lines, min_chars, max_chars);
+    let fits = fit.is_some();
+    let width = fit.unwrap_or(min_chars.max(max_chars).max(1));
+    let text = justify_text(text, width);
+    let result = LineCountFit {
+        width,
+        line_count: count_wrapped_lines_of_output(&text),
+        fits,
+        text,
+    };
+
+    serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_size_from_line_count() {
+        // 20 half-width columns of 8 px and ten 24 px lines, inside 8 px of padding
+        let size = image_size(10, 20, 16.0, 1.5, 8.0);
+        assert_eq!((size.width_px, size.height_px), (176.0, 256.0));
+        assert!(!size.exceeds_safe_canvas_height && !size.exceeds_max_canvas_height);
+
+        let tall = image_size(700, 20, 16.0, 1.5, 0.0);
+        assert_eq!(tall.height_px, 16_800.0);
+        assert!(tall.exceeds_safe_canvas_height && !tall.exceeds_max_canvas_height);
+        let taller = image_size(1_400, 20, 16.0, 1.5, 0.0);
+        assert!(taller.exceeds_safe_canvas_height && taller.exceeds_max_canvas_height);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn estimate_image_size_counts_wrapped_lines() {
+        // "hello" / "world" / "foo" at 5 columns
+        let size: serde_json::Value =
+            serde_json::from_str(&estimate_image_size("hello world foo", 5, 16.0, 1.5, 8.0))
+                .unwrap();
+        assert_eq!(size["lineCount"], 3);
+        assert_eq!(size["widthPx"], 56.0);
+        assert_eq!(size["heightPx"], 88.0);
+        assert_eq!(size["exceedsSafeCanvasHeight"], false);
+    }
+
+    #[test]
+    fn layout_lines_go_down_the_canvas() {
+        let justified =
+            crate::justify_text("长图标题\nThe body runs on over a few lines of text", 12);
+        let lines = layout_lines(&justified, 12, 16.0, 1.5, 8.0, Align::Center);
+        assert!(lines.len() > 3);
+        assert!(lines.windows(2).all(|pair| pair[0].y_px < pair[1].y_px));
+        // Four full-width characters are 64 px of the 96 px block, 16 px in from either side
+        let title = &lines[0];
+        assert_eq!(title.text, "长图标题");
+        assert_eq!((title.x_px, title.y_px, title.width_px), (24.0, 8.0, 64.0));
+        let right = layout_lines(&justified, 12, 16.0, 1.5, 8.0, Align::Right);
+        assert_eq!(right[0].x_px, 40.0);
+    }
+
+    #[test]
+    fn char_advances_of_mixed_lines() {
+        // Half-width characters take 8 px at 16 px, full-width ones 16 px
+        assert_eq!(
+            char_advances("ab漢c字d", 16.0),
+            [0.0, 8.0, 16.0, 32.0, 40.0, 56.0]
+        );
+        assert_eq!(char_advances("漢字 x", 20.0), [0.0, 20.0, 40.0, 50.0]);
+        assert!(char_advances("", 16.0).is_empty());
+
+        let line = "ab漢c字d";
+        let laid_out = layout_lines(line, 10, 16.0, 1.0, 0.0, Align::Left);
+        assert_eq!(laid_out[0].width_px, 64.0);
+        assert_eq!(*char_advances(line, 16.0).last().unwrap() + 8.0, 64.0);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn char_advances_lines_per_wrapped_line() {
+        assert_eq!(
+            char_advances_lines("ab\r\n漢字", 16.0),
+            "[[0.0,8.0],[0.0,16.0]]"
+        );
+    }
+
+    #[test]
+    fn columns_for_a_pixel_width_round_down() {
+        // 800 px less 2 × 20 px of padding is 760 px: 95 columns of 8 px
+        assert_eq!(chars_per_line_for_width(800.0, 16.0, 20.0), 95);
+        // 100 px of 7 px columns is 14.28…, and 14 columns only fill 98 px
+        assert_eq!(chars_per_line_for_width(100.0, 14.0, 0.0), 14);
+        assert_eq!(chars_per_line_for_width(107.9, 16.0, 0.0), 13);
+        assert_eq!(pixel_width_for_chars(95, 16.0, 20.0), 800.0);
+        // Never narrower than MIN_CHARS_PER_LINE, even with no room at all
+        assert_eq!(
+            chars_per_line_for_width(10.0, 16.0, 20.0),
+            MIN_CHARS_PER_LINE
+        );
+        assert_eq!(
+            justify_text_for_image("one two three", 56.0, 16.0, 0.0),
+            crate::justify_text("one two three", 7)
+        );
+    }
+
+    #[test]
+    fn fit_aspect_ratio_finds_the_closest_ratio() {
+        let text = "长图文字，".repeat(40) + "\n\n" + &"Some words of English prose. ".repeat(12);
+        for (target, expected) in [(1.0, 40), (0.75, 36)] {
+            let width = fit_aspect_ratio(&text, target, 16.0, 4, 120);
+            assert_eq!(width, expected, "for {target}");
+            let distance = |chars| (block_aspect_ratio(&text, chars, 16.0).unwrap() - target).abs();
+            // As close as any width a scan of the whole range finds
+            assert!(
+                (4..=120).all(|chars| distance(width) <= distance(chars)),
+                "{width} for {target}"
+            );
+        }
+        assert_eq!(fit_aspect_ratio("", 1.0, 16.0, 4, 120), 120);
+        assert_eq!(fit_aspect_ratio(&text, 100.0, 16.0, 4, 120), 120);
+        assert_eq!(fit_aspect_ratio(&text, 0.001, 16.0, 120, 4), 4);
+    }
+
+    #[test]
+    fn each_rounding_takes_the_columns_it_says() {
+        use ColumnRounding::{Ceil, Floor, Round};
+        // 20px font: 10px columns, 5px padding either side
+        for (width_px, rounding, columns, slack_px) in [
+            (410.0, Floor, 40, 0.0),
+            (410.0, Round, 40, 0.0),
+            (410.0, Ceil, 40, 0.0),
+            (409.0, Floor, 39, 9.0),
+            (409.0, Round, 40, -1.0),
+            (414.0, Round, 40, 4.0),
+            (414.0, Ceil, 41, -6.0),
+            (415.0, Floor, 40, 5.0),
+            (415.0, Round, 41, -5.0),
+            (416.0, Round, 41, -4.0),
+            (10.0, Floor, MIN_CHARS_PER_LINE, -20.0),
+        ] {
+            assert_eq!(
+                fit_columns(width_px, 20.0, 5.0, rounding),
+                ColumnFit { columns, slack_px },
+                "{width_px}px, {rounding:?}"
+            );
+        }
+        for rounding in [Floor, Round, Ceil] {
+            // 0.7 / 0.1 is 6.999999999999999
+            assert_eq!(chars_per_line_for_width_with(0.7, 0.2, 0.0, rounding), 7);
+        }
+        assert_eq!(
+            ColumnRounding::parse("CEIL").map(OptionValue::name),
+            Ok("ceil")
+        );
+        assert!(ColumnRounding::parse("truncate").is_err());
+    }
+
+    #[test]
+    fn fit_aspect_ratio_with_takes_a_width_either_side_of_the_target() {
+        use ColumnRounding::{Ceil, Floor, Round};
+        // 48 ideographs: 11 columns make a 0.55 block, 12 columns 0.75
+        let ideographs = "漢字".repeat(24);
+        for (target, expected) in [
+            (0.6, [11, 11, 12]),
+            (0.7, [11, 12, 12]),
+            (0.75, [12, 12, 12]),
+        ] {
+            let fitted = [Floor, Round, Ceil]
+                .map(|rounding| fit_aspect_ratio_with(&ideographs, target, 20.0, 2, 200, rounding));
+            assert_eq!(fitted, expected, "fit_aspect_ratio_with at {target}");
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn line_count_fit(
+        text: &str,
+        target_lines: u32,
+        min_chars: u32,
+        max_chars: u32,
+    ) -> serde_json::Value {
+        serde_json::from_str(&wrap_to_line_count(
+            text,
+            target_lines,
+            min_chars,
+            max_chars,
+        ))
+        .unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn wrap_to_line_count_takes_the_narrowest_fit() {
+        let text = "aa bb cc dd ee ff";
+        // Three lines need two words a line, which takes 5 columns
+        let fit = line_count_fit(text, 3, 2, 20);
+        assert_eq!(fit["width"], 5);
+        assert_eq!(fit["lineCount"], 3);
+        assert_eq!(fit["fits"], true);
+        assert_eq!(fit["text"], "aa bb\r\ncc dd\r\nee ff");
+
+        // One line needs all 17 columns, more than 10
+        let fit = line_count_fit(text, 1, 2, 10);
+        assert_eq!(
+            (fit["width"].clone(), fit["fits"].clone()),
+            (10.into(), false.into())
+        );
+        assert_eq!(fit["lineCount"], 2);
+        assert_eq!(fit["text"], crate::justify_text(text, 10));
+
+        // More lines than the text has at any width: the narrowest width still wins
+        let fit = line_count_fit(text, 50, 20, 2);
+        assert_eq!(
+            (fit["width"].clone(), fit["lineCount"].clone()),
+            (2.into(), 6.into())
+        );
+    }
+
+    fn heading_layout(text: &str, heading_scale: f64) -> Vec<(String, f64, f64, Option<f64>)> {
+        layout_lines_with_headings(
+            text,
+            20,
+            10.0,
+            1.0,
+            0.0,
+            Align::Left,
+            heading_scale,
+            Spacing::default(),
+        )
+        .into_iter()
+        .map(|line| {
+            (
+                line.text.into_owned(),
+                line.y_px,
+                line.width_px,
+                line.font_scale,
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn a_long_heading_wraps_at_the_reduced_width() {
+        // At twice the size a heading gets 10 of the 20 columns; each line is 20 px tall
+        let lines = heading_layout("# A heading that is long\nbody goes here", 2.0);
+        let line =
+            |text: &str, y_px, width_px, font_scale| (text.to_string(), y_px, width_px, font_scale);
+        assert_eq!(
+            lines,
+            [
+                line("# A", 0.0, 30.0, Some(2.0)),
+                line("heading", 20.0, 70.0, Some(2.0)),
+                line("that is", 40.0, 70.0, Some(2.0)),
+                line("long", 60.0, 40.0, Some(2.0)),
+                // Four heading lines above it: 80 px, not 40
+                line("body goes here", 80.0, 70.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn setext_headings_take_their_underline() {
+        let lines = heading_layout("body\nTitle\n===\nend", 2.0);
+        let tops: Vec<(&str, f64, Option<f64>)> = lines
+            .iter()
+            .map(|line| (line.0.as_str(), line.1, line.3))
+            .collect();
+        // The underline itself is drawn at body size
+        assert_eq!(
+            tops,
+            [
+                ("body", 0.0, None),
+                ("Title", 10.0, Some(2.0)),
+                ("===", 30.0, None),
+                ("end", 40.0, None)
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn a_heading_scale_of_one_is_layout_text() {
+        let text = "# Heading\nbody text that wraps\nTitle\n---";
+        assert_eq!(
+            layout_text_with_headings(text, 10, 16.0, 1.5, 4.0, "left", 1.0),
+            layout_text(text, 10, 16.0, 1.5, 4.0, "left")
+        );
+        // As is a scale that isn't a positive number
+        assert_eq!(
+            layout_text_with_headings(text, 10, 16.0, 1.5, 4.0, "left", f64::NAN),
+            layout_text(text, 10, 16.0, 1.5, 4.0, "left")
+        );
+    }
+}
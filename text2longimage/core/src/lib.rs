@@ -0,0 +1,1227 @@
+//! Text wrapping, measurement and layout behind text2longimage
+//! Plain Rust with no wasm-bindgen dependency; the wasm module and the CLI are thin layers over it
+
+use serde::{Deserialize, Serialize};
+
+mod algorithm;
+mod ansi;
+mod batch;
+#[cfg(feature = "json")]
+mod batch_json;
+mod bidi;
+mod breaks;
+mod budget;
+mod bytes;
+mod cache;
+#[cfg(feature = "json")]
+mod canvas;
+mod capabilities;
+mod case;
+mod chunking;
+mod classes;
+mod clean;
+mod code_spans;
+#[cfg(feature = "json")]
+mod config;
+mod conflicts;
+mod confusables;
+mod coverage;
+#[cfg(feature = "json")]
+mod diff;
+mod display;
+mod document;
+mod edges;
+mod edits;
+mod emoji;
+mod empty;
+#[cfg(feature = "encodings")]
+mod encodings;
+mod error;
+mod escape;
+mod estimate;
+mod expansion;
+mod find;
+mod fit;
+mod footer;
+mod guides;
+mod html;
+mod hygiene;
+mod incremental;
+mod indent;
+mod joiners;
+mod kinsoku;
+mod large;
+mod layout;
+mod limits;
+mod line_height;
+mod lines;
+mod links;
+mod lists;
+mod markdown;
+mod marks;
+mod merge;
+mod metrics;
+#[cfg(feature = "json")]
+mod myers;
+mod normalize;
+mod numbering;
+mod options;
+mod paginate;
+mod paragraphs;
+mod postconditions;
+mod preview;
+mod profile;
+mod provider;
+mod punctuation;
+mod quote;
+mod ragged;
+#[cfg(feature = "raster")]
+mod raster;
+mod reflow;
+mod repeats;
+mod ruby;
+mod runs;
+mod scan;
+#[cfg(feature = "schema")]
+mod schema;
+mod selftest;
+mod sentences;
+mod signature;
+mod slice;
+mod spacing;
+mod stream_stats;
+mod svg;
+mod table;
+mod transfer;
+mod tsv;
+mod ucd;
+mod utf16;
+mod validate;
+mod vertical;
+mod visualize;
+mod warnings;
+mod width;
+mod words;
+
+pub use algorithm::*;
+pub use ansi::strip_ansi;
+pub use batch::*;
+#[cfg(feature = "json")]
+pub use batch_json::*;
+pub use bidi::{count_rtl_runs, has_rtl, is_rtl_char};
+pub use breaks::*;
+pub use budget::*;
+pub use bytes::*;
+pub use cache::*;
+#[cfg(feature = "json")]
+pub use canvas::*;
+pub use capabilities::*;
+pub use case::*;
+pub use chunking::*;
+pub use classes::*;
+pub use clean::*;
+#[cfg(feature = "json")]
+pub use config::*;
+pub use conflicts::*;
+pub use confusables::*;
+pub use coverage::*;
+#[cfg(feature = "json")]
+pub use diff::*;
+pub use display::*;
+pub use document::*;
+pub use edges::*;
+pub use edits::*;
+#[cfg(feature = "json")]
+pub use emoji::emoji_positions;
+pub use emoji::{emoji_placements, EmojiPlacement};
+pub use empty::*;
+#[cfg(feature = "encodings")]
+pub use encodings::*;
+pub use error::WebtoolsError;
+pub use escape::*;
+pub use estimate::*;
+pub use find::*;
+pub use fit::*;
+pub use footer::*;
+pub use guides::*;
+pub use html::*;
+pub use hygiene::*;
+pub use incremental::*;
+pub use indent::*;
+pub use kinsoku::*;
+pub use large::*;
+pub use layout::*;
+pub use limits::*;
+pub use line_height::*;
+pub use lines::*;
+pub use lists::*;
+pub use markdown::strip_markdown;
+pub use marks::*;
+pub use merge::*;
+pub use metrics::*;
+pub use normalize::*;
+pub use numbering::*;
+pub use options::*;
+pub use paginate::*;
+pub use paragraphs::*;
+pub use preview::*;
+#[cfg(feature = "profiling")]
+pub use profile::set_stage_marker;
+pub use profile::Stage;
+pub use provider::*;
+pub use punctuation::*;
+pub use quote::*;
+pub use ragged::*;
+#[cfg(feature = "raster")]
+pub use raster::*;
+pub use reflow::*;
+pub use repeats::*;
+pub use ruby::RubySyntax;
+pub use runs::*;
+#[cfg(feature = "schema")]
+pub use schema::*;
+pub use selftest::*;
+pub use sentences::*;
+pub use signature::*;
+pub use slice::*;
+pub use spacing::*;
+pub use stream_stats::*;
+pub use svg::*;
+pub use transfer::*;
+pub use tsv::*;
+pub use utf16::*;
+pub use validate::*;
+pub use vertical::*;
+pub use visualize::*;
+pub use warnings::*;
+pub use width::{
+    align_to_width, get_char_width_presented, truncate_to_width, width_prefix, width_prefix_len,
+    width_suffix,
+};
+pub(crate) use width::{width_of, width_of_str};
+pub use words::*;
+
+use expansion::{CappedOutput, ExpansionGuard};
+use joiners::{JoinerWidths, BOM};
+use links::{may_contain_links, wrap_chars_splitting_links};
+use marks::{is_combining_mark, is_zalgo};
+use metrics::{
+    number_token_len, wrap_chars_with, wrap_text_into, wrap_words_with, AstralColumnWidths,
+    ColumnWidths, FractionalWidths, LineBreaking, TrimTrailing,
+};
+use postconditions::{debug_check_wrapped, WrapCheck};
+use profile::mark;
+use scan::ascii_prefix_len;
+
+/// Check if a character is CJK (Chinese, Japanese, Korean)
+/// This is a critical performance function called for every character
+/// Covers Han (U+4E00-U+9FFF), Hiragana, Katakana and CJK Extensions A and B; see table.rs
+pub fn is_cjk_char(c: char) -> bool {
+    table::is_cjk(c)
+}
+
+/// Fast check if string contains any CJK characters
+/// Optimized to return early on first match; ASCII runs are skipped a word at a time
+pub fn is_cjk(text: &str) -> bool {
+    let mut rest = text;
+    loop {
+        rest = &rest[ascii_prefix_len(rest.as_bytes())..];
+        let mut chars = rest.chars();
+        match chars.next() {
+            None => return false,
+            Some(c) if CharClass::of(c).cjk => return true,
+            Some(_) => rest = chars.as_str(),
+        }
+    }
+}
+
+/// Get character width for text justification
+/// ASCII chars = 1, CJK chars = 2
+pub fn get_char_width(c: char) -> u32 {
+    width_of(c)
+}
+
+/// Per-character classification shared by the statistics and the wrappers
+/// Everything that asks "is this CJK?" or "how wide is this?" goes through here so the
+/// stats panel and the wrapped output can't disagree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CharClass {
+    pub(crate) cjk: bool,
+    /// width_of(c), read in the same table lookup as cjk
+    pub(crate) width: u32,
+}
+
+impl CharClass {
+    #[inline]
+    pub(crate) fn of(c: char) -> Self {
+        let (cjk, width) = table::classify(c);
+        CharClass { cjk, width }
+    }
+
+    /// Single-column character (what get_text_stats reports as asciiCount)
+    #[inline]
+    pub(crate) fn is_narrow(self) -> bool {
+        self.width == 1
+    }
+
+    /// Width in em units (half the column width)
+    #[inline]
+    pub(crate) fn width_em(self) -> f64 {
+        self.width as f64 / 2.0
+    }
+}
+
+/// Fractional character width in em units
+/// ASCII chars = 0.5, CJK chars = 1.0 (get_char_width / 2)
+pub fn get_char_width_f(c: char) -> f64 {
+    CharClass::of(c).width_em()
+}
+
+/// High-performance CJK text justification
+/// Processes character-by-character with optimized width calculations.
+/// URLs and Windows paths that have to break do so after a separator (see links.rs)
+pub fn justify_text_cjk(text: &str, max_chars_per_line: u32) -> String {
+    let mut result = String::with_capacity(text.len() + text.len() / 20); // Pre-allocate with buffer
+    let mut pending = String::new();
+    let mut out = TrimTrailing::new(&mut result, &mut pending);
+    // Line by line, so a CRLF is one break and a blank line is one empty line (even with a
+    // lone '\r' in it), as in justify_text
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            out.source_line_break();
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if may_contain_links(line) {
+            wrap_chars_splitting_links(line, max_chars_per_line, None, 0, "", width_of, &mut out);
+        } else {
+            wrap_chars_with(
+                line,
+                max_chars_per_line as f64 / 2.0,
+                &ColumnWidths,
+                &mut out,
+            );
+        }
+    }
+    debug_check_wrapped(
+        &result,
+        &WrapCheck::columns("justify_text_cjk", max_chars_per_line),
+    );
+    result
+}
+
+/// High-performance English text justification
+/// Word-based wrapping with optimized string operations
+pub fn justify_text_english(text: &str, max_chars_per_line: u32) -> String {
+    let mut result = String::with_capacity(text.len() + text.len() / 20);
+    wrap_words_with(
+        text,
+        max_chars_per_line as f64 / 2.0,
+        &ColumnWidths,
+        false,
+        &mut result,
+    );
+    debug_check_wrapped(
+        &result,
+        &WrapCheck::columns("justify_text_english", max_chars_per_line),
+    );
+    result
+}
+
+/// Fractional-width justification
+/// max_width is in em units: ASCII chars are 0.5 wide, CJK chars 1.0
+pub fn justify_text_fractional(text: &str, max_width: f64) -> String {
+    wrap_text_with(text, max_width, &FractionalWidths)
+}
+
+/// Main text justification function
+/// Automatically detects CJK content and uses appropriate algorithm
+/// Every source line break is one "\r\n" in the output, so N trailing line breaks (LF or
+/// CRLF) give N trailing "\r\n"
+/// Lines are wrapped straight into one pre-sized output buffer, without per-line Strings
+pub fn justify_text(text: &str, max_chars_per_line: u32) -> String {
+    justify_text_with_trailing(text, max_chars_per_line, TrailingWhitespace::Trim)
+}
+
+/// justify_text with a choice of what happens to whitespace at the end of wrapped lines
+/// justify_text trims it; Keep leaves it for pre-wrap rendering
+pub fn justify_text_with_trailing(
+    text: &str,
+    max_chars_per_line: u32,
+    trailing: TrailingWhitespace,
+) -> String {
+    let result = justify_text_breaking(text, max_chars_per_line, &trailing.into());
+    debug_check_wrapped(
+        &result,
+        &WrapCheck::columns("justify_text", max_chars_per_line),
+    );
+    result
+}
+
+pub(crate) fn justify_text_breaking(
+    text: &str,
+    max_chars_per_line: u32,
+    breaking: &LineBreaking,
+) -> String {
+    let mut result = CappedOutput::new(text.len() + text.len() / 20, breaking.output_ceiling);
+    let max_width = max_chars_per_line as f64 / 2.0;
+    if breaking.bom_joiner {
+        let model = JoinerWidths(AstralColumnWidths);
+        wrap_text_into(text, max_width, &model, breaking, &mut result);
+    } else if breaking.narrow_astral {
+        wrap_text_into(text, max_width, &AstralColumnWidths, breaking, &mut result);
+    } else {
+        wrap_text_into(text, max_width, &ColumnWidths, breaking, &mut result);
+    }
+    result.text
+}
+
+/// Count the lines `justify_text` would produce without building the output
+/// Empty input (see is_empty_input) has zero lines
+pub fn count_wrapped_lines(text: &str, max_chars_per_line: u32) -> u32 {
+    let mut lines = 0;
+    line_records(text, max_chars_per_line, |_| lines += 1);
+    lines
+}
+
+/// One line of justify_text's output, as the line counters see it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LineRecord {
+    /// Width in columns; over the limit only for a word (or number) too wide for any line
+    pub(crate) width: u64,
+    /// Whether the line ends its source line rather than being broken by the wrapper
+    pub(crate) last: bool,
+}
+
+/// Call record for each line `justify_text` would produce, in order, without building the
+/// output; count_wrapped_lines counts them
+/// Empty input (see is_empty_input) has no lines
+pub(crate) fn line_records(
+    text: &str,
+    max_chars_per_line: u32,
+    mut record: impl FnMut(LineRecord),
+) {
+    if is_empty_input(text) {
+        return;
+    }
+
+    for line in text.split('\n') {
+        let trimmed_line = line.trim();
+        if trimmed_line.is_empty() {
+            record(LineRecord {
+                width: 0,
+                last: true,
+            });
+        } else if has_rtl(trimmed_line) {
+            // RTL runs move whole; rare enough to measure by wrapping the line
+            let justified = justify_text(trimmed_line, max_chars_per_line);
+            let mut lines = justified.split("\r\n").peekable();
+            while let Some(line) = lines.next() {
+                record(LineRecord {
+                    width: width_of_str(line),
+                    last: lines.peek().is_none(),
+                });
+            }
+        } else if is_cjk(trimmed_line) {
+            records_cjk(trimmed_line, max_chars_per_line, &mut record);
+        } else {
+            records_english(trimmed_line, max_chars_per_line, &mut record);
+        }
+    }
+}
+
+/// Number of lines in already justified output (empty output has none)
+pub fn count_wrapped_lines_of_output(justified: &str) -> u32 {
+    if justified.is_empty() {
+        0
+    } else {
+        justified.matches("\r\n").count() as u32 + 1
+    }
+}
+
+/// Line records of `justify_text_cjk` output, mirroring its break decisions
+fn records_cjk(text: &str, max_chars_per_line: u32, record: &mut impl FnMut(LineRecord)) {
+    let mut current_line_width = 0u32;
+    let place = |width: u32, current_line_width: &mut u32, record: &mut dyn FnMut(LineRecord)| {
+        if *current_line_width + width > max_chars_per_line {
+            record(LineRecord {
+                width: u64::from(*current_line_width),
+                last: false,
+            });
+            *current_line_width = width;
+        } else {
+            *current_line_width += width;
+        }
+    };
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        // Number tokens are ASCII, one column per byte
+        let token_len = number_token_len(rest.as_bytes());
+        if token_len > 1 {
+            let token_width = token_len as u32;
+            if token_width <= max_chars_per_line {
+                place(token_width, &mut current_line_width, record);
+            } else {
+                (0..token_len).for_each(|_| place(1, &mut current_line_width, record));
+            }
+            rest = &rest[token_len..];
+            continue;
+        }
+
+        rest = &rest[c.len_utf8()..];
+        if c == '\r' || c == '\n' {
+            record(LineRecord {
+                width: u64::from(current_line_width),
+                last: true,
+            });
+            current_line_width = 0;
+        } else {
+            place(CharClass::of(c).width, &mut current_line_width, record);
+        }
+    }
+
+    record(LineRecord {
+        width: u64::from(current_line_width),
+        last: true,
+    });
+}
+
+/// Line records of `justify_text_english` output, mirroring its greedy word placement
+fn records_english(text: &str, max_chars_per_line: u32, record: &mut impl FnMut(LineRecord)) {
+    let max_len = max_chars_per_line as u64;
+    let mut current_len = 0u64;
+
+    for word in text.split_whitespace() {
+        let word_len = word.len() as u64;
+        let space_needed = if current_len == 0 { 0 } else { 1 };
+
+        if current_len + space_needed + word_len <= max_len {
+            current_len += space_needed + word_len;
+        } else {
+            if current_len > 0 {
+                record(LineRecord {
+                    width: current_len,
+                    last: false,
+                });
+            }
+            current_len = word_len;
+        }
+    }
+
+    if current_len > 0 {
+        record(LineRecord {
+            width: current_len,
+            last: true,
+        });
+    }
+}
+
+#[cfg(feature = "json")]
+/// Batch process multiple text justification operations
+/// Takes JSON string array, returns JSON string array
+/// Items are capped at max_input_size() bytes each, max_batch_items() items and
+/// max_batch_size() bytes in total, checked while texts_json is parsed (see parse_batch_json)
+/// use_cache wraps duplicate items once and reuses results across calls (see justify_batch)
+pub fn batch_justify_text(
+    texts_json: &str,
+    max_chars_per_line: u32,
+    use_cache: bool,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let text_array = parse_batch_json(texts_json)?;
+    mark(Stage::Decode);
+    mark(Stage::Classify);
+
+    let options = JustifyOptions {
+        max_chars_per_line,
+        ..JustifyOptions::default()
+    };
+    let results = justify_batch(&text_array, &options, use_cache);
+    mark(Stage::Wrap);
+
+    let json = serde_json::to_string(&results).unwrap_or_else(|_| "[]".to_string());
+    mark(Stage::Serialize);
+    Ok(json)
+}
+
+/// Process text in chunks for large text handling
+/// Reduces memory pressure and enables progress tracking
+/// chunk_size is in bytes; chunk ends are moved back to the nearest character boundary.
+/// chunk_size 0 picks recommended_chunk_size and ends chunks at line breaks instead (back
+/// to the chunk's last one, or on to the next), which makes the output justify_text's.
+/// A CRLF is never split, and a chunk ending right before a line break adds no break of its
+/// own, so trailing line breaks come out as justify_text's whatever the chunk size.
+/// Text over max_input_size() bytes is rejected, and output growing past max_output_ratio()
+/// times the chunks wrapped so far stops with OutputTooLarge (see expansion.rs)
+pub fn process_text_chunks(
+    text: &str,
+    max_chars_per_line: u32,
+    chunk_size: u32,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    check_input_size(text)?;
+
+    let auto = chunk_size == 0;
+    let chunk_size = if auto {
+        recommended_chunk_size(text.len(), max_chars_per_line)
+    } else {
+        chunk_size
+    } as usize;
+    let text_len = text.len();
+
+    if text_len <= chunk_size {
+        // Small text, process directly
+        let justified = justify_text(text, max_chars_per_line);
+        ExpansionGuard::new().add(text_len, &justified)?;
+        return Ok(justified);
+    }
+
+    let mut result = String::with_capacity(text_len + text_len / 20);
+    let mut start = 0;
+    let mut guard = ExpansionGuard::new();
+
+    while start < text_len {
+        let mut end = std::cmp::min(start.saturating_add(chunk_size), text_len);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if auto && end < text_len {
+            end = match text[start..end].rfind('\n') {
+                Some(newline) => start + newline + 1,
+                None => text[end..].find('\n').map_or(text_len, |i| end + i + 1),
+            };
+        }
+        if end == start {
+            // Chunk smaller than the next character: take the whole character
+            end = start + text[start..].chars().next().map_or(0, char::len_utf8);
+        }
+        if text[..end].ends_with('\r') && text[end..].starts_with('\n') {
+            // Keep a CRLF in one chunk, so it stays one line break
+            end += 1;
+        }
+        let chunk = &text[start..end];
+
+        let justified_chunk = justify_text(chunk, max_chars_per_line);
+        let chunk_output = result.len();
+        result.push_str(&justified_chunk);
+
+        // Add separator between chunks if not at the end
+        // A chunk of nothing but spaces wraps to "" and separates nothing, and one ending right
+        // before a line break gets that break from the next chunk
+        let at_line_break = text[end..].starts_with('\n') || text[end..].starts_with("\r\n");
+        if end < text_len
+            && !at_line_break
+            && !justified_chunk.is_empty()
+            && !justified_chunk.ends_with("\r\n")
+        {
+            result.push_str("\r\n");
+        }
+        guard.add(chunk.len(), &result[chunk_output..])?;
+
+        start = end;
+    }
+
+    Ok(result)
+}
+
+/// Calculate character count with CJK width consideration
+/// Used for accurate text measurements; u64 so very large native inputs can't overflow
+pub fn calculate_text_width(text: &str) -> u64 {
+    width_of_str(text)
+}
+
+/// Display width of every '\n'-separated line, in order
+/// Line breaks (including the '\r' of a CRLF) contribute zero width; empty input has no lines
+pub fn line_widths(text: &str) -> Vec<u64> {
+    if is_empty_input(text) {
+        return Vec::new();
+    }
+    source_lines(text).map(width_of_str).collect()
+}
+
+/// Widest line of the text; the same measurement as line_widths, so 0 for empty input
+pub fn max_line_width(text: &str) -> u64 {
+    if is_empty_input(text) {
+        return 0;
+    }
+    source_lines(text).map(width_of_str).max().unwrap_or(0)
+}
+
+/// Lines split on '\n' with a trailing '\r' removed
+fn source_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// Counters gathered in a single pass over the text
+/// Shared by get_text_stats and the per-paragraph statistics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextCounts {
+    /// Characters, BOMs aside
+    pub char_count: usize,
+    pub byte_count: usize,
+    pub line_count: usize,
+    pub cjk_count: usize,
+    pub ascii_count: usize,
+    pub display_width: u32,
+    /// Characters above U+FFFF (two UTF-16 code units each)
+    pub astral_count: usize,
+    /// Combining marks (diacritics drawn onto the character before them)
+    pub mark_count: usize,
+    /// U+FEFF, at the start or inside the text
+    pub bom_count: usize,
+}
+
+impl TextCounts {
+    pub fn of(text: &str) -> Self {
+        let mut counts = TextCounts {
+            char_count: 0,
+            byte_count: text.len(),
+            line_count: 0,
+            cjk_count: 0,
+            ascii_count: 0,
+            display_width: 0,
+            astral_count: 0,
+            mark_count: 0,
+            bom_count: 0,
+        };
+
+        let mut rest = text;
+        while !rest.is_empty() {
+            // ASCII runs: every byte is one narrow, single-column, non-CJK character
+            let ascii_len = ascii_prefix_len(rest.as_bytes());
+            if ascii_len > 0 {
+                let (run, tail) = rest.split_at(ascii_len);
+                let newlines = run.bytes().filter(|&b| b == b'\n').count();
+                counts.char_count += ascii_len;
+                counts.line_count += newlines;
+                counts.ascii_count += ascii_len;
+                counts.display_width += ascii_len as u32;
+                rest = tail;
+                continue;
+            }
+
+            let mut chars = rest.chars();
+            let Some(c) = chars.next() else { break };
+            rest = chars.as_str();
+            let class = CharClass::of(c);
+            if c == BOM {
+                counts.bom_count += 1;
+            } else {
+                counts.char_count += 1;
+            }
+            // 々 and 〻 sit in CJK Symbols and Punctuation but stand for the ideograph before
+            if class.cjk || kinsoku::is_iteration_mark(c) {
+                counts.cjk_count += 1;
+            }
+            if class.is_narrow() {
+                counts.ascii_count += 1;
+            }
+            if c as u32 > 0xFFFF {
+                counts.astral_count += 1;
+            }
+            if is_combining_mark(c) {
+                counts.mark_count += 1;
+            }
+            counts.display_width += class.width;
+        }
+
+        // Same semantics as str::lines(): a final line without '\n' still counts, unless the
+        // text is empty input, which has no lines
+        if !is_empty_input(text) && !text.ends_with('\n') {
+            counts.line_count += 1;
+        }
+
+        counts
+    }
+}
+
+/// Text statistics as serialized by get_text_stats
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TextStats {
+    /// Characters, BOMs aside
+    pub char_count: usize,
+    pub byte_count: usize,
+    pub line_count: usize,
+    /// CJK characters, iteration marks (々) included
+    pub cjk_count: usize,
+    pub ascii_count: usize,
+    pub display_width: u32,
+    pub has_cjk: bool,
+    /// Words as split_words finds them
+    pub word_count: usize,
+    /// Right-to-left runs (Hebrew, Arabic), which are wrapped but not reordered
+    pub rtl_run_count: usize,
+    /// Characters above U+FFFF: emoji, Extension B+ ideographs, math alphanumerics, ...
+    pub astral_count: usize,
+    /// Widest word the word wrapper can't break, in columns (see maxUnbreakableRun)
+    pub longest_unbreakable_run: usize,
+    /// Most times one character (or emoji) repeats in a row, whitespace aside (see
+    /// maxCharRepeat)
+    pub longest_char_repeat: usize,
+    /// Combining marks, each drawn onto the character before it
+    pub combining_mark_count: usize,
+    /// More than ZALGO_MARKS_PER_BASE combining marks per other character on average: marks
+    /// piled up for effect ("Zalgo" text) rather than diacritics (see maxMarksPerCluster)
+    pub zalgo_suspect: bool,
+    /// U+FEFF byte order marks, a leading one included: concatenated files leave them inside
+    /// text too, where from algorithm V6 on they join the characters around them
+    pub bom_count: usize,
+}
+
+impl TextStats {
+    pub fn of(text: &str) -> Self {
+        let counts = TextCounts::of(text);
+        TextStats {
+            char_count: counts.char_count,
+            byte_count: counts.byte_count,
+            line_count: counts.line_count,
+            cjk_count: counts.cjk_count,
+            ascii_count: counts.ascii_count,
+            display_width: counts.display_width,
+            has_cjk: counts.cjk_count > 0,
+            word_count: count_words(text),
+            rtl_run_count: count_rtl_runs(text),
+            astral_count: counts.astral_count,
+            longest_unbreakable_run: longest_unbreakable_run(text),
+            longest_char_repeat: longest_char_repeat(text),
+            combining_mark_count: counts.mark_count,
+            zalgo_suspect: is_zalgo(counts.mark_count, counts.char_count),
+            bom_count: counts.bom_count,
+        }
+    }
+}
+
+/// Get text processing statistics
+/// Returns JSON string with analysis data
+/// Written by hand (all fields are numbers or booleans) so it doesn't need the json feature
+pub fn get_text_stats(text: &str) -> String {
+    let stats = TextStats::of(text);
+    mark(Stage::Classify);
+    let json = stats_json(&stats);
+    mark(Stage::Serialize);
+    json
+}
+
+/// get_text_stats' JSON for stats
+pub(crate) fn stats_json(stats: &TextStats) -> String {
+    format!(
+        r#"{{"charCount":{},"byteCount":{},"lineCount":{},"cjkCount":{},"asciiCount":{},"displayWidth":{},"hasCjk":{},"wordCount":{},"rtlRunCount":{},"astralCount":{},"longestUnbreakableRun":{},"longestCharRepeat":{},"combiningMarkCount":{},"zalgoSuspect":{},"bomCount":{}}}"#,
+        stats.char_count,
+        stats.byte_count,
+        stats.line_count,
+        stats.cjk_count,
+        stats.ascii_count,
+        stats.display_width,
+        stats.has_cjk,
+        stats.word_count,
+        stats.rtl_run_count,
+        stats.astral_count,
+        stats.longest_unbreakable_run,
+        stats.longest_char_repeat,
+        stats.combining_mark_count,
+        stats.zalgo_suspect,
+        stats.bom_count
+    )
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParagraphStats {
+    start_line: usize,
+    char_count: usize,
+    display_width: u32,
+    cjk_ratio: f64,
+    wrapped_line_count: u32,
+}
+
+#[cfg(feature = "json")]
+/// Per-paragraph statistics for document outline views
+/// Returns JSON array with one object per blank-line-separated paragraph
+pub fn paragraph_stats(text: &str, max_chars_per_line: u32) -> String {
+    let stats: Vec<ParagraphStats> = paragraphs(text)
+        .into_iter()
+        .map(|(start_line, paragraph)| {
+            let counts = TextCounts::of(paragraph);
+            ParagraphStats {
+                start_line,
+                char_count: counts.char_count,
+                display_width: counts.display_width,
+                cjk_ratio: if counts.char_count == 0 {
+                    0.0
+                } else {
+                    counts.cjk_count as f64 / counts.char_count as f64
+                },
+                wrapped_line_count: count_wrapped_lines(paragraph, max_chars_per_line),
+            }
+        })
+        .collect();
+
+    serde_json::to_string(&stats).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// The system allocator, counting allocations per thread so tests running alongside each
+    /// other don't add to one another's counts
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Allocations and reallocations made on this thread while run runs
+    fn allocations<T>(run: impl FnOnce() -> T) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        std::hint::black_box(run());
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    /// Strings of up to 40 pieces of ASCII, CJK, astral, combining and control characters,
+    /// the same ones for every run
+    pub(crate) fn random_texts(count: usize) -> Vec<String> {
+        const PIECES: &[&str] = &[
+            "a",
+            "Z",
+            " ",
+            "\t",
+            "\n",
+            "\r\n",
+            "\r",
+            "漢",
+            "。",
+            "「",
+            "😀",
+            "👩‍💻",
+            "\u{301}",
+            "\u{200B}",
+            "\u{FEFF}",
+            "\u{1F1EF}",
+            "א",
+            "-",
+            "https://",
+            "\u{0}",
+        ];
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let len = next() % 40;
+                (0..len)
+                    .map(|_| PIECES[(next() % PIECES.len() as u64) as usize])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn public_functions_take_any_text_and_width() {
+        let widths = [
+            0,
+            1,
+            2,
+            3,
+            7,
+            36,
+            MAX_MAX_CHARS_PER_LINE,
+            MAX_MAX_CHARS_PER_LINE + 1,
+            u32::MAX,
+        ];
+        for text in random_texts(300) {
+            get_text_stats(&text);
+            calculate_text_width(&text);
+            max_line_width(&text);
+            for &width in &widths {
+                count_wrapped_lines(&text, width);
+                let chunked = process_text_chunks(&text, width, 5);
+                if width > MAX_MAX_CHARS_PER_LINE {
+                    assert!(matches!(chunked, Err(WebtoolsError::WidthTooLarge { .. })));
+                    continue;
+                }
+                justify_text(&text, width);
+                justify_text_cjk(&text, width);
+                justify_text_english(&text, width);
+                chunked.unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_end_on_character_boundaries() {
+        let text = "漢字😀 text\r\nmore 👩‍💻 and é";
+        let visible = |text: &str| -> String { text.split_whitespace().collect() };
+        for chunk_size in 1..=12 {
+            let chunked = process_text_chunks(text, 8, chunk_size).unwrap();
+            // Every character comes out once and in order, wherever the chunks were cut
+            assert_eq!(
+                visible(&chunked),
+                visible(text),
+                "chunk_size {}",
+                chunk_size
+            );
+        }
+        // 0 picks a chunk size and ends chunks at line breaks, giving justify_text's output
+        assert_eq!(
+            process_text_chunks(text, 8, 0).unwrap(),
+            justify_text(text, 8)
+        );
+    }
+
+    #[test]
+    fn trailing_line_breaks_survive_chunk_seams() {
+        let trailing = |output: &str| output.len() - output.trim_end_matches("\r\n").len();
+        // A wrapped line, a CJK one and one with a blank line inside, ending with 0 to 3 LF or
+        // CRLF line breaks
+        for base in ["hello world again", "中文中文中文中文中文", "a\n\nb"] {
+            for (line_break, count) in ["\n", "\r\n"]
+                .into_iter()
+                .flat_map(|line_break| (0..=3).map(move |count| (line_break, count)))
+            {
+                let text = base.to_string() + &line_break.repeat(count);
+                let expected = justify_text(base, 6) + &"\r\n".repeat(count);
+                assert_eq!(justify_text(&text, 6), expected, "{:?}", text);
+                assert_eq!(wrapped_lines(&text, 6).join("\r\n"), expected, "{:?}", text);
+                for chunk_size in 0..=text.len() as u32 + 1 {
+                    let chunked = process_text_chunks(&text, 6, chunk_size).unwrap();
+                    assert_eq!(
+                        trailing(&chunked),
+                        trailing(&expected),
+                        "{:?} in chunks of {}: {:?}",
+                        text,
+                        chunk_size,
+                        chunked
+                    );
+                }
+                for window in 1..=text.len() {
+                    let mut output = String::new();
+                    justify_windows::<WebtoolsError>(
+                        text.len(),
+                        6,
+                        window,
+                        |range, buffer| buffer.extend_from_slice(&text.as_bytes()[range]),
+                        |block| {
+                            output.push_str(block);
+                            Ok(())
+                        },
+                    )
+                    .unwrap();
+                    assert_eq!(output, expected, "{:?} in {}-byte windows", text, window);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn widths_of_zero_and_one_lose_no_characters() {
+        let visible = |text: &str| -> String { text.split_whitespace().collect() };
+        for text in random_texts(300) {
+            for width in [0, 1] {
+                for wrapped in [
+                    justify_text(&text, width),
+                    justify_text_cjk(&text, width),
+                    process_text_chunks(&text, width, 3).unwrap(),
+                ] {
+                    assert_eq!(visible(&wrapped), visible(&text), "{:?}", text);
+                }
+            }
+        }
+        // Too narrow for even the space between words, which gets a line of its own
+        assert_eq!(justify_text("ab 漢字\nc", 1), "a\r\nb\r\n\r\n漢\r\n字\r\nc");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn batches_take_any_width_up_to_the_limit() {
+        assert_eq!(
+            batch_justify_text(r#"["ab 漢字", ""]"#, 0, false).unwrap(),
+            r#"["\r\na\r\nb\r\n\r\n漢\r\n字",""]"#
+        );
+        assert_eq!(
+            batch_justify_text("[]", MAX_MAX_CHARS_PER_LINE + 1, false),
+            Err(WebtoolsError::WidthTooLarge {
+                max: MAX_MAX_CHARS_PER_LINE,
+                actual: MAX_MAX_CHARS_PER_LINE + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn justify_text_allocates_per_call_not_per_line() {
+        let paragraph = "这是一段用于测试的中文文本，包含标点符号和English words混排。\n\
+            The quick brown fox jumps over the lazy dog, again and again.\n\n";
+        let counts: Vec<usize> = [10, 100, 1000]
+            .iter()
+            .map(|&repeats| {
+                let text = paragraph.repeat(repeats);
+                allocations(|| justify_text(&text, 20))
+            })
+            .collect();
+        // The output buffer and a few scratch buffers, however many of the 9,001 lines there are
+        assert!(counts[2] <= 4, "{:?}", counts);
+        assert_eq!(counts[0], counts[2], "{:?}", counts);
+    }
+
+    #[test]
+    fn stats_of_fixtures() {
+        assert_eq!(
+            get_text_stats("Hello, 世界!\n"),
+            r#"{"charCount":11,"byteCount":15,"lineCount":1,"cjkCount":2,"asciiCount":9,"displayWidth":13,"hasCjk":true,"wordCount":3,"rtlRunCount":0,"astralCount":0,"longestUnbreakableRun":0,"longestCharRepeat":2,"combiningMarkCount":0,"zalgoSuspect":false,"bomCount":0}"#
+        );
+        // The BOM is no character but is two columns wide, like every other character outside
+        // the narrow tables (the combining mark and the Hebrew letters too)
+        assert_eq!(
+            get_text_stats("\u{FEFF}々 e\u{301} 😀\r\nשלום"),
+            r#"{"charCount":12,"byteCount":25,"lineCount":2,"cjkCount":1,"asciiCount":5,"displayWidth":21,"hasCjk":true,"wordCount":3,"rtlRunCount":1,"astralCount":1,"longestUnbreakableRun":8,"longestCharRepeat":1,"combiningMarkCount":1,"zalgoSuspect":false,"bomCount":1}"#
+        );
+        // Everything above U+FFFF counts, narrow math letters and wide ideographs alike
+        assert_eq!(
+            get_text_stats("𝐀𝐁 😀\n𠀀"),
+            r#"{"charCount":6,"byteCount":18,"lineCount":2,"cjkCount":1,"asciiCount":2,"displayWidth":10,"hasCjk":true,"wordCount":2,"rtlRunCount":0,"astralCount":4,"longestUnbreakableRun":8,"longestCharRepeat":1,"combiningMarkCount":0,"zalgoSuspect":false,"bomCount":0}"#
+        );
+        assert_eq!(
+            get_text_stats("  \n\n"),
+            r#"{"charCount":4,"byteCount":4,"lineCount":2,"cjkCount":0,"asciiCount":4,"displayWidth":4,"hasCjk":false,"wordCount":0,"rtlRunCount":0,"astralCount":0,"longestUnbreakableRun":0,"longestCharRepeat":0,"combiningMarkCount":0,"zalgoSuspect":false,"bomCount":0}"#
+        );
+    }
+
+    #[test]
+    fn iteration_marks_count_as_cjk() {
+        assert_eq!(TextStats::of("人々と日々、堂々").cjk_count, 7);
+    }
+
+    #[test]
+    fn one_pass_counts_match_counting_each_on_its_own() {
+        for text in random_texts(300) {
+            let chars = || text.chars().filter(|&c| c != '\u{FEFF}');
+            let expected = TextCounts {
+                char_count: chars().count(),
+                byte_count: text.len(),
+                line_count: if text.trim().is_empty() && !text.contains('\n') {
+                    0
+                } else {
+                    text.lines().count()
+                },
+                cjk_count: text
+                    .chars()
+                    .filter(|&c| is_cjk_char(c) || c == '々' || c == '〻')
+                    .count(),
+                ascii_count: text.chars().filter(|&c| get_char_width(c) == 1).count(),
+                display_width: text.chars().map(get_char_width).sum(),
+                astral_count: text.chars().filter(|&c| c as u32 > 0xFFFF).count(),
+                mark_count: text.chars().filter(|&c| is_combining_mark(c)).count(),
+                bom_count: text.matches('\u{FEFF}').count(),
+            };
+            assert_eq!(TextCounts::of(&text), expected, "{:?}", text);
+            assert_eq!(calculate_text_width(&text), expected.display_width as u64);
+        }
+    }
+
+    #[test]
+    fn line_widths_leave_line_breaks_out() {
+        assert_eq!(line_widths(""), Vec::<u64>::new());
+        assert_eq!(line_widths("\n"), [0, 0]);
+        // 世界 is 4 columns, and neither "\r\n" nor "\n" adds any
+        assert_eq!(line_widths("ab\r\n世界\n\nx"), [2, 4, 0, 1]);
+        assert_eq!(max_line_width("ab\r\n世界\n\nx"), 4);
+        assert_eq!(max_line_width(""), 0);
+    }
+
+    #[test]
+    fn max_line_width_is_the_widest_of_line_widths() {
+        for text in random_texts(300) {
+            let widths = line_widths(&text);
+            assert_eq!(
+                max_line_width(&text),
+                widths.iter().copied().max().unwrap_or(0),
+                "{:?}",
+                text
+            );
+            // Only the line breaks are left out of the total: each '\n' and the '\r' that
+            // ends a line
+            let breaks = text.matches('\n').count()
+                + text.split('\n').filter(|line| line.ends_with('\r')).count();
+            if !widths.is_empty() {
+                assert_eq!(
+                    widths.iter().sum::<u64>() + breaks as u64,
+                    calculate_text_width(&text),
+                    "{:?}",
+                    text
+                );
+            }
+        }
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "scans 2 GB; CI runs it with --release")]
+    fn a_line_half_of_u32_max_wide() {
+        let half = u64::from(u32::MAX / 2);
+        let text = "a".repeat(half as usize);
+        assert_eq!(max_line_width(&text), half);
+        assert_eq!(line_widths(&text), [half]);
+        assert_eq!(calculate_text_width(&text), half);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn hand_written_stats_json_is_serdes() {
+        for text in random_texts(200) {
+            let stats = TextStats::of(&text);
+            assert_eq!(stats_json(&stats), serde_json::to_string(&stats).unwrap());
+            let parsed: TextStats = serde_json::from_str(&get_text_stats(&text)).unwrap();
+            assert_eq!(parsed, stats);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn paragraph_stats_of_three_scripts() {
+        let text = "Plain English words\n\n漢字かな交じり\n\n한국어 문장\n\n";
+        let stats: serde_json::Value = serde_json::from_str(&paragraph_stats(text, 10)).unwrap();
+        let expected = serde_json::json!([
+            {"startLine": 0, "charCount": 19, "displayWidth": 19, "cjkRatio": 0.0, "wrappedLineCount": 3},
+            {"startLine": 2, "charCount": 7, "displayWidth": 14, "cjkRatio": 1.0, "wrappedLineCount": 2},
+            {"startLine": 4, "charCount": 6, "displayWidth": 11, "cjkRatio": 0.0, "wrappedLineCount": 2},
+        ]);
+        // The blank lines at the end are no paragraph of their own
+        assert_eq!(stats, expected);
+        assert_eq!(paragraph_stats("", 10), "[]");
+        assert_eq!(paragraph_stats("\n\n\n", 10), "[]");
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use crate::WebtoolsError;
+
+/// Default per-input cap in UTF-8 bytes
+pub const DEFAULT_MAX_INPUT_SIZE: usize = 500_000;
+
+/// Default cap on the combined size of a batch, in UTF-8 bytes
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 5_000_000;
+
+/// Default cap on the number of items in a batch
+pub const DEFAULT_MAX_BATCH_ITEMS: usize = 100_000;
+
+/// Default cap on output bytes per input byte (see expansion.rs)
+pub const DEFAULT_MAX_OUTPUT_RATIO: u32 = 10;
+
+/// Output up to this many bytes is never too large, whatever the input's size
+/// A footer or a line-number gutter on a few words is many times their size and harmless.
+pub const MIN_OUTPUT_CEILING: usize = 64 * 1024;
+
+static MAX_INPUT_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_INPUT_SIZE);
+static MAX_BATCH_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BATCH_SIZE);
+static MAX_BATCH_ITEMS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BATCH_ITEMS);
+static MAX_OUTPUT_RATIO: AtomicU32 = AtomicU32::new(DEFAULT_MAX_OUTPUT_RATIO);
+
+/// Held by tests that change the limits or count on them being the defaults, which are shared
+/// by every test thread
+#[cfg(test)]
+pub(crate) fn lock_limits() -> std::sync::MutexGuard<'static, ()> {
+    static LIMITS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LIMITS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Change the per-input cap; takes effect on the next call
+pub fn set_max_input_size(bytes: usize) {
+    MAX_INPUT_SIZE.store(bytes, Ordering::Relaxed);
+}
+
+/// Current per-input cap in bytes
+pub fn max_input_size() -> usize {
+    MAX_INPUT_SIZE.load(Ordering::Relaxed)
+}
+
+/// Change the combined batch cap; takes effect on the next call
+pub fn set_max_batch_size(bytes: usize) {
+    MAX_BATCH_SIZE.store(bytes, Ordering::Relaxed);
+}
+
+/// Current combined batch cap in bytes
+pub fn max_batch_size() -> usize {
+    MAX_BATCH_SIZE.load(Ordering::Relaxed)
+}
+
+/// Change the cap on a batch's item count; takes effect on the next call
+pub fn set_max_batch_items(items: usize) {
+    MAX_BATCH_ITEMS.store(items, Ordering::Relaxed);
+}
+
+/// Current cap on a batch's item count
+pub fn max_batch_items() -> usize {
+    MAX_BATCH_ITEMS.load(Ordering::Relaxed)
+}
+
+/// Change the cap on output bytes per input byte, 0 for none; takes effect on the next call
+pub fn set_max_output_ratio(ratio: u32) {
+    MAX_OUTPUT_RATIO.store(ratio, Ordering::Relaxed);
+}
+
+/// Current cap on output bytes per input byte, 0 for none
+pub fn max_output_ratio() -> u32 {
+    MAX_OUTPUT_RATIO.load(Ordering::Relaxed)
+}
+
+/// Reject text longer than max_input_size()
+pub fn check_input_size(text: &str) -> Result<(), WebtoolsError> {
+    let limit = max_input_size();
+    if text.len() > limit {
+        return Err(WebtoolsError::InputTooLarge {
+            limit,
+            actual: text.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Reject a batch with an item over max_input_size(), more than max_batch_items() items or a
+/// total over max_batch_size(), checked in that order
+pub fn check_batch_size<S: AsRef<str>>(texts: &[S]) -> Result<(), WebtoolsError> {
+    let limits = BatchLimits::current();
+    let mut total = 0usize;
+    for (index, text) in texts.iter().enumerate() {
+        let len = text.as_ref().len();
+        limits.check_item(index, len)?;
+        total = total.saturating_add(len);
+    }
+    limits.check_totals(texts.len(), total)
+}
+
+/// Reject a batch of more than max_batch_items() items before reading any of them
+pub fn check_batch_item_count(count: usize) -> Result<(), WebtoolsError> {
+    BatchLimits::current().check_totals(count, 0)
+}
+
+/// The batch limits as set when a call starts, so one call checks against one set of them
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BatchLimits {
+    pub(crate) item_bytes: usize,
+    pub(crate) items: usize,
+    pub(crate) total_bytes: usize,
+}
+
+impl BatchLimits {
+    pub(crate) fn current() -> Self {
+        BatchLimits {
+            item_bytes: max_input_size(),
+            items: max_batch_items(),
+            total_bytes: max_batch_size(),
+        }
+    }
+
+    /// BatchItemTooLarge if item index is len bytes, over the per-item limit
+    pub(crate) fn check_item(&self, index: usize, len: usize) -> Result<(), WebtoolsError> {
+        if len > self.item_bytes {
+            return Err(WebtoolsError::BatchItemTooLarge {
+                index,
+                limit: self.item_bytes,
+                actual: len,
+                id: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// TooManyBatchItems or BatchTooLarge for a batch of count items adding up to total bytes
+    pub(crate) fn check_totals(&self, count: usize, total: usize) -> Result<(), WebtoolsError> {
+        if count > self.items {
+            return Err(WebtoolsError::TooManyBatchItems {
+                limit: self.items,
+                actual: count,
+            });
+        }
+        if total > self.total_bytes {
+            return Err(WebtoolsError::BatchTooLarge {
+                limit: self.total_bytes,
+                actual: total,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_are_checked_per_item_and_in_total() {
+        let _limits = lock_limits();
+        let item = "a".repeat(DEFAULT_MAX_INPUT_SIZE);
+        // Eleven items at the per-item limit add up to over the 5,000,000 bytes of a batch
+        let items = vec![item.as_str(); 11];
+        assert_eq!(check_batch_size(&items[..10]), Ok(()));
+        assert_eq!(
+            check_batch_size(&items),
+            Err(WebtoolsError::BatchTooLarge {
+                limit: DEFAULT_MAX_BATCH_SIZE,
+                actual: 11 * DEFAULT_MAX_INPUT_SIZE,
+            })
+        );
+        let large = "a".repeat(DEFAULT_MAX_INPUT_SIZE + 1);
+        assert_eq!(
+            check_batch_size(&["small", &large]),
+            Err(WebtoolsError::BatchItemTooLarge {
+                index: 1,
+                limit: DEFAULT_MAX_INPUT_SIZE,
+                actual: DEFAULT_MAX_INPUT_SIZE + 1,
+                id: None,
+            })
+        );
+        assert_eq!(
+            check_batch_item_count(DEFAULT_MAX_BATCH_ITEMS + 1).map_err(|e| e.code()),
+            Err("TOO_MANY_BATCH_ITEMS")
+        );
+    }
+
+    #[test]
+    fn new_limits_apply_to_the_next_call() {
+        let _limits = lock_limits();
+        // Raised rather than lowered, so tests not holding the lock still pass meanwhile
+        let text = "a".repeat(DEFAULT_MAX_INPUT_SIZE + 5);
+        assert_eq!(
+            check_input_size(&text).map_err(|e| e.code()),
+            Err("INPUT_TOO_LARGE")
+        );
+        set_max_input_size(DEFAULT_MAX_INPUT_SIZE + 10);
+        set_max_batch_items(DEFAULT_MAX_BATCH_ITEMS + 1);
+        assert_eq!(max_input_size(), DEFAULT_MAX_INPUT_SIZE + 10);
+        assert_eq!(check_input_size(&text), Ok(()));
+        assert_eq!(check_batch_size(&[text.as_str()]), Ok(()));
+        assert_eq!(check_batch_item_count(DEFAULT_MAX_BATCH_ITEMS + 1), Ok(()));
+        set_max_input_size(DEFAULT_MAX_INPUT_SIZE);
+        set_max_batch_items(DEFAULT_MAX_BATCH_ITEMS);
+        assert!(check_input_size(&text).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Line heights by script, so Latin lines can sit tighter than CJK and emoji lines
+//! Every line box is font_size_px × line_height_factor × its heightFactor: 1 for a line
+//! without CJK or emoji, cjk_line_height_factor for a line with CJK, emoji_line_height_factor
+//! for a line with emoji, and the larger of the two for a line with both. Layout y positions
+//! and image heights both add up these boxes, by counting lines of each kind, so they agree.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::emoji::has_emoji;
+use crate::table::is_cjk;
+use crate::WebtoolsError;
+
+/// heightFactor of a line with CJK, until set_cjk_line_height_factor changes it
+pub const DEFAULT_CJK_LINE_HEIGHT_FACTOR: f64 = 1.15;
+
+/// heightFactor of a line with emoji, until set_emoji_line_height_factor changes it
+pub const DEFAULT_EMOJI_LINE_HEIGHT_FACTOR: f64 = 1.25;
+
+static CJK_LINE_HEIGHT_FACTOR: AtomicU64 = AtomicU64::new(DEFAULT_CJK_LINE_HEIGHT_FACTOR.to_bits());
+static EMOJI_LINE_HEIGHT_FACTOR: AtomicU64 =
+    AtomicU64::new(DEFAULT_EMOJI_LINE_HEIGHT_FACTOR.to_bits());
+
+/// Change the heightFactor of lines with CJK; takes effect on the next call
+pub fn set_cjk_line_height_factor(factor: f64) {
+    CJK_LINE_HEIGHT_FACTOR.store(factor.to_bits(), Ordering::Relaxed);
+}
+
+/// Current heightFactor of lines with CJK
+pub fn cjk_line_height_factor() -> f64 {
+    f64::from_bits(CJK_LINE_HEIGHT_FACTOR.load(Ordering::Relaxed))
+}
+
+/// Change the heightFactor of lines with emoji; takes effect on the next call
+pub fn set_emoji_line_height_factor(factor: f64) {
+    EMOJI_LINE_HEIGHT_FACTOR.store(factor.to_bits(), Ordering::Relaxed);
+}
+
+/// Current heightFactor of lines with emoji
+pub fn emoji_line_height_factor() -> f64 {
+    f64::from_bits(EMOJI_LINE_HEIGHT_FACTOR.load(Ordering::Relaxed))
+}
+
+/// What a line holds, as far as its height goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineScript {
+    /// Neither CJK nor emoji
+    Plain,
+    Cjk,
+    Emoji,
+    CjkAndEmoji,
+}
+
+impl LineScript {
+    const COUNT: usize = 4;
+
+    pub fn of(line: &str) -> Self {
+        if line.is_ascii() {
+            return LineScript::Plain;
+        }
+        match (line.chars().any(is_cjk), has_emoji(line)) {
+            (false, false) => LineScript::Plain,
+            (true, false) => LineScript::Cjk,
+            (false, true) => LineScript::Emoji,
+            (true, true) => LineScript::CjkAndEmoji,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The heightFactor of each kind of line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScriptLineHeights {
+    pub cjk: f64,
+    pub emoji: f64,
+}
+
+impl ScriptLineHeights {
+    /// Every line 1 high, for fixed grids such as the bitmap renderer's
+    pub const UNIFORM: ScriptLineHeights = ScriptLineHeights {
+        cjk: 1.0,
+        emoji: 1.0,
+    };
+
+    /// Factors from an options object or call: each must be a positive number
+    pub fn checked(cjk: f64, emoji: f64) -> Result<Self, WebtoolsError> {
+        for (name, factor) in [
+            ("cjkLineHeightFactor", cjk),
+            ("emojiLineHeightFactor", emoji),
+        ] {
+            if !(factor.is_finite() && factor > 0.0) {
+                return Err(WebtoolsError::invalid_argument(format!(
+                    "{} must be a positive number, got {}",
+                    name, factor
+                )));
+            }
+        }
+        Ok(ScriptLineHeights { cjk, emoji })
+    }
+
+    /// The factors as currently set
+    pub fn current() -> Self {
+        ScriptLineHeights {
+            cjk: cjk_line_height_factor(),
+            emoji: emoji_line_height_factor(),
+        }
+    }
+
+    pub fn factor(self, script: LineScript) -> f64 {
+        match script {
+            LineScript::Plain => 1.0,
+            LineScript::Cjk => self.cjk,
+            LineScript::Emoji => self.emoji,
+            LineScript::CjkAndEmoji => self.cjk.max(self.emoji),
+        }
+    }
+
+    /// heightFactor of one line
+    pub fn of_line(self, line: &str) -> f64 {
+        self.factor(LineScript::of(line))
+    }
+
+    /// A heightFactor no line of text goes over: what text would get as one line, and at
+    /// least a plain line's
+    pub fn tallest_in(self, text: &str) -> f64 {
+        self.of_line(text).max(1.0)
+    }
+}
+
+/// Running total of line heights, in units of font_size_px × line_height_factor
+/// Counting lines of each kind rather than summing factors keeps the total exact on long
+/// documents, so the y of a line never drifts from the height of the lines above it.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LineHeightSum {
+    counts: [u64; LineScript::COUNT],
+}
+
+impl LineHeightSum {
+    pub(crate) fn add(&mut self, script: LineScript) {
+        self.counts[script.index()] += 1;
+    }
+
+    pub(crate) fn total(&self, heights: ScriptLineHeights) -> f64 {
+        [
+            LineScript::Plain,
+            LineScript::Cjk,
+            LineScript::Emoji,
+            LineScript::CjkAndEmoji,
+        ]
+        .into_iter()
+        .map(|script| self.counts[script.index()] as f64 * heights.factor(script))
+        .sum()
+    }
+
+    /// Total height of lines
+    pub(crate) fn of_lines<S: AsRef<str>>(lines: &[S], heights: ScriptLineHeights) -> f64 {
+        let mut sum = LineHeightSum::default();
+        for line in lines {
+            sum.add(LineScript::of(line.as_ref()));
+        }
+        sum.total(heights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn cjk_lines_are_taller_in_layout_and_image_height() {
+        use crate::{estimate_image_size, layout_text};
+
+        let _limits = crate::limits::lock_limits();
+        let (font_size_px, line_height_factor, padding_px) = (16.0, 1.5, 8.0);
+        let line_height_px = font_size_px * line_height_factor;
+        let text = "Hello world\n你好世界\nHello again\n再见";
+        let layout: serde_json::Value = serde_json::from_str(&layout_text(
+            text,
+            20,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            "left",
+        ))
+        .unwrap();
+        let size: serde_json::Value = serde_json::from_str(&estimate_image_size(
+            text,
+            20,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+        ))
+        .unwrap();
+
+        let cjk = cjk_line_height_factor();
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-9 * a.abs().max(1.0);
+        let expected_y =
+            [0.0, 1.0, 1.0 + cjk, 2.0 + cjk].map(|units| padding_px + units * line_height_px);
+        let lines = layout.as_array().unwrap();
+        assert_eq!(lines.len(), 4);
+        for (index, (line, factor)) in lines.iter().zip([1.0, cjk, 1.0, cjk]).enumerate() {
+            let y_px = line["yPx"].as_f64().unwrap();
+            assert!(
+                close(y_px, expected_y[index]),
+                "yPx of line {index}: {y_px}"
+            );
+            assert_eq!(line["heightFactor"], factor, "line {index}");
+        }
+        let height_px = size["heightPx"].as_f64().unwrap();
+        let expected = (2.0 + 2.0 * cjk) * line_height_px + 2.0 * padding_px;
+        assert!(close(height_px, expected), "{height_px}");
+    }
+
+    #[test]
+    fn a_line_with_both_takes_the_taller_factor() {
+        let heights = ScriptLineHeights {
+            cjk: 1.15,
+            emoji: 1.25,
+        };
+        assert_eq!(LineScript::of("plain text"), LineScript::Plain);
+        assert_eq!(LineScript::of("中文 😀"), LineScript::CjkAndEmoji);
+        assert_eq!(heights.factor(LineScript::CjkAndEmoji), 1.25);
+        assert_eq!(LineHeightSum::of_lines(&["a", "中", "😀"], heights), 3.4);
+    }
+
+    #[test]
+    fn factors_must_be_positive_numbers() {
+        assert!(ScriptLineHeights::checked(1.5, 1.75).is_ok());
+        for (cjk, emoji) in [
+            (0.0, 1.0),
+            (-1.0, 1.0),
+            (1.0, f64::NAN),
+            (1.0, f64::INFINITY),
+        ] {
+            assert_eq!(
+                ScriptLineHeights::checked(cjk, emoji).unwrap_err().code(),
+                "INVALID_ARGUMENT",
+                "{cjk} and {emoji}"
+            );
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! The wrapper as an iterator over line records
+//! WrapIter produces justify_text's lines one at a time without building the whole output,
+//! each as a range of the source plus what a caller needs to lay it out. One source line is
+//! wrapped at a time, so memory stays proportional to the longest line.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::metrics::{wrap_text_into, ColumnWidths, WrapSink};
+use crate::{is_cjk, is_empty_input, width_of_str, TrailingWhitespace};
+
+/// One line of justify_text output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrappedLine {
+    /// Byte range of the line in the source text; whitespace trimmed at the break is outside it
+    pub text_range: Range<usize>,
+    /// Display width in half-width columns
+    pub width: u32,
+    /// The line ends at a line break in the source (or at the end of the text) rather than
+    /// where the wrapper broke it
+    pub hard_break: bool,
+    /// Placed by the word wrapper, which joins words with one space whatever separated them
+    pub collapse_whitespace: bool,
+}
+
+impl WrappedLine {
+    /// The line as justify_text writes it, given the text the iterator wrapped
+    pub fn text<'s>(&self, source: &'s str) -> Cow<'s, str> {
+        let slice = &source[self.text_range.clone()];
+        let collapsed = self.collapse_whitespace
+            && slice
+                .split(' ')
+                .any(|gap| gap.is_empty() || gap.contains(char::is_whitespace));
+        if collapsed {
+            Cow::Owned(slice.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            Cow::Borrowed(slice)
+        }
+    }
+}
+
+/// Output lines of one source line, split at the wrapper's line breaks
+#[derive(Default)]
+struct LineCollector {
+    text: String,
+    ends: Vec<usize>,
+}
+
+impl WrapSink for LineCollector {
+    fn push_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn line_break(&mut self) {
+        self.ends.push(self.text.len());
+    }
+}
+
+fn skip_whitespace(source: &str, position: usize) -> usize {
+    source[position..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(source.len(), |offset| position + offset)
+}
+
+/// Range of `line` in source, starting the search at `from`
+/// Of the whitespace at `from`, the line keeps as much as it starts with; the rest was trimmed
+/// at the previous break or taken by a '\r' the wrapper broke at. One space in the line matches
+/// a whole whitespace run before the next word, as the word wrapper collapses them.
+fn source_range(source: &str, from: usize, line: &str) -> Range<usize> {
+    if line.is_empty() {
+        // A blank source line, or one between two '\r' breaks: place it after the first of them
+        let whitespace = &source[from..skip_whitespace(source, from)];
+        let position = whitespace
+            .find('\r')
+            .map_or(from, |offset| from + offset + 1);
+        return position..position;
+    }
+
+    let leading = line.chars().take_while(|c| c.is_whitespace()).count();
+    let mut position = from;
+    let mut skipped = source[from..]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .count()
+        .saturating_sub(leading);
+    while skipped > 0 {
+        position += source[position..].chars().next().map_or(0, char::len_utf8);
+        skipped -= 1;
+    }
+    let start = position;
+
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let Some(source_char) = source[position..].chars().next() else {
+            break;
+        };
+        if c.is_whitespace() != source_char.is_whitespace()
+            || !c.is_whitespace() && c != source_char
+        {
+            break;
+        }
+        position += source_char.len_utf8();
+        if c.is_whitespace() && chars.peek().is_some_and(|next| !next.is_whitespace()) {
+            position = skip_whitespace(source, position);
+        }
+    }
+
+    start..position
+}
+
+/// Iterator over the lines justify_text produces, as WrappedLine records
+/// Joining `line.text(source)` of every record with "\r\n" gives exactly justify_text's
+/// output; text that justify_text turns into "" yields no lines. Generic over the text's
+/// owner so a caller can keep the iterator and the text together.
+pub struct WrapIter<T: AsRef<str>> {
+    text: T,
+    max_chars_per_line: u32,
+    /// Start of the next source line to wrap, None once every line has been wrapped
+    next_source_line: Option<usize>,
+    pending: VecDeque<WrappedLine>,
+    /// Lines still to yield when the iterator was built with_line_limit
+    remaining: Option<usize>,
+}
+
+impl<T: AsRef<str>> WrapIter<T> {
+    pub fn new(text: T, max_chars_per_line: u32) -> Self {
+        let source = text.as_ref();
+        let empty = is_empty_input(source);
+        WrapIter {
+            next_source_line: (!empty).then_some(0),
+            text,
+            max_chars_per_line,
+            pending: VecDeque::new(),
+            remaining: None,
+        }
+    }
+
+    /// An iterator that stops after max_lines lines and wraps no more of the text than they
+    /// need, so its cost follows max_lines rather than the text: a source line much longer than
+    /// the lines still wanted is wrapped a growing prefix at a time
+    pub fn with_line_limit(text: T, max_chars_per_line: u32, max_lines: usize) -> Self {
+        WrapIter {
+            remaining: Some(max_lines),
+            ..WrapIter::new(text, max_chars_per_line)
+        }
+    }
+
+    /// The text being wrapped, which the records' ranges point into
+    pub fn source(&self) -> &str {
+        self.text.as_ref()
+    }
+
+    /// Wrap the next source line into pending; false when there is none left
+    fn wrap_next_source_line(&mut self) -> bool {
+        let Some(start) = self.next_source_line else {
+            return false;
+        };
+        let source = self.text.as_ref();
+        let mut end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        self.next_source_line = (end < source.len()).then_some(end + 1);
+
+        let line = &source[start..end];
+        let (mut collector, prefix_len) = match self.remaining {
+            Some(remaining) => self.wrap_prefix(line, remaining),
+            None => (self.wrap(line), None),
+        };
+        let cut = prefix_len.is_some();
+        if let Some(prefix_len) = prefix_len {
+            // Only the lines before the prefix's last are exact, and nothing past them is wanted
+            collector.ends.pop();
+            self.next_source_line = None;
+            end = start + prefix_len;
+        }
+
+        // A prefix is only cut off a line it shares word or character wrapping with
+        let collapse_whitespace = !is_cjk(source[start..end].trim());
+        let first = self.pending.len();
+        let mut from = start;
+        let mut line_start = 0;
+        for &line_end in &collector.ends {
+            let text = &collector.text[line_start..line_end];
+            let text_range = source_range(&source[..end], from, text);
+            from = text_range.end;
+            line_start = line_end;
+            self.pending.push_back(WrappedLine {
+                text_range,
+                width: width_of_str(text).min(u32::MAX as u64) as u32,
+                hard_break: false,
+                collapse_whitespace,
+            });
+        }
+
+        // The character wrapper also breaks at a '\r', which counts as a line break in the source
+        for index in first..self.pending.len() {
+            let gap_end = self
+                .pending
+                .get(index + 1)
+                .map_or(end, |next| next.text_range.start);
+            let gap = &source[self.pending[index].text_range.end..gap_end];
+            let last = index + 1 == self.pending.len();
+            self.pending[index].hard_break = last && !cut || gap.contains('\r');
+        }
+        true
+    }
+
+    fn wrap(&self, line: &str) -> LineCollector {
+        let mut collector = LineCollector::default();
+        wrap_text_into(
+            line,
+            self.max_chars_per_line as f64 / 2.0,
+            &ColumnWidths,
+            &TrailingWhitespace::Trim.into(),
+            &mut collector,
+        );
+        collector.ends.push(collector.text.len());
+        collector
+    }
+
+    /// Wrap enough of line for `remaining` exact lines, and the prefix's length if that stopped
+    /// short of its end
+    /// A cut changes at most the last two lines of the prefix (the last word or number may
+    /// have fitted on the line before had it been whole), so a prefix is enough once it gives
+    /// two lines more than wanted, and one of them is dropped. The wrapper picks word or
+    /// character wrapping by whether the line holds CJK anywhere, so a prefix without CJK is
+    /// only used when the whole line has none.
+    fn wrap_prefix(&self, line: &str, remaining: usize) -> (LineCollector, Option<usize>) {
+        let wanted = remaining + 2;
+        let mut prefix_len = wanted
+            .saturating_mul(self.max_chars_per_line as usize + 1)
+            .saturating_mul(2);
+        let mut line_is_cjk = None;
+        while prefix_len < line.len() {
+            let mut cut_at = prefix_len;
+            while !line.is_char_boundary(cut_at) {
+                cut_at -= 1;
+            }
+            let prefix = &line[..cut_at];
+            let same_mode =
+                is_cjk(prefix) || !*line_is_cjk.get_or_insert_with(|| is_cjk(&line[cut_at..]));
+            if !same_mode {
+                break;
+            }
+            let collector = self.wrap(prefix);
+            if collector.ends.len() >= wanted {
+                return (collector, Some(cut_at));
+            }
+            prefix_len = prefix_len.saturating_mul(2);
+        }
+        (self.wrap(line), None)
+    }
+}
+
+impl<T: AsRef<str>> Iterator for WrapIter<T> {
+    type Item = WrappedLine;
+
+    fn next(&mut self) -> Option<WrappedLine> {
+        loop {
+            if self.remaining == Some(0) {
+                return None;
+            }
+            if let Some(line) = self.pending.pop_front() {
+                if let Some(remaining) = &mut self.remaining {
+                    *remaining -= 1;
+                }
+                return Some(line);
+            }
+            if !self.wrap_next_source_line() {
+                return None;
+            }
+        }
+    }
+}
+
+/// Lines of justify_text(text, max_chars_per_line), borrowing from text where they can
+pub fn wrapped_lines(text: &str, max_chars_per_line: u32) -> Vec<Cow<'_, str>> {
+    WrapIter::new(text, max_chars_per_line)
+        .map(|line| line.text(text))
+        .collect()
+}
+
+/// Char indices of text after which justify_text breaks a line, ascending
+/// Only the wrapper's own breaks count, not the text's line breaks. Whitespace dropped at a
+/// break is after the index: it is the last character the line keeps. A line the wrapper
+/// leaves empty, as it can when the width is narrower than a wide character, has none. Goes
+/// through WrapIter, so the wrapped text is never built whole.
+pub fn break_positions(text: &str, max_chars_per_line: u32) -> Vec<u32> {
+    let mut positions = Vec::new();
+    // Char index of byte offset `counted`, counting forward as the lines go by
+    let mut counted = 0;
+    let mut chars = 0;
+    for line in WrapIter::new(text, max_chars_per_line) {
+        if line.hard_break || line.text_range.is_empty() {
+            continue;
+        }
+        chars += text[counted..line.text_range.end].chars().count();
+        counted = line.text_range.end;
+        positions.push(u32::try_from(chars - 1).unwrap_or(u32::MAX));
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::random_texts;
+    use crate::{count_wrapped_lines, justify_text};
+
+    fn records(text: &str, max_chars_per_line: u32) -> Vec<(Range<usize>, u32, bool)> {
+        WrapIter::new(text, max_chars_per_line)
+            .map(|line| (line.text_range, line.width, line.hard_break))
+            .collect()
+    }
+
+    #[test]
+    fn lines_join_to_justify_text() {
+        for text in random_texts(300) {
+            for max_chars_per_line in [1, 4, 7, 20, 80] {
+                let lines = wrapped_lines(&text, max_chars_per_line);
+                let justified = justify_text(&text, max_chars_per_line);
+                assert_eq!(
+                    lines.join("\r\n"),
+                    justified,
+                    "{:?} at {}",
+                    text,
+                    max_chars_per_line
+                );
+                assert_eq!(
+                    lines.len() as u32,
+                    count_wrapped_lines(&text, max_chars_per_line),
+                    "{:?} at {}",
+                    text,
+                    max_chars_per_line
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn records_point_into_the_source() {
+        let text = "hello world\n中文文本测试";
+        assert_eq!(
+            records(text, 6),
+            [
+                (0..5, 5, false),
+                (6..11, 5, true),
+                (12..21, 6, false),
+                (21..30, 6, true)
+            ]
+        );
+        // Whitespace trimmed at a break and around the text is outside every range
+        assert_eq!(
+            records("  lead trail  \nx", 8),
+            [(2..6, 4, false), (7..12, 5, true), (15..16, 1, true)]
+        );
+    }
+
+    #[test]
+    fn collapsed_whitespace_and_blank_lines() {
+        let text = "a  b   c\r\n\r\nd";
+        let lines: Vec<WrappedLine> = WrapIter::new(text, 3).collect();
+        // The range keeps both spaces; text() writes the one the word wrapper joins with
+        assert_eq!(lines[0].text_range, 0..4);
+        assert!(lines[0].collapse_whitespace);
+        assert_eq!(lines[0].text(text), "a b");
+        // The blank line sits after the first '\r' of the break it stands between
+        assert_eq!((lines[2].text_range.clone(), lines[2].width), (11..11, 0));
+        assert_eq!(lines.len(), 4);
+        assert_eq!(WrapIter::new("", 10).count(), 0);
+    }
+
+    #[test]
+    fn a_line_limit_stops_early() {
+        let text = "one two three four five six";
+        let limited: Vec<WrappedLine> = WrapIter::with_line_limit(text, 5, 2).collect();
+        let all: Vec<WrappedLine> = WrapIter::new(text, 5).collect();
+        assert_eq!(limited, all[..2]);
+        assert_eq!(WrapIter::with_line_limit(text, 5, 0).count(), 0);
+        assert_eq!(WrapIter::with_line_limit(text, 5, 100).count(), all.len());
+        // A very long source line is wrapped only as far as the lines wanted
+        let long = "word ".repeat(200_000);
+        let first: Vec<WrappedLine> = WrapIter::with_line_limit(long.as_str(), 9, 3).collect();
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|line| line.width == 9 && !line.hard_break));
+    }
+
+    #[test]
+    fn break_positions_are_char_indices() {
+        assert_eq!(break_positions("hello world\n中文文本测试", 6), [4, 14]);
+        assert_eq!(break_positions("a  b   c\r\n\r\nd", 3), [3]);
+        assert!(break_positions("short", 10).is_empty());
+    }
+
+    #[test]
+    fn segments_cut_at_break_positions_rebuild_the_text() {
+        for text in random_texts(300) {
+            let chars: Vec<char> = text.chars().collect();
+            for max_chars_per_line in [1, 4, 7, 20, 80] {
+                let positions = break_positions(&text, max_chars_per_line);
+                let mut rebuilt = String::new();
+                let mut start = 0;
+                for &position in &positions {
+                    let end = position as usize + 1;
+                    assert!(
+                        start < end && end <= chars.len(),
+                        "{:?} at {}",
+                        text,
+                        max_chars_per_line
+                    );
+                    rebuilt.extend(&chars[start..end]);
+                    start = end;
+                }
+                rebuilt.extend(&chars[start..]);
+                assert_eq!(rebuilt, text, "{:?} at {}", text, max_chars_per_line);
+                assert!(
+                    positions.len()
+                        < count_wrapped_lines(&text, max_chars_per_line).max(1) as usize
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,515 @@
+//! Where justify_text_cjk breaks URLs and file paths
+//! Chinese text runs straight into long URLs and Windows paths with no spaces around them, so
+//! the character wrapper would cut them at whatever column the line ends on. Inside such a run
+//! the break moves back to just after a separator, keeping the scheme (or drive) and the
+//! first segment after it whole; without a usable separator the run is split at the limit.
+//! With break markers, a split at the limit inside any run of printable ASCII is marked.
+
+use std::ops::Range;
+
+use crate::metrics::{breaks_after, number_tokens, BreakMarkers, WrapSink};
+use crate::width_of_str;
+
+/// Characters a URL or path may break after
+const SEPARATORS: &[char] = &['/', '\\', '?', '&', '=', '-', '_', '.'];
+
+/// A URL or path as char indices: breaking inside it looks for a separator at or after
+/// `protected_end`
+struct LinkRun {
+    start: usize,
+    protected_end: usize,
+    end: usize,
+}
+
+/// Whether text may contain a URL ("scheme://") or a Windows path ("C:\" or "\\server")
+pub(crate) fn may_contain_links(text: &str) -> bool {
+    text.contains("://") || text.contains(":\\") || text.contains("\\\\")
+}
+
+/// Length of the root at chars[start..]: "scheme://", "C:\" or "\\", if one starts there
+fn root_len(chars: &[char], start: usize) -> Option<usize> {
+    let rest = &chars[start..];
+    match rest {
+        ['\\', '\\', ..] => Some(2),
+        [drive, ':', '\\', ..] if drive.is_ascii_alphabetic() => Some(3),
+        [first, ..] if first.is_ascii_alphabetic() => {
+            let scheme = rest
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                .count();
+            matches!(rest[scheme..], [':', '/', '/', ..]).then_some(scheme + 3)
+        }
+        _ => None,
+    }
+}
+
+/// URLs and paths in chars; each runs over printable ASCII up to whitespace or non-ASCII
+fn link_runs(chars: &[char]) -> Vec<LinkRun> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        // A root only counts at the start of a token, so "abc://" doesn't start at "c"
+        let at_token_start = i == 0 || !chars[i - 1].is_ascii_alphanumeric();
+        let Some(root) = at_token_start.then(|| root_len(chars, i)).flatten() else {
+            i += 1;
+            continue;
+        };
+
+        let end = i + chars[i..]
+            .iter()
+            .take_while(|c| c.is_ascii_graphic())
+            .count();
+        let after_root = (i + root).min(end);
+        let protected_end = chars[after_root..end]
+            .iter()
+            .position(|&c| c == '/' || c == '\\')
+            .map_or(end, |offset| after_root + offset + 1);
+
+        runs.push(LinkRun {
+            start: i,
+            protected_end,
+            end,
+        });
+        i = end.max(i + 1);
+    }
+
+    runs
+}
+
+/// Break position for a line chars[start..] that overflows at `limit`, when that falls
+/// inside a link run: just after its last separator on the line past the protected prefix,
+/// else before the run if it began on this line
+fn link_break(runs: &[LinkRun], chars: &[char], start: usize, limit: usize) -> Option<usize> {
+    let run = runs
+        .iter()
+        .find(|run| run.start < limit && limit < run.end)?;
+    let earliest = (start + 1).max(run.protected_end);
+    (earliest..=limit)
+        .rev()
+        .find(|&k| SEPARATORS.contains(&chars[k - 1]))
+        .or((run.start > start).then_some(run.start))
+}
+
+/// Break position that moves a number or date token split at `limit` to the next line whole,
+/// when it fits on one and doesn't start the line already
+fn number_break(numbers: &[Range<usize>], start: usize, limit: usize, max: u32) -> Option<usize> {
+    numbers
+        .iter()
+        .find(|token| token.start < limit && limit < token.end)
+        .filter(|token| token.start > start && token.len() as u32 <= max)
+        .map(|token| token.start)
+}
+
+/// Characters a token cut at the line limit may break after instead
+const BREAK_AFTER: &[char] = &['.', ',', ';', ')', '、', '，'];
+
+/// Break position for a line chars[start..] whose token is cut at `limit`: just after the
+/// last BREAK_AFTER character at most `window` columns back (limit itself when the cut already
+/// follows one), provided the line it leaves is at least half of max wide (line_width is the
+/// line's width up to limit)
+fn punctuation_break(
+    chars: &[char],
+    start: usize,
+    limit: usize,
+    line_width: u32,
+    window: u32,
+    max: u32,
+    width_of: fn(char) -> u32,
+) -> Option<usize> {
+    let mut width = line_width;
+    let mut moved = 0u32;
+    for k in (start + 1..=limit).rev() {
+        if BREAK_AFTER.contains(&chars[k - 1]) {
+            return (2 * width >= max).then_some(k);
+        }
+        let char_width = width_of(chars[k - 1]);
+        moved += char_width;
+        width -= char_width;
+        if moved > window {
+            break;
+        }
+    }
+    None
+}
+
+/// Break position for a line chars[start..] that would start the next line at `limit` with a
+/// character of break_after: one character earlier, so the one before goes down with it
+fn break_after_break(
+    chars: &[char],
+    start: usize,
+    limit: usize,
+    break_after: &str,
+) -> Option<usize> {
+    (limit > start + 1 && breaks_after(chars, limit, break_after)).then(|| limit - 1)
+}
+
+/// Whether a break before chars[k], on a line starting at chars[start], cuts through a run of
+/// printable ASCII
+fn inside_token(chars: &[char], start: usize, k: usize) -> bool {
+    k > start && k < chars.len() && chars[k - 1].is_ascii_graphic() && chars[k].is_ascii_graphic()
+}
+
+fn marker_width(marker: &str) -> u32 {
+    u32::try_from(width_of_str(marker)).unwrap_or(u32::MAX)
+}
+
+/// Character wrapping that breaks URLs and paths after separators
+/// Everything outside a link run breaks exactly as wrap_chars_with does with column widths.
+/// A break at the limit inside a token first moves back to after punctuation within
+/// punctuation_window columns (see punctuation_break; 0 never moves). With markers, one still
+/// at the limit writes markers.end before it and markers.start after it, and moves back far
+/// enough for markers.end to fit; breaks after a separator, a punctuation mark or before a
+/// number don't cut a token and stay unmarked, and markers too wide for a line to hold both
+/// around one character aren't written at all. A line never starts with a character of
+/// break_after that breaks_after allows a break after (algorithm V4; "" for none). width_of
+/// measures characters in columns (get_char_width, or the astral-aware widths of algorithm
+/// V3).
+pub(crate) fn wrap_chars_splitting_links<S: WrapSink>(
+    text: &str,
+    max_chars_per_line: u32,
+    markers: Option<&BreakMarkers>,
+    punctuation_window: u32,
+    break_after: &str,
+    width_of: fn(char) -> u32,
+    out: &mut S,
+) {
+    let chars: Vec<char> = text.chars().collect();
+    let runs = link_runs(&chars);
+    let numbers = number_tokens(&chars);
+    let (end_marker_width, start_marker_width) = markers.map_or((0, 0), |markers| {
+        (marker_width(markers.end), marker_width(markers.start))
+    });
+    // Markers go in only if a line can hold both of them and a character of the token between
+    let fit = start_marker_width
+        .saturating_add(1)
+        .saturating_add(end_marker_width);
+    let markers = markers.filter(|_| fit <= max_chars_per_line);
+    let mut start = 0;
+    let mut width = 0u32;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\r' || c == '\n' {
+            chars[start..i].iter().for_each(|&c| out.push_char(c));
+            out.source_line_break();
+            i += 1;
+            start = i;
+            width = 0;
+            continue;
+        }
+
+        let char_width = width_of(c);
+        if width + char_width <= max_chars_per_line {
+            width += char_width;
+            i += 1;
+            continue;
+        }
+
+        let chosen = link_break(&runs, &chars, start, i)
+            .or_else(|| number_break(&numbers, start, i, max_chars_per_line))
+            .or_else(|| break_after_break(&chars, start, i, break_after))
+            .or_else(|| {
+                inside_token(&chars, start, i)
+                    .then(|| {
+                        punctuation_break(
+                            &chars,
+                            start,
+                            i,
+                            width,
+                            punctuation_window,
+                            max_chars_per_line,
+                            width_of,
+                        )
+                    })
+                    .flatten()
+            });
+        let mut end = chosen.unwrap_or(i);
+        let mut marked = None;
+        if let Some(markers) = markers.filter(|_| chosen.is_none()) {
+            // Moving back can reach the start of the token, where no marker is needed; the one
+            // character at least that stays on the line leaves room for the marker
+            let mut line_width = width;
+            while inside_token(&chars, start, end)
+                && end > start + 1
+                && line_width.saturating_add(end_marker_width) > max_chars_per_line
+            {
+                end -= 1;
+                line_width -= width_of(chars[end]);
+            }
+            marked = inside_token(&chars, start, end).then_some(markers);
+        }
+
+        chars[start..end].iter().for_each(|&c| out.push_char(c));
+        if let Some(markers) = marked {
+            out.push_text(markers.end);
+        }
+        out.line_break();
+        let lead = match marked {
+            Some(markers) => {
+                out.push_text(markers.start);
+                start_marker_width
+            }
+            None => 0,
+        };
+        start = end;
+        if end == i {
+            // c starts the new line even when it is wider than the whole line
+            width = lead.saturating_add(char_width);
+            i += 1;
+        } else {
+            // The characters after the break moved down; look at c again
+            width = chars[end..i]
+                .iter()
+                .map(|&c| width_of(c))
+                .fold(lead, u32::saturating_add);
+        }
+    }
+    chars[start..].iter().for_each(|&c| out.push_char(c));
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::justify_text_cjk;
+
+    /// 200 characters of URL
+    const URL: &str =
+        "https://www.example.com/articles/2024/03/15/how-to-wrap-long-urls-in-chinese-text/\
+index.html?utm_source=newsletter&utm_medium=email&utm_campaign=spring_sale&ref=homepage_banner\
+&lang=zh-CN&page=2&x=123";
+
+    #[test]
+    fn a_long_url_mid_sentence_breaks_after_separators() {
+        assert_eq!(URL.len(), 200);
+        let text = format!("详情请访问{}了解更多信息。", URL);
+        let url_start = text.find("https").unwrap();
+        // "https://www.example.com/" is the protected prefix
+        let protected_end = url_start + 24;
+        for max_chars_per_line in [20, 30, 40, 60, 80] {
+            let wrapped = justify_text_cjk(&text, max_chars_per_line);
+            assert_eq!(wrapped.replace("\r\n", ""), text, "{}", max_chars_per_line);
+            let mut offset = 0;
+            for line in wrapped.split("\r\n") {
+                assert!(
+                    width_of_str(line) <= max_chars_per_line as u64,
+                    "{:?}",
+                    line
+                );
+                offset += line.len();
+                if (url_start..url_start + URL.len()).contains(&offset) && offset > url_start {
+                    assert!(
+                        offset >= protected_end && line.ends_with(SEPARATORS)
+                            || offset < protected_end && line.len() == max_chars_per_line as usize,
+                        "{} columns: {:?}",
+                        max_chars_per_line,
+                        line
+                    );
+                }
+            }
+        }
+        // Where the prefix fits on a line it is never cut
+        assert!(justify_text_cjk(&text, 30).contains("\r\nhttps://www.example.com/\r\n"));
+    }
+
+    #[test]
+    fn the_scheme_and_first_segment_stay_whole() {
+        // The run moves down whole rather than break before the end of example.com/
+        assert_eq!(
+            justify_text_cjk("请访问https://example.com/docs/guide.html了解", 24),
+            "请访问\r\nhttps://example.com/\r\ndocs/guide.html了解"
+        );
+        // Narrower than the prefix, the line starting it is cut at the limit
+        assert_eq!(
+            justify_text_cjk("请访问https://example.com/docs/guide.html了解", 16),
+            "请访问\r\nhttps://example.\r\ncom/docs/guide.\r\nhtml了解"
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(
+            justify_text_cjk("路径C:\\Users\\admin\\Documents\\report_final.docx在这", 14),
+            "路径C:\\Users\\\r\nadmin\\\r\nDocuments\\\r\nreport_final.\r\ndocx在这"
+        );
+        assert_eq!(
+            justify_text_cjk("文件\\\\server\\share\\folder\\file.txt", 10),
+            "文件\r\n\\\\server\\\r\nshare\\\r\nfolder\\\r\nfile.txt"
+        );
+    }
+
+    #[test]
+    fn no_separator_in_reach_is_a_hard_split() {
+        assert_eq!(
+            justify_text_cjk("网址https://a.b/cccccccccccccccccccccc结束", 10),
+            "网址\r\nhttps://a.\r\nb/\r\ncccccccccc\r\ncccccccccc\r\ncc结束"
+        );
+        // A host longer than the line can only be cut through
+        assert_eq!(
+            justify_text_cjk("看http://averyveryverylonghostname.example.com/x吧", 12),
+            "看\r\nhttp://avery\r\nveryverylong\r\nhostname.exa\r\nmple.com/x吧"
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let chars = |text: &str| text.chars().collect::<Vec<char>>();
+        assert_eq!(root_len(&chars("https://x"), 0), Some(8));
+        assert_eq!(root_len(&chars("git+ssh://x"), 0), Some(10));
+        assert_eq!(root_len(&chars("C:\\x"), 0), Some(3));
+        assert_eq!(root_len(&chars("\\\\server"), 0), Some(2));
+        assert_eq!(root_len(&chars("http:x"), 0), None);
+        assert!(!may_contain_links("这里没有链接 a/b"));
+        // "abc://" only starts a run at "a"
+        let runs = link_runs(&chars("xabc://d/e f"));
+        assert_eq!(runs.len(), 1);
+        assert_eq!(
+            (runs[0].start, runs[0].protected_end, runs[0].end),
+            (0, 9, 10)
+        );
+    }
+
+    const BASE64: &str = "QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVo";
+
+    fn marked(max_chars_per_line: u32, end: &str, start: &str) -> crate::JustifyOptions {
+        crate::JustifyOptions {
+            max_chars_per_line,
+            break_marker_end: end.to_string(),
+            break_marker_start: start.to_string(),
+            ..crate::JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn a_token_split_three_times_is_marked_at_each_split() {
+        let text = format!("数据{}", BASE64);
+        assert_eq!(
+            marked(12, "-", "").justify(&text),
+            "数据QUJDREV-\r\nGR0hJSktMTU-\r\n5PUFFSU1RVV-\r\nldYWVo"
+        );
+        let arrows = marked(12, "↩", "↪").justify(&text);
+        assert_eq!(
+            arrows,
+            "数据QUJDRE↩\r\n↪VGR0hJSk↩\r\n↪tMTU5PUF↩\r\n↪FSU1RVVl↩\r\n↪dYWVo"
+        );
+        // The markers are counted: every line but the last is full
+        let widths: Vec<u64> = arrows.split("\r\n").map(width_of_str).collect();
+        assert_eq!(widths, [12, 12, 12, 12, 7]);
+        assert_eq!(arrows.replace(['↩', '↪'], "").replace("\r\n", ""), text);
+    }
+
+    #[test]
+    fn breaks_that_cut_no_token_stay_unmarked() {
+        let arrows = marked(16, "↩", "↪");
+        // After a URL separator
+        assert_eq!(
+            arrows.justify("链接https://example.com/a/very/long/path结束"),
+            "链接\r\nhttps://example.\r\ncom/a/very/long/\r\npath结束"
+        );
+        // A number moved down whole
+        assert_eq!(
+            marked(8, "↩", "↪").justify("价格12345678个"),
+            "价格\r\n12345678\r\n个"
+        );
+        // Between CJK characters, and between words
+        assert_eq!(
+            marked(8, "↩", "↪").justify("这是一段中文文本内容"),
+            "这是一段\r\n中文文本\r\n内容"
+        );
+        assert_eq!(
+            marked(10, "↩", "↪").justify("the quick brown fox"),
+            "the quick\r\nbrown fox"
+        );
+    }
+
+    #[test]
+    fn markers_too_wide_for_the_line_are_left_out() {
+        let text = format!("数据{}", BASE64);
+        // "↪", one character and "↩" take five columns
+        assert_eq!(
+            marked(4, "↩", "↪").justify(&text),
+            crate::JustifyOptions {
+                max_chars_per_line: 4,
+                ..crate::JustifyOptions::default()
+            }
+            .justify(&text)
+        );
+        // Five columns is enough
+        assert_eq!(
+            marked(5, "↩", "↪").justify("数据QUJDRE"),
+            "数据\r\nQUJ↩\r\n↪DRE"
+        );
+        for max_chars_per_line in 1..=30 {
+            for (end, start) in [("↩", "↪"), ("-", ""), ("", "→"), ("[cont]", "")] {
+                let wrapped = marked(max_chars_per_line, end, start).justify(&text);
+                for line in wrapped.split("\r\n") {
+                    assert!(
+                        width_of_str(line) <= u64::from(max_chars_per_line.max(2)),
+                        "{:?} {:?} at {}: {:?}",
+                        end,
+                        start,
+                        max_chars_per_line,
+                        wrapped
+                    );
+                }
+            }
+        }
+    }
+
+    fn window(max_chars_per_line: u32, window: u32, version: u32) -> crate::JustifyOptions {
+        crate::JustifyOptions {
+            max_chars_per_line,
+            punctuation_break_window: window,
+            algorithm_version: version,
+            ..crate::JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn a_comma_just_before_the_limit_takes_the_break() {
+        // The comma ends column 17 of 20
+        let text = format!("数据{},{}", "ACGT".repeat(3), "ACGT".repeat(3));
+        assert_eq!(
+            window(20, 8, 2).justify(&text),
+            "数据ACGTACGTACGT,\r\nACGTACGTACGT"
+        );
+        // Off, and before algorithm version 2, the token is cut at the limit
+        let cut = "数据ACGTACGTACGT,ACG\r\nTACGTACGT";
+        assert_eq!(window(20, 0, 2).justify(&text), cut);
+        assert_eq!(window(20, 8, 1).justify(&text), cut);
+        assert_eq!(
+            window(20, 8, 2).justify("数据ACGT;ACGT)ACGT.ACGTACGTACGT"),
+            "数据ACGT;ACGT)ACGT.\r\nACGTACGTACGT"
+        );
+    }
+
+    #[test]
+    fn punctuation_out_of_reach_is_ignored() {
+        // 22 columns before the limit: outside even a 20 column window
+        let far = format!("数据{}A,{}", "ACGT".repeat(3), "ACGT".repeat(8));
+        let cut = "数据ACGTACGTACGTA,ACGTACGTACGTACGTACGTAC\r\nGTACGTACGT";
+        assert_eq!(window(40, 8, 2).justify(&far), cut);
+        assert_eq!(window(40, 20, 2).justify(&far), cut);
+        // In the window, but the line left would be under half the width
+        assert_eq!(
+            window(20, 20, 2).justify("数据AB,CDEFGHIJKLMNOPQRSTUVWXYZ"),
+            "数据AB,CDEFGHIJKLMNO\r\nPQRSTUVWXYZ"
+        );
+    }
+
+    #[test]
+    fn breaks_outside_tokens_ignore_the_window() {
+        for text in [
+            "这是一段, 中文文本内容多",
+            "这是一段中文，文本内容多多",
+            "the quick, brown fox, jumps",
+        ] {
+            assert_eq!(
+                window(10, 8, 2).justify(text),
+                window(10, 0, 2).justify(text),
+                "{:?}",
+                text
+            );
+        }
+    }
+}
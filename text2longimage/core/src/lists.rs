@@ -0,0 +1,393 @@
+//! List markers, for wrapping list items with a hanging indent
+//! With JustifyOptions' hangingIndent, a source line that starts with a list marker wraps its
+//! continuation lines under the text after the marker instead of at the margin. Besides
+//! Markdown's "- " and "1. ", legal and regulatory text numbers its clauses "(a)", "a)",
+//! "iv.", "第1条", "（一）" or "①", so those count too. Every form is bounded: one letter, up to
+//! three digits, roman numerals and CJK numbers up to 20, so a sentence that happens to start
+//! with a word and a full stop isn't taken for a list item.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::{expected_values, find_value};
+use crate::metrics::{WidthModel, WrapSink};
+use crate::{OptionValue, WebtoolsError};
+
+/// Roman numerals a marker may use, 1 to 20
+const ROMAN_NUMERALS: [&str; 20] = [
+    "i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix", "x", "xi", "xii", "xiii", "xiv", "xv",
+    "xvi", "xvii", "xviii", "xix", "xx",
+];
+
+/// CJK numbers a "（一）" or "一、" marker may use, 1 to 20
+const CJK_NUMBERS: [&str; 20] = [
+    "一", "二", "三", "四", "五", "六", "七", "八", "九", "十", "十一", "十二", "十三", "十四",
+    "十五", "十六", "十七", "十八", "十九", "二十",
+];
+
+/// Characters the number of a 第N条 marker is written with, besides digits
+const CJK_NUMERAL_CHARS: &str = "〇零一二三四五六七八九十百千两";
+
+/// Units a 第N条 marker may end with: article, item and paragraph, simplified and traditional
+const CLAUSE_UNITS: [char; 5] = ['条', '條', '項', '项', '款'];
+
+/// Longest number a 第N条 marker may have, in characters
+const MAX_CLAUSE_NUMBER_LEN: usize = 6;
+
+/// What goes between a list marker and its text when hangingIndent wraps the item
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListMarkerSpacing {
+    /// The whitespace the source has (possibly none, after a CJK marker)
+    #[default]
+    Keep,
+    /// Exactly one space
+    Space,
+    /// Exactly one ideographic space (U+3000)
+    Ideographic,
+}
+
+impl ListMarkerSpacing {
+    /// Parse "keep", "space" or "ideographic"
+    pub fn parse(spacing: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| spacing == name).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown list marker spacing: {} (expected {})",
+                spacing,
+                expected_values::<ListMarkerSpacing>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for ListMarkerSpacing {
+    const KIND: &'static str = "listMarkerSpacing";
+    const USED_BY: &'static [&'static str] = &["JustifyOptions.listMarkerSpacing"];
+    const ALL: &'static [Self] = &[
+        ListMarkerSpacing::Keep,
+        ListMarkerSpacing::Space,
+        ListMarkerSpacing::Ideographic,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ListMarkerSpacing::Keep => "keep",
+            ListMarkerSpacing::Space => "space",
+            ListMarkerSpacing::Ideographic => "ideographic",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ListMarkerSpacing::Keep => "The whitespace after the marker as the source has it",
+            ListMarkerSpacing::Space => "Exactly one space after the marker",
+            ListMarkerSpacing::Ideographic => "Exactly one ideographic space (U+3000) after it",
+        }
+    }
+}
+
+/// A source line that starts with a list marker, in pieces
+pub(crate) struct ListItem<'a> {
+    /// Leading spaces, tabs and ideographic spaces
+    pub(crate) indent: &'a str,
+    pub(crate) marker: &'a str,
+    /// Whitespace between the marker and the text; may be empty after a CJK marker
+    pub(crate) spacing: &'a str,
+    pub(crate) body: &'a str,
+    /// Indent, marker and spacing together
+    head: &'a str,
+}
+
+impl<'a> ListItem<'a> {
+    /// Indent, marker and spacing, with the spacing written as asked
+    pub(crate) fn prefix(&self, spacing: ListMarkerSpacing) -> Cow<'a, str> {
+        let spacing = match spacing {
+            ListMarkerSpacing::Keep => self.spacing,
+            ListMarkerSpacing::Space => " ",
+            ListMarkerSpacing::Ideographic => "\u{3000}",
+        };
+        if spacing == self.spacing {
+            Cow::Borrowed(self.head)
+        } else {
+            Cow::Owned(format!("{}{}{}", self.indent, self.marker, spacing))
+        }
+    }
+}
+
+/// Whitespace that indents a list item or separates its marker from the text
+fn is_list_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\u{3000}')
+}
+
+/// ①–⑳, ⑴–⒇ and ⒈–⒛
+fn is_enclosed_number(c: char) -> bool {
+    matches!(c as u32, 0x2460..=0x249B)
+}
+
+/// A label of an ASCII marker: one to three digits, one letter, or a roman numeral up to 20
+/// in one case
+fn is_ascii_label(label: &str) -> bool {
+    let digits = label.bytes().all(|b| b.is_ascii_digit());
+    let letter = label.len() == 1 && label.bytes().all(|b| b.is_ascii_alphabetic());
+    let roman = |numeral: &str| ROMAN_NUMERALS.contains(&numeral);
+    (digits && (1..=3).contains(&label.len()))
+        || letter
+        || roman(label)
+        || (label.bytes().all(|b| b.is_ascii_uppercase()) && roman(&label.to_ascii_lowercase()))
+}
+
+/// A label of a "（一）" or "一、" marker: a CJK number up to 20, or one to three digits
+fn is_cjk_label(label: &str) -> bool {
+    CJK_NUMBERS.contains(&label)
+        || (label.bytes().all(|b| b.is_ascii_digit()) && (1..=3).contains(&label.len()))
+}
+
+/// The N of 第N条: up to MAX_CLAUSE_NUMBER_LEN ASCII or fullwidth digits or CJK numerals
+fn is_clause_number(number: &str) -> bool {
+    let len = number.chars().count();
+    (1..=MAX_CLAUSE_NUMBER_LEN).contains(&len)
+        && (number
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('０'..='９').contains(&c))
+            || number.chars().all(|c| CJK_NUMERAL_CHARS.contains(c)))
+}
+
+/// Byte length of "open label close" at the start of rest, if label passes
+fn enclosed(rest: &str, open: char, close: char, label: fn(&str) -> bool) -> Option<usize> {
+    let inner = rest.strip_prefix(open)?;
+    let end = inner.find(close)?;
+    label(&inner[..end]).then(|| open.len_utf8() + end + close.len_utf8())
+}
+
+/// Byte length of "label terminator" at the start of rest, if label passes
+fn terminated(rest: &str, terminators: &[char], label: fn(&str) -> bool) -> Option<usize> {
+    let end = rest.find(terminators)?;
+    let terminator = rest[end..].chars().next()?;
+    label(&rest[..end]).then(|| end + terminator.len_utf8())
+}
+
+/// Byte length of a 第N条 marker at the start of rest
+fn clause(rest: &str) -> Option<usize> {
+    let number = rest.strip_prefix('第')?;
+    let end = number.find(CLAUSE_UNITS)?;
+    let unit = number[end..].chars().next()?;
+    is_clause_number(&number[..end]).then(|| '第'.len_utf8() + end + unit.len_utf8())
+}
+
+/// Byte length of the marker rest starts with, and whether whitespace has to follow it
+/// ASCII markers need it, so "e.g." or "a)b" isn't one; CJK text often runs straight on
+/// after "①" or "（一）", but 第N条 starts many a sentence and needs it too.
+fn marker_len(rest: &str) -> Option<(usize, bool)> {
+    let first = rest.chars().next()?;
+    if matches!(first, '-' | '*' | '+' | '•') {
+        return Some((first.len_utf8(), true));
+    }
+    if is_enclosed_number(first) {
+        return Some((first.len_utf8(), false));
+    }
+    if let Some(len) = enclosed(rest, '(', ')', is_ascii_label)
+        .or_else(|| terminated(rest, &['.', ')'], is_ascii_label))
+    {
+        return Some((len, true));
+    }
+    if let Some(len) = clause(rest) {
+        return Some((len, true));
+    }
+    enclosed(rest, '（', '）', is_cjk_label)
+        .or_else(|| enclosed(rest, '(', ')', is_cjk_label))
+        .or_else(|| terminated(rest, &['、'], is_cjk_label))
+        .map(|len| (len, false))
+}
+
+/// line split into indent, marker, spacing and text, if it is a list item with some text
+pub(crate) fn list_item(line: &str) -> Option<ListItem<'_>> {
+    let rest = line.trim_start_matches(is_list_space);
+    let indent = &line[..line.len() - rest.len()];
+    let (len, needs_spacing) = marker_len(rest)?;
+    let (marker, after) = rest.split_at(len);
+    let body = after.trim_start_matches(is_list_space);
+    let spacing = &after[..after.len() - body.len()];
+    if (needs_spacing && spacing.is_empty()) || body.trim().is_empty() {
+        return None;
+    }
+    Some(ListItem {
+        indent,
+        marker,
+        spacing,
+        body,
+        head: &line[..line.len() - body.len()],
+    })
+}
+
+/// The list marker line starts with after its indentation, e.g. "-", "(a)", "iv.", "第1条",
+/// "（一）" or "①"; None unless text follows it
+pub fn list_marker(line: &str) -> Option<&str> {
+    list_item(line).map(|item| item.marker)
+}
+
+/// A list item as the wrapper places it: prefix on the first line, indent on the rest
+pub(crate) struct HangingItem<'a> {
+    pub(crate) prefix: Cow<'a, str>,
+    /// Spaces in front of each continuation line, as wide as the prefix
+    pub(crate) indent: String,
+    pub(crate) body: &'a str,
+}
+
+/// line as a hanging list item, if it is one and its prefix takes at most half of max_width
+pub(crate) fn hanging_item<'a, M: WidthModel>(
+    line: &'a str,
+    spacing: ListMarkerSpacing,
+    max_width: f64,
+    model: &M,
+) -> Option<HangingItem<'a>> {
+    let item = list_item(line)?;
+    let prefix = item.prefix(spacing);
+    let width: f64 = prefix.chars().map(|c| model.char_width(c)).sum();
+    let space = model.char_width(' ');
+    if width > max_width / 2.0 || space <= 0.0 {
+        return None;
+    }
+    Some(HangingItem {
+        prefix,
+        indent: " ".repeat((width / space).ceil() as usize),
+        body: item.body,
+    })
+}
+
+/// Starts every line after the first with the indent of a hanging list item
+pub(crate) struct HangingIndent<'a, S: WrapSink> {
+    pub(crate) inner: &'a mut S,
+    pub(crate) indent: &'a str,
+}
+
+impl<S: WrapSink> WrapSink for HangingIndent<'_, S> {
+    fn push_text(&mut self, text: &str) {
+        self.inner.push_text(text);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.inner.push_char(c);
+    }
+
+    fn line_break(&mut self) {
+        self.inner.line_break();
+        self.inner.push_text(self.indent);
+    }
+
+    fn word_break(&mut self) {
+        self.inner.word_break();
+        self.inner.push_text(self.indent);
+    }
+
+    fn source_line_break(&mut self) {
+        self.inner.source_line_break();
+        self.inner.push_text(self.indent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines that start with a list marker, and lines that don't
+    const LIST_MARKER_EXAMPLES: [(&str, Option<&str>); 20] = [
+        ("(a) Seller", Some("(a)")),
+        ("a) Seller", Some("a)")),
+        ("  ii. Buyer", Some("ii.")),
+        ("IV. Term", Some("IV.")),
+        ("XX) Notices", Some("XX)")),
+        ("12. Notices", Some("12.")),
+        ("- item", Some("-")),
+        ("第1条 目的", Some("第1条")),
+        ("第一百二十三条\u{3000}目的", Some("第一百二十三条")),
+        ("第二款 甲方", Some("第二款")),
+        ("（一）甲方", Some("（一）")),
+        ("十五、乙方", Some("十五、")),
+        ("①甲方", Some("①")),
+        ("xxi. too far", None),
+        ("Iv. mixed case", None),
+        ("(aa) two letters", None),
+        ("e.g. an example", None),
+        ("1999. a year", None),
+        ("第1条规定的", None),
+        ("(a)", None),
+    ];
+
+    /// An English contract excerpt at 40 columns, and a Chinese statute excerpt at 30 with and
+    /// without ideographic spacing, wrapped with hangingIndent
+    #[cfg(feature = "json")]
+    const HANGING_EXAMPLES: [(&str, &str, &[&str]); 3] = [
+        (
+            r#"{"maxCharsPerLine": 40, "hangingIndent": true}"#,
+            "1. Definitions\n\
+             (a) \"Agreement\" means this agreement together with every schedule attached to it.\n\
+             (b) \"Services\" means the services described in Schedule 1.\n  \
+             (i) Each party shall keep the other's confidential information secret.\n\
+             IV. Term and termination of this agreement.",
+            &[
+                "1. Definitions",
+                "(a) \"Agreement\" means this agreement",
+                "    together with every schedule",
+                "    attached to it.",
+                "(b) \"Services\" means the services",
+                "    described in Schedule 1.",
+                "  (i) Each party shall keep the other's",
+                "      confidential information secret.",
+                "IV. Term and termination of this",
+                "    agreement.",
+            ],
+        ),
+        (
+            r#"{"maxCharsPerLine": 30, "hangingIndent": true}"#,
+            "第一条\u{3000}为了规范合同行为，保护当事人的合法权益，制定本法。\n\
+             第二条 本法所称合同是民事主体之间设立、变更、终止民事法律关系的协议。\n\
+             （一）当事人应当遵循公平原则确定各方的权利和义务；\n\
+             ①当事人应当按照约定全面履行自己的义务。",
+            &[
+                "第一条\u{3000}为了规范合同行为，保护",
+                "        当事人的合法权益，制定",
+                "        本法。",
+                "第二条 本法所称合同是民事主体",
+                "       之间设立、变更、终止民",
+                "       事法律关系的协议。",
+                "（一）当事人应当遵循公平原则确",
+                "      定各方的权利和义务；",
+                "①当事人应当按照约定全面履行自",
+                "  己的义务。",
+            ],
+        ),
+        (
+            r#"{"maxCharsPerLine": 30, "hangingIndent": true, "listMarkerSpacing": "ideographic"}"#,
+            "第二条 本法所称合同是民事主体之间设立、变更、终止民事法律关系的协议。\n\
+             ①当事人应当按照约定全面履行自己的义务。",
+            &[
+                "第二条\u{3000}本法所称合同是民事主体",
+                "        之间设立、变更、终止民",
+                "        事法律关系的协议。",
+                "①\u{3000}当事人应当按照约定全面履行",
+                "    自己的义务。",
+            ],
+        ),
+    ];
+
+    #[test]
+    fn markers_of_contracts_and_statutes() {
+        for (line, marker) in LIST_MARKER_EXAMPLES {
+            assert_eq!(list_marker(line), marker, "{line:?}");
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn hanging_indent_lines_up_under_the_text() {
+        for (options_json, text, expected) in HANGING_EXAMPLES {
+            let options = crate::JustifyOptions::from_json(options_json).unwrap();
+            assert_eq!(
+                options.justify(text),
+                expected.join("\r\n"),
+                "{options_json}"
+            );
+        }
+    }
+}
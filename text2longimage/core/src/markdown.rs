@@ -0,0 +1,457 @@
+use crate::paragraphs::{line_kinds, LineKind};
+
+/// Markdown-to-prose conversion for stats and plain-text rendering
+/// Line-oriented and forgiving rather than CommonMark: heading hashes, blockquote and list
+/// markers, horizontal rules and link reference definitions are dropped; emphasis markers,
+/// link and image syntax (keeping the text / alt text) and code backticks are removed inline.
+/// Fenced code block contents are left untouched. Lines that are pure syntax (fences, rules,
+/// setext underlines, reference definitions) are removed along with their line break.
+/// Anything that doesn't match cleanly is kept as written.
+pub fn strip_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut first_line = true;
+
+    for (raw_line, kind) in text.split('\n').zip(line_kinds(text)) {
+        let (line, cr) = match raw_line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (raw_line, ""),
+        };
+
+        match kind {
+            LineKind::FenceOpen | LineKind::FenceClose => continue,
+            LineKind::Code => {}
+            _ if is_rule(line) || is_reference_definition(line) => continue,
+            _ => {}
+        }
+
+        if !first_line {
+            out.push('\n');
+        }
+        first_line = false;
+
+        if kind == LineKind::Code {
+            out.push_str(line);
+        } else {
+            let (indent, content) = block_prefix(line);
+            out.push_str(indent);
+            strip_inline(content, &mut out);
+        }
+        out.push_str(cr);
+    }
+
+    out
+}
+
+/// (fence char, length, info string) if the line opens or closes a code fence
+pub(crate) fn fence_run(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(c).len();
+    let info = trimmed[len..].trim();
+    // A backtick fence's info string can't itself contain backticks
+    (len >= 3 && !(c == '`' && info.contains('`'))).then_some((c, len, info))
+}
+
+/// Horizontal rule or setext heading underline: "---", "***", "___", "===", "- - -"
+fn is_rule(line: &str) -> bool {
+    let mut chars = line.chars().filter(|c| !c.is_whitespace());
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    let rest = chars.clone().count();
+    matches!(first, '-' | '*' | '_' | '=') && rest >= 2 && chars.all(|c| c == first)
+}
+
+/// "[label]: url" lines, which render as nothing
+fn is_reference_definition(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with('[')
+        && trimmed
+            .find("]:")
+            .is_some_and(|end| end > 1 && !trimmed[1..end].contains(']'))
+}
+
+/// Split off leading whitespace (kept, so nesting stays visible) after removing blockquote
+/// markers, a heading's hashes or a list marker; returns (indent, content)
+fn block_prefix(line: &str) -> (&str, &str) {
+    let mut content = line;
+    // "> > quote" nests; each level is one '>' and an optional space
+    loop {
+        let trimmed = content.trim_start();
+        match trimmed.strip_prefix('>') {
+            Some(rest) => content = rest.strip_prefix(' ').unwrap_or(rest),
+            None => break,
+        }
+    }
+
+    let body = content.trim_start();
+    let indent = &content[..content.len() - body.len()];
+
+    match atx_heading(body) {
+        Some(heading) => (indent, heading),
+        None => (indent, strip_list_marker(body)),
+    }
+}
+
+/// Text of an ATX heading: 1-6 hashes then a space, with an optional closing run of hashes
+pub(crate) fn atx_heading(body: &str) -> Option<&str> {
+    let hashes = body.len() - body.trim_start_matches('#').len();
+    if !(1..=6).contains(&hashes) {
+        return None;
+    }
+    let after = &body[hashes..];
+    if !after.is_empty() && !after.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    let heading = after.trim();
+    let without_closing = heading.trim_end_matches('#');
+    if without_closing.is_empty() || without_closing.ends_with(' ') {
+        Some(without_closing.trim_end())
+    } else {
+        Some(heading)
+    }
+}
+
+/// "===" or "---" under a line, which makes that line a heading
+pub(crate) fn is_setext_underline(line: &str) -> bool {
+    let trimmed = line.trim();
+    ['=', '-']
+        .into_iter()
+        .any(|c| !trimmed.is_empty() && trimmed.chars().all(|other| other == c))
+}
+
+/// "- ", "* ", "+ ", "1. " or "1) ", plus a task list box after it
+pub(crate) fn strip_list_marker(body: &str) -> &str {
+    let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker_len = if body.starts_with(['-', '*', '+']) {
+        1
+    } else if (1..=9).contains(&digits) && body[digits..].starts_with(['.', ')']) {
+        digits + 1
+    } else {
+        return body;
+    };
+
+    let after = &body[marker_len..];
+    if !after.starts_with([' ', '\t']) {
+        return body;
+    }
+    let item = after.trim_start();
+    ["[ ] ", "[x] ", "[X] "]
+        .iter()
+        .find_map(|task| item.strip_prefix(task))
+        .unwrap_or(item)
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Owned(String),
+    /// A run of '*', '_' or "~~"; dropped once matched with a closing run
+    Delimiter {
+        run: &'a str,
+        matched: bool,
+    },
+}
+
+/// Remove inline syntax from one line's content, appending the result to `out`
+fn strip_inline(line: &str, out: &mut String) {
+    for piece in inline_pieces(line) {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Owned(text) => out.push_str(&text),
+            Piece::Delimiter { run, matched } => {
+                if !matched {
+                    out.push_str(run);
+                }
+            }
+        }
+    }
+}
+
+fn inline_pieces(line: &str) -> Vec<Piece<'_>> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    // Indexes into pieces of delimiter runs that may still be closed
+    let mut openers: Vec<usize> = Vec::new();
+    // Start of the literal text not yet pushed
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+        let Some(c) = rest.chars().next() else { break };
+
+        let (piece, len) = match c {
+            '\\' if rest[1..].starts_with(|next: char| next.is_ascii_punctuation()) => {
+                (Piece::Text(&rest[1..2]), 2)
+            }
+            '`' => {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+                match code_span(&rest[ticks..], ticks) {
+                    Some((code, len)) => (Piece::Text(code), ticks + len),
+                    // Unmatched backticks are literal
+                    None => {
+                        i += ticks;
+                        continue;
+                    }
+                }
+            }
+            '!' | '[' => match link(rest) {
+                Some((label, len)) => {
+                    let mut text = String::new();
+                    strip_inline(label, &mut text);
+                    (Piece::Owned(text), len)
+                }
+                None => {
+                    i += 1;
+                    continue;
+                }
+            },
+            '<' => match autolink(rest) {
+                Some((address, len)) => (Piece::Text(address), len),
+                None => {
+                    i += 1;
+                    continue;
+                }
+            },
+            '*' | '_' | '~' => {
+                let len = rest.len() - rest.trim_start_matches(c).len();
+                // Only "~~" is strikethrough, so "~/path" and "~5" stay as they are
+                if c == '~' && len != 2 {
+                    i += len;
+                    continue;
+                }
+
+                let before = line[..i].chars().next_back();
+                let after = rest[len..].chars().next();
+                let mut can_open = after.is_some_and(|a| !a.is_whitespace());
+                let mut can_close = before.is_some_and(|b| !b.is_whitespace());
+                // Underscores inside words ("snake_case") never emphasize
+                if c == '_' {
+                    can_open &= !before.is_some_and(char::is_alphanumeric);
+                    can_close &= !after.is_some_and(char::is_alphanumeric);
+                }
+
+                let run = &rest[..len];
+                let opener = openers
+                    .iter()
+                    .rposition(|&index| {
+                        matches!(pieces[index], Piece::Delimiter { run: open, .. } if open == run)
+                    })
+                    .filter(|_| can_close);
+                let matched = match opener {
+                    Some(position) => {
+                        if let Piece::Delimiter { matched, .. } = &mut pieces[openers[position]] {
+                            *matched = true;
+                        }
+                        openers.truncate(position);
+                        true
+                    }
+                    None => false,
+                };
+                if !matched && can_open {
+                    // +1 for the text piece pushed below, if any
+                    openers.push(pieces.len() + usize::from(text_start < i));
+                }
+                (Piece::Delimiter { run, matched }, len)
+            }
+            _ => {
+                i += c.len_utf8();
+                continue;
+            }
+        };
+
+        if text_start < i {
+            pieces.push(Piece::Text(&line[text_start..i]));
+        }
+        pieces.push(piece);
+        i += len;
+        text_start = i;
+    }
+
+    if text_start < line.len() {
+        pieces.push(Piece::Text(&line[text_start..]));
+    }
+    pieces
+}
+
+/// Content of a code span whose opening run (of `ticks` backticks) was just consumed:
+/// (content, bytes through the closing run)
+fn code_span(rest: &str, ticks: usize) -> Option<(&str, usize)> {
+    let mut from = 0;
+    while let Some(offset) = rest[from..].find('`') {
+        let start = from + offset;
+        let len = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        if len == ticks {
+            let code = &rest[..start];
+            // "`` `x` ``" pads with one space on each side
+            let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                Some(inner) if !inner.is_empty() => inner,
+                _ => code,
+            };
+            return Some((code, start + len));
+        }
+        from = start + len;
+    }
+    None
+}
+
+/// "[text](url)", "[text][ref]", "[text][]" or "![alt](url)" at the start of `rest`:
+/// (text, bytes consumed)
+fn link(rest: &str) -> Option<(&str, usize)> {
+    let open = if rest.starts_with("![") {
+        2
+    } else if rest.starts_with('[') {
+        1
+    } else {
+        return None;
+    };
+
+    let close = open + matching(&rest[open..], '[', ']')?;
+    let label = &rest[open..close];
+    let after = &rest[close + 1..];
+
+    let target_len = if let Some(target) = after.strip_prefix('(') {
+        matching(target, '(', ')')? + 2
+    } else if let Some(reference) = after.strip_prefix('[') {
+        let end = reference.find(']')?;
+        if reference[..end].contains('[') {
+            return None;
+        }
+        end + 2
+    } else {
+        return None;
+    };
+    Some((label, close + 1 + target_len))
+}
+
+/// Offset of the `close` bracket balancing an already consumed `open`
+fn matching(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == open => depth += 1,
+            _ if c == close => {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// "<https://example.com>" or "<me@example.com>": (address, bytes consumed)
+fn autolink(rest: &str) -> Option<(&str, usize)> {
+    let end = rest.find('>')?;
+    let inner = &rest[1..end];
+    let is_url = inner.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() >= 2
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+    });
+    let is_email = inner.contains('@') && !inner.starts_with('@');
+    (!inner.is_empty() && !inner.contains(char::is_whitespace) && (is_url || is_email))
+        .then_some((inner, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const README: &str = "\
+# text2longimage
+
+[![Build](https://ci.example/badge.svg)](https://ci.example) ![logo](logo.png)
+
+Turn **long text** into a _single_ image. See [the docs][docs] or <https://example.com>.
+
+> **Note:** needs a `wasm32` build
+> > nested
+
+## Install
+
+1. Run `npm install`
+2. Build with `` cargo `build` ``
+- [x] CJK support
+* ~~Old~~ New API
+
+```sh
+# not a heading
+npm run **build**
+```
+
+---
+Setup
+=====
+Keep snake_case_names, 2 * 3 * 4, ~/path and \\*stars\\*.
+
+[docs]: https://example.com/docs
+";
+
+    #[test]
+    fn readme_fragment() {
+        let expected = "\
+text2longimage
+
+Build logo
+
+Turn long text into a single image. See the docs or https://example.com.
+
+Note: needs a wasm32 build
+nested
+
+Install
+
+Run npm install
+Build with cargo `build`
+CJK support
+Old New API
+
+# not a heading
+npm run **build**
+
+Setup
+Keep snake_case_names, 2 * 3 * 4, ~/path and *stars*.
+
+";
+        assert_eq!(strip_markdown(README), expected);
+    }
+
+    #[test]
+    fn unmatched_syntax_is_kept() {
+        for text in [
+            "**bold",
+            "a * b",
+            "[not a link]",
+            "[text](unclosed",
+            "`tick",
+            "<not an autolink>",
+            "#hashtag",
+            "-dash",
+            "1.5 million",
+            "~~",
+        ] {
+            assert_eq!(strip_markdown(text), text, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn line_breaks_and_an_unclosed_fence() {
+        assert_eq!(strip_markdown("# A\r\n\r\n*b*\r\n"), "A\r\n\r\nb\r\n");
+        // Everything after a fence that never closes is code
+        assert_eq!(
+            strip_markdown("text\n```\n# code\n**x**"),
+            "text\n# code\n**x**"
+        );
+        assert_eq!(strip_markdown(""), "");
+    }
+}
@@ -0,0 +1,173 @@
+//! Combining marks piled onto one character ("Zalgo" text), and lines of nothing but
+//! invisible characters
+//! The wrappers give every mark a width of its own while a canvas stacks them all on their
+//! base, so a few hundred make one wide line that renders as a tall smear, and a line of
+//! zero-width characters draws as nothing at all. JustifyOptions' maxMarksPerCluster keeps the
+//! first few marks on each character and empties such lines; the stats and validate_input
+//! report the text so a UI can warn before wrapping.
+
+use std::borrow::Cow;
+
+use crate::clean::is_zero_width;
+use crate::emoji::{VS15, VS16};
+use crate::table::{in_ranges, COMBINING_RANGES};
+
+/// Default maxMarksPerCluster: more than any real language puts on one letter
+pub const DEFAULT_MAX_MARKS_PER_CLUSTER: u32 = 8;
+
+/// Combining marks per other character above which get_text_stats sets zalgoSuspect
+pub const ZALGO_MARKS_PER_BASE: f64 = 1.0;
+
+pub(crate) fn is_combining_mark(c: char) -> bool {
+    in_ranges(COMBINING_RANGES, c)
+}
+
+/// Whether c draws nothing of its own: a combining mark, a zero-width character or a
+/// presentation selector
+fn is_invisible(c: char) -> bool {
+    is_combining_mark(c) || is_zero_width(c) || c == VS15 || c == VS16
+}
+
+/// Whether line has something other than whitespace, all of it invisible
+pub(crate) fn is_invisible_line(line: &str) -> bool {
+    line.chars().any(|c| !c.is_whitespace())
+        && line.chars().all(|c| c.is_whitespace() || is_invisible(c))
+}
+
+/// Every run of consecutive combining marks in text, as (byte offset, marks)
+pub(crate) fn mark_runs(text: &str) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut run: Option<(usize, usize)> = None;
+    for (offset, c) in text.char_indices() {
+        match (is_combining_mark(c), run.as_mut()) {
+            (true, Some((_, marks))) => *marks += 1,
+            (true, None) => run = Some((offset, 1)),
+            (false, _) => runs.extend(run.take()),
+        }
+    }
+    runs.extend(run);
+    runs
+}
+
+/// Whether mark_count combining marks among char_count characters average more than
+/// ZALGO_MARKS_PER_BASE on each of the others
+pub(crate) fn is_zalgo(mark_count: usize, char_count: usize) -> bool {
+    mark_count > 0
+        && mark_count as f64 > ZALGO_MARKS_PER_BASE * char_count.saturating_sub(mark_count) as f64
+}
+
+/// text with at most max_marks_per_cluster combining marks after each character (the later
+/// ones dropped) and every line of only whitespace and invisible characters (combining marks,
+/// zero-width characters, presentation selectors) emptied
+/// Line breaks are kept as they are, so the line count doesn't change. A limit of 0 changes
+/// nothing.
+pub fn limit_marks(text: &str, max_marks_per_cluster: u32) -> Cow<'_, str> {
+    let max = max_marks_per_cluster as usize;
+    let changes = max > 0
+        && (mark_runs(text).iter().any(|&(_, marks)| marks > max)
+            || text.split('\n').any(is_invisible_line));
+    if !changes {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            result.push('\n');
+        }
+        let (body, cr) = match line.strip_suffix('\r') {
+            Some(body) => (body, "\r"),
+            None => (line, ""),
+        };
+        if !is_invisible_line(body) {
+            let mut marks = 0;
+            for c in body.chars() {
+                marks = if is_combining_mark(c) { marks + 1 } else { 0 };
+                if marks <= max {
+                    result.push(c);
+                }
+            }
+        }
+        result.push_str(cr);
+    }
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validate_input_report, JustifyOptions, TextStats, DEFAULT_MAX_MARKS_PER_CLUSTER};
+
+    /// "Zalgo" text: every letter of "zalgo" with the given number of combining marks on it
+    fn zalgo(marks: u32) -> String {
+        "zalgo"
+            .chars()
+            .flat_map(|c| {
+                let marks = (0..marks).filter_map(|k| char::from_u32(0x300 + k % 0x70));
+                std::iter::once(c).chain(marks)
+            })
+            .collect()
+    }
+
+    fn stacked_marks_warnings(text: &str) -> usize {
+        validate_input_report(text, 20)
+            .warnings
+            .iter()
+            .filter(|warning| warning.code() == "STACKED_MARKS")
+            .count()
+    }
+
+    #[test]
+    fn zalgo_marks_are_cut_to_the_limit() {
+        let options = JustifyOptions {
+            max_chars_per_line: 20,
+            ..JustifyOptions::default()
+        };
+        let zalgo_text = zalgo(30);
+        assert_eq!(
+            limit_marks(&zalgo_text, DEFAULT_MAX_MARKS_PER_CLUSTER),
+            zalgo(8)
+        );
+        assert_eq!(options.justify(&zalgo_text), options.justify(&zalgo(8)));
+        // 0 keeps them all
+        assert_eq!(limit_marks(&zalgo_text, 0), zalgo_text);
+        let stats = TextStats::of(&zalgo_text);
+        assert!(stats.zalgo_suspect);
+        assert_eq!(stats.combining_mark_count, 150);
+        assert_eq!(stacked_marks_warnings(&zalgo_text), 1);
+    }
+
+    #[test]
+    fn a_line_of_joiners_is_cut_to_nothing() {
+        let zwj_line = format!("a\n{}\r\nb", "\u{200D}".repeat(500));
+        assert_eq!(limit_marks(&zwj_line, 8), "a\n\r\nb");
+        let options = JustifyOptions {
+            max_chars_per_line: 20,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(options.justify(&zwj_line), "a\r\n\r\nb");
+    }
+
+    #[test]
+    fn accents_are_left_alone() {
+        let cafe = "cafe\u{301} au lait";
+        assert_eq!(limit_marks(cafe, 8), cafe);
+        assert!(!TextStats::of(cafe).zalgo_suspect);
+        assert_eq!(stacked_marks_warnings(cafe), 0);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_warning_counts_the_stacked_clusters() {
+        let report = validate_input_report(&zalgo(30), 20);
+        let warning = report
+            .warnings
+            .iter()
+            .find(|warning| warning.code() == "STACKED_MARKS")
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(warning).unwrap()["details"],
+            serde_json::json!({"count": 5, "most": 30, "firstOffset": 1, "maxMarksPerCluster": 8})
+        );
+    }
+}
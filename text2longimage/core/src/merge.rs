@@ -0,0 +1,248 @@
+//! Short source lines joined up before wrapping, for chat logs
+//! A chat export has a line per message, most of them a few words, so wrapped as they are
+//! they make a long thin image that is mostly empty on the right. merge_short_lines joins
+//! consecutive lines of one block while the joined line stays within a share of the width.
+//! A block ends at a blank line and before a line that starts a message: one beginning with a
+//! timestamp ("[09:41]", "12/31/23, 9:41 PM - ") or a speaker's name and a colon ("Alice: ",
+//! "小明："), so two people's messages never end up on one line.
+
+use crate::capabilities::{expected_values, find_value};
+use crate::{
+    check_max_chars_per_line, is_cjk_char, justify_text, width_of_str, OptionValue, WebtoolsError,
+};
+
+/// Longest name, in chars, a "name: " prefix may have
+const MAX_SPEAKER_CHARS: usize = 24;
+
+/// Most words a "name: " prefix may have, so "Note that the result is: " isn't a speaker
+const MAX_SPEAKER_WORDS: usize = 3;
+
+/// What merge_short_lines puts between two lines it joins
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeSeparator {
+    /// Nothing between two CJK characters, a space anywhere else
+    Auto,
+    /// Always a space
+    Space,
+    /// " · ", which keeps the messages apart on the joined line
+    Dot,
+}
+
+impl MergeSeparator {
+    /// Parse "auto" / "space" / "dot" in any case
+    pub fn parse(separator: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| separator.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown merge separator: {} (expected {})",
+                separator,
+                expected_values::<MergeSeparator>()
+            ))
+        })
+    }
+
+    /// The separator between a line ending in `before` and one starting with `after`
+    fn between(self, before: char, after: char) -> &'static str {
+        match self {
+            MergeSeparator::Auto if is_cjk_char(before) && is_cjk_char(after) => "",
+            MergeSeparator::Auto | MergeSeparator::Space => " ",
+            MergeSeparator::Dot => " · ",
+        }
+    }
+}
+
+impl OptionValue for MergeSeparator {
+    const KIND: &'static str = "mergeSeparator";
+    const USED_BY: &'static [&'static str] = &["merge_short_lines"];
+    const ALL: &'static [Self] = &[
+        MergeSeparator::Auto,
+        MergeSeparator::Space,
+        MergeSeparator::Dot,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            MergeSeparator::Auto => "auto",
+            MergeSeparator::Space => "space",
+            MergeSeparator::Dot => "dot",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            MergeSeparator::Auto => "A space, or nothing between two CJK characters",
+            MergeSeparator::Space => "Always a space",
+            MergeSeparator::Dot => "A middle dot between spaces",
+        }
+    }
+}
+
+/// Whether line starts with a time ("9:41") or a date ("2023-01-05", "12/31/23"), after an
+/// optional opening bracket
+fn starts_with_timestamp(line: &str) -> bool {
+    let rest = line.trim_start().trim_start_matches(['[', '(']);
+    let digits = |text: &str| text.bytes().take_while(u8::is_ascii_digit).count();
+    let lead = digits(rest);
+    if !(1..=4).contains(&lead) {
+        return false;
+    }
+    let rest = &rest[lead..];
+    let Some(separator) = rest.chars().next() else {
+        return false;
+    };
+    match separator {
+        ':' => digits(&rest[1..]) == 2,
+        // Two separators, so "3.14" or "1-2 items" isn't a date
+        '-' | '/' | '.' => {
+            let month = digits(&rest[1..]);
+            let after = &rest[1 + month..];
+            (1..=2).contains(&month)
+                && after.starts_with(separator)
+                && (1..=4).contains(&digits(&after[1..]))
+        }
+        _ => false,
+    }
+}
+
+/// Whether line starts with a speaker's name and a colon: a short name of a few words, then
+/// ':' and whitespace (or nothing), or the fullwidth '：'
+fn starts_with_speaker(line: &str) -> bool {
+    let body = line.trim_start();
+    let Some((index, colon)) = body.char_indices().find(|&(_, c)| c == ':' || c == '：') else {
+        return false;
+    };
+    let name = &body[..index];
+    let after = body[index + colon.len_utf8()..].chars().next();
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_SPEAKER_CHARS
+        && name.split_whitespace().count() <= MAX_SPEAKER_WORDS
+        && (colon == '：' || after.is_none_or(char::is_whitespace))
+}
+
+/// Whether line starts a message, and so a block of its own
+fn starts_block(line: &str) -> bool {
+    starts_with_timestamp(line) || starts_with_speaker(line)
+}
+
+/// text with consecutive short lines of each block joined, then wrapped like justify_text
+/// Lines are joined while the joined line is at most max_chars_per_line × min_fill_ratio
+/// columns wide, with separator between them; the whitespace at the join is dropped. A
+/// min_fill_ratio of 0 joins nothing, giving justify_text's output. Blank lines and lines
+/// starting a message (see the module docs) are never joined onto the line before.
+pub fn merge_short_lines(
+    text: &str,
+    max_chars_per_line: u32,
+    min_fill_ratio: f64,
+    separator: MergeSeparator,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    if !(0.0..=1.0).contains(&min_fill_ratio) {
+        return Err(WebtoolsError::invalid_argument(format!(
+            "minFillRatio must be 0 to 1, got {}",
+            min_fill_ratio
+        )));
+    }
+    let limit = (max_chars_per_line as f64 * min_fill_ratio).floor() as u64;
+
+    let mut merged = String::with_capacity(text.len());
+    // The line being joined onto, None at the start of a block
+    let mut current: Option<String> = None;
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() || starts_block(line) {
+            if let Some(done) = current.take() {
+                merged.push_str(&done);
+                merged.push('\n');
+            }
+            if line.trim().is_empty() {
+                merged.push_str(line);
+                merged.push('\n');
+            } else {
+                current = Some(line.to_string());
+            }
+            continue;
+        }
+
+        let Some(open) = current.as_mut() else {
+            current = Some(line.to_string());
+            continue;
+        };
+        let left = open.trim_end();
+        let right = line.trim_start();
+        let gap = left
+            .chars()
+            .next_back()
+            .zip(right.chars().next())
+            .map_or(" ", |(before, after)| separator.between(before, after));
+        if width_of_str(left) + width_of_str(gap) + width_of_str(right) <= limit {
+            open.truncate(left.len());
+            open.push_str(gap);
+            open.push_str(right);
+        } else {
+            merged.push_str(open);
+            merged.push('\n');
+            *open = line.to_string();
+        }
+    }
+    if let Some(done) = current {
+        merged.push_str(&done);
+    } else {
+        // The last line was blank and has a '\n' too many
+        merged.pop();
+    }
+
+    Ok(justify_text(&merged, max_chars_per_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chat export with timestamps: messages over several lines, a CJK one, and a speaker
+    /// without a timestamp after a blank line
+    const CHAT_EXPORT: &str =
+        "[09:41] Alice: are you coming\ntonight?\n[09:42] Bob: yes\nsee you\n\
+        at eight\n[09:43] 小明：好的\n我也来\n\nAlice: great";
+
+    const POEM: &str = "The woods are lovely, dark and deep,\nBut I have promises to keep,\n\
+        And miles to go before I sleep,\nAnd miles to go before I sleep.";
+
+    fn merged(text: &str, width: u32, ratio: f64, separator: MergeSeparator) -> String {
+        merge_short_lines(text, width, ratio, separator).unwrap()
+    }
+
+    #[test]
+    fn messages_join_but_not_across_timestamps() {
+        assert_eq!(
+            merged(CHAT_EXPORT, 40, 1.0, MergeSeparator::Auto),
+            "[09:41] Alice: are you coming tonight?\r\n[09:42] Bob: yes see you at eight\r\n\
+             [09:43] 小明：好的我也来\r\n\r\nAlice: great"
+        );
+        assert!(merged(CHAT_EXPORT, 40, 1.0, MergeSeparator::Dot)
+            .contains("\r\n[09:42] Bob: yes · see you · at eight\r\n"));
+        // Bob's three lines are 33 columns joined, over 40 × 0.8
+        assert!(merged(CHAT_EXPORT, 40, 0.8, MergeSeparator::Space)
+            .contains("\r\n[09:42] Bob: yes see you\r\nat eight\r\n"));
+    }
+
+    #[test]
+    fn a_zero_fill_ratio_joins_nothing() {
+        assert_eq!(
+            merged(POEM, 40, 0.0, MergeSeparator::Auto),
+            justify_text(POEM, 40)
+        );
+        assert_ne!(
+            merged(POEM, 80, 1.0, MergeSeparator::Auto),
+            justify_text(POEM, 80)
+        );
+    }
+
+    #[test]
+    fn fill_ratios_outside_0_to_1_are_rejected() {
+        for ratio in [-0.1, 1.5, f64::NAN] {
+            assert!(matches!(
+                merge_short_lines(POEM, 40, ratio, MergeSeparator::Auto),
+                Err(WebtoolsError::InvalidArgument { .. })
+            ));
+        }
+    }
+}
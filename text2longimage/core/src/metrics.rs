@@ -0,0 +1,1567 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+#[cfg(feature = "json")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+
+use crate::bidi::{wrap_chars_rtl_with, wrap_words_rtl_with};
+use crate::code_spans::{code_spans, has_span, span_words, wrap_span_chars_with};
+use crate::joiners::{joined_spans, joiner_aware_width, merge_spans};
+use crate::links::wrap_chars_splitting_links;
+use crate::lists::{hanging_item, HangingIndent};
+use crate::ruby::{ruby_spans, wrap_ruby_with};
+use crate::table::astral_aware_width;
+#[cfg(feature = "json")]
+use crate::WebtoolsError;
+use crate::{has_rtl, is_cjk, width_of, CharClass, ListMarkerSpacing, RubySyntax};
+
+/// Tolerance for comparing accumulated fractional widths against the line limit
+pub(crate) const WIDTH_EPSILON: f64 = 1e-9;
+
+/// Width source for the fractional wrappers
+pub trait WidthModel {
+    /// Advance of a single character, in the same unit as the line limit
+    fn char_width(&self, c: char) -> f64;
+
+    /// Advance of a whole word; defaults to the sum of its characters
+    fn word_width(&self, word: &str) -> f64 {
+        word.chars().map(|c| self.char_width(c)).sum()
+    }
+
+    /// Advance shared by every ASCII character, if the model has one
+    /// Lets the wrappers place whole ASCII runs at once instead of char by char
+    fn ascii_width(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Whether a line of accumulated width still fits the limit
+/// The tolerance scales with the limit so long lines of fractional widths don't wrap early
+pub(crate) fn fits_width(width: f64, max_width: f64) -> bool {
+    width <= max_width + WIDTH_EPSILON * max_width.abs().max(1.0)
+}
+
+/// Default fractional model: ASCII = 0.5 em, CJK = 1.0 em
+pub(crate) struct FractionalWidths;
+
+impl WidthModel for FractionalWidths {
+    fn char_width(&self, c: char) -> f64 {
+        CharClass::of(c).width_em()
+    }
+
+    fn word_width(&self, word: &str) -> f64 {
+        if word.is_ascii() {
+            word.len() as f64 * 0.5
+        } else {
+            word.chars().map(|c| self.char_width(c)).sum()
+        }
+    }
+
+    fn ascii_width(&self) -> Option<f64> {
+        Some(0.5)
+    }
+}
+
+/// Model behind the integer column API, expressed in em (columns / 2)
+/// Words are measured by UTF-8 byte length, as justify_text_english always has
+pub(crate) struct ColumnWidths;
+
+impl WidthModel for ColumnWidths {
+    fn char_width(&self, c: char) -> f64 {
+        CharClass::of(c).width_em()
+    }
+
+    fn word_width(&self, word: &str) -> f64 {
+        word.len() as f64 / 2.0
+    }
+
+    fn ascii_width(&self) -> Option<f64> {
+        Some(0.5)
+    }
+}
+
+/// ColumnWidths with the astral-aware widths of algorithm V3 (see NARROW_ASTRAL_RANGES)
+/// Words are still measured by byte length, except that each narrow astral letter counts as
+/// the one column it takes instead of its four bytes
+pub(crate) struct AstralColumnWidths;
+
+impl WidthModel for AstralColumnWidths {
+    fn char_width(&self, c: char) -> f64 {
+        astral_aware_width(c) as f64 / 2.0
+    }
+
+    fn word_width(&self, word: &str) -> f64 {
+        let narrow_astral = word
+            .chars()
+            .filter(|&c| c as u32 > 0xFFFF && astral_aware_width(c) == 1)
+            .count();
+        (word.len() - 3 * narrow_astral) as f64 / 2.0
+    }
+
+    fn ascii_width(&self) -> Option<f64> {
+        Some(0.5)
+    }
+}
+
+/// Where the wrappers write their output
+/// String gets the plain "\r\n"-separated text; other sinks (e.g. the HTML one behind
+/// justify_text_html) transform it on the way through, still in a single pass
+pub(crate) trait WrapSink {
+    fn push_text(&mut self, text: &str);
+
+    fn push_char(&mut self, c: char) {
+        self.push_text(c.encode_utf8(&mut [0; 4]));
+    }
+
+    fn line_break(&mut self);
+
+    /// A break the wrapper chose inside a word (after a dash), where no space was replaced
+    fn word_break(&mut self) {
+        self.line_break();
+    }
+
+    /// A break the text itself asks for in the middle of a wrapped line (a lone '\r'),
+    /// as opposed to one the wrapper chose
+    fn source_line_break(&mut self) {
+        self.line_break();
+    }
+
+    /// Whether the sink takes no more output (see CappedOutput); wrapping stops at the next
+    /// source line
+    fn full(&self) -> bool {
+        false
+    }
+}
+
+impl WrapSink for String {
+    fn push_text(&mut self, text: &str) {
+        self.push_str(text);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.push(c);
+    }
+
+    fn line_break(&mut self) {
+        self.push_str("\r\n");
+    }
+}
+
+/// What the wrappers do with whitespace left at the end of a wrapped line
+/// Trailing spaces are invisible but still count toward measured widths and make output
+/// comparisons brittle, so every wrapper trims them unless asked not to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TrailingWhitespace {
+    #[default]
+    Trim,
+    /// Keep whitespace where the break left it (e.g. for a white-space: pre-wrap container)
+    Keep,
+    /// Trim, except that a source line ending in exactly two spaces (a Markdown hard break)
+    /// keeps them on its last wrapped line
+    KeepHardBreaks,
+}
+
+/// Holds whitespace back until something visible follows it on the same line, so nothing
+/// the wrappers place at a line's end reaches the inner sink; widths are unaffected
+/// The whitespace is held in pending, a buffer the caller keeps for every line of a text so
+/// lines don't each allocate their own.
+pub(crate) struct TrimTrailing<'a, S: WrapSink> {
+    inner: &'a mut S,
+    pending: &'a mut String,
+}
+
+impl<'a, S: WrapSink> TrimTrailing<'a, S> {
+    pub(crate) fn new(inner: &'a mut S, pending: &'a mut String) -> Self {
+        pending.clear();
+        TrimTrailing { inner, pending }
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.inner.push_text(self.pending);
+            self.pending.clear();
+        }
+    }
+}
+
+impl<S: WrapSink> WrapSink for TrimTrailing<'_, S> {
+    fn push_text(&mut self, text: &str) {
+        let visible = text.trim_end();
+        if !visible.is_empty() {
+            self.flush();
+            self.inner.push_text(visible);
+        }
+        self.pending.push_str(&text[visible.len()..]);
+    }
+
+    fn push_char(&mut self, c: char) {
+        if c.is_whitespace() {
+            self.pending.push(c);
+        } else {
+            self.flush();
+            self.inner.push_char(c);
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.pending.clear();
+        self.inner.line_break();
+    }
+
+    fn source_line_break(&mut self) {
+        self.pending.clear();
+        self.inner.source_line_break();
+    }
+}
+
+/// Writes a marker at the end of every line the wrapper broke, so remove_soft_breaks can
+/// rejoin them; breaks from the text itself go through unmarked
+/// A word break keeps the space it replaced in front of the marker, and whitespace the
+/// character wrapper left at the break stays there too (TrimTrailing only trims what ends a
+/// line, and the marker ends it now), so rejoining is plain concatenation.
+struct MarkSoftBreaks<'a, S: WrapSink> {
+    inner: &'a mut S,
+    marker: &'a str,
+    /// The wrapper replaces a space with each break (word wrapping)
+    replaces_space: bool,
+}
+
+impl<S: WrapSink> WrapSink for MarkSoftBreaks<'_, S> {
+    fn push_text(&mut self, text: &str) {
+        self.inner.push_text(text);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.inner.push_char(c);
+    }
+
+    fn line_break(&mut self) {
+        if self.replaces_space {
+            self.inner.push_char(' ');
+        }
+        self.inner.push_text(self.marker);
+        self.inner.line_break();
+    }
+
+    fn word_break(&mut self) {
+        self.inner.push_text(self.marker);
+        self.inner.line_break();
+    }
+
+    fn source_line_break(&mut self) {
+        self.inner.source_line_break();
+    }
+}
+
+/// Undo mark_soft_breaks: join every line that ends in marker to the next one
+/// justify_text's output wrapped with a marker comes back as the text wrapped at unlimited
+/// width; breaks inside CJK runs rejoin with no space, word breaks with the one they replaced.
+pub fn remove_soft_breaks(text: &str, marker: &str) -> String {
+    if marker.is_empty() {
+        return text.to_string();
+    }
+    text.replace(&format!("{}\r\n", marker), "")
+        .replace(&format!("{}\n", marker), "")
+}
+
+/// Whether a source line ends in a Markdown hard break: exactly two spaces after its text
+pub(crate) fn is_hard_break(line: &str) -> bool {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    line.ends_with("  ") && !line.ends_with("   ") && !line.trim().is_empty()
+}
+
+/// How wrap_text_into treats line ends and caller-supplied break markers
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LineBreaking<'a> {
+    pub(crate) trailing: TrailingWhitespace,
+    /// Forces a line break wherever it appears; removed from the output
+    pub(crate) hard_break: Option<&'a str>,
+    /// Zero-width break opportunity, like U+200B; removed from the output
+    pub(crate) soft_break: Option<&'a str>,
+    /// Ruby notation kept on one line and measured by its base
+    pub(crate) ruby: RubySyntax,
+    /// Written at the end of every line the wrapper broke (see MarkSoftBreaks)
+    pub(crate) mark_soft_breaks: Option<&'a str>,
+    /// Written around breaks forced inside a token in character-wrapped lines
+    pub(crate) break_markers: Option<BreakMarkers<'a>>,
+    /// Set from algorithm V2 on: character-wrapped lines break URLs after separators and move
+    /// breaks that cut a token back to punctuation up to this many columns before the limit
+    pub(crate) punctuation_window: Option<u32>,
+    /// Set from algorithm V3 on: astral letters in NARROW_ASTRAL_RANGES are one column wide
+    pub(crate) narrow_astral: bool,
+    /// Words on word-wrapped lines wider than this many columns are cut into lines by
+    /// character instead of overflowing
+    pub(crate) max_unbreakable_run: Option<u32>,
+    /// Word-wrapped lines keep the whitespace between two words they put on one line (an
+    /// ideographic space, a tab, several spaces) instead of writing one space
+    pub(crate) keep_separators: bool,
+    /// Set from algorithm V4 on: characters a word may break after (see breaks_after), and
+    /// which never start a character-wrapped line
+    pub(crate) break_after: Option<&'a str>,
+    /// List-aware wrapping: a line starting with a list marker hangs its continuation lines
+    /// under the text after the marker (see hanging_item), spaced as asked
+    pub(crate) list_markers: Option<ListMarkerSpacing>,
+    /// Set from algorithm V5 on with markdown: inline code spans are unbreakable tokens (see
+    /// code_spans.rs)
+    pub(crate) code_spans: bool,
+    /// Set from algorithm V6 on: a BOM is zero columns wide and no line breaks next to it
+    /// (see joiners.rs)
+    pub(crate) bom_joiner: bool,
+    /// Set for algorithm V1 only: number tokens (see number_token_len) and right-to-left runs
+    /// (see bidi.rs) break like any other characters instead of moving to the next line whole
+    pub(crate) split_runs: bool,
+    /// Bytes of output past which nothing more is written and wrapping stops (see
+    /// expansion.rs)
+    pub(crate) output_ceiling: Option<usize>,
+}
+
+/// Text written at the end and the start of a line where a character-wrapped line had to cut
+/// through a run of printable ASCII (a URL, a base64 blob); either may be empty
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BreakMarkers<'a> {
+    pub(crate) end: &'a str,
+    pub(crate) start: &'a str,
+}
+
+impl From<TrailingWhitespace> for LineBreaking<'_> {
+    fn from(trailing: TrailingWhitespace) -> Self {
+        LineBreaking {
+            trailing,
+            ..LineBreaking::default()
+        }
+    }
+}
+
+/// Wrap text line by line with f64 width accumulation
+/// Mirrors justify_text: blank lines are kept, CJK lines break per character, other lines per word
+pub fn wrap_text_with<M: WidthModel>(text: &str, max_width: f64, model: &M) -> String {
+    let mut result = String::with_capacity(text.len() + text.len() / 20);
+    wrap_text_into(
+        text,
+        max_width,
+        model,
+        &TrailingWhitespace::Trim.into(),
+        &mut result,
+    );
+    result
+}
+
+pub(crate) fn wrap_text_into<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+) {
+    let mut pending = String::new();
+    for (index, line) in text.split('\n').enumerate() {
+        if out.full() {
+            return;
+        }
+        if index > 0 {
+            out.line_break();
+        }
+
+        let hanging = breaking
+            .list_markers
+            .and_then(|spacing| hanging_item(line, spacing, max_width, model));
+        match hanging {
+            Some(item) => {
+                out.push_text(&item.prefix);
+                let indent_width = item.indent.len() as f64 * model.char_width(' ');
+                let mut hanging = HangingIndent {
+                    inner: out,
+                    indent: &item.indent,
+                };
+                wrap_source_line_into(
+                    item.body,
+                    max_width - indent_width,
+                    model,
+                    breaking,
+                    &mut hanging,
+                    &mut pending,
+                );
+            }
+            None => wrap_source_line_into(line, max_width, model, breaking, out, &mut pending),
+        }
+        if breaking.trailing == TrailingWhitespace::KeepHardBreaks && is_hard_break(line) {
+            out.push_text("  ");
+        }
+    }
+}
+
+/// One source line, split at hard break markers
+fn wrap_source_line_into<M: WidthModel, S: WrapSink>(
+    line: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+    pending: &mut String,
+) {
+    match breaking.hard_break {
+        // Each marker ends a line, so two in a row leave an empty one
+        Some(marker) => {
+            for (segment_index, segment) in line.split(marker).enumerate() {
+                if segment_index > 0 {
+                    out.line_break();
+                }
+                wrap_segment_into(segment, max_width, model, breaking, out, pending);
+            }
+        }
+        None => wrap_segment_into(line, max_width, model, breaking, out, pending),
+    }
+}
+
+/// pending is TrimTrailing's buffer, kept by the caller across segments
+fn wrap_segment_into<M: WidthModel, S: WrapSink>(
+    segment: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+    pending: &mut String,
+) {
+    let trimmed = segment.trim();
+    if trimmed.is_empty() {
+        return;
+    }
+
+    if breaking.trailing == TrailingWhitespace::Keep {
+        wrap_marked_into(trimmed, max_width, model, breaking, out);
+    } else {
+        let mut trimming = TrimTrailing::new(out, pending);
+        wrap_marked_into(trimmed, max_width, model, breaking, &mut trimming);
+    }
+}
+
+fn wrap_marked_into<M: WidthModel, S: WrapSink>(
+    line: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+) {
+    match breaking.mark_soft_breaks {
+        Some(marker) => {
+            let mut marking = MarkSoftBreaks {
+                inner: out,
+                marker,
+                replaces_space: wraps_words(line, breaking),
+            };
+            wrap_line_capped_into(line, max_width, model, breaking, &mut marking);
+        }
+        None => wrap_line_capped_into(line, max_width, model, breaking, out),
+    }
+}
+
+/// Whether wrap_line_into places this line word by word (mirrors its dispatch)
+/// Pieces of a word split at a soft break marker count as words here, so they rejoin with
+/// a space.
+fn wraps_words(line: &str, breaking: &LineBreaking) -> bool {
+    if breaking.ruby != RubySyntax::None && line.contains('《') {
+        let without_markers = match breaking.soft_break {
+            Some(marker) => Cow::Owned(line.replace(marker, "")),
+            None => Cow::Borrowed(line),
+        };
+        if !ruby_spans(&without_markers, breaking.ruby).is_empty() {
+            return false;
+        }
+    }
+    match breaking.soft_break.filter(|marker| line.contains(marker)) {
+        Some(marker) => !is_cjk(line.replace(marker, "").trim()),
+        None => !is_cjk(line),
+    }
+}
+
+/// wrap_line_into, except that on a word-wrapped line a word wider than
+/// breaking.max_unbreakable_run is cut into lines of max_width by character
+/// The cut word starts and ends lines of its own; the text either side of it wraps as it would
+/// have, since a word that wide goes on a new line and pushes the next one off it anyway.
+fn wrap_line_capped_into<M: WidthModel, S: WrapSink>(
+    line: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+) {
+    let Some(max_run) = breaking.max_unbreakable_run else {
+        return wrap_line_into(line, max_width, model, breaking, out);
+    };
+    let max_run_width = max_run as f64 / 2.0;
+    let is_overlong = |word: &&str| model.word_width(word) > max_run_width + WIDTH_EPSILON;
+    if !line.split_whitespace().any(|word| is_overlong(&word)) || !wraps_words(line, breaking) {
+        return wrap_line_into(line, max_width, model, breaking, out);
+    }
+
+    let mut rest = line;
+    while let Some(word) = rest.split_whitespace().find(is_overlong) {
+        let start = word.as_ptr() as usize - rest.as_ptr() as usize;
+        let end = start + word.len();
+        let before = rest[..start].trim();
+        if !before.is_empty() {
+            wrap_line_into(before, max_width, model, breaking, out);
+            out.line_break();
+        }
+        let word = match breaking.soft_break {
+            Some(marker) => Cow::Owned(word.replace(marker, "")),
+            None => Cow::Borrowed(word),
+        };
+        wrap_chars_with(&word, max_width, model, out);
+        rest = &rest[end..];
+        if !rest.trim().is_empty() {
+            out.line_break();
+        }
+    }
+    let rest = rest.trim();
+    if !rest.is_empty() {
+        wrap_line_into(rest, max_width, model, breaking, out);
+    }
+}
+
+/// The spans of line no break may fall inside, as breaking asks: its code spans and the
+/// characters its BOMs join, ascending
+pub(crate) fn unbreakable_spans(line: &str, breaking: &LineBreaking) -> Vec<Range<usize>> {
+    let code = if breaking.code_spans {
+        code_spans(line)
+    } else {
+        Vec::new()
+    };
+    if breaking.bom_joiner {
+        merge_spans(code, joined_spans(line))
+    } else {
+        code
+    }
+}
+
+fn wrap_line_into<M: WidthModel, S: WrapSink>(
+    line: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+) {
+    if breaking.ruby != RubySyntax::None && line.contains('《') {
+        // Markers only add break opportunities, and every character is one already
+        let without_markers = match breaking.soft_break {
+            Some(marker) => Cow::Owned(line.replace(marker, "")),
+            None => Cow::Borrowed(line),
+        };
+        let spans = ruby_spans(&without_markers, breaking.ruby);
+        if !spans.is_empty() {
+            wrap_ruby_with(&without_markers, &spans, max_width, model, out);
+            return;
+        }
+    }
+
+    if let Some(marker) = breaking.soft_break.filter(|marker| line.contains(marker)) {
+        // Character wrapping can break anywhere already, so the markers only need removing
+        let without_markers = line.replace(marker, "");
+        let without_markers = without_markers.trim();
+        if is_cjk(without_markers) {
+            wrap_chars_breaking(without_markers, max_width, model, breaking, out);
+        } else {
+            wrap_word_pieces_with(
+                separated_words(line),
+                |word| word.split(marker).filter(|piece| !piece.is_empty()),
+                S::line_break,
+                max_width,
+                model,
+                breaking.keep_separators,
+                out,
+            );
+        }
+    } else if !breaking.split_runs && has_rtl(line) {
+        if is_cjk(line) {
+            wrap_chars_rtl_with(line, max_width, model, out);
+        } else {
+            wrap_words_rtl_with(line, max_width, model, breaking.keep_separators, out);
+        }
+    } else if let Some(spans) = Some(unbreakable_spans(line, breaking)).filter(|s| !s.is_empty()) {
+        if is_cjk(line) {
+            wrap_span_chars_with(line, &spans, max_width, model, out);
+        } else {
+            let set = breaking.break_after.unwrap_or_default();
+            wrap_word_pieces_with(
+                span_words(line, &spans),
+                |word| {
+                    if set.is_empty() || has_span(line, word, &spans) {
+                        vec![word]
+                    } else {
+                        break_after_pieces(word, set)
+                    }
+                },
+                S::word_break,
+                max_width,
+                model,
+                breaking.keep_separators,
+                out,
+            );
+        }
+    } else if is_cjk(line) {
+        wrap_chars_breaking(line, max_width, model, breaking, out);
+    } else if let Some(set) = breaking
+        .break_after
+        .filter(|set| line.contains(|c| set.contains(c)))
+    {
+        wrap_word_pieces_with(
+            separated_words(line),
+            |word| break_after_pieces(word, set),
+            S::word_break,
+            max_width,
+            model,
+            breaking.keep_separators,
+            out,
+        );
+    } else {
+        wrap_words_with(line, max_width, model, breaking.keep_separators, out);
+    }
+}
+
+/// Character wrapping with breaking's handling of breaks forced inside a token
+/// Those breaks need to look back over the line, so they go through the column-based wrapper
+/// in links.rs; markers and the punctuation window are only ever set by JustifyOptions, which
+/// measures in columns.
+fn wrap_chars_breaking<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    breaking: &LineBreaking,
+    out: &mut S,
+) {
+    if breaking.break_markers.is_some()
+        || breaking.punctuation_window.is_some()
+        || breaking.break_after.is_some()
+    {
+        let width_of = if breaking.bom_joiner {
+            joiner_aware_width
+        } else if breaking.narrow_astral {
+            astral_aware_width
+        } else {
+            width_of
+        };
+        wrap_chars_splitting_links(
+            text,
+            (max_width * 2.0) as u32,
+            breaking.break_markers.as_ref(),
+            breaking.punctuation_window.unwrap_or(0),
+            breaking.break_after.unwrap_or_default(),
+            width_of,
+            out,
+        );
+    } else if breaking.split_runs {
+        wrap_chars_splitting_numbers(text, max_width, model, out);
+    } else {
+        wrap_chars_with(text, max_width, model, out);
+    }
+}
+
+/// wrap_chars_with as algorithm V1 has it, breaking number tokens like any other characters
+fn wrap_chars_splitting_numbers<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    out: &mut S,
+) {
+    let mut current_line_width = 0.0;
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(ascii_width) = model.ascii_width() {
+            let run_len = rest
+                .bytes()
+                .take_while(|&byte| byte.is_ascii() && byte != b'\r' && byte != b'\n')
+                .count();
+            if run_len > 0 {
+                let (run, tail) = rest.split_at(run_len);
+                place_ascii_run(run, ascii_width, max_width, &mut current_line_width, out);
+                rest = tail;
+                continue;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let Some(c) = chars.next() else { break };
+        rest = chars.as_str();
+
+        if c == '\r' || c == '\n' {
+            out.source_line_break();
+            current_line_width = 0.0;
+            continue;
+        }
+
+        place_char(c, max_width, model, &mut current_line_width, out);
+    }
+}
+
+pub(crate) fn wrap_chars_with<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    out: &mut S,
+) {
+    place_chars_with(text, max_width, model, &mut 0.0, out);
+}
+
+/// The character wrapping loop, continuing a line that is already current_line_width wide
+/// Number and date tokens (see number_token_len) move to the next line whole when they don't
+/// fit, unless they are wider than a whole line.
+pub(crate) fn place_chars_with<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    current_line_width: &mut f64,
+    out: &mut S,
+) {
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let token_len = number_token_len(rest.as_bytes());
+        if token_len > 1 {
+            let (token, tail) = rest.split_at(token_len);
+            let token_width: f64 = token.chars().map(|c| model.char_width(c)).sum();
+            if fits_width(*current_line_width + token_width, max_width) {
+                out.push_text(token);
+                *current_line_width += token_width;
+            } else if fits_width(token_width, max_width) {
+                out.line_break();
+                out.push_text(token);
+                *current_line_width = token_width;
+            } else if let Some(ascii_width) = model.ascii_width() {
+                place_ascii_run(token, ascii_width, max_width, current_line_width, out);
+            } else {
+                token
+                    .chars()
+                    .for_each(|c| place_char(c, max_width, model, current_line_width, out));
+            }
+            rest = tail;
+            continue;
+        }
+
+        if let Some(ascii_width) = model.ascii_width() {
+            let run_len = ascii_run_len(rest.as_bytes());
+            if run_len > 0 {
+                let (run, tail) = rest.split_at(run_len);
+                place_ascii_run(run, ascii_width, max_width, current_line_width, out);
+                rest = tail;
+                continue;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let Some(c) = chars.next() else { break };
+        rest = chars.as_str();
+
+        if c == '\r' || c == '\n' {
+            out.source_line_break();
+            *current_line_width = 0.0;
+            continue;
+        }
+
+        place_char(c, max_width, model, current_line_width, out);
+    }
+}
+
+fn place_char<M: WidthModel, S: WrapSink>(
+    c: char,
+    max_width: f64,
+    model: &M,
+    current_line_width: &mut f64,
+    out: &mut S,
+) {
+    let char_width = model.char_width(c);
+    if !fits_width(*current_line_width + char_width, max_width) {
+        out.line_break();
+        *current_line_width = char_width;
+    } else {
+        *current_line_width += char_width;
+    }
+
+    out.push_char(c);
+}
+
+/// Length of the number or date token at the start of units, 0 when it doesn't start with a
+/// digit: ASCII digits joined by '.', ',', ':', '-' or an exponent ("1.5e-7", "2024-03-15",
+/// "12:30:45", "1,234,567.89"). Works on UTF-8 bytes, UTF-16 units or chars alike.
+pub(crate) fn number_token_len<T: Copy + Into<u32>>(units: &[T]) -> usize {
+    let is_digit = |index: usize| {
+        units
+            .get(index)
+            .is_some_and(|&unit| (b'0' as u32..=b'9' as u32).contains(&unit.into()))
+    };
+    let is = |index: usize, set: &[u8]| {
+        units
+            .get(index)
+            .is_some_and(|&unit| set.iter().any(|&b| b as u32 == unit.into()))
+    };
+    let digits_from = |start: usize| (start..).take_while(|&index| is_digit(index)).count();
+
+    let mut len = digits_from(0);
+    if len == 0 {
+        return 0;
+    }
+    loop {
+        let separator = if is(len, b".,:-") {
+            1
+        } else if is(len, b"eE") && is(len + 1, b"+-") {
+            2
+        } else if is(len, b"eE") {
+            1
+        } else {
+            break;
+        };
+        let digits = digits_from(len + separator);
+        if digits == 0 {
+            break;
+        }
+        len += separator + digits;
+    }
+    len
+}
+
+/// Char ranges of the number and date tokens in chars (only those longer than one digit)
+pub(crate) fn number_tokens(chars: &[char]) -> Vec<Range<usize>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let len = number_token_len(&chars[i..]);
+        if len > 1 {
+            tokens.push(i..i + len);
+        }
+        i += len.max(1);
+    }
+    tokens
+}
+
+/// Leading run of ASCII bytes other than line breaks, up to the next number token
+fn ascii_run_len(bytes: &[u8]) -> usize {
+    // Byte by byte: measuring all the ASCII ahead each time made long tokens quadratic
+    let mut index = 0;
+    while index < bytes.len() && bytes[index].is_ascii() {
+        match bytes[index] {
+            b'\r' | b'\n' => break,
+            b'0'..=b'9' => {
+                // Number tokens are ASCII throughout
+                let token_len = number_token_len(&bytes[index..]);
+                if token_len > 1 {
+                    break;
+                }
+                index += token_len;
+            }
+            _ => index += 1,
+        }
+    }
+    index
+}
+
+/// Place a run of equally wide ASCII characters, breaking exactly where the per-character
+/// loop in wrap_chars_with would. Widths are multiples of 0.5, so `width * count` matches the
+/// loop's repeated addition bit for bit.
+fn place_ascii_run<S: WrapSink>(
+    run: &str,
+    char_width: f64,
+    max_width: f64,
+    current_line_width: &mut f64,
+    out: &mut S,
+) {
+    let mut rest = run;
+    while !rest.is_empty() {
+        let fit = ascii_fit_count(*current_line_width, char_width, max_width, rest.len());
+        if fit == 0 {
+            // Not even one more character fits: break and start the line with it
+            out.line_break();
+            out.push_text(&rest[..1]);
+            *current_line_width = char_width;
+            rest = &rest[1..];
+        } else {
+            out.push_text(&rest[..fit]);
+            *current_line_width += char_width * fit as f64;
+            rest = &rest[fit..];
+        }
+    }
+}
+
+/// Largest k <= available with fits_width(current + char_width * k)
+fn ascii_fit_count(current: f64, char_width: f64, max_width: f64, available: usize) -> usize {
+    let fits = |k: usize| fits_width(current + char_width * k as f64, max_width);
+
+    // Start from the estimate and correct it; fits is monotonic in k
+    let estimate = ((max_width - current) / char_width).floor();
+    let mut k = if estimate > 0.0 {
+        (estimate as usize).min(available)
+    } else {
+        0
+    };
+    while k < available && fits(k + 1) {
+        k += 1;
+    }
+    while k > 0 && !fits(k) {
+        k -= 1;
+    }
+    k
+}
+
+/// The words of a line, each with the whitespace before it ("" before the first)
+pub(crate) fn separated_words(line: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut end = 0;
+    line.split_whitespace().map(move |word| {
+        let start = word.as_ptr() as usize - line.as_ptr() as usize;
+        let separator = &line[end..start];
+        end = start + word.len();
+        (separator, word)
+    })
+}
+
+/// What a word wrapper writes between two words it puts on one line, and its width: the
+/// whitespace that was between them with keep_separators, one space otherwise
+/// Kept whitespace is measured by character, so an ideographic space takes two columns.
+pub(crate) struct Separators {
+    keep: bool,
+    space_width: f64,
+}
+
+impl Separators {
+    pub(crate) fn new<M: WidthModel>(keep: bool, model: &M) -> Self {
+        Separators {
+            keep,
+            space_width: model.char_width(' '),
+        }
+    }
+
+    pub(crate) fn width<M: WidthModel>(&self, whitespace: &str, model: &M) -> f64 {
+        if self.keep {
+            whitespace.chars().map(|c| model.char_width(c)).sum()
+        } else {
+            self.space_width
+        }
+    }
+
+    pub(crate) fn push<S: WrapSink>(&self, whitespace: &str, out: &mut S) {
+        if self.keep {
+            out.push_text(whitespace);
+        } else {
+            out.push_char(' ');
+        }
+    }
+}
+
+pub(crate) fn wrap_words_with<M: WidthModel, S: WrapSink>(
+    text: &str,
+    max_width: f64,
+    model: &M,
+    keep_separators: bool,
+    out: &mut S,
+) {
+    let separators = Separators::new(keep_separators, model);
+    let mut current_line_width = 0.0f64;
+    let mut line_is_empty = true;
+
+    for (separator, word) in separated_words(text) {
+        let word_width = model.word_width(word);
+        let separator_width = separators.width(separator, model);
+
+        if line_is_empty {
+            out.push_text(word);
+            current_line_width = word_width;
+            line_is_empty = false;
+        } else if fits_width(current_line_width + separator_width + word_width, max_width) {
+            separators.push(separator, out);
+            out.push_text(word);
+            current_line_width += separator_width + word_width;
+        } else {
+            out.line_break();
+            out.push_text(word);
+            current_line_width = word_width;
+        }
+    }
+}
+
+/// wrap_words_with over words (separated_words of a line, or span_words) where pieces splits
+/// each word into pieces that may go on separate lines
+/// Pieces of one word are joined without a space when they share a line, and a break between
+/// two of them goes through word_break; a word in one piece is placed exactly as
+/// wrap_words_with places it.
+fn wrap_word_pieces_with<'t, M, S, W, P, I>(
+    words: W,
+    pieces: P,
+    word_break: fn(&mut S),
+    max_width: f64,
+    model: &M,
+    keep_separators: bool,
+    out: &mut S,
+) where
+    M: WidthModel,
+    S: WrapSink,
+    W: IntoIterator<Item = (&'t str, &'t str)>,
+    P: Fn(&'t str) -> I,
+    I: IntoIterator<Item = &'t str>,
+{
+    let separators = Separators::new(keep_separators, model);
+    let mut current_line_width = 0.0f64;
+    let mut line_is_empty = true;
+
+    for (separator, word) in words {
+        let separator_width = separators.width(separator, model);
+        for (piece_index, piece) in pieces(word).into_iter().enumerate() {
+            let piece_width = model.word_width(piece);
+            let gap_width = if piece_index == 0 {
+                separator_width
+            } else {
+                0.0
+            };
+
+            if line_is_empty {
+                out.push_text(piece);
+                current_line_width = piece_width;
+                line_is_empty = false;
+            } else if fits_width(current_line_width + gap_width + piece_width, max_width) {
+                if piece_index == 0 {
+                    separators.push(separator, out);
+                }
+                out.push_text(piece);
+                current_line_width += gap_width + piece_width;
+            } else {
+                if piece_index == 0 {
+                    out.line_break();
+                } else {
+                    word_break(out);
+                }
+                out.push_text(piece);
+                current_line_width = piece_width;
+            }
+        }
+    }
+}
+
+/// Whether a line may break after chars[k]: it is in set, with a character outside set on
+/// both sides, and a hyphen-minus also needs a letter on both sides ("x-ray" but not "-5")
+/// Breaking only after the last of a run keeps "--" and "//" together and never starts a
+/// line with one of them.
+pub(crate) fn breaks_after(chars: &[char], k: usize, set: &str) -> bool {
+    let (Some(&before), Some(&after)) = (
+        k.checked_sub(1).and_then(|i| chars.get(i)),
+        chars.get(k + 1),
+    ) else {
+        return false;
+    };
+    let c = chars[k];
+    set.contains(c)
+        && !set.contains(before)
+        && !set.contains(after)
+        && (c != '-' || before.is_alphabetic() && after.is_alphabetic())
+}
+
+/// word split after each character breaks_after allows a break after, the character ending
+/// its piece
+fn break_after_pieces<'w>(word: &'w str, set: &str) -> Vec<&'w str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let plain: Vec<char> = chars.iter().map(|&(_, c)| c).collect();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (k, &(offset, c)) in chars.iter().enumerate() {
+        if breaks_after(&plain, k, set) {
+            let end = offset + c.len_utf8();
+            pieces.push(&word[start..end]);
+            start = end;
+        }
+    }
+    pieces.push(&word[start..]);
+    pieces
+}
+
+/// Character widths measured from the actual font, in em units
+pub struct FontMetrics {
+    chars: HashMap<char, f64>,
+    ranges: Vec<(u32, u32, f64)>,
+    narrow: f64,
+    wide: f64,
+}
+
+impl Default for FontMetrics {
+    fn default() -> Self {
+        FontMetrics {
+            chars: HashMap::new(),
+            ranges: Vec::new(),
+            narrow: 0.5,
+            wide: 1.0,
+        }
+    }
+}
+
+impl WidthModel for FontMetrics {
+    fn char_width(&self, c: char) -> f64 {
+        if let Some(&width) = self.chars.get(&c) {
+            return width;
+        }
+
+        let code_point = c as u32;
+        if let Some(&(_, _, width)) = self
+            .ranges
+            .iter()
+            .find(|&&(start, end, _)| (start..=end).contains(&code_point))
+        {
+            return width;
+        }
+
+        if CharClass::of(c).is_narrow() {
+            self.narrow
+        } else {
+            self.wide
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn default_narrow() -> f64 {
+    0.5
+}
+
+#[cfg(feature = "json")]
+fn default_wide() -> f64 {
+    1.0
+}
+
+#[cfg(feature = "json")]
+/// JSON shape accepted by FontMetrics::from_json
+/// {"narrow": 0.5, "wide": 1.0, "chars": {"i": 0.3}, "ranges": [[0x4E00, 0x9FFF, 1.0]]}
+#[derive(Deserialize, Serialize)]
+struct FontMetricsJson {
+    #[serde(default = "default_narrow")]
+    narrow: f64,
+    #[serde(default = "default_wide")]
+    wide: f64,
+    #[serde(default)]
+    chars: BTreeMap<String, f64>,
+    #[serde(default)]
+    ranges: Vec<(u32, u32, f64)>,
+}
+
+#[cfg(feature = "json")]
+fn check_width(width: f64) -> Result<f64, WebtoolsError> {
+    if width.is_finite() && width >= 0.0 {
+        Ok(width)
+    } else {
+        Err(WebtoolsError::invalid_argument(format!(
+            "Invalid width: {}",
+            width
+        )))
+    }
+}
+
+#[cfg(feature = "json")]
+impl FontMetrics {
+    /// Parse metrics measured from the rendering font
+    pub fn from_json(metrics_json: &str) -> Result<FontMetrics, WebtoolsError> {
+        let parsed: FontMetricsJson =
+            serde_json::from_str(metrics_json).map_err(WebtoolsError::invalid_json)?;
+
+        let mut chars = HashMap::with_capacity(parsed.chars.len());
+        for (key, width) in parsed.chars {
+            let mut key_chars = key.chars();
+            match (key_chars.next(), key_chars.next()) {
+                (Some(c), None) => {
+                    chars.insert(c, check_width(width)?);
+                }
+                _ => {
+                    return Err(WebtoolsError::invalid_argument(format!(
+                        "Metrics key must be a single character: {:?}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        let mut ranges = Vec::with_capacity(parsed.ranges.len());
+        for (start, end, width) in parsed.ranges {
+            if start > end {
+                return Err(WebtoolsError::InvalidRange { start, end });
+            }
+            ranges.push((start, end, check_width(width)?));
+        }
+
+        Ok(FontMetrics {
+            chars,
+            ranges,
+            narrow: check_width(parsed.narrow)?,
+            wide: check_width(parsed.wide)?,
+        })
+    }
+
+    /// The metrics in the shape from_json takes, characters in order; None for the defaults
+    pub fn to_json_value(&self) -> Option<serde_json::Value> {
+        let defaults = self.chars.is_empty()
+            && self.ranges.is_empty()
+            && self.narrow == default_narrow()
+            && self.wide == default_wide();
+        if defaults {
+            return None;
+        }
+        let json = FontMetricsJson {
+            narrow: self.narrow,
+            wide: self.wide,
+            chars: self
+                .chars
+                .iter()
+                .map(|(c, &width)| (c.to_string(), width))
+                .collect(),
+            ranges: self.ranges.clone(),
+        };
+        serde_json::to_value(json).ok()
+    }
+}
+
+/// Justify text against measured font metrics
+/// max_width_em is the line width in em units
+pub fn justify_text_with_metrics(text: &str, max_width_em: f64, metrics: &FontMetrics) -> String {
+    wrap_text_with(text, max_width_em, metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::random_texts;
+
+    /// ColumnWidths without the shared ASCII width, so every character goes through the loop
+    struct CharByChar;
+
+    impl WidthModel for CharByChar {
+        fn char_width(&self, c: char) -> f64 {
+            ColumnWidths.char_width(c)
+        }
+
+        fn word_width(&self, word: &str) -> f64 {
+            ColumnWidths.word_width(word)
+        }
+    }
+
+    #[test]
+    fn ascii_runs_wrap_as_the_character_loop_does() {
+        for text in random_texts(300) {
+            let text = format!(
+                "漢字 {}averyveryverylongasciiword and some 12,345 more{}",
+                text, text
+            );
+            for max_width in [0.5, 1.0, 3.5, 6.0, 20.0] {
+                assert_eq!(
+                    wrap_text_with(&text, max_width, &ColumnWidths),
+                    wrap_text_with(&text, max_width, &CharByChar),
+                    "{:?} at {} em",
+                    text,
+                    max_width
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn measured_widths_move_the_wrap_points() {
+        let metrics = FontMetrics::from_json(r#"{"chars": {"i": 0.3, "W": 0.9}}"#).unwrap();
+        let default = FontMetrics::default();
+        // "iii" is 0.9 em against 1.5 em at the default half em, so two fit in 3 em
+        assert_eq!(
+            justify_text_with_metrics("iii iii iii", 3.0, &metrics),
+            "iii iii\r\niii"
+        );
+        assert_eq!(
+            justify_text_with_metrics("iii iii iii", 3.0, &default),
+            "iii\r\niii\r\niii"
+        );
+        // "WW" is 1.8 em against 1 em, so the pair no longer shares a line
+        assert_eq!(
+            justify_text_with_metrics("WW WW", 3.0, &metrics),
+            "WW\r\nWW"
+        );
+        assert_eq!(justify_text_with_metrics("WW WW", 3.0, &default), "WW WW");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn font_metrics_json_is_checked() {
+        assert!(FontMetrics::from_json("not json").is_err());
+        assert!(FontMetrics::from_json(r#"{"chars": {"ab": 0.5}}"#).is_err());
+        let ranged = FontMetrics::from_json(r#"{"wide": 1.2, "ranges": [[48, 57, 0.6]]}"#).unwrap();
+        assert_eq!(ranged.char_width('5'), 0.6);
+        assert_eq!(ranged.char_width('漢'), 1.2);
+        assert_eq!(ranged.char_width('a'), 0.5);
+    }
+
+    #[test]
+    fn fits_width_allows_for_accumulated_error() {
+        let sum: f64 = std::iter::repeat_n(0.1, 10_000).sum();
+        assert_ne!(sum, 1_000.0);
+        assert!(fits_width(sum, 1_000.0));
+        assert!(!fits_width(1_000.001, 1_000.0));
+    }
+
+    #[test]
+    fn long_fractional_lines_wrap_where_the_widths_add_up() {
+        let cjk = "漢".repeat(10_000);
+        let wrapped = crate::justify_text_fractional(&cjk, 100.0);
+        assert!(wrapped
+            .split("\r\n")
+            .all(|line| line.chars().count() == 100));
+        assert_eq!(wrapped.split("\r\n").count(), 100);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn tenths_of_an_em_over_a_long_line() {
+        // 0.1 isn't exact in binary: 10,000 of them add up to a hair over 1,000
+        let metrics = FontMetrics::from_json(r#"{"chars": {"i": 0.1}}"#).unwrap();
+        let line = "i".repeat(10_000);
+        assert!(justify_text_with_metrics(&line, 1_000.0, &metrics) == line);
+        let spaced = "i ".repeat(5_000);
+        // Each "i " is 0.6 em: 1,000 em holds 1,666 of them and the last "i"
+        let wrapped = justify_text_with_metrics(&spaced, 1_000.0, &metrics);
+        let lines: Vec<&str> = wrapped.split("\r\n").collect();
+        assert_eq!(lines[0].len(), 1_667 * 2 - 1);
+    }
+
+    fn markers(max_chars_per_line: u32, hard: &str, soft: &str) -> crate::JustifyOptions {
+        crate::JustifyOptions {
+            max_chars_per_line,
+            hard_break_marker: hard.to_string(),
+            soft_break_marker: soft.to_string(),
+            ..crate::JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn hard_break_markers_end_lines() {
+        let hard = markers(20, "⏎", "");
+        assert_eq!(hard.justify("第一行⏎第二行"), "第一行\r\n第二行");
+        assert_eq!(hard.justify("⏎start"), "\r\nstart");
+        assert_eq!(hard.justify("end⏎"), "end\r\n");
+        assert_eq!(hard.justify("⏎"), "\r\n");
+        // Two in a row leave an empty line between them
+        assert_eq!(hard.justify("a⏎⏎b"), "a\r\n\r\nb");
+        assert_eq!(markers(10, "<br>", "").justify("a<br><br>b"), "a\r\n\r\nb");
+    }
+
+    #[test]
+    fn soft_break_markers_are_zero_width_break_opportunities() {
+        let soft = markers(6, "", "<wbr>");
+        // 漢字漢字漢字 is 12 columns; the marker's five characters weren't counted
+        assert_eq!(soft.justify("漢字<wbr>漢字漢字"), "漢字漢\r\n字漢字");
+        assert_eq!(
+            soft.justify("super<wbr>califragilistic"),
+            "super\r\ncalifragilistic"
+        );
+        assert_eq!(soft.justify("ab<wbr>cdef"), "abcdef");
+        assert_eq!(markers(10, "", "<wbr>").justify("<wbr>abc<wbr>"), "abc");
+        assert_eq!(
+            markers(14, "", "<wbr>").justify("Foo<wbr>bar baz<wbr>quxquux"),
+            "Foobar baz\r\nquxquux"
+        );
+    }
+
+    #[test]
+    fn multibyte_markers_never_count_toward_the_width() {
+        // ⏎ is two columns and 🔚 four bytes; neither is measured or written
+        assert_eq!(markers(4, "⏎", "").justify("ab⏎cd"), "ab\r\ncd");
+        assert_eq!(markers(4, "", "⏎").justify("ab⏎cd"), "abcd");
+        assert_eq!(markers(3, "🔚", "").justify("ab🔚cd"), "ab\r\ncd");
+        assert_eq!(markers(20, "", "\u{200b}x").justify("a\u{200b}xb"), "ab");
+        assert_eq!(
+            markers(6, "⏎", "<wbr>").justify("ab<wbr>cdef⏎g"),
+            "abcdef\r\ng"
+        );
+    }
+
+    const NUMBER_TOKENS: &[&str] = &[
+        "1.5e-7",
+        "2024-03-15",
+        "12:30:45",
+        "1,234,567.89",
+        "6.02E+23",
+    ];
+
+    #[test]
+    fn number_tokens_join_digits() {
+        for token in NUMBER_TOKENS {
+            assert_eq!(number_token_len(token.as_bytes()), token.len(), "{}", token);
+        }
+        // A separator with no digit after it isn't part of the token
+        assert_eq!(number_token_len(b"3. Next"), 1);
+        assert_eq!(number_token_len(b"10e"), 2);
+        assert_eq!(number_token_len(b"1-2-"), 3);
+        assert_eq!(number_token_len(b"x1"), 0);
+        let chars: Vec<char> = "于2024-03-15和7到1.5e-7".chars().collect();
+        assert_eq!(number_tokens(&chars), [1..11, 14..20]);
+    }
+
+    #[test]
+    fn number_tokens_at_the_margin_are_never_split() {
+        for token in NUMBER_TOKENS {
+            for text in [
+                format!("The reading was {} at noon", token),
+                format!("测量结果为{}左右", token),
+            ] {
+                // Every width the token fits in, so the margin falls on each of its characters
+                for max_chars_per_line in token.len() as u32..40 {
+                    for wrapped in [
+                        crate::justify_text(&text, max_chars_per_line),
+                        crate::justify_text_cjk(&text, max_chars_per_line),
+                    ] {
+                        assert!(
+                            wrapped.split("\r\n").any(|line| line.contains(token)),
+                            "{:?} at {}: {:?}",
+                            text,
+                            max_chars_per_line,
+                            wrapped
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn number_tokens_wider_than_the_line_still_break() {
+        // Cut at the limit, as other atomic tokens are
+        assert_eq!(
+            crate::justify_text_cjk("值1,234,567.89", 6),
+            "值1,23\r\n4,567.\r\n89"
+        );
+        assert_eq!(
+            crate::justify_text("测量结果为2024-03-15左右", 12),
+            "测量结果为\r\n2024-03-15左\r\n右"
+        );
+    }
+
+    #[test]
+    fn algorithm_v1_splits_number_tokens_as_it_always_did() {
+        let v1 = |max_chars_per_line| crate::JustifyOptions {
+            max_chars_per_line,
+            algorithm_version: 1,
+            ..crate::JustifyOptions::default()
+        };
+        assert_eq!(v1(7).justify("中文1.5e-7"), "中文1.5\r\ne-7");
+        assert_eq!(crate::justify_text("中文1.5e-7", 7), "中文\r\n1.5e-7");
+        assert_eq!(v1(8).justify("時刻12:30:45です"), "時刻12:3\r\n0:45です");
+        let v2 = crate::JustifyOptions {
+            algorithm_version: 2,
+            ..v1(8)
+        };
+        assert_eq!(v2.justify("時刻12:30:45です"), "時刻\r\n12:30:45\r\nです");
+    }
+
+    fn marking(max_chars_per_line: u32, marker: &str) -> crate::JustifyOptions {
+        crate::JustifyOptions {
+            max_chars_per_line,
+            mark_soft_breaks: marker.to_string(),
+            ..crate::JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn soft_breaks_are_marked_and_source_breaks_are_not() {
+        // A word break keeps its space in front of the marker
+        assert_eq!(
+            marking(10, "↩").justify("the quick brown fox"),
+            "the quick ↩\r\nbrown fox"
+        );
+        assert_eq!(
+            marking(8, "\u{200B}").justify("这是一段中文文本内容"),
+            "这是一段\u{200B}\r\n中文文本\u{200B}\r\n内容"
+        );
+        assert_eq!(
+            marking(6, "\u{200B}").justify("第一行\n第二行很长很长"),
+            "第一行\r\n第二行\u{200B}\r\n很长很\u{200B}\r\n长"
+        );
+        assert_eq!(
+            marking(7, "\u{200B}").justify("Hello世界 mixed 中文 text"),
+            "Hello世\u{200B}\r\n界 mixe\u{200B}\r\nd 中文 \u{200B}\r\ntext"
+        );
+        assert!(marking(10, "a ").validate().is_err());
+    }
+
+    #[test]
+    fn removing_the_marks_rejoins_the_lines() {
+        for (text, max_chars_per_line) in [
+            ("the quick brown fox", 10),
+            ("这是一段中文文本内容", 8),
+            ("Hello世界 mixed 中文 text", 7),
+        ] {
+            let marked = marking(max_chars_per_line, "\u{200B}").justify(text);
+            assert_eq!(remove_soft_breaks(&marked, "\u{200B}"), text);
+        }
+        assert_eq!(remove_soft_breaks("a\u{200B}\nb", "\u{200B}"), "ab");
+        assert_eq!(remove_soft_breaks("a\r\nb", ""), "a\r\nb");
+    }
+
+    #[test]
+    fn soft_break_round_trip_on_random_text() {
+        let unlimited = marking(u32::MAX, "");
+        // Texts holding the marker, or a carriage return on its own, aren't round-trip exact
+        let texts = random_texts(500)
+            .into_iter()
+            .filter(|text| !text.contains('\u{200B}') && !text.replace("\r\n", "").contains('\r'));
+        for text in texts {
+            for max_chars_per_line in [1, 2, 3, 5, 8, 13, 40] {
+                let marked = marking(max_chars_per_line, "\u{200B}").justify(&text);
+                assert_eq!(
+                    remove_soft_breaks(&marked, "\u{200B}"),
+                    unlimited.justify(&text),
+                    "{:?} at {}",
+                    text,
+                    max_chars_per_line
+                );
+            }
+        }
+    }
+
+    fn version(algorithm_version: u32, max_chars_per_line: u32) -> crate::JustifyOptions {
+        crate::JustifyOptions {
+            max_chars_per_line,
+            algorithm_version,
+            ..crate::JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn astral_only_lines_wrap_at_small_widths() {
+        // The ideographs make these character-wrapped lines
+        let gothic = "𐌰𐌱𐌲𐌳𠀀𠀁";
+        assert_eq!(version(1, 4).justify(gothic), "𐌰𐌱\r\n𐌲𐌳\r\n𠀀𠀁");
+        assert_eq!(version(3, 4).justify(gothic), "𐌰𐌱𐌲𐌳\r\n𠀀𠀁");
+        assert_eq!(version(3, 3).justify(gothic), "𐌰𐌱𐌲\r\n𐌳𠀀\r\n𠀁");
+        // Without them the letters are one word, which isn't cut
+        assert_eq!(version(3, 2).justify("𐌰𐌱𐌲𐌳"), "𐌰𐌱𐌲𐌳");
+        // Extension B ideographs are two columns in every version
+        for algorithm_version in [1, 3] {
+            assert_eq!(
+                version(algorithm_version, 4).justify("𠀀𠀁𠀂𠀃"),
+                "𠀀𠀁\r\n𠀂𠀃"
+            );
+        }
+        // Words of math letters are words: they wrap at spaces and aren't cut
+        assert_eq!(version(3, 4).justify("𝐀𝐁𝐂 𝐃𝐄𝐅"), "𝐀𝐁𝐂\r\n𝐃𝐄𝐅");
+    }
+
+    #[test]
+    fn utf16_never_splits_a_surrogate_pair() {
+        let astral = ['𝐀', '𐌰', '𠀀', '😀', ' ', '\n'];
+        for seed in 0..200usize {
+            let text: String = (0..seed % 17 + 1)
+                .map(|i| astral[(seed * 7 + i * 13) % astral.len()])
+                .collect();
+            let units: Vec<u16> = text.encode_utf16().collect();
+            for max_chars_per_line in 1..=5 {
+                let wrapped = crate::justify_text_utf16(&units, max_chars_per_line);
+                let wrapped = String::from_utf16(&wrapped).expect("whole surrogate pairs");
+                assert_eq!(
+                    wrapped,
+                    crate::justify_text(&text, max_chars_per_line),
+                    "{:?}",
+                    text
+                );
+            }
+        }
+    }
+}
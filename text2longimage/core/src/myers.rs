@@ -0,0 +1,115 @@
+//! Shortest edit scripts between two sequences (Myers, "An O(ND) Difference Algorithm")
+//! diff_texts compares the lines of two texts with it, then the words of the lines that
+//! changed. The forward search keeps the furthest path of every diagonal for each edit count, so
+//! memory grows with the square of the edit distance; sequences more than MAX_EDIT_DISTANCE
+//! edits apart are reported as wholly replaced instead.
+
+/// One step of an edit script
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Most edits edit_script searches for before giving up on a shortest script
+pub(crate) const MAX_EDIT_DISTANCE: usize = 1_000;
+
+/// The steps turning old into new: Keep and Delete take the next item of old, Keep and
+/// Insert the next of new; in each run of changes the deletions come first
+pub(crate) fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let mut script = vec![Edit::Keep; prefix];
+    match shortest_middle(old, new) {
+        Some(middle) => script.extend(deletions_first(middle)),
+        None => {
+            script.extend(std::iter::repeat_n(Edit::Delete, old.len()));
+            script.extend(std::iter::repeat_n(Edit::Insert, new.len()));
+        }
+    }
+    script.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    script
+}
+
+/// A shortest script for old and new, None past MAX_EDIT_DISTANCE edits
+fn shortest_middle<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (old.len() + new.len()).min(MAX_EDIT_DISTANCE) as isize;
+    // furthest[k + offset]: the furthest x reached on diagonal k = x - y
+    let offset = max + 1;
+    let mut furthest = vec![0isize; 2 * max as usize + 3];
+    // Before each round d, the diagonals -d - 1..=d + 1 it starts from
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    for d in 0..=max {
+        trace.push(furthest[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let at = (k + offset) as usize;
+            let mut x = if k == -d || k != d && furthest[at - 1] < furthest[at + 1] {
+                furthest[at + 1]
+            } else {
+                furthest[at - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[at] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    None
+}
+
+/// The script of the path that reached (n, m) in trace.len() - 1 edits
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| furthest[(k + d + 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || k != d && at(k - 1) < at(k + 1) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            script.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            script.push(if x == previous_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    script.reverse();
+    script
+}
+
+/// script with every run of changes reordered to its deletions, then its insertions
+fn deletions_first(mut script: Vec<Edit>) -> Vec<Edit> {
+    for run in script.split_mut(|&edit| edit == Edit::Keep) {
+        run.sort_by_key(|&edit| edit == Edit::Insert);
+    }
+    script
+}
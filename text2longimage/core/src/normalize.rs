@@ -0,0 +1,226 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::{expected_values, find_value};
+use crate::edits::{EditRecorder, TextEdit};
+use crate::{OptionValue, WebtoolsError};
+
+/// Forms accepted by normalize_unicode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationForm {
+    /// Canonical composition: "e" + U+0301 becomes "é", "か" + U+3099 becomes "が"
+    Nfc,
+    /// Compatibility composition: NFC plus folding fullwidth ASCII, compatibility
+    /// ideographs, ligatures and the like to their plain forms
+    Nfkc,
+}
+
+impl NormalizationForm {
+    /// Parse "NFC" / "NFKC" in any case
+    pub fn parse(form: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| form.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown normalization form: {} (expected {})",
+                form,
+                expected_values::<NormalizationForm>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for NormalizationForm {
+    const KIND: &'static str = "normalizationForm";
+    const USED_BY: &'static [&'static str] = &["normalize_unicode"];
+    const ALL: &'static [Self] = &[NormalizationForm::Nfc, NormalizationForm::Nfkc];
+
+    fn name(self) -> &'static str {
+        match self {
+            NormalizationForm::Nfc => "nfc",
+            NormalizationForm::Nfkc => "nfkc",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            NormalizationForm::Nfc => "Canonical composition",
+            NormalizationForm::Nfkc => {
+                "Canonical composition, with fullwidth ASCII, ligatures and the like folded"
+            }
+        }
+    }
+}
+
+#[cfg(feature = "normalization")]
+/// Normalize text so decomposed input (macOS, some IMEs) counts, measures and caches the
+/// same as its precomposed equivalent
+pub fn normalize_unicode(text: &str, form: NormalizationForm) -> String {
+    use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+    match form {
+        NormalizationForm::Nfc if is_nfc_quick(text.chars()) == IsNormalized::Yes => {
+            text.to_string()
+        }
+        NormalizationForm::Nfc => text.nfc().collect(),
+        NormalizationForm::Nfkc if is_nfkc_quick(text.chars()) == IsNormalized::Yes => {
+            text.to_string()
+        }
+        NormalizationForm::Nfkc => text.nfkc().collect(),
+    }
+}
+
+/// NFC for the normalizeNfc options, borrowing when nothing changes
+/// Without the normalization feature the text is returned as is; the options reject the flag
+/// up front (see check_normalization_available)
+pub(crate) fn to_nfc(text: &str) -> Cow<'_, str> {
+    #[cfg(feature = "normalization")]
+    {
+        use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+        if is_nfc_quick(text.chars()) != IsNormalized::Yes {
+            return Cow::Owned(text.nfc().collect());
+        }
+    }
+    Cow::Borrowed(text)
+}
+
+/// to_nfc, and the edits that make the change when record is set
+/// The edits are made segment by segment, each segment starting at an ASCII character: nothing
+/// composes with an ASCII character before it, so NFC of the whole text is NFC of each segment,
+/// and only the segments that change are edited.
+pub(crate) fn to_nfc_recorded(text: &str, record: bool) -> (Cow<'_, str>, Vec<TextEdit>) {
+    let normalized = to_nfc(text);
+    if !record || matches!(normalized, Cow::Borrowed(_)) {
+        return (normalized, Vec::new());
+    }
+    let mut out = EditRecorder::new(normalized.len(), true);
+    let bytes = text.as_bytes();
+    let mut start = 0;
+    while start < text.len() {
+        let end = (start + 1..text.len())
+            .find(|&i| bytes[i].is_ascii())
+            .unwrap_or(text.len());
+        let segment = &text[start..end];
+        match to_nfc(segment) {
+            Cow::Owned(composed) if composed != segment => {
+                out.replace(segment.chars().count(), &composed)
+            }
+            _ => out.keep(segment),
+        }
+        start = end;
+    }
+    let (composed, edits) = out.finish();
+    (Cow::Owned(composed), edits)
+}
+
+/// Error for an options object asking for NFC in a build without the normalization feature
+pub(crate) fn check_normalization_available(requested: bool) -> Result<(), WebtoolsError> {
+    if requested && !cfg!(feature = "normalization") {
+        return Err(WebtoolsError::invalid_argument(
+            "normalizeNfc needs a build with the normalization feature",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forms_parse_in_any_case() {
+        assert_eq!(
+            NormalizationForm::parse("NFC").unwrap(),
+            NormalizationForm::Nfc
+        );
+        assert_eq!(
+            NormalizationForm::parse("nfkc").unwrap(),
+            NormalizationForm::Nfkc
+        );
+        let error = NormalizationForm::parse("NFD").unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+        assert!(
+            error.to_string().ends_with("(expected nfc or nfkc)"),
+            "{}",
+            error
+        );
+    }
+
+    #[cfg(not(feature = "normalization"))]
+    #[test]
+    fn without_the_feature_nfc_is_refused() {
+        assert_eq!(to_nfc("e\u{301}"), "e\u{301}");
+        assert!(check_normalization_available(false).is_ok());
+        assert_eq!(
+            check_normalization_available(true).unwrap_err().code(),
+            "INVALID_ARGUMENT"
+        );
+    }
+
+    #[cfg(feature = "normalization")]
+    #[test]
+    fn decomposed_input_measures_as_precomposed() {
+        use crate::{calculate_text_width, get_text_stats, is_cjk, justify_text};
+
+        for (decomposed, precomposed) in [
+            ("e\u{301}", "é"),
+            ("\u{304B}\u{3099}", "が"),
+            ("Cafe\u{301} \u{306F}\u{309A}\u{30F3}", "Café ぱン"),
+        ] {
+            assert_ne!(decomposed, precomposed);
+            for form in [NormalizationForm::Nfc, NormalizationForm::Nfkc] {
+                let normalized = normalize_unicode(decomposed, form);
+                assert_eq!(normalized, precomposed);
+                assert_eq!(
+                    calculate_text_width(&normalized),
+                    calculate_text_width(precomposed)
+                );
+                assert_eq!(get_text_stats(&normalized), get_text_stats(precomposed));
+                assert_eq!(is_cjk(&normalized), is_cjk(precomposed));
+                assert_eq!(justify_text(&normalized, 4), justify_text(precomposed, 4));
+            }
+        }
+        // Before normalizing they differ: e + U+0301 is two characters
+        assert_ne!(get_text_stats("e\u{301}"), get_text_stats("é"));
+    }
+
+    #[cfg(feature = "normalization")]
+    #[test]
+    fn nfkc_folds_compatibility_characters() {
+        let folded = normalize_unicode("ＡＢＣ１２３ ﬁ \u{F900}", NormalizationForm::Nfkc);
+        assert_eq!(folded, "ABC123 fi 豈");
+        // NFC leaves them alone
+        let nfc = normalize_unicode("ＡＢＣ ﬁ", NormalizationForm::Nfc);
+        assert_eq!(nfc, "ＡＢＣ ﬁ");
+    }
+
+    #[cfg(feature = "normalization")]
+    #[test]
+    fn recorded_edits_touch_only_the_changed_segments() {
+        use crate::edits::apply_edits;
+
+        let text = "caf\u{65}\u{301} and \u{304B}\u{3099} stay";
+        let (composed, edits) = to_nfc_recorded(text, true);
+        assert_eq!(composed, "café and が stay");
+        assert_eq!(apply_edits(text, &edits), composed);
+        assert_eq!(edits.len(), 2);
+        assert!(
+            matches!(to_nfc_recorded("é", true), (Cow::Borrowed(_), edits) if edits.is_empty())
+        );
+    }
+
+    #[cfg(feature = "normalization")]
+    #[test]
+    fn justify_options_compose_first() {
+        let options = crate::JustifyOptions {
+            normalize_nfc: true,
+            max_chars_per_line: 2,
+            ..crate::JustifyOptions::default()
+        };
+        assert_eq!(
+            options.justify("\u{304B}\u{3099}\u{304B}\u{3099}"),
+            "が\r\nが"
+        );
+    }
+}
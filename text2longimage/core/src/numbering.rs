@@ -0,0 +1,304 @@
+#[cfg(feature = "json")]
+use crate::layout::{layout_lines, Align};
+use crate::metrics::LineBreaking;
+use crate::postconditions::{debug_check_wrapped, WrapCheck};
+use crate::{is_empty_input, justify_text_breaking, width_of, width_of_str, TrailingWhitespace};
+
+/// Separator between the line number and the text
+pub const GUTTER_SEPARATOR: &str = " │ ";
+
+/// Gutter prefix for a numbered line and for its wrapped continuations
+fn gutter(number: Option<u32>, digits: usize) -> String {
+    match number {
+        Some(number) => format!("{:>digits$}{}", number, GUTTER_SEPARATOR),
+        None => format!("{:>digits$}{}", "", GUTTER_SEPARATOR),
+    }
+}
+
+/// Digits in the widest line number of a text of line_count lines
+fn gutter_digits(line_count: usize, number_start: u32) -> usize {
+    let last_number = number_start.saturating_add(line_count.saturating_sub(1) as u32);
+    last_number.to_string().len()
+}
+
+/// Columns left for the text of each line once the gutter is taken out
+fn numbered_text_width(max_chars_per_line: u32, digits: usize) -> u32 {
+    let blank_gutter = gutter(None, digits);
+    max_chars_per_line
+        .saturating_sub(u32::try_from(width_of_str(&blank_gutter)).unwrap_or(u32::MAX))
+        .max(1)
+}
+
+/// The width justify_text_numbered wraps the lines of text in
+pub(crate) fn text_width_with_gutter(
+    text: &str,
+    max_chars_per_line: u32,
+    number_start: u32,
+) -> u32 {
+    let digits = gutter_digits(text.split('\n').count(), number_start);
+    numbered_text_width(max_chars_per_line, digits)
+}
+
+/// How to check justify_text_numbered_with's output of text: the gutter's characters, and the
+/// width the text after it is wrapped in
+pub(crate) fn numbered_check(
+    what: &'static str,
+    text: &str,
+    max_chars_per_line: u32,
+    number_start: u32,
+) -> WrapCheck<'static> {
+    let digits = gutter_digits(text.split('\n').count(), number_start);
+    WrapCheck {
+        gutter_chars: gutter(None, digits).chars().count(),
+        ..WrapCheck::columns(what, numbered_text_width(max_chars_per_line, digits))
+    }
+}
+
+/// Justify text with a right-aligned line-number gutter in front of every source line
+/// The gutter width comes out of max_chars_per_line, so numbered lines keep the same total width;
+/// wrapped continuation lines get an empty gutter
+pub fn justify_text_numbered(text: &str, max_chars_per_line: u32, number_start: u32) -> String {
+    let result = justify_text_numbered_with(
+        text,
+        max_chars_per_line,
+        number_start,
+        &TrailingWhitespace::Trim.into(),
+    );
+    debug_check_wrapped(
+        &result,
+        &numbered_check(
+            "justify_text_numbered",
+            text,
+            max_chars_per_line,
+            number_start,
+        ),
+    );
+    result
+}
+
+pub(crate) fn justify_text_numbered_with(
+    text: &str,
+    max_chars_per_line: u32,
+    number_start: u32,
+    breaking: &LineBreaking,
+) -> String {
+    if is_empty_input(text) {
+        return String::new();
+    }
+    let source_lines: Vec<&str> = text.split('\n').collect();
+    let digits = gutter_digits(source_lines.len(), number_start);
+    let blank_gutter = gutter(None, digits);
+    let text_width = numbered_text_width(max_chars_per_line, digits);
+
+    let mut result = String::with_capacity(text.len() + source_lines.len() * (digits + 8));
+    for (index, line) in source_lines.iter().enumerate() {
+        if breaking
+            .output_ceiling
+            .is_some_and(|ceiling| result.len() > ceiling)
+        {
+            break;
+        }
+        if index > 0 {
+            result.push_str("\r\n");
+        }
+
+        let number = number_start.saturating_add(index as u32);
+        result.push_str(gutter(Some(number), digits).trim_end());
+
+        let justified = justify_text_breaking(line, text_width, breaking);
+        for (segment_index, segment) in justified.split("\r\n").enumerate() {
+            if segment_index > 0 {
+                result.push_str("\r\n");
+                result.push_str(&blank_gutter);
+            } else if !segment.is_empty() {
+                result.push(' ');
+            }
+            result.push_str(segment);
+        }
+    }
+
+    result
+}
+
+#[cfg(feature = "json")]
+/// layout_text for the numbered output of justify_text_numbered (left aligned)
+pub fn layout_text_numbered(
+    text: &str,
+    max_chars_per_line: u32,
+    number_start: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+) -> String {
+    let justified = justify_text_numbered(text, max_chars_per_line, number_start);
+    let lines = layout_lines(
+        &justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::Left,
+    );
+
+    serde_json::to_string(&lines).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Numbers number_lines gives out: one per line, or only non-blank ones; the last is the largest
+fn line_numbers(
+    text: &str,
+    start: u32,
+    include_blank: bool,
+) -> impl Iterator<Item = (&str, Option<u32>)> {
+    let mut next = start;
+    text.split('\n').map(move |line| {
+        if !include_blank && line.trim().is_empty() {
+            return (line, None);
+        }
+        let number = next;
+        next = next.saturating_add(1);
+        (line, Some(number))
+    })
+}
+
+/// Columns taken by the widest number
+fn number_columns(text: &str, start: u32, include_blank: bool) -> usize {
+    line_numbers(text, start, include_blank)
+        .filter_map(|(_, number)| number)
+        .last()
+        .map_or(0, |last| last.to_string().len())
+}
+
+/// Prefix every line with a right-aligned line number and separator, as plain text
+/// The number column is as wide as the last number. Padding uses pad_char, measured by display
+/// width: a fullwidth pad character fills two columns, and a column it can't fill is a space.
+/// With include_blank unset, whitespace-only lines are left as they are and don't use up a
+/// number. Line breaks (including "\r\n") are kept.
+pub fn number_lines(
+    text: &str,
+    start: u32,
+    separator: &str,
+    pad_char: char,
+    include_blank: bool,
+) -> String {
+    let columns = number_columns(text, start, include_blank);
+    let pad_width = (width_of(pad_char) as usize).max(1);
+
+    let mut result = String::with_capacity(text.len() + text.len() / 8 * (columns + 4));
+    for (index, (line, number)) in line_numbers(text, start, include_blank).enumerate() {
+        if index > 0 {
+            result.push('\n');
+        }
+        if let Some(number) = number {
+            let digits = number.to_string();
+            let missing = columns - digits.len();
+            result.extend(std::iter::repeat_n(pad_char, missing / pad_width));
+            result.extend(std::iter::repeat_n(' ', missing % pad_width));
+            result.push_str(&digits);
+            result.push_str(separator);
+        }
+        result.push_str(line);
+    }
+
+    result
+}
+
+/// Display width of the prefix number_lines adds with the same arguments, for taking it off
+/// max_chars_per_line before wrapping the numbered lines' content
+pub fn number_lines_gutter_width(
+    text: &str,
+    start: u32,
+    separator: &str,
+    include_blank: bool,
+) -> u32 {
+    let columns = number_columns(text, start, include_blank);
+    if columns == 0 {
+        return 0;
+    }
+    let separator_width = u32::try_from(width_of_str(separator)).unwrap_or(u32::MAX);
+    (columns as u32).saturating_add(separator_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_text_width;
+
+    #[test]
+    fn three_digit_gutter() {
+        let text = vec!["x"; 100].join("\n");
+        let numbered = justify_text_numbered(&text, 20, 1);
+        let lines: Vec<&str> = numbered.split("\r\n").collect();
+        assert_eq!(lines.len(), 100);
+        assert_eq!(lines[0], "  1 │ x");
+        assert_eq!(lines[8], "  9 │ x");
+        assert_eq!(lines[99], "100 │ x");
+        // The gutter is as wide as the last number
+        assert_eq!(justify_text_numbered("a\nb", 20, 98), "98 │ a\r\n99 │ b");
+        assert_eq!(justify_text_numbered("a\nb", 20, 99), " 99 │ a\r\n100 │ b");
+    }
+
+    #[test]
+    fn wrapped_comment_continues_under_an_empty_gutter() {
+        let code = "let x = 1;\n// 这是一个很长的中文注释\n";
+        let numbered = justify_text_numbered(code, 14, 1);
+        // "1 │ " takes 4 of the 14 columns, leaving 10 for the text
+        assert_eq!(
+            numbered,
+            "1 │ let x =\r\n  │ 1;\r\n2 │ // 这是一\r\n  │ 个很长的\r\n  │ 中文注释\r\n3 │"
+        );
+        for line in numbered.split("\r\n") {
+            assert!(calculate_text_width(line) <= 14, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn number_lines_widens_the_gutter_at_10_and_100() {
+        let nine = ["x"; 9].join("\n");
+        assert_eq!(
+            number_lines(&nine, 1, ": ", ' ', true).lines().last(),
+            Some("9: x")
+        );
+        assert_eq!(number_lines_gutter_width(&nine, 1, ": ", true), 3);
+
+        let ten = ["x"; 10].join("\n");
+        let numbered = number_lines(&ten, 1, ": ", ' ', true);
+        let lines: Vec<&str> = numbered.lines().collect();
+        assert_eq!((lines[0], lines[9]), (" 1: x", "10: x"));
+        assert_eq!(number_lines_gutter_width(&ten, 1, ": ", true), 4);
+
+        // 99 lines fit two digits, 100 don't; so do 2 lines starting at 99
+        let numbered = number_lines(&vec!["x"; 100].join("\n"), 1, "│ ", '0', true);
+        assert_eq!(numbered.lines().nth(98), Some("099│ x"));
+        assert_eq!(number_lines("a\nb", 98, "│ ", ' ', true), "98│ a\n99│ b");
+        assert_eq!(number_lines("a\nb", 99, "│ ", ' ', true), " 99│ a\n100│ b");
+        // │ is one of the wide characters, so the separator takes 3 columns
+        assert_eq!(number_lines_gutter_width("a\nb", 99, "│ ", true), 6);
+    }
+
+    #[test]
+    fn number_lines_blank_lines_and_padding() {
+        // Blank lines keep no number unless asked, so they don't widen the gutter either
+        let text = "a\n\n  \nb\r\nc";
+        assert_eq!(
+            number_lines(text, 1, " ", ' ', false),
+            "1 a\n\n  \n2 b\r\n3 c"
+        );
+        assert_eq!(
+            number_lines(text, 9, " ", ' ', true),
+            " 9 a\n10 \n11   \n12 b\r\n13 c"
+        );
+        // A fullwidth pad fills two columns and a space the odd one
+        let text = vec!["x"; 1000].join("\n");
+        let numbered = number_lines(&text, 1, " ", '\u{3000}', true);
+        assert_eq!(numbered.lines().next(), Some("\u{3000} 1 x"));
+        assert_eq!(numbered.lines().nth(9), Some("\u{3000}10 x"));
+        for line in numbered.lines() {
+            assert_eq!(
+                calculate_text_width(line) as u32,
+                number_lines_gutter_width(&text, 1, " ", true) + 1,
+                "{line:?}"
+            );
+        }
+        assert_eq!(number_lines_gutter_width(" \n", 1, ": ", false), 0);
+    }
+}
@@ -0,0 +1,959 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::expansion::ExpansionGuard;
+use crate::metrics::{BreakMarkers, LineBreaking};
+use crate::normalize::{check_normalization_available, to_nfc};
+use crate::numbering::{justify_text_numbered_with, numbered_check};
+use crate::postconditions::{check_wrapped, RenderPasses, WrapCheck};
+use crate::signature::append_signature;
+#[cfg(feature = "json")]
+use crate::OptionWarning;
+use crate::{
+    append_footer, escape_html, justify_text_breaking, limit_marks, limit_repeats,
+    max_output_ratio, split_signature, strip_ansi, visualize_whitespace, Align, ListMarkerSpacing,
+    RubySyntax, TrailingWhitespace, Warning, WebtoolsError, DEFAULT_MAX_MARKS_PER_CLUSTER,
+    DEFAULT_MAX_UNBREAKABLE_RUN, DEFAULT_SIGNATURE_DELIMITER,
+};
+use crate::{latest_algorithm_version, AlgorithmVersion};
+
+/// Line width used when an options object doesn't set maxCharsPerLine
+/// (the frontend's 18 full-width characters)
+pub const DEFAULT_MAX_CHARS_PER_LINE: u32 = 36;
+
+/// Default punctuationBreakWindow, in half-width columns
+pub const DEFAULT_PUNCTUATION_BREAK_WINDOW: u32 = 8;
+
+/// Default breakAfterChars: em dash, en dash, hyphen-minus, '/', katakana middle dot and
+/// middle dot
+pub const DEFAULT_BREAK_AFTER_CHARS: &str = "—–-/・·";
+
+/// Narrowest accepted maxCharsPerLine; zero would push every character onto its own line
+pub const MIN_MAX_CHARS_PER_LINE: u32 = 1;
+
+/// Widest accepted maxCharsPerLine
+/// Thousands of times wider than any canvas can draw, so only a caller bug (an unbound slider
+/// sending u32::MAX) gets there; the fallible entry points reject wider ones with
+/// WidthTooLarge rather than pad or allocate for them.
+pub const MAX_MAX_CHARS_PER_LINE: u32 = 100_000;
+
+/// Reject a line width above MAX_MAX_CHARS_PER_LINE
+pub fn check_max_chars_per_line(max_chars_per_line: u32) -> Result<(), WebtoolsError> {
+    if max_chars_per_line > MAX_MAX_CHARS_PER_LINE {
+        return Err(WebtoolsError::WidthTooLarge {
+            max: MAX_MAX_CHARS_PER_LINE,
+            actual: max_chars_per_line,
+        });
+    }
+    Ok(())
+}
+
+/// Options shared by the object-based entry points
+/// Serialized with camelCase keys; every field is optional when deserializing
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct JustifyOptions {
+    /// Line width in half-width columns (CJK characters take two), at most
+    /// MAX_MAX_CHARS_PER_LINE
+    pub max_chars_per_line: u32,
+    /// Prefix every source line with a line-number gutter
+    pub line_numbers: bool,
+    /// Number of the first line when line_numbers is set
+    pub number_start: u32,
+    /// Remove ANSI escape sequences (terminal colors) before wrapping
+    pub strip_ansi: bool,
+    /// Compose the text to NFC first; needs the normalization feature
+    pub normalize_nfc: bool,
+    /// Drop whitespace left at the end of wrapped lines
+    pub trim_trailing: bool,
+    /// Markdown source: with trim_trailing, a line ending in exactly two spaces (a hard
+    /// break) keeps them; from algorithm V5 on, inline code spans (`like this`) are never
+    /// broken
+    pub markdown: bool,
+    /// Text that forces a line break wherever it appears (e.g. "⏎"); removed from the
+    /// output, empty for none
+    pub hard_break_marker: String,
+    /// Text marking a zero-width break opportunity (e.g. "<wbr>"); removed from the output,
+    /// empty for none
+    pub soft_break_marker: String,
+    /// Wrapping algorithm to use; defaults to latest_algorithm_version(). Pin 1 to keep
+    /// output identical to earlier renders across releases
+    pub algorithm_version: u32,
+    /// Ruby notation in the source, kept on one line and measured by its base only
+    /// ("none", "kanji" for 漢字《かんじ》 or "aozora", which adds ｜振仮名《ふりがな》)
+    pub ruby_syntax: RubySyntax,
+    /// Line appended below the text (e.g. an attribution); never wrapped, cut with an
+    /// ellipsis if it is wider than a line. Empty for none
+    pub footer_text: String,
+    /// Alignment of the footer, done by padding it with spaces to maxCharsPerLine
+    pub footer_align: Align,
+    /// Blank lines between the text and the footer
+    pub footer_blank_lines: u32,
+    /// Text written at the end of every line the wrapper broke (e.g. U+200B), so
+    /// remove_soft_breaks can recover the paragraphs from copied output; empty for none
+    pub mark_soft_breaks: String,
+    /// Written at the end of a line where a character-wrapped line had to cut through a
+    /// token of printable ASCII (a URL, a base64 blob), e.g. "↩"; counted in the line width.
+    /// Breaks at URL separators and ordinary word or CJK breaks get no marker. Empty for none
+    pub break_marker_end: String,
+    /// Written at the start of the line continuing such a token, e.g. "↪"; empty for none
+    pub break_marker_start: String,
+    /// How far back, in columns, a break that has to cut through a run of printable ASCII in
+    /// a CJK line looks for punctuation ('.', ',', ';', ')', '、', '，') to break after
+    /// instead; the line it leaves must still be at least half as wide as maxCharsPerLine.
+    /// 0 turns the lookback off. Algorithm version 2 and later
+    pub punctuation_break_window: u32,
+    /// Show the output's whitespace as symbols (see visualize_whitespace), for a debug
+    /// overlay; the symbols' widths differ from the whitespace they replace
+    pub visualize_whitespace: bool,
+    /// Write & < > " ' as HTML entities, as justify_text_html does, for output put into HTML
+    /// as it is; line breaks stay "\r\n". Done last, after the widths are decided, so lines
+    /// break where they would unescaped
+    pub escape_html: bool,
+    /// Widest word, in columns, the word wrapper lets overflow a line; wider ones (a pasted
+    /// base64 blob) are cut into lines by character so the output stays within the width.
+    /// Applies to every algorithm version. 0 for no limit
+    pub max_unbreakable_run: u32,
+    /// Keep the whitespace between two words a word-wrapped line puts together as it was in
+    /// the source (an ideographic space from an IME, a tab, several spaces), measured by its
+    /// own width, instead of writing one space. Whitespace where a line breaks is still
+    /// dropped
+    pub preserve_separator_chars: bool,
+    /// Characters a word may break after, so "state—of—the—art" or "Tanaka・Sato" doesn't
+    /// overflow a line: a break goes after one that has a character other than these on both
+    /// sides (letters on both sides for a hyphen-minus), and a CJK line never starts with one.
+    /// Algorithm version 4 and later; empty for none
+    pub break_after_chars: String,
+    /// Most combining marks kept after one character; later ones ("Zalgo" text) are dropped,
+    /// and lines of nothing but invisible characters are emptied (see limit_marks). Applies
+    /// to every algorithm version. 0 for no limit
+    pub max_marks_per_cluster: u32,
+    /// Most times one character (or emoji grapheme) is kept in a row; the rest of a longer run
+    /// ("哈哈哈…" pasted fifty thousand times) is dropped before wrapping, so widths and heights
+    /// are those of the cut text (see limit_repeats). Whitespace is never cut. Applies to every
+    /// algorithm version. 0 for no limit
+    pub max_char_repeat: u32,
+    /// List-aware wrapping: a line starting with a list marker ("- ", "1. ", "(a) ", "iv. ",
+    /// "第1条 ", "（一）", "①", see list_marker) indents its continuation lines under the text
+    /// after the marker. A marker wider than half the line wraps as an ordinary line
+    pub hanging_indent: bool,
+    /// What hangingIndent writes between a marker and its text: "keep" the source's
+    /// whitespace, or exactly one "space" or one "ideographic" space
+    pub list_marker_spacing: ListMarkerSpacing,
+    /// The line that opens a signature block (see signature.rs): from the first line equal to
+    /// it on, the text is written as it is, without wrapping or trimming. Algorithm version 7
+    /// and later; empty for none
+    pub signature_delimiter: String,
+    /// Most output bytes per input byte justify_checked writes before stopping with
+    /// OutputTooLarge, 0 for no limit; None for max_output_ratio(), the module setting
+    pub max_output_ratio: Option<u32>,
+    /// Reject (true) or ignore (false) fields of this options object JustifyOptions doesn't
+    /// have; None for strict_options(), the module setting
+    pub strict_options: Option<bool>,
+}
+
+impl Default for JustifyOptions {
+    fn default() -> Self {
+        JustifyOptions {
+            max_chars_per_line: DEFAULT_MAX_CHARS_PER_LINE,
+            line_numbers: false,
+            number_start: 1,
+            strip_ansi: false,
+            normalize_nfc: false,
+            trim_trailing: true,
+            markdown: false,
+            hard_break_marker: String::new(),
+            soft_break_marker: String::new(),
+            algorithm_version: latest_algorithm_version(),
+            ruby_syntax: RubySyntax::None,
+            footer_text: String::new(),
+            footer_align: Align::Right,
+            footer_blank_lines: 1,
+            mark_soft_breaks: String::new(),
+            break_marker_end: String::new(),
+            break_marker_start: String::new(),
+            punctuation_break_window: DEFAULT_PUNCTUATION_BREAK_WINDOW,
+            visualize_whitespace: false,
+            escape_html: false,
+            max_unbreakable_run: DEFAULT_MAX_UNBREAKABLE_RUN,
+            preserve_separator_chars: false,
+            break_after_chars: DEFAULT_BREAK_AFTER_CHARS.to_string(),
+            max_marks_per_cluster: DEFAULT_MAX_MARKS_PER_CLUSTER,
+            max_char_repeat: 0,
+            hanging_indent: false,
+            list_marker_spacing: ListMarkerSpacing::Keep,
+            signature_delimiter: DEFAULT_SIGNATURE_DELIMITER.to_string(),
+            max_output_ratio: None,
+            strict_options: None,
+        }
+    }
+}
+
+/// Output of JustifyOptions::justify_with_warnings
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct JustifiedText {
+    pub text: String,
+    pub warnings: Vec<Warning>,
+}
+
+static STRICT_OPTIONS: AtomicBool = AtomicBool::new(false);
+
+/// Reject options objects with fields JustifyOptions doesn't have, instead of ignoring them
+/// (the default); takes effect on the next call
+pub fn set_strict_options(strict: bool) {
+    STRICT_OPTIONS.store(strict, Ordering::Relaxed);
+}
+
+/// Whether unknown option fields are rejected
+pub fn strict_options() -> bool {
+    STRICT_OPTIONS.load(Ordering::Relaxed)
+}
+
+/// Deserializer that only records the field or variant list a derived Deserialize impl asks for
+struct FieldNames;
+
+#[derive(Debug)]
+struct Fields(&'static [&'static str]);
+
+impl fmt::Display for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("field names only")
+    }
+}
+
+impl std::error::Error for Fields {}
+
+impl serde::de::Error for Fields {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Fields(&[])
+    }
+}
+
+impl<'de> Deserializer<'de> for FieldNames {
+    type Error = Fields;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Fields> {
+        Err(Fields(&[]))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Fields> {
+        Err(Fields(fields))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Fields> {
+        Err(Fields(variants))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+/// The camelCase field names an options object may have, in declaration order
+/// Read from the Deserialize impl, so it can't fall out of step with the struct
+pub fn option_names() -> &'static [&'static str] {
+    match JustifyOptions::deserialize(FieldNames) {
+        Err(Fields(fields)) => fields,
+        Ok(_) => &[],
+    }
+}
+
+/// The names a derived Deserialize impl of an enum accepts, in declaration order; empty for
+/// other types
+/// Lets the OptionValue lists be checked against what JSON options actually take
+pub fn variant_names<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    match T::deserialize(FieldNames) {
+        Err(Fields(variants)) => variants,
+        Ok(_) => &[],
+    }
+}
+
+/// The names that aren't JustifyOptions fields, in the order given
+pub fn unknown_option_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let known = option_names();
+    names
+        .into_iter()
+        .filter(|name| !known.contains(name))
+        .collect()
+}
+
+/// Whether an options object with this strictOptions field rejects unknown fields: the field
+/// when it is set, strict_options() otherwise
+pub fn strict_for(strict_field: Option<bool>) -> bool {
+    strict_field.unwrap_or_else(strict_options)
+}
+
+/// When strict, an error naming the first unknown field
+pub fn check_option_names<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    strict: bool,
+) -> Result<(), WebtoolsError> {
+    if !strict {
+        return Ok(());
+    }
+    match unknown_option_names(names).first() {
+        Some(name) => Err(WebtoolsError::invalid_argument(format!(
+            "Unknown option: {}",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "json")]
+impl JustifyOptions {
+    /// Parse and validate options JSON; an empty string means all defaults
+    /// Unknown fields are ignored unless strictOptions (or, without it, strict_options()) is on
+    pub fn from_json(options_json: &str) -> Result<JustifyOptions, WebtoolsError> {
+        if options_json.trim().is_empty() {
+            return Ok(JustifyOptions::default());
+        }
+        let value: serde_json::Value =
+            serde_json::from_str(options_json).map_err(WebtoolsError::invalid_json)?;
+        if let Some(fields) = value.as_object() {
+            let strict = fields
+                .get("strictOptions")
+                .and_then(serde_json::Value::as_bool);
+            check_option_names(fields.keys().map(String::as_str), strict_for(strict))?;
+        }
+        let options = JustifyOptions::deserialize(value).map_err(WebtoolsError::invalid_json)?;
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+#[cfg(feature = "json")]
+/// What validate_options found in one options object
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OptionsReport {
+    /// No errors; unknown fields only make it invalid when strict (see strict_for)
+    pub valid: bool,
+    pub errors: Vec<WebtoolsError>,
+    /// Fields JustifyOptions doesn't have; ignored unless strict
+    pub unknown_fields: Vec<String>,
+    /// Options that are set but overridden by another (see option_warnings); never errors
+    pub warnings: Vec<OptionWarning>,
+}
+
+#[cfg(feature = "json")]
+impl OptionsReport {
+    /// Report for an options object with the given field names that parsed to `parsed`
+    pub fn new(parsed: Result<JustifyOptions, WebtoolsError>, names: &[String]) -> Self {
+        let (mut errors, warnings, strict) = match parsed {
+            Ok(options) => (
+                options.validation_errors(),
+                options.option_warnings(),
+                strict_for(options.strict_options),
+            ),
+            Err(error) => (vec![error], Vec::new(), strict_options()),
+        };
+        let unknown_fields: Vec<String> = unknown_option_names(names.iter().map(String::as_str))
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        if strict {
+            errors.extend(
+                unknown_fields.iter().map(|name| {
+                    WebtoolsError::invalid_argument(format!("Unknown option: {}", name))
+                }),
+            );
+        }
+
+        OptionsReport {
+            valid: errors.is_empty(),
+            errors,
+            unknown_fields,
+            warnings,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+impl JustifyOptions {
+    /// Reject option values the wrappers can't do anything useful with
+    /// Fails with the first of validation_errors
+    pub fn validate(&self) -> Result<(), WebtoolsError> {
+        match self.validation_errors().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem validate would report, in field order
+    pub fn validation_errors(&self) -> Vec<WebtoolsError> {
+        let mut errors = Vec::new();
+        if self.max_chars_per_line < MIN_MAX_CHARS_PER_LINE {
+            errors.push(WebtoolsError::InvalidWidth {
+                min: MIN_MAX_CHARS_PER_LINE,
+                actual: self.max_chars_per_line,
+            });
+        }
+        errors.extend(check_max_chars_per_line(self.max_chars_per_line).err());
+        if [
+            &self.hard_break_marker,
+            &self.soft_break_marker,
+            &self.mark_soft_breaks,
+            &self.break_marker_end,
+            &self.break_marker_start,
+        ]
+        .iter()
+        .any(|marker| marker.contains(['\r', '\n']))
+        {
+            errors.push(WebtoolsError::invalid_argument(
+                "Break markers can't contain line breaks",
+            ));
+        }
+        if self.break_after_chars.contains(char::is_whitespace) {
+            errors.push(WebtoolsError::invalid_argument(
+                "breakAfterChars can't contain whitespace",
+            ));
+        }
+        if self.signature_delimiter.contains(['\r', '\n']) {
+            errors.push(WebtoolsError::invalid_argument(
+                "signatureDelimiter must be a single line",
+            ));
+        }
+        if self.footer_text.contains(['\r', '\n']) {
+            errors.push(WebtoolsError::invalid_argument(
+                "footerText must be a single line",
+            ));
+        }
+        // TrimTrailing would swallow a marker that ends in whitespace
+        if self.mark_soft_breaks.ends_with(char::is_whitespace) {
+            errors.push(WebtoolsError::invalid_argument(
+                "markSoftBreaks can't end with whitespace",
+            ));
+        }
+        if self.break_marker_end.ends_with(char::is_whitespace) {
+            errors.push(WebtoolsError::invalid_argument(
+                "breakMarkerEnd can't end with whitespace",
+            ));
+        }
+        errors.extend(self.conflict_errors());
+        errors.extend(AlgorithmVersion::from_number(self.algorithm_version).err());
+        errors.extend(check_normalization_available(self.normalize_nfc).err());
+        errors
+    }
+
+    /// algorithm_version as an AlgorithmVersion (the latest if it isn't a known version)
+    pub fn algorithm(&self) -> AlgorithmVersion {
+        AlgorithmVersion::from_number(self.algorithm_version).unwrap_or(AlgorithmVersion::LATEST)
+    }
+
+    /// maxOutputRatio, or max_output_ratio() without it
+    pub fn output_ratio(&self) -> u32 {
+        self.max_output_ratio.unwrap_or_else(max_output_ratio)
+    }
+
+    /// Justify text according to these options
+    pub fn justify(&self, text: &str) -> String {
+        self.justify_and_check(text, None).0
+    }
+
+    /// justify, with the wrapped lines checked against the width limit in a strict-checks
+    /// build (see postconditions.rs): a line the wrapper should have broken but didn't is an
+    /// INTERNAL_ERROR instead of output. Other builds return justify's text.
+    /// Output growing past output_ratio() times the size of text stops the wrapping with
+    /// OUTPUT_TOO_LARGE (see expansion.rs).
+    pub fn justify_checked(&self, text: &str) -> Result<String, WebtoolsError> {
+        let mut guard = ExpansionGuard::with_ratio(self.output_ratio());
+        let (output, checked) = self.justify_and_check(text, guard.ceiling(text.len()));
+        guard.add(text.len(), &output)?;
+        checked.map(|()| output)
+    }
+
+    /// The output and its postcondition check; with an output ceiling, the wrapper stops once
+    /// it is past that many bytes
+    fn justify_and_check(
+        &self,
+        text: &str,
+        output_ceiling: Option<usize>,
+    ) -> (String, Result<(), WebtoolsError>) {
+        let (body, signature) = self.split_signature(text);
+        let after_body = body.len() + signature.map_or(0, str::len) < text.len();
+        let prepared = self.prepare(body);
+        let repeats = limit_repeats(&prepared, self.max_char_repeat);
+        let limited = limit_marks(&repeats, self.max_marks_per_cluster);
+        let text = &*limited;
+        // Markers are matched on the raw text, before any width is measured
+        let breaking = LineBreaking {
+            trailing: self.trailing(),
+            hard_break: Some(self.hard_break_marker.as_str()).filter(|m| !m.is_empty()),
+            soft_break: Some(self.soft_break_marker.as_str()).filter(|m| !m.is_empty()),
+            ruby: self.ruby_syntax,
+            mark_soft_breaks: Some(self.mark_soft_breaks.as_str()).filter(|m| !m.is_empty()),
+            break_markers: self.break_markers(),
+            punctuation_window: None,
+            narrow_astral: false,
+            max_unbreakable_run: Some(self.max_unbreakable_run).filter(|&run| run > 0),
+            keep_separators: self.preserve_separator_chars,
+            break_after: None,
+            list_markers: self.hanging_indent.then_some(self.list_marker_spacing),
+            code_spans: false,
+            bom_joiner: false,
+            split_runs: false,
+            output_ceiling,
+        };
+        // Each version keeps its own path so later ones can't change its output
+        let justified = match self.algorithm() {
+            AlgorithmVersion::V1 => self.wrap(
+                text,
+                &LineBreaking {
+                    split_runs: true,
+                    ..breaking
+                },
+            ),
+            AlgorithmVersion::V2 => self.wrap(
+                text,
+                &LineBreaking {
+                    punctuation_window: Some(self.punctuation_break_window),
+                    ..breaking
+                },
+            ),
+            AlgorithmVersion::V3 => self.wrap(
+                text,
+                &LineBreaking {
+                    punctuation_window: Some(self.punctuation_break_window),
+                    narrow_astral: true,
+                    ..breaking
+                },
+            ),
+            AlgorithmVersion::V4 => self.wrap(
+                text,
+                &LineBreaking {
+                    punctuation_window: Some(self.punctuation_break_window),
+                    narrow_astral: true,
+                    break_after: Some(self.break_after_chars.as_str())
+                        .filter(|set| !set.is_empty()),
+                    ..breaking
+                },
+            ),
+            AlgorithmVersion::V5 => self.wrap(
+                text,
+                &LineBreaking {
+                    punctuation_window: Some(self.punctuation_break_window),
+                    narrow_astral: true,
+                    break_after: Some(self.break_after_chars.as_str())
+                        .filter(|set| !set.is_empty()),
+                    code_spans: self.markdown,
+                    ..breaking
+                },
+            ),
+            AlgorithmVersion::V6 | AlgorithmVersion::V7 => self.wrap(
+                text,
+                &LineBreaking {
+                    punctuation_window: Some(self.punctuation_break_window),
+                    narrow_astral: true,
+                    break_after: Some(self.break_after_chars.as_str())
+                        .filter(|set| !set.is_empty()),
+                    code_spans: self.markdown,
+                    bom_joiner: true,
+                    ..breaking
+                },
+            ),
+        };
+        // The signature's lines are as wide as they were written, and the footer is cut to fit
+        // on its own, so the check stops before them; a signature with no body in front of it
+        // starts on the first line
+        let wrapped_lines = match signature {
+            Some(_) if !after_body => 0,
+            _ => justified.split("\r\n").count(),
+        };
+        let mut justified = justified;
+        if let Some(signature) = signature {
+            append_signature(&mut justified, signature, after_body);
+        }
+        let output = append_footer(
+            &justified,
+            &self.footer_text,
+            self.max_chars_per_line,
+            self.footer_align,
+            self.footer_blank_lines,
+        );
+        // Render passes last, once every width is decided (see postconditions.rs)
+        let output = if self.visualize_whitespace {
+            visualize_whitespace(&output)
+        } else {
+            output
+        };
+        let output = if self.escape_html {
+            escape_html(&output)
+        } else {
+            output
+        };
+        let checked = check_wrapped(
+            &output,
+            &WrapCheck {
+                wrapped_lines,
+                ..self.wrap_check(text)
+            },
+        );
+        (output, checked)
+    }
+
+    /// How the output for text is measured: past the gutter, ruby by its base, without the
+    /// soft break marker, astral letters as the algorithm version has them, through the render
+    /// passes these options run, and with trailing whitespace allowed as trimTrailing has it
+    fn wrap_check(&self, text: &str) -> WrapCheck<'_> {
+        let what = "JustifyOptions::justify";
+        let check = if self.line_numbers {
+            numbered_check(what, text, self.max_chars_per_line, self.number_start)
+        } else {
+            WrapCheck::columns(what, self.max_chars_per_line)
+        };
+        WrapCheck {
+            ruby: self.ruby_syntax,
+            soft_break_marker: &self.mark_soft_breaks,
+            narrow_astral: self.algorithm() >= AlgorithmVersion::V3,
+            list_markers: self.hanging_indent,
+            code_spans: self.markdown && self.algorithm() >= AlgorithmVersion::V5,
+            bom_joiner: self.algorithm() >= AlgorithmVersion::V6,
+            render: RenderPasses {
+                visualized: self.visualize_whitespace,
+                html_escaped: self.escape_html,
+            },
+            trailing: self.trailing(),
+            ..check
+        }
+    }
+
+    /// justify, with a warning for every fallback it took (see JustifyOptions::warnings); the
+    /// text is exactly justify's
+    pub fn justify_with_warnings(&self, text: &str) -> JustifiedText {
+        JustifiedText {
+            text: self.justify(text),
+            warnings: self.warnings(text),
+        }
+    }
+
+    /// text split into the body that is wrapped and its signature, which only is from algorithm
+    /// V7 on
+    pub(crate) fn split_signature<'a>(&self, text: &'a str) -> (&'a str, Option<&'a str>) {
+        if self.algorithm() < AlgorithmVersion::V7 {
+            return (text, None);
+        }
+        split_signature(text, &self.signature_delimiter)
+    }
+
+    /// text with stripAnsi and normalizeNfc applied
+    pub(crate) fn prepare<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = if self.strip_ansi {
+            Cow::Owned(strip_ansi(text))
+        } else {
+            Cow::Borrowed(text)
+        };
+        if !self.normalize_nfc {
+            return text;
+        }
+        let normalized = match to_nfc(&text) {
+            Cow::Owned(normalized) => Some(normalized),
+            Cow::Borrowed(_) => None,
+        };
+        normalized.map_or(text, Cow::Owned)
+    }
+
+    fn wrap(&self, text: &str, breaking: &LineBreaking) -> String {
+        if self.line_numbers {
+            justify_text_numbered_with(text, self.max_chars_per_line, self.number_start, breaking)
+        } else {
+            justify_text_breaking(text, self.max_chars_per_line, breaking)
+        }
+    }
+
+    /// break_marker_end and break_marker_start, unless both are empty
+    fn break_markers(&self) -> Option<BreakMarkers<'_>> {
+        (!self.break_marker_end.is_empty() || !self.break_marker_start.is_empty()).then(|| {
+            BreakMarkers {
+                end: &self.break_marker_end,
+                start: &self.break_marker_start,
+            }
+        })
+    }
+
+    /// Trailing whitespace handling selected by trim_trailing and markdown
+    pub fn trailing(&self) -> TrailingWhitespace {
+        match (self.trim_trailing, self.markdown) {
+            (false, _) => TrailingWhitespace::Keep,
+            (true, true) => TrailingWhitespace::KeepHardBreaks,
+            (true, false) => TrailingWhitespace::Trim,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn options_round_trip_through_json() {
+        let options = JustifyOptions {
+            max_chars_per_line: 20,
+            line_numbers: true,
+            number_start: 7,
+            ruby_syntax: RubySyntax::Aozora,
+            footer_text: "— 出典".to_string(),
+            footer_align: Align::Center,
+            list_marker_spacing: ListMarkerSpacing::Ideographic,
+            max_output_ratio: Some(4),
+            ..JustifyOptions::default()
+        };
+        let json = serde_json::to_string(&options).unwrap();
+        assert!(json.contains(r#""maxCharsPerLine":20"#), "{}", json);
+        assert!(json.contains(r#""rubySyntax":"aozora""#), "{}", json);
+        assert_eq!(JustifyOptions::from_json(&json).unwrap(), options);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        assert_eq!(
+            JustifyOptions::from_json("").unwrap(),
+            JustifyOptions::default()
+        );
+        assert_eq!(
+            JustifyOptions::from_json("{}").unwrap(),
+            JustifyOptions::default()
+        );
+        let options = JustifyOptions::from_json(r#"{"trimTrailing": false}"#).unwrap();
+        assert!(!options.trim_trailing);
+        assert_eq!(options.max_chars_per_line, DEFAULT_MAX_CHARS_PER_LINE);
+        assert_eq!(options.algorithm_version, latest_algorithm_version());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn wrong_types_are_invalid_json() {
+        let error = JustifyOptions::from_json(r#"{"maxCharsPerLine": "wide"}"#).unwrap_err();
+        assert_eq!(error.code(), "INVALID_JSON");
+        let error = JustifyOptions::from_json(r#"{"rubySyntax": "pinyin"}"#).unwrap_err();
+        assert_eq!(error.code(), "INVALID_JSON");
+    }
+
+    #[test]
+    fn trim_trailing_off_keeps_spaces_at_the_break() {
+        let keep = JustifyOptions {
+            max_chars_per_line: 4,
+            trim_trailing: false,
+            ..JustifyOptions::default()
+        };
+        // The space after "ab" is where the line broke; 漢字 is four columns
+        assert_eq!(keep.justify("ab 漢字漢字"), "ab \r\n漢字\r\n漢字");
+        let keep = JustifyOptions {
+            max_chars_per_line: 6,
+            ..keep
+        };
+        assert_eq!(keep.justify("漢字\u{3000}漢字"), "漢字\u{3000}\r\n漢字");
+    }
+
+    #[test]
+    fn trim_trailing_removes_them_by_default() {
+        let trim = JustifyOptions {
+            max_chars_per_line: 4,
+            ..JustifyOptions::default()
+        };
+        assert!(trim.trim_trailing);
+        assert_eq!(trim.justify("ab 漢字漢字"), "ab\r\n漢字\r\n漢字");
+        assert_eq!(trim.justify("漢字 ab cd"), "漢字\r\n ab\r\ncd");
+        let trim = JustifyOptions {
+            max_chars_per_line: 6,
+            ..trim
+        };
+        assert_eq!(trim.justify("漢字\u{3000}漢字"), "漢字\r\n漢字");
+    }
+
+    #[test]
+    fn preserved_separators_are_kept_between_words() {
+        for (line, width, expected) in [
+            ("abc\u{3000}def", 8, "abc\u{3000}def"),
+            ("abc\u{3000}def", 7, "abc\r\ndef"),
+            ("ＡＢＣ\u{3000}ＤＥＦ", 20, "ＡＢＣ\u{3000}ＤＥＦ"),
+            ("ＡＢＣ\u{3000}ＤＥＦ", 19, "ＡＢＣ\r\nＤＥＦ"),
+            ("a\tb  c", 6, "a\tb  c"),
+        ] {
+            let options = JustifyOptions {
+                max_chars_per_line: width,
+                preserve_separator_chars: true,
+                ..JustifyOptions::default()
+            };
+            assert_eq!(options.justify(line), expected, "{line:?} at width {width}");
+        }
+    }
+
+    #[test]
+    fn widths_up_to_the_cap_are_accepted() {
+        assert_eq!(check_max_chars_per_line(MAX_MAX_CHARS_PER_LINE), Ok(()));
+        let too_large = WebtoolsError::WidthTooLarge {
+            max: MAX_MAX_CHARS_PER_LINE,
+            actual: MAX_MAX_CHARS_PER_LINE + 1,
+        };
+        assert_eq!(
+            check_max_chars_per_line(MAX_MAX_CHARS_PER_LINE + 1),
+            Err(too_large.clone())
+        );
+        let options = JustifyOptions {
+            max_chars_per_line: MAX_MAX_CHARS_PER_LINE + 1,
+            ..JustifyOptions::default()
+        };
+        assert!(options.validation_errors().contains(&too_large));
+    }
+
+    #[test]
+    fn markdown_keeps_exactly_two_trailing_spaces() {
+        let markdown = JustifyOptions {
+            max_chars_per_line: 20,
+            markdown: true,
+            ..JustifyOptions::default()
+        };
+        assert_eq!(
+            markdown.justify("line one  \nline two"),
+            "line one  \r\nline two"
+        );
+        // One space or three isn't a hard break
+        assert_eq!(markdown.justify("a \nb"), "a\r\nb");
+        assert_eq!(markdown.justify("a   \nb"), "a\r\nb");
+        let plain = JustifyOptions {
+            markdown: false,
+            ..markdown
+        };
+        assert_eq!(
+            plain.justify("line one  \nline two"),
+            "line one\r\nline two"
+        );
+    }
+
+    #[test]
+    fn option_names_are_the_camel_case_fields() {
+        let names = option_names();
+        assert_eq!(names.len(), 30);
+        assert!(names.contains(&"maxCharsPerLine"));
+        assert!(names.contains(&"signatureDelimiter"));
+        assert_eq!(variant_names::<RubySyntax>(), ["none", "kanji", "aozora"]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_defaults_round_trip_through_json() {
+        let json = serde_json::to_string(&JustifyOptions::default()).unwrap();
+        assert_eq!(
+            JustifyOptions::from_json(&json).unwrap(),
+            JustifyOptions::default()
+        );
+        // Every option is there to be looked at
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let fields = value.as_object().unwrap();
+        let missing: Vec<&&str> = option_names()
+            .iter()
+            .filter(|name| !fields.contains_key(**name))
+            .collect();
+        assert!(missing.is_empty(), "{:?}", missing);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn unknown_fields_are_rejected_only_when_strict() {
+        let _limits = crate::limits::lock_limits();
+        let lenient = JustifyOptions::from_json(r#"{"maxCharsPerLine": 20, "bogus": 1}"#);
+        assert_eq!(lenient.unwrap().max_chars_per_line, 20);
+        let strict = JustifyOptions::from_json(r#"{"bogus": 1, "strictOptions": true}"#);
+        assert_eq!(
+            strict.unwrap_err(),
+            WebtoolsError::invalid_argument("Unknown option: bogus")
+        );
+        assert!(JustifyOptions::from_json(r#"{"strictOptions": true}"#).is_ok());
+        assert_eq!(
+            unknown_option_names(["maxCharsPerLine", "bogus", "lineNumbers", "x"]),
+            ["bogus", "x"]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    fn report(options_json: &str) -> String {
+        let value: serde_json::Value = serde_json::from_str(options_json).unwrap();
+        let names: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        let parsed = JustifyOptions::deserialize(value).map_err(WebtoolsError::invalid_json);
+        OptionsReport::new(parsed, &names).to_json()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn validation_reports_every_problem() {
+        let _limits = crate::limits::lock_limits();
+        // from_json stops at the first
+        let json = r#"{"maxCharsPerLine": 0, "markSoftBreaks": "x ", "bogus": 1}"#;
+        assert_eq!(
+            JustifyOptions::from_json(json).unwrap_err().code(),
+            "INVALID_WIDTH"
+        );
+        assert_eq!(
+            report(json),
+            r#"{"valid":false,"errors":[{"code":"INVALID_WIDTH","message":"Invalid width: 0 (minimum 1)","details":{"min":1,"actual":0}},{"code":"INVALID_ARGUMENT","message":"markSoftBreaks can't end with whitespace","details":{}}],"unknownFields":["bogus"],"warnings":[]}"#
+        );
+        assert_eq!(
+            report(r#"{"bogus": 1, "strictOptions": true}"#),
+            r#"{"valid":false,"errors":[{"code":"INVALID_ARGUMENT","message":"Unknown option: bogus","details":{}}],"unknownFields":["bogus"],"warnings":[]}"#
+        );
+        // A bad enum value is the one error
+        assert_eq!(
+            report(r#"{"rubySyntax": "pinyin", "maxCharsPerLine": 0}"#),
+            r#"{"valid":false,"errors":[{"code":"INVALID_JSON","message":"Invalid JSON: unknown variant `pinyin`, expected one of `none`, `kanji`, `aozora`","details":{}}],"unknownFields":[],"warnings":[]}"#
+        );
+        // An overridden option is a warning, not an error
+        assert_eq!(
+            report(r#"{"numberStart": 3}"#),
+            r#"{"valid":true,"errors":[],"unknownFields":[],"warnings":[{"field":"numberStart","overriddenBy":"lineNumbers","message":"numberStart has no effect without lineNumbers"}]}"#
+        );
+        assert_eq!(
+            report("{}"),
+            r#"{"valid":true,"errors":[],"unknownFields":[],"warnings":[]}"#
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn an_options_strictness_overrides_the_module_setting() {
+        let _limits = crate::limits::lock_limits();
+        for strict in [false, true] {
+            set_strict_options(strict);
+            for (field, rejected) in [
+                ("", strict),
+                (r#","strictOptions":true"#, true),
+                (r#","strictOptions":false"#, false),
+            ] {
+                let json = format!(r#"{{"nope":1{field}}}"#);
+                assert_eq!(
+                    JustifyOptions::from_json(&json).is_err(),
+                    rejected,
+                    "{json} with strict options {strict}"
+                );
+                assert_eq!(
+                    report(&json).starts_with(r#"{"valid":true"#),
+                    !rejected,
+                    "report of {json} with strict options {strict}"
+                );
+            }
+        }
+        set_strict_options(false);
+    }
+}
@@ -0,0 +1,672 @@
+use std::borrow::Borrow;
+#[cfg(feature = "json")]
+use std::borrow::Cow;
+#[cfg(feature = "json")]
+use std::ops::Range;
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+#[cfg(feature = "json")]
+use crate::line_height::LineHeightSum;
+#[cfg(feature = "json")]
+use crate::{count_wrapped_lines_of_output, justify_text, ScriptLineHeights};
+use crate::{paragraphs, wrapped_lines, TextCounts};
+
+/// How many lines short of the limit a block may end to land on a paragraph break
+pub const PARAGRAPH_BREAK_TOLERANCE: usize = 3;
+
+#[cfg(feature = "json")]
+/// Split wrapped lines into consecutive blocks of at most `capacity` lines
+/// When a blank line (paragraph break) falls within `tolerance` lines of the
+/// limit the block ends there instead; blank lines at block starts are dropped.
+/// avoid_widows moves block ends as paginate_lines does (see avoid_widow)
+pub(crate) fn split_blocks<S: AsRef<str>>(
+    lines: &[S],
+    capacity: usize,
+    tolerance: usize,
+    avoid_widows: bool,
+) -> Vec<Range<usize>> {
+    let capacity = capacity.max(1);
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    while start < lines.len() {
+        if lines[start].as_ref().is_empty() {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start.saturating_add(capacity).min(lines.len());
+        if end < lines.len() && !lines[end].as_ref().is_empty() {
+            let earliest = (end.saturating_sub(tolerance)).max(start + 1);
+            if let Some(blank) = (earliest..end)
+                .rev()
+                .find(|&i| lines[i].as_ref().is_empty())
+            {
+                end = blank;
+            }
+        }
+        if avoid_widows {
+            end = avoid_widow(lines, start, end, capacity);
+        }
+
+        blocks.push(start..end);
+        start = end;
+    }
+
+    blocks
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImageBlock {
+    text: String,
+    line_count: usize,
+    height_px: f64,
+}
+
+#[cfg(feature = "json")]
+/// Split wrapped text into several images that each stay under max_height_px
+/// Returns JSON array of {text, lineCount, heightPx}; blocks prefer ending at paragraph breaks.
+/// A block has room for max_height_px of the text's tallest lines, and heightPx adds up its
+/// lines as layout_text does (see ScriptLineHeights).
+/// avoid_widows keeps a paragraph's last line off the top of an image
+pub fn paginate_for_images(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    max_height_px: f64,
+    avoid_widows: bool,
+) -> String {
+    let lines = wrapped_lines(text, max_chars_per_line);
+
+    let heights = ScriptLineHeights::current();
+    let line_height_px = font_size_px * line_height_factor;
+    // Tolerate float noise so a multiple of the line height isn't one line short
+    let capacity = (max_height_px / (line_height_px * heights.tallest_in(text)) + 1e-9).floor();
+    let capacity = if capacity.is_finite() && capacity >= 1.0 {
+        capacity.min(usize::MAX as f64) as usize
+    } else {
+        1
+    };
+
+    let blocks: Vec<ImageBlock> =
+        split_blocks(&lines, capacity, PARAGRAPH_BREAK_TOLERANCE, avoid_widows)
+            .into_iter()
+            .map(|range| ImageBlock {
+                text: lines[range.clone()].join("\r\n"),
+                line_count: range.len(),
+                height_px: LineHeightSum::of_lines(&lines[range.clone()], heights) * line_height_px,
+            })
+            .collect();
+
+    serde_json::to_string(&blocks).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Whether wrapped line `index` is the closing line of a multi-line paragraph
+fn is_paragraph_tail<S: AsRef<str>>(lines: &[S], index: usize) -> bool {
+    index > 0
+        && !lines[index].as_ref().is_empty()
+        && !lines[index - 1].as_ref().is_empty()
+        && lines
+            .get(index + 1)
+            .is_none_or(|next| next.as_ref().is_empty())
+}
+
+/// End for a block of lines[start..end] that doesn't leave a paragraph's last line (a widow)
+/// at the top of the next block
+/// Takes the widow onto this block when that stays within capacity, otherwise moves this
+/// block's last line to the next block so the widow has company; growing deviates less from
+/// capacity, so it wins whenever it fits. A one-line block is left alone, so paging always
+/// makes progress even at a capacity of 1.
+fn avoid_widow<S: AsRef<str>>(lines: &[S], start: usize, end: usize, capacity: usize) -> usize {
+    if end >= lines.len() || !is_paragraph_tail(lines, end) {
+        end
+    } else if end + 1 - start <= capacity {
+        end + 1
+    } else if end - start > 1 {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// Page the body lines of every form-feed section; capacity(page_index) gives the
+/// number of body lines page `page_index` can hold (at least 1 is always used)
+fn paginate_sections(
+    text: &str,
+    max_chars_per_line: u32,
+    capacity: impl Fn(usize) -> usize,
+    avoid_widows: bool,
+) -> Vec<String> {
+    page_wrapped_sections(
+        text.split('\u{000C}')
+            .map(|section| wrapped_lines(section, max_chars_per_line)),
+        capacity,
+        avoid_widows,
+    )
+}
+
+/// paginate_sections over sections that are already wrapped, one line list per section
+pub(crate) fn page_wrapped_sections<S: AsRef<str> + Borrow<str>>(
+    sections: impl IntoIterator<Item = Vec<S>>,
+    capacity: impl Fn(usize) -> usize,
+    avoid_widows: bool,
+) -> Vec<String> {
+    let mut pages = Vec::new();
+
+    for lines in sections {
+        let mut start = 0;
+        while start < lines.len() {
+            let page_capacity = capacity(pages.len()).max(1);
+            let mut end = (start + page_capacity).min(lines.len());
+            if avoid_widows {
+                end = avoid_widow(&lines, start, end, page_capacity);
+            }
+
+            pages.push(lines[start..end].join("\r\n"));
+            start = end;
+        }
+    }
+
+    pages
+}
+
+/// Paginate wrapped text into pages of at most lines_per_page lines
+/// Wrapped lines are never split; avoid_widows keeps a paragraph's last line off the top of a page.
+/// Form feeds (U+000C) in the source force a page break
+pub fn paginate_lines(
+    text: &str,
+    max_chars_per_line: u32,
+    lines_per_page: u32,
+    avoid_widows: bool,
+) -> Vec<String> {
+    let capacity = lines_per_page as usize;
+    paginate_sections(text, max_chars_per_line, |_| capacity, avoid_widows)
+}
+
+#[cfg(feature = "json")]
+/// Distribute wrapped lines into columns, filling each top to bottom
+/// Returns JSON array of columns, each an array of lines. With balance the per-column
+/// height shrinks so the last column isn't nearly empty. Content that needs more than
+/// `columns` columns at lines_per_column spills into extra columns rather than being dropped.
+pub fn layout_columns(
+    text: &str,
+    max_chars_per_line: u32,
+    columns: u32,
+    lines_per_column: u32,
+    balance: bool,
+) -> String {
+    let lines = wrapped_lines(text, max_chars_per_line);
+
+    let mut capacity = lines_per_column.max(1) as usize;
+    if balance {
+        let columns = columns.max(1) as usize;
+        capacity = capacity.min(lines.len().div_ceil(columns).max(1));
+    }
+
+    let result: Vec<&[Cow<str>]> = split_blocks(
+        &lines,
+        capacity,
+        PARAGRAPH_BREAK_TOLERANCE.min(capacity / 4),
+        false,
+    )
+    .into_iter()
+    .map(|range| &lines[range])
+    .collect();
+
+    serde_json::to_string(&result).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BandedPage {
+    header: String,
+    body: String,
+    footer: String,
+    line_count: usize,
+}
+
+#[cfg(feature = "json")]
+/// Substitute {page} and {pages} in a footer template
+fn fill_footer(template: &str, page: usize, pages: usize) -> String {
+    template
+        .replace("{page}", &page.to_string())
+        .replace("{pages}", &pages.to_string())
+}
+
+#[cfg(feature = "json")]
+/// Wrapped line count of a band; an empty band takes no lines
+fn band_lines(text: &str, max_chars_per_line: u32) -> usize {
+    count_wrapped_lines_of_output(&justify_text(text, max_chars_per_line)) as usize
+}
+
+#[cfg(feature = "json")]
+/// Paginate with a header band and a footer band on each page
+/// Returns JSON array of {header, body, footer, lineCount}. The header appears on the first page
+/// (every page with header_on_all_pages); footer_template may use {page} and {pages}.
+/// Both bands are wrapped at max_chars_per_line and their lines are taken out of lines_per_page,
+/// so header + body + footer never exceeds it (a page always keeps at least one body line).
+pub fn paginate_with_bands(
+    text: &str,
+    max_chars_per_line: u32,
+    lines_per_page: u32,
+    header_text: &str,
+    footer_template: &str,
+    header_on_all_pages: bool,
+) -> String {
+    let lines_per_page = lines_per_page as usize;
+    let header_lines = band_lines(header_text, max_chars_per_line);
+
+    // The footer's wrapped height depends on the page count and vice versa: size the footer
+    // with the widest numbers ({page} = {pages}) and repeat until the page count settles
+    let mut page_count = 1;
+    let mut bodies = Vec::new();
+    for _ in 0..8 {
+        let footer_lines = band_lines(
+            &fill_footer(footer_template, page_count, page_count),
+            max_chars_per_line,
+        );
+        bodies = paginate_sections(
+            text,
+            max_chars_per_line,
+            |page| {
+                let header = if page == 0 || header_on_all_pages {
+                    header_lines
+                } else {
+                    0
+                };
+                lines_per_page.saturating_sub(header + footer_lines)
+            },
+            false,
+        );
+
+        let settled = bodies.len().max(1).to_string().len() == page_count.to_string().len();
+        page_count = bodies.len().max(1);
+        if settled {
+            break;
+        }
+    }
+
+    let pages: Vec<BandedPage> = bodies
+        .into_iter()
+        .enumerate()
+        .map(|(index, body)| {
+            let header = if index == 0 || header_on_all_pages {
+                justify_text(header_text, max_chars_per_line)
+            } else {
+                String::new()
+            };
+            let footer = justify_text(
+                &fill_footer(footer_template, index + 1, page_count),
+                max_chars_per_line,
+            );
+            let line_count = count_wrapped_lines_of_output(&header) as usize
+                + count_wrapped_lines_of_output(&body) as usize
+                + count_wrapped_lines_of_output(&footer) as usize;
+
+            BandedPage {
+                header,
+                body,
+                footer,
+                line_count,
+            }
+        })
+        .collect();
+
+    serde_json::to_string(&pages).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Number of sections the greedy pass needs when no section may exceed `limit`
+fn sections_needed(sizes: &[u64], limit: u64) -> usize {
+    let mut sections = 1;
+    let mut current = 0;
+    for &size in sizes {
+        if current > 0 && current + size > limit {
+            sections += 1;
+            current = 0;
+        }
+        current += size;
+    }
+    sections
+}
+
+/// Split a document into n similarly sized sections for an image thread
+/// Cuts only at blank-line paragraph boundaries; returns the section texts in order.
+/// Section size is the total display width of its paragraphs. With fewer paragraphs than
+/// sections, each paragraph becomes its own section.
+pub fn split_even_sections(text: &str, n: u32) -> Vec<&str> {
+    let paragraphs = paragraphs(text);
+    if paragraphs.is_empty() {
+        return Vec::new();
+    }
+
+    let sizes: Vec<u64> = paragraphs
+        .iter()
+        .map(|(_, paragraph)| TextCounts::of(paragraph).display_width as u64)
+        .collect();
+    let sections = (n.max(1) as usize).min(paragraphs.len());
+
+    // Smallest achievable maximum section size
+    let (mut low, mut high) = (
+        sizes.iter().copied().max().unwrap_or(0),
+        sizes.iter().sum::<u64>(),
+    );
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if sections_needed(&sizes, mid) <= sections {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    // Fill sections greedily under the limit, leaving enough paragraphs for the rest
+    let offset_of = |slice: &str| slice.as_ptr() as usize - text.as_ptr() as usize;
+    let mut result = Vec::with_capacity(sections);
+    let mut first = 0;
+    for section in 0..sections {
+        let remaining_sections = sections - section - 1;
+        let mut last = first;
+        let mut size = sizes[first];
+        while last + 1 < paragraphs.len() - remaining_sections
+            && (section + 1 == sections || size + sizes[last + 1] <= low)
+        {
+            last += 1;
+            size += sizes[last];
+        }
+
+        let start = offset_of(paragraphs[first].1);
+        let end = offset_of(paragraphs[last].1) + paragraphs[last].1.len();
+        result.push(&text[start..end]);
+        first = last + 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "json")]
+    fn image_blocks(text: &str, max_height_px: f64) -> Vec<(String, u64, f64)> {
+        let blocks: serde_json::Value = serde_json::from_str(&paginate_for_images(
+            text,
+            5,
+            10.0,
+            1.0,
+            max_height_px,
+            false,
+        ))
+        .unwrap();
+        blocks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| {
+                (
+                    block["text"].as_str().unwrap().to_string(),
+                    block["lineCount"].as_u64().unwrap(),
+                    block["heightPx"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn images_end_at_paragraph_breaks() {
+        // Nine 10 px lines, three to an image; each image ends at the blank line before the
+        // third, and the blank lines start no image
+        let text = "alpha beta\n\ngamma delta\n\neps zeta eta";
+        assert_eq!(
+            image_blocks(text, 30.0),
+            [
+                ("alpha\r\nbeta".to_string(), 2, 20.0),
+                ("gamma\r\ndelta".to_string(), 2, 20.0),
+                ("eps\r\nzeta\r\neta".to_string(), 3, 30.0),
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn a_paragraph_taller_than_an_image_is_cut() {
+        let blocks = image_blocks("one two three four five six seven", 30.0);
+        let counts: Vec<u64> = blocks.iter().map(|block| block.1).collect();
+        assert_eq!(counts, [3, 3, 1]);
+        assert!(blocks.iter().all(|block| block.2 <= 30.0));
+        // Less than a line's height still takes a line per image
+        assert_eq!(image_blocks("one two", 4.0).len(), 2);
+    }
+
+    #[test]
+    fn pages_of_n_lines() {
+        let text = "one two three four five six";
+        assert_eq!(
+            paginate_lines(text, 5, 2, false),
+            ["one\r\ntwo", "three\r\nfour", "five\r\nsix"]
+        );
+        assert_eq!(
+            paginate_lines(text, 5, 4, false),
+            ["one\r\ntwo\r\nthree\r\nfour", "five\r\nsix"]
+        );
+        assert!(paginate_lines("", 5, 4, false).is_empty());
+        // A form feed starts a new page however much room is left
+        assert_eq!(
+            paginate_lines("one\u{000C}two", 5, 4, false),
+            ["one", "two"]
+        );
+    }
+
+    #[test]
+    fn widows_are_pulled_back() {
+        // "gamma" would open page 2 as the last line of its paragraph
+        let text = "alpha beta gamma\n\ndelta";
+        assert_eq!(
+            paginate_lines(text, 5, 2, false),
+            ["alpha\r\nbeta", "gamma\r\n", "delta"]
+        );
+        assert_eq!(
+            paginate_lines(text, 5, 2, true),
+            ["alpha", "beta\r\ngamma", "\r\ndelta"]
+        );
+    }
+
+    #[test]
+    fn a_boundary_on_the_last_line_moves_back() {
+        // Three a page would open page 2 with "delta", the last line of its paragraph
+        let text = "alpha beta gamma delta\n\nzeta";
+        assert_eq!(
+            paginate_lines(text, 5, 3, false),
+            ["alpha\r\nbeta\r\ngamma", "delta\r\n\r\nzeta"]
+        );
+        assert_eq!(
+            paginate_lines(text, 5, 3, true),
+            ["alpha\r\nbeta", "gamma\r\ndelta\r\n", "zeta"]
+        );
+        // A boundary anywhere else is left where it was
+        assert_eq!(
+            paginate_lines(text, 5, 2, true),
+            paginate_lines(text, 5, 2, false)
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn images_keep_widows_off_their_tops() {
+        let text = "alpha beta gamma delta\n\nzeta";
+        let blocks = |avoid_widows| -> Vec<String> {
+            let blocks: serde_json::Value =
+                serde_json::from_str(&paginate_for_images(text, 5, 10.0, 1.0, 30.0, avoid_widows))
+                    .unwrap();
+            blocks
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|block| block["text"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            blocks(false),
+            ["alpha\r\nbeta\r\ngamma", "delta\r\n\r\nzeta"]
+        );
+        assert_eq!(blocks(true), ["alpha\r\nbeta", "gamma\r\ndelta", "zeta"]);
+    }
+
+    #[test]
+    fn pages_of_one_line_still_make_progress() {
+        let text = "alpha beta gamma\n\ndelta";
+        assert_eq!(
+            paginate_lines(text, 5, 1, true),
+            ["alpha", "beta", "gamma", "", "delta"]
+        );
+        assert_eq!(
+            paginate_lines(text, 5, 0, true),
+            paginate_lines(text, 5, 1, true)
+        );
+    }
+
+    #[test]
+    fn widow_control_keeps_every_line_in_order() {
+        for text in crate::tests::random_texts(200) {
+            let lines = wrapped_lines(&text, 6).join("\r\n");
+            for lines_per_page in 1..6 {
+                let pages = paginate_lines(&text, 6, lines_per_page, true);
+                assert_eq!(pages.join("\r\n"), lines, "{:?} {}", text, lines_per_page);
+                for page in &pages {
+                    let count = page.split("\r\n").count() as u32;
+                    assert!(count <= lines_per_page, "{:?} {}", text, lines_per_page);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn columns_fill_in_reading_order() {
+        let text = "one two three four five";
+        assert_eq!(
+            layout_columns(text, 5, 2, 4, false),
+            r#"[["one","two","three","four"],["five"]]"#
+        );
+        // Balanced, the five lines are shared three and two
+        assert_eq!(
+            layout_columns(text, 5, 2, 4, true),
+            r#"[["one","two","three"],["four","five"]]"#
+        );
+        assert_eq!(
+            layout_columns("one two", 5, 2, 10, true),
+            r#"[["one"],["two"]]"#
+        );
+        assert_eq!(
+            layout_columns("one two", 5, 2, 10, false),
+            r#"[["one","two"]]"#
+        );
+        // Past the columns asked for, the text goes on in more of them
+        assert_eq!(
+            layout_columns(text, 5, 2, 2, false),
+            r#"[["one","two"],["three","four"],["five"]]"#
+        );
+    }
+
+    #[cfg(feature = "json")]
+    fn banded_pages(
+        lines_per_page: u32,
+        header_text: &str,
+        header_on_all_pages: bool,
+    ) -> Vec<(String, String, String, u64)> {
+        let pages: serde_json::Value = serde_json::from_str(&paginate_with_bands(
+            "aa bb cc dd ee",
+            4,
+            lines_per_page,
+            header_text,
+            "{page}/{pages}",
+            header_on_all_pages,
+        ))
+        .unwrap();
+        pages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|page| {
+                let band = |name: &str| page[name].as_str().unwrap().to_string();
+                let line_count = page["lineCount"].as_u64().unwrap();
+                (band("header"), band("body"), band("footer"), line_count)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn footer_fills_the_rest_of_the_page() {
+        let page = |body: &str, footer: &str, lines| {
+            (String::new(), body.to_string(), footer.to_string(), lines)
+        };
+        // Two body lines and the footer make each page exactly three lines
+        assert_eq!(
+            banded_pages(3, "", false),
+            [
+                page("aa\r\nbb", "1/3", 3),
+                page("cc\r\ndd", "2/3", 3),
+                page("ee", "3/3", 2)
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn header_of_two_lines() {
+        // "Big" / "news" leaves one body line under it, and none over on the later pages
+        let pages = banded_pages(4, "Big news", false);
+        let bodies: Vec<&str> = pages.iter().map(|page| page.1.as_str()).collect();
+        assert_eq!(bodies, ["aa", "bb\r\ncc\r\ndd", "ee"]);
+        assert_eq!(pages[0].0, "Big\r\nnews");
+        assert!(pages[1..].iter().all(|page| page.0.is_empty()));
+        assert_eq!(
+            pages.iter().map(|page| page.3).collect::<Vec<_>>(),
+            [4, 4, 2]
+        );
+
+        let every_page = banded_pages(4, "Big news", true);
+        assert_eq!(every_page.len(), 5);
+        assert!(every_page
+            .iter()
+            .all(|page| page.0 == "Big\r\nnews" && page.3 == 4));
+    }
+
+    #[test]
+    fn equal_paragraphs_split_evenly() {
+        let text = ["aaaa", "bbbb", "cccc", "dddd", "eeee", "ffff"].join("\n\n");
+        assert_eq!(
+            split_even_sections(&text, 3),
+            ["aaaa\n\nbbbb", "cccc\n\ndddd", "eeee\n\nffff"]
+        );
+        assert_eq!(split_even_sections(&text, 1), [text.as_str()]);
+        // More sections than paragraphs: one paragraph each
+        assert_eq!(split_even_sections("aa\n\nbb", 5), ["aa", "bb"]);
+        assert!(split_even_sections("\n\n", 2).is_empty());
+    }
+
+    #[test]
+    fn a_huge_paragraph_gets_a_section_to_itself() {
+        let huge = "x".repeat(1_000);
+        let text = format!("a\n\nb\n\n{huge}\n\nc\n\nd");
+        // Nothing can be smaller than the huge paragraph, so the rest share what is left
+        assert_eq!(
+            split_even_sections(&text, 3),
+            ["a\n\nb", huge.as_str(), "c\n\nd"]
+        );
+        assert_eq!(
+            split_even_sections(&text, 2),
+            [format!("a\n\nb\n\n{huge}"), "c\n\nd".to_string()]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! What a paragraph is, for every feature that works paragraph by paragraph
+//! Source lines (split at '\n', a trailing '\r' ignored) are classified once by line_kinds:
+//! fenced code blocks first, so nothing inside one counts as a heading, list item or blank
+//! line, then blank lines, headings (ATX, or a line with a setext underline right below it) and
+//! list item starts. paragraph_segments groups those lines into segments, paragraphs joins the
+//! segments between blank lines, and heading_lines and strip_markdown read the kinds directly.
+
+use serde::Serialize;
+
+use crate::markdown::{atx_heading, fence_run, is_setext_underline, strip_list_marker};
+use crate::{signature_start, DEFAULT_SIGNATURE_DELIMITER};
+
+/// What one source line is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LineKind {
+    /// Whitespace only, outside a code fence
+    Blank,
+    /// Any other line; it continues the prose paragraph or list item above it
+    Prose,
+    /// A line starting with a list marker ("- ", "* ", "+ ", "1. ", "1) ")
+    ListItem,
+    /// An ATX heading, or the text line of a setext heading
+    Heading,
+    /// The "===" or "---" line under a setext heading
+    Underline,
+    /// The line opening a code fence
+    FenceOpen,
+    /// A line inside a code fence
+    Code,
+    /// The line closing a code fence
+    FenceClose,
+}
+
+/// The kind of every line of text.split('\n')
+pub(crate) fn line_kinds(text: &str) -> Vec<LineKind> {
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let mut kinds = Vec::with_capacity(lines.len());
+    // Fence character and length while inside a fenced code block
+    let mut fence: Option<(char, usize)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some((fence_char, fence_len)) = fence {
+            let closes = fence_run(line).is_some_and(|(c, len, info)| {
+                c == fence_char && len >= fence_len && info.is_empty()
+            });
+            if closes {
+                fence = None;
+                kinds.push(LineKind::FenceClose);
+            } else {
+                kinds.push(LineKind::Code);
+            }
+            continue;
+        }
+        if let Some((c, len, _)) = fence_run(line) {
+            fence = Some((c, len));
+            kinds.push(LineKind::FenceOpen);
+            continue;
+        }
+
+        let body = line.trim_start();
+        // Only right under a setext heading; anywhere else "---" is a rule
+        let underlines = kinds.last() == Some(&LineKind::Heading)
+            && atx_heading(lines[index - 1].trim_start()).is_none()
+            && is_setext_underline(line);
+        let underlined = !is_setext_underline(line)
+            && lines
+                .get(index + 1)
+                .is_some_and(|next| is_setext_underline(next));
+        let kind = if body.is_empty() {
+            LineKind::Blank
+        } else if underlines {
+            LineKind::Underline
+        } else if underlined || atx_heading(body).is_some() {
+            LineKind::Heading
+        } else if strip_list_marker(body).len() < body.len() {
+            LineKind::ListItem
+        } else {
+            LineKind::Prose
+        };
+        kinds.push(kind);
+    }
+
+    kinds
+}
+
+/// Which source lines (split at '\n') are headings: ATX lines, and non-blank lines with a
+/// setext underline right below them; fenced code blocks have none
+pub(crate) fn heading_lines(text: &str) -> Vec<bool> {
+    line_kinds(text)
+        .into_iter()
+        .map(|kind| kind == LineKind::Heading)
+        .collect()
+}
+
+/// What a segment of split_paragraphs is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum ParagraphKind {
+    /// Lines of text up to a blank line or another kind of segment
+    Prose,
+    /// A list item and the lines that continue it
+    ListItem,
+    /// A fenced code block, fences included; its blank lines belong to it
+    CodeBlock,
+    /// One heading line, with its setext underline
+    Heading,
+    /// Blank lines between the others
+    Blank,
+    /// The signature block the default signatureDelimiter opens, to the end of the text (see
+    /// signature.rs)
+    Signature,
+}
+
+/// One segment of a text
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Paragraph<'a> {
+    /// The segment's source lines, without the final line break
+    pub text: &'a str,
+    /// First and last source line (0-based, both included)
+    pub src_start_line: usize,
+    pub src_end_line: usize,
+    /// Blank lines right before the segment (0 for a blank segment)
+    pub blank_lines_before: usize,
+    pub kind: ParagraphKind,
+}
+
+/// Split text into consecutive segments: prose paragraphs, list items, fenced code blocks,
+/// headings and runs of blank lines, covering every source line once, then the signature
+/// The empty line after a final line break isn't a line of its own, so "a\n" is one prose
+/// segment and "" is none.
+pub fn paragraph_segments(text: &str) -> Vec<Paragraph<'_>> {
+    let Some(start) = signature_start(text, DEFAULT_SIGNATURE_DELIMITER) else {
+        return body_segments(text);
+    };
+    let mut segments = body_segments(&text[..start]);
+    let src_start_line = text[..start].matches('\n').count();
+    let signature = text[start..].strip_suffix('\n').unwrap_or(&text[start..]);
+    let blank_lines_before = match segments.last() {
+        Some(last) if last.kind == ParagraphKind::Blank => {
+            last.src_end_line + 1 - last.src_start_line
+        }
+        _ => 0,
+    };
+    segments.push(Paragraph {
+        text: signature.strip_suffix('\r').unwrap_or(signature),
+        src_start_line,
+        src_end_line: src_start_line + signature.matches('\n').count(),
+        blank_lines_before,
+        kind: ParagraphKind::Signature,
+    });
+    segments
+}
+
+/// paragraph_segments of text without a signature
+fn body_segments(text: &str) -> Vec<Paragraph<'_>> {
+    let kinds = line_kinds(text);
+    let line_count = if text.ends_with('\n') {
+        kinds.len() - 1
+    } else if text.is_empty() {
+        0
+    } else {
+        kinds.len()
+    };
+
+    let mut segments: Vec<Paragraph> = Vec::new();
+    // Byte offsets of the current segment's first line and of the next line
+    let mut start = 0;
+    let mut offset = 0;
+    for (index, line) in text.split('\n').take(line_count).enumerate() {
+        let end = offset + line.strip_suffix('\r').unwrap_or(line).len();
+        let kind = kinds[index];
+        let last = segments.last_mut();
+        let joins = last.as_ref().is_some_and(|last| {
+            matches!(
+                (last.kind, kind),
+                (ParagraphKind::Blank, LineKind::Blank)
+                    | (
+                        ParagraphKind::CodeBlock,
+                        LineKind::Code | LineKind::FenceClose
+                    )
+                    | (ParagraphKind::Heading, LineKind::Underline)
+                    | (
+                        ParagraphKind::Prose | ParagraphKind::ListItem,
+                        LineKind::Prose
+                    )
+            )
+        });
+
+        match last {
+            Some(last) if joins => {
+                last.text = &text[start..end];
+                last.src_end_line = index;
+            }
+            last => {
+                let kind = match kind {
+                    LineKind::Blank => ParagraphKind::Blank,
+                    LineKind::Prose => ParagraphKind::Prose,
+                    LineKind::ListItem => ParagraphKind::ListItem,
+                    LineKind::Heading | LineKind::Underline => ParagraphKind::Heading,
+                    LineKind::FenceOpen | LineKind::Code | LineKind::FenceClose => {
+                        ParagraphKind::CodeBlock
+                    }
+                };
+                let blank_lines_before = match last {
+                    Some(last) if last.kind == ParagraphKind::Blank => {
+                        last.src_end_line + 1 - last.src_start_line
+                    }
+                    _ => 0,
+                };
+                start = offset;
+                segments.push(Paragraph {
+                    text: &text[start..end],
+                    src_start_line: index,
+                    src_end_line: index,
+                    blank_lines_before,
+                    kind,
+                });
+            }
+        }
+        offset += line.len() + 1;
+    }
+
+    segments
+}
+
+#[cfg(feature = "json")]
+/// paragraph_segments as JSON: array of {text, srcStartLine, srcEndLine, blankLinesBefore,
+/// kind}, kind being "prose", "listItem", "codeBlock", "heading", "blank" or "signature"
+pub fn split_paragraphs(text: &str) -> String {
+    serde_json::to_string(&paragraph_segments(text)).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Split text into blank-line-separated paragraphs
+/// Yields the 0-based source line index of each paragraph and its text (without the final
+/// line break): the segments of paragraph_segments between blank segments, joined, so a code
+/// block's blank lines don't split it
+pub fn paragraphs(text: &str) -> Vec<(usize, &str)> {
+    let mut result: Vec<(usize, &str)> = Vec::new();
+    let mut previous_blank = true;
+    let offset_of = |slice: &str| slice.as_ptr() as usize - text.as_ptr() as usize;
+
+    for segment in paragraph_segments(text) {
+        if segment.kind == ParagraphKind::Blank {
+            previous_blank = true;
+            continue;
+        }
+        match result.last_mut() {
+            Some((_, paragraph)) if !previous_blank => {
+                let start = offset_of(paragraph);
+                *paragraph = &text[start..offset_of(segment.text) + segment.text.len()];
+            }
+            _ => result.push((segment.src_start_line, segment.text)),
+        }
+        previous_blank = false;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A document with one segment of each kind but signature, ending without a line break
+    const DOCUMENT: &str =
+        "# Title\n\nSome prose\nwrapped.\n\n\n- item one\n  continued\n- item two\n\
+        ```\ncode\n\nmore code\n```\nSetext\n===\nlast paragraph";
+
+    #[test]
+    fn segments_of_each_kind() {
+        use ParagraphKind::*;
+        let segments: Vec<_> = paragraph_segments(DOCUMENT)
+            .into_iter()
+            .map(|segment| {
+                (
+                    segment.text,
+                    segment.src_start_line,
+                    segment.src_end_line,
+                    segment.blank_lines_before,
+                    segment.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("# Title", 0, 0, 0, Heading),
+                ("", 1, 1, 0, Blank),
+                ("Some prose\nwrapped.", 2, 3, 1, Prose),
+                ("\n", 4, 5, 0, Blank),
+                ("- item one\n  continued", 6, 7, 2, ListItem),
+                ("- item two", 8, 8, 0, ListItem),
+                ("```\ncode\n\nmore code\n```", 9, 13, 0, CodeBlock),
+                ("Setext\n===", 14, 15, 0, Heading),
+                ("last paragraph", 16, 16, 0, Prose),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_code_block_is_one_paragraph() {
+        let joined: Vec<(usize, &str)> = paragraphs(DOCUMENT);
+        assert_eq!(
+            joined,
+            [
+                (0, "# Title"),
+                (2, "Some prose\nwrapped."),
+                (
+                    6,
+                    "- item one\n  continued\n- item two\n```\ncode\n\nmore code\n```\nSetext\n===\nlast paragraph"
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_kinds_are_camel_case() {
+        let segments: serde_json::Value =
+            serde_json::from_str(&split_paragraphs(DOCUMENT)).unwrap();
+        assert_eq!(
+            segments[4],
+            serde_json::json!({
+                "text": "- item one\n  continued",
+                "srcStartLine": 6,
+                "srcEndLine": 7,
+                "blankLinesBefore": 2,
+                "kind": "listItem",
+            })
+        );
+        assert_eq!(segments[6]["kind"], "codeBlock");
+    }
+}
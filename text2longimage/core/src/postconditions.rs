@@ -0,0 +1,351 @@
+//! The wrappers' own postcondition: no wrapped line is wider than the limit
+//! Every wrapped line fits in max_chars_per_line columns, unless it is one word the wrapper
+//! couldn't break (TOKEN_OVERFLOW), every line break is "\r\n", and with trimTrailing no line
+//! ends in whitespace but a Markdown hard break. The wrappers check their output against it in
+//! debug builds, and in release builds with the strict-checks feature; elsewhere the check
+//! compiles to nothing. A violation panics in a debug build, naming the line; a strict release
+//! build has JustifyOptions::justify_checked return it as an INTERNAL_ERROR instead, and the
+//! String-returning wrappers pass the output on unchecked.
+//! What runs after the breaking falls in two kinds. Additions that take columns — the
+//! line-number gutter, hanging indents, break markers, the footer's padding and ellipsis — are
+//! measured while breaking, so the check counts them like any text (a gutter by the width the
+//! wrapper left for the text after it). Render passes — visualizeWhitespace's symbols,
+//! escapeHtml's entities — only change how characters are written once the lines are decided;
+//! they run last, and the check, told which ran (RenderPasses), measures through them: an
+//! entity as the character it stands for, a whitespace symbol as the narrowest character it
+//! may stand for and the pilcrow ending a line as nothing. A line that fit therefore always
+//! passes, at the cost of missing an overflow only a literal symbol in the text could cause.
+//! markSoftBreaks is written past the limit for remove_soft_breaks to find and isn't measured.
+
+use std::borrow::Cow;
+
+use crate::code_spans::span_words;
+use crate::escape::unescape_html;
+use crate::joiners::joiner_aware_width;
+use crate::lists::list_item;
+use crate::metrics::{is_hard_break, unbreakable_spans, LineBreaking, TrailingWhitespace};
+use crate::ruby::ruby_spans;
+use crate::table::astral_aware_width;
+use crate::visualize::{symbolized, PILCROW};
+use crate::{width_of, RubySyntax, WebtoolsError};
+
+/// Whether wrapped output is checked at all
+const ENABLED: bool = cfg!(any(debug_assertions, feature = "strict-checks"));
+
+/// The render passes that ran over the output after it was wrapped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct RenderPasses {
+    /// visualize_whitespace: whitespace shown as symbols, a pilcrow before every line break
+    pub(crate) visualized: bool,
+    /// escape_html: & < > " ' written as entities
+    pub(crate) html_escaped: bool,
+}
+
+/// How to measure the lines of a wrapper's output against its limit
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WrapCheck<'a> {
+    /// The wrapper, for the message
+    pub(crate) what: &'static str,
+    pub(crate) max_columns: u32,
+    /// Characters of line-number gutter in front of every line, not measured
+    pub(crate) gutter_chars: usize,
+    /// Ruby notation is measured by its base, as the wrapper measures it
+    pub(crate) ruby: RubySyntax,
+    /// Written after the limit at the end of broken lines (markSoftBreaks), not measured
+    pub(crate) soft_break_marker: &'a str,
+    /// Narrow astral letters are one column, as from algorithm V3 on
+    pub(crate) narrow_astral: bool,
+    /// A list item's marker doesn't count as a word, as with hangingIndent
+    pub(crate) list_markers: bool,
+    /// An inline code span is one word, as with markdown from algorithm V5 on
+    pub(crate) code_spans: bool,
+    /// BOMs are zero columns wide, as from algorithm V6 on
+    pub(crate) bom_joiner: bool,
+    /// What ran over the output after wrapping, measured through
+    pub(crate) render: RenderPasses,
+    /// Whether lines may end in whitespace (Keep), or only in a Markdown hard break
+    pub(crate) trailing: TrailingWhitespace,
+    /// The wrapped lines at the start of the output; what follows them (a signature, the
+    /// footer) is laid out on its own and isn't checked
+    pub(crate) wrapped_lines: usize,
+}
+
+impl WrapCheck<'_> {
+    /// Plain column-wrapped output of what
+    pub(crate) fn columns(what: &'static str, max_columns: u32) -> Self {
+        WrapCheck {
+            what,
+            max_columns,
+            gutter_chars: 0,
+            ruby: RubySyntax::None,
+            soft_break_marker: "",
+            narrow_astral: false,
+            list_markers: false,
+            code_spans: false,
+            bom_joiner: false,
+            render: RenderPasses::default(),
+            trailing: TrailingWhitespace::Trim,
+            wrapped_lines: usize::MAX,
+        }
+    }
+
+    /// line past the gutter
+    fn past_gutter<'l>(&self, line: &'l str) -> &'l str {
+        let start = line
+            .char_indices()
+            .nth(self.gutter_chars)
+            .map_or(line.len(), |(offset, _)| offset);
+        &line[start..]
+    }
+
+    /// Whether line ends in whitespace the wrapper should have trimmed; a soft break marker
+    /// ends its line, whatever whitespace was kept in front of it
+    fn has_trailing_whitespace(&self, line: &str) -> bool {
+        let body = self.past_gutter(line);
+        if !self.soft_break_marker.is_empty() && body.ends_with(self.soft_break_marker) {
+            return false;
+        }
+        match self.trailing {
+            TrailingWhitespace::Keep => false,
+            TrailingWhitespace::KeepHardBreaks if is_hard_break(body) => false,
+            _ => body.ends_with(char::is_whitespace),
+        }
+    }
+
+    /// Width of line as the wrapper measured it, without gutter, marker, trailing whitespace
+    /// and readings, and whether it is one word (after a list marker) that may overflow
+    fn measured(&self, line: &str) -> (u64, bool) {
+        let line = if self.render.html_escaped {
+            unescape_html(line)
+        } else {
+            Cow::Borrowed(line)
+        };
+        let line = match line.strip_suffix(PILCROW) {
+            Some(unmarked) if self.render.visualized => unmarked,
+            _ => &line,
+        };
+        let body = self.past_gutter(line);
+        let body = Some(self.soft_break_marker)
+            .filter(|marker| !marker.is_empty())
+            .and_then(|marker| body.strip_suffix(marker))
+            .unwrap_or(body)
+            .trim_end();
+        let width_of = if self.bom_joiner {
+            joiner_aware_width
+        } else if self.narrow_astral {
+            astral_aware_width
+        } else {
+            width_of
+        };
+        let visualized = self.render.visualized;
+        let char_width = |c: char| -> u64 {
+            let stood_for = symbolized(c).iter().filter(|_| visualized);
+            stood_for
+                .chain([&c])
+                .map(|&c| width_of(c))
+                .min()
+                .map_or(0, u64::from)
+        };
+        let width = |text: &str| -> u64 { text.chars().map(char_width).sum() };
+
+        let mut columns = 0;
+        let mut rest = 0;
+        for span in ruby_spans(body, self.ruby) {
+            columns += width(&body[rest..span.range.start]) + width(span.base);
+            rest = span.range.end;
+        }
+        columns += width(&body[rest..]);
+        let words = match list_item(body).filter(|_| self.list_markers) {
+            Some(item) => item.body,
+            None => body,
+        };
+        let words = words.trim();
+        let one_word = if self.code_spans || self.bom_joiner {
+            let breaking = LineBreaking {
+                code_spans: self.code_spans,
+                bom_joiner: self.bom_joiner,
+                ..LineBreaking::default()
+            };
+            span_words(words, &unbreakable_spans(words, &breaking)).len() <= 1
+        } else {
+            !words.contains(char::is_whitespace)
+        };
+        (columns, one_word)
+    }
+
+    /// The first way output breaks the postcondition
+    fn violation(&self, output: &str) -> Option<WebtoolsError> {
+        let internal = |line_index: usize, line: &str, message: String| {
+            Some(WebtoolsError::Internal {
+                message: format!("{}: {}", self.what, message),
+                line_index,
+                line: line.to_string(),
+            })
+        };
+        for (index, line) in output.split("\r\n").enumerate().take(self.wrapped_lines) {
+            if line.contains('\n') {
+                return internal(
+                    index,
+                    line,
+                    "a line break is \\n instead of \\r\\n".to_string(),
+                );
+            }
+            if self.has_trailing_whitespace(line) {
+                return internal(index, line, "a line ends in whitespace".to_string());
+            }
+            let (columns, one_word) = self.measured(line);
+            if columns > u64::from(self.max_columns) && !one_word {
+                return internal(
+                    index,
+                    line,
+                    format!(
+                        "a {}-column line is over the {}-column limit",
+                        columns, self.max_columns
+                    ),
+                );
+            }
+        }
+        None
+    }
+}
+
+/// check_wrapped for a wrapper that returns its output either way: a violation panics in a
+/// debug build, and nothing is checked in a release build
+pub(crate) fn debug_check_wrapped(output: &str, check: &WrapCheck) {
+    if cfg!(debug_assertions) {
+        if let Some(error) = check.violation(output) {
+            panic!("{}", error);
+        }
+    }
+}
+
+/// Check output against the postcondition, if checks are on: a violation panics in a debug
+/// build and is returned in a strict release build
+pub(crate) fn check_wrapped(output: &str, check: &WrapCheck) -> Result<(), WebtoolsError> {
+    if !ENABLED {
+        return Ok(());
+    }
+    match check.violation(output) {
+        None => Ok(()),
+        Some(error) if cfg!(debug_assertions) => panic!("{}", error),
+        Some(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_text_width, escape_html, visualize_whitespace, JustifyOptions};
+
+    fn trailing(trailing: TrailingWhitespace) -> WrapCheck<'static> {
+        WrapCheck {
+            trailing,
+            ..WrapCheck::columns("test", 8)
+        }
+    }
+
+    #[test]
+    fn trailing_whitespace_is_flagged_when_trimming() {
+        let error = trailing(TrailingWhitespace::Trim)
+            .violation("ab \r\ncd")
+            .unwrap();
+        assert_eq!(error.code(), "INTERNAL_ERROR");
+        assert!(
+            error.to_string().contains("ends in whitespace"),
+            "{}",
+            error
+        );
+        assert!(trailing(TrailingWhitespace::Trim)
+            .violation("ab\r\ncd\u{3000}")
+            .is_some());
+        assert!(trailing(TrailingWhitespace::Keep)
+            .violation("ab \r\ncd\u{3000}")
+            .is_none());
+    }
+
+    #[test]
+    fn markdown_hard_breaks_pass() {
+        let check = trailing(TrailingWhitespace::KeepHardBreaks);
+        assert!(check.violation("line one  \r\nline two").is_none());
+        assert!(check.violation("a \r\nb").is_some());
+        assert!(check.violation("a   \r\nb").is_some());
+        assert!(trailing(TrailingWhitespace::Trim)
+            .violation("line one  \r\nline two")
+            .is_some());
+    }
+
+    #[test]
+    fn the_gutter_and_soft_break_marker_are_not_the_line() {
+        // A blank line's gutter ends in its separator's space
+        let numbered = WrapCheck {
+            gutter_chars: 5,
+            ..trailing(TrailingWhitespace::Trim)
+        };
+        assert!(numbered.violation(" 1 │ ab\r\n   │ ").is_none());
+        let marked = WrapCheck {
+            soft_break_marker: "↩",
+            ..trailing(TrailingWhitespace::Trim)
+        };
+        // Word wrapping keeps the space it broke at in front of the marker
+        assert!(marked.violation("ab  ↩\r\ncd").is_none());
+        assert!(marked.violation("ab ↩\r\ncd ").is_some());
+    }
+
+    /// A list item with markup, quotes and a URL a CJK line has to cut through, for the render
+    /// pass test
+    const RENDER_TEXT: &str =
+        "- 链接<a href=\"https://example.com/path\">示例</a>和'引号' & 更多文字继续换行\n\
+                               第二行：R&D <b>粗体</b> 文字";
+
+    /// The width RENDER_TEXT is wrapped at
+    const RENDER_WIDTH: u32 = 16;
+
+    #[test]
+    fn lines_are_measured_through_the_render_passes() {
+        let layout = JustifyOptions {
+            max_chars_per_line: RENDER_WIDTH,
+            line_numbers: true,
+            hanging_indent: true,
+            break_marker_end: "↩".to_string(),
+            break_marker_start: "↪".to_string(),
+            ..JustifyOptions::default()
+        };
+        let escaped = JustifyOptions {
+            escape_html: true,
+            ..layout.clone()
+        };
+        let plain = layout.justify(RENDER_TEXT);
+        let html = escaped.justify_checked(RENDER_TEXT).unwrap();
+        assert_eq!(html, escape_html(&plain), "escapeHtml moved a break");
+        assert!(html.contains("&lt;") && html.contains("&amp;") && html.contains("&#39;"));
+        for line in plain.split("\r\n") {
+            assert!(
+                calculate_text_width(line) <= u64::from(RENDER_WIDTH),
+                "{line:?} in {plain:?}"
+            );
+        }
+        assert!(
+            html.split("\r\n")
+                .any(|line| calculate_text_width(line) > u64::from(RENDER_WIDTH)),
+            "no line of {html:?} needed its entities counted as one column"
+        );
+        let both = JustifyOptions {
+            visualize_whitespace: true,
+            ..escaped.clone()
+        };
+        assert_eq!(
+            both.justify_checked(RENDER_TEXT).unwrap(),
+            escape_html(&visualize_whitespace(&plain))
+        );
+        let footer = JustifyOptions {
+            footer_text: "<长图> & Co.".to_string(),
+            mark_soft_breaks: "\u{200B}".to_string(),
+            ..both.clone()
+        };
+        let with_footer = footer.justify_checked(RENDER_TEXT).unwrap();
+        assert!(
+            with_footer.ends_with("&lt;长图&gt;·&amp;·Co."),
+            "{with_footer:?}"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Short previews of long texts, for galleries and link cards
+//! A preview is the first few lines of justify_text's output and is built with a line-limited
+//! WrapIter, so it costs about as much as the lines it shows however long the text is.
+
+use std::borrow::Cow;
+
+use crate::{truncate_to_width, width_of_str, WrapIter};
+
+/// The first max_lines lines of justify_text(text, max_chars_per_line), joined with "\r\n"
+/// Blank lines before the first visible character are skipped, and so are blank lines at the
+/// end of the preview. When visible text follows, the last line ends in `ellipsis`, with as
+/// many of its characters cut as that needs to stay within max_chars_per_line; an ellipsis
+/// wider than the line is left out, and an empty one leaves the line as it is. Output depends
+/// only on the arguments.
+pub fn preview_snippet(
+    text: &str,
+    max_chars_per_line: u32,
+    max_lines: u32,
+    ellipsis: &str,
+) -> String {
+    let Some(first_visible) = text.find(|c: char| !c.is_whitespace()) else {
+        return String::new();
+    };
+    // Both line breaks justify_text honours: '\n', and '\r' where the character wrapper breaks
+    let start = text[..first_visible]
+        .rfind(['\n', '\r'])
+        .map_or(0, |index| index + 1);
+    let text = &text[start..];
+
+    let mut lines: Vec<Cow<str>> = Vec::new();
+    let mut end = 0;
+    for line in WrapIter::with_line_limit(text, max_chars_per_line, max_lines as usize) {
+        end = line.text_range.end;
+        lines.push(line.text(text));
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let truncated = !ellipsis.is_empty() && text[end..].contains(|c: char| !c.is_whitespace());
+    if let Some(last) = lines.last_mut().filter(|_| truncated) {
+        *last = Cow::Owned(with_ellipsis(last, ellipsis, max_chars_per_line));
+    }
+    lines.join("\r\n")
+}
+
+/// line followed by ellipsis, cut so the two fit max_chars_per_line together
+fn with_ellipsis(line: &str, ellipsis: &str, max_chars_per_line: u32) -> String {
+    let Some(budget) = (max_chars_per_line as u64).checked_sub(width_of_str(ellipsis)) else {
+        return line.to_string();
+    };
+    truncate_to_width(line, budget).trim_end().to_string() + ellipsis
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::justify_text;
+
+    #[test]
+    fn leading_blank_lines_are_skipped() {
+        assert_eq!(
+            preview_snippet(
+                "\n\n  \nthe quick brown fox jumps over the lazy dog",
+                10,
+                2,
+                "…"
+            ),
+            "the quick\r\nbrown fo…"
+        );
+        assert_eq!(preview_snippet(" \n\t\n", 10, 2, "…"), "");
+        assert_eq!(preview_snippet("a b c d e f", 3, 0, "…"), "");
+    }
+
+    #[test]
+    fn the_ellipsis_fits_in_the_line() {
+        // "……" is four columns, so two of the four characters go
+        assert_eq!(
+            preview_snippet("这是一段中文文本内容还有更多", 8, 2, "……"),
+            "这是一段\r\n中文……"
+        );
+        assert_eq!(
+            preview_snippet("the quick brown fox jumps", 10, 1, "[more]"),
+            "the[more]"
+        );
+        // Wider than the line: left out. Empty: the line stays as it is
+        assert_eq!(
+            preview_snippet("the quick brown fox jumps", 4, 1, "[more]"),
+            "the"
+        );
+        assert_eq!(
+            preview_snippet("the quick brown fox jumps", 10, 1, ""),
+            "the quick"
+        );
+    }
+
+    #[test]
+    fn no_ellipsis_when_nothing_follows() {
+        assert_eq!(preview_snippet("short", 10, 2, "…"), "short");
+        assert_eq!(
+            preview_snippet("the quick brown", 10, 2, "…"),
+            "the quick\r\nbrown"
+        );
+        // Blank lines at the end of the preview go; the ellipsis goes on the last one left
+        assert_eq!(preview_snippet("one\n\n\ntwo", 10, 2, "…"), "one…");
+        assert_eq!(preview_snippet("one\n\n\n", 10, 2, "…"), "one");
+    }
+
+    #[test]
+    fn previews_are_the_first_lines_of_justify_text() {
+        let text =
+            "这是一段用于测试的中文文本，包含English words混排。\nThe quick brown fox jumps.";
+        let wrapped = justify_text(text, 12);
+        let lines: Vec<&str> = wrapped.split("\r\n").collect();
+        for max_lines in 1..=lines.len() {
+            assert_eq!(
+                preview_snippet(text, 12, max_lines as u32, ""),
+                lines[..max_lines].join("\r\n")
+            );
+        }
+    }
+
+    #[test]
+    fn a_preview_stops_reading_after_its_lines() {
+        let paragraph = "这是一段用于测试的中文文本，包含English words混排。\n";
+        let text = paragraph.repeat(100_000);
+        assert!(text.len() > 5_000_000);
+        let consumed: Vec<usize> = WrapIter::with_line_limit(&text, 20, 2)
+            .map(|line| line.text_range.end)
+            .collect();
+        assert_eq!(consumed.len(), 2);
+        assert!(consumed[1] < paragraph.len(), "{:?}", consumed);
+        assert_eq!(
+            preview_snippet(&text, 20, 2, "…"),
+            preview_snippet(&paragraph.repeat(2), 20, 2, "…")
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Stage marks for profiling where a call spends its time
+//! The entry points call mark when a stage of their work ends; with the `profiling` feature a
+//! marker installed by set_stage_marker (the wasm module's timer) attributes the time since
+//! the previous mark to that stage. Without the feature mark is empty and compiles away.
+
+/// The parts of a call a profile tells apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Turning the arguments into Rust values: JS objects converted, JSON inputs parsed
+    Decode,
+    /// Sorting out the input before wrapping it: size limits, option checks, text statistics
+    Classify,
+    /// Wrapping and laying out
+    Wrap,
+    /// Building the value returned: JSON strings, JS objects, line counts
+    Serialize,
+}
+
+impl Stage {
+    /// Every stage, in the order a call goes through them
+    pub const ALL: [Stage; 4] = [
+        Stage::Decode,
+        Stage::Classify,
+        Stage::Wrap,
+        Stage::Serialize,
+    ];
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    static MARKER: std::cell::Cell<Option<fn(Stage)>> = const { std::cell::Cell::new(None) };
+}
+
+#[cfg(feature = "profiling")]
+/// Call marker at every stage mark from now on; None stops
+pub fn set_stage_marker(marker: Option<fn(Stage)>) {
+    MARKER.with(|m| m.set(marker));
+}
+
+#[cfg(feature = "profiling")]
+/// The work since the previous mark was stage
+pub(crate) fn mark(stage: Stage) {
+    if let Some(marker) = MARKER.with(std::cell::Cell::get) {
+        marker(stage);
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+/// The work since the previous mark was stage (a no-op without the profiling feature)
+#[inline(always)]
+pub(crate) fn mark(_stage: Stage) {}
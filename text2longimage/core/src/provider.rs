@@ -0,0 +1,121 @@
+//! Widths the host measures a character at a time, for fonts no metrics table describes
+//! A host with a canvas can measure each character as the wrapper first meets it instead of
+//! building a FontMetrics table for every character it might see. MemoizedWidths keeps what
+//! the provider answered, so each distinct character is measured once however often it
+//! appears, and turns the provider into a WidthModel every fractional wrapper takes.
+//! Performance: the built-in widths place ASCII runs and words whole, but provided widths
+//! are summed a character at a time, and the first sight of each character is a call out to
+//! the provider (a JS callback crosses the wasm boundary). A warm cache saves the calls, not
+//! the per-character sums, so prefer a FontMetrics table where one can be built up front.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::{wrap_text_with, CharClass, WidthModel};
+
+/// Something that measures characters, in em (or any unit the line limit is in)
+pub trait WidthProvider {
+    /// Advance of c; a width that isn't a finite number of at least 0 is replaced by the
+    /// built-in one
+    fn width(&mut self, c: char) -> f64;
+}
+
+/// The built-in widths: 0.5 em for narrow characters, 1.0 em for wide ones, as
+/// justify_text_fractional measures them
+pub struct BuiltinWidths;
+
+impl WidthProvider for BuiltinWidths {
+    fn width(&mut self, c: char) -> f64 {
+        CharClass::of(c).width_em()
+    }
+}
+
+impl<F: FnMut(char) -> f64> WidthProvider for F {
+    fn width(&mut self, c: char) -> f64 {
+        self(c)
+    }
+}
+
+/// A WidthProvider asked about each character at most once
+pub struct MemoizedWidths<P> {
+    provider: RefCell<P>,
+    widths: RefCell<HashMap<char, f64>>,
+}
+
+impl<P: WidthProvider> MemoizedWidths<P> {
+    pub fn new(provider: P) -> Self {
+        MemoizedWidths {
+            provider: RefCell::new(provider),
+            widths: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Number of distinct characters measured so far
+    pub fn measured_count(&self) -> usize {
+        self.widths.borrow().len()
+    }
+
+    /// Forget every measured width, e.g. after the font changed
+    pub fn clear(&self) {
+        self.widths.borrow_mut().clear();
+    }
+
+    /// The provider itself
+    pub fn provider_mut(&mut self) -> &mut P {
+        self.provider.get_mut()
+    }
+}
+
+impl<P: WidthProvider> WidthModel for MemoizedWidths<P> {
+    fn char_width(&self, c: char) -> f64 {
+        if let Some(&width) = self.widths.borrow().get(&c) {
+            return width;
+        }
+        let measured = self.provider.borrow_mut().width(c);
+        let width = if measured.is_finite() && measured >= 0.0 {
+            measured
+        } else {
+            CharClass::of(c).width_em()
+        };
+        self.widths.borrow_mut().insert(c, width);
+        width
+    }
+}
+
+/// justify_text_fractional with widths from widths' provider
+/// max_width_em is in the provider's unit; characters the cache already holds aren't measured
+/// again
+pub fn justify_text_with_provider<P: WidthProvider>(
+    text: &str,
+    max_width_em: f64,
+    widths: &MemoizedWidths<P>,
+) -> String {
+    wrap_text_with(text, max_width_em, widths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::justify_text_fractional;
+
+    #[test]
+    fn wider_widths_push_words_down() {
+        let calls = Cell::new(0);
+        let widths = MemoizedWidths::new(|c| {
+            calls.set(calls.get() + 1);
+            2.0 * BuiltinWidths.width(c)
+        });
+        assert_eq!(justify_text_fractional("aaaa bbbb", 4.5), "aaaa bbbb");
+        assert_eq!(
+            justify_text_with_provider("aaaa bbbb", 4.5, &widths),
+            "aaaa\r\nbbbb"
+        );
+        // 'a', 'b' and the space, each measured once however often they come up
+        assert_eq!(widths.measured_count(), 3);
+        assert_eq!(calls.get(), 3);
+        widths.clear();
+        assert_eq!(widths.measured_count(), 0);
+    }
+}
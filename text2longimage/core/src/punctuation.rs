@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::capabilities::{expected_values, find_value};
+#[cfg(feature = "json")]
+use crate::edits::edits_json;
+use crate::edits::{EditRecorder, TextEdit};
+use crate::{is_cjk_char, OptionValue, WebtoolsError};
+
+/// Punctuation style convert_punctuation writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PunctuationTarget {
+    /// Fullwidth ，。！？：；（）“” next to CJK text
+    Cjk,
+    /// ASCII , . ! ? : ; ( ) "
+    Ascii,
+}
+
+impl PunctuationTarget {
+    /// Parse "cjk" / "ascii" in any case
+    pub fn parse(target: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| target.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown punctuation target: {} (expected {})",
+                target,
+                expected_values::<PunctuationTarget>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for PunctuationTarget {
+    const KIND: &'static str = "punctuationTarget";
+    const USED_BY: &'static [&'static str] = &["convert_punctuation", "convert_punctuation_report"];
+    const ALL: &'static [Self] = &[PunctuationTarget::Cjk, PunctuationTarget::Ascii];
+
+    fn name(self) -> &'static str {
+        match self {
+            PunctuationTarget::Cjk => "cjk",
+            PunctuationTarget::Ascii => "ascii",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            PunctuationTarget::Cjk => "Fullwidth punctuation next to CJK text",
+            PunctuationTarget::Ascii => "ASCII punctuation",
+        }
+    }
+}
+
+/// One kind of substitution and how often it was made
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Substitution {
+    pub from: char,
+    pub to: char,
+    pub count: usize,
+}
+
+/// Converted text plus what was replaced
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PunctuationReport {
+    pub text: String,
+    /// Total characters replaced
+    pub substitutions: usize,
+    /// Per (from, to) pair, ordered by code point
+    pub details: Vec<Substitution>,
+}
+
+const PAIRS: &[(char, char)] = &[
+    (',', '，'),
+    ('.', '。'),
+    ('!', '！'),
+    ('?', '？'),
+    (':', '：'),
+    (';', '；'),
+    ('(', '（'),
+    (')', '）'),
+];
+
+/// CJK ideographs and kana, Hangul, and the CJK symbol and fullwidth punctuation blocks
+fn is_cjk_context(c: char) -> bool {
+    is_cjk_char(c)
+        || matches!(c as u32, 0x3000..=0x303F | 0xFF00..=0xFFEF | 0xAC00..=0xD7AF)
+        || matches!(c, '“' | '”' | '‘' | '’' | '…' | '—')
+}
+
+/// Characters inside URLs and email addresses, which are never converted
+fn protected_spans(chars: &[char]) -> Vec<bool> {
+    let mut protected = vec![false; chars.len()];
+    let mut start = 0;
+    while start < chars.len() {
+        if !chars[start].is_ascii_graphic() {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < chars.len() && chars[end].is_ascii_graphic() {
+            end += 1;
+        }
+        let run: String = chars[start..end].iter().collect();
+        let lower = run.to_ascii_lowercase();
+        if lower.contains("://") || lower.starts_with("www.") || run.contains('@') {
+            protected[start..end].iter_mut().for_each(|p| *p = true);
+        }
+        start = end;
+    }
+    protected
+}
+
+/// Convert punctuation to one style, counting the substitutions
+/// To cjk, ASCII marks are converted only next to CJK text, and the spaces around them are
+/// dropped (fullwidth marks carry their own); decimal points and separators between digits
+/// (3.14, 1,000, 12:30), "..." and anything inside a URL or email address are left alone.
+/// Straight double quotes are paired into “ ” per line. To ascii, fullwidth marks become
+/// ASCII, with a space added where they meet Latin letters or digits.
+pub fn convert_punctuation_with(text: &str, target: PunctuationTarget) -> PunctuationReport {
+    convert_recorded(text, target, false).0
+}
+
+/// convert_punctuation_with, and the edits that make the change when record is set
+fn convert_recorded(
+    text: &str,
+    target: PunctuationTarget,
+    record: bool,
+) -> (PunctuationReport, Vec<TextEdit>) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut counts: BTreeMap<(char, char), usize> = BTreeMap::new();
+    let (out, edits) = match target {
+        PunctuationTarget::Cjk => to_cjk(&chars, &mut counts, record),
+        PunctuationTarget::Ascii => to_ascii(&chars, &mut counts, record),
+    };
+
+    let details: Vec<Substitution> = counts
+        .into_iter()
+        .map(|((from, to), count)| Substitution { from, to, count })
+        .collect();
+    let report = PunctuationReport {
+        text: out,
+        substitutions: details.iter().map(|d| d.count).sum(),
+        details,
+    };
+    (report, edits)
+}
+
+/// Convert punctuation to "cjk" or "ascii"; see convert_punctuation_with
+pub fn convert_punctuation(text: &str, target: &str) -> Result<String, WebtoolsError> {
+    let target = PunctuationTarget::parse(target)?;
+    Ok(convert_punctuation_with(text, target).text)
+}
+
+#[cfg(feature = "json")]
+/// convert_punctuation plus what was replaced
+/// Returns JSON {text, substitutions, details: [{from, to, count}]}
+pub fn convert_punctuation_report(text: &str, target: &str) -> Result<String, WebtoolsError> {
+    let target = PunctuationTarget::parse(target)?;
+    let report = convert_punctuation_with(text, target);
+    Ok(serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[cfg(feature = "json")]
+/// convert_punctuation as edits of text, for an editor to apply in place
+/// Returns JSON [{start, end, replacement}] in char indices of text, ascending and not
+/// touching; applied to text (see apply_edits) they give convert_punctuation's output.
+pub fn convert_punctuation_edits(text: &str, target: &str) -> Result<String, WebtoolsError> {
+    let target = PunctuationTarget::parse(target)?;
+    let (_, edits) = convert_recorded(text, target, true);
+    Ok(edits_json(&edits))
+}
+
+fn to_cjk(
+    chars: &[char],
+    counts: &mut BTreeMap<(char, char), usize>,
+    record: bool,
+) -> (String, Vec<TextEdit>) {
+    let protected = protected_spans(chars);
+    let mut out = EditRecorder::new(chars.len() * 3, record);
+    // Whether each open '(' was converted, so its ')' matches
+    let mut parens: Vec<bool> = Vec::new();
+    // Set while a converted “ waits for its closing quote; None outside quotes
+    let mut quote: Option<bool> = None;
+    // Drop spaces after a converted mark
+    let mut skip_spaces = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '\n' {
+            quote = None;
+        }
+        if c == ' ' && skip_spaces {
+            out.replace(1, "");
+            continue;
+        }
+        skip_spaces = false;
+
+        // Context before comes from the output, so an already converted ” counts as CJK
+        let previous = out.text.chars().rev().find(|&p| p != ' ');
+        let next = chars[i + 1..].iter().find(|&&n| n != ' ').copied();
+        let cjk_before = previous.is_some_and(is_cjk_context);
+        let cjk_after = next.is_some_and(is_cjk_context);
+        let between_digits = chars
+            .get(i.wrapping_sub(1))
+            .is_some_and(char::is_ascii_digit)
+            && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+
+        let converted = if protected[i] {
+            None
+        } else {
+            match c {
+                '.' if chars.get(i + 1) == Some(&'.') || i > 0 && chars[i - 1] == '.' => None,
+                '.' | ',' | ':' if between_digits => None,
+                ',' | '.' | '!' | '?' | ':' | ';' if cjk_before || cjk_after => wide(c),
+                '(' => {
+                    let convert = cjk_before || cjk_after;
+                    parens.push(convert);
+                    convert.then(|| wide(c)).flatten()
+                }
+                ')' => {
+                    let convert = parens.pop().unwrap_or(cjk_before || cjk_after);
+                    convert.then(|| wide(c)).flatten()
+                }
+                '"' => match quote.take() {
+                    Some(convert) => convert.then_some('”'),
+                    None => {
+                        let convert = cjk_before || cjk_after;
+                        quote = Some(convert);
+                        convert.then_some('“')
+                    }
+                },
+                _ => None,
+            }
+        };
+
+        match converted {
+            Some(to) => {
+                let spaces = out.unkeep(' ');
+                out.replace(spaces + 1, to.encode_utf8(&mut [0; 4]));
+                skip_spaces = true;
+                *counts.entry((c, to)).or_default() += 1;
+            }
+            None => out.keep_char(c),
+        }
+    }
+
+    out.finish()
+}
+
+fn wide(c: char) -> Option<char> {
+    PAIRS.iter().find(|(ascii, _)| *ascii == c).map(|&(_, w)| w)
+}
+
+fn to_ascii(
+    chars: &[char],
+    counts: &mut BTreeMap<(char, char), usize>,
+    record: bool,
+) -> (String, Vec<TextEdit>) {
+    let mut out = EditRecorder::new(chars.len(), record);
+
+    for (i, &c) in chars.iter().enumerate() {
+        let converted = match c {
+            '“' | '”' => Some('"'),
+            '、' => Some(','),
+            '．' => Some('.'),
+            _ => PAIRS.iter().find(|(_, w)| *w == c).map(|&(ascii, _)| ascii),
+        };
+        let Some(to) = converted else {
+            out.keep_char(c);
+            continue;
+        };
+
+        // Fullwidth marks include their spacing; ASCII ones need spaces next to Latin text
+        if to == '(' && out.text.ends_with(|p: char| p.is_ascii_alphanumeric()) {
+            out.replace(0, " ");
+        }
+        out.replace(1, to.encode_utf8(&mut [0; 4]));
+        *counts.entry((c, to)).or_default() += 1;
+
+        let spaced = matches!(to, ',' | '.' | '!' | '?' | ':' | ';' | ')');
+        if spaced && chars.get(i + 1).is_some_and(char::is_ascii_alphanumeric) {
+            out.replace(0, " ");
+        }
+    }
+
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_cjk(text: &str) -> String {
+        convert_punctuation_with(text, PunctuationTarget::Cjk).text
+    }
+
+    fn to_ascii(text: &str) -> String {
+        convert_punctuation_with(text, PunctuationTarget::Ascii).text
+    }
+
+    #[test]
+    fn mixed_chinese_punctuation() {
+        assert_eq!(
+            to_cjk("你好,世界!真的吗?是的."),
+            "你好，世界！真的吗？是的。"
+        );
+        // The spaces around a converted mark go
+        assert_eq!(
+            to_cjk("注意 : 小心 ; 好 (很好) 了"),
+            "注意：小心；好（很好）了"
+        );
+        // Latin text keeps its ASCII marks
+        assert_eq!(to_cjk("Hello, world. Bye 你好"), "Hello, world. Bye 你好");
+    }
+
+    #[test]
+    fn numbers_and_urls_stay() {
+        assert_eq!(
+            to_cjk("圆周率是3.14,约1,000人在12:30到."),
+            "圆周率是3.14，约1,000人在12:30到。"
+        );
+        assert_eq!(
+            to_cjk("见https://example.com/a.html?b=1,c=2!"),
+            "见https://example.com/a.html?b=1,c=2!"
+        );
+        assert_eq!(to_cjk("邮件me@example.com。"), "邮件me@example.com。");
+        assert_eq!(to_cjk("www.example.cn,欢迎"), "www.example.cn,欢迎");
+        assert_eq!(to_cjk("等等..."), "等等...");
+    }
+
+    #[test]
+    fn straight_quotes_pair_per_line() {
+        assert_eq!(to_cjk("他说\"你好\"然后\"再见\""), "他说“你好”然后“再见”");
+        // An unclosed quote doesn't pair with one on the next line
+        assert_eq!(to_cjk("他说\"你好\n\"再见\""), "他说“你好\n“再见”");
+        // Quotes in Latin text stay straight
+        assert_eq!(to_cjk("say \"hi\" 好"), "say \"hi\" 好");
+        // A ')' follows what its '(' did
+        assert_eq!(to_cjk("中(a)b"), "中（a）b");
+    }
+
+    #[test]
+    fn ascii_target() {
+        assert_eq!(
+            to_ascii("你好，世界！“引用”（注）"),
+            "你好,世界!\"引用\"(注)"
+        );
+        // A space where a mark meets Latin text
+        assert_eq!(to_ascii("版本，v2。ok（备注）"), "版本, v2. ok (备注)");
+        assert_eq!(to_ascii("一、二．"), "一,二.");
+    }
+
+    #[test]
+    fn consistent_text_is_left_alone() {
+        for text in [
+            "已经一致，没有问题。",
+            "Already consistent, fine.",
+            "混合 mixed，ok。",
+            "",
+        ] {
+            let cjk = convert_punctuation_with(text, PunctuationTarget::Cjk);
+            let twice = convert_punctuation_with(&cjk.text, PunctuationTarget::Cjk);
+            assert_eq!(twice.text, cjk.text, "{:?}", text);
+            assert_eq!(twice.substitutions, 0, "{:?}", text);
+            let ascii = to_ascii(text);
+            assert_eq!(to_ascii(&ascii), ascii, "{:?}", text);
+        }
+        assert_eq!(to_cjk("已经一致，没有问题。"), "已经一致，没有问题。");
+    }
+
+    #[test]
+    fn report_counts_each_substitution() {
+        let report = convert_punctuation_with("好,好,好!", PunctuationTarget::Cjk);
+        assert_eq!(report.text, "好，好，好！");
+        assert_eq!(report.substitutions, 3);
+        assert_eq!(
+            report.details,
+            [
+                Substitution {
+                    from: '!',
+                    to: '！',
+                    count: 1
+                },
+                Substitution {
+                    from: ',',
+                    to: '，',
+                    count: 2
+                },
+            ]
+        );
+        assert_eq!(convert_punctuation("好,", "ASCII").unwrap(), "好,");
+        assert_eq!(
+            convert_punctuation("x", "latin").unwrap_err().code(),
+            "INVALID_ARGUMENT"
+        );
+    }
+
+    #[test]
+    fn edits_give_the_converted_text() {
+        use crate::edits::apply_edits;
+
+        for (text, target) in [
+            ("你好 , 世界 ! (注)\"引\"", PunctuationTarget::Cjk),
+            ("版本，v2。ok（备注）", PunctuationTarget::Ascii),
+        ] {
+            let (report, edits) = convert_recorded(text, target, true);
+            assert_eq!(apply_edits(text, &edits), report.text, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+
+use crate::sentences::join_soft_breaks;
+use crate::{justify_text, width_of_str};
+
+/// One line with its email quote markers removed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct QuotedLine {
+    /// Number of '>' markers; 0 for unquoted lines
+    pub depth: u32,
+    pub text: String,
+}
+
+/// Split a line into its quote prefix, depth and content
+/// Indentation before the markers and spaces between them ("> > >" as well as ">>>") belong
+/// to the prefix, as does one space after the last marker; further spaces stay with the content.
+fn split_prefix(line: &str) -> (&str, u32, &str) {
+    let mut rest = line.trim_start_matches([' ', '\t', '\u{3000}']);
+    if !rest.starts_with('>') {
+        return ("", 0, line);
+    }
+
+    let mut depth = 0;
+    while let Some(after) = rest.strip_prefix('>') {
+        depth += 1;
+        let spaced = after.trim_start_matches([' ', '\t']);
+        rest = if spaced.starts_with('>') {
+            spaced
+        } else {
+            after.strip_prefix([' ', '\t']).unwrap_or(after)
+        };
+    }
+    (&line[..line.len() - rest.len()], depth, rest)
+}
+
+/// Lines split on '\n' with any trailing '\r' split off, so it can be put back unchanged
+fn lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split('\n').map(|line| match line.strip_suffix('\r') {
+        Some(line) => (line, "\r"),
+        None => (line, ""),
+    })
+}
+
+/// Every line of text with its quote markers removed, and how deep it was quoted
+pub fn quoted_lines(text: &str) -> Vec<QuotedLine> {
+    lines(text)
+        .map(|(line, _)| {
+            let (_, depth, content) = split_prefix(line);
+            QuotedLine {
+                depth,
+                text: content.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Remove the email quote markers from the start of every line
+/// Line breaks, including "\r\n", are kept as they are
+pub fn strip_quote_prefix(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (index, (line, cr)) in lines(text).enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        out.push_str(split_prefix(line).2);
+        out.push_str(cr);
+    }
+    out
+}
+
+#[cfg(feature = "json")]
+/// strip_quote_prefix, one entry per line
+/// Returns JSON array [{depth, text}]
+pub fn strip_quote_prefix_detailed(text: &str) -> String {
+    serde_json::to_string(&quoted_lines(text)).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn canonical_prefix(depth: u32) -> String {
+    if depth == 0 {
+        String::new()
+    } else {
+        format!("{} ", ">".repeat(depth as usize))
+    }
+}
+
+/// Quote every line `depth` levels deeper, writing markers the way mail clients do (">> text")
+/// Lines that are already quoted have their markers rewritten at the new depth; empty lines
+/// get the markers without the trailing space. A depth of 0 returns text unchanged.
+pub fn add_quote_prefix(text: &str, depth: u32) -> String {
+    if depth == 0 {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len() + (depth as usize + 1) * 16);
+    for (index, (line, cr)) in lines(text).enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let (_, current, content) = split_prefix(line);
+        let prefix = canonical_prefix(current.saturating_add(depth));
+        if content.is_empty() {
+            out.push_str(prefix.trim_end());
+        } else {
+            out.push_str(&prefix);
+            out.push_str(content);
+        }
+        out.push_str(cr);
+    }
+    out
+}
+
+/// Rewrap quoted text one depth group (consecutive lines quoted equally deep) at a time
+/// max_chars_per_line includes the prefix, which is kept as the group's first line wrote it.
+/// Groups whose lines all fit are left exactly as they are; in a group with an overlong line,
+/// each paragraph (split at empty quoted lines) is joined back together and wrapped to the
+/// width left after the prefix, so the result doesn't alternate long and short lines.
+pub fn rewrap_quoted(text: &str, max_chars_per_line: u32) -> String {
+    let parsed: Vec<(&str, &str, u32, &str)> = lines(text)
+        .map(|(line, cr)| {
+            let (_, depth, content) = split_prefix(line);
+            (line, cr, depth, content)
+        })
+        .collect();
+
+    let mut out: Vec<String> = Vec::with_capacity(parsed.len());
+    let mut start = 0;
+    while start < parsed.len() {
+        let depth = parsed[start].2;
+        let end = parsed[start..]
+            .iter()
+            .position(|&(_, _, d, _)| d != depth)
+            .map_or(parsed.len(), |offset| start + offset);
+        let group = &parsed[start..end];
+        start = end;
+
+        let fits = group
+            .iter()
+            .all(|&(line, ..)| width_of_str(line) <= max_chars_per_line as u64);
+        if fits {
+            out.extend(
+                group
+                    .iter()
+                    .map(|&(line, cr, ..)| format!("{}{}", line, cr)),
+            );
+            continue;
+        }
+
+        let prefix = split_prefix(group[0].0).0;
+        let cr = group[0].1;
+        let width = max_chars_per_line
+            .saturating_sub(u32::try_from(width_of_str(prefix)).unwrap_or(u32::MAX))
+            .max(1);
+        for (index, paragraph) in group
+            .split(|&(.., content)| content.trim().is_empty())
+            .enumerate()
+        {
+            if index > 0 {
+                out.push(format!("{}{}", prefix.trim_end(), cr));
+            }
+            if paragraph.is_empty() {
+                continue;
+            }
+            let contents: Vec<&str> = paragraph.iter().map(|&(.., content)| content).collect();
+            let joined = join_soft_breaks(&contents.join("\n"));
+            for segment in justify_text(&joined, width).split("\r\n") {
+                out.push(format!("{}{}{}", prefix, segment, cr));
+            }
+        }
+    }
+
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREAD: &str = "\
+Sounds good to me.
+> Can we ship on Friday?
+>
+> > Build is green.
+> > > The release notes draft is long enough that this line runs well past the limit
+> > > and continues here.
+> > Thanks.";
+
+    #[test]
+    fn only_the_innermost_quote_is_rewrapped() {
+        assert_eq!(
+            rewrap_quoted(THREAD, 40),
+            "\
+Sounds good to me.
+> Can we ship on Friday?
+>
+> > Build is green.
+> > > The release notes draft is long
+> > > enough that this line runs well
+> > > past the limit and continues here.
+> > Thanks."
+        );
+        // Every line fits in 90 columns, so nothing changes
+        assert_eq!(rewrap_quoted(THREAD, 90), THREAD);
+    }
+
+    #[test]
+    fn depth_tolerates_both_marker_styles_and_indentation() {
+        let cases = [
+            (">>>deep", 3, "deep"),
+            ("> > > deep", 3, "deep"),
+            ("  >> > deep", 3, "deep"),
+            ("\t>text", 1, "text"),
+            (">  two spaces", 1, " two spaces"),
+            (">", 1, ""),
+            ("no > quote", 0, "no > quote"),
+        ];
+        for (line, depth, text) in cases {
+            assert_eq!(
+                quoted_lines(line),
+                [QuotedLine {
+                    depth,
+                    text: text.to_string()
+                }],
+                "{:?}",
+                line
+            );
+        }
+        assert_eq!(strip_quote_prefix("> a\r\n>> b\r\nc"), "a\r\nb\r\nc");
+    }
+
+    #[test]
+    fn adding_depth_rewrites_the_markers() {
+        assert_eq!(add_quote_prefix("a\n\n> b\n>>c", 1), "> a\n>\n>> b\n>>> c");
+        assert_eq!(add_quote_prefix("x\r\n", 2), ">> x\r\n>>");
+        assert_eq!(add_quote_prefix("> as is", 0), "> as is");
+        assert_eq!(
+            strip_quote_prefix(&add_quote_prefix(THREAD, 2)),
+            strip_quote_prefix(THREAD)
+        );
+    }
+
+    #[test]
+    fn rewrapping_keeps_blank_quoted_lines_and_the_group_prefix() {
+        let text = ">> first paragraph that is too long\n>>\n>> second";
+        assert_eq!(
+            rewrap_quoted(text, 20),
+            ">> first paragraph\n>> that is too long\n>>\n>> second"
+        );
+        // The first line's prefix style is written for the whole group
+        assert_eq!(
+            rewrap_quoted("> > aaa bbb\n>> ccc", 10),
+            "> > aaa\n> > bbb\n> > ccc"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn detailed_json() {
+        assert_eq!(
+            strip_quote_prefix_detailed("> a\nb"),
+            r#"[{"depth":1,"text":"a"},{"depth":0,"text":"b"}]"#
+        );
+    }
+}
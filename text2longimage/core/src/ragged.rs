@@ -0,0 +1,127 @@
+//! How even the right edge of wrapped text is, and the width that makes it most even
+//! Users drag the width slider looking for a setting where lines end near the same column.
+//! raggedness puts a number on it from the line counters' records (see line_records), so
+//! scanning every width of a range never builds a single wrapped string, and suggest_width
+//! does the scan.
+
+use crate::{line_records, MAX_MAX_CHARS_PER_LINE};
+
+/// How many percent more lines than the widest width gives suggest_width may pick a width for
+pub const SUGGEST_WIDTH_MAX_LINE_INCREASE: u32 = 10;
+
+/// raggedness and the line count of text at max_chars_per_line, from one pass
+fn measure(text: &str, max_chars_per_line: u32) -> (f64, u32) {
+    let width = f64::from(max_chars_per_line.max(1));
+    let (mut slack_squares, mut broken, mut lines) = (0.0, 0u32, 0u32);
+    line_records(text, max_chars_per_line, |line| {
+        lines += 1;
+        if !line.last {
+            let slack = (width - line.width as f64).max(0.0);
+            slack_squares += slack * slack;
+            broken += 1;
+        }
+    });
+    let raggedness = if broken == 0 {
+        0.0
+    } else {
+        slack_squares / f64::from(broken) / (width * width)
+    };
+    (raggedness, lines)
+}
+
+/// How ragged the right edge of justify_text(text, max_chars_per_line) is: the mean squared
+/// slack (columns left empty) of the lines the wrapper broke, over the width squared
+/// 0 when every broken line fills its width exactly, approaching 1 as they get shorter.
+/// Lines ending a paragraph (or the text) are left out, as they are short by nature, and a
+/// word too wide for any line has no slack; text with no broken lines is 0.
+pub fn raggedness(text: &str, max_chars_per_line: u32) -> f64 {
+    measure(text, max_chars_per_line).0
+}
+
+/// The width in [min_chars, max_chars] (either order) with the least raggedness, among those
+/// wrapping text into at most SUGGEST_WIDTH_MAX_LINE_INCREASE percent more lines than the
+/// widest one does
+/// Of widths equally ragged the widest wins, so text that wraps nowhere gets the widest.
+/// Both ends are cut to MAX_MAX_CHARS_PER_LINE, the widest line justify_text is given.
+pub fn suggest_width(text: &str, min_chars: u32, max_chars: u32) -> u32 {
+    let clamp = |chars: u32| chars.clamp(1, MAX_MAX_CHARS_PER_LINE);
+    let (low, high) = if min_chars <= max_chars {
+        (clamp(min_chars), clamp(max_chars))
+    } else {
+        (clamp(max_chars), clamp(min_chars))
+    };
+    let (mut best_raggedness, widest_lines) = measure(text, high);
+    let line_budget =
+        u64::from(widest_lines) * u64::from(100 + SUGGEST_WIDTH_MAX_LINE_INCREASE) / 100;
+    let mut best = high;
+    for width in (low..high).rev() {
+        // No width is more even than one with no slack at all
+        if best_raggedness == 0.0 {
+            break;
+        }
+        let (raggedness, lines) = measure(text, width);
+        if u64::from(lines) <= line_budget && raggedness < best_raggedness {
+            best = width;
+            best_raggedness = raggedness;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_width_cuts_the_range_to_the_widest_line() {
+        let started = std::time::Instant::now();
+        assert_eq!(suggest_width("", 0, u32::MAX), MAX_MAX_CHARS_PER_LINE);
+        assert_eq!(suggest_width("", u32::MAX, 0), MAX_MAX_CHARS_PER_LINE);
+        assert_eq!(
+            suggest_width("a b c d e f g h", u32::MAX - 1, u32::MAX),
+            MAX_MAX_CHARS_PER_LINE
+        );
+        assert!(started.elapsed().as_secs() < 5, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn suggest_width_picks_the_most_even_width() {
+        // From 4 to 8 columns every word takes a line; only at 4 do they fill it
+        let text = "aaaa bbbb cccc dddd";
+        assert_eq!(raggedness(text, 8), 0.25);
+        assert_eq!(raggedness(text, 4), 0.0);
+        assert_eq!(suggest_width(text, 4, 8), 4);
+        assert_eq!(suggest_width(text, 8, 4), 4);
+        // At 9 two words share a line and nothing is left over
+        assert_eq!(suggest_width(text, 4, 9), 9);
+    }
+
+    /// A text, a width and its raggedness there, worked out by hand: full lines, a paragraph's
+    /// short last line and an overflowing word count 0, a 3-column line in 8 columns 25/64, and
+    /// one full and one 4-column line in 5 columns 1/50
+    const RAGGEDNESS_EXAMPLES: [(&str, u32, f64); 6] = [
+        ("aaaa bbbb cccc", 9, 0.0),
+        ("一二三四五六七", 4, 0.0),
+        ("aaaa\nb\n\nc", 4, 0.0),
+        ("aaaa overflowing bb", 4, 0.0),
+        ("aaa bbbbbbb", 8, 25.0 / 64.0),
+        ("aa bb c dd ee", 5, 1.0 / 50.0),
+    ];
+
+    #[test]
+    fn raggedness_worked_out_by_hand() {
+        for (text, width, expected) in RAGGEDNESS_EXAMPLES {
+            assert_eq!(raggedness(text, width), expected, "{text:?} at {width}");
+        }
+    }
+
+    #[test]
+    fn suggest_width_takes_the_widest_of_a_tie_and_no_more_lines() {
+        // Text on one line is never ragged, so the widest wins the tie
+        assert_eq!(suggest_width("short", 8, 12), 12);
+        // At 9 "aaaa bbbb" fills its line; 8 makes three lines, over the 10% allowed over two
+        assert_eq!(suggest_width("aaaa bbbb cccc", 8, 12), 9);
+        assert_eq!(suggest_width("aaaa bbbb cc", 6, 10), 9);
+        assert_eq!(suggest_width("aaaa bbbb cc", 10, 6), 9);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::layout::{layout_lines_with_heights, Align};
+use crate::{count_wrapped_lines_of_output, justify_text, width_of, RubySyntax, ScriptLineHeights};
+
+mod font;
+
+use font::{ASCII_GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// Largest pixel buffer render_bitmap will allocate (256 MB of RGBA)
+const MAX_BITMAP_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Pixel dimensions of the bitmap for the wrapped text: [width, height]
+/// One half-width column is 8 × scale pixels wide, one line 16 × scale pixels tall
+pub fn bitmap_size(text: &str, max_chars_per_line: u32, scale: u32) -> Vec<u32> {
+    let line_count = count_wrapped_lines_of_output(&justify_text(text, max_chars_per_line));
+    let (width, height) = bitmap_dimensions(line_count, max_chars_per_line, scale);
+    vec![width, height]
+}
+
+fn bitmap_dimensions(line_count: u32, max_chars_per_line: u32, scale: u32) -> (u32, u32) {
+    let scale = scale.max(1);
+    (
+        max_chars_per_line.saturating_mul(GLYPH_WIDTH.saturating_mul(scale)),
+        line_count.saturating_mul(GLYPH_HEIGHT.saturating_mul(scale)),
+    )
+}
+
+/// Rasterize the wrapped text into an RGBA buffer (row-major, 4 bytes per pixel)
+/// ASCII is drawn with the embedded 8×16 font; other characters get an outlined box
+/// of their column width. Colors are 0xRRGGBBAA. Oversized canvases return an empty buffer.
+pub fn render_bitmap(
+    text: &str,
+    max_chars_per_line: u32,
+    scale: u32,
+    fg_rgba: u32,
+    bg_rgba: u32,
+) -> Vec<u8> {
+    let scale = scale.max(1);
+    let justified = justify_text(text, max_chars_per_line);
+    let (width, height) = bitmap_dimensions(
+        count_wrapped_lines_of_output(&justified),
+        max_chars_per_line,
+        scale,
+    );
+
+    let byte_len = (width as u64 * height as u64).saturating_mul(4);
+    if byte_len == 0 || byte_len > MAX_BITMAP_BYTES {
+        return Vec::new();
+    }
+
+    let mut canvas = Canvas {
+        pixels: bg_rgba.to_be_bytes().repeat((byte_len / 4) as usize),
+        width,
+        height,
+        color: fg_rgba.to_be_bytes(),
+        scale,
+    };
+
+    // The font is a fixed grid, so every line is one glyph tall whatever its script
+    let font_size_px = (GLYPH_HEIGHT * scale) as f64;
+    for line in layout_lines_with_heights(
+        &justified,
+        max_chars_per_line,
+        font_size_px,
+        1.0,
+        0.0,
+        Align::Left,
+        RubySyntax::None,
+        ScriptLineHeights::UNIFORM,
+    ) {
+        let mut x = line.x_px as u32;
+        let y = line.y_px as u32;
+        for c in line.text.chars() {
+            let columns = width_of(c);
+            canvas.draw_char(c, columns, x, y);
+            x += columns * GLYPH_WIDTH * scale;
+        }
+    }
+
+    canvas.pixels
+}
+
+struct Canvas {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    color: [u8; 4],
+    scale: u32,
+}
+
+impl Canvas {
+    /// Fill one font pixel, i.e. a scale × scale block
+    fn plot(&mut self, x: u32, y: u32) {
+        for dy in 0..self.scale {
+            for dx in 0..self.scale {
+                let (px, py) = (x * self.scale + dx, y * self.scale + dy);
+                if px < self.width && py < self.height {
+                    let offset = ((py as usize * self.width as usize) + px as usize) * 4;
+                    self.pixels[offset..offset + 4].copy_from_slice(&self.color);
+                }
+            }
+        }
+    }
+
+    fn draw_char(&mut self, c: char, columns: u32, x_px: u32, y_px: u32) {
+        let (origin_x, origin_y) = (x_px / self.scale, y_px / self.scale);
+
+        match c {
+            ' '..='~' => {
+                let glyph = ASCII_GLYPHS[(c as u32 - 0x20) as usize];
+                for (row, bits) in glyph.iter().enumerate() {
+                    for col in 0..GLYPH_WIDTH {
+                        if bits & (0x80 >> col) != 0 {
+                            self.plot(origin_x + col, origin_y + row as u32);
+                        }
+                    }
+                }
+            }
+            _ if c.is_whitespace() => {}
+            _ => {
+                // Fallback box inset by one pixel on each side
+                let box_width = columns * GLYPH_WIDTH;
+                for col in 1..box_width - 1 {
+                    self.plot(origin_x + col, origin_y + 1);
+                    self.plot(origin_x + col, origin_y + GLYPH_HEIGHT - 2);
+                }
+                for row in 1..GLYPH_HEIGHT - 1 {
+                    self.plot(origin_x + 1, origin_y + row);
+                    self.plot(origin_x + box_width - 2, origin_y + row);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FG: u32 = 0x000000FF;
+    const BG: u32 = 0xFFFFFFFF;
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> u32 {
+        let offset = (y * width + x) as usize * 4;
+        u32::from_be_bytes(pixels[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn bitmap_of_ascii_and_a_box() {
+        assert_eq!(bitmap_size("A漢", 4, 1), [32, 16]);
+        // "A漢" / "seco" / "nd", each line 32 pixels tall at twice the size
+        assert_eq!(bitmap_size("A漢 second", 4, 2), [64, 96]);
+        let pixels = render_bitmap("A漢", 4, 1, FG, BG);
+        assert_eq!(pixels.len(), 32 * 16 * 4);
+        // 漢 is a 16×16 box from x = 8, outlined one pixel in
+        assert_eq!(pixel(&pixels, 32, 9, 1), FG);
+        assert_eq!(pixel(&pixels, 32, 22, 14), FG);
+        assert_eq!(pixel(&pixels, 32, 8, 0), BG);
+        assert_eq!(pixel(&pixels, 32, 15, 8), BG);
+        // The last two columns are empty
+        assert!((0..16).all(|y| (24..32).all(|x| pixel(&pixels, 32, x, y) == BG)));
+        assert!((0..16).any(|y| (0..8).any(|x| pixel(&pixels, 32, x, y) == FG)));
+    }
+
+    #[test]
+    fn scale_repeats_each_font_pixel() {
+        let small = render_bitmap("x", 1, 1, FG, BG);
+        let large = render_bitmap("x", 1, 3, FG, BG);
+        for y in 0..16 {
+            for x in 0..8 {
+                assert_eq!(
+                    pixel(&large, 24, x * 3 + 2, y * 3 + 1),
+                    pixel(&small, 8, x, y)
+                );
+            }
+        }
+        assert!(render_bitmap("", 4, 1, FG, BG).is_empty());
+    }
+
+    #[test]
+    fn huge_widths_and_scales_saturate() {
+        assert_eq!(bitmap_size("x", u32::MAX, 1), [u32::MAX, 16]);
+        assert_eq!(bitmap_size("x", 1, u32::MAX), [u32::MAX, u32::MAX]);
+        // Far over MAX_BITMAP_BYTES, so nothing is allocated
+        assert!(render_bitmap("x", 1, u32::MAX, FG, BG).is_empty());
+        assert!(render_bitmap("x", u32::MAX, u32::MAX, FG, BG).is_empty());
+        // 2,400,000 × 48 pixels is 460 MB
+        assert!(render_bitmap("x", 100_000, 3, FG, BG).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Hard-wrapped text unwrapped into paragraphs and wrapped again at a new width
+//! Text copied out of a PDF or an email keeps the line breaks of its old width. reflow_text
+//! joins the lines of each prose paragraph and list item (see paragraphs.rs), leaves headings,
+//! code blocks and blank lines as they are, and wraps the result like justify_text. Words the
+//! source hyphenated at a line end ("justifi-\ncation") are healed back into one word.
+
+use crate::paragraphs::{paragraph_segments, ParagraphKind};
+use crate::{check_max_chars_per_line, is_cjk_char, justify_text, WebtoolsError};
+
+/// The hyphens a line may end with: ASCII '-' and U+2010 HYPHEN
+const HYPHENS: [char; 2] = ['-', '\u{2010}'];
+
+/// Whether c is CJK text or CJK punctuation, which lines join around without a space
+fn is_cjk_text(c: char) -> bool {
+    is_cjk_char(c) || matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF60}')
+}
+
+/// What goes between a joined line ending in left and the next line's right, and whether the
+/// hyphen ending left is dropped
+/// Two CJK characters (or CJK punctuation) join with nothing. A hyphen after a letter or digit joins with nothing:
+/// it is dropped before a lowercase letter (the word was split at the line end) unless
+/// keep_hyphens, and kept before anything else alphanumeric ("Anglo-\nSaxon", "COVID-\n19").
+/// Anything else joins with a space.
+fn join_at(left: &str, right: &str, keep_hyphens: bool) -> (&'static str, bool) {
+    let mut before = left.chars().rev();
+    let (Some(last), Some(after)) = (before.next(), right.chars().next()) else {
+        return (" ", false);
+    };
+    if is_cjk_text(last) && is_cjk_text(after) {
+        return ("", false);
+    }
+    let hyphenated = HYPHENS.contains(&last)
+        && before
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() && !is_cjk_char(c));
+    if hyphenated && after.is_alphanumeric() && !is_cjk_char(after) {
+        return ("", after.is_lowercase() && !keep_hyphens);
+    }
+    (" ", false)
+}
+
+/// The lines of one paragraph joined into one, the first line's indent kept
+fn join_lines(paragraph: &str, keep_hyphens: bool) -> String {
+    let mut lines = paragraph
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line));
+    let mut joined = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        let right = line.trim();
+        if right.is_empty() {
+            continue;
+        }
+        joined.truncate(joined.trim_end().len());
+        let (gap, drop_hyphen) = join_at(&joined, right, keep_hyphens);
+        if drop_hyphen {
+            joined.pop();
+        }
+        joined.push_str(gap);
+        joined.push_str(right);
+    }
+    joined
+}
+
+/// text with the lines of every prose paragraph and list item joined, then wrapped like
+/// justify_text
+/// Lines join with a space, or with nothing between two CJK characters; a line ending in a
+/// hyphen after a letter or digit joins the next with nothing, dropping the hyphen when the
+/// next line starts with a lowercase letter. keep_hyphens keeps every hyphen, for text whose line-end
+/// hyphens are real compounds ("state-\nof-the-art"). Headings, fenced code blocks, blank
+/// lines and a signature are kept as they are.
+pub fn reflow_text(
+    text: &str,
+    max_chars_per_line: u32,
+    keep_hyphens: bool,
+) -> Result<String, WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let segments: Vec<String> = paragraph_segments(text)
+        .into_iter()
+        .map(|segment| match segment.kind {
+            ParagraphKind::Prose | ParagraphKind::ListItem => {
+                join_lines(segment.text, keep_hyphens)
+            }
+            ParagraphKind::CodeBlock
+            | ParagraphKind::Heading
+            | ParagraphKind::Blank
+            | ParagraphKind::Signature => segment.text.to_string(),
+        })
+        .collect();
+    let mut reflowed = segments.join("\n");
+    if text.ends_with('\n') {
+        reflowed.push('\n');
+    }
+    Ok(justify_text(&reflowed, max_chars_per_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_MAX_CHARS_PER_LINE;
+
+    /// A paragraph hard-wrapped by a PDF export, words hyphenated at two of its line ends
+    const PDF_PARAGRAPH: &str = "Full justifi-\ncation spreads the words of a\nline across the \
+        col-\numn.\n\n## Notes\nSee the Anglo-\nSaxon types.";
+
+    fn reflowed(text: &str, width: u32, keep_hyphens: bool) -> String {
+        reflow_text(text, width, keep_hyphens).unwrap()
+    }
+
+    #[test]
+    fn hyphenated_words_heal_unless_kept() {
+        // The hyphen before a capital stays either way
+        assert_eq!(
+            reflowed(PDF_PARAGRAPH, 80, false),
+            "Full justification spreads the words of a line across the column.\r\n\r\n## Notes\r\n\
+             See the Anglo-Saxon types."
+        );
+        assert_eq!(
+            reflowed(PDF_PARAGRAPH, 80, true),
+            "Full justifi-cation spreads the words of a line across the col-umn.\r\n\r\n\
+             ## Notes\r\nSee the Anglo-Saxon types."
+        );
+        assert_eq!(
+            reflowed("a state-\nof-the-art tool", 40, true),
+            "a state-of-the-art tool"
+        );
+        assert_eq!(
+            reflowed("pages 10-\n12 and - \nmore", 40, false),
+            "pages 10-12 and - more"
+        );
+    }
+
+    #[test]
+    fn chinese_lines_join_with_nothing() {
+        assert_eq!(
+            reflowed(
+                "这是一个被硬换行\n的中文段落，\n没有连字符处理。",
+                60,
+                false
+            ),
+            "这是一个被硬换行的中文段落，没有连字符处理。"
+        );
+    }
+
+    #[test]
+    fn list_items_stay_apart() {
+        assert_eq!(
+            reflowed("- item one\n  continued\n- item-\n  two", 40, false),
+            "- item one continued\r\n- itemtwo"
+        );
+    }
+
+    #[test]
+    fn widths_over_the_cap_are_rejected() {
+        assert!(matches!(
+            reflow_text(PDF_PARAGRAPH, MAX_MAX_CHARS_PER_LINE + 1, false),
+            Err(WebtoolsError::WidthTooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+//! Runs of one character repeated over and over, cut before wrapping
+//! Pasted spam ("wwwwww…", "哈哈哈…" fifty thousand times) makes an image taller than any
+//! canvas and nothing anyone reads. JustifyOptions' maxCharRepeat keeps the first N of such a
+//! run and drops the rest, before any width is measured. A repeat is counted in clusters: an
+//! emoji grapheme (a ZWJ sequence, a flag, an emoji with its skin tone) or a character with the
+//! combining marks and presentation selectors after it, so 👍🏽👍🏽 is two of one and a cut
+//! never parts a letter from its accent. Whitespace runs are indentation and blank lines, and
+//! are never cut.
+
+use std::borrow::Cow;
+
+use crate::emoji::{emoji_len, VS15, VS16};
+use crate::marks::is_combining_mark;
+
+/// Byte offset and length of every cluster of text, in order
+fn clusters(text: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut clusters = Vec::new();
+    let mut offset = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let mut end = i + emoji_len(&chars, i).unwrap_or(1);
+        while chars
+            .get(end)
+            .is_some_and(|&c| is_combining_mark(c) || c == VS15 || c == VS16)
+        {
+            end += 1;
+        }
+        let len: usize = chars[i..end].iter().map(|c| c.len_utf8()).sum();
+        clusters.push((offset, len));
+        offset += len;
+        i = end;
+    }
+    clusters
+}
+
+/// Every run of one non-whitespace cluster repeated: (byte offset, cluster length in bytes,
+/// times in a row), a cluster that isn't repeated being a run of 1
+pub(crate) fn repeat_runs(text: &str) -> Vec<(usize, usize, usize)> {
+    let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+    for (offset, len) in clusters(text) {
+        let cluster = &text[offset..offset + len];
+        if cluster.starts_with(char::is_whitespace) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((start, run_len, count))
+                if *start + *run_len * *count == offset
+                    && &text[*start..*start + *run_len] == cluster =>
+            {
+                *count += 1
+            }
+            _ => runs.push((offset, len, 1)),
+        }
+    }
+    runs
+}
+
+/// Most times one cluster (a character, or an emoji grapheme) repeats in a row in text,
+/// whitespace aside; 0 for text of nothing but whitespace
+pub fn longest_char_repeat(text: &str) -> usize {
+    repeat_runs(text)
+        .into_iter()
+        .map(|(_, _, count)| count)
+        .max()
+        .unwrap_or(0)
+}
+
+/// text with every run of one cluster repeated more than max_char_repeat times cut to its
+/// first max_char_repeat
+/// The limit counts repeats, not columns: a run of 哈 is cut to max_char_repeat characters,
+/// twice as many columns. Whitespace is left alone, and a limit of 0 changes nothing.
+pub fn limit_repeats(text: &str, max_char_repeat: u32) -> Cow<'_, str> {
+    let max = max_char_repeat as usize;
+    if max == 0 {
+        return Cow::Borrowed(text);
+    }
+    let runs: Vec<(usize, usize, usize)> = repeat_runs(text)
+        .into_iter()
+        .filter(|&(_, _, count)| count > max)
+        .collect();
+    if runs.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut limited = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, len, count) in runs {
+        limited.push_str(&text[copied..start + len * max]);
+        copied = start + len * count;
+    }
+    limited.push_str(&text[copied..]);
+    Cow::Owned(limited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stats_after_append, JustifyOptions, TextStats, WarningCode};
+
+    #[test]
+    fn a_long_run_is_cut_to_its_first_few() {
+        let options = JustifyOptions {
+            max_chars_per_line: 20,
+            max_char_repeat: 8,
+            ..JustifyOptions::default()
+        };
+        let spam = "哈".repeat(100_000);
+        assert_eq!(limit_repeats(&spam, 8), "哈".repeat(8));
+        assert_eq!(options.justify(&spam), "哈".repeat(8));
+        assert_eq!(limit_repeats(&spam, 0), spam);
+        let warnings = options.warnings(&spam);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, WarningCode::RepeatTruncated);
+    }
+
+    #[test]
+    fn graphemes_repeat_whole_and_whitespace_is_left_alone() {
+        let thumbs = format!("ok {}!", "👍🏽".repeat(1000));
+        assert_eq!(limit_repeats(&thumbs, 3), format!("ok {}!", "👍🏽".repeat(3)));
+        let accented = "e\u{301}".repeat(10);
+        assert_eq!(limit_repeats(&accented, 2), "e\u{301}".repeat(2));
+        let indented = format!("{}x\n\n\n\nwwwwwwwwwwwwwwwwwwwww", " ".repeat(16));
+        assert_eq!(
+            limit_repeats(&indented, 8),
+            format!("{}x\n\n\n\nwwwwwwww", " ".repeat(16))
+        );
+    }
+
+    #[test]
+    fn stats_count_the_longest_run() {
+        assert_eq!(
+            TextStats::of(&"哈".repeat(100_000)).longest_char_repeat,
+            100_000
+        );
+        assert_eq!(TextStats::of(" \n\t").longest_char_repeat, 0);
+        assert_eq!(TextStats::of("aa\naaa").longest_char_repeat, 3);
+        // An append extending the run the text ended with
+        let typed = stats_after_append(&TextStats::of("xx www"), "xx wwwwwwwwww");
+        assert_eq!(typed.longest_char_repeat, 10);
+        assert_eq!(typed, TextStats::of("xx wwwwwwwwww"));
+    }
+}
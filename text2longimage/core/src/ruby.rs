@@ -0,0 +1,320 @@
+//! Inline ruby (furigana) annotations in Japanese source text
+//! 漢字《かんじ》 annotates the run of kanji before the brackets; Aozora Bunko text can also mark
+//! the base explicitly as ｜振仮名《ふりがな》. The wrapper keeps each annotation on one line and
+//! counts only its base toward the width, since the reading is drawn above the line.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::{expected_values, find_value};
+use crate::metrics::{fits_width, place_chars_with, WidthModel, WrapSink};
+use crate::{OptionValue, WebtoolsError};
+
+/// Ruby notation recognised by the wrapper
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RubySyntax {
+    /// Brackets are ordinary text
+    #[default]
+    None,
+    /// 漢字《かんじ》: the reading belongs to the kanji run right before 《
+    Kanji,
+    /// Aozora Bunko: ｜base《reading》 for any base, and the kanji form without ｜
+    Aozora,
+}
+
+impl RubySyntax {
+    /// Parse "none", "kanji" or "aozora"
+    pub fn parse(syntax: &str) -> Result<Self, WebtoolsError> {
+        find_value(|name| syntax == name).ok_or_else(|| {
+            WebtoolsError::invalid_argument(format!(
+                "Unknown ruby syntax: {} (expected {})",
+                syntax,
+                expected_values::<RubySyntax>()
+            ))
+        })
+    }
+}
+
+impl OptionValue for RubySyntax {
+    const KIND: &'static str = "rubySyntax";
+    const USED_BY: &'static [&'static str] = &["JustifyOptions.rubySyntax"];
+    const ALL: &'static [Self] = &[RubySyntax::None, RubySyntax::Kanji, RubySyntax::Aozora];
+
+    fn name(self) -> &'static str {
+        match self {
+            RubySyntax::None => "none",
+            RubySyntax::Kanji => "kanji",
+            RubySyntax::Aozora => "aozora",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            RubySyntax::None => "Brackets are ordinary text",
+            RubySyntax::Kanji => "漢字《かんじ》: the reading belongs to the kanji run before 《",
+            RubySyntax::Aozora => {
+                "Aozora Bunko: ｜base《reading》 for any base, and the kanji form"
+            }
+        }
+    }
+}
+
+/// One annotation found in a line
+pub(crate) struct RubySpan<'a> {
+    /// Byte range of the whole notation, ｜ and brackets included
+    pub(crate) range: Range<usize>,
+    pub(crate) base: &'a str,
+    pub(crate) reading: &'a str,
+}
+
+/// Characters the kanji form takes as its base: Han plus 々, 〆 and ヶ
+fn is_ruby_base_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F
+    ) || matches!(c, '々' | '〆' | 'ヶ')
+}
+
+/// Start of the base for the reading opening at `open`, without reaching back before `floor`
+fn base_start(line: &str, floor: usize, open: usize, syntax: RubySyntax) -> Option<usize> {
+    if syntax == RubySyntax::Aozora {
+        // A ｜ whose base would hold a literal 》 belongs to brackets that were left as text
+        if let Some(bar) = line[floor..open].rfind('｜') {
+            let bar = floor + bar;
+            let base = &line[bar + '｜'.len_utf8()..open];
+            if !base.contains('》') {
+                return (!base.is_empty()).then_some(bar);
+            }
+        }
+    }
+
+    line[floor..open]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ruby_base_char(c))
+        .last()
+        .map(|(index, _)| floor + index)
+}
+
+/// Annotations of one line in order
+/// A 《 without a closing 》 on the same line, an empty reading or a missing base leaves the
+/// brackets as literal text.
+pub(crate) fn ruby_spans(line: &str, syntax: RubySyntax) -> Vec<RubySpan<'_>> {
+    let mut spans = Vec::new();
+    if syntax == RubySyntax::None {
+        return spans;
+    }
+
+    let mut floor = 0;
+    let mut search = 0;
+    while let Some(offset) = line[search..].find('《') {
+        let open = search + offset;
+        let reading_start = open + '《'.len_utf8();
+        let Some(length) = line[reading_start..].find('》') else {
+            break;
+        };
+        let reading = &line[reading_start..reading_start + length];
+        let end = reading_start + length + '》'.len_utf8();
+
+        // A second 《 before the 》 means this one was never closed
+        let start = if reading.is_empty() || reading.contains('《') {
+            None
+        } else {
+            base_start(line, floor, open, syntax)
+        };
+        match start {
+            Some(start) => {
+                let base_from = if line[start..].starts_with('｜') {
+                    start + '｜'.len_utf8()
+                } else {
+                    start
+                };
+                spans.push(RubySpan {
+                    range: start..end,
+                    base: &line[base_from..open],
+                    reading,
+                });
+                floor = end;
+                search = end;
+            }
+            None => search = reading_start,
+        }
+    }
+
+    spans
+}
+
+/// The line as drawn: every annotation replaced by its base
+pub(crate) fn ruby_display_text<'a>(line: &'a str, spans: &[RubySpan<'a>]) -> Cow<'a, str> {
+    if spans.is_empty() {
+        return Cow::Borrowed(line);
+    }
+
+    let mut display = String::with_capacity(line.len());
+    let mut position = 0;
+    for span in spans {
+        display.push_str(&line[position..span.range.start]);
+        display.push_str(span.base);
+        position = span.range.end;
+    }
+    display.push_str(&line[position..]);
+    Cow::Owned(display)
+}
+
+/// Character wrapping where each annotation is one unit as wide as its base
+/// The notation is written out unchanged; a unit wider than the line gets a line to itself.
+pub(crate) fn wrap_ruby_with<M: WidthModel, S: WrapSink>(
+    line: &str,
+    spans: &[RubySpan],
+    max_width: f64,
+    model: &M,
+    out: &mut S,
+) {
+    let mut current_line_width = 0.0f64;
+    let mut position = 0;
+    for span in spans {
+        let before = &line[position..span.range.start];
+        place_chars_with(before, max_width, model, &mut current_line_width, out);
+
+        let base_width: f64 = span.base.chars().map(|c| model.char_width(c)).sum();
+        if current_line_width > 0.0 && !fits_width(current_line_width + base_width, max_width) {
+            out.line_break();
+            current_line_width = 0.0;
+        }
+        out.push_text(&line[span.range.clone()]);
+        current_line_width += base_width;
+        position = span.range.end;
+    }
+    place_chars_with(
+        &line[position..],
+        max_width,
+        model,
+        &mut current_line_width,
+        out,
+    );
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout_lines_with_ruby, Align, JustifyOptions};
+
+    fn justify(text: &str, max_chars_per_line: u32, ruby_syntax: RubySyntax) -> String {
+        JustifyOptions {
+            max_chars_per_line,
+            ruby_syntax,
+            ..JustifyOptions::default()
+        }
+        .justify(text)
+    }
+
+    /// A laid-out line as drawn, with its readings as (base, reading, xPx)
+    type Drawn<'a> = (String, Vec<(&'a str, &'a str, f64)>);
+
+    fn drawn(justified: &str, max_chars_per_line: u32, ruby_syntax: RubySyntax) -> Vec<Drawn<'_>> {
+        layout_lines_with_ruby(
+            justified,
+            max_chars_per_line,
+            10.0,
+            1.0,
+            0.0,
+            Align::Left,
+            ruby_syntax,
+        )
+        .into_iter()
+        .map(|line| {
+            let ruby = line
+                .ruby
+                .iter()
+                .map(|r| (r.base, r.reading, r.x_px))
+                .collect();
+            (line.text.into_owned(), ruby)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn an_annotation_at_the_margin_moves_whole() {
+        let text = "今日は漢字《かんじ》を";
+        // Only the base's four columns count: 今日は漢字 is exactly 10
+        let wrapped = justify(text, 10, RubySyntax::Kanji);
+        assert_eq!(wrapped, "今日は漢字《かんじ》\r\nを");
+        assert_eq!(
+            drawn(&wrapped, 10, RubySyntax::Kanji),
+            [
+                ("今日は漢字".to_string(), vec![("漢字", "かんじ", 30.0)]),
+                ("を".to_string(), vec![]),
+            ]
+        );
+        // One column short, the base and its reading go to the next line together
+        let wrapped = justify(text, 8, RubySyntax::Kanji);
+        assert_eq!(wrapped, "今日は\r\n漢字《かんじ》を");
+        assert_eq!(
+            drawn(&wrapped, 8, RubySyntax::Kanji)[1],
+            ("漢字を".to_string(), vec![("漢字", "かんじ", 0.0)])
+        );
+        // Without a syntax the brackets are text, and break like it
+        assert_eq!(
+            justify("漢字《かんじ》を", 6, RubySyntax::None),
+            "漢字《\r\nかんじ\r\n》を"
+        );
+    }
+
+    #[test]
+    fn a_base_wider_than_the_line_gets_one_to_itself() {
+        assert_eq!(
+            justify("漢字漢字《かんじかんじ》", 4, RubySyntax::Kanji),
+            "漢字漢字《かんじかんじ》"
+        );
+    }
+
+    #[test]
+    fn aozora_bars_mark_the_base() {
+        let text = "これは｜振仮名《ふりがな》です";
+        let wrapped = justify(text, 6, RubySyntax::Aozora);
+        assert_eq!(wrapped, "これは\r\n｜振仮名《ふりがな》\r\nです");
+        assert_eq!(
+            drawn(&wrapped, 6, RubySyntax::Aozora)[1],
+            ("振仮名".to_string(), vec![("振仮名", "ふりがな", 0.0)])
+        );
+        // The kanji form leaves the bar as text before its base
+        assert_eq!(
+            justify(text, 6, RubySyntax::Kanji),
+            "これは\r\n｜\r\n振仮名《ふりがな》\r\nです"
+        );
+    }
+
+    #[test]
+    fn unclosed_or_empty_brackets_are_literal_text() {
+        assert_eq!(
+            justify("漢字《かんじ の続き", 6, RubySyntax::Kanji),
+            "漢字《\r\nかんじ\r\n の続\r\nき"
+        );
+        assert_eq!(
+            justify("漢字《》です", 6, RubySyntax::Kanji),
+            "漢字《\r\n》です"
+        );
+        assert!(ruby_spans("漢字《かんじ の続き", RubySyntax::Kanji).is_empty());
+        // A 《 opened again before the 》 is the one that closes
+        let spans = ruby_spans("漢《字《かんじ》", RubySyntax::Kanji);
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].base, spans[0].reading), ("字", "かんじ"));
+        assert_eq!(ruby_display_text("漢《字《かんじ》", &spans), "漢《字");
+    }
+
+    #[test]
+    fn syntax_names() {
+        assert_eq!(RubySyntax::parse("aozora").unwrap(), RubySyntax::Aozora);
+        let error = RubySyntax::parse("pinyin").unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+        assert!(
+            error
+                .to_string()
+                .ends_with("(expected none, kanji or aozora)"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Unbreakable runs: the words the word wrapper can only overflow with
+//! On lines without CJK a word is never broken, so a pasted megabyte of base64 would make one
+//! line as wide as itself. JustifyOptions' maxUnbreakableRun cuts such runs into lines instead,
+//! and the stats and validate_input report them so a UI can warn before wrapping.
+
+use crate::is_cjk;
+
+/// Default maxUnbreakableRun, in columns: far wider than any real word, URL or path
+pub const DEFAULT_MAX_UNBREAKABLE_RUN: u32 = 10_000;
+
+/// Width of every word on the lines the word wrapper places, measured by byte length as
+/// justify_text_english measures words
+pub(crate) fn unbreakable_runs(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.split('\n')
+        .filter(|line| !is_cjk(line))
+        .flat_map(str::split_whitespace)
+        .map(str::len)
+}
+
+/// Widest unbreakable run in the text, in columns (0 for none)
+pub fn longest_unbreakable_run(text: &str) -> usize {
+    unbreakable_runs(text).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::limits::lock_limits;
+    use crate::{
+        calculate_text_width, set_max_input_size, validate_input_with_limit, JustifyOptions,
+        TextStats, ValidationWarning, DEFAULT_MAX_INPUT_SIZE,
+    };
+
+    /// A pasted base64 blob of len bytes: one word, no character repeated twice in a row
+    fn base64_blob(len: usize) -> String {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        alphabet.chars().cycle().take(len).collect()
+    }
+
+    #[test]
+    fn runs_are_the_words_of_lines_without_cjk() {
+        assert_eq!(longest_unbreakable_run("a bcd ef\nghijk"), 5);
+        // Measured in bytes, as justify_text_english measures words
+        assert_eq!(longest_unbreakable_run("café"), 5);
+        // CJK lines break per character, so they have no runs
+        assert_eq!(longest_unbreakable_run("中文abcdefghij\nxy"), 2);
+        assert_eq!(longest_unbreakable_run(" \n\t"), 0);
+    }
+
+    #[test]
+    fn a_run_past_the_cap_is_cut_and_reported() {
+        let blob = base64_blob(100);
+        let options = JustifyOptions {
+            max_chars_per_line: 20,
+            max_unbreakable_run: 30,
+            ..JustifyOptions::default()
+        };
+        let output = options.justify(&format!("see {blob} here"));
+        assert_eq!(output.replace("\r\n", ""), format!("see{blob}here"));
+        assert!(output
+            .split("\r\n")
+            .all(|line| calculate_text_width(line) <= 30));
+        assert_eq!(TextStats::of(&blob).longest_unbreakable_run, 100);
+    }
+
+    #[test]
+    fn a_megabyte_token_is_cut_quickly() {
+        let _limits = lock_limits();
+        // Room for the blob, twice the default limit
+        set_max_input_size(2_000_000);
+        let blob = base64_blob(1_000_000);
+        let options = JustifyOptions {
+            max_chars_per_line: 40,
+            ..JustifyOptions::default()
+        };
+
+        let started = Instant::now();
+        let output = options.justify(&blob);
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "took {:?}",
+            started.elapsed()
+        );
+        let bound = u64::from(DEFAULT_MAX_UNBREAKABLE_RUN);
+        assert!(output
+            .split("\r\n")
+            .all(|line| calculate_text_width(line) <= bound));
+        assert_eq!(output.replace("\r\n", ""), blob);
+
+        assert_eq!(TextStats::of(&blob).longest_unbreakable_run, 1_000_000);
+        let report = validate_input_with_limit(&blob, 40, 2_000_000);
+        set_max_input_size(DEFAULT_MAX_INPUT_SIZE);
+        assert!(report.valid, "{:?}", report.errors);
+        assert_eq!(
+            report.warnings,
+            [
+                ValidationWarning::LongTokens {
+                    count: 1,
+                    longest: 1_000_000,
+                    max_chars_per_line: 40
+                },
+                ValidationWarning::UnbreakableRuns {
+                    count: 1,
+                    longest: 1_000_000,
+                    max_unbreakable_run: DEFAULT_MAX_UNBREAKABLE_RUN
+                },
+            ]
+        );
+    }
+}
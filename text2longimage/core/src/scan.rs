@@ -0,0 +1,92 @@
+//! The ASCII fast path under is_cjk, width_of_str and get_text_stats
+//! Most text is mostly ASCII, and every byte of an ASCII run is one narrow, single-column,
+//! non-CJK character, so those scans skip the run before decoding what follows. The run is
+//! found eight bytes at a time in plain integer code, a build for any engine. With the simd
+//! feature a wasm build scans sixteen at a time with SIMD instructions instead; that .wasm
+//! only loads where wasm SIMD does (Safari 16.4 and later), so sites that still serve older
+//! engines ship both builds and pick one by feature detection.
+
+/// Length of the leading run of ASCII bytes
+pub(crate) fn ascii_prefix_len(bytes: &[u8]) -> usize {
+    #[cfg(all(feature = "simd", target_arch = "wasm32"))]
+    let len = ascii_prefix_len_simd128(bytes);
+    #[cfg(not(all(feature = "simd", target_arch = "wasm32")))]
+    let len = 0;
+
+    len + ascii_prefix_len_scalar(&bytes[len..])
+}
+
+/// Length of the leading run of ASCII bytes, checked eight bytes at a time
+fn ascii_prefix_len_scalar(bytes: &[u8]) -> usize {
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+    let mut len = 0;
+    for chunk in bytes.chunks_exact(8) {
+        let word = u64::from_ne_bytes(chunk.try_into().unwrap_or([0x80; 8]));
+        if word & HIGH_BITS != 0 {
+            break;
+        }
+        len += 8;
+    }
+
+    len + bytes[len..]
+        .iter()
+        .position(|&b| !b.is_ascii())
+        .unwrap_or(bytes.len() - len)
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32"))]
+/// Length of the ASCII run at the start of bytes in whole sixteen-byte blocks; the scalar
+/// scan finds the rest of it
+#[target_feature(enable = "simd128")]
+fn ascii_prefix_len_simd128(bytes: &[u8]) -> usize {
+    use core::arch::wasm32::{u8x16_bitmask, v128, v128_load};
+
+    let mut len = 0;
+    for chunk in bytes.chunks_exact(16) {
+        // SAFETY: chunk is 16 readable bytes, and v128_load has no alignment requirement
+        let block = unsafe { v128_load(chunk.as_ptr().cast::<v128>()) };
+        if u8x16_bitmask(block) != 0 {
+            break;
+        }
+        len += 16;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::random_texts;
+    use crate::{is_cjk, is_cjk_char, width_of, width_of_str};
+
+    #[test]
+    fn prefix_ends_at_the_first_non_ascii_byte() {
+        // Every length around the eight-byte words, with the non-ASCII byte at every place
+        for len in 0..40 {
+            let ascii = "a".repeat(len);
+            assert_eq!(ascii_prefix_len(ascii.as_bytes()), len);
+            for at in 0..len {
+                let mut bytes = ascii.clone().into_bytes();
+                bytes[at] = 0xE6;
+                assert_eq!(
+                    ascii_prefix_len(&bytes),
+                    at,
+                    "{} bytes, 0xE6 at {}",
+                    len,
+                    at
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fast_scans_match_the_character_loop() {
+        for text in random_texts(300) {
+            let text = format!("{} plain ASCII words to skip {}", text, text);
+            assert_eq!(is_cjk(&text), text.chars().any(is_cjk_char), "{:?}", text);
+            let width: u64 = text.chars().map(|c| width_of(c) as u64).sum();
+            assert_eq!(width_of_str(&text), width, "{:?}", text);
+        }
+    }
+}
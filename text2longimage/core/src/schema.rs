@@ -0,0 +1,326 @@
+//! JSON Schema documents for the JSON the core writes
+//! Each kind names one output shape and is derived from the serde type that writes it, so the
+//! schema can't drift from the output: a field added to TextStats shows up in "textStats" on
+//! the next build. The documents are JSON Schema 2020-12, with the shared types under $defs.
+
+use schemars::generate::SchemaSettings;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+use crate::canvas::{LayoutWithWarnings, SizeForWidth, SizeWithWarnings};
+use crate::clean::CleanReportJson;
+use crate::diff::WrapDiff;
+use crate::guides::GuidedLayout;
+use crate::layout::LineCountFit;
+use crate::paginate::{BandedPage, ImageBlock};
+use crate::{
+    Capabilities, CaseTransform, ClassRun, ConfusablesReport, EdgeCharReport, EmojiPlacement,
+    FontCoverage, FormattedStats, JustifiedText, KinsokuSets, LayoutLine, LayoutRange, LineHygiene,
+    MatchSegment, OptionsReport, Paragraph, ParagraphStats, PunctuationReport, QuotedLine,
+    RuntimeConfig, SelfTestReport, StreamedStats, TextDiff, TextEdit, TextStats, TransferReport,
+    ValidationReport, VerticalLayout, WebtoolsError,
+};
+
+/// One output shape json_schema describes
+pub struct SchemaKind {
+    pub name: &'static str,
+    /// The functions whose JSON output it is
+    pub produced_by: &'static str,
+    schema: fn() -> Schema,
+}
+
+/// Every kind json_schema takes, by name
+pub const SCHEMA_KINDS: &[SchemaKind] = &[
+    SchemaKind {
+        name: "textStats",
+        produced_by: "get_text_stats, stats_incremental_json",
+        schema: root_schema::<TextStats>,
+    },
+    SchemaKind {
+        name: "formattedStats",
+        produced_by: "format_stats",
+        schema: root_schema::<FormattedStats>,
+    },
+    SchemaKind {
+        name: "kinsokuSets",
+        produced_by: "kinsoku_sets",
+        schema: root_schema::<KinsokuSets>,
+    },
+    SchemaKind {
+        name: "edgeCharReport",
+        produced_by: "edge_char_report",
+        schema: root_schema::<EdgeCharReport>,
+    },
+    SchemaKind {
+        name: "streamedStats",
+        produced_by: "justify_text_large_with_stats",
+        schema: root_schema::<StreamedStats>,
+    },
+    SchemaKind {
+        name: "paragraphStats",
+        produced_by: "paragraph_stats",
+        schema: root_schema::<Vec<ParagraphStats>>,
+    },
+    SchemaKind {
+        name: "layoutLines",
+        produced_by: "layout_text, layout_text_numbered, layout_text_with_spacing",
+        schema: root_schema::<Vec<LayoutLine>>,
+    },
+    SchemaKind {
+        name: "layoutRange",
+        produced_by: "Document::layout_range_json",
+        schema: root_schema::<LayoutRange>,
+    },
+    SchemaKind {
+        name: "charAdvanceLines",
+        produced_by: "char_advances_lines_with_spacing",
+        schema: root_schema::<Vec<Vec<f32>>>,
+    },
+    SchemaKind {
+        name: "guidedLayout",
+        produced_by: "layout_text_with_guides",
+        schema: root_schema::<GuidedLayout>,
+    },
+    SchemaKind {
+        name: "layoutWithLimits",
+        produced_by: "layout_text_with_limits",
+        schema: root_schema::<LayoutWithWarnings>,
+    },
+    SchemaKind {
+        name: "imageSize",
+        produced_by: "estimate_image_size_with_limits",
+        schema: root_schema::<SizeWithWarnings>,
+    },
+    SchemaKind {
+        name: "imageSizeForWidth",
+        produced_by: "estimate_image_size_for_width",
+        schema: root_schema::<SizeForWidth>,
+    },
+    SchemaKind {
+        name: "lineCountFit",
+        produced_by: "wrap_to_line_count",
+        schema: root_schema::<LineCountFit>,
+    },
+    SchemaKind {
+        name: "verticalLayout",
+        produced_by: "layout_vertical",
+        schema: root_schema::<VerticalLayout>,
+    },
+    SchemaKind {
+        name: "classRuns",
+        produced_by: "classify_runs",
+        schema: root_schema::<Vec<ClassRun>>,
+    },
+    SchemaKind {
+        name: "lineClassRuns",
+        produced_by: "classify_line_runs",
+        schema: root_schema::<Vec<Vec<ClassRun>>>,
+    },
+    SchemaKind {
+        name: "emojiPlacements",
+        produced_by: "emoji_positions",
+        schema: root_schema::<Vec<EmojiPlacement>>,
+    },
+    SchemaKind {
+        name: "imageBlocks",
+        produced_by: "paginate_for_images",
+        schema: root_schema::<Vec<ImageBlock>>,
+    },
+    SchemaKind {
+        name: "columns",
+        produced_by: "layout_columns",
+        schema: root_schema::<Vec<Vec<String>>>,
+    },
+    SchemaKind {
+        name: "bandedPages",
+        produced_by: "paginate_with_bands",
+        schema: root_schema::<Vec<BandedPage>>,
+    },
+    SchemaKind {
+        name: "paragraphs",
+        produced_by: "split_paragraphs",
+        schema: root_schema::<Vec<Paragraph>>,
+    },
+    SchemaKind {
+        name: "quotedLines",
+        produced_by: "strip_quote_prefix_detailed",
+        schema: root_schema::<Vec<QuotedLine>>,
+    },
+    SchemaKind {
+        name: "matchSegments",
+        produced_by: "find_in_wrapped",
+        schema: root_schema::<Vec<MatchSegment>>,
+    },
+    SchemaKind {
+        name: "breakOpportunities",
+        produced_by: "break_opportunities_json",
+        schema: root_schema::<Vec<usize>>,
+    },
+    SchemaKind {
+        name: "wrapDiff",
+        produced_by: "diff_wrap",
+        schema: root_schema::<WrapDiff>,
+    },
+    SchemaKind {
+        name: "textDiff",
+        produced_by: "diff_texts",
+        schema: root_schema::<TextDiff>,
+    },
+    SchemaKind {
+        name: "caseTransform",
+        produced_by: "transform_case_json",
+        schema: root_schema::<CaseTransform>,
+    },
+    SchemaKind {
+        name: "punctuationReport",
+        produced_by: "convert_punctuation_report",
+        schema: root_schema::<PunctuationReport>,
+    },
+    SchemaKind {
+        name: "confusablesReport",
+        produced_by: "confusables_report",
+        schema: root_schema::<ConfusablesReport>,
+    },
+    SchemaKind {
+        name: "cleanReport",
+        produced_by: "clean_text_report",
+        schema: root_schema::<CleanReportJson>,
+    },
+    SchemaKind {
+        name: "textEdits",
+        produced_by: "convert_punctuation_edits, clean_text_edits",
+        schema: root_schema::<Vec<TextEdit>>,
+    },
+    #[cfg(feature = "encodings")]
+    SchemaKind {
+        name: "encodingGuess",
+        produced_by: "detect_encoding_json",
+        schema: root_schema::<crate::EncodingGuess>,
+    },
+    SchemaKind {
+        name: "fontCoverage",
+        produced_by: "font_coverage_report",
+        schema: root_schema::<FontCoverage>,
+    },
+    SchemaKind {
+        name: "lineHygiene",
+        produced_by: "line_hygiene_report",
+        schema: root_schema::<LineHygiene>,
+    },
+    SchemaKind {
+        name: "inputReport",
+        produced_by: "validate_input",
+        schema: root_schema::<ValidationReport>,
+    },
+    SchemaKind {
+        name: "optionsReport",
+        produced_by: "OptionsReport::to_json",
+        schema: root_schema::<OptionsReport>,
+    },
+    SchemaKind {
+        name: "justifiedText",
+        produced_by: "JustifyOptions::justify_with_warnings",
+        schema: root_schema::<JustifiedText>,
+    },
+    SchemaKind {
+        name: "batchTexts",
+        produced_by: "batch_justify_text, batch_justify_text_with_report",
+        schema: root_schema::<Vec<String>>,
+    },
+    SchemaKind {
+        name: "transferReport",
+        produced_by: "TransferReport::to_json",
+        schema: root_schema::<TransferReport>,
+    },
+    SchemaKind {
+        name: "capabilities",
+        produced_by: "Capabilities::to_json",
+        schema: root_schema::<Capabilities>,
+    },
+    SchemaKind {
+        name: "runtimeConfig",
+        produced_by: "RuntimeConfig::to_json",
+        schema: root_schema::<RuntimeConfig>,
+    },
+    SchemaKind {
+        name: "selfTest",
+        produced_by: "self_test",
+        schema: root_schema::<SelfTestReport>,
+    },
+    SchemaKind {
+        name: "error",
+        produced_by: "every WebtoolsError, as the wasm module throws it",
+        schema: root_schema::<WebtoolsError>,
+    },
+];
+
+/// T's schema as serde writes it: a skipped-when-empty field is optional
+fn root_schema<T: JsonSchema>() -> Schema {
+    SchemaGenerator::new(SchemaSettings::draft2020_12().for_serialize()).into_root_schema_for::<T>()
+}
+
+/// The JSON Schema document for one of SCHEMA_KINDS, titled with the kind's name
+pub fn json_schema(kind: &str) -> Result<String, WebtoolsError> {
+    let found = SCHEMA_KINDS
+        .iter()
+        .find(|known| known.name == kind)
+        .ok_or_else(|| {
+            let names: Vec<&str> = SCHEMA_KINDS.iter().map(|known| known.name).collect();
+            WebtoolsError::invalid_argument(format!(
+                "Unknown schema kind: {} (expected {})",
+                kind,
+                names.join(", ")
+            ))
+        })?;
+    let mut schema = (found.schema)();
+    schema.insert("title".to_string(), found.name.into());
+    Ok(serde_json::to_string_pretty(&schema).unwrap_or_else(|_| "{}".to_string()))
+}
+
+/// {code, message, details} objects, one shape per code with its details schema, as
+/// WebtoolsError and ValidationWarning serialize
+pub(crate) fn coded_schema(codes: Vec<(&str, Schema)>) -> Schema {
+    let shapes: Vec<Schema> = codes
+        .into_iter()
+        .map(|(code, details)| {
+            json_schema!({
+                "type": "object",
+                "properties": {
+                    "code": { "const": code },
+                    "message": { "type": "string" },
+                    "details": details
+                },
+                "required": ["code", "message", "details"],
+                "additionalProperties": false
+            })
+        })
+        .collect();
+    json_schema!({ "oneOf": shapes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_kind_is_listed_and_titled() {
+        let names: Vec<&str> = SCHEMA_KINDS.iter().map(|kind| kind.name).collect();
+        let listed: Vec<&str> = crate::capabilities()
+            .schemas
+            .iter()
+            .map(|kind| kind.name)
+            .collect();
+        assert_eq!(names, listed);
+        for name in names {
+            let schema: serde_json::Value =
+                serde_json::from_str(&json_schema(name).unwrap()).unwrap();
+            assert_eq!(schema["title"], name);
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        let error = json_schema("nope").unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+        assert!(error.to_string().contains("textStats"), "{error}");
+    }
+}
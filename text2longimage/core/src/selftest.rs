@@ -0,0 +1,248 @@
+//! A small suite the deployed module runs on itself, for bug reports
+//! When a user sees wrong output, the cause can be the build they were served rather than the
+//! wrapper: a stale cached wasm next to newer JavaScript, or a miscompile on one engine.
+//! self_test wraps tiny fixtures and compares them with the output this source gives, checks
+//! that the width model agrees with itself and that chunked wrapping matches whole-text
+//! wrapping, and reports each check with what it expected and what it got. The fixtures are
+//! kept to a few lines so the module barely grows; strict-checks builds add larger ones.
+
+use serde::Serialize;
+
+use crate::{
+    calculate_text_width, get_char_width, justify_text, line_widths, process_text_chunks, TextStats,
+};
+
+/// Name, text, maxCharsPerLine and justify_text's output for it
+type WrapFixture = (&'static str, &'static str, u32, &'static str);
+
+/// One fixture per thing the wrapper handles differently
+const WRAP_FIXTURES: &[WrapFixture] = &[
+    (
+        "english",
+        "The quick brown fox jumps over the lazy dog.",
+        16,
+        "The quick brown\r\nfox jumps over\r\nthe lazy dog.",
+    ),
+    (
+        "chinese",
+        "春眠不觉晓，处处闻啼鸟。夜来风雨声，花落知多少。",
+        12,
+        "春眠不觉晓，\r\n处处闻啼鸟。\r\n夜来风雨声，\r\n花落知多少。",
+    ),
+    (
+        "mixed",
+        "用Rust写的wasm模块wraps CJK和English text.",
+        14,
+        "用Rust写的wasm\r\n模块wraps CJK\r\n和English text\r\n.",
+    ),
+    (
+        "japanese",
+        "これは「テスト」です。次の行へ。",
+        10,
+        "これは「テ\r\nスト」です\r\n。次の行へ\r\n。",
+    ),
+    (
+        "emoji",
+        "Ship it 🚀🚀 today 👍🏽 ok",
+        10,
+        "Ship it\r\n🚀🚀\r\ntoday\r\n👍🏽\r\nok",
+    ),
+    (
+        "crlf",
+        "one two\r\nthree four five",
+        9,
+        "one two\r\nthree\r\nfour five",
+    ),
+    (
+        "longWord",
+        "see https://example.com/a/very/long/path now",
+        12,
+        "see\r\nhttps://example.com/a/very/long/path\r\nnow",
+    ),
+    ("empty", "", 10, ""),
+    ("blankLines", "a\n\n\nb", 10, "a\r\n\r\n\r\nb"),
+    (
+        "combiningMarks",
+        "cafe\u{301} cre\u{300}me bru\u{302}le\u{301}e",
+        8,
+        "cafe\u{301}\r\ncre\u{300}me\r\nbru\u{302}le\u{301}e",
+    ),
+    ("fullwidth", "ＡＢＣ１２３ abc", 8, "ＡＢＣ１２３\r\nabc"),
+    (
+        "trailingNewline",
+        "end of text words\n",
+        8,
+        "end of\r\ntext\r\nwords\r\n",
+    ),
+];
+
+#[cfg(feature = "strict-checks")]
+/// Longer fixtures, for strict-checks builds
+const STRICT_WRAP_FIXTURES: &[WrapFixture] = &[
+    (
+        "hangul",
+        "안녕하세요 세계 여러분",
+        8,
+        "안녕하세요\r\n세계\r\n여러분",
+    ),
+    (
+        "article",
+        "长图文字排版需要处理中英文混排（mixed scripts）、标点挤压和emoji 🎉。\n\
+         Long images are read on phones, so lines stay short and words are never split.\n\n\
+         第二段：数字123和URL https://example.com 也要正确换行。",
+        20,
+        "长图文字排版需要处理\r\n中英文混排（mixed sc\r\nripts）、标点挤压和e\r\nmoji 🎉。\r\n\
+         Long images are read\r\non phones, so lines\r\nstay short and words\r\n\
+         are never split.\r\n\r\n第二段：数字123和URL\r\n https://example.com\r\n 也要正确换行。",
+    ),
+];
+
+/// What the width check measures: every width class, in one text
+const WIDTH_SAMPLE: &str = "ASCII é 漢字 かな ＡＢ 👍🏽 한글\u{3000}e\u{301}nd";
+
+/// Lines of the chunking check, all the same number of bytes
+const CHUNK_LINES: u32 = 40;
+
+/// One check and how it went; expected and actual are set only when it failed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+}
+
+/// Every check self_test ran, and whether all of them passed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Record a check named name, passed when actual is expected
+    /// Lets a caller with checks of its own (the wasm module's memory check) add to the report.
+    pub fn check(&mut self, name: impl Into<String>, expected: String, actual: String) {
+        let passed = expected == actual;
+        self.checks.push(SelfTestCheck {
+            name: name.into(),
+            passed,
+            expected: (!passed).then_some(expected),
+            actual: (!passed).then_some(actual),
+        });
+        self.passed = self.checks.iter().all(|check| check.passed);
+    }
+
+    #[cfg(feature = "json")]
+    /// As JSON {passed, checks: [{name, passed, expected?, actual?}]}
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".into())
+    }
+}
+
+/// The width model, measured four ways: get_char_width summed, calculate_text_width,
+/// TextStats' displayWidth and line_widths, on WIDTH_SAMPLE
+fn check_widths(report: &mut SelfTestReport) {
+    let summed: u64 = WIDTH_SAMPLE
+        .chars()
+        .map(|c| u64::from(get_char_width(c)))
+        .sum();
+    let measured = [
+        calculate_text_width(WIDTH_SAMPLE),
+        TextStats::of(WIDTH_SAMPLE).display_width as u64,
+        line_widths(WIDTH_SAMPLE).iter().sum(),
+    ];
+    report.check(
+        "widthModel",
+        format!("{:?}", [summed; 3]),
+        format!("{measured:?}"),
+    );
+}
+
+/// Builds the synthetic text of the chunking check: CHUNK_LINES lines of the same length
+fn chunk_text() -> String {
+    (0..CHUNK_LINES)
+        .map(|i| format!("Line {i:03} 汉字 wraps here and there\n"))
+        .collect()
+}
+
+/// process_text_chunks with chunks ending at line breaks, and with automatic chunks, gives
+/// justify_text's output for chunk_text
+fn check_chunks(report: &mut SelfTestReport) {
+    let text = chunk_text();
+    let width = 12;
+    let expected = justify_text(&text, width);
+    let line_len = text.len() as u32 / CHUNK_LINES;
+    for (name, chunk_size) in [("chunking", line_len * 3), ("chunkingAuto", 0)] {
+        let actual =
+            process_text_chunks(&text, width, chunk_size).unwrap_or_else(|e| e.to_string());
+        report.check(name, expected.clone(), actual);
+    }
+}
+
+/// Run the embedded suite: the wrap fixtures (more in strict-checks builds), the width model
+/// and chunked wrapping
+pub fn self_test() -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    let fixtures = WRAP_FIXTURES.iter();
+    #[cfg(feature = "strict-checks")]
+    let fixtures = fixtures.chain(STRICT_WRAP_FIXTURES);
+    for &(name, text, width, expected) in fixtures {
+        report.check(
+            format!("wrap:{name}"),
+            expected.to_string(),
+            justify_text(text, width),
+        );
+    }
+    check_widths(&mut report);
+    check_chunks(&mut report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_check_passes_in_this_build() {
+        let report = self_test();
+        let failed: Vec<&SelfTestCheck> =
+            report.checks.iter().filter(|check| !check.passed).collect();
+        assert!(
+            report.passed && failed.is_empty(),
+            "self_test failed: {failed:?}"
+        );
+        let wraps = report
+            .checks
+            .iter()
+            .filter(|check| check.name.starts_with("wrap:"))
+            .count();
+        assert!(wraps >= 12, "{wraps} wrap fixtures");
+        assert!(report
+            .checks
+            .iter()
+            .all(|check| check.expected.is_none() && check.actual.is_none()));
+    }
+
+    #[test]
+    fn a_failed_check_carries_what_it_expected_and_got() {
+        let mut report = self_test();
+        report.check("failing", "expected".into(), "actual".into());
+        assert!(!report.passed);
+        assert_eq!(
+            report.checks.last(),
+            Some(&SelfTestCheck {
+                name: "failing".into(),
+                passed: false,
+                expected: Some("expected".into()),
+                actual: Some("actual".into()),
+            })
+        );
+    }
+}
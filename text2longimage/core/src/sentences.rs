@@ -0,0 +1,268 @@
+use crate::is_cjk_char;
+
+/// Words whose trailing '.' doesn't end a sentence (compared lowercased, without the '.')
+const ABBREVIATIONS: &[&str] = &[
+    "al", "approx", "apr", "art", "aug", "cf", "co", "corp", "dec", "dept", "dr", "e.g", "ed",
+    "eds", "est", "feb", "fig", "gen", "gov", "hon", "i.e", "inc", "jan", "jr", "jul", "jun",
+    "ltd", "mar", "mr", "mrs", "ms", "no", "nov", "oct", "p", "pp", "prof", "rep", "sec", "sen",
+    "sep", "sept", "sr", "st", "u.k", "u.s", "u.s.a", "vol", "vs",
+];
+
+fn is_cjk_terminator(c: char) -> bool {
+    matches!(c, '。' | '！' | '？' | '；' | '．')
+}
+
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '…') || is_cjk_terminator(c)
+}
+
+fn is_opening(c: char) -> bool {
+    matches!(c, '「' | '『' | '（' | '(' | '“' | '[' | '《' | '【')
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, '」' | '』' | '）' | ')' | '”' | ']' | '》' | '】')
+}
+
+/// Split text into sentences, trimmed and in order
+/// ASCII terminators (. ! ? …) end a sentence when followed by whitespace or the end; CJK
+/// terminators (。！？；) end one immediately. Closing quotes and brackets stay with the
+/// sentence they close. Decimal points, common abbreviations (Mr., e.g., U.S.), initials,
+/// terminators inside quotes or brackets and an ellipsis followed by lowercase text don't
+/// split. A blank line always ends a sentence; a single line break inside one becomes a
+/// space (nothing between two CJK characters).
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let char_at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    let offset_at = |i: usize| chars.get(i).map_or(text.len(), |&(offset, _)| offset);
+
+    let mut sentences = Vec::new();
+    let mut push = |start: usize, end: usize| {
+        let sentence = text[start..end].trim();
+        if !sentence.is_empty() {
+            sentences.push(join_soft_breaks(sentence));
+        }
+    };
+
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+
+        if c == '\n' {
+            let blank_line = chars[i + 1..]
+                .iter()
+                .map(|&(_, c)| c)
+                .find(|c| !matches!(c, ' ' | '\t' | '\r'))
+                == Some('\n');
+            if blank_line {
+                push(start, offset_at(i));
+                start = offset_at(i);
+                depth = 0;
+            }
+            i += 1;
+            continue;
+        }
+        if c == '"' {
+            // A straight quote opens after whitespace or an opening bracket, else closes
+            let opens =
+                i == 0 || char_at(i - 1).is_some_and(|p| p.is_whitespace() || is_opening(p));
+            if opens {
+                depth += 1;
+            } else {
+                depth = depth.saturating_sub(1);
+            }
+            i += 1;
+            continue;
+        }
+        if is_opening(c) {
+            depth += 1;
+            i += 1;
+            continue;
+        }
+        if is_closing(c) {
+            depth = depth.saturating_sub(1);
+            i += 1;
+            continue;
+        }
+        if !is_terminator(c) {
+            i += 1;
+            continue;
+        }
+
+        // A run of terminators ("?!", "...", "……"), then whatever it closes
+        let run_start = i;
+        while char_at(i).is_some_and(is_terminator) {
+            i += 1;
+        }
+        let run_end = i;
+        let mut closed = false;
+        while let Some(c) = char_at(i) {
+            if is_closing(c) || (c == '"' && depth > 0) {
+                depth = depth.saturating_sub(1);
+                closed = true;
+                i += 1;
+            } else if matches!(c, '’' | '\'') {
+                // Single quotes double as apostrophes, so they only count right after the run
+                closed = true;
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        if depth > 0 {
+            continue;
+        }
+
+        let run: Vec<char> = chars[run_start..run_end].iter().map(|&(_, c)| c).collect();
+        let before = run_start.checked_sub(1).and_then(char_at);
+        let cjk = run.iter().any(|&c| is_cjk_terminator(c))
+            || (run[0] == '…' && before.is_some_and(is_cjk_char));
+        let next = char_at(i);
+        let next_word = chars[i..]
+            .iter()
+            .map(|&(_, c)| c)
+            .find(|c| !c.is_whitespace());
+
+        let ends = if cjk {
+            // 「…。」と言った: a closed quote followed straight by text is part of a sentence
+            !closed || next.is_none_or(|n| n.is_whitespace() || is_opening(n) || n == '"')
+        } else if !next.is_none_or(char::is_whitespace) || next_word.is_some_and(char::is_lowercase)
+        {
+            false
+        } else if run == ['.'] && !closed {
+            !is_abbreviation(&text[..chars[run_start].0])
+        } else {
+            true
+        };
+
+        if ends {
+            let end = offset_at(i);
+            push(start, end);
+            start = end;
+        }
+    }
+    push(start, text.len());
+
+    sentences
+}
+
+/// Whether the word ending `before` (the text up to a '.') is an abbreviation or an initial
+fn is_abbreviation(before: &str) -> bool {
+    let word_start = before
+        .rfind(|c: char| c.is_whitespace() || is_opening(c) || c == '"')
+        .map_or(0, |i| {
+            i + before[i..].chars().next().map_or(1, char::len_utf8)
+        });
+    let word = &before[word_start..];
+
+    let mut letters = word.chars();
+    let is_initial = letters.next().is_some_and(char::is_uppercase) && letters.next().is_none();
+    is_initial
+        || ABBREVIATIONS
+            .iter()
+            .any(|abbreviation| word.eq_ignore_ascii_case(abbreviation))
+}
+
+/// Collapse whitespace runs containing a line break into one space, or nothing between two
+/// CJK characters
+pub(crate) fn join_soft_breaks(sentence: &str) -> String {
+    if !sentence.contains('\n') {
+        return sentence.to_string();
+    }
+
+    let mut out = String::with_capacity(sentence.len());
+    let mut rest = sentence;
+    while let Some(newline) = rest.find('\n') {
+        let before = rest[..newline].trim_end();
+        let after = rest[newline + 1..].trim_start();
+        out.push_str(before);
+        let joins_cjk = before.chars().next_back().is_some_and(is_cjk_char)
+            && after.chars().next().is_some_and(is_cjk_char);
+        if !joins_cjk {
+            out.push(' ');
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Sentences from split_sentences joined with "\n", ready for justify_text
+pub fn one_sentence_per_line(text: &str) -> String {
+    split_sentences(text).join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legalese_full_of_abbreviations() {
+        let text = "This Agreement is made by Acme Corp. and Mr. J. Smith, Esq., of the U.S. \
+                    (see Sec. 4, e.g. Art. 2). Payment of $3.50 is due on Jan. 5, i.e. before \
+                    the end of the term! Does it apply? Yes.";
+        assert_eq!(
+            split_sentences(text),
+            [
+                "This Agreement is made by Acme Corp. and Mr. J. Smith, Esq., of the U.S. \
+                 (see Sec. 4, e.g. Art. 2).",
+                "Payment of $3.50 is due on Jan. 5, i.e. before the end of the term!",
+                "Does it apply?",
+                "Yes.",
+            ]
+        );
+    }
+
+    #[test]
+    fn japanese_paragraph_with_quotes() {
+        let text = "彼は「もう帰ります。また明日。」と言った。雨が降っていた！\n本当に？「はい。」";
+        assert_eq!(
+            split_sentences(text),
+            [
+                "彼は「もう帰ります。また明日。」と言った。",
+                "雨が降っていた！",
+                "本当に？",
+                "「はい。」",
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_and_ellipses() {
+        // Closing quotes go with the sentence they end
+        assert_eq!(
+            split_sentences("He said \"Stop.\" Then he left."),
+            ["He said \"Stop.\"", "Then he left."]
+        );
+        // An ellipsis in the middle of a quote, or before lowercase, doesn't end anything
+        assert_eq!(
+            split_sentences("\"Well... maybe,\" she said. Wait… not yet. Done?!"),
+            ["\"Well... maybe,\" she said.", "Wait… not yet.", "Done?!"]
+        );
+        assert_eq!(
+            split_sentences("It's 'done.' Next."),
+            ["It's 'done.'", "Next."]
+        );
+    }
+
+    #[test]
+    fn line_breaks() {
+        // A single break is a space (nothing between CJK characters), a blank line always ends
+        assert_eq!(
+            split_sentences("One sentence\nacross lines. 中文\n续行。\n\nNo terminator\n\nEnd"),
+            [
+                "One sentence across lines.",
+                "中文续行。",
+                "No terminator",
+                "End"
+            ]
+        );
+        assert_eq!(split_sentences("  \n\n "), Vec::<String>::new());
+        assert_eq!(
+            one_sentence_per_line("First. Second! 第三。第四"),
+            "First.\nSecond!\n第三。\n第四"
+        );
+    }
+}
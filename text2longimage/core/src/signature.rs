@@ -0,0 +1,176 @@
+//! Signature blocks, passed through as they were written
+//! Posts often end in a signature: contact lines, a placeholder line for a QR code, a
+//! copyright notice, laid out by hand with leading spaces and lines wider than the image. From
+//! algorithm V7 on, everything from the first line equal to JustifyOptions' signatureDelimiter
+//! (the conventional "-- ", a trailing '\r' aside) to the end of the text is left out of
+//! wrapping, trimming and every other pass, and written after the wrapped body exactly as it
+//! was, only its line breaks becoming "\r\n" like the rest of the output. A text without such a
+//! line is wrapped as before. paragraph_segments reports the block as a "signature" segment,
+//! and get_text_stats_excluding_signature counts the body only.
+
+use crate::get_text_stats;
+
+/// Default signatureDelimiter: the line that conventionally opens an email signature
+pub const DEFAULT_SIGNATURE_DELIMITER: &str = "-- ";
+
+/// Byte offset of the start of text's signature: its first line equal to delimiter, a trailing
+/// '\r' aside; None when there is no such line or delimiter is empty
+pub fn signature_start(text: &str, delimiter: &str) -> Option<usize> {
+    if delimiter.is_empty() {
+        return None;
+    }
+    let mut offset = 0;
+    for line in text.split('\n') {
+        if line.strip_suffix('\r').unwrap_or(line) == delimiter {
+            return Some(offset);
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+/// text split into the body before its signature, without the line break ending it, and the
+/// signature (None without one, the body then being all of text)
+pub fn split_signature<'a>(text: &'a str, delimiter: &str) -> (&'a str, Option<&'a str>) {
+    match signature_start(text, delimiter) {
+        Some(start) => {
+            let body = &text[..start];
+            let body = body.strip_suffix('\n').unwrap_or(body);
+            (
+                body.strip_suffix('\r').unwrap_or(body),
+                Some(&text[start..]),
+            )
+        }
+        None => (text, None),
+    }
+}
+
+/// Append signature to wrapped, the wrapped body, after a line break when the signature didn't
+/// start the text, each of its line breaks written as "\r\n" and nothing else changed
+pub(crate) fn append_signature(wrapped: &mut String, signature: &str, after_body: bool) {
+    if after_body {
+        wrapped.push_str("\r\n");
+    }
+    wrapped.push_str(&signature.replace("\r\n", "\n").replace('\n', "\r\n"));
+}
+
+/// get_text_stats of text before its signature; an empty delimiter counts the whole text
+pub fn get_text_stats_excluding_signature(text: &str, delimiter: &str) -> String {
+    get_text_stats(split_signature(text, delimiter).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{paragraph_segments, JustifyOptions, ParagraphKind};
+
+    /// A post whose signature has lines wider than SIGNATURE_WIDTH and leading whitespace, both of
+    /// which the wrapper would change
+    const SIGNATURE_TEXT: &str = concat!(
+        "长图文字排版需要处理中英文混排。Long images are read on phones.\r\n\n",
+        "-- \r\n",
+        "\u{3000}\u{3000}张三 · Zhang San    \n",
+        "\t[QR code: https://example.com/contact/zhang-san]\n\n",
+        "     © 2026 Example Studio. All rights reserved.  ",
+    );
+
+    /// The width SIGNATURE_TEXT is wrapped at
+    const SIGNATURE_WIDTH: u32 = 20;
+
+    fn with_version(version: u32, width: u32) -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line: width,
+            algorithm_version: version,
+            ..JustifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn v7_writes_the_signature_as_it_was() {
+        let (body, signature) = SIGNATURE_TEXT.split_at(SIGNATURE_TEXT.find("-- ").unwrap());
+        let verbatim = signature.replace("\r\n", "\n").replace('\n', "\r\n");
+        let wrapped = with_version(7, SIGNATURE_WIDTH).justify(SIGNATURE_TEXT);
+        assert_eq!(
+            wrapped,
+            format!(
+                "{}\r\n{verbatim}",
+                crate::justify_text(body.strip_suffix('\n').unwrap(), SIGNATURE_WIDTH)
+            ),
+            "signature in V7"
+        );
+        assert!(wrapped.ends_with(
+            "-- \r\n\u{3000}\u{3000}张三 · Zhang San    \r\n\t[QR code: https://example.com/contact/zhang-san]\r\n\r\n     © 2026 Example Studio. All rights reserved.  "
+        ));
+        let numbered = JustifyOptions {
+            line_numbers: true,
+            footer_text: "长图".to_string(),
+            ..with_version(7, SIGNATURE_WIDTH)
+        };
+        let numbered = numbered.justify_checked(SIGNATURE_TEXT).unwrap();
+        assert!(
+            numbered.contains(&format!("\r\n{verbatim}\r\n\r\n")),
+            "{numbered:?}"
+        );
+    }
+
+    #[test]
+    fn without_the_delimiter_line_v7_wraps_as_v6() {
+        let (_, signature) = split_signature(SIGNATURE_TEXT, DEFAULT_SIGNATURE_DELIMITER);
+        let verbatim = signature
+            .unwrap()
+            .replace("\r\n", "\n")
+            .replace('\n', "\r\n");
+        let v6 = with_version(6, SIGNATURE_WIDTH).justify(SIGNATURE_TEXT);
+        assert!(!v6.contains(&verbatim), "V6 kept the signature");
+        let no_delimiter = JustifyOptions {
+            signature_delimiter: String::new(),
+            ..with_version(7, SIGNATURE_WIDTH)
+        };
+        assert_eq!(no_delimiter.justify(SIGNATURE_TEXT), v6);
+        let custom = JustifyOptions {
+            signature_delimiter: "--".to_string(),
+            ..with_version(7, SIGNATURE_WIDTH)
+        };
+        assert_eq!(custom.justify(SIGNATURE_TEXT), v6, "\"-- \" isn't \"--\"");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn a_delimiter_spanning_lines_is_rejected() {
+        assert!(JustifyOptions::from_json(r#"{"signatureDelimiter": "--\n"}"#).is_err());
+    }
+
+    #[test]
+    fn the_signature_leaves_stats_and_warnings_alone() {
+        let (body, _) = split_signature(SIGNATURE_TEXT, DEFAULT_SIGNATURE_DELIMITER);
+        assert_eq!(
+            get_text_stats_excluding_signature(SIGNATURE_TEXT, "-- "),
+            get_text_stats(body)
+        );
+        assert_eq!(
+            get_text_stats_excluding_signature(SIGNATURE_TEXT, ""),
+            get_text_stats(SIGNATURE_TEXT)
+        );
+        let warnings = with_version(7, 4).warnings(SIGNATURE_TEXT);
+        assert!(
+            warnings.iter().all(|warning| warning.line_index < Some(2)),
+            "{warnings:?}"
+        );
+    }
+
+    #[test]
+    fn the_signature_is_the_last_segment() {
+        let signature = &SIGNATURE_TEXT[signature_start(SIGNATURE_TEXT, "-- ").unwrap()..];
+        let segments = paragraph_segments(SIGNATURE_TEXT);
+        let last = segments.last().unwrap();
+        assert_eq!(last.kind, ParagraphKind::Signature);
+        assert_eq!(last.text, signature);
+        assert_eq!((last.src_start_line, last.src_end_line), (2, 6));
+        #[cfg(feature = "json")]
+        {
+            let segments: serde_json::Value =
+                serde_json::from_str(&crate::split_paragraphs(SIGNATURE_TEXT)).unwrap();
+            assert_eq!(segments[2]["kind"], "signature");
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Cutting a line by display columns, for scrolling wide lines sideways
+//! Columns are the wrapper's half-width units (width_of). A combining mark stays with the
+//! character before it, so a base and its marks are kept, dropped or blanked out together.
+
+use crate::table::{in_ranges, COMBINING_RANGES};
+use crate::width_of;
+
+/// A base character and the combining marks after it
+struct Cluster<'a> {
+    text: &'a str,
+    char_index: usize,
+    start_col: u32,
+    end_col: u32,
+}
+
+/// The clusters of a line in order; a mark at the very start is a cluster of its own
+fn clusters(line: &str) -> impl Iterator<Item = Cluster<'_>> {
+    let mut chars = line.char_indices().enumerate().peekable();
+    let mut col = 0;
+    std::iter::from_fn(move || {
+        let (char_index, (start, c)) = chars.next()?;
+        let mut end = start + c.len_utf8();
+        let mut width = width_of(c);
+        while let Some(&(_, (offset, mark))) = chars.peek() {
+            if !in_ranges(COMBINING_RANGES, mark) {
+                break;
+            }
+            end = offset + mark.len_utf8();
+            width += width_of(mark);
+            chars.next();
+        }
+
+        let cluster = Cluster {
+            text: &line[start..end],
+            char_index,
+            start_col: col,
+            end_col: col + width,
+        };
+        col += width;
+        Some(cluster)
+    })
+}
+
+/// The part of line covering columns start_col..end_col
+/// A character only partly inside the range (the half of a wide character at either edge) is
+/// replaced by a space for each of its columns inside, so the result stays aligned to
+/// start_col, or left out when pad_partial is false. Past the end of the line there's nothing
+/// to pad: the result is as short as the line leaves it.
+pub fn slice_columns(line: &str, start_col: u32, end_col: u32, pad_partial: bool) -> String {
+    let mut out = String::new();
+    if end_col <= start_col {
+        return out;
+    }
+
+    for cluster in clusters(line) {
+        if cluster.end_col <= start_col {
+            continue;
+        }
+        if cluster.start_col >= end_col {
+            break;
+        }
+        if cluster.start_col >= start_col && cluster.end_col <= end_col {
+            out.push_str(cluster.text);
+        } else if pad_partial {
+            let covered = cluster.end_col.min(end_col) - cluster.start_col.max(start_col);
+            out.extend(std::iter::repeat_n(' ', covered as usize));
+        }
+    }
+    out
+}
+
+/// Index of the character (in chars, not bytes) drawn at column col
+/// A column inside a wide character, or on one of its combining marks, maps to the character
+/// itself; a column past the end of the line maps to the line's length in chars.
+pub fn column_to_char_index(line: &str, col: u32) -> usize {
+    clusters(line)
+        .find(|cluster| col < cluster.end_col)
+        .map_or_else(|| line.chars().count(), |cluster| cluster.char_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = "a漢b字c";
+
+    #[test]
+    fn whole_characters_inside_the_range() {
+        assert_eq!(slice_columns(MIXED, 1, 3, true), "漢");
+        assert_eq!(slice_columns(MIXED, 3, 7, true), "b字c");
+        assert_eq!(slice_columns(MIXED, 0, 7, false), MIXED);
+        assert_eq!(slice_columns(MIXED, 4, 4, true), "");
+        assert_eq!(slice_columns(MIXED, 5, 2, true), "");
+    }
+
+    #[test]
+    fn half_a_wide_character_at_either_edge() {
+        // a 0..1, 漢 1..3, b 3..4, 字 4..6, c 6..7
+        assert_eq!(slice_columns(MIXED, 0, 2, true), "a ");
+        assert_eq!(slice_columns(MIXED, 0, 2, false), "a");
+        assert_eq!(slice_columns(MIXED, 2, 5, true), " b ");
+        assert_eq!(slice_columns(MIXED, 2, 5, false), "b");
+        assert_eq!(slice_columns(MIXED, 5, 6, true), " ");
+        assert_eq!(slice_columns(MIXED, 5, 6, false), "");
+        // Nothing to pad past the end of the line
+        assert_eq!(slice_columns(MIXED, 5, 100, true), " c");
+        assert_eq!(slice_columns(MIXED, 7, 9, true), "");
+    }
+
+    #[test]
+    fn ascii_and_cjk_alternating_at_odd_boundaries() {
+        let line = "a漢".repeat(10);
+        for start_col in (1..23).step_by(3) {
+            let padded = slice_columns(&line, start_col, start_col + 7, true);
+            assert_eq!(crate::calculate_text_width(&padded), 7, "{}", start_col);
+            let dropped = slice_columns(&line, start_col, start_col + 7, false);
+            assert!(
+                padded.trim() == dropped.trim(),
+                "{:?} {:?}",
+                padded,
+                dropped
+            );
+        }
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_base() {
+        // x 0..1, e and its mark 1..4, 漢 and its mark 4..8, y 8..9
+        let line = "xe\u{301}漢\u{301}y";
+        assert_eq!(slice_columns(line, 1, 4, true), "e\u{301}");
+        assert_eq!(slice_columns(line, 1, 2, true), " ");
+        assert_eq!(slice_columns(line, 1, 2, false), "");
+        assert_eq!(slice_columns(line, 4, 8, true), "漢\u{301}");
+        assert_eq!(slice_columns(line, 7, 9, true), " y");
+        assert_eq!(slice_columns(line, 7, 9, false), "y");
+    }
+
+    #[test]
+    fn columns_to_char_indexes() {
+        assert_eq!(column_to_char_index(MIXED, 0), 0);
+        assert_eq!(column_to_char_index(MIXED, 1), 1);
+        assert_eq!(column_to_char_index(MIXED, 2), 1);
+        assert_eq!(column_to_char_index(MIXED, 3), 2);
+        assert_eq!(column_to_char_index(MIXED, 5), 3);
+        assert_eq!(column_to_char_index(MIXED, 7), 5);
+        assert_eq!(column_to_char_index(MIXED, 99), 5);
+        // The mark belongs to the "e" before it: the "漢" after is char 3
+        assert_eq!(column_to_char_index("xe\u{301}漢", 1), 1);
+        assert_eq!(column_to_char_index("xe\u{301}漢", 3), 1);
+        assert_eq!(column_to_char_index("xe\u{301}漢", 4), 3);
+        assert_eq!(column_to_char_index("", 0), 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! CSS letter-spacing and word-spacing in the width accounting
+//! A canvas with letterSpacing set draws that much space after every character, and word
+//! spacing on top of each word separator, so the wrappers count both: a line wrapped to fit
+//! still fits once drawn. Both are in em, the unit of the fractional widths.
+
+use crate::{wrap_text_with, ColumnWidths, FractionalWidths, WebtoolsError, WidthModel};
+
+/// letter-spacing and word-spacing, in em; the default adds nothing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Spacing {
+    /// Added after every character, spaces included
+    pub letter_spacing_em: f64,
+    /// Added after every word separator (see is_word_separator), on top of letter_spacing_em
+    pub word_spacing_em: f64,
+}
+
+impl Spacing {
+    /// Spacing from the CSS values; both must be finite and not negative
+    pub fn new(letter_spacing_em: f64, word_spacing_em: f64) -> Result<Self, WebtoolsError> {
+        for (name, value) in [
+            ("letterSpacingEm", letter_spacing_em),
+            ("wordSpacingEm", word_spacing_em),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(WebtoolsError::invalid_argument(format!(
+                    "{} must be a finite number of at least 0, got {}",
+                    name, value
+                )));
+            }
+        }
+        Ok(Spacing {
+            letter_spacing_em,
+            word_spacing_em,
+        })
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.letter_spacing_em == 0.0 && self.word_spacing_em == 0.0
+    }
+
+    /// Space drawn after c, in em
+    pub fn extra_em(self, c: char) -> f64 {
+        if is_word_separator(c) {
+            self.letter_spacing_em + self.word_spacing_em
+        } else {
+            self.letter_spacing_em
+        }
+    }
+
+    /// Space drawn across text, in em
+    pub fn extra_em_str(self, text: &str) -> f64 {
+        if self.is_zero() {
+            return 0.0;
+        }
+        text.chars().map(|c| self.extra_em(c)).sum()
+    }
+}
+
+/// The characters CSS word-spacing applies to
+pub fn is_word_separator(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\u{00A0}' | '\u{1361}' | '\u{10100}' | '\u{10101}' | '\u{1039F}' | '\u{1091F}'
+    )
+}
+
+/// A width model with spacing added to every character it measures
+/// There's no shared ASCII width: with spacing, a run's width is no longer an exact multiple
+/// of 0.5, so runs are placed character by character.
+pub(crate) struct SpacedWidths<'a, M> {
+    pub(crate) model: &'a M,
+    pub(crate) spacing: Spacing,
+}
+
+impl<M: WidthModel> WidthModel for SpacedWidths<'_, M> {
+    fn char_width(&self, c: char) -> f64 {
+        self.model.char_width(c) + self.spacing.extra_em(c)
+    }
+
+    fn word_width(&self, word: &str) -> f64 {
+        self.model.word_width(word) + self.spacing.extra_em_str(word)
+    }
+}
+
+/// Wrap with the model, spaced when spacing adds anything
+fn wrap_spaced<M: WidthModel>(text: &str, max_width: f64, model: &M, spacing: Spacing) -> String {
+    if spacing.is_zero() {
+        wrap_text_with(text, max_width, model)
+    } else {
+        wrap_text_with(text, max_width, &SpacedWidths { model, spacing })
+    }
+}
+
+/// justify_text_fractional for text drawn with letter and word spacing
+/// max_width is in em; zero spacing gives exactly the justify_text_fractional output
+pub fn justify_text_fractional_with_spacing(
+    text: &str,
+    max_width: f64,
+    spacing: Spacing,
+) -> String {
+    wrap_spaced(text, max_width, &FractionalWidths, spacing)
+}
+
+/// justify_text for text drawn with letter and word spacing, still limited to
+/// max_chars_per_line columns of the unspaced font
+/// Zero spacing gives exactly the justify_text output
+pub fn justify_text_with_spacing(text: &str, max_chars_per_line: u32, spacing: Spacing) -> String {
+    wrap_spaced(
+        text,
+        max_chars_per_line as f64 / 2.0,
+        &ColumnWidths,
+        spacing,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::justify_text;
+
+    #[test]
+    fn spacing_pushes_a_full_line_over() {
+        // "ab cd ef" is exactly 4em; 0.1em of letter spacing pushes "ef" onto a line of its own
+        let tracked = Spacing::new(0.1, 0.0).unwrap();
+        assert_eq!(
+            justify_text_with_spacing("ab cd ef", 8, Spacing::default()),
+            "ab cd ef"
+        );
+        assert_eq!(
+            justify_text_with_spacing("ab cd ef", 8, tracked),
+            "ab cd\r\nef"
+        );
+        // Word spacing goes on the separators only
+        let spaced = Spacing::new(0.0, 0.25).unwrap();
+        assert_eq!(justify_text_with_spacing("abcdefgh", 8, spaced), "abcdefgh");
+        assert_eq!(
+            justify_text_with_spacing("ab cd ef", 8, spaced),
+            "ab cd\r\nef"
+        );
+    }
+
+    #[test]
+    fn zero_spacing_changes_nothing() {
+        let text = "中文 English 混排\n第二行";
+        assert_eq!(
+            justify_text_with_spacing(text, 7, Spacing::default()),
+            justify_text(text, 7)
+        );
+    }
+
+    #[test]
+    fn negative_and_non_finite_spacing_are_rejected() {
+        for (letter, word) in [
+            (-0.1, 0.0),
+            (0.0, -1.0),
+            (f64::NAN, 0.0),
+            (0.0, f64::INFINITY),
+        ] {
+            assert!(Spacing::new(letter, word).is_err(), "{letter} {word}");
+        }
+        assert_eq!(Spacing::new(0.1, 0.2).unwrap().extra_em_str("a b"), 0.5);
+    }
+}
@@ -0,0 +1,289 @@
+//! Text statistics gathered while a large input streams through justify_windows
+//! Buffering a 20 MB log a second time just to call get_text_stats on it defeats reading it a
+//! window at a time. StreamStats takes the input in pieces as they are read and keeps the
+//! counters of get_text_stats that don't need more than one character at a time: characters,
+//! bytes, lines, CJK and ASCII characters, display width, astral characters, combining marks
+//! and BOMs, and from the output the widest wrapped line so far. A piece may end partway
+//! through a character; its first bytes are held back and counted once the rest arrives, so
+//! the counts never depend on where the pieces were cut. A CRLF or a cluster cut in two needs
+//! nothing held back, as every counter here goes one character at a time.
+
+use serde::Serialize;
+
+use crate::width::width_of_str;
+use crate::{TextCounts, WebtoolsError};
+
+/// The counters StreamStats keeps, as get_text_stats would give them for the input so far
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct StreamedStats {
+    /// Characters, BOMs aside
+    pub char_count: usize,
+    /// Bytes of whole characters read: held-back bytes of a cut character aren't counted yet
+    pub byte_count: usize,
+    pub line_count: usize,
+    /// CJK characters, iteration marks (々) included
+    pub cjk_count: usize,
+    pub ascii_count: usize,
+    pub display_width: u32,
+    pub has_cjk: bool,
+    pub astral_count: usize,
+    pub combining_mark_count: usize,
+    pub bom_count: usize,
+    /// Widest line of the wrapped output so far, in columns; a line still being written
+    /// counts with what it has
+    pub widest_output_line: u64,
+}
+
+/// Counters of an input fed in pieces, and of the output wrapped from it
+#[derive(Clone, Debug, Default)]
+pub struct StreamStats {
+    /// Counts of the whole characters read, line_count being the number of '\n'
+    counts: TextCounts,
+    /// The first bytes of a character the last piece cut through
+    partial: Vec<u8>,
+    /// A character other than whitespace was read: the text isn't empty input
+    has_text: bool,
+    ends_with_newline: bool,
+    output_line_width: u64,
+    widest_output_line: u64,
+}
+
+impl StreamStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count bytes, the next piece of the input; bytes that can't be UTF-8 whatever follows
+    /// are an InvalidUtf8 error naming their offset in the whole input
+    pub fn feed(&mut self, mut bytes: &[u8]) -> Result<(), WebtoolsError> {
+        // Complete a held-back character first, a byte at a time
+        while !self.partial.is_empty() && !bytes.is_empty() {
+            let mut partial = std::mem::take(&mut self.partial);
+            partial.push(bytes[0]);
+            bytes = &bytes[1..];
+            match std::str::from_utf8(&partial) {
+                Ok(c) => self.count(c),
+                Err(e) => {
+                    let invalid = e.error_len().is_some();
+                    self.partial = partial;
+                    if invalid {
+                        return Err(self.invalid());
+                    }
+                }
+            }
+        }
+        if !self.partial.is_empty() {
+            return Ok(());
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(text) => self.count(text),
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // Checked by from_utf8 up to valid_up_to
+                self.count(std::str::from_utf8(valid).unwrap_or_default());
+                if e.error_len().is_some() {
+                    return Err(self.invalid());
+                }
+                self.partial.extend_from_slice(rest);
+            }
+        }
+        Ok(())
+    }
+
+    /// The InvalidUtf8 error for bad bytes right after the last whole character
+    fn invalid(&self) -> WebtoolsError {
+        WebtoolsError::InvalidUtf8 {
+            offset: self.counts.byte_count,
+        }
+    }
+
+    fn count(&mut self, text: &str) {
+        let Some(last) = text.chars().next_back() else {
+            return;
+        };
+        let mut piece = TextCounts::of(text);
+        // The open line TextCounts adds for a text not ending in '\n' is added once, at the end
+        piece.line_count = text.bytes().filter(|&b| b == b'\n').count();
+        self.has_text |= text.contains(|c: char| !c.is_whitespace());
+        self.ends_with_newline = last == '\n';
+        let counts = self.counts;
+        self.counts = TextCounts {
+            char_count: counts.char_count + piece.char_count,
+            byte_count: counts.byte_count + piece.byte_count,
+            line_count: counts.line_count + piece.line_count,
+            cjk_count: counts.cjk_count + piece.cjk_count,
+            ascii_count: counts.ascii_count + piece.ascii_count,
+            display_width: counts.display_width + piece.display_width,
+            astral_count: counts.astral_count + piece.astral_count,
+            mark_count: counts.mark_count + piece.mark_count,
+            bom_count: counts.bom_count + piece.bom_count,
+        };
+    }
+
+    /// Measure output, the next piece of the wrapped output; its lines end in "\r\n"
+    pub fn feed_output(&mut self, output: &str) {
+        let mut lines = output.split('\n');
+        if let Some(first) = lines.next() {
+            self.output_line_width += width_of_str(first.trim_end_matches('\r'));
+        }
+        for line in lines {
+            self.widest_output_line = self.widest_output_line.max(self.output_line_width);
+            self.output_line_width = width_of_str(line.trim_end_matches('\r'));
+        }
+    }
+
+    /// The stats of the whole characters read so far; bytes held back aren't counted
+    pub fn stats_so_far(&self) -> StreamedStats {
+        let counts = self.counts;
+        // As TextCounts counts lines: a last line without '\n' counts unless all is whitespace
+        let open_line = (self.has_text || counts.line_count > 0) && !self.ends_with_newline;
+        StreamedStats {
+            char_count: counts.char_count,
+            byte_count: counts.byte_count,
+            line_count: counts.line_count + usize::from(open_line),
+            cjk_count: counts.cjk_count,
+            ascii_count: counts.ascii_count,
+            display_width: counts.display_width,
+            has_cjk: counts.cjk_count > 0,
+            astral_count: counts.astral_count,
+            combining_mark_count: counts.mark_count,
+            bom_count: counts.bom_count,
+            widest_output_line: self.widest_output_line.max(self.output_line_width),
+        }
+    }
+
+    /// The stats of the whole input, once all of it was fed; InvalidUtf8 if it ended partway
+    /// through a character
+    pub fn finish(&self) -> Result<StreamedStats, WebtoolsError> {
+        match self.partial.is_empty() {
+            true => Ok(self.stats_so_far()),
+            false => Err(self.invalid()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+/// stats as JSON {charCount, byteCount, lineCount, ..., widestOutputLine}
+pub fn streamed_stats_json(stats: &StreamedStats) -> String {
+    serde_json::to_string(stats).unwrap_or_else(|_| "{}".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{justify_text, justify_windows_with_stats, max_line_width, TextStats};
+
+    /// Multi-byte characters of every length, CRLFs, a BOM, a combining mark and a ZWJ emoji
+    /// cluster, so that 7-byte pieces cut through each of them somewhere
+    const STREAM_TEXT: &str =
+        "\u{feff}Straße 東京\r\n\r\nnaïve cafe\u{301} 👩\u{200d}💻 𠮷野家\r\n々\n  \nend";
+
+    /// StreamStats of bytes fed piece_len bytes at a time
+    fn stream_stats(bytes: &[u8], piece_len: usize) -> Result<StreamedStats, WebtoolsError> {
+        let mut stats = StreamStats::new();
+        for piece in bytes.chunks(piece_len.max(1)) {
+            stats.feed(piece)?;
+        }
+        stats.finish()
+    }
+
+    /// Whether streamed has the counts TextStats gives for text
+    fn assert_streamed(streamed: &StreamedStats, text: &str, what: &str) {
+        let stats = TextStats::of(text);
+        assert_eq!(
+            (
+                streamed.char_count,
+                streamed.byte_count,
+                streamed.line_count,
+                streamed.cjk_count,
+                streamed.ascii_count,
+                streamed.display_width,
+                streamed.has_cjk,
+                streamed.astral_count,
+                streamed.combining_mark_count,
+                streamed.bom_count,
+            ),
+            (
+                stats.char_count,
+                stats.byte_count,
+                stats.line_count,
+                stats.cjk_count,
+                stats.ascii_count,
+                stats.display_width,
+                stats.has_cjk,
+                stats.astral_count,
+                stats.combining_mark_count,
+                stats.bom_count,
+            ),
+            "{what} of {text:?}"
+        );
+    }
+
+    #[test]
+    fn pieces_of_any_length_count_as_the_whole() {
+        for piece_len in [1, 2, 3, 7, STREAM_TEXT.len()] {
+            let streamed = stream_stats(STREAM_TEXT.as_bytes(), piece_len).unwrap();
+            assert_streamed(&streamed, STREAM_TEXT, &format!("{piece_len}-byte pieces"));
+            assert_eq!(streamed.widest_output_line, 0);
+        }
+    }
+
+    #[test]
+    fn a_character_cut_in_two_waits_for_the_rest() {
+        let mut stats = StreamStats::new();
+        let emoji = "a👩".as_bytes();
+        stats.feed(b"a").unwrap();
+        for end in 2..emoji.len() {
+            stats.feed(&emoji[end - 1..end]).unwrap();
+            assert_streamed(&stats.stats_so_far(), "a", "a cut emoji");
+            assert!(matches!(
+                stats.finish(),
+                Err(WebtoolsError::InvalidUtf8 { offset: 1 })
+            ));
+        }
+        stats.feed(&emoji[emoji.len() - 1..]).unwrap();
+        assert_streamed(&stats.finish().unwrap(), "a👩", "a whole emoji");
+        let mut invalid = STREAM_TEXT.as_bytes().to_vec();
+        invalid.insert(STREAM_TEXT.find('東').unwrap() + 1, b'x');
+        match stream_stats(&invalid, 7) {
+            Err(WebtoolsError::InvalidUtf8 { offset }) => {
+                assert_eq!(offset, STREAM_TEXT.find('東').unwrap())
+            }
+            other => panic!("expected InvalidUtf8, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn each_window_gets_the_stats_so_far() {
+        let (text, width) = (STREAM_TEXT, 8);
+        for window in [1, 7, 64] {
+            let mut output = String::new();
+            let result = justify_windows_with_stats::<WebtoolsError>(
+                text.len(),
+                width,
+                window,
+                |range, buffer| buffer.extend_from_slice(&text.as_bytes()[range]),
+                |block, so_far| {
+                    output.push_str(block);
+                    assert!(
+                        text.is_char_boundary(so_far.byte_count),
+                        "{} bytes wrapped",
+                        so_far.byte_count
+                    );
+                    assert_streamed(so_far, &text[..so_far.byte_count], "a block's stats");
+                    assert_eq!(so_far.widest_output_line, max_line_width(&output));
+                    Ok(())
+                },
+            );
+            let streamed = result.unwrap();
+            assert_streamed(&streamed, text, "justify_windows_with_stats");
+            assert_eq!(
+                streamed.widest_output_line,
+                max_line_width(&justify_text(text, width)),
+                "widest line in {window}-byte windows"
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use crate::layout::{image_size_of_lines, layout_lines, Align};
+use crate::{justify_text, ScriptLineHeights};
+
+/// Escape text for XML/HTML content and attribute values
+pub(crate) fn push_xml_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// Render wrapped text as a standalone SVG document
+/// Uses the layout_text positions (top-aligned like canvas textBaseline = "top");
+/// width/height match estimate_image_size
+#[allow(clippy::too_many_arguments)]
+pub fn render_svg(
+    text: &str,
+    max_chars_per_line: u32,
+    font_family: &str,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    fg: &str,
+    bg: &str,
+) -> String {
+    let justified = justify_text(text, max_chars_per_line);
+    let wrapped: Vec<&str> = if justified.is_empty() {
+        Vec::new()
+    } else {
+        justified.split("\r\n").collect()
+    };
+    let size = image_size_of_lines(
+        &wrapped,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        ScriptLineHeights::current(),
+    );
+    let lines = layout_lines(
+        &justified,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::Left,
+    );
+
+    let mut svg = String::with_capacity(justified.len() * 2 + 512);
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = size.width_px,
+        h = size.height_px
+    );
+    svg.push_str(r#"<rect width="100%" height="100%" fill=""#);
+    push_xml_escaped(&mut svg, bg);
+    svg.push_str(r#""/><text font-family=""#);
+    push_xml_escaped(&mut svg, font_family);
+    let _ = write!(svg, r#"" font-size="{}" fill=""#, font_size_px);
+    push_xml_escaped(&mut svg, fg);
+    svg.push_str(r#"" dominant-baseline="text-before-edge" xml:space="preserve">"#);
+
+    for line in lines.iter().filter(|line| !line.text.is_empty()) {
+        let _ = write!(svg, r#"<tspan x="{}" y="{}">"#, line.x_px, line.y_px);
+        push_xml_escaped(&mut svg, &line.text);
+        svg.push_str("</tspan>");
+    }
+
+    svg.push_str("</text></svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Element names in document order, panicking unless every tag is closed in order, every
+    /// attribute is quoted and every & starts one of the entities push_xml_escaped writes
+    fn check_well_formed(svg: &str) -> Vec<&str> {
+        let mut open: Vec<&str> = Vec::new();
+        let mut names = Vec::new();
+        let mut rest = svg;
+        while let Some(start) = rest.find('<') {
+            check_text(&rest[..start]);
+            let end = start + rest[start..].find('>').expect("unclosed tag");
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "closing {name}");
+                continue;
+            }
+            let (tag, closed) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let name = tag.split(' ').next().unwrap();
+            for attribute in tag[name.len()..]
+                .split_terminator('"')
+                .collect::<Vec<_>>()
+                .chunks(2)
+            {
+                assert!(
+                    attribute[0].trim().ends_with('='),
+                    "attribute {attribute:?}"
+                );
+                check_text(attribute[1]);
+            }
+            names.push(name);
+            if !closed {
+                open.push(name);
+            }
+        }
+        assert!(open.is_empty() && rest.is_empty(), "{open:?} left open");
+        names
+    }
+
+    fn check_text(text: &str) {
+        assert!(!text.contains(['<', '>', '"']), "{text:?} unescaped");
+        for (at, _) in text.match_indices('&') {
+            assert!(
+                ["&amp;", "&lt;", "&gt;", "&quot;", "&#39;"]
+                    .iter()
+                    .any(|entity| text[at..].starts_with(entity)),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn svg_is_well_formed_and_escaped() {
+        let text = "Plain line\nA <script>alert(\"x\")</script> & more\n\n漢字の行";
+        let svg = render_svg(text, 16, "Noto \"Sans\"", 16.0, 1.5, 8.0, "#000", "<white>");
+        let names = check_well_formed(&svg);
+        assert_eq!(names[..3], ["svg", "rect", "text"]);
+        assert!(names[3..].iter().all(|&name| name == "tspan"));
+        assert!(!svg.contains("<script>"));
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(svg.contains(r#"font-family="Noto &quot;Sans&quot;""#));
+        // The blank line is left out, not drawn as an empty tspan
+        assert!(!svg.contains("\"></tspan>"));
+        assert!(
+            check_well_formed(&render_svg("", 16, "serif", 16.0, 1.5, 8.0, "#000", "#fff")).len()
+                == 3
+        );
+    }
+}
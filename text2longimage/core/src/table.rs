@@ -0,0 +1,271 @@
+//! Two-level lookup table behind is_cjk_char and width_of
+//! Code points are split into 256-entry pages; each page maps to one of a few deduplicated
+//! blocks of bitsets, so classifying a character is two array indexes. The table is built at
+//! compile time from the range lists, which stay the single source of truth: the block-based
+//! ones generated from the UCD's Blocks.txt into ucd.rs (see the ucd/ generator), the rest
+//! below.
+
+use crate::ucd::CJK_RANGES;
+pub(crate) use crate::ucd::{
+    COMBINING_RANGES, NARROW_ASTRAL_RANGES, SUPPLEMENTARY_IDEOGRAPH_RANGES,
+};
+
+/// Single-column ranges (inclusive); everything else is two columns wide
+const NARROW_RANGES: &[(u32, u32)] = &[(0x00, 0xFF)];
+
+/// Private Use Areas: the BMP block and supplementary planes 15 and 16
+pub(crate) const PRIVATE_USE_RANGES: &[(u32, u32)] =
+    &[(0xE000, 0xF8FF), (0xF0000, 0xFFFFD), (0x100000, 0x10FFFD)];
+
+/// Large stretches with nothing assigned as of Unicode 15.1, plus the U+FDD0 noncharacters;
+/// the FFFE/FFFF noncharacters of every plane are checked separately (see is_unassigned)
+const UNASSIGNED_RANGES: &[(u32, u32)] = &[
+    (0xFDD0, 0xFDEF),
+    (0x2FA20, 0x2FFFD),
+    (0x323B0, 0x3FFFD),
+    (0x40000, 0xDFFFF),
+    (0xE01F0, 0xEFFFD),
+];
+
+const PAGE_SHIFT: u32 = 8;
+const PAGE_SIZE: u32 = 1 << PAGE_SHIFT;
+const WORDS: usize = (PAGE_SIZE / 32) as usize;
+
+/// Upper bound on distinct blocks; building the table fails to compile if it's exceeded
+const MAX_BLOCKS: usize = 8;
+
+/// Pages past the last range all share DEFAULT_BLOCK and aren't stored
+const PAGE_COUNT: usize = (last_listed_code_point() >> PAGE_SHIFT) as usize + 1;
+
+/// Block used for every page with no listed code point: not CJK, two columns wide
+const DEFAULT_BLOCK: u8 = 0;
+
+#[derive(Clone, Copy)]
+struct Block {
+    cjk: [u32; WORDS],
+    narrow: [u32; WORDS],
+}
+
+struct Table {
+    pages: [u8; PAGE_COUNT],
+    blocks: [Block; MAX_BLOCKS],
+}
+
+static TABLE: Table = build_table();
+
+const fn last_listed_code_point() -> u32 {
+    let mut last = 0;
+    let mut i = 0;
+    while i < CJK_RANGES.len() {
+        if CJK_RANGES[i].1 > last {
+            last = CJK_RANGES[i].1;
+        }
+        i += 1;
+    }
+    let mut i = 0;
+    while i < NARROW_RANGES.len() {
+        if NARROW_RANGES[i].1 > last {
+            last = NARROW_RANGES[i].1;
+        }
+        i += 1;
+    }
+    last
+}
+
+/// Set the bits of `ranges` that fall inside `page`
+const fn fill(bits: &mut [u32; WORDS], ranges: &[(u32, u32)], page: u32) {
+    let page_start = page << PAGE_SHIFT;
+    let page_end = page_start + PAGE_SIZE - 1;
+
+    let mut r = 0;
+    while r < ranges.len() {
+        let (start, end) = ranges[r];
+        if start <= page_end && end >= page_start {
+            let mut code_point = if start > page_start {
+                start
+            } else {
+                page_start
+            };
+            let last = if end < page_end { end } else { page_end };
+            while code_point <= last {
+                let offset = code_point - page_start;
+                bits[(offset / 32) as usize] |= 1 << (offset % 32);
+                code_point += 1;
+            }
+        }
+        r += 1;
+    }
+}
+
+const fn block_for_page(page: u32) -> Block {
+    let mut block = Block {
+        cjk: [0; WORDS],
+        narrow: [0; WORDS],
+    };
+    fill(&mut block.cjk, CJK_RANGES, page);
+    fill(&mut block.narrow, NARROW_RANGES, page);
+    block
+}
+
+const fn same_block(a: &Block, b: &Block) -> bool {
+    let mut i = 0;
+    while i < WORDS {
+        if a.cjk[i] != b.cjk[i] || a.narrow[i] != b.narrow[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn build_table() -> Table {
+    let empty = Block {
+        cjk: [0; WORDS],
+        narrow: [0; WORDS],
+    };
+    let mut table = Table {
+        pages: [DEFAULT_BLOCK; PAGE_COUNT],
+        blocks: [empty; MAX_BLOCKS],
+    };
+    let mut block_count = 1; // blocks[DEFAULT_BLOCK] is the empty block
+
+    let mut page = 0;
+    while page < PAGE_COUNT {
+        let block = block_for_page(page as u32);
+
+        // Neighbouring pages are usually identical, so check the previous one first
+        let mut index = if page > 0 {
+            table.pages[page - 1] as usize
+        } else {
+            DEFAULT_BLOCK as usize
+        };
+        if !same_block(&table.blocks[index], &block) {
+            index = 0;
+            while index < block_count && !same_block(&table.blocks[index], &block) {
+                index += 1;
+            }
+            if index == block_count {
+                assert!(block_count < MAX_BLOCKS, "raise MAX_BLOCKS");
+                table.blocks[index] = block;
+                block_count += 1;
+            }
+        }
+
+        table.pages[page] = index as u8;
+        page += 1;
+    }
+
+    table
+}
+
+#[inline]
+fn block_of(code_point: u32) -> &'static Block {
+    let page = (code_point >> PAGE_SHIFT) as usize;
+    let index = match TABLE.pages.get(page) {
+        Some(&index) => index,
+        None => DEFAULT_BLOCK,
+    };
+    &TABLE.blocks[index as usize]
+}
+
+#[inline]
+fn bit(bits: &[u32; WORDS], code_point: u32) -> bool {
+    let offset = code_point & (PAGE_SIZE - 1);
+    bits[(offset / 32) as usize] & (1 << (offset % 32)) != 0
+}
+
+/// (is CJK, width in columns) from a single table lookup
+#[inline]
+pub(crate) fn classify(c: char) -> (bool, u32) {
+    let code_point = c as u32;
+    let block = block_of(code_point);
+    (bit(&block.cjk, code_point), block_width(block, code_point))
+}
+
+#[inline]
+pub(crate) fn is_cjk(c: char) -> bool {
+    let code_point = c as u32;
+    bit(&block_of(code_point).cjk, code_point)
+}
+
+#[inline]
+pub(crate) fn width(c: char) -> u32 {
+    let code_point = c as u32;
+    block_width(block_of(code_point), code_point)
+}
+
+#[inline]
+fn block_width(block: &Block, code_point: u32) -> u32 {
+    if bit(&block.narrow, code_point) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Width from algorithm V3 on: the narrow astral letters take one column instead of two
+#[inline]
+pub(crate) fn astral_aware_width(c: char) -> u32 {
+    if c as u32 > 0xFFFF && in_ranges(NARROW_ASTRAL_RANGES, c) {
+        1
+    } else {
+        width(c)
+    }
+}
+
+pub(crate) fn in_ranges(ranges: &[(u32, u32)], c: char) -> bool {
+    let code_point = c as u32;
+    ranges
+        .iter()
+        .any(|&(start, end)| (start..=end).contains(&code_point))
+}
+
+/// Whether c is a noncharacter or lies in a range with nothing assigned
+/// Not a full Unicode database: unassigned code points scattered inside assigned blocks pass.
+pub(crate) fn is_unassigned(c: char) -> bool {
+    c as u32 & 0xFFFE == 0xFFFE || in_ranges(UNASSIGNED_RANGES, c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_matches_the_ranges_for_every_code_point() {
+        for c in (0..0x110000).filter_map(char::from_u32) {
+            let cjk = in_ranges(CJK_RANGES, c);
+            let width = if in_ranges(NARROW_RANGES, c) { 1 } else { 2 };
+            assert_eq!(classify(c), (cjk, width), "U+{:04X}", c as u32);
+            assert_eq!(is_cjk(c), cjk, "U+{:04X}", c as u32);
+        }
+    }
+
+    #[test]
+    fn table_stays_small() {
+        // A byte per page up to the last listed code point and the deduplicated blocks: about
+        // 1.2 KB with the current ranges
+        assert!(std::mem::size_of::<Table>() < 2048);
+        let past_the_end = std::ptr::from_ref(block_of(0x10FFFF));
+        assert!(std::ptr::eq(
+            past_the_end,
+            &TABLE.blocks[DEFAULT_BLOCK as usize]
+        ));
+    }
+
+    #[test]
+    fn narrow_astral_letters_are_one_column_from_v3() {
+        // Math bold, math digits, Old Italic, Gothic, Deseret
+        for c in ['𝐀', '𝟘', '𐌀', '𐌰', '𐐀'] {
+            assert_eq!(
+                (width(c), astral_aware_width(c)),
+                (2, 1),
+                "U+{:04X}",
+                c as u32
+            );
+        }
+        // Extension B ideographs and emoji stay wide; the BMP is as before
+        for c in ['𠀀', '𪜀', '😀', '漢', 'A'] {
+            assert_eq!(astral_aware_width(c), width(c), "U+{:04X}", c as u32);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! How much a batch call moves across the JS/wasm boundary
+//! Filled in only when a caller asks for it: the plain batch paths skip the clock and the sums.
+//! Timing comes from a clock the caller passes in, since the host's clock isn't available here.
+
+use serde::Serialize;
+
+use crate::cache::justify_batch_each;
+#[cfg(feature = "json")]
+use crate::profile::mark;
+use crate::JustifyOptions;
+#[cfg(feature = "json")]
+use crate::{check_max_chars_per_line, parse_batch_json, Stage, WebtoolsError};
+
+/// Totals for one batch call
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TransferReport {
+    pub item_count: usize,
+    /// UTF-8 bytes of the item texts received (not counting any JSON around them)
+    pub input_bytes: usize,
+    /// UTF-8 bytes of the item results produced
+    pub output_bytes: usize,
+    /// Values converted at the boundary: one per string or array passed in or out
+    pub boundary_crossings: usize,
+    /// Wall-clock ms each item took, in input order; as coarse as the caller's clock
+    pub item_ms: Vec<f64>,
+    pub total_ms: f64,
+    /// Growth of wasm memory during the call; memory never shrinks, so this is the peak
+    pub memory_growth_bytes: usize,
+}
+
+impl TransferReport {
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// justify_batch, also reporting item counts, byte totals and per-item times
+/// clock() returns milliseconds; boundary_crossings and memory_growth_bytes are left for the
+/// caller, which knows how the values crossed
+pub fn justify_batch_with_report<S: AsRef<str>>(
+    texts: &[S],
+    options: &JustifyOptions,
+    use_cache: bool,
+    clock: impl Fn() -> f64,
+) -> (Vec<String>, TransferReport) {
+    let started_ms = clock();
+    let mut previous_ms = started_ms;
+    let mut item_ms = Vec::with_capacity(texts.len());
+    let results = justify_batch_each(texts, options, use_cache, || {
+        let now_ms = clock();
+        // A clock that steps backwards must not produce negative durations
+        item_ms.push((now_ms - previous_ms).max(0.0));
+        previous_ms = now_ms;
+    });
+
+    let report = TransferReport {
+        item_count: texts.len(),
+        input_bytes: texts.iter().map(|text| text.as_ref().len()).sum(),
+        output_bytes: results.iter().map(String::len).sum(),
+        boundary_crossings: 0,
+        item_ms,
+        total_ms: (previous_ms - started_ms).max(0.0),
+        memory_growth_bytes: 0,
+    };
+    (results, report)
+}
+
+#[cfg(feature = "json")]
+/// batch_justify_text that also returns the TransferReport of the call
+/// The results are the same JSON array batch_justify_text returns
+pub fn batch_justify_text_with_report(
+    texts_json: &str,
+    max_chars_per_line: u32,
+    use_cache: bool,
+    clock: impl Fn() -> f64,
+) -> Result<(String, TransferReport), WebtoolsError> {
+    check_max_chars_per_line(max_chars_per_line)?;
+    let text_array = parse_batch_json(texts_json)?;
+    mark(Stage::Decode);
+    mark(Stage::Classify);
+
+    let options = JustifyOptions {
+        max_chars_per_line,
+        ..JustifyOptions::default()
+    };
+    let (results, report) = justify_batch_with_report(&text_array, &options, use_cache, clock);
+    mark(Stage::Wrap);
+
+    let results = serde_json::to_string(&results).unwrap_or_else(|_| "[]".to_string());
+    mark(Stage::Serialize);
+    Ok((results, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::cache::justify_batch;
+
+    const TEXTS: [&str; 4] = [
+        "这是一段中文文本内容",
+        "",
+        "the quick brown fox",
+        "😀 e\u{301}",
+    ];
+
+    fn options() -> JustifyOptions {
+        JustifyOptions {
+            max_chars_per_line: 8,
+            ..JustifyOptions::default()
+        }
+    }
+
+    /// A clock that moves on by step ms every time it is read
+    fn ticking(step: f64) -> impl Fn() -> f64 {
+        let now = Cell::new(1_000.0);
+        move || {
+            now.set(now.get() + step);
+            now.get()
+        }
+    }
+
+    #[test]
+    fn byte_totals_are_the_sums_of_the_items() {
+        let (results, report) = justify_batch_with_report(&TEXTS, &options(), false, ticking(2.5));
+        assert_eq!(results, justify_batch(&TEXTS, &options(), false));
+        assert_eq!(report.item_count, 4);
+        assert_eq!(
+            report.input_bytes,
+            TEXTS.iter().map(|text| text.len()).sum::<usize>()
+        );
+        assert_eq!(
+            report.output_bytes,
+            results.iter().map(String::len).sum::<usize>()
+        );
+        // Left for the caller to fill in
+        assert_eq!(
+            (report.boundary_crossings, report.memory_growth_bytes),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn every_item_is_timed() {
+        let (_, report) = justify_batch_with_report(&TEXTS, &options(), false, ticking(2.5));
+        assert_eq!(report.item_ms, [2.5; 4]);
+        assert_eq!(report.total_ms, 10.0);
+        // A clock stepping backwards gives zeros, never negative times
+        let (_, report) = justify_batch_with_report(&TEXTS, &options(), true, ticking(-1.0));
+        assert_eq!(report.item_ms, [0.0; 4]);
+        assert_eq!(report.total_ms, 0.0);
+        let (results, report) =
+            justify_batch_with_report(&[] as &[&str], &options(), false, ticking(1.0));
+        assert!(results.is_empty());
+        assert_eq!(report, TransferReport::default());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_results_are_batch_justify_texts() {
+        let texts_json = serde_json::to_string(&TEXTS).unwrap();
+        let (results, report) =
+            batch_justify_text_with_report(&texts_json, 8, false, ticking(1.0)).unwrap();
+        assert_eq!(
+            results,
+            crate::batch_justify_text(&texts_json, 8, false).unwrap()
+        );
+        // The item texts' own 57 bytes, without the quotes and commas around them
+        assert_eq!(
+            report.to_json(),
+            r#"{"itemCount":4,"inputBytes":57,"outputBytes":64,"boundaryCrossings":0,"itemMs":[1.0,1.0,1.0,1.0],"totalMs":4.0,"memoryGrowthBytes":0}"#
+        );
+    }
+}
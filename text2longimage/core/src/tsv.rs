@@ -0,0 +1,263 @@
+//! Tab-separated data laid out as aligned columns
+//! Pasted TSV (logs, spreadsheet exports) wrapped like prose loses its columns, so format_tsv
+//! pads every cell to its column's width instead, under the same display widths as the
+//! wrappers. Rows are never wrapped: cells too wide for their column are cut with an ellipsis,
+//! and columns past max_chars_per_line are dropped or moved to a block of their own below.
+
+use std::ops::Range;
+
+use serde::Deserialize;
+
+#[cfg(feature = "json")]
+use crate::{check_max_chars_per_line, WebtoolsError, MIN_MAX_CHARS_PER_LINE};
+use crate::{truncate_to_width, width_of, width_of_str, OptionValue};
+
+const ELLIPSIS: char = '…';
+
+/// Drawn under the header row
+const HEADER_RULE: char = '─';
+
+/// What format_tsv does with the columns that don't fit max_chars_per_line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TsvOverflow {
+    /// Leave them out
+    #[default]
+    Drop,
+    /// Lay them out as another table below, after an empty line, as often as needed
+    Wrap,
+}
+
+impl OptionValue for TsvOverflow {
+    const KIND: &'static str = "tsvOverflow";
+    const USED_BY: &'static [&'static str] = &["TsvOptions.overflow"];
+    const ALL: &'static [Self] = &[TsvOverflow::Drop, TsvOverflow::Wrap];
+
+    fn name(self) -> &'static str {
+        match self {
+            TsvOverflow::Drop => "drop",
+            TsvOverflow::Wrap => "wrap",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            TsvOverflow::Drop => "Columns past the line width are left out",
+            TsvOverflow::Wrap => "Columns past the line width go into another table below",
+        }
+    }
+}
+
+/// Settings for format_tsv; every field is optional when deserializing
+/// JSON keys are camelCase: {"maxColumnWidth", "columnGap", "header", "overflow": "drop"|"wrap"}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct TsvOptions {
+    /// Widest a column gets, in columns; longer cells are cut. 0 for no cap
+    pub max_column_width: u32,
+    /// Spaces between two columns
+    pub column_gap: u32,
+    /// The first row is a header and gets a rule of ─ under it
+    pub header: bool,
+    pub overflow: TsvOverflow,
+}
+
+impl Default for TsvOptions {
+    fn default() -> Self {
+        TsvOptions {
+            max_column_width: 24,
+            column_gap: 2,
+            header: false,
+            overflow: TsvOverflow::Drop,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl TsvOptions {
+    /// Parse options JSON; an empty string means all defaults
+    pub fn from_json(options_json: &str) -> Result<TsvOptions, WebtoolsError> {
+        if options_json.trim().is_empty() {
+            return Ok(TsvOptions::default());
+        }
+        serde_json::from_str(options_json).map_err(WebtoolsError::invalid_json)
+    }
+}
+
+/// Align tab-separated rows into columns at most max_chars_per_line wide
+/// Rows are split on "\n" (a "\r" before it is dropped) and cells on tabs; rows with fewer
+/// cells than others get empty ones. Each column is as wide as its widest cell, capped by
+/// max_column_width and by the line, and cells are left-aligned with spaces; spaces at the end
+/// of a line are trimmed. Lines are joined with "\r\n" like justify_text output.
+pub fn format_tsv(text: &str, max_chars_per_line: u32, options: &TsvOptions) -> String {
+    let max_chars_per_line = max_chars_per_line.max(1);
+    let rows: Vec<Vec<&str>> = text
+        .split('\n')
+        .map(|row| row.strip_suffix('\r').unwrap_or(row).split('\t').collect())
+        .collect();
+    let widths = column_widths(&rows, max_chars_per_line, options.max_column_width);
+
+    let mut blocks = Vec::new();
+    let mut first = 0;
+    while first < widths.len() {
+        let end = block_end(&widths, first, max_chars_per_line, options.column_gap);
+        blocks.push(format_block(&rows, &widths, first..end, options));
+        if options.overflow == TsvOverflow::Drop {
+            break;
+        }
+        first = end;
+    }
+    blocks.join("\r\n\r\n")
+}
+
+#[cfg(feature = "json")]
+/// format_tsv with the options as JSON {maxColumnWidth, columnGap, header, overflow}, all
+/// optional; "" uses the defaults (24-column cap, 2-space gap, no header, overflow "drop")
+pub fn format_tsv_json(
+    text: &str,
+    max_chars_per_line: u32,
+    options_json: &str,
+) -> Result<String, WebtoolsError> {
+    if max_chars_per_line < MIN_MAX_CHARS_PER_LINE {
+        return Err(WebtoolsError::InvalidWidth {
+            min: MIN_MAX_CHARS_PER_LINE,
+            actual: max_chars_per_line,
+        });
+    }
+    check_max_chars_per_line(max_chars_per_line)?;
+    let options = TsvOptions::from_json(options_json)?;
+    Ok(format_tsv(text, max_chars_per_line, &options))
+}
+
+/// Width of every column: its widest cell, within the cap and the line
+fn column_widths(rows: &[Vec<&str>], max_chars_per_line: u32, max_column_width: u32) -> Vec<u32> {
+    let cap = match max_column_width {
+        0 => max_chars_per_line,
+        cap => cap.min(max_chars_per_line),
+    };
+    let mut widths: Vec<u32> = Vec::new();
+    for row in rows {
+        if widths.len() < row.len() {
+            widths.resize(row.len(), 0);
+        }
+        for (width, cell) in widths.iter_mut().zip(row) {
+            let cell_width = width_of_str(cell).min(cap as u64) as u32;
+            *width = (*width).max(cell_width);
+        }
+    }
+    widths
+}
+
+/// One past the last column, from first on, that fits on the line; always at least first + 1
+fn block_end(widths: &[u32], first: usize, max_chars_per_line: u32, column_gap: u32) -> usize {
+    let mut used = u64::from(widths[first]);
+    let mut end = first + 1;
+    while end < widths.len() {
+        used += u64::from(column_gap) + u64::from(widths[end]);
+        if used > u64::from(max_chars_per_line) {
+            break;
+        }
+        end += 1;
+    }
+    end
+}
+
+/// The rows' cells in columns, with the header rule under the first row if asked for
+fn format_block(
+    rows: &[Vec<&str>],
+    widths: &[u32],
+    columns: Range<usize>,
+    options: &TsvOptions,
+) -> String {
+    let gap = " ".repeat(options.column_gap as usize);
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (index, row) in rows.iter().enumerate() {
+        let mut line = String::new();
+        for column in columns.clone() {
+            if column > columns.start {
+                line.push_str(&gap);
+            }
+            let cell = row.get(column).copied().unwrap_or("");
+            push_cell(&mut line, cell, widths[column]);
+        }
+        lines.push(line.trim_end_matches(' ').to_string());
+
+        if index == 0 && options.header {
+            let block_width: u64 = widths[columns.clone()]
+                .iter()
+                .map(|&w| u64::from(w))
+                .sum::<u64>()
+                + u64::from(options.column_gap) * (columns.len() as u64 - 1);
+            let rule_count = block_width / u64::from(width_of(HEADER_RULE));
+            lines.push(HEADER_RULE.to_string().repeat(rule_count as usize));
+        }
+    }
+    lines.join("\r\n")
+}
+
+/// cell padded to width columns, or cut to fit with an ellipsis
+/// A column too narrow for the ellipsis gets the characters that fit and no ellipsis.
+fn push_cell(line: &mut String, cell: &str, width: u32) {
+    let cell_width = width_of_str(cell);
+    let shown_width = if cell_width <= u64::from(width) {
+        line.push_str(cell);
+        cell_width
+    } else {
+        match width.checked_sub(width_of(ELLIPSIS)) {
+            Some(budget) => {
+                let kept = truncate_to_width(cell, u64::from(budget));
+                line.push_str(kept);
+                line.push(ELLIPSIS);
+                width_of_str(kept) + u64::from(width_of(ELLIPSIS))
+            }
+            None => {
+                let kept = truncate_to_width(cell, u64::from(width));
+                line.push_str(kept);
+                width_of_str(kept)
+            }
+        }
+    };
+    let padding = u64::from(width).saturating_sub(shown_width);
+    line.extend(std::iter::repeat_n(' ', padding as usize));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap() -> TsvOptions {
+        TsvOptions {
+            overflow: TsvOverflow::Wrap,
+            ..TsvOptions::default()
+        }
+    }
+
+    #[test]
+    fn columns_line_up_by_display_width() {
+        let header = TsvOptions {
+            header: true,
+            ..TsvOptions::default()
+        };
+        let table = "名前\t年齢\n田中太郎\t30\nBob\t4";
+        assert_eq!(
+            format_tsv(table, 36, &header),
+            "名前      年齢\r\n───────\r\n田中太郎  30\r\nBob       4"
+        );
+        assert_eq!(
+            format_tsv("a\tb\tc\nd\ne\tf", 36, &TsvOptions::default()),
+            "a  b  c\r\nd\r\ne  f"
+        );
+    }
+
+    #[test]
+    fn wide_cells_and_columns_are_cut_or_wrapped() {
+        let capped = TsvOptions {
+            max_column_width: 4,
+            ..TsvOptions::default()
+        };
+        assert_eq!(format_tsv("abcdefgh\tx", 36, &capped), "ab…  x");
+        let wide = "alpha\tbeta\tgamma";
+        assert_eq!(format_tsv(wide, 12, &TsvOptions::default()), "alpha  beta");
+        assert_eq!(format_tsv(wide, 12, &wrap()), "alpha  beta\r\n\r\ngamma");
+    }
+}
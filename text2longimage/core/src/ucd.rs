@@ -0,0 +1,38 @@
+//! Range tables generated from the Unicode Character Database's Blocks.txt
+//! Written by `cargo run -p text_processor_ucd -- --update`; don't edit by hand.
+
+/// Unicode version of the Blocks.txt the tables were generated from
+pub(crate) const UNICODE_VERSION: &str = "15.1.0";
+
+/// CJK ranges (inclusive)
+pub(crate) const CJK_RANGES: &[(u32, u32)] = &[
+    (0x3040, 0x30FF),   // Hiragana, Katakana
+    (0x3400, 0x4DBF),   // CJK Unified Ideographs Extension A
+    (0x4E00, 0x9FFF),   // CJK Unified Ideographs
+    (0x20000, 0x2A6DF), // CJK Unified Ideographs Extension B
+];
+
+/// Ideographs outside the BMP: CJK Extensions B through I and the compatibility supplement
+/// (only Extension B counts as CJK for wrapping)
+pub(crate) const SUPPLEMENTARY_IDEOGRAPH_RANGES: &[(u32, u32)] = &[
+    (0x20000, 0x2FA1F), // CJK Extensions B to F and I, compatibility supplement
+    (0x30000, 0x323AF), // CJK Extensions G and H
+];
+
+/// Astral-plane letters that fonts draw about as wide as Latin ones: Old Italic, Gothic,
+/// Deseret and Mathematical Alphanumeric Symbols. Everything else above U+FFFF (emoji,
+/// ideographs, other symbols) stays two columns wide.
+pub(crate) const NARROW_ASTRAL_RANGES: &[(u32, u32)] = &[
+    (0x10300, 0x1034F), // Old Italic, Gothic
+    (0x10400, 0x1044F), // Deseret
+    (0x1D400, 0x1D7FF), // Mathematical Alphanumeric Symbols
+];
+
+/// Combining marks: diacritics plus their extended, supplement, symbol and half-mark blocks
+pub(crate) const COMBINING_RANGES: &[(u32, u32)] = &[
+    (0x0300, 0x036F), // Combining Diacritical Marks
+    (0x1AB0, 0x1AFF), // Combining Diacritical Marks Extended
+    (0x1DC0, 0x1DFF), // Combining Diacritical Marks Supplement
+    (0x20D0, 0x20FF), // Combining Diacritical Marks for Symbols
+    (0xFE20, 0xFE2F), // Combining Half Marks
+];
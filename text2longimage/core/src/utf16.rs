@@ -0,0 +1,281 @@
+use crate::metrics::{fits_width, number_token_len};
+use crate::{is_rtl_char, justify_text, CharClass};
+
+const LF: u16 = 0x000A;
+const CR: u16 = 0x000D;
+const CRLF: [u16; 2] = [CR, LF];
+
+/// Decodes UTF-16 as (unit offset, char), lone surrogates becoming U+FFFD
+struct Utf16Chars<'a> {
+    units: &'a [u16],
+    pos: usize,
+}
+
+impl Iterator for Utf16Chars<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        let start = self.pos;
+        let unit = *self.units.get(start)?;
+        self.pos += 1;
+
+        let c = match unit {
+            0xD800..=0xDBFF => match self.units.get(self.pos) {
+                Some(&low @ 0xDC00..=0xDFFF) => {
+                    self.pos += 1;
+                    let code_point =
+                        0x10000 + (((unit as u32) - 0xD800) << 10) + ((low as u32) - 0xDC00);
+                    char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+                _ => char::REPLACEMENT_CHARACTER,
+            },
+            0xDC00..=0xDFFF => char::REPLACEMENT_CHARACTER,
+            _ => char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+        };
+        Some((start, c))
+    }
+}
+
+fn chars(units: &[u16]) -> Utf16Chars<'_> {
+    Utf16Chars { units, pos: 0 }
+}
+
+fn push_char(out: &mut Vec<u16>, c: char) {
+    let mut buf = [0u16; 2];
+    out.extend_from_slice(c.encode_utf16(&mut buf));
+}
+
+/// Same as str::trim, on UTF-16 units
+fn trim(units: &[u16]) -> &[u16] {
+    let mut start = None;
+    let mut end = 0;
+    for (offset, c) in chars(units) {
+        if !c.is_whitespace() {
+            start.get_or_insert(offset);
+            end = offset + c.len_utf16();
+        }
+    }
+    match start {
+        Some(start) => &units[start..end],
+        None => &[],
+    }
+}
+
+/// justify_text over UTF-16 code units (e.g. a Uint16Array from a JS string), returning UTF-16
+/// Output equals justify_text on the same text; lone surrogates are treated as U+FFFD
+/// (see count_lone_surrogates). Surrogate pairs are decoded inside the wrapping loop, so no
+/// UTF-8 copy of the input is made.
+pub fn justify_text_utf16(units: &[u16], max_chars_per_line: u32) -> Vec<u16> {
+    let max_width = max_chars_per_line as f64 / 2.0;
+    let mut out = Vec::with_capacity(units.len() + units.len() / 20);
+
+    // A surrogate is never 0x000A, so splitting on LF units can't cut a pair
+    for (index, line) in units.split(|&unit| unit == LF).enumerate() {
+        if index > 0 {
+            out.extend_from_slice(&CRLF);
+        }
+
+        let line = trim(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        if chars(line).any(|(_, c)| is_rtl_char(c)) {
+            // RTL runs move whole (see bidi.rs); wrap such lines as a string instead
+            let line: String = chars(line).map(|(_, c)| c).collect();
+            out.extend(justify_text(&line, max_chars_per_line).encode_utf16());
+        } else if chars(line).any(|(_, c)| CharClass::of(c).cjk) {
+            wrap_chars(line, max_width, &mut out);
+        } else {
+            wrap_words(line, max_width, &mut out);
+        }
+    }
+
+    out
+}
+
+/// Drop whitespace at the end of the output line being written, as TrimTrailing does
+/// Every whitespace character is a single BMP unit, and CR/LF stop the scan at the line start
+fn trim_trailing(out: &mut Vec<u16>) {
+    while out.last().is_some_and(|&unit| {
+        unit != LF && unit != CR && char::from_u32(unit as u32).is_some_and(char::is_whitespace)
+    }) {
+        out.pop();
+    }
+}
+
+/// Mirrors wrap_chars_with under the column width model, trimming like justify_text
+fn wrap_chars(line: &[u16], max_width: f64, out: &mut Vec<u16>) {
+    let mut current_line_width = 0.0f64;
+    let place = |width: f64, out: &mut Vec<u16>, current_line_width: &mut f64| {
+        if !fits_width(*current_line_width + width, max_width) {
+            trim_trailing(out);
+            out.extend_from_slice(&CRLF);
+            *current_line_width = width;
+        } else {
+            *current_line_width += width;
+        }
+    };
+
+    let mut offset = 0;
+    while let Some((_, c)) = chars(&line[offset..]).next() {
+        // Number tokens are ASCII, so each unit is one half-width character
+        let token_len = number_token_len(&line[offset..]);
+        if token_len > 1 {
+            let token = &line[offset..offset + token_len];
+            let token_width = token_len as f64 * CharClass::of('0').width_em();
+            if fits_width(token_width, max_width) {
+                place(token_width, out, &mut current_line_width);
+                out.extend_from_slice(token);
+            } else {
+                for &unit in token {
+                    place(CharClass::of('0').width_em(), out, &mut current_line_width);
+                    out.push(unit);
+                }
+            }
+            offset += token_len;
+            continue;
+        }
+
+        offset += c.len_utf16();
+        if c == '\r' {
+            trim_trailing(out);
+            out.extend_from_slice(&CRLF);
+            current_line_width = 0.0;
+            continue;
+        }
+
+        place(CharClass::of(c).width_em(), out, &mut current_line_width);
+        push_char(out, c);
+    }
+}
+
+/// Mirrors wrap_words_with under the column width model: words are measured by their
+/// UTF-8 byte length, as justify_text_english does
+fn wrap_words(line: &[u16], max_width: f64, out: &mut Vec<u16>) {
+    let space_width = CharClass::of(' ').width_em();
+    let mut current_line_width = 0.0f64;
+    let mut line_is_empty = true;
+
+    let mut place_word = |word: &[u16], utf8_len: usize, out: &mut Vec<u16>| {
+        let word_width = utf8_len as f64 / 2.0;
+        if line_is_empty {
+            current_line_width = word_width;
+            line_is_empty = false;
+        } else if fits_width(current_line_width + space_width + word_width, max_width) {
+            out.push(u16::from(b' '));
+            current_line_width += space_width + word_width;
+        } else {
+            out.extend_from_slice(&CRLF);
+            current_line_width = word_width;
+        }
+        chars(word).for_each(|(_, c)| push_char(out, c));
+    };
+
+    let mut word_start = None;
+    let mut utf8_len = 0;
+    for (offset, c) in chars(line) {
+        if c.is_whitespace() {
+            if let Some(start) = word_start.take() {
+                place_word(&line[start..offset], utf8_len, out);
+            }
+        } else {
+            if word_start.is_none() {
+                word_start = Some(offset);
+                utf8_len = 0;
+            }
+            utf8_len += c.len_utf8();
+        }
+    }
+    if let Some(start) = word_start {
+        place_word(&line[start..], utf8_len, out);
+    }
+}
+
+/// Number of unpaired surrogates in the input, i.e. how many U+FFFD justify_text_utf16
+/// substituted
+pub fn count_lone_surrogates(units: &[u16]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < units.len() {
+        match units[i] {
+            0xD800..=0xDBFF if matches!(units.get(i + 1), Some(0xDC00..=0xDFFF)) => i += 1,
+            0xD800..=0xDFFF => count += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::random_texts;
+
+    fn utf16(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn round_trips_match_the_string_path() {
+        for text in random_texts(200) {
+            for width in [1, 2, 5, 10, 40] {
+                let wrapped = justify_text_utf16(&utf16(&text), width);
+                assert_eq!(
+                    String::from_utf16(&wrapped).unwrap(),
+                    justify_text(&text, width),
+                    "{:?} at {}",
+                    text,
+                    width
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fixtures() {
+        let cases = [
+            ("The quick brown fox", 10, "The quick\r\nbrown fox"),
+            ("这是一段中文文本", 8, "这是一段\r\n中文文本"),
+            // A surrogate pair is one wide character
+            ("日本😀", 4, "日本\r\n😀"),
+            ("  a  \n\n  b  ", 10, "a\r\n\r\nb"),
+            ("", 10, ""),
+        ];
+        for (text, width, expected) in cases {
+            let wrapped = justify_text_utf16(&utf16(text), width);
+            assert_eq!(
+                String::from_utf16(&wrapped).unwrap(),
+                expected,
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn lone_surrogates_become_replacement_characters() {
+        let high = 0xD83D;
+        let low = 0xDE00;
+        let cases: [(&[u16], usize); 6] = [
+            (&[high, low], 0),
+            (&[high], 1),
+            (&[low], 1),
+            (&[low, high], 2),
+            (&[high, high, low], 1),
+            (&[0x61, high, 0x62, low], 2),
+        ];
+        for (units, lone) in cases {
+            assert_eq!(count_lone_surrogates(units), lone, "{:x?}", units);
+            let decoded = String::from_utf16_lossy(units);
+            assert_eq!(decoded.matches('\u{FFFD}').count(), lone);
+            assert_eq!(
+                justify_text_utf16(units, 40),
+                utf16(&justify_text(&decoded, 40)),
+                "{:x?}",
+                units
+            );
+        }
+    }
+}
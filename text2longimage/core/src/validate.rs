@@ -0,0 +1,629 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::joiners::BOM;
+use crate::marks::mark_runs;
+use crate::runs::unbreakable_runs;
+use crate::{
+    check_input_size, check_max_chars_per_line, is_empty_input, width_of, WebtoolsError,
+    DEFAULT_MAX_MARKS_PER_CLUSTER, DEFAULT_MAX_UNBREAKABLE_RUN,
+};
+
+/// Default character limit for validate_input
+pub const MAX_INPUT_CHARS: usize = 500_000;
+
+/// Width a single full-width character needs
+const WIDE_CHAR_WIDTH: u32 = 2;
+
+/// Most BOM offsets a ByteOrderMarks warning lists; the count covers the rest
+pub const MAX_LISTED_BOMS: usize = 100;
+
+/// Validate text input for processing
+/// Returns error message if invalid, empty string if valid
+pub fn validate_text_input(text: &str) -> String {
+    match validate_text(text) {
+        Ok(()) => String::new(), // Empty string means valid
+        Err(error) => error.to_string(),
+    }
+}
+
+/// Structured form of validate_text_input
+/// The size cap is max_input_size(), in bytes
+pub fn validate_text(text: &str) -> Result<(), WebtoolsError> {
+    if is_empty_input(text) {
+        return Err(WebtoolsError::EmptyInput);
+    }
+    check_input_size(text)
+}
+
+fn check_char_count(text: &str, max_chars: usize) -> Result<(), WebtoolsError> {
+    // A string can't have more characters than bytes, so short input skips the count
+    if text.len() <= max_chars {
+        return Ok(());
+    }
+
+    let actual = text.chars().count();
+    if actual > max_chars {
+        return Err(WebtoolsError::TooManyCharacters {
+            limit: max_chars,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Non-blocking findings from validate_input
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationWarning {
+    /// C0/C1 control characters other than tab and line breaks
+    ControlCharacters { count: usize, first_offset: usize },
+    /// U+FFFD, usually left behind by a lossy decode
+    ReplacementCharacters { count: usize, first_offset: usize },
+    /// Words wider than the line; they overflow instead of wrapping
+    LongTokens {
+        count: usize,
+        longest: usize,
+        max_chars_per_line: u32,
+    },
+    /// More than one of CRLF, LF and bare CR line endings
+    MixedLineEndings { crlf: usize, lf: usize, cr: usize },
+    /// Words wider than DEFAULT_MAX_UNBREAKABLE_RUN; JustifyOptions cuts them into lines
+    UnbreakableRuns {
+        count: usize,
+        longest: usize,
+        max_unbreakable_run: u32,
+    },
+    /// Characters carrying more than DEFAULT_MAX_MARKS_PER_CLUSTER combining marks ("Zalgo"
+    /// text); JustifyOptions drops the extra ones
+    StackedMarks {
+        count: usize,
+        most: usize,
+        first_offset: usize,
+        max_marks_per_cluster: u32,
+    },
+    /// U+FEFF byte order marks, left inside text by concatenated files; no line breaks next to
+    /// one from algorithm V6 on. offsets are the byte offsets of the first MAX_LISTED_BOMS
+    ByteOrderMarks { count: usize, offsets: Vec<usize> },
+}
+
+impl ValidationWarning {
+    /// Stable machine-readable code, e.g. "MIXED_LINE_ENDINGS"
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationWarning::ControlCharacters { .. } => "CONTROL_CHARACTERS",
+            ValidationWarning::ReplacementCharacters { .. } => "REPLACEMENT_CHARACTERS",
+            ValidationWarning::LongTokens { .. } => "LONG_TOKENS",
+            ValidationWarning::MixedLineEndings { .. } => "MIXED_LINE_ENDINGS",
+            ValidationWarning::UnbreakableRuns { .. } => "UNBREAKABLE_RUNS",
+            ValidationWarning::StackedMarks { .. } => "STACKED_MARKS",
+            ValidationWarning::ByteOrderMarks { .. } => "BYTE_ORDER_MARKS",
+        }
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationWarning::ControlCharacters { count, .. } => {
+                write!(f, "Text contains {} control character(s)", count)
+            }
+            ValidationWarning::ReplacementCharacters { count, .. } => write!(
+                f,
+                "Text contains {} replacement character(s) (U+FFFD)",
+                count
+            ),
+            ValidationWarning::LongTokens {
+                count,
+                max_chars_per_line,
+                ..
+            } => write!(
+                f,
+                "{} word(s) are wider than {} columns and will overflow the line",
+                count, max_chars_per_line
+            ),
+            ValidationWarning::MixedLineEndings { .. } => {
+                f.write_str("Text mixes different line endings")
+            }
+            ValidationWarning::UnbreakableRuns {
+                count,
+                max_unbreakable_run,
+                ..
+            } => write!(
+                f,
+                "{} run(s) without a break are wider than {} columns and will be cut into lines",
+                count, max_unbreakable_run
+            ),
+            ValidationWarning::StackedMarks {
+                count,
+                max_marks_per_cluster,
+                ..
+            } => write!(
+                f,
+                "{} character(s) carry more than {} combining marks; the extra marks will be dropped",
+                count, max_marks_per_cluster
+            ),
+            ValidationWarning::ByteOrderMarks { count, .. } => write!(
+                f,
+                "Text contains {} byte order mark(s) (U+FEFF); lines won't break next to them",
+                count
+            ),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct CountDetails {
+    count: usize,
+    first_offset: usize,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct LongTokenDetails {
+    count: usize,
+    longest: usize,
+    max_chars_per_line: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct UnbreakableRunDetails {
+    count: usize,
+    longest: usize,
+    max_unbreakable_run: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+struct StackedMarkDetails {
+    count: usize,
+    most: usize,
+    first_offset: usize,
+    max_marks_per_cluster: u32,
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct ByteOrderMarkDetails<'a> {
+    count: usize,
+    offsets: &'a [usize],
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+struct LineEndingDetails {
+    crlf: usize,
+    lf: usize,
+    cr: usize,
+}
+
+#[cfg(feature = "schema")]
+/// The same shapes as WebtoolsError's, one per code
+impl schemars::JsonSchema for ValidationWarning {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "ValidationWarning".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let count = generator.subschema_for::<CountDetails>();
+        crate::schema::coded_schema(vec![
+            ("CONTROL_CHARACTERS", count.clone()),
+            ("REPLACEMENT_CHARACTERS", count),
+            ("LONG_TOKENS", generator.subschema_for::<LongTokenDetails>()),
+            (
+                "MIXED_LINE_ENDINGS",
+                generator.subschema_for::<LineEndingDetails>(),
+            ),
+            (
+                "UNBREAKABLE_RUNS",
+                generator.subschema_for::<UnbreakableRunDetails>(),
+            ),
+            (
+                "STACKED_MARKS",
+                generator.subschema_for::<StackedMarkDetails>(),
+            ),
+            (
+                "BYTE_ORDER_MARKS",
+                generator.subschema_for::<ByteOrderMarkDetails>(),
+            ),
+        ])
+    }
+}
+
+/// Serialized as {code, message, details}, the same shape as WebtoolsError
+impl Serialize for ValidationWarning {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ValidationWarning", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        match *self {
+            ValidationWarning::ControlCharacters {
+                count,
+                first_offset,
+            }
+            | ValidationWarning::ReplacementCharacters {
+                count,
+                first_offset,
+            } => state.serialize_field(
+                "details",
+                &CountDetails {
+                    count,
+                    first_offset,
+                },
+            )?,
+            ValidationWarning::LongTokens {
+                count,
+                longest,
+                max_chars_per_line,
+            } => state.serialize_field(
+                "details",
+                &LongTokenDetails {
+                    count,
+                    longest,
+                    max_chars_per_line,
+                },
+            )?,
+            ValidationWarning::MixedLineEndings { crlf, lf, cr } => {
+                state.serialize_field("details", &LineEndingDetails { crlf, lf, cr })?
+            }
+            ValidationWarning::ByteOrderMarks { count, ref offsets } => {
+                state.serialize_field("details", &ByteOrderMarkDetails { count, offsets })?
+            }
+            ValidationWarning::UnbreakableRuns {
+                count,
+                longest,
+                max_unbreakable_run,
+            } => state.serialize_field(
+                "details",
+                &UnbreakableRunDetails {
+                    count,
+                    longest,
+                    max_unbreakable_run,
+                },
+            )?,
+            ValidationWarning::StackedMarks {
+                count,
+                most,
+                first_offset,
+                max_marks_per_cluster,
+            } => state.serialize_field(
+                "details",
+                &StackedMarkDetails {
+                    count,
+                    most,
+                    first_offset,
+                    max_marks_per_cluster,
+                },
+            )?,
+        }
+        state.end()
+    }
+}
+
+/// Result of validate_input: errors block processing, warnings are informational
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<WebtoolsError>,
+    pub warnings: Vec<ValidationWarning>,
+}
+
+/// Check text against every validation rule with the default character limit
+pub fn validate_input_report(text: &str, max_chars_per_line: u32) -> ValidationReport {
+    validate_input_with_limit(text, max_chars_per_line, MAX_INPUT_CHARS)
+}
+
+/// Check text against every validation rule
+/// Errors: empty input, more than max_input_size() bytes, more than max_chars characters,
+/// a width that can't hold the content.
+/// Warnings: control characters, U+FFFD, words wider than the line, mixed line endings, runs
+/// wider than DEFAULT_MAX_UNBREAKABLE_RUN, more than DEFAULT_MAX_MARKS_PER_CLUSTER combining
+/// marks on one character, byte order marks.
+pub fn validate_input_with_limit(
+    text: &str,
+    max_chars_per_line: u32,
+    max_chars: usize,
+) -> ValidationReport {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    if is_empty_input(text) {
+        errors.push(WebtoolsError::EmptyInput);
+    }
+    if let Err(error) = check_input_size(text) {
+        errors.push(error);
+    }
+    if let Err(error) = check_char_count(text, max_chars) {
+        errors.push(error);
+    }
+    if let Some(error) = check_width(text, max_chars_per_line) {
+        errors.push(error);
+    }
+    if let Err(error) = check_max_chars_per_line(max_chars_per_line) {
+        errors.push(error);
+    }
+
+    let mut controls = (0, 0);
+    let mut replacements = (0, 0);
+    let mut boms = (0, Vec::new());
+    let (mut crlf, mut lf, mut cr) = (0, 0, 0);
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.next_if(|&(_, next)| next == '\n').is_some() {
+                    crlf += 1;
+                } else {
+                    cr += 1;
+                }
+            }
+            '\n' => lf += 1,
+            '\t' => {}
+            '\u{FFFD}' => count_at(&mut replacements, offset),
+            BOM => {
+                if boms.0 < MAX_LISTED_BOMS {
+                    boms.1.push(offset);
+                }
+                boms.0 += 1;
+            }
+            c if c.is_control() => count_at(&mut controls, offset),
+            _ => {}
+        }
+    }
+
+    if controls.0 > 0 {
+        warnings.push(ValidationWarning::ControlCharacters {
+            count: controls.0,
+            first_offset: controls.1,
+        });
+    }
+    if replacements.0 > 0 {
+        warnings.push(ValidationWarning::ReplacementCharacters {
+            count: replacements.0,
+            first_offset: replacements.1,
+        });
+    }
+    if let Some(warning) = check_long_tokens(text, max_chars_per_line) {
+        warnings.push(warning);
+    }
+    if let Some(warning) = check_unbreakable_runs(text, DEFAULT_MAX_UNBREAKABLE_RUN) {
+        warnings.push(warning);
+    }
+    if let Some(warning) = check_stacked_marks(text, DEFAULT_MAX_MARKS_PER_CLUSTER) {
+        warnings.push(warning);
+    }
+    if boms.0 > 0 {
+        warnings.push(ValidationWarning::ByteOrderMarks {
+            count: boms.0,
+            offsets: boms.1,
+        });
+    }
+    if [crlf, lf, cr].iter().filter(|&&n| n > 0).count() > 1 {
+        warnings.push(ValidationWarning::MixedLineEndings { crlf, lf, cr });
+    }
+
+    ValidationReport {
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+    }
+}
+
+#[cfg(feature = "json")]
+/// validate_input_report as JSON: {valid, errors: [...], warnings: [...]}
+/// Each entry is {code, message, details}
+pub fn validate_input(text: &str, max_chars_per_line: u32) -> String {
+    serde_json::to_string(&validate_input_report(text, max_chars_per_line))
+        .unwrap_or_else(|_| r#"{"valid":false,"errors":[],"warnings":[]}"#.to_string())
+}
+
+/// Bump a (count, first offset) tally
+fn count_at(tally: &mut (usize, usize), offset: usize) {
+    if tally.0 == 0 {
+        tally.1 = offset;
+    }
+    tally.0 += 1;
+}
+
+/// Zero columns fit nothing; one column can't hold a full-width character
+fn check_width(text: &str, max_chars_per_line: u32) -> Option<WebtoolsError> {
+    let min = if max_chars_per_line == 0 {
+        1
+    } else if max_chars_per_line < WIDE_CHAR_WIDTH
+        && text.chars().any(|c| width_of(c) == WIDE_CHAR_WIDTH)
+    {
+        WIDE_CHAR_WIDTH
+    } else {
+        return None;
+    };
+
+    Some(WebtoolsError::InvalidWidth {
+        min,
+        actual: max_chars_per_line,
+    })
+}
+
+/// Words on non-CJK lines are measured by byte length, as justify_text_english does,
+/// and never broken, so anything wider than the line overflows it
+fn check_long_tokens(text: &str, max_chars_per_line: u32) -> Option<ValidationWarning> {
+    if max_chars_per_line == 0 {
+        return None;
+    }
+
+    let (count, longest) = runs_wider_than(text, max_chars_per_line)?;
+    Some(ValidationWarning::LongTokens {
+        count,
+        longest,
+        max_chars_per_line,
+    })
+}
+
+/// Runs the default JustifyOptions will cut rather than let overflow
+fn check_unbreakable_runs(text: &str, max_unbreakable_run: u32) -> Option<ValidationWarning> {
+    let (count, longest) = runs_wider_than(text, max_unbreakable_run)?;
+    Some(ValidationWarning::UnbreakableRuns {
+        count,
+        longest,
+        max_unbreakable_run,
+    })
+}
+
+/// Characters the default JustifyOptions will drop marks from
+fn check_stacked_marks(text: &str, max_marks_per_cluster: u32) -> Option<ValidationWarning> {
+    let mut stacked = mark_runs(text)
+        .into_iter()
+        .filter(|&(_, marks)| marks > max_marks_per_cluster as usize);
+    let (first_offset, first) = stacked.next()?;
+    let (count, most) = stacked.fold((1, first), |(count, most), (_, marks)| {
+        (count + 1, most.max(marks))
+    });
+    Some(ValidationWarning::StackedMarks {
+        count,
+        most,
+        first_offset,
+        max_marks_per_cluster,
+    })
+}
+
+/// Number and width of the longest of the unbreakable runs wider than limit, if there are any
+fn runs_wider_than(text: &str, limit: u32) -> Option<(usize, usize)> {
+    let (count, longest) = unbreakable_runs(text)
+        .filter(|&len| len > limit as usize)
+        .fold((0, 0), |(count, longest), len| {
+            (count + 1, longest.max(len))
+        });
+    (count > 0).then_some((count, longest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_MAX_CHARS_PER_LINE;
+
+    fn codes(
+        text: &str,
+        max_chars_per_line: u32,
+        max_chars: usize,
+    ) -> (Vec<&'static str>, Vec<&'static str>) {
+        let report = validate_input_with_limit(text, max_chars_per_line, max_chars);
+        assert_eq!(report.valid, report.errors.is_empty());
+        (
+            report.errors.iter().map(WebtoolsError::code).collect(),
+            report
+                .warnings
+                .iter()
+                .map(ValidationWarning::code)
+                .collect(),
+        )
+    }
+
+    fn warnings(text: &str, max_chars_per_line: u32) -> Vec<ValidationWarning> {
+        validate_input_with_limit(text, max_chars_per_line, MAX_INPUT_CHARS).warnings
+    }
+
+    #[test]
+    fn clean_text_passes() {
+        let report = validate_input_report("漢字かな交じり and English\nsecond line\n", 20);
+        assert!(report.valid && report.errors.is_empty() && report.warnings.is_empty());
+    }
+
+    #[test]
+    fn each_error_on_its_own() {
+        assert_eq!(codes(" ", 20, 100).0, ["EMPTY_INPUT"]);
+        assert_eq!(codes("abcdefg", 20, 5).0, ["TOO_MANY_CHARACTERS"]);
+        assert_eq!(codes("abc", 0, 100).0, ["INVALID_WIDTH"]);
+        assert_eq!(codes("abc", u32::MAX, 100).0, ["WIDTH_TOO_LARGE"]);
+        // One column holds ASCII but not a full-width character
+        assert!(codes("abc", 1, 100).0.is_empty());
+        let report = validate_input_with_limit("a漢", 1, 100);
+        assert_eq!(
+            report.errors,
+            [WebtoolsError::InvalidWidth { min: 2, actual: 1 }]
+        );
+    }
+
+    #[test]
+    fn each_warning_on_its_own() {
+        assert_eq!(
+            warnings("ab\u{7}c\u{7}", 20),
+            [ValidationWarning::ControlCharacters {
+                count: 2,
+                first_offset: 2
+            }]
+        );
+        assert_eq!(
+            warnings("a \u{FFFD}", 20),
+            [ValidationWarning::ReplacementCharacters {
+                count: 1,
+                first_offset: 2
+            }]
+        );
+        assert_eq!(
+            warnings("supercalifragilistic words", 10),
+            [ValidationWarning::LongTokens {
+                count: 1,
+                longest: 20,
+                max_chars_per_line: 10
+            }]
+        );
+        assert_eq!(
+            warnings("a\r\nb\nc\nd", 20),
+            [ValidationWarning::MixedLineEndings {
+                crlf: 1,
+                lf: 2,
+                cr: 0
+            }]
+        );
+        let run = DEFAULT_MAX_UNBREAKABLE_RUN as usize + 5;
+        assert_eq!(
+            warnings(&"x".repeat(run), MAX_MAX_CHARS_PER_LINE),
+            [ValidationWarning::UnbreakableRuns {
+                count: 1,
+                longest: run,
+                max_unbreakable_run: DEFAULT_MAX_UNBREAKABLE_RUN,
+            }]
+        );
+        // first_offset is where the run of marks starts, after the e
+        let marks = DEFAULT_MAX_MARKS_PER_CLUSTER as usize + 1;
+        assert_eq!(
+            warnings(&format!("ok e{}", "\u{301}".repeat(marks)), 20),
+            [ValidationWarning::StackedMarks {
+                count: 1,
+                most: marks,
+                first_offset: 4,
+                max_marks_per_cluster: DEFAULT_MAX_MARKS_PER_CLUSTER,
+            }]
+        );
+        assert_eq!(
+            warnings("\u{FEFF}a\u{FEFF}b", 20),
+            [ValidationWarning::ByteOrderMarks {
+                count: 2,
+                offsets: vec![0, 4]
+            }]
+        );
+    }
+
+    #[test]
+    fn rules_fire_together() {
+        let text = "\u{FEFF}漢字\u{7} supercalifragilistic\u{FFFD}\r\nmore\n";
+        let (errors, warnings) = codes(text, 1, 10);
+        assert_eq!(errors, ["TOO_MANY_CHARACTERS", "INVALID_WIDTH"]);
+        assert_eq!(
+            warnings,
+            [
+                "CONTROL_CHARACTERS",
+                "REPLACEMENT_CHARACTERS",
+                "LONG_TOKENS",
+                "BYTE_ORDER_MARKS",
+                "MIXED_LINE_ENDINGS",
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Vertical (tategaki) layout: top-to-bottom columns laid out right to left
+//! Columns are the lines justify_text_cjk_locale would produce with automatic locale detection,
+//! so a column breaks exactly where the horizontal line would, kinsoku rules included.
+//! Half-width characters are drawn rotated a quarter turn, each run of them as one group.
+
+use serde::Serialize;
+
+use crate::layout::column_width_px;
+use crate::{justify_text_cjk_with_rules, CharClass, CjkLocale, KinsokuRules};
+
+/// One character of a vertical layout
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct VerticalCell {
+    /// Column number counted from the right edge, starting at 0
+    pub col_index: u32,
+    /// Position of the character within its column
+    pub row_index: u32,
+    /// Left edge of the column
+    pub x_px: f64,
+    /// Top of the cell: full-width characters advance font_size_px, half-width ones half that
+    pub y_px: f64,
+    pub char: char,
+    /// Half-width run this character belongs to, numbered through the layout; rotate each run
+    /// as a unit. Absent for characters drawn upright
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_group: Option<u32>,
+}
+
+/// Every cell of a vertical layout and the image size it needs
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct VerticalLayout {
+    pub width_px: f64,
+    pub height_px: f64,
+    pub column_count: u32,
+    pub cells: Vec<VerticalCell>,
+}
+
+/// Lay text out in columns of max_chars_per_column half-width units
+/// The first column is at the right edge; columns are font_size_px wide with column_gap_px
+/// between them. The height fits a full column the way image_size fits a full line.
+pub fn vertical_layout(
+    text: &str,
+    max_chars_per_column: u32,
+    font_size_px: f64,
+    column_gap_px: f64,
+    padding_px: f64,
+) -> VerticalLayout {
+    let rules = KinsokuRules::new(CjkLocale::Auto.resolve(text), "", "");
+    let wrapped = justify_text_cjk_with_rules(text, max_chars_per_column, &rules);
+    let columns: Vec<&str> = if wrapped.is_empty() {
+        Vec::new()
+    } else {
+        wrapped.split("\r\n").collect()
+    };
+
+    let column_count = columns.len() as u32;
+    let width_px = 2.0 * padding_px
+        + column_count as f64 * font_size_px
+        + column_count.saturating_sub(1) as f64 * column_gap_px;
+    let height_px = 2.0 * padding_px + max_chars_per_column as f64 * column_width_px(font_size_px);
+
+    let mut cells = Vec::with_capacity(wrapped.len());
+    let mut next_group = 0;
+    for (col_index, column) in columns.iter().enumerate() {
+        let x_px = width_px
+            - padding_px
+            - (col_index + 1) as f64 * font_size_px
+            - col_index as f64 * column_gap_px;
+        let mut y_px = padding_px;
+        let mut group = None;
+
+        for (row_index, c) in column.chars().enumerate() {
+            let class = CharClass::of(c);
+            if class.is_narrow() {
+                // A run never continues into the next column: each is rotated on its own
+                group.get_or_insert_with(|| {
+                    next_group += 1;
+                    next_group - 1
+                });
+            } else {
+                group = None;
+            }
+
+            cells.push(VerticalCell {
+                col_index: col_index as u32,
+                row_index: row_index as u32,
+                x_px,
+                y_px,
+                char: c,
+                rotation_group: group,
+            });
+            y_px += class.width as f64 * column_width_px(font_size_px);
+        }
+    }
+
+    VerticalLayout {
+        width_px,
+        height_px,
+        column_count,
+        cells,
+    }
+}
+
+#[cfg(feature = "json")]
+/// Vertical layout of text, columns right to left; see vertical_layout
+/// Returns JSON {widthPx, heightPx, columnCount, cells: [{colIndex, rowIndex, xPx, yPx, char,
+/// rotationGroup?}]}
+pub fn layout_vertical(
+    text: &str,
+    max_chars_per_column: u32,
+    font_size_px: f64,
+    column_gap_px: f64,
+    padding_px: f64,
+) -> String {
+    let layout = vertical_layout(
+        text,
+        max_chars_per_column,
+        font_size_px,
+        column_gap_px,
+        padding_px,
+    );
+    serde_json::to_string(&layout).unwrap_or_else(|_| "{}".to_string())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The characters of each column, rightmost first
+    fn columns(layout: &VerticalLayout) -> Vec<String> {
+        let mut columns = vec![String::new(); layout.column_count as usize];
+        for cell in &layout.cells {
+            columns[cell.col_index as usize].push(cell.char);
+        }
+        columns
+    }
+
+    #[test]
+    fn the_first_character_is_at_the_top_of_the_rightmost_column() {
+        let layout = vertical_layout("这是一段文字。后面", 8, 20.0, 10.0, 5.0);
+        assert_eq!(columns(&layout), ["这是一段", "文字。后", "面"]);
+        // Three 20 px columns, two 10 px gaps and 5 px of padding each side
+        assert_eq!(layout.width_px, 90.0);
+        assert_eq!(layout.height_px, 2.0 * 5.0 + 8.0 * 10.0);
+        let first = &layout.cells[0];
+        assert_eq!((first.char, first.col_index, first.row_index), ('这', 0, 0));
+        assert_eq!((first.x_px, first.y_px), (65.0, 5.0));
+        let xs: Vec<f64> = layout.cells.iter().map(|cell| cell.x_px).collect();
+        assert!(xs.windows(2).all(|pair| pair[1] <= pair[0]), "{:?}", xs);
+        let last = layout.cells.last().unwrap();
+        assert_eq!((last.char, last.x_px, last.y_px), ('面', 5.0, 5.0));
+    }
+
+    #[test]
+    fn half_width_runs_are_rotation_groups() {
+        let layout = vertical_layout("中abc文de", 20, 20.0, 10.0, 5.0);
+        let groups: Vec<Option<u32>> = layout.cells.iter().map(|c| c.rotation_group).collect();
+        assert_eq!(
+            groups,
+            [None, Some(0), Some(0), Some(0), None, Some(1), Some(1)]
+        );
+        // Half-width characters advance half as far down the column
+        let ys: Vec<f64> = layout.cells.iter().map(|cell| cell.y_px).collect();
+        assert_eq!(ys, [5.0, 25.0, 35.0, 45.0, 55.0, 75.0, 85.0]);
+    }
+
+    #[test]
+    fn a_run_split_across_columns_is_a_group_in_each() {
+        let layout = vertical_layout("中abcdefg", 4, 20.0, 10.0, 5.0);
+        assert_eq!(columns(&layout), ["中ab", "cdef", "g"]);
+        let groups: Vec<Option<u32>> = layout.cells.iter().map(|c| c.rotation_group).collect();
+        assert_eq!(
+            groups,
+            [
+                None,
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_break_where_horizontal_lines_do() {
+        for (text, max_chars_per_column) in [
+            ("这是一段文字。", 12),
+            ("「引用」的文字，很长的一句话。", 6),
+            ("日本語のテキスト、ですね。", 8),
+        ] {
+            let layout = vertical_layout(text, max_chars_per_column, 16.0, 4.0, 0.0);
+            let horizontal =
+                crate::justify_text_cjk_locale(text, max_chars_per_column, "auto", "", "").unwrap();
+            assert_eq!(columns(&layout).join("\r\n"), horizontal, "{:?}", text);
+        }
+        // 。 can't start a column, so 字 comes down with it
+        let layout = vertical_layout("这是一段文字。", 12, 16.0, 4.0, 0.0);
+        assert_eq!(columns(&layout), ["这是一段文", "字。"]);
+    }
+
+    #[test]
+    fn no_text_is_only_padding() {
+        let layout = vertical_layout("", 4, 20.0, 10.0, 5.0);
+        assert_eq!(layout.column_count, 0);
+        assert!(layout.cells.is_empty());
+        assert_eq!((layout.width_px, layout.height_px), (10.0, 50.0));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn layout_vertical_json() {
+        assert_eq!(
+            layout_vertical("中a", 4, 20.0, 0.0, 0.0),
+            r#"{"widthPx":20.0,"heightPx":40.0,"columnCount":1,"cells":[{"colIndex":0,"rowIndex":0,"xPx":0.0,"yPx":0.0,"char":"中"},{"colIndex":0,"rowIndex":1,"xPx":0.0,"yPx":20.0,"char":"a","rotationGroup":0}]}"#
+        );
+    }
+}
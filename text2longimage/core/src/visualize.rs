@@ -0,0 +1,104 @@
+//! Whitespace made visible, for debugging spacing that looks wrong
+//! Every space, tab, ideographic space, no-break space and zero-width character becomes a
+//! symbol, and every line break gets a pilcrow in front of it. The symbols aren't as wide as
+//! what they stand for, so the result is for showing only: widths and wrap points don't carry
+//! over, and it can't be turned back into the text.
+
+/// Symbol shown in place of c, None for characters that are kept
+pub(crate) fn symbol(c: char) -> Option<char> {
+    match c {
+        ' ' => Some('·'),
+        '\t' => Some('→'),
+        '\u{3000}' => Some('□'),
+        '\u{00A0}' | '\u{202F}' => Some('⍽'),
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => Some('∅'),
+        _ => None,
+    }
+}
+
+/// The characters symbol shows as c, empty for a character that isn't a symbol
+pub(crate) fn symbolized(c: char) -> &'static [char] {
+    match c {
+        '·' => &[' '],
+        '→' => &['\t'],
+        '□' => &['\u{3000}'],
+        '⍽' => &['\u{00A0}', '\u{202F}'],
+        '∅' => &['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'],
+        _ => &[],
+    }
+}
+
+/// Marks the end of every line; the line break itself is kept
+pub(crate) const PILCROW: char = '¶';
+
+/// The text with spaces as "·", tabs as "→", U+3000 as "□", no-break spaces (U+00A0, U+202F)
+/// as "⍽", zero-width characters (U+200B–U+200D, U+2060, U+FEFF) as "∅", and "¶" before each
+/// "\n", "\r\n" or lone "\r"; everything else is unchanged
+pub fn visualize_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + text.len() / 4);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                result.push(PILCROW);
+                result.push('\n');
+            }
+            '\r' => {
+                result.push(PILCROW);
+                result.push('\r');
+                if chars.next_if_eq(&'\n').is_some() {
+                    result.push('\n');
+                }
+            }
+            _ => result.push(symbol(c).unwrap_or(c)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_whitespace_character_has_its_symbol() {
+        for (text, expected) in [
+            (" ", "·"),
+            ("\t", "→"),
+            ("\u{3000}", "□"),
+            ("\u{00A0}", "⍽"),
+            ("\u{202F}", "⍽"),
+            ("\u{200B}", "∅"),
+            ("\u{200D}", "∅"),
+            ("\u{FEFF}", "∅"),
+            ("\n", "¶\n"),
+            ("\r\n", "¶\r\n"),
+            ("\r", "¶\r"),
+        ] {
+            assert_eq!(visualize_whitespace(text), expected, "{text:?}");
+        }
+        assert_eq!(
+            visualize_whitespace("a b\tc\u{3000}d\r\n\ne"),
+            "a·b→c□d¶\r\n¶\ne"
+        );
+    }
+
+    #[test]
+    fn text_without_whitespace_is_unchanged() {
+        for text in ["", "abc", "中文テキスト", "👩\u{1F3FD}", "e\u{301}", "·→¶"] {
+            assert_eq!(visualize_whitespace(text), text, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn symbolized_undoes_symbol() {
+        for c in [
+            ' ', '\t', '\u{3000}', '\u{00A0}', '\u{202F}', '\u{200C}', '\u{2060}',
+        ] {
+            let symbol = symbol(c).unwrap();
+            assert!(symbolized(symbol).contains(&c), "{c:?}");
+        }
+        assert_eq!(symbol('a'), None);
+        assert!(symbolized('a').is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+//! Warnings for what JustifyOptions did on its own instead of failing
+//! Where the input can't be wrapped as asked, justify makes a best effort: a word wider than
+//! the line overflows it, a run past maxUnbreakableRun is cut, a character repeated past
+//! maxCharRepeat is cut, surplus combining marks are dropped, an unclosed ruby bracket is left as text, a footer too wide is cut, an option another
+//! one overrides is ignored. justify_with_warnings reports each of them next to the unchanged
+//! result, so a UI can annotate the spots. Every code is a WarningCode; a code is reported at
+//! most MAX_WARNINGS_PER_CODE times.
+
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::code_spans::{has_span, span_words};
+use crate::joiners::BOM;
+use crate::lists::hanging_item;
+use crate::marks::{is_invisible_line, mark_runs};
+use crate::metrics::{unbreakable_spans, ColumnWidths, LineBreaking};
+use crate::numbering::text_width_with_gutter;
+use crate::repeats::repeat_runs;
+use crate::ruby::ruby_spans;
+use crate::table::astral_aware_width;
+use crate::{
+    is_cjk, limit_repeats, width_of_str, AlgorithmVersion, JustifyOptions, OptionWarning,
+    RubySyntax,
+};
+
+/// Most warnings of one code a text gets; the first ones are the ones reported
+pub const MAX_WARNINGS_PER_CODE: usize = 100;
+
+/// What a Warning is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningCode {
+    /// An option has no effect because another one overrides it (see OPTION_CONFLICTS)
+    OptionIgnored,
+    /// A word wider than the line overflows it
+    TokenOverflow,
+    /// A word wider than maxUnbreakableRun was cut into lines
+    RunCut,
+    /// A run of one character past maxCharRepeat was cut to maxCharRepeat
+    RepeatTruncated,
+    /// Combining marks past maxMarksPerCluster were dropped from a character
+    MarksDropped,
+    /// A line of nothing but invisible characters was emptied (see limit_marks)
+    InvisibleLineEmptied,
+    /// A ruby bracket 《 without its 》 (or without a base) was left as text
+    RubyUnclosed,
+    /// The footer was cut with an ellipsis to fit the line
+    FooterTruncated,
+}
+
+impl WarningCode {
+    /// Every code, in the order justify_with_warnings reports them
+    pub const ALL: &'static [WarningCode] = &[
+        WarningCode::OptionIgnored,
+        WarningCode::TokenOverflow,
+        WarningCode::RunCut,
+        WarningCode::RepeatTruncated,
+        WarningCode::MarksDropped,
+        WarningCode::InvisibleLineEmptied,
+        WarningCode::RubyUnclosed,
+        WarningCode::FooterTruncated,
+    ];
+
+    /// Stable machine-readable code, e.g. "TOKEN_OVERFLOW"
+    pub fn code(self) -> &'static str {
+        match self {
+            WarningCode::OptionIgnored => "OPTION_IGNORED",
+            WarningCode::TokenOverflow => "TOKEN_OVERFLOW",
+            WarningCode::RunCut => "RUN_CUT",
+            WarningCode::RepeatTruncated => "REPEAT_TRUNCATED",
+            WarningCode::MarksDropped => "MARKS_DROPPED",
+            WarningCode::InvisibleLineEmptied => "INVISIBLE_LINE_EMPTIED",
+            WarningCode::RubyUnclosed => "RUBY_UNCLOSED",
+            WarningCode::FooterTruncated => "FOOTER_TRUNCATED",
+        }
+    }
+
+    /// Index in ALL
+    fn rank(self) -> usize {
+        WarningCode::ALL
+            .iter()
+            .position(|&code| code == self)
+            .unwrap_or(0)
+    }
+}
+
+impl fmt::Display for WarningCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl Serialize for WarningCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for WarningCode {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "WarningCode".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let codes: Vec<&str> = WarningCode::ALL.iter().map(|code| code.code()).collect();
+        schemars::json_schema!({ "type": "string", "enum": codes })
+    }
+}
+
+/// One best-effort fallback justify took
+/// line_index and char_index locate it in the input (after stripAnsi and normalizeNfc): the
+/// line, split at '\n', and the char offset within that line. Past a REPEAT_TRUNCATED on the
+/// same line, char_index counts in the cut line, which is what gets wrapped. They are absent
+/// for warnings about the options or the footer; field and overridden_by are set for
+/// OPTION_IGNORED only.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub char_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overridden_by: Option<&'static str>,
+}
+
+impl Warning {
+    fn at(code: WarningCode, message: String, line_index: usize, char_index: usize) -> Self {
+        Warning {
+            code,
+            message,
+            line_index: Some(line_index),
+            char_index: Some(char_index),
+            field: None,
+            overridden_by: None,
+        }
+    }
+}
+
+impl From<OptionWarning> for Warning {
+    fn from(warning: OptionWarning) -> Self {
+        Warning {
+            code: WarningCode::OptionIgnored,
+            message: warning.message.to_string(),
+            line_index: None,
+            char_index: None,
+            field: Some(warning.field),
+            overridden_by: Some(warning.overridden_by),
+        }
+    }
+}
+
+/// Char offset of byte offset within line
+fn char_index(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count()
+}
+
+/// Words the word wrapper can't break: on every line without CJK, (line index, char index,
+/// word, whether it holds one of the unbreakable spans breaking asks for), such a span being
+/// one word
+fn words<'t>(text: &'t str, breaking: &LineBreaking) -> Vec<(usize, usize, &'t str, bool)> {
+    let mut words = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        if is_cjk(line) {
+            continue;
+        }
+        let spans = unbreakable_spans(line, breaking);
+        let line_words: Vec<&str> = if spans.is_empty() {
+            line.split(char::is_whitespace)
+                .filter(|word| !word.is_empty())
+                .collect()
+        } else {
+            span_words(line, &spans)
+                .into_iter()
+                .map(|(_, word)| word)
+                .collect()
+        };
+        for word in line_words {
+            let offset = word.as_ptr() as usize - line.as_ptr() as usize;
+            let code = has_span(line, word, &spans);
+            words.push((index, char_index(line, offset), word, code));
+        }
+    }
+    words
+}
+
+/// Width of word as the word wrapper measures it: by byte length, less three for each
+/// narrow astral letter from algorithm version 3 on, and from version 4 only its widest
+/// piece between breakAfterChars unless it holds an unbreakable span; from version 6 on a BOM
+/// adds nothing
+fn word_width(options: &JustifyOptions, word: &str, code: bool) -> usize {
+    let version = options.algorithm();
+    let width = |piece: &str| {
+        let narrow_astral = if version >= AlgorithmVersion::V3 {
+            piece
+                .chars()
+                .filter(|&c| c as u32 > 0xFFFF && astral_aware_width(c) == 1)
+                .count()
+        } else {
+            0
+        };
+        // From version 6 on a BOM takes no room
+        let boms = if version >= AlgorithmVersion::V6 {
+            piece.matches(BOM).count()
+        } else {
+            0
+        };
+        piece.len() - 3 * narrow_astral - BOM.len_utf8() * boms
+    };
+    if version >= AlgorithmVersion::V4 && !code && !options.break_after_chars.is_empty() {
+        word.split_inclusive(|c| options.break_after_chars.contains(c))
+            .map(width)
+            .max()
+            .unwrap_or(0)
+    } else {
+        width(word)
+    }
+}
+
+/// TOKEN_OVERFLOW and RUN_CUT for the words of text
+fn word_warnings(options: &JustifyOptions, text: &str) -> Vec<Warning> {
+    let max = if options.line_numbers {
+        text_width_with_gutter(text, options.max_chars_per_line, options.number_start)
+    } else {
+        options.max_chars_per_line
+    } as usize;
+    // A hanging list item's words wrap in what its prefix leaves of the line
+    let line_max: Vec<usize> = text
+        .split('\n')
+        .map(|line| {
+            let indent = options
+                .hanging_indent
+                .then(|| {
+                    hanging_item(
+                        line,
+                        options.list_marker_spacing,
+                        max as f64 / 2.0,
+                        &ColumnWidths,
+                    )
+                })
+                .flatten()
+                .map_or(0, |item| item.indent.len());
+            max - indent
+        })
+        .collect();
+    let run = options.max_unbreakable_run as usize;
+    let mut warnings = Vec::new();
+    let breaking = LineBreaking {
+        code_spans: options.markdown && options.algorithm() >= AlgorithmVersion::V5,
+        bom_joiner: options.algorithm() >= AlgorithmVersion::V6,
+        ..LineBreaking::default()
+    };
+    for (line, column, word, code) in words(text, &breaking) {
+        let width = word_width(options, word, code);
+        let max = line_max[line];
+        if width <= max {
+            continue;
+        }
+        warnings.push(if run > 0 && width > run {
+            Warning::at(
+                WarningCode::RunCut,
+                format!(
+                    "A {}-column word was cut into lines (maxUnbreakableRun is {})",
+                    width, run
+                ),
+                line,
+                column,
+            )
+        } else {
+            Warning::at(
+                WarningCode::TokenOverflow,
+                format!(
+                    "A {}-column word is wider than the {}-column line and overflows it",
+                    width, max
+                ),
+                line,
+                column,
+            )
+        });
+    }
+    warnings
+}
+
+/// REPEAT_TRUNCATED for every run limit_repeats cuts
+fn repeat_warnings(options: &JustifyOptions, text: &str) -> Vec<Warning> {
+    let max = options.max_char_repeat as usize;
+    let mut warnings = Vec::new();
+    if max == 0 {
+        return warnings;
+    }
+    for (index, line) in text.split('\n').enumerate() {
+        for (offset, _, count) in repeat_runs(line)
+            .into_iter()
+            .filter(|&(_, _, count)| count > max)
+        {
+            warnings.push(Warning::at(
+                WarningCode::RepeatTruncated,
+                format!(
+                    "A character repeated {} times in a row was cut to {} (maxCharRepeat is {})",
+                    count, max, max
+                ),
+                index,
+                char_index(line, offset),
+            ));
+        }
+    }
+    warnings
+}
+
+/// MARKS_DROPPED and INVISIBLE_LINE_EMPTIED, as limit_marks finds them
+fn mark_warnings(options: &JustifyOptions, text: &str) -> Vec<Warning> {
+    let max = options.max_marks_per_cluster as usize;
+    let mut warnings = Vec::new();
+    if max == 0 {
+        return warnings;
+    }
+    for (index, line) in text.split('\n').enumerate() {
+        let body = line.strip_suffix('\r').unwrap_or(line);
+        if is_invisible_line(body) {
+            warnings.push(Warning::at(
+                WarningCode::InvisibleLineEmptied,
+                "A line of only invisible characters was emptied".to_string(),
+                index,
+                0,
+            ));
+            continue;
+        }
+        for (offset, marks) in mark_runs(body)
+            .into_iter()
+            .filter(|&(_, marks)| marks > max)
+        {
+            warnings.push(Warning::at(
+                WarningCode::MarksDropped,
+                format!(
+                    "{} of {} combining marks on one character were dropped \
+                     (maxMarksPerCluster is {})",
+                    marks - max,
+                    marks,
+                    max
+                ),
+                index,
+                char_index(body, offset),
+            ));
+        }
+    }
+    warnings
+}
+
+/// RUBY_UNCLOSED for every 《 that doesn't open an annotation
+fn ruby_warnings(options: &JustifyOptions, text: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if options.ruby_syntax == RubySyntax::None {
+        return warnings;
+    }
+    for (index, line) in text.split('\n').enumerate() {
+        let spans = ruby_spans(line, options.ruby_syntax);
+        for (offset, _) in line.match_indices('《') {
+            if !spans.iter().any(|span| span.range.contains(&offset)) {
+                warnings.push(Warning::at(
+                    WarningCode::RubyUnclosed,
+                    "A ruby bracket 《 has no closing 》 or no base and was kept as text"
+                        .to_string(),
+                    index,
+                    char_index(line, offset),
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+impl JustifyOptions {
+    /// Every fallback justify takes on text, at most MAX_WARNINGS_PER_CODE of each code
+    pub fn warnings(&self, text: &str) -> Vec<Warning> {
+        // A signature is written as it is, so nothing the wrapper does happens to it
+        let (text, _) = self.split_signature(text);
+        let prepared = self.prepare(text);
+        let mut warnings: Vec<Warning> = self
+            .option_warnings()
+            .into_iter()
+            .map(Warning::from)
+            .collect();
+        warnings.extend(repeat_warnings(self, &prepared));
+        // The rest is about the cut text, which is what gets wrapped
+        let limited = limit_repeats(&prepared, self.max_char_repeat);
+        warnings.extend(word_warnings(self, &limited));
+        warnings.extend(mark_warnings(self, &limited));
+        warnings.extend(ruby_warnings(self, &limited));
+        if width_of_str(&self.footer_text) > self.max_chars_per_line as u64 {
+            warnings.push(Warning {
+                code: WarningCode::FooterTruncated,
+                message: format!(
+                    "The footer is wider than {} columns and was cut",
+                    self.max_chars_per_line
+                ),
+                line_index: None,
+                char_index: None,
+                field: None,
+                overridden_by: None,
+            });
+        }
+
+        // Stable, so each code's warnings stay in text order
+        warnings.sort_by_key(|warning| warning.code.rank());
+        let mut seen = [0; WarningCode::ALL.len()];
+        warnings.retain(|warning| {
+            let count = &mut seen[warning.code.rank()];
+            *count += 1;
+            *count <= MAX_WARNINGS_PER_CODE
+        });
+        warnings
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    /// Options and a text taking eight fallbacks
+    const OPTIONS: &str = r#"{"maxCharsPerLine": 10, "maxUnbreakableRun": 20, "rubySyntax": "kanji",
+        "maxMarksPerCluster": 2, "footerText": "a footer far too wide", "markdown": true,
+        "trimTrailing": false, "maxCharRepeat": 3}"#;
+    const TEXT: &str =
+        "overflowing1234\nabcdefghijklmnopqrstuvwxyz\nno!!!!!!\ne\u{301}\u{301}\u{301}\n\u{200B}\n\
+        漢字《かんじ";
+
+    #[test]
+    fn every_fallback_is_reported_where_it_happened() {
+        let options = JustifyOptions::from_json(OPTIONS).unwrap();
+        let justified = options.justify_with_warnings(TEXT);
+        assert_eq!(justified.text, options.justify(TEXT));
+        let reported: Vec<(&str, Option<usize>, Option<usize>)> = justified
+            .warnings
+            .iter()
+            .map(|warning| (warning.code.code(), warning.line_index, warning.char_index))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("OPTION_IGNORED", None, None),
+                ("TOKEN_OVERFLOW", Some(0), Some(0)),
+                ("RUN_CUT", Some(1), Some(0)),
+                ("REPEAT_TRUNCATED", Some(2), Some(2)),
+                ("MARKS_DROPPED", Some(3), Some(1)),
+                ("INVISIBLE_LINE_EMPTIED", Some(4), Some(0)),
+                ("RUBY_UNCLOSED", Some(5), Some(2)),
+                ("FOOTER_TRUNCATED", None, None),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&justified.warnings[1]).unwrap(),
+            serde_json::json!({
+                "code": "TOKEN_OVERFLOW",
+                "message": "A 15-column word is wider than the 10-column line and overflows it",
+                "lineIndex": 0,
+                "charIndex": 0
+            })
+        );
+    }
+
+    #[test]
+    fn plain_text_gets_no_warnings() {
+        let options = JustifyOptions::default();
+        assert!(options
+            .justify_with_warnings("中文 and English")
+            .warnings
+            .is_empty());
+    }
+}
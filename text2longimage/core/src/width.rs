@@ -0,0 +1,242 @@
+//! The width contract: how many columns a character takes, for everything that measures text
+//! Stats, the wrappers, layout, alignment and truncation all measure through width_of, so a
+//! line is as wide in get_text_stats as the wrapper thought and layout_text draws it. Columns
+//! are half an em: 1 for U+0000–U+00FF, 2 for everything else. The only other width is
+//! algorithm V3's, which makes the narrow astral letters 1 column and is pinned by version.
+
+use crate::budget::{extends, graphemes};
+use crate::clean::is_zero_width;
+use crate::emoji::{takes_presentation_selector, VS15, VS16};
+use crate::{table, Align, CountingMode};
+
+/// Width of c in columns
+#[inline]
+pub(crate) fn width_of(c: char) -> u32 {
+    table::width(c)
+}
+
+/// Width of c as a platform draws it before next: VS16 (U+FE0F) after a character it applies
+/// to makes it an emoji 2 columns wide, VS15 (U+FE0E) text 1 column wide, and the selectors
+/// themselves take none
+/// For a frontend measuring glyph by glyph; everything in the width contract above, the
+/// wrappers included, counts a selector as a character of its own.
+pub fn get_char_width_presented(c: char, next: Option<char>) -> u32 {
+    match (c, next) {
+        (VS15 | VS16, _) => 0,
+        (c, Some(VS16)) if takes_presentation_selector(c) => 2,
+        (c, Some(VS15)) if takes_presentation_selector(c) => 1,
+        (c, _) => width_of(c),
+    }
+}
+
+/// Width of text in columns: width_of summed over its characters, ASCII runs a word at a time
+pub(crate) fn width_of_str(text: &str) -> u64 {
+    let ascii_len = crate::ascii_prefix_len(text.as_bytes());
+    let rest: u64 = text[ascii_len..].chars().map(|c| width_of(c) as u64).sum();
+    ascii_len as u64 + rest
+}
+
+/// The longest prefix of text (whole characters) at most max_columns wide
+pub fn truncate_to_width(text: &str, max_columns: u64) -> &str {
+    let mut width = 0;
+    text.char_indices()
+        .find(|&(_, c)| {
+            width += width_of(c) as u64;
+            width > max_columns
+        })
+        .map_or(text, |(index, _)| &text[..index])
+}
+
+/// The graphemes of text as (byte start, byte end, columns), the way truncate_to_budget's
+/// displayWidth count splits them; zero-width characters (joiners, ZWSP, the BOM, combining
+/// marks and presentation selectors) take no columns, everything else width_of's
+fn grapheme_widths(text: &str) -> Vec<(usize, usize, u64)> {
+    let mut start = 0;
+    graphemes(text, CountingMode::Chars)
+        .into_iter()
+        .map(|(end, _)| {
+            let columns = text[start..end]
+                .chars()
+                .filter(|&c| !extends(c) && !is_zero_width(c))
+                .map(|c| width_of(c) as u64)
+                .sum();
+            let grapheme = (start, end, columns);
+            start = end;
+            grapheme
+        })
+        .collect()
+}
+
+/// Byte length of the longest prefix of text, in whole graphemes, at most max_width columns
+/// wide
+fn width_prefix_end(text: &str, max_width: u32) -> usize {
+    let mut width = 0;
+    grapheme_widths(text)
+        .into_iter()
+        .take_while(|&(_, _, columns)| {
+            width += columns;
+            width <= u64::from(max_width)
+        })
+        .last()
+        .map_or(0, |(_, end, _)| end)
+}
+
+/// The longest prefix of text at most max_width columns wide, cut between graphemes
+/// An emoji sequence or a character with its marks is kept or left out whole, a wide
+/// character that would go past max_width is left out rather than half kept, and zero-width
+/// characters take no columns, so ones right after the prefix's last grapheme come with it.
+pub fn width_prefix(text: &str, max_width: u32) -> &str {
+    &text[..width_prefix_end(text, max_width)]
+}
+
+/// Length of width_prefix(text, max_width) in chars (code points)
+pub fn width_prefix_len(text: &str, max_width: u32) -> u32 {
+    text[..width_prefix_end(text, max_width)].chars().count() as u32
+}
+
+/// The longest suffix of text at most max_width columns wide, cut between graphemes as
+/// width_prefix cuts, for truncating from the left
+pub fn width_suffix(text: &str, max_width: u32) -> &str {
+    let mut width = 0;
+    let start = grapheme_widths(text)
+        .into_iter()
+        .rev()
+        .take_while(|&(_, _, columns)| {
+            width += columns;
+            width <= u64::from(max_width)
+        })
+        .last()
+        .map_or(text.len(), |(start, _, _)| start);
+    &text[start..]
+}
+
+/// text moved within a line columns wide by leading spaces, as the footer is
+/// Nothing is added after the text, and text at least that wide is returned as it is.
+pub fn align_to_width(text: &str, columns: u64, align: Align) -> String {
+    let free = columns.saturating_sub(width_of_str(text));
+    let padding = match align {
+        Align::Left => 0,
+        Align::Center => free / 2,
+        Align::Right => free,
+    };
+    " ".repeat(padding as usize) + text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        calculate_text_width, fits_in_width, get_char_width, justify_text, line_widths, TextStats,
+    };
+
+    /// A text, a width, and width_prefix and width_suffix of the text at that width: budgets just
+    /// before, on and just after a wide character and an emoji cluster, and zero-width characters
+    const WIDTH_PREFIX_EXAMPLES: [(&str, u32, &str, &str); 13] = [
+        ("ab漢cd", 2, "ab", "cd"),
+        ("ab漢cd", 3, "ab", "cd"),
+        ("ab漢cd", 4, "ab漢", "漢cd"),
+        ("ab漢cd", 5, "ab漢c", "b漢cd"),
+        ("a👍🏽b", 1, "a", "b"),
+        ("a👍🏽b", 4, "a", "b"),
+        ("a👍🏽b", 5, "a👍🏽", "👍🏽b"),
+        ("a👍🏽b", 6, "a👍🏽b", "a👍🏽b"),
+        ("👨\u{200D}👩\u{200D}👧!", 5, "", "!"),
+        ("👨\u{200D}👩\u{200D}👧!", 6, "👨\u{200D}👩\u{200D}👧", "!"),
+        ("cafe\u{301}s", 4, "cafe\u{301}", "afe\u{301}s"),
+        ("ab\u{200B}cd", 2, "ab\u{200B}", "\u{200B}cd"),
+        ("", 3, "", ""),
+    ];
+
+    #[test]
+    fn prefixes_and_suffixes_cut_between_graphemes() {
+        for (text, width, prefix, suffix) in WIDTH_PREFIX_EXAMPLES {
+            assert_eq!(width_prefix(text, width), prefix, "{text:?} at {width}");
+            assert_eq!(
+                width_prefix_len(text, width) as usize,
+                prefix.chars().count(),
+                "{text:?} at {width}"
+            );
+            assert_eq!(width_suffix(text, width), suffix, "{text:?} at {width}");
+        }
+    }
+
+    #[test]
+    fn selectors_pick_the_presentation_width() {
+        for (text, widths) in [
+            ("\u{263A}\u{FE0E}", [1, 0]),
+            ("\u{263A}\u{FE0F}", [2, 0]),
+            ("\u{2460}\u{FE0E}", [2, 0]),
+            ("a\u{FE0F}", [1, 0]),
+            ("1\u{FE0F}", [2, 0]),
+        ] {
+            let chars: Vec<char> = text.chars().collect();
+            let measured = [
+                get_char_width_presented(chars[0], Some(chars[1])),
+                get_char_width_presented(chars[1], None),
+            ];
+            assert_eq!(measured, widths, "{text:?}");
+        }
+    }
+
+    /// What the width corpus is built from: ASCII, CJK, emoji (one with a ZWJ sequence),
+    /// combining marks and fullwidth forms, plus the spaces and breaks between them
+    const WIDTH_PIECES: [&str; 12] = [
+        "word",
+        " ",
+        "\n",
+        "漢字かな",
+        "😀",
+        "👨\u{200D}👩\u{200D}👧",
+        "e\u{0301}",
+        "\u{0300}",
+        "ＡＢＣ１２",
+        "\u{3000}",
+        "https://example.com/a-b",
+        "straße",
+    ];
+
+    /// Every piece, every pair of pieces, and all of them in a row
+    fn width_corpus() -> Vec<String> {
+        let pieces = WIDTH_PIECES.iter();
+        let pairs = pieces
+            .clone()
+            .flat_map(|a| WIDTH_PIECES.iter().map(move |b| format!("{a}{b}")));
+        pieces
+            .map(|piece| piece.to_string())
+            .chain(pairs)
+            .chain([WIDTH_PIECES.concat(), WIDTH_PIECES.join(" ")])
+            .collect()
+    }
+
+    #[test]
+    fn every_api_measures_the_corpus_as_the_wrapper_does() {
+        for text in width_corpus() {
+            for max_chars_per_line in [1, 2, 3, 5, 8, 13, 21, 40] {
+                let wrapped = justify_text(&text, max_chars_per_line);
+                let lines: Vec<&str> = match wrapped.as_str() {
+                    "" => Vec::new(),
+                    wrapped => wrapped.split("\r\n").collect(),
+                };
+                let widths: Vec<u64> = lines
+                    .iter()
+                    .map(|line| calculate_text_width(line))
+                    .collect();
+                let at = format!("{text:?} at {max_chars_per_line}");
+                assert_eq!(line_widths(&wrapped), widths, "line_widths of {at}");
+                let columns = u64::from(max_chars_per_line);
+                for (line, &width) in lines.iter().zip(&widths) {
+                    let chars: u64 = line.chars().map(|c| u64::from(get_char_width(c))).sum();
+                    assert_eq!(chars, width, "get_char_width over {line:?} of {at}");
+                    assert_eq!(TextStats::of(line).display_width as u64, width, "{line:?}");
+                    assert!(
+                        width <= columns || !line.trim().contains(char::is_whitespace),
+                        "{line:?} of {at} is {width} columns"
+                    );
+                    assert_eq!(truncate_to_width(line, width), *line, "{line:?} of {at}");
+                }
+                let one_line = lines.len() <= 1 && widths.iter().all(|&width| width <= columns);
+                assert_eq!(fits_in_width(&text, max_chars_per_line), one_line, "{at}");
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Word segmentation in the spirit of UAX #29, hand-rolled rather than table-driven
+//! Close enough for word counts: letters and digits form words, joined across an internal
+//! apostrophe, hyphen or period ("don't", "state-of-the-art", "U.S") and digits across
+//! separators ("3.14", "1,000"); each Han character is its own word, as is each run of
+//! hiragana or of katakana. Combining marks stay with the character before them. Punctuation,
+//! symbols (including emoji) and whitespace are skipped.
+
+use crate::is_cjk_char;
+use crate::table::{in_ranges, COMBINING_RANGES};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Han,
+    Hiragana,
+    Katakana,
+    /// Letters and digits outside the CJK scripts
+    Alphanumeric,
+    /// Combining marks and kana voicing marks
+    Extend,
+    Other,
+}
+
+fn kind(c: char) -> Kind {
+    match c as u32 {
+        0x3099..=0x309A => Kind::Extend,
+        0x3041..=0x309F => Kind::Hiragana,
+        // ・ separates katakana words; ー lengthens either kana script
+        0x30FB => Kind::Other,
+        0x30A0..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Kind::Katakana,
+        _ if in_ranges(COMBINING_RANGES, c) => Kind::Extend,
+        _ if is_cjk_char(c) => Kind::Han,
+        _ if c.is_alphanumeric() => Kind::Alphanumeric,
+        _ => Kind::Other,
+    }
+}
+
+/// Characters that keep a word going when they sit between two of its characters
+fn joins(joiner: char, before: char, after: char) -> bool {
+    match joiner {
+        '\'' | '’' | '-' | '‐' | '.' => true,
+        ',' | '٫' | '٬' => before.is_numeric() && after.is_numeric(),
+        _ => false,
+    }
+}
+
+/// Iterator over the words of a text, as slices of it
+pub struct Words<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let text = self.rest;
+        let mut chars = text.char_indices().peekable();
+
+        // Skip to the first character that starts a word
+        let (start, first_kind) = loop {
+            let Some((offset, c)) = chars.next() else {
+                self.rest = "";
+                return None;
+            };
+            match kind(c) {
+                Kind::Other | Kind::Extend => continue,
+                kind => break (offset, kind),
+            }
+        };
+
+        let mut end = text.len();
+        let mut last = text[start..].chars().next().unwrap_or(' ');
+        while let Some(&(offset, c)) = chars.peek() {
+            let continues = match kind(c) {
+                Kind::Extend => true,
+                // ー after hiragana (らーめん) as well as after katakana
+                Kind::Katakana if c == 'ー' => first_kind != Kind::Han,
+                Kind::Han => false,
+                k if k == first_kind => first_kind != Kind::Han,
+                _ if first_kind == Kind::Alphanumeric && kind(last) == Kind::Alphanumeric => {
+                    let after = text[offset + c.len_utf8()..].chars().next();
+                    after.is_some_and(|a| kind(a) == Kind::Alphanumeric && joins(c, last, a))
+                }
+                _ => false,
+            };
+            if !continues {
+                end = offset;
+                break;
+            }
+            last = c;
+            chars.next();
+        }
+
+        self.rest = &text[end..];
+        Some(&text[start..end])
+    }
+}
+
+/// Words of text in order; see the module notes for what counts as a word
+pub fn words(text: &str) -> Words<'_> {
+    Words { rest: text }
+}
+
+/// split_words collected
+pub fn split_words(text: &str) -> Vec<&str> {
+    words(text).collect()
+}
+
+/// Number of words split_words finds, without collecting them
+pub fn count_words(text: &str) -> usize {
+    words(text).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin_words_keep_their_joiners() {
+        assert_eq!(
+            split_words("Don't say \"state-of-the-art\" — it’s 3.14, or 1,000 U.S. dollars!"),
+            [
+                "Don't",
+                "say",
+                "state-of-the-art",
+                "it’s",
+                "3.14",
+                "or",
+                "1,000",
+                "U.S",
+                "dollars"
+            ]
+        );
+        // A joiner at the edge of a word, or doubled, joins nothing
+        assert_eq!(
+            split_words("'quoted' well-- -x end."),
+            ["quoted", "well", "x", "end"]
+        );
+        // A comma only joins digits
+        assert_eq!(split_words("a,b 1,2"), ["a", "b", "1,2"]);
+        assert_eq!(split_words("cafe\u{301} naïve"), ["cafe\u{301}", "naïve"]);
+    }
+
+    #[test]
+    fn japanese_sentence() {
+        // Each kanji is a word and so is each hiragana run, okurigana and particles included
+        assert_eq!(
+            split_words("私はラーメンとらーめんが好きです。コーヒー・ティー"),
+            [
+                "私",
+                "は",
+                "ラーメン",
+                "とらーめんが",
+                "好",
+                "きです",
+                "コーヒー",
+                "ティー"
+            ]
+        );
+        // A voicing mark stays with its kana
+        assert_eq!(split_words("か\u{3099}き"), ["か\u{3099}き"]);
+        assert_eq!(
+            split_words("中文 text 混排"),
+            ["中", "文", "text", "混", "排"]
+        );
+    }
+
+    #[test]
+    fn punctuation_symbols_and_whitespace_are_skipped() {
+        assert_eq!(split_words(""), Vec::<&str>::new());
+        assert_eq!(split_words(" \t\n!? 😀 — … \u{301}"), Vec::<&str>::new());
+        assert_eq!(count_words("one, two; three 四五"), 5);
+    }
+
+    #[test]
+    fn text_stats_count_the_same_words() {
+        for text in crate::tests::random_texts(200) {
+            assert_eq!(
+                crate::TextStats::of(&text).word_count,
+                count_words(&text),
+                "{:?}",
+                text
+            );
+            assert_eq!(count_words(&text), split_words(&text).len());
+        }
+    }
+}
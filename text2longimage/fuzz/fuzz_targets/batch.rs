@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use text_processor_fuzz::{check_batch, FuzzInput};
+
+fuzz_target!(|data: &[u8]| {
+    check_batch(&FuzzInput::from_bytes(data));
+});
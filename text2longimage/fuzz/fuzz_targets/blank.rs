@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use text_processor_fuzz::{blank_text, check_blank};
+
+fuzz_target!(|data: &[u8]| {
+    check_blank(&blank_text(data));
+});
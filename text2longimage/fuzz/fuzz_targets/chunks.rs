@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use text_processor_fuzz::{check_chunks, FuzzInput};
+
+fuzz_target!(|data: &[u8]| {
+    check_chunks(&FuzzInput::from_bytes(data));
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use text_processor_fuzz::{check_width, width_text};
+
+// The first byte picks the width, each later one a piece of the text
+fuzz_target!(|data: &[u8]| {
+    let max_chars_per_line = u32::from(data.first().copied().unwrap_or(0) % 64) + 1;
+    check_width(
+        &width_text(data.get(1..).unwrap_or_default()),
+        max_chars_per_line,
+    );
+});
@@ -0,0 +1,2789 @@
+//! Invariants the fuzz targets check against the public API
+//! Every check panics on a violation, which is what libFuzzer reports as a crash; the same
+//! input bytes replay the failure with `cargo fuzz run <target> <artifact>`.
+
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::OnceLock;
+
+use text_processor_core::{
+    align_to_width, apply_confusable_fixes_with, apply_edits, batch_justify_text,
+    batch_justify_text_with_report, break_opportunities, break_opportunities_json, break_positions,
+    calculate_text_width, calibrate_chunk_cost, capabilities, char_advances_lines_with_spacing,
+    char_advances_with_spacing, chars_per_line_for_width, check_batch_size,
+    check_max_chars_per_line, class_runs, class_runs_by_line, classify_line_runs, classify_runs,
+    clean_text, clean_text_edits, clean_text_report, clean_text_with, confusables_report,
+    convert_punctuation, convert_punctuation_edits, convert_punctuation_report,
+    count_wrapped_lines, count_wrapped_lines_of_output, decode_bytes, detect_encoding,
+    detect_encoding_json, diff_texts, diff_wrap, edge_char_report, emoji_positions, escape_html,
+    estimate_image_size, estimate_image_size_for_width, estimate_image_size_with_limits,
+    find_confusables, find_in_wrapped, first_overflow_index, fit_aspect_ratio,
+    fit_aspect_ratio_with, fit_columns, fits_in_width, font_coverage_report, format_stats,
+    format_tsv, format_tsv_json, get_char_width, get_text_stats, is_cjk, is_cjk_char,
+    is_empty_input, json_schema, justify_batch_items, justify_text, justify_text_cjk,
+    justify_text_cjk_locale, justify_text_cjk_with_rules, justify_text_english,
+    justify_text_for_image, justify_text_for_image_with, justify_text_fractional,
+    justify_text_fractional_with_spacing, justify_text_html, justify_text_numbered,
+    justify_text_utf16, justify_text_with_provider, justify_text_with_spacing, justify_windows,
+    justify_windows_with_stats, kinsoku_sets, layout_columns, layout_text, layout_text_with_guides,
+    layout_text_with_limits, layout_text_with_spacing, layout_vertical, limit_marks, limit_repeats,
+    line_hygiene_report, line_widths, list_marker, max_batch_items, max_batch_size, max_input_size,
+    max_line_width, merge_short_lines, normalize_cjk_indent_with, paginate_for_images,
+    paginate_lines, paginate_with_bands, paragraph_segments, paragraph_stats, paragraphs,
+    parse_batch_json, preview_snippet, process_text_chunks, raggedness, recommended_chunk_size,
+    reflow_text, remaining_budget, self_test, set_cjk_line_height_factor,
+    set_emoji_line_height_factor, signature_start, slice_columns, split_paragraphs,
+    stats_after_append, stats_incremental_json, streamed_stats_json, strip_quote_prefix_detailed,
+    suggest_width, transform_case, transform_case_json, truncate_to_budget, truncate_to_width,
+    validate_input, validate_input_report, validate_text, visualize_whitespace, width_prefix,
+    width_prefix_len, width_suffix, wrap_to_line_count, wrapped_lines, Align, BatchId, BatchItem,
+    BuiltinWidths, CanvasLimits, CaseMode, CjkIndentMode, CjkLocale, CleanOptions, ColumnRounding,
+    ConfusablePolicy, CountingMode, Document, FontMetrics, HeightEstimator, JustifyOptions,
+    KinsokuRules, MemoizedWidths, MergeSeparator, OptionValue, OptionsReport, ParagraphKind,
+    RubySyntax, RuntimeConfig, ScriptLineHeights, SelfTestReport, Spacing, StreamStats,
+    StreamedStats, TextEdit, TextEncoding, TextStats, TsvOptions, TsvOverflow, WarningCode,
+    WebtoolsError, WidthProvider, DEFAULT_BREAK_AFTER_CHARS, DEFAULT_MAX_MARKS_PER_CLUSTER,
+    DEFAULT_SIGNATURE_DELIMITER, LIMIT_WARNING_RATIO, MAX_MAX_CHARS_PER_LINE,
+    MAX_WARNINGS_PER_CODE, MIN_CHARS_PER_LINE, SCHEMA_KINDS, SUGGEST_WIDTH_MAX_LINE_INCREASE,
+};
+
+/// Widths and chunk sizes are drawn from 0..=MAX_FUZZ_SIZE
+pub const MAX_FUZZ_SIZE: u32 = 10_000;
+
+/// Separates batch items in the fuzzed text
+const ITEM_SEPARATOR: char = '\0';
+
+/// What the blank target builds its texts from, one piece per input byte; no bytes is ""
+pub const BLANK_PIECES: [&str; 6] = [" ", "\n", "\r\n", "\u{3000}", "\t", "\r"];
+
+/// What the width target builds its texts from: ASCII, CJK, emoji (one with a ZWJ sequence),
+/// combining marks and fullwidth forms, plus the spaces and breaks between them
+pub const WIDTH_PIECES: [&str; 12] = [
+    "word",
+    " ",
+    "\n",
+    "漢字かな",
+    "😀",
+    "👨\u{200D}👩\u{200D}👧",
+    "e\u{0301}",
+    "\u{0300}",
+    "ＡＢＣ１２",
+    "\u{3000}",
+    "https://example.com/a-b",
+    "straße",
+];
+
+/// One fuzz case decoded from raw bytes
+#[derive(Debug)]
+pub struct FuzzInput {
+    pub text: String,
+    pub max_chars_per_line: u32,
+    pub chunk_size: u32,
+}
+
+impl FuzzInput {
+    /// First two bytes pick the width, the next two the chunk size; the rest is the text, with
+    /// invalid UTF-8 replaced so the wrappers always see some text
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let size = |i: usize| {
+            let bytes = [
+                data.get(i).copied().unwrap_or(0),
+                data.get(i + 1).copied().unwrap_or(0),
+            ];
+            u32::from(u16::from_le_bytes(bytes)) % (MAX_FUZZ_SIZE + 1)
+        };
+        FuzzInput {
+            text: String::from_utf8_lossy(data.get(4..).unwrap_or_default()).into_owned(),
+            max_chars_per_line: size(0),
+            chunk_size: size(2),
+        }
+    }
+}
+
+/// The characters a wrapper must keep: whitespace may be collapsed or turned into breaks
+fn visible(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn assert_no_loss(what: &str, input: &str, output: &str) {
+    assert_eq!(
+        visible(input),
+        visible(output),
+        "{what} lost or reordered characters"
+    );
+}
+
+/// Lines fit max_chars_per_line unless they hold one word (or character) too wide for any line
+fn assert_line_widths(what: &str, output: &str, max_chars_per_line: u32) {
+    for line in output.split("\r\n") {
+        let width = calculate_text_width(line);
+        assert!(
+            width <= u64::from(max_chars_per_line) || !line.trim().contains(char::is_whitespace),
+            "{what} line {line:?} is {width} columns, over the limit of {max_chars_per_line}"
+        );
+    }
+}
+
+/// justify_text, justify_text_cjk, justify_text_english and get_text_stats
+pub fn check_justify(input: &FuzzInput) {
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+
+    for (what, output) in [
+        ("justify_text", justify_text(text, width)),
+        ("justify_text_cjk", justify_text_cjk(text, width)),
+        ("justify_text_english", justify_text_english(text, width)),
+    ] {
+        assert_no_loss(what, text, &output);
+        assert_line_widths(what, &output, width);
+    }
+
+    let stats: serde_json::Value =
+        serde_json::from_str(&get_text_stats(text)).expect("get_text_stats returned invalid JSON");
+    let boms = text.matches('\u{FEFF}').count();
+    assert_eq!(stats["charCount"], text.chars().count() - boms);
+    assert_eq!(stats["bomCount"], boms);
+    assert_eq!(stats["byteCount"], text.len());
+
+    check_visualize(text);
+    check_unbreakable_runs(input);
+    check_stats_after_append(input);
+    check_preserve_separators(input);
+    check_fit(input);
+    check_variation_selectors(text);
+    check_huge_width(text);
+    check_height_estimator(input);
+    check_output_ratio(input);
+    check_case(text);
+    check_format_stats(text);
+    check_budget(input);
+    check_width_prefix(input);
+    check_raggedness(input);
+    check_merge(input);
+    check_cjk_indent(input);
+    check_class_runs(input);
+    check_reflow(input);
+    check_marks(input);
+    check_char_repeat(input);
+    check_canvas_limits(input);
+    check_line_heights(input);
+    check_layout_range(input);
+    check_instance_settings(input);
+    check_paragraphs(text);
+    check_spacing(input);
+    check_guides(input);
+    check_width_provider(input);
+    check_warnings(input);
+    check_option_conflicts(text);
+    check_tsv(input);
+    check_break_opportunities(input);
+    check_iteration_marks(input);
+    check_kinsoku_sets(input);
+    check_edge_char_report(input);
+    check_column_rounding(input);
+    check_break_positions(input);
+    check_break_after(input);
+    check_code_spans(input);
+    check_boms(input);
+    check_text_edits(input);
+    check_signature(input);
+    check_render_passes(input);
+    check_diff_texts(input);
+    check_hanging_indent(input);
+    check_schemas(input);
+    check_encodings(input);
+    check_confusables(&input.text);
+    check_strict(input);
+}
+
+/// HeightEstimator counts the lines count_wrapped_lines does, at the fuzzed width and around it
+fn check_height_estimator(input: &FuzzInput) {
+    let estimator = HeightEstimator::new(&input.text);
+    let width = input.max_chars_per_line;
+    for width in [0, 1, width / 2, width.saturating_sub(1), width, width + 1] {
+        assert_eq!(
+            estimator.estimate(width),
+            count_wrapped_lines(&input.text, width),
+            "HeightEstimator at {width}"
+        );
+    }
+}
+
+/// A provider doubling the built-in widths wraps the fuzzed text at twice the width exactly as
+/// the built-in widths do, and two passes measure each distinct character once
+fn check_width_provider(input: &FuzzInput) {
+    let calls = Cell::new(0);
+    let widths = MemoizedWidths::new(|c| {
+        calls.set(calls.get() + 1);
+        2.0 * BuiltinWidths.width(c)
+    });
+    let text = &input.text;
+    let max_width = f64::from(input.max_chars_per_line.max(2)) / 2.0;
+    let expected = justify_text_fractional(text, max_width);
+    for pass in 0..2 {
+        assert_eq!(
+            justify_text_with_provider(text, 2.0 * max_width, &widths),
+            expected,
+            "doubled widths at {} em, pass {pass}",
+            2.0 * max_width
+        );
+    }
+    assert_eq!(calls.get(), widths.measured_count());
+    // The word wrappers measure the space they put between words even where there is none
+    let distinct: HashSet<char> = text.chars().chain([' ']).collect();
+    assert!(widths.measured_count() <= distinct.len());
+}
+
+/// Options that change how the wrapper measures or marks its lines
+const STRICT_OPTIONS: [&str; 11] = [
+    "{}",
+    r#"{"lineNumbers": true, "numberStart": 98}"#,
+    r#"{"hangingIndent": true, "listMarkerSpacing": "ideographic"}"#,
+    r#"{"rubySyntax": "kanji"}"#,
+    r#"{"markSoftBreaks": "↩", "trimTrailing": false}"#,
+    r#"{"breakMarkerEnd": "-", "breakMarkerStart": "+", "algorithmVersion": 2}"#,
+    r#"{"algorithmVersion": 1, "markdown": true}"#,
+    r#"{"markdown": true, "breakAfterChars": "/"}"#,
+    r#"{"algorithmVersion": 3, "maxUnbreakableRun": 12}"#,
+    r#"{"breakAfterChars": "/-", "preserveSeparatorChars": true}"#,
+    r#"{"hardBreakMarker": "|", "softBreakMarker": "~", "footerText": "footer"}"#,
+];
+
+/// Under every STRICT_OPTIONS, justify_checked finds nothing wrong with justify's output
+fn check_strict(input: &FuzzInput) {
+    for options_json in STRICT_OPTIONS {
+        let options = JustifyOptions {
+            max_chars_per_line: input.max_chars_per_line,
+            ..JustifyOptions::from_json(options_json).expect(options_json)
+        };
+        assert_eq!(
+            options.justify_checked(&input.text),
+            Ok(options.justify(&input.text)),
+            "{options_json}"
+        );
+    }
+}
+
+/// Collecting warnings leaves the text as justify writes it, every located warning points
+/// inside the text and no code is reported more than MAX_WARNINGS_PER_CODE times
+fn check_warnings(input: &FuzzInput) {
+    let text = &input.text;
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line.max(1),
+        max_unbreakable_run: input.chunk_size % 64,
+        ..JustifyOptions::default()
+    };
+    let justified = options.justify_with_warnings(text);
+    assert_eq!(justified.text, options.justify(text));
+    let lines: Vec<&str> = text.split('\n').collect();
+    for warning in &justified.warnings {
+        if let (Some(line), Some(column)) = (warning.line_index, warning.char_index) {
+            assert!(
+                column <= lines[line].chars().count(),
+                "{warning:?} is past the end of its line"
+            );
+        }
+    }
+    for code in WarningCode::ALL {
+        let count = justified
+            .warnings
+            .iter()
+            .filter(|warning| warning.code == *code)
+            .count();
+        assert!(count <= MAX_WARNINGS_PER_CODE, "{count} {code} warnings");
+    }
+}
+
+/// On the fuzzed text, UTF-8 is detected as such and the text survives a round trip through
+/// every encoding that can hold it; on bytes made from it, a decoding error names an offset
+/// before which the bytes decode
+fn check_encodings(input: &FuzzInput) {
+    let text = &input.text;
+    let bom_free = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    assert_eq!(
+        decode_bytes(text.as_bytes(), "utf-8").as_deref(),
+        Ok(bom_free)
+    );
+    let guess = detect_encoding(text.as_bytes());
+    assert_eq!(
+        (guess.encoding, guess.confidence),
+        (TextEncoding::Utf8, 1.0)
+    );
+    for &encoding in TextEncoding::ALL {
+        let label = encoding.name();
+        let codec = encoding_rs::Encoding::for_label(label.as_bytes()).unwrap();
+        let (encoded, _, unmappable) = codec.encode(bom_free);
+        if !unmappable {
+            assert_eq!(
+                decode_bytes(&encoded, label).as_deref(),
+                Ok(bom_free),
+                "{label} round trip"
+            );
+        }
+    }
+
+    let shift = input.chunk_size as u8;
+    let bytes: Vec<u8> = text.bytes().map(|b| b.wrapping_add(shift)).collect();
+    let guess = detect_encoding(&bytes);
+    assert!((0.0..=1.0).contains(&guess.confidence), "{guess:?}");
+    for &encoding in TextEncoding::ALL {
+        let label = encoding.name();
+        match decode_bytes(&bytes, label) {
+            Ok(_) => {}
+            Err(
+                WebtoolsError::InvalidUtf8 { offset }
+                | WebtoolsError::InvalidEncoding { offset, .. },
+            ) => {
+                assert!(offset < bytes.len(), "{label} error past the end");
+                assert!(
+                    decode_bytes(&bytes[..offset], label).is_ok(),
+                    "{label} error at {offset}, but the bytes before it don't decode"
+                );
+            }
+            Err(error) => panic!("{label}: {error:?}"),
+        }
+    }
+}
+
+/// Every reported character is in its group's word and differs from its suggestion, and a
+/// fix only touches characters of reported words, one for one
+fn check_confusables(text: &str) {
+    let chars: Vec<char> = text.chars().collect();
+    let report = find_confusables(text);
+    assert_eq!(
+        report.count,
+        report
+            .groups
+            .iter()
+            .map(|group| group.chars.len())
+            .sum::<usize>()
+    );
+    let mut reported = vec![false; chars.len()];
+    for group in &report.groups {
+        let word: Vec<char> = group.word.chars().collect();
+        assert_eq!(chars[group.start..group.start + word.len()], word[..]);
+        assert!(!group.chars.is_empty(), "{group:?}");
+        for c in &group.chars {
+            assert_eq!(chars[c.char_index], c.char);
+            assert_ne!(c.char, c.suggestion);
+            assert!((group.start..group.start + word.len()).contains(&c.char_index));
+        }
+        reported[group.start..group.start + word.len()].fill(true);
+    }
+    for &policy in ConfusablePolicy::ALL {
+        let fixed: Vec<char> = apply_confusable_fixes_with(text, policy).chars().collect();
+        assert_eq!(fixed.len(), chars.len(), "{policy:?}");
+        for (index, (&before, &after)) in chars.iter().zip(&fixed).enumerate() {
+            assert!(
+                before == after || reported[index],
+                "{policy:?} changed {index}"
+            );
+        }
+    }
+}
+
+/// A validator for each of SCHEMA_KINDS, built once
+fn schema_validators() -> &'static [(&'static str, jsonschema::Validator)] {
+    static VALIDATORS: OnceLock<Vec<(&str, jsonschema::Validator)>> = OnceLock::new();
+    VALIDATORS.get_or_init(|| {
+        SCHEMA_KINDS
+            .iter()
+            .map(|kind| {
+                let document = json_schema(kind.name).expect("a listed kind has no schema");
+                let schema: serde_json::Value =
+                    serde_json::from_str(&document).expect("json_schema returned invalid JSON");
+                let validator = jsonschema::validator_for(&schema)
+                    .unwrap_or_else(|e| panic!("schema {:?} is invalid: {e}", kind.name));
+                (kind.name, validator)
+            })
+            .collect()
+    })
+}
+
+/// output is valid JSON of the given kind
+fn assert_schema(kind: &str, output: &str) {
+    let (_, validator) = schema_validators()
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .unwrap_or_else(|| panic!("no schema kind {kind:?}"));
+    let value: serde_json::Value = serde_json::from_str(output)
+        .unwrap_or_else(|e| panic!("{kind} output {output:?} is invalid JSON: {e}"));
+    if let Err(error) = validator.validate(&value) {
+        panic!("{kind} output {output} doesn't match its schema: {error}");
+    }
+}
+
+/// A fallible entry point's output is of the given kind, its error an "error"
+fn assert_schema_or_error(kind: &str, output: Result<String, WebtoolsError>) {
+    match output {
+        Ok(output) => assert_schema(kind, &output),
+        Err(error) => assert_schema("error", &serde_json::to_string(&error).unwrap()),
+    }
+}
+
+/// self_test's report with a failed check added, so both shapes of a check are in it
+fn failed_self_test() -> SelfTestReport {
+    let mut report = self_test();
+    report.check("failing", "expected".into(), "actual".into());
+    report
+}
+
+/// Each JSON output of the fuzzed text matches the schema of its kind
+fn check_schemas(input: &FuzzInput) {
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+    let wrapped = justify_text(text, width);
+    let first_line = text.lines().next().unwrap_or_default();
+    let spacing = Spacing::new(0.05, 0.1).unwrap();
+    let limits = CanvasLimits::default();
+    let options = JustifyOptions {
+        max_chars_per_line: width,
+        hanging_indent: true,
+        ..JustifyOptions::default()
+    };
+    let texts = serde_json::to_string(&[text.as_str(), "", "漢字 and words"]).unwrap();
+    let option_names = vec!["maxCharsPerLine".to_string(), "noSuchOption".to_string()];
+    let samples = [
+        ("textStats", get_text_stats(text)),
+        ("paragraphStats", paragraph_stats(text, width)),
+        (
+            "layoutLines",
+            layout_text(text, width, 16.0, 1.5, 8.0, "center"),
+        ),
+        (
+            "charAdvanceLines",
+            char_advances_lines_with_spacing(&wrapped, 16.0, spacing),
+        ),
+        (
+            "guidedLayout",
+            layout_text_with_guides(text, width, 16.0, 1.5, 8.0, "left", 1.5, spacing, 4),
+        ),
+        (
+            "layoutRange",
+            Document::new(text.clone(), options.clone()).layout_range_json(
+                1,
+                4,
+                16.0,
+                1.5,
+                8.0,
+                Align::Right,
+            ),
+        ),
+        (
+            "layoutWithLimits",
+            layout_text_with_limits(text, width, 16.0, 1.5, 8.0, "right", &limits),
+        ),
+        (
+            "imageSize",
+            estimate_image_size_with_limits(text, width, 16.0, 1.5, 8.0, &limits),
+        ),
+        (
+            "imageSizeForWidth",
+            estimate_image_size_for_width(
+                text,
+                f64::from(width) * 8.0 + 20.0,
+                16.0,
+                1.5,
+                8.0,
+                ColumnRounding::Round,
+                &limits,
+            ),
+        ),
+        ("lineCountFit", wrap_to_line_count(text, 3, 1, 80)),
+        (
+            "verticalLayout",
+            layout_vertical(text, width, 16.0, 4.0, 8.0),
+        ),
+        ("classRuns", classify_runs(text)),
+        ("lineClassRuns", classify_line_runs(&wrapped)),
+        ("emojiPlacements", emoji_positions(&wrapped, 16.0)),
+        (
+            "imageBlocks",
+            paginate_for_images(text, width, 16.0, 1.5, 200.0, true),
+        ),
+        ("columns", layout_columns(text, width, 2, 5, true)),
+        (
+            "bandedPages",
+            paginate_with_bands(text, width, 5, "Header", "Page {page} of {pages}", false),
+        ),
+        ("paragraphs", split_paragraphs(text)),
+        ("quotedLines", strip_quote_prefix_detailed(text)),
+        ("matchSegments", find_in_wrapped(text, width, "o", true)),
+        ("confusablesReport", confusables_report(text)),
+        ("encodingGuess", detect_encoding_json(text.as_bytes())),
+        ("fontCoverage", font_coverage_report(text)),
+        ("lineHygiene", line_hygiene_report(text, 4)),
+        ("inputReport", validate_input(text, width)),
+        (
+            "optionsReport",
+            OptionsReport::new(
+                JustifyOptions::from_json(r#"{"maxCharsPerLine":0,"listMarkerSpacing":"space"}"#),
+                &option_names,
+            )
+            .to_json(),
+        ),
+        (
+            "justifiedText",
+            serde_json::to_string(&options.justify_with_warnings(text)).unwrap(),
+        ),
+        ("capabilities", capabilities().to_json()),
+        ("runtimeConfig", RuntimeConfig::default().to_json()),
+        ("selfTest", failed_self_test().to_json()),
+        (
+            "error",
+            serde_json::to_string(&json_schema("nope").unwrap_err()).unwrap(),
+        ),
+    ];
+    for (kind, output) in &samples {
+        assert_schema(kind, output);
+    }
+    let fallible = [
+        (
+            "textStats",
+            stats_incremental_json(&get_text_stats(text), text.len(), text),
+        ),
+        (
+            "breakOpportunities",
+            break_opportunities_json(first_line, ""),
+        ),
+        (
+            "wrapDiff",
+            diff_wrap(
+                text,
+                &format!(r#"{{"maxCharsPerLine":{width}}}"#),
+                r#"{"maxCharsPerLine":12,"hangingIndent":true}"#,
+            ),
+        ),
+        ("textDiff", diff_texts(text, &text.replace('e', "E"), width)),
+        ("caseTransform", transform_case_json(text, "title")),
+        ("punctuationReport", convert_punctuation_report(text, "cjk")),
+        ("cleanReport", clean_text_report(text, "")),
+        ("textEdits", convert_punctuation_edits(text, "cjk")),
+        ("formattedStats", format_stats(text, "zh")),
+        ("kinsokuSets", kinsoku_sets("zh-Hant")),
+        (
+            "edgeCharReport",
+            edge_char_report(text, width, r#"{"locale":"auto"}"#),
+        ),
+        (
+            "streamedStats",
+            stream_stats(text.as_bytes(), 7).map(|stats| streamed_stats_json(&stats)),
+        ),
+        ("batchTexts", batch_justify_text(&texts, width, false)),
+        (
+            "transferReport",
+            batch_justify_text_with_report(&texts, width, false, || 0.0)
+                .map(|(_, report)| report.to_json()),
+        ),
+    ];
+    let mut covered: HashSet<&str> = samples.iter().map(|(kind, _)| *kind).collect();
+    for (kind, output) in fallible {
+        covered.insert(kind);
+        assert_schema_or_error(kind, output);
+    }
+    assert_eq!(covered.len(), SCHEMA_KINDS.len(), "kinds without a sample");
+}
+
+/// hangingIndent leaves lines without a marker alone, and wraps a line with one as its text
+/// alone at the width its prefix leaves, the prefix in front of the first line and as many
+/// spaces in front of the others (or as an ordinary line, when the prefix is wider than half
+/// the line)
+fn check_hanging_indent(input: &FuzzInput) {
+    let width = input.max_chars_per_line.max(1);
+    let plain = JustifyOptions {
+        max_chars_per_line: width,
+        ..JustifyOptions::default()
+    };
+    let hanging = JustifyOptions {
+        hanging_indent: true,
+        ..plain.clone()
+    };
+    for line in input.text.split('\n').take(20) {
+        if limit_marks(line, plain.max_marks_per_cluster) != line {
+            continue;
+        }
+        let Some(marker) = list_marker(line) else {
+            assert_eq!(hanging.justify(line), plain.justify(line), "{line:?}");
+            continue;
+        };
+        let marker_end = marker.as_ptr() as usize - line.as_ptr() as usize + marker.len();
+        let body = line[marker_end..].trim_start_matches([' ', '\t', '\u{3000}']);
+        let prefix = &line[..line.len() - body.len()];
+        let prefix_width = calculate_text_width(prefix) as u32;
+        if 2 * prefix_width > width {
+            assert_eq!(hanging.justify(line), plain.justify(line), "{line:?}");
+            continue;
+        }
+        let indent = " ".repeat(prefix_width as usize);
+        let body_options = JustifyOptions {
+            max_chars_per_line: width - prefix_width,
+            ..plain.clone()
+        };
+        let expected: Vec<String> = body_options
+            .justify(body)
+            .split("\r\n")
+            .enumerate()
+            .map(|(index, wrapped)| {
+                format!("{}{}", if index == 0 { prefix } else { &indent }, wrapped)
+            })
+            .collect();
+        assert_eq!(
+            hanging.justify(line),
+            expected.join("\r\n"),
+            "{line:?} at {width}"
+        );
+    }
+}
+
+/// Algorithm version 4 loses no character and keeps lines within the width, and text without
+/// any breakAfterChars wraps as in version 3
+fn check_break_after(input: &FuzzInput) {
+    let with_version = |version, width| JustifyOptions {
+        max_chars_per_line: width,
+        algorithm_version: version,
+        ..JustifyOptions::default()
+    };
+    let width = input.max_chars_per_line.max(1);
+    let text = &input.text;
+    let v4 = with_version(4, width).justify(text);
+    let limited = limit_marks(text, DEFAULT_MAX_MARKS_PER_CLUSTER);
+    assert_no_loss("algorithm version 4", &limited, &v4);
+    // Version 3 on measures narrow astral letters as one column, which calculate_text_width
+    // doesn't
+    if text.chars().all(|c| c <= '\u{FFFF}') {
+        assert_line_widths("algorithm version 4", &v4, width);
+    }
+    if !text.contains(|c| DEFAULT_BREAK_AFTER_CHARS.contains(c)) {
+        assert_eq!(
+            v4,
+            with_version(3, width).justify(text),
+            "V4 without dashes"
+        );
+    }
+}
+
+/// Algorithm version 5 with markdown loses no character of the fuzzed text, and text without
+/// backticks, or without markdown, wraps as in version 4
+fn check_code_spans(input: &FuzzInput) {
+    let with_version = |version, width| JustifyOptions {
+        max_chars_per_line: width,
+        algorithm_version: version,
+        markdown: true,
+        ..JustifyOptions::default()
+    };
+    let width = input.max_chars_per_line.max(1);
+    let text = &input.text;
+    let v5 = with_version(5, width).justify(text);
+    let limited = limit_marks(text, DEFAULT_MAX_MARKS_PER_CLUSTER);
+    assert_no_loss("code spans", &limited, &v5);
+    let plain = JustifyOptions {
+        markdown: false,
+        ..with_version(5, width)
+    };
+    if !text.contains('`') {
+        assert_eq!(
+            v5,
+            with_version(4, width).justify(text),
+            "V5 without backticks"
+        );
+    }
+    assert_eq!(
+        plain.justify(text),
+        JustifyOptions {
+            algorithm_version: 4,
+            ..plain.clone()
+        }
+        .justify(text),
+        "V5 without markdown"
+    );
+}
+
+/// Algorithm version 6 loses no character of the fuzzed text, and text without BOMs wraps as
+/// in version 5
+fn check_boms(input: &FuzzInput) {
+    let with_version = |version, width| JustifyOptions {
+        max_chars_per_line: width,
+        algorithm_version: version,
+        ..JustifyOptions::default()
+    };
+    let width = input.max_chars_per_line.max(1);
+    let text = &input.text;
+    let v6 = with_version(6, width).justify(text);
+    let limited = limit_marks(text, DEFAULT_MAX_MARKS_PER_CLUSTER);
+    assert_no_loss("algorithm version 6", &limited, &v6);
+    if !text.contains('\u{FEFF}') {
+        assert_eq!(v6, with_version(5, width).justify(text), "V6 without BOMs");
+    }
+}
+
+/// From algorithm V7 on the fuzzed text's signature, if it has one, is written as it was,
+/// line breaks aside, at the end of the output; without one it wraps as in V6
+fn check_signature(input: &FuzzInput) {
+    let with_version = |version, width| JustifyOptions {
+        max_chars_per_line: width,
+        algorithm_version: version,
+        ..JustifyOptions::default()
+    };
+    let width = input.max_chars_per_line.max(1);
+    let text = &input.text;
+    let v7 = with_version(7, width).justify(text);
+    match signature_start(text, DEFAULT_SIGNATURE_DELIMITER) {
+        Some(start) => {
+            let verbatim = text[start..].replace("\r\n", "\n").replace('\n', "\r\n");
+            assert!(v7.ends_with(&verbatim), "signature of {text:?}");
+        }
+        None => assert_eq!(
+            v7,
+            with_version(6, width).justify(text),
+            "V7 without a signature"
+        ),
+    }
+}
+
+/// escapeHtml and visualizeWhitespace change how the fuzzed text's lines are written, never
+/// where they break, and with a gutter, break markers and hanging indents every render pass
+/// combination passes the postcondition
+fn check_render_passes(input: &FuzzInput) {
+    let layout = JustifyOptions {
+        line_numbers: true,
+        hanging_indent: true,
+        break_marker_end: "↩".to_string(),
+        break_marker_start: "↪".to_string(),
+        ..JustifyOptions::default()
+    };
+    let width = input.max_chars_per_line.clamp(1, 200);
+    for (visualize, escape) in [(false, true), (true, false), (true, true)] {
+        let unrendered = JustifyOptions {
+            max_chars_per_line: width,
+            ..layout.clone()
+        };
+        let options = JustifyOptions {
+            visualize_whitespace: visualize,
+            escape_html: escape,
+            ..unrendered.clone()
+        };
+        let output = options.justify_checked(&input.text);
+        let output = output.unwrap_or_else(|e| panic!("{e} at {width}"));
+        let mut expected = unrendered.justify(&input.text);
+        if visualize {
+            expected = visualize_whitespace(&expected);
+        }
+        if escape {
+            expected = escape_html(&expected);
+        }
+        assert_eq!(output, expected, "render passes at {width}");
+    }
+}
+
+/// On the fuzzed text, each line's segments of diff_texts follow one another and add up to its
+/// width, the unchanged and deleted ones keep the non-whitespace of the old text and the
+/// unchanged and inserted ones that of the new, and a text compared with itself is unchanged
+/// and wrapped as justify_text
+fn check_diff_texts(input: &FuzzInput) {
+    let width = input.max_chars_per_line;
+    let (old, new) = (&input.text, input.text.replace('a', "b") + "\nend");
+    let Ok(diff) = diff_texts(old, &new, width) else {
+        assert!(check_max_chars_per_line(width).is_err());
+        return;
+    };
+    let diff: serde_json::Value = serde_json::from_str(&diff).unwrap();
+    let mut line = (usize::MAX, 0);
+    let (mut kept_old, mut kept_new) = (String::new(), String::new());
+    for segment in diff["segments"].as_array().unwrap() {
+        let text = segment["text"].as_str().unwrap();
+        let index = segment["lineIndex"].as_u64().unwrap() as usize;
+        let start = segment["startCol"].as_u64().unwrap();
+        if index != line.0 {
+            line = (index, 0);
+        }
+        assert_eq!(start, line.1, "segment {segment} of {old:?}");
+        line.1 = segment["endCol"].as_u64().unwrap();
+        assert_eq!(line.1 - start, calculate_text_width(text), "{segment}");
+        let visible = text.chars().filter(|c| !c.is_whitespace());
+        match segment["change"].as_str().unwrap() {
+            "unchanged" => {
+                kept_old.extend(visible.clone());
+                kept_new.extend(visible);
+            }
+            "deleted" => kept_old.extend(visible),
+            "inserted" => kept_new.extend(visible),
+            other => panic!("unknown change {other}"),
+        }
+    }
+    let visible = |text: &str| -> String { text.chars().filter(|c| !c.is_whitespace()).collect() };
+    assert_eq!(kept_old, visible(old), "old text of {old:?}");
+    assert_eq!(kept_new, visible(&new), "new text of {old:?}");
+
+    let same: serde_json::Value =
+        serde_json::from_str(&diff_texts(old, old, width).unwrap()).unwrap();
+    let wrapped = justify_text(old, width);
+    let lines: Vec<&str> = match wrapped.as_str() {
+        "" => Vec::new(),
+        wrapped => wrapped.split("\r\n").collect(),
+    };
+    assert_eq!(same["lineCount"], lines.len(), "lines of {old:?}");
+    let mut written = vec![String::new(); lines.len()];
+    for segment in same["segments"].as_array().unwrap() {
+        assert_eq!(segment["change"], "unchanged");
+        written[segment["lineIndex"].as_u64().unwrap() as usize]
+            .push_str(segment["text"].as_str().unwrap());
+    }
+    assert_eq!(written, lines, "{old:?} compared with itself");
+}
+
+/// Options the edit check runs clean_text_edits with
+const EDIT_CLEAN_OPTIONS: [&str; 4] = [
+    "",
+    r#"{"stripAnsi": true}"#,
+    r#"{"normalizeNewlinesTo": "crlf"}"#,
+    r#"{"stripAnsi": true, "normalizeNewlinesTo": "keep", "stripBom": false}"#,
+];
+
+/// An edit list as JSON is ascending, its edits never touching and inside text, and applying
+/// it to text gives expected
+fn assert_edits_apply(what: &str, text: &str, edits_json: &str, expected: &str) {
+    let edits: Vec<serde_json::Value> = serde_json::from_str(edits_json).unwrap();
+    let edits: Vec<TextEdit> = edits
+        .iter()
+        .map(|edit| TextEdit {
+            start: edit["start"].as_u64().unwrap() as usize,
+            end: edit["end"].as_u64().unwrap() as usize,
+            replacement: edit["replacement"].as_str().unwrap().to_string(),
+        })
+        .collect();
+    let char_count = text.chars().count();
+    assert!(
+        edits
+            .iter()
+            .all(|edit| edit.start <= edit.end && edit.end <= char_count)
+            && edits.windows(2).all(|pair| pair[0].end < pair[1].start),
+        "{what} edits of {text:?}: {edits:?}"
+    );
+    assert_eq!(
+        apply_edits(text, &edits),
+        expected,
+        "{what} edits of {text:?}"
+    );
+}
+
+/// Applying the edits of convert_punctuation_edits and clean_text_edits to the fuzzed text gives
+/// what convert_punctuation and clean_text return
+fn check_text_edits(input: &FuzzInput) {
+    let text = &input.text;
+    for target in ["cjk", "ascii"] {
+        let converted = convert_punctuation(text, target).unwrap();
+        let edits = convert_punctuation_edits(text, target).unwrap();
+        assert_edits_apply(target, text, &edits, &converted);
+    }
+    for options in EDIT_CLEAN_OPTIONS {
+        let cleaned = clean_text(text, options).unwrap();
+        let edits = clean_text_edits(text, options).unwrap();
+        assert_edits_apply(options, text, &edits, &cleaned);
+    }
+}
+
+/// break_positions is ascending and inside the text, and for text whose only whitespace is
+/// line feeds (so nothing is collapsed or dropped at a break) a line break after each of its
+/// positions, and CRLF for each line feed, gives justify_text's output
+fn check_break_positions(input: &FuzzInput) {
+    let width = input.max_chars_per_line;
+    let positions = break_positions(&input.text, width);
+    let char_count = input.text.chars().count();
+    assert!(
+        positions.windows(2).all(|pair| pair[0] < pair[1])
+            && positions.iter().all(|&index| (index as usize) < char_count),
+        "break_positions of {:?} at {width}: {positions:?}",
+        input.text
+    );
+
+    // Narrower than a wide character, the wrapper can leave a line empty, which no position
+    // stands for
+    if width < 2 {
+        return;
+    }
+    let text: String = input
+        .text
+        .chars()
+        .filter(|&c| c == '\n' || !c.is_whitespace() && !matches!(c, '\u{00AD}' | '\u{200B}'))
+        .collect();
+    let positions = break_positions(&text, width);
+    let mut rebuilt = String::with_capacity(text.len() * 2);
+    let mut next = positions.iter().peekable();
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            '\n' => rebuilt.push_str("\r\n"),
+            c => rebuilt.push(c),
+        }
+        if next
+            .next_if(|&&position| position as usize == index)
+            .is_some()
+        {
+            rebuilt.push_str("\r\n");
+        }
+    }
+    assert_eq!(
+        rebuilt,
+        justify_text(&text, width),
+        "break_positions of {text:?} at {width}: {positions:?}"
+    );
+}
+
+/// Every break justify_text and justify_text_cjk_with_rules (Japanese rules) choose on a line
+/// is at one of break_opportunities, unless the stretch between two opportunities it falls in
+/// is wider than the line
+fn check_break_opportunities(input: &FuzzInput) {
+    let width = input.max_chars_per_line;
+    let rules = KinsokuRules::new(CjkLocale::Ja, "", "");
+    for line in input.text.split('\n').filter(|line| !line.contains('\r')) {
+        assert_breaks_at_opportunities(
+            "justify_text",
+            line,
+            &justify_text(line, width),
+            &break_opportunities(line, None),
+            width,
+            !is_cjk(line),
+        );
+        assert_breaks_at_opportunities(
+            "justify_text_cjk_with_rules",
+            line,
+            &justify_text_cjk_with_rules(line, width, &rules),
+            &break_opportunities(line, Some(&rules)),
+            width,
+            false,
+        );
+    }
+}
+
+/// Map each break in output back to line and check it against opportunities; by_word when
+/// the line was wrapped word by word
+fn assert_breaks_at_opportunities(
+    what: &str,
+    line: &str,
+    output: &str,
+    opportunities: &[usize],
+    width: u32,
+    by_word: bool,
+) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut position = 0;
+    // One past the last visible char of the previous output line
+    let mut previous_end = None;
+    for output_line in output.split("\r\n") {
+        let mut line_start = None;
+        for c in output_line.chars().filter(|c| !c.is_whitespace()) {
+            while chars[position].is_whitespace() {
+                position += 1;
+            }
+            assert_eq!(chars[position], c, "{what} changed {line:?}");
+            line_start.get_or_insert(position);
+            position += 1;
+        }
+        let Some(start) = line_start else { continue };
+        if let Some(end) = previous_end {
+            if !opportunities.iter().any(|&o| end <= o && o <= start) {
+                let unit_start = opportunities.iter().rev().find(|&&o| o < end);
+                let unit_end = opportunities.iter().find(|&&o| o > start);
+                let unit: String = chars
+                    [*unit_start.unwrap_or(&0)..*unit_end.unwrap_or(&chars.len())]
+                    .iter()
+                    .collect();
+                // The word wrapper measures words in bytes, the character wrappers in columns
+                let unit_width = if by_word {
+                    unit.len() as u64
+                } else {
+                    calculate_text_width(&unit)
+                };
+                assert!(
+                    unit_width > u64::from(width),
+                    "{what} broke {line:?} at {start}, not an opportunity ({opportunities:?})"
+                );
+            }
+        }
+        previous_end = Some(position);
+    }
+}
+
+/// On the fuzzed widths Floor never overflows, Round is within half a column and Ceil within
+/// one, estimate_image_size_for_width echoes the columns and slack, and fit_aspect_ratio_with
+/// takes the widths either side of the target ratio
+fn check_column_rounding(input: &FuzzInput) {
+    use ColumnRounding::{Ceil, Floor, Round};
+    let text = &input.text;
+    let font_size_px = f64::from(input.chunk_size % 40 + 8);
+    let column_px = font_size_px / 2.0;
+    let width_px = f64::from(input.max_chars_per_line) * 3.7 + 24.0;
+    let exact = (width_px - 16.0) / column_px;
+    let fits =
+        [Floor, Round, Ceil].map(|rounding| fit_columns(width_px, font_size_px, 8.0, rounding));
+    let [floor, round, ceil] = fits;
+    assert!(floor.columns <= round.columns && round.columns <= ceil.columns);
+    assert!(ceil.columns - floor.columns <= 1, "{fits:?}");
+    if exact > f64::from(MIN_CHARS_PER_LINE) {
+        assert!(
+            floor.slack_px > -1e-6,
+            "floor overflowed {width_px}px: {floor:?}"
+        );
+        assert!(round.slack_px.abs() <= column_px / 2.0 + 1e-6, "{round:?}");
+        assert!(ceil.slack_px > -column_px - 1e-6, "{ceil:?}");
+    }
+    assert_eq!(
+        chars_per_line_for_width(width_px, font_size_px, 8.0),
+        floor.columns
+    );
+    assert_eq!(
+        justify_text_for_image_with(text, width_px, font_size_px, 8.0, Round),
+        justify_text(text, round.columns)
+    );
+    let limits = CanvasLimits::default();
+    for (rounding, fit) in [(Floor, floor), (Ceil, ceil)] {
+        let estimate: serde_json::Value = serde_json::from_str(&estimate_image_size_for_width(
+            text,
+            width_px,
+            font_size_px,
+            1.5,
+            8.0,
+            rounding,
+            &limits,
+        ))
+        .expect("estimate_image_size_for_width returned invalid JSON");
+        assert_eq!(estimate["columns"], fit.columns);
+        let slack_px = estimate["slackPx"].as_f64().unwrap_or(f64::NAN);
+        let width = estimate["widthPx"].as_f64().unwrap_or_default();
+        assert!((slack_px - fit.slack_px).abs() < 1e-6, "{estimate}");
+        assert!((width + slack_px - width_px).abs() < 1e-6, "{estimate}");
+    }
+
+    let (min_chars, max_chars) = (1, input.max_chars_per_line % 120 + 1);
+    let target = f64::from(input.chunk_size % 300 + 1) / 100.0;
+    let [floor, round, ceil] = [Floor, Round, Ceil]
+        .map(|rounding| fit_aspect_ratio_with(text, target, 16.0, min_chars, max_chars, rounding));
+    assert_eq!(
+        fit_aspect_ratio(text, target, 16.0, min_chars, max_chars),
+        round
+    );
+    assert!(floor <= round && round <= ceil && ceil - floor <= 1);
+    assert!(round == floor || round == ceil);
+    let ratio = |chars: u32| match count_wrapped_lines(text, chars) {
+        0 => None,
+        lines => Some(f64::from(chars) / (2.0 * f64::from(lines))),
+    };
+    if floor > min_chars {
+        assert!(
+            ratio(floor).is_none_or(|ratio| ratio <= target),
+            "floor {floor} is wider than {target}"
+        );
+    }
+    if ceil < max_chars {
+        assert!(
+            ratio(ceil).is_none_or(|ratio| ratio >= target),
+            "ceil {ceil} falls short of {target}"
+        );
+    }
+}
+
+/// A line wrapped in any locale starts with an iteration mark (々, ゝ, ヽ and their voiced
+/// forms) only after a line of at most one character, that being all that preceded it (lines
+/// with two marks in a row aside)
+fn check_iteration_marks(input: &FuzzInput) {
+    let marks = |c: char| matches!(c, '々' | '〻' | 'ゝ' | 'ゞ' | 'ヽ' | 'ヾ');
+    let width = input.max_chars_per_line.max(1);
+    for line in input.text.split('\n').filter(|line| !line.contains('\r')) {
+        let chars: Vec<char> = line.chars().collect();
+        if chars
+            .windows(2)
+            .any(|pair| marks(pair[0]) && marks(pair[1]))
+        {
+            continue;
+        }
+        for locale in ["ja", "zh-Hans", "zh-Hant", "ko"] {
+            let output = justify_text_cjk_locale(line, width, locale, "", "")
+                .expect("justify_text_cjk_locale rejected a valid width");
+            let lines: Vec<&str> = output.split("\r\n").collect();
+            for pair in lines.windows(2) {
+                if pair[1].starts_with(marks) {
+                    assert!(
+                        pair[0].chars().count() <= 1,
+                        "{locale} split an iteration mark from its base in {line:?} at {width}: \
+                         {output:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// kinsoku_sets lists exactly the characters KinsokuRules holds back, the fuzzed text's
+/// among them, each once
+fn check_kinsoku_sets(input: &FuzzInput) {
+    for locale in ["ja", "zh-Hans", "zh-Hant", "ko"] {
+        let sets: serde_json::Value = serde_json::from_str(&kinsoku_sets(locale).unwrap()).unwrap();
+        assert_eq!(sets["locale"], locale);
+        let rules = KinsokuRules::new(CjkLocale::parse(locale).unwrap(), "", "");
+        let no_start = sets["noStart"].as_str().unwrap();
+        let no_end = sets["noEnd"].as_str().unwrap();
+        for (set, forbids) in [
+            (
+                no_start,
+                &(|c| rules.forbids_start(c)) as &dyn Fn(char) -> bool,
+            ),
+            (no_end, &|c| rules.forbids_end(c)),
+        ] {
+            let unique: HashSet<char> = set.chars().collect();
+            assert_eq!(unique.len(), set.chars().count(), "repeats in {locale}");
+            for c in input
+                .text
+                .chars()
+                .chain(set.chars())
+                .chain("a漢か、（".chars())
+            {
+                assert_eq!(forbids(c), set.contains(c), "{c:?} in {locale}");
+            }
+        }
+    }
+}
+
+/// edge_char_report on the fuzzed text adds up to the breaks the wrapper made, the forbidden
+/// counts being the flagged entries' and each flag the rules'
+fn check_edge_char_report(input: &FuzzInput) {
+    let report = |text: &str, width, options: &str| -> serde_json::Value {
+        serde_json::from_str(&edge_char_report(text, width, options).unwrap()).unwrap()
+    };
+    let edges = |value: &serde_json::Value| -> Vec<(String, u64, bool)> {
+        value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| {
+                (
+                    edge["character"].as_str().unwrap().to_string(),
+                    edge["count"].as_u64().unwrap(),
+                    edge["forbidden"].as_bool().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    let text = &input.text;
+    let width = input.max_chars_per_line.clamp(1, MAX_MAX_CHARS_PER_LINE);
+    // Every source line is at least one output line; the rest follow soft breaks
+    let source_lines = text.split('\n').count() as u64;
+    let soft_breaks = |wrapped: &str| {
+        u64::from(count_wrapped_lines_of_output(wrapped)).saturating_sub(source_lines)
+    };
+    for (options, soft_breaks) in [
+        ("", soft_breaks(&justify_text(text, width))),
+        (
+            r#"{"locale":"zh-Hant","extraNoStart":"a"}"#,
+            soft_breaks(&justify_text_cjk_locale(text, width, "zh-Hant", "a", "").unwrap()),
+        ),
+    ] {
+        let value = report(text, width, options);
+        assert_eq!(value["softBreaks"], soft_breaks, "{options}");
+        let rules = match options {
+            "" => KinsokuRules::new(CjkLocale::Auto.resolve(text), "", ""),
+            _ => KinsokuRules::new(CjkLocale::ZhHant, "a", ""),
+        };
+        assert_eq!(value["locale"], rules.locale().tag());
+        for (field, forbidden_field, forbids) in [
+            (
+                "lineStarts",
+                "forbiddenStarts",
+                &(|c| rules.forbids_start(c)) as &dyn Fn(char) -> bool,
+            ),
+            ("lineEnds", "forbiddenEnds", &|c| rules.forbids_end(c)),
+        ] {
+            let edges = edges(&value[field]);
+            assert!(edges.iter().map(|edge| edge.1).sum::<u64>() <= soft_breaks);
+            assert!(edges.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            let flagged: u64 = edges.iter().filter(|edge| edge.2).map(|edge| edge.1).sum();
+            assert_eq!(value[forbidden_field], flagged);
+            for (c, _, forbidden) in &edges {
+                let mut chars = c.chars();
+                let c = chars.next().unwrap();
+                assert!(chars.next().is_none());
+                assert_eq!(*forbidden, forbids(c), "{c:?} in {field}");
+            }
+        }
+    }
+}
+
+/// format_tsv lines fit the width and every table block has one line per row (plus the header
+/// rule), with an empty line between blocks
+fn check_tsv(input: &FuzzInput) {
+    let (text, width) = (&input.text, input.max_chars_per_line.max(1));
+    let header = TsvOptions {
+        header: true,
+        ..TsvOptions::default()
+    };
+    let wrap = TsvOptions {
+        overflow: TsvOverflow::Wrap,
+        ..TsvOptions::default()
+    };
+    let row_count = text.split('\n').count();
+    for (options, rule) in [(TsvOptions::default(), 0), (header, 1), (wrap, 0)] {
+        let output = format_tsv(text, width, &options);
+        let lines: Vec<&str> = output.split("\r\n").collect();
+        for line in &lines {
+            let line_width = calculate_text_width(line);
+            assert!(
+                line_width <= u64::from(width),
+                "format_tsv line {line:?} is {line_width} columns, over {width}"
+            );
+        }
+        let block_lines = row_count + rule;
+        assert_eq!(
+            (lines.len() + 1) % (block_lines + 1),
+            0,
+            "format_tsv {options:?} gave {} lines for {row_count} rows",
+            lines.len()
+        );
+        if options.overflow == TsvOverflow::Drop {
+            assert_eq!(lines.len(), block_lines, "format_tsv {options:?}");
+        }
+    }
+}
+
+/// Options with a field another one overrides, and the same options without it; the rows of
+/// OPTION_CONFLICTS that are warned about rather than rejected
+const IGNORED_CONFLICTS: [(&str, &str); 7] = [
+    (r#"{"numberStart": 5}"#, "{}"),
+    (
+        r#"{"markdown": true, "trimTrailing": false}"#,
+        r#"{"trimTrailing": false}"#,
+    ),
+    (r#"{"footerAlign": "left", "footerBlankLines": 3}"#, "{}"),
+    (
+        r#"{"algorithmVersion": 1, "punctuationBreakWindow": 2}"#,
+        r#"{"algorithmVersion": 1}"#,
+    ),
+    (
+        r#"{"algorithmVersion": 3, "breakAfterChars": "-"}"#,
+        r#"{"algorithmVersion": 3}"#,
+    ),
+    (r#"{"listMarkerSpacing": "space"}"#, "{}"),
+    (
+        r#"{"algorithmVersion": 6, "signatureDelimiter": "--"}"#,
+        r#"{"algorithmVersion": 6}"#,
+    ),
+];
+
+/// An ignored field changes nothing: the options justify any text exactly as the options
+/// without it do
+fn check_option_conflicts(text: &str) {
+    for (options_json, resolved_json) in IGNORED_CONFLICTS {
+        let options = JustifyOptions::from_json(options_json).expect(options_json);
+        let resolved = JustifyOptions::from_json(resolved_json).expect(resolved_json);
+        assert_eq!(
+            options.justify(text),
+            resolved.justify(text),
+            "{options_json} on {text:?}"
+        );
+    }
+}
+
+/// Zero spacing changes nothing, and with spacing every line layout_text_with_spacing draws
+/// still fits unless it is one unbreakable word, with char_advances ending at its widthPx;
+/// the chunk size field picks the letter and word spacing
+fn check_spacing(input: &FuzzInput) {
+    let (text, width) = (&input.text, input.max_chars_per_line.max(1));
+    assert_eq!(
+        justify_text_with_spacing(text, width, Spacing::default()),
+        justify_text(text, width),
+        "zero spacing changed justify_text"
+    );
+    assert_eq!(
+        justify_text_fractional_with_spacing(text, 10.0, Spacing::default()),
+        justify_text_fractional(text, 10.0),
+        "zero spacing changed justify_text_fractional"
+    );
+
+    let spacing = Spacing::new(
+        f64::from(input.chunk_size % 4) * 0.05,
+        f64::from(input.chunk_size / 4 % 4) * 0.1,
+    )
+    .expect("valid spacing");
+    let font_size_px = 16.0;
+    let block_width_px = f64::from(width) * font_size_px / 2.0;
+    assert_no_loss(
+        "justify_text_with_spacing",
+        text,
+        &justify_text_with_spacing(text, width, spacing),
+    );
+    let layout: serde_json::Value = serde_json::from_str(&layout_text_with_spacing(
+        text,
+        width,
+        font_size_px,
+        1.5,
+        0.0,
+        "left",
+        1.0,
+        spacing,
+    ))
+    .expect("layout_text_with_spacing returned invalid JSON");
+    for line in layout.as_array().expect("layout is an array") {
+        let line_text = line["text"].as_str().expect("text is a string");
+        let width_px = line["widthPx"].as_f64().expect("widthPx is a number");
+        assert!(
+            width_px <= block_width_px + 1e-6 || !line_text.trim().contains(char::is_whitespace),
+            "{spacing:?} line {line_text:?} is {width_px}px, over {block_width_px}px"
+        );
+        if let Some(last) = line_text.chars().last() {
+            let advances = char_advances_with_spacing(line_text, font_size_px, spacing);
+            let end = f64::from(*advances.last().expect("one advance per character"))
+                + (f64::from(get_char_width(last)) / 2.0 + spacing.extra_em(last)) * font_size_px;
+            assert!(
+                (end - width_px).abs() <= 1e-3 * width_px.max(1.0),
+                "char_advances of {line_text:?} end at {end}px, widthPx is {width_px}px"
+            );
+        }
+    }
+}
+
+/// layout_text_with_guides keeps layout_text_with_spacing's lines and gives every line
+/// max_chars_per_line less its width as slack (0 for a full line), with the spacing counted as
+/// it is when wrapping
+fn check_guides(input: &FuzzInput) {
+    let guided = |text: &str, width, spacing, guide_columns| -> serde_json::Value {
+        serde_json::from_str(&layout_text_with_guides(
+            text,
+            width,
+            20.0,
+            1.5,
+            5.0,
+            "center",
+            1.0,
+            spacing,
+            guide_columns,
+        ))
+        .expect("layout_text_with_guides returned invalid JSON")
+    };
+    let (text, width) = (&input.text, input.max_chars_per_line.max(1));
+    let spacing = Spacing::new(f64::from(input.chunk_size % 4) * 0.05, 0.0).expect("valid spacing");
+    let layout = guided(text, width, spacing, input.chunk_size % 16);
+    let lines: serde_json::Value = serde_json::from_str(&layout_text_with_spacing(
+        text, width, 20.0, 1.5, 5.0, "center", 1.0, spacing,
+    ))
+    .expect("layout_text_with_spacing returned invalid JSON");
+    assert_eq!(
+        layout["lines"], lines,
+        "guides changed the lines of {text:?}"
+    );
+    let slack = layout["guides"]["slack"]
+        .as_array()
+        .expect("slack is an array");
+    let lines = lines.as_array().expect("layout is an array");
+    assert_eq!(slack.len(), lines.len());
+    for (line, slack) in lines.iter().zip(slack) {
+        let line_text = line["text"].as_str().expect("text is a string");
+        let columns =
+            calculate_text_width(line_text) as f64 + 2.0 * spacing.extra_em_str(line_text);
+        let slack = slack.as_f64().expect("slack is a number");
+        assert!(
+            (slack - (f64::from(width) - columns)).abs() < 1e-9,
+            "slack of {line_text:?} at {width} is {slack}"
+        );
+        if spacing.is_zero() {
+            assert_eq!(
+                slack == 0.0,
+                calculate_text_width(line_text) == u64::from(width)
+            );
+        }
+    }
+}
+
+/// split_paragraphs covers every source line once, in order, each segment's text being its
+/// lines; blank runs sit between other segments and give their length to the next one's
+/// blankLinesBefore; paragraphs joins what is between them
+fn check_paragraphs(text: &str) {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line_count = lines.len() - usize::from(text.is_empty() || text.ends_with('\n'));
+    let segments = paragraph_segments(text);
+    let mut next_line = 0;
+    // First and last line of each paragraph
+    let mut joined: Vec<(usize, usize)> = Vec::new();
+    let mut blank_run = 0;
+    for segment in &segments {
+        assert_eq!(segment.src_start_line, next_line, "segments of {text:?}");
+        assert!(segment.src_end_line >= segment.src_start_line);
+        let source = lines[segment.src_start_line..=segment.src_end_line].join("\n");
+        assert_eq!(
+            segment.text,
+            source.strip_suffix('\r').unwrap_or(&source),
+            "segment text of {text:?}"
+        );
+        next_line = segment.src_end_line + 1;
+        if segment.kind == ParagraphKind::Blank {
+            assert_eq!(blank_run, 0, "adjacent blank segments in {text:?}");
+            blank_run = next_line - segment.src_start_line;
+            continue;
+        }
+        assert_eq!(
+            segment.blank_lines_before, blank_run,
+            "blankLinesBefore in {text:?}"
+        );
+        match joined.last_mut() {
+            Some((_, end)) if blank_run == 0 => *end = segment.src_end_line,
+            _ => joined.push((segment.src_start_line, segment.src_end_line)),
+        }
+        blank_run = 0;
+    }
+    assert_eq!(next_line, line_count, "lines covered in {text:?}");
+    let joined: Vec<(usize, String)> = joined
+        .into_iter()
+        .map(|(start, end)| {
+            let source = lines[start..=end].join("\n");
+            (
+                start,
+                source.strip_suffix('\r').unwrap_or(&source).to_string(),
+            )
+        })
+        .collect();
+    let paragraphs: Vec<(usize, String)> = paragraphs(text)
+        .into_iter()
+        .map(|(line, paragraph)| (line, paragraph.to_string()))
+        .collect();
+    assert_eq!(paragraphs, joined, "paragraphs of {text:?}");
+}
+
+/// Each layout_text line starts where the one above ends, the line above being heightFactor
+/// line heights tall, heightFactor is 1, the CJK or the emoji factor, estimate_image_size's
+/// height ends where the last line does, and paginate_for_images blocks stay under the height
+/// they were split at
+fn check_line_heights(input: &FuzzInput) {
+    let (font_size_px, line_height_factor, padding_px) = (16.0, 1.5, 8.0);
+    let line_height_px = font_size_px * line_height_factor;
+    let heights = ScriptLineHeights::current();
+    let layout_and_height = |text: &str, width: u32| {
+        let layout: serde_json::Value = serde_json::from_str(&layout_text(
+            text,
+            width,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            "left",
+        ))
+        .unwrap();
+        let size: serde_json::Value = serde_json::from_str(&estimate_image_size(
+            text,
+            width,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+        ))
+        .unwrap();
+        let lines: Vec<(f64, f64)> = layout
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|line| {
+                (
+                    line["yPx"].as_f64().unwrap(),
+                    line["heightFactor"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        (lines, size["heightPx"].as_f64().unwrap())
+    };
+    let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 * a.abs().max(b.abs()).max(1.0);
+
+    let width = input.max_chars_per_line.clamp(1, 200);
+    let (lines, height_px) = layout_and_height(&input.text, width);
+    let mut bottom_px = padding_px;
+    for &(y_px, factor) in &lines {
+        assert!(
+            [1.0, heights.cjk, heights.emoji].contains(&factor),
+            "heightFactor {factor} of {:?}",
+            input.text
+        );
+        assert!(
+            close(y_px, bottom_px),
+            "yPx {y_px} after a line ending at {bottom_px}"
+        );
+        bottom_px = y_px + factor * line_height_px;
+    }
+    if !lines.is_empty() {
+        assert!(
+            close(height_px, bottom_px + padding_px),
+            "heightPx {height_px} of {:?}",
+            input.text
+        );
+    }
+
+    let max_height_px = line_height_px * 1.3 * 10.0;
+    let images: serde_json::Value = serde_json::from_str(&paginate_for_images(
+        &input.text,
+        width,
+        font_size_px,
+        line_height_factor,
+        max_height_px,
+        input.chunk_size % 2 == 1,
+    ))
+    .unwrap();
+    for image in images.as_array().unwrap() {
+        let image_height_px = image["heightPx"].as_f64().unwrap();
+        assert!(
+            image_height_px <= max_height_px + 1e-6,
+            "image {image_height_px}px high split at {max_height_px}px"
+        );
+    }
+}
+
+/// Document::layout_range requests of the chunk size field's length, stitched together, are
+/// the same Document's layout, with and without Aozora ruby; a single line comes back as it does
+/// in the full layout, and a range past the end is cut to the lines there are
+fn check_layout_range(input: &FuzzInput) {
+    let (font_size_px, line_height_factor, padding_px) = (16.0, 1.5, 8.0);
+    let width = input.max_chars_per_line;
+    let step = input.chunk_size % 7 + 1;
+    let plain = Document::new(
+        input.text.clone(),
+        JustifyOptions {
+            max_chars_per_line: width,
+            ..JustifyOptions::default()
+        },
+    );
+    let aozora = Document::new(
+        input.text.clone(),
+        JustifyOptions {
+            max_chars_per_line: width,
+            ruby_syntax: RubySyntax::Aozora,
+            ..JustifyOptions::default()
+        },
+    );
+    for document in [&plain, &aozora] {
+        let expected = serde_json::to_string(&document.layout(
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            Align::Center,
+        ))
+        .unwrap();
+        let count = document.line_count();
+        let range = |first: u32, last: u32| {
+            document.layout_range(
+                first,
+                last,
+                font_size_px,
+                line_height_factor,
+                padding_px,
+                Align::Center,
+            )
+        };
+
+        let mut stitched = Vec::new();
+        let mut first = 0;
+        while first < count {
+            let part = range(first, first + step - 1);
+            assert_eq!(
+                (part.first_line, part.last_line, part.line_count),
+                (first, Some((first + step).min(count) - 1), count)
+            );
+            stitched.extend(part.lines);
+            first += step;
+        }
+        assert_eq!(
+            serde_json::to_string(&stitched).unwrap(),
+            expected,
+            "ranges of {step} lines"
+        );
+
+        if let Some(last) = count.checked_sub(1) {
+            assert_eq!(range(last, last).lines[..], stitched[last as usize..]);
+        }
+        let past = range(count.saturating_sub(2), u32::MAX);
+        assert_eq!(
+            (past.first_line, past.last_line),
+            (count.saturating_sub(2), count.checked_sub(1))
+        );
+        let beyond = range(count + 3, count + 5);
+        assert_eq!((beyond.first_line, beyond.last_line), (count, None));
+        assert!(beyond.lines.is_empty());
+    }
+}
+
+/// Documents of the fuzzed text built under different line height settings keep their own,
+/// whatever is set between their calls, until set_line_heights
+fn check_instance_settings(input: &FuzzInput) {
+    let (font_size_px, line_height_factor, padding_px) = (16.0, 1.5, 8.0);
+    let saved = RuntimeConfig::current(false, 0, &FontMetrics::default());
+    let text = format!("你好世界\nHello 😀\n{}", input.text);
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line.clamp(1, MAX_MAX_CHARS_PER_LINE),
+        ..JustifyOptions::default()
+    };
+    // Each line's heightFactor as heights give it, layout_range agreeing with layout
+    let assert_heights = |document: &Document, heights: ScriptLineHeights| {
+        let layout = document.layout(font_size_px, line_height_factor, padding_px, Align::Left);
+        let range = document.layout_range(
+            0,
+            u32::MAX,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            Align::Left,
+        );
+        assert_eq!(range.lines, layout, "layout_range of the whole Document");
+        assert!(layout.len() >= 2);
+        for line in &layout {
+            assert_eq!(
+                line.height_factor,
+                heights.of_line(&line.text),
+                "{:?}",
+                line.text
+            );
+        }
+    };
+
+    let first_heights = ScriptLineHeights::checked(1.5, 1.75).expect("positive factors");
+    let second_heights = ScriptLineHeights::checked(2.5, 3.0).expect("positive factors");
+    set_cjk_line_height_factor(first_heights.cjk);
+    set_emoji_line_height_factor(first_heights.emoji);
+    let first = Document::new(text.clone(), options.clone());
+    set_cjk_line_height_factor(second_heights.cjk);
+    set_emoji_line_height_factor(second_heights.emoji);
+    let mut second = Document::new(text.clone(), options.clone());
+    assert_eq!(first.line_heights(), first_heights);
+    assert_eq!(second.line_heights(), second_heights);
+    for (cjk, emoji) in [(1.0, 1.0), (4.0, 2.0), (1.5, 1.75)] {
+        set_cjk_line_height_factor(cjk);
+        set_emoji_line_height_factor(emoji);
+        assert_heights(&second, second_heights);
+        assert_heights(&first, first_heights);
+    }
+    let heights = ScriptLineHeights::checked(1.25, 1.5).expect("positive factors");
+    second.set_line_heights(heights);
+    assert_eq!(second.line_heights(), heights);
+    assert_heights(&second, heights);
+    assert_heights(&first, first_heights);
+    saved.apply_limits();
+    assert_eq!(
+        RuntimeConfig::current(false, 0, &FontMetrics::default()).to_json(),
+        saved.to_json()
+    );
+}
+
+/// An image just under LIMIT_WARNING_RATIO of a canvas limit gets no warning, and one just over
+/// gets warnings whose suggested width, applied, clears every limit, and whose split makes
+/// exactly imageCount images that each do; the chunk size field picks which limit is tight
+fn check_canvas_limits(input: &FuzzInput) {
+    let (text, width) = (&input.text, input.max_chars_per_line.max(1));
+    let (font_size_px, line_height_factor, padding_px) = (16.0, 1.5, 8.0);
+    let estimate = |width: u32, limits: &CanvasLimits| -> serde_json::Value {
+        let json = estimate_image_size_with_limits(
+            text,
+            width,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            limits,
+        );
+        serde_json::from_str(&json).expect("estimate_image_size returned invalid JSON")
+    };
+    let size = estimate(width, &CanvasLimits::default());
+    let (width_px, height_px) = (
+        size["widthPx"].as_f64().unwrap(),
+        size["heightPx"].as_f64().unwrap(),
+    );
+    let limit_at = |actual: f64, share: f64| actual / LIMIT_WARNING_RATIO / share;
+    let tight = |share: f64| {
+        let unlimited = CanvasLimits {
+            max_height_px: f64::MAX,
+            max_width_px: f64::MAX,
+            max_total_pixels: f64::MAX,
+        };
+        match input.chunk_size % 3 {
+            0 => CanvasLimits {
+                max_height_px: limit_at(height_px, share),
+                ..unlimited
+            },
+            1 => CanvasLimits {
+                max_width_px: limit_at(width_px, share),
+                ..unlimited
+            },
+            _ => CanvasLimits {
+                max_total_pixels: limit_at(width_px * height_px, share),
+                ..unlimited
+            },
+        }
+    };
+
+    let under = estimate(width, &tight(0.999));
+    assert_eq!(
+        under["warnings"],
+        serde_json::json!([]),
+        "warnings just under a limit"
+    );
+
+    let limits = tight(1.001);
+    let over = estimate(width, &limits);
+    let warnings = over["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1, "warnings just over a limit: {over}");
+    let suggestion = &warnings[0]["suggestion"];
+    if let Some(suggested) = suggestion["maxCharsPerLine"].as_u64() {
+        let applied = estimate(suggested as u32, &limits);
+        assert_eq!(
+            applied["warnings"],
+            serde_json::json!([]),
+            "suggested width {suggested} for {limits:?}"
+        );
+    }
+    if let Some(split_height_px) = suggestion["splitHeightPx"].as_f64() {
+        let images: serde_json::Value = serde_json::from_str(&paginate_for_images(
+            text,
+            width,
+            font_size_px,
+            line_height_factor,
+            split_height_px,
+            false,
+        ))
+        .unwrap();
+        let images = images.as_array().unwrap();
+        assert_eq!(
+            suggestion["imageCount"],
+            images.len(),
+            "images for {limits:?}"
+        );
+        for image in images {
+            let image_height_px = image["heightPx"].as_f64().unwrap() + 2.0 * padding_px;
+            assert!(
+                image_height_px < limits.max_height_px * LIMIT_WARNING_RATIO
+                    && width_px * image_height_px < limits.max_total_pixels * LIMIT_WARNING_RATIO,
+                "split image {image_height_px}px high for {limits:?}"
+            );
+        }
+    }
+}
+
+/// With maxUnbreakableRun a line is never wider than the line width or the cap, except one
+/// holding a single character; the chunk size field picks the cap. Version 2 measures every
+/// character the way calculate_text_width does
+fn check_unbreakable_runs(input: &FuzzInput) {
+    let text = &input.text;
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line,
+        max_unbreakable_run: input.chunk_size % 64 + 1,
+        algorithm_version: 2,
+        ..JustifyOptions::default()
+    };
+    let output = options.justify(text);
+    assert_no_loss(
+        "maxUnbreakableRun",
+        &limit_marks(text, options.max_marks_per_cluster),
+        &output,
+    );
+    let bound = u64::from(options.max_chars_per_line.max(options.max_unbreakable_run));
+    for line in output.split("\r\n") {
+        let width = calculate_text_width(line);
+        assert!(
+            width <= bound || line.trim().chars().count() == 1,
+            "line {line:?} is {width} columns with maxUnbreakableRun {}",
+            options.max_unbreakable_run
+        );
+    }
+}
+
+/// On the fuzzed text, each character repeated as the chunk size picks, limit_repeats is
+/// idempotent and leaves no longer run than the limit
+fn check_char_repeat(input: &FuzzInput) {
+    let times = input.chunk_size as usize % 12 + 1;
+    let repeated: String = input
+        .text
+        .chars()
+        .flat_map(|c| std::iter::repeat_n(c, times))
+        .collect();
+    let max = input.max_chars_per_line % 12;
+    let limited = limit_repeats(&repeated, max);
+    assert_eq!(
+        limit_repeats(&limited, max),
+        limited,
+        "limit_repeats of {repeated:?} twice"
+    );
+    if max > 0 {
+        assert!(TextStats::of(&limited).longest_char_repeat <= max as usize);
+    }
+    assert_eq!(limited.split('\n').count(), repeated.split('\n').count());
+}
+
+/// Typing the text char by char after a prefix the chunk size picks, stats_after_append keeps
+/// giving TextStats::of every step, and stats_incremental_json gives get_text_stats whether or
+/// not the old length matches
+fn check_stats_after_append(input: &FuzzInput) {
+    let text = &input.text;
+    let mut cut = input.chunk_size as usize % (text.len() + 1);
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let prefix = &text[..cut];
+    let mut stats = TextStats::of(prefix);
+    let mut ends: Vec<usize> = text[cut..]
+        .char_indices()
+        .map(|(i, c)| cut + i + c.len_utf8())
+        .take(64)
+        .collect();
+    ends.push(text.len());
+    for end in ends {
+        stats = stats_after_append(&stats, &text[..end]);
+        assert_eq!(
+            stats,
+            TextStats::of(&text[..end]),
+            "stats_after_append of {:?} after {:?}",
+            &text[cut..end],
+            prefix
+        );
+    }
+
+    let old_text_len = prefix.encode_utf16().count();
+    for len in [old_text_len, old_text_len + 1] {
+        assert_eq!(
+            stats_incremental_json(&get_text_stats(prefix), len, text).as_deref(),
+            Ok(get_text_stats(text).as_str()),
+            "stats_incremental_json after {prefix:?} with old length {len}"
+        );
+    }
+}
+
+/// On the fuzzed text with marks piled onto every character, no run of marks in the output is
+/// over maxMarksPerCluster, and limiting twice is limiting once
+fn check_marks(input: &FuzzInput) {
+    let marks = input.chunk_size as usize % 24;
+    let piled: String = input
+        .text
+        .chars()
+        .flat_map(|c| std::iter::once(c).chain(std::iter::repeat_n('\u{301}', marks)))
+        .collect();
+    let max = input.max_chars_per_line % 12;
+    let limited = limit_marks(&piled, max);
+    assert_eq!(
+        limit_marks(&limited, max),
+        limited,
+        "limit_marks of {piled:?} twice"
+    );
+    assert_eq!(limited.split('\n').count(), piled.split('\n').count());
+    let output = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line.max(1),
+        max_marks_per_cluster: max,
+        ..JustifyOptions::default()
+    }
+    .justify(&piled);
+    if max > 0 {
+        let (_, longest) = output.chars().fold((0, 0), |(run, longest), c| {
+            let run = if ('\u{300}'..='\u{36F}').contains(&c) {
+                run + 1
+            } else {
+                0
+            };
+            (run, longest.max(run))
+        });
+        assert!(
+            longest <= max as usize,
+            "{longest} marks in a row with maxMarksPerCluster {max}"
+        );
+    }
+}
+
+/// With preserveSeparatorChars every line wrapped out of a word-wrapped source line is a
+/// piece of it, whitespace and all
+fn check_preserve_separators(input: &FuzzInput) {
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line,
+        preserve_separator_chars: true,
+        ..JustifyOptions::default()
+    };
+    for line in input.text.split('\n') {
+        if line.contains('\r') || is_cjk(line) {
+            continue;
+        }
+        let output = options.justify(line);
+        assert_no_loss(
+            "preserveSeparatorChars",
+            &limit_marks(line, options.max_marks_per_cluster),
+            &output,
+        );
+        for wrapped in output.split("\r\n") {
+            assert!(
+                line.contains(wrapped),
+                "preserveSeparatorChars line {wrapped:?} isn't part of {line:?}"
+            );
+        }
+    }
+}
+
+/// fits_in_width agrees with justify_text giving one line within the width, and an overflow
+/// index is inside the text
+fn check_fit(input: &FuzzInput) {
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+    let output = justify_text(text, width);
+    let one_line = !output.contains("\r\n") && calculate_text_width(&output) <= u64::from(width);
+    assert_eq!(
+        fits_in_width(text, width),
+        one_line,
+        "fits_in_width at {width}"
+    );
+    if let Some(index) = first_overflow_index(text, width) {
+        assert!(
+            index < text.chars().count(),
+            "overflow index {index} past the text"
+        );
+    }
+}
+
+/// clean_text_with counts every variation selector it drops
+fn check_variation_selectors(text: &str) {
+    let is_selector = |c: char| matches!(c, '\u{FE0E}' | '\u{FE0F}');
+    let report = clean_text_with(text, &CleanOptions::default());
+    let kept = report.text.chars().filter(|&c| is_selector(c)).count();
+    let before = clean_text_with(
+        text,
+        &CleanOptions {
+            strip_stray_variation_selectors: false,
+            ..CleanOptions::default()
+        },
+    );
+    let all = before.text.chars().filter(|&c| is_selector(c)).count();
+    assert_eq!(
+        kept + report.variation_selectors_removed,
+        all,
+        "variation selectors miscounted"
+    );
+}
+
+/// On the fuzzed text format_stats' raw numbers are get_text_stats'
+fn check_format_stats(text: &str) {
+    let fields = ["charCount", "wordCount", "lineCount", "displayWidth"];
+    let stats: serde_json::Value = serde_json::from_str(&get_text_stats(text)).unwrap();
+    let formatted: serde_json::Value =
+        serde_json::from_str(&format_stats(text, "en").unwrap()).unwrap();
+    for field in fields {
+        assert_eq!(formatted[format!("{field}Raw")], stats[field], "{field}");
+    }
+}
+
+/// A width of u32::MAX is a WidthTooLarge error from every fallible entry point, and the
+/// infallible ones wrap without allocating for it (a footer is padded to at most
+/// MAX_MAX_CHARS_PER_LINE columns)
+fn check_huge_width(text: &str) {
+    let huge = u32::MAX;
+    let too_large = WebtoolsError::WidthTooLarge {
+        max: MAX_MAX_CHARS_PER_LINE,
+        actual: huge,
+    };
+    let options = JustifyOptions {
+        max_chars_per_line: huge,
+        ..JustifyOptions::default()
+    };
+    assert!(options.validation_errors().contains(&too_large));
+    assert_eq!(
+        process_text_chunks(text, huge, huge),
+        Err(too_large.clone())
+    );
+    assert_eq!(
+        justify_text_cjk_locale(text, huge, "ja", "", ""),
+        Err(too_large.clone())
+    );
+    assert_eq!(format_tsv_json(text, huge, ""), Err(too_large.clone()));
+    assert_eq!(edge_char_report(text, huge, ""), Err(too_large.clone()));
+    assert!(validate_input_report(text, huge)
+        .errors
+        .contains(&too_large));
+
+    assert_no_loss("justify_text at u32::MAX", text, &justify_text(text, huge));
+    for align in Align::ALL {
+        let footer = JustifyOptions {
+            footer_text: "footer".into(),
+            footer_align: *align,
+            ..options.clone()
+        }
+        .justify(text);
+        let line = footer.rsplit("\r\n").next().unwrap_or_default();
+        assert!(
+            line.len() <= MAX_MAX_CHARS_PER_LINE as usize,
+            "footer aligned {align:?} at u32::MAX is {} bytes",
+            line.len()
+        );
+    }
+}
+
+/// Every transform_case mode keeps the CJK characters as they are, and one that doesn't report
+/// widthChanged keeps the display width
+fn check_case(text: &str) {
+    let cjk = |text: &str| -> String { text.chars().filter(|&c| is_cjk_char(c)).collect() };
+    for &mode in CaseMode::ALL {
+        let transformed = transform_case(text, mode);
+        assert_eq!(
+            cjk(&transformed.text),
+            cjk(text),
+            "transform_case {mode:?} changed CJK in {text:?}"
+        );
+        assert!(
+            transformed.width_changed
+                || calculate_text_width(&transformed.text) == calculate_text_width(text),
+            "transform_case {mode:?} changed the width of {text:?} without widthChanged"
+        );
+    }
+}
+
+/// raggedness of the fuzzed text is in [0, 1]; suggest_width picks inside the range, the widest
+/// of equally ragged widths, never a more ragged width than the widest and never one giving
+/// more lines than SUGGEST_WIDTH_MAX_LINE_INCREASE allows
+fn check_raggedness(input: &FuzzInput) {
+    let text = &input.text;
+    let width = input.max_chars_per_line % 200 + 1;
+    let ragged = raggedness(text, width);
+    assert!((0.0..=1.0).contains(&ragged), "raggedness {ragged}");
+    let (min, max) = (width, width + input.chunk_size % 40);
+    let suggested = suggest_width(text, min, max);
+    assert!(
+        (min..=max).contains(&suggested),
+        "{suggested} outside {min}..={max}"
+    );
+    assert!(raggedness(text, suggested) <= raggedness(text, max));
+    let widest = u64::from(count_wrapped_lines(text, max));
+    assert!(
+        u64::from(count_wrapped_lines(text, suggested)) * 100
+            <= widest * u64::from(100 + SUGGEST_WIDTH_MAX_LINE_INCREASE),
+        "suggest_width picked {suggested} in {min}..={max}"
+    );
+}
+
+/// On the fuzzed text at the fuzzed width width_prefix and width_suffix are a prefix and a
+/// suffix that fit that width, width_prefix_len counts the prefix's chars, a wider budget never
+/// keeps less, and text that fits is kept whole
+fn check_width_prefix(input: &FuzzInput) {
+    let text = &input.text;
+    let width = input.max_chars_per_line % 256;
+    let prefix = width_prefix(text, width);
+    let suffix = width_suffix(text, width);
+    assert!(text.starts_with(prefix) && text.ends_with(suffix));
+    assert_eq!(width_prefix(prefix, width), prefix, "{text:?} at {width}");
+    assert_eq!(width_suffix(suffix, width), suffix, "{text:?} at {width}");
+    assert_eq!(
+        width_prefix_len(text, width) as usize,
+        prefix.chars().count()
+    );
+    assert!(width_prefix(text, width + 1).starts_with(prefix));
+    assert!(width_suffix(text, width + 1).ends_with(suffix));
+    let all = u32::try_from(calculate_text_width(text)).unwrap_or(u32::MAX);
+    assert_eq!(width_prefix(text, all), text.as_str());
+    assert_eq!(width_suffix(text, all), text.as_str());
+}
+
+/// remaining_budget counts text as chars(), UTF-16 and calculate_text_width do; truncate_to_budget
+/// leaves text within the limit as it is, and otherwise keeps a prefix up to a grapheme boundary
+/// plus the ellipsis, within the limit
+fn check_budget(input: &FuzzInput) {
+    let text = &input.text;
+    let limit = input.max_chars_per_line;
+    let signed = |count: usize| limit as i32 - count as i32;
+    assert_eq!(
+        remaining_budget(text, limit, CountingMode::Chars),
+        signed(text.chars().count())
+    );
+    assert_eq!(
+        remaining_budget(text, limit, CountingMode::Utf16),
+        signed(text.encode_utf16().count())
+    );
+    assert_eq!(
+        remaining_budget(text, limit, CountingMode::DisplayWidth),
+        signed(calculate_text_width(text) as usize)
+    );
+    for &mode in CountingMode::ALL {
+        let truncated = truncate_to_budget(text, limit, mode, "…");
+        assert!(
+            remaining_budget(&truncated, limit, mode) >= 0,
+            "truncate_to_budget {mode:?} to {limit} gave {truncated:?}, over the limit"
+        );
+        if remaining_budget(text, limit, mode) >= 0 {
+            assert_eq!(&truncated, text, "{mode:?} cut text within the limit");
+            continue;
+        }
+        let kept = truncated.strip_suffix('…').unwrap_or(&truncated);
+        let rest = text
+            .strip_prefix(kept)
+            .unwrap_or_else(|| panic!("{mode:?} kept {kept:?}, not a prefix of {text:?}"));
+        let next = rest.chars().next();
+        assert!(
+            !next.is_some_and(|c| matches!(c, '\u{0300}'..='\u{036F}' | '\u{FE0F}' | '\u{200D}')),
+            "{mode:?} cut {text:?} inside a grapheme after {kept:?}"
+        );
+    }
+}
+
+/// merge_short_lines joins nothing at a min_fill_ratio of 0, giving justify_text's output, and
+/// never loses a character of the fuzzed text
+fn check_merge(input: &FuzzInput) {
+    let merged = |text, width, ratio, separator| {
+        merge_short_lines(text, width, ratio, separator).expect("merge_short_lines failed")
+    };
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+    assert_eq!(
+        merged(text, width, 0.0, MergeSeparator::Space),
+        justify_text(text, width),
+        "merge_short_lines with nothing to join"
+    );
+    let ratio = f64::from(input.chunk_size % 101) / 100.0;
+    for separator in [MergeSeparator::Auto, MergeSeparator::Space] {
+        assert_no_loss(
+            "merge_short_lines",
+            text,
+            &merged(text, width, ratio, separator),
+        );
+    }
+}
+
+/// On the fuzzed text reflow_text never loses a character other than a line-end hyphen, and a
+/// text of one line per paragraph comes back as justify_text wraps it
+fn check_reflow(input: &FuzzInput) {
+    let reflowed = |text, width, keep_hyphens| {
+        reflow_text(text, width, keep_hyphens).expect("reflow_text failed")
+    };
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+    for keep_hyphens in [false, true] {
+        let output = reflowed(text, width, keep_hyphens);
+        let kept = |text: &str| visible(text).replace(['-', '\u{2010}'], "");
+        if keep_hyphens {
+            assert_no_loss("reflow_text", text, &output);
+        } else {
+            assert_eq!(kept(text), kept(&output), "reflow_text lost characters");
+        }
+    }
+    if !text.contains(['\n', '\r']) {
+        assert_eq!(reflowed(text, width, false), justify_text(text, width));
+    }
+}
+
+/// Runs of the fuzzed text are never empty, never next to one of their own class, and add up
+/// to its UTF-16 length, and classify_line_runs of the wrapped text adds up to each line's
+/// length
+fn check_class_runs(input: &FuzzInput) {
+    let runs = class_runs(&input.text);
+    let total: u32 = runs.iter().map(|run| run.1).sum();
+    assert_eq!(total as usize, input.text.encode_utf16().count());
+    for pair in runs.windows(2) {
+        assert_ne!(pair[0].0, pair[1].0, "two {:?} runs in a row", pair[0].0);
+    }
+    assert!(runs.iter().all(|run| run.1 > 0));
+
+    let wrapped = justify_text(&input.text, input.max_chars_per_line);
+    let lines = class_runs_by_line(&wrapped);
+    assert_eq!(lines.len(), wrapped.split("\r\n").count());
+    for (runs, line) in lines.iter().zip(wrapped.split("\r\n")) {
+        let total: u32 = runs.iter().map(|run| run.1).sum();
+        assert_eq!(total as usize, line.encode_utf16().count(), "{line:?}");
+    }
+}
+
+/// On the fuzzed text each normalize_cjk_indent mode is idempotent, remove after add is remove,
+/// leave changes nothing, and only whitespace changes
+fn check_cjk_indent(input: &FuzzInput) {
+    let normalized = |text: &str, mode| normalize_cjk_indent_with(text, mode);
+    let text = &input.text;
+    let add = normalized(text, CjkIndentMode::Add);
+    let remove = normalized(text, CjkIndentMode::Remove);
+    assert_eq!(normalized(&add, CjkIndentMode::Add), add, "add twice");
+    assert_eq!(
+        normalized(&remove, CjkIndentMode::Remove),
+        remove,
+        "remove twice"
+    );
+    assert_eq!(
+        normalized(&add, CjkIndentMode::Remove),
+        remove,
+        "remove after add"
+    );
+    assert_eq!(normalized(text, CjkIndentMode::Leave), *text);
+    assert_no_loss("normalize_cjk_indent", text, &add);
+    assert_no_loss("normalize_cjk_indent", text, &remove);
+}
+
+/// The characters visualize_whitespace replaces and what it shows for each
+const WHITESPACE_SYMBOLS: [(char, char); 10] = [
+    (' ', '·'),
+    ('\t', '→'),
+    ('\u{3000}', '□'),
+    ('\u{00A0}', '⍽'),
+    ('\u{202F}', '⍽'),
+    ('\u{200B}', '∅'),
+    ('\u{200C}', '∅'),
+    ('\u{200D}', '∅'),
+    ('\u{2060}', '∅'),
+    ('\u{FEFF}', '∅'),
+];
+
+/// visualize_whitespace maps each character of the table, puts '¶' before each line break and
+/// leaves everything else alone, so text without any of them comes back unchanged
+fn check_visualize(text: &str) {
+    let mut expected = String::new();
+    for (index, c) in text.char_indices() {
+        let second_of_crlf = c == '\n' && text[..index].ends_with('\r');
+        if matches!(c, '\r' | '\n') && !second_of_crlf {
+            expected.push('¶');
+        }
+        match WHITESPACE_SYMBOLS.iter().find(|&&(from, _)| from == c) {
+            Some(&(_, symbol)) => expected.push(symbol),
+            None => expected.push(c),
+        }
+    }
+    assert_eq!(
+        visualize_whitespace(text),
+        expected,
+        "visualize_whitespace of {text:?}"
+    );
+}
+
+/// process_text_chunks fails only for oversized input, and otherwise keeps every character
+/// whatever the chunk boundaries cut through; chunk size 0 gives justify_text's output, and
+/// recommended_chunk_size grows with the length and stays within the text
+pub fn check_chunks(input: &FuzzInput) {
+    // A clock that makes wrapping look slow (it only counts on the first call), so auto
+    // chunks cut even short texts
+    let ticks = Cell::new(0.0);
+    calibrate_chunk_cost(|| {
+        ticks.set(ticks.get() + 1e6);
+        ticks.get()
+    });
+
+    let text = &input.text;
+    let width = input.max_chars_per_line;
+    match process_text_chunks(text, width, input.chunk_size) {
+        Ok(output) => {
+            assert_no_loss("process_text_chunks", text, &output);
+            assert_line_widths("process_text_chunks", &output, width);
+            if input.chunk_size == 0 {
+                assert_eq!(output, justify_text(text, width), "auto chunks of {text:?}");
+            }
+        }
+        Err(e) => assert!(
+            text.len() > max_input_size(),
+            "process_text_chunks rejected valid input: {e}"
+        ),
+    }
+
+    let len = text.len();
+    let recommended = recommended_chunk_size(len, width) as usize;
+    assert!(
+        (1..=len.max(1)).contains(&recommended),
+        "recommended_chunk_size({len}, {width}) is {recommended}"
+    );
+    for longer in [len + 1, len * 2, len * 1_000 + 1] {
+        assert!(
+            recommended_chunk_size(longer, width) as usize >= recommended,
+            "recommended_chunk_size shrank from {len} to {longer} bytes"
+        );
+    }
+
+    check_windows(input);
+    check_stream_stats(input);
+    check_trailing_newlines(input);
+}
+
+/// StreamStats of bytes fed piece_len bytes at a time
+fn stream_stats(bytes: &[u8], piece_len: usize) -> Result<StreamedStats, WebtoolsError> {
+    let mut stats = StreamStats::new();
+    for piece in bytes.chunks(piece_len.max(1)) {
+        stats.feed(piece)?;
+    }
+    stats.finish()
+}
+
+/// Whether streamed has the counts get_text_stats gives for text
+fn assert_streamed(streamed: &StreamedStats, text: &str, what: &str) {
+    let stats = TextStats::of(text);
+    assert_eq!(
+        (
+            streamed.char_count,
+            streamed.byte_count,
+            streamed.line_count,
+            streamed.cjk_count,
+            streamed.ascii_count,
+            streamed.display_width,
+            streamed.has_cjk,
+            streamed.astral_count,
+            streamed.combining_mark_count,
+            streamed.bom_count,
+        ),
+        (
+            stats.char_count,
+            stats.byte_count,
+            stats.line_count,
+            stats.cjk_count,
+            stats.ascii_count,
+            stats.display_width,
+            stats.has_cjk,
+            stats.astral_count,
+            stats.combining_mark_count,
+            stats.bom_count,
+        ),
+        "{what} of {text:?}"
+    );
+}
+
+/// Fed in pieces of any length, StreamStats counts what get_text_stats counts for the fuzzed
+/// text, and justify_windows_with_stats gives each block the stats of the input wrapped so far
+/// and of the output, its widest line included
+fn check_stream_stats(input: &FuzzInput) {
+    let (text, width) = (&input.text, input.max_chars_per_line);
+    for piece_len in [1, 7, input.chunk_size as usize] {
+        let streamed = stream_stats(text.as_bytes(), piece_len).expect("valid input");
+        assert_streamed(&streamed, text, &format!("{piece_len}-byte pieces"));
+        assert_eq!(streamed.widest_output_line, 0);
+    }
+
+    let window = (input.chunk_size as usize).max(1);
+    let mut output = String::new();
+    let result = justify_windows_with_stats::<WebtoolsError>(
+        text.len(),
+        width,
+        window,
+        |range, buffer| buffer.extend_from_slice(&text.as_bytes()[range]),
+        |block, so_far| {
+            output.push_str(block);
+            assert!(
+                text.is_char_boundary(so_far.byte_count),
+                "{} bytes wrapped",
+                so_far.byte_count
+            );
+            assert_streamed(so_far, &text[..so_far.byte_count], "a block's stats");
+            assert_eq!(so_far.widest_output_line, max_line_width(&output));
+            Ok(())
+        },
+    );
+    let streamed = result.expect("valid input");
+    assert_streamed(&streamed, text, "justify_windows_with_stats");
+    assert_eq!(
+        streamed.widest_output_line,
+        max_line_width(&justify_text(text, width)),
+        "widest line of {text:?} at {width}"
+    );
+}
+
+/// The number of "\r\n" output ends with
+fn trailing_breaks(output: &str) -> usize {
+    let mut rest = output;
+    let mut count = 0;
+    while let Some(before) = rest.strip_suffix("\r\n") {
+        rest = before;
+        count += 1;
+    }
+    count
+}
+
+/// The fuzzed text with its trailing whitespace replaced by 0 to 3 line breaks, LF or CRLF,
+/// keeps them through process_text_chunks at its own chunk size, as justify_text does
+fn check_trailing_newlines(input: &FuzzInput) {
+    let base = input.text.trim_end();
+    let width = input.max_chars_per_line;
+    for count in 0..=3 {
+        for line_break in ["\n", "\r\n"] {
+            let text = base.to_string() + &line_break.repeat(count);
+            if let Ok(output) = process_text_chunks(&text, width, input.chunk_size) {
+                assert_eq!(
+                    trailing_breaks(&output),
+                    trailing_breaks(&justify_text(&text, width)),
+                    "process_text_chunks of {text:?} in {}s: {output:?}",
+                    input.chunk_size
+                );
+            }
+        }
+    }
+}
+
+/// justify_windows' blocks add up to justify_text, each but the last ending with a line break,
+/// while the bytes it holds stay within a window plus the longest line; invalid UTF-8 names
+/// its offset in the whole input, and a window of 0 is rejected
+fn check_windows(input: &FuzzInput) {
+    let (text, width) = (&input.text, input.max_chars_per_line);
+    let window = (input.chunk_size as usize).max(1);
+    let longest_line = text.split('\n').map(str::len).max().unwrap_or(0);
+    let windows = |bytes: &[u8], window: usize| {
+        let mut blocks = Vec::new();
+        let result = justify_windows::<WebtoolsError>(
+            bytes.len(),
+            width,
+            window,
+            |range, buffer| {
+                buffer.extend_from_slice(&bytes[range]);
+                assert!(
+                    buffer.len() <= window + longest_line + 1,
+                    "{} bytes held for a {window}-byte window",
+                    buffer.len()
+                );
+            },
+            |block| {
+                blocks.push(block.to_string());
+                Ok(())
+            },
+        );
+        result.map(|()| blocks)
+    };
+
+    let blocks = windows(text.as_bytes(), window).expect("justify_windows rejected valid input");
+    if let Some((_, earlier)) = blocks.split_last() {
+        for block in earlier {
+            assert!(block.ends_with("\r\n"), "block {block:?} of {text:?}");
+        }
+    }
+    assert_eq!(
+        blocks.concat(),
+        justify_text(text, width),
+        "justify_windows of {text:?} at {window} bytes"
+    );
+
+    let mut invalid = text.as_bytes().to_vec();
+    let offset = invalid.len() / 2;
+    let offset = (0..=offset)
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    invalid.insert(offset, 0xFF);
+    match windows(&invalid, window) {
+        Err(WebtoolsError::InvalidUtf8 { offset: at }) => assert_eq!(at, offset),
+        other => panic!("expected InvalidUtf8 at {offset}, got {other:?}"),
+    }
+    assert!(matches!(
+        windows(text.as_bytes(), 0),
+        Err(WebtoolsError::InvalidArgument { .. })
+    ));
+}
+
+/// Plain wrapping of the fuzzed text stays under the default output ratio, so
+/// justify_checked gives what justify does
+fn check_output_ratio(input: &FuzzInput) {
+    let plain = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line.clamp(1, MAX_MAX_CHARS_PER_LINE),
+        ..JustifyOptions::default()
+    };
+    assert_eq!(
+        plain.justify_checked(&input.text),
+        Ok(plain.justify(&input.text))
+    );
+}
+
+/// batch_justify_text returns one result per item, each what JustifyOptions::justify gives
+pub fn check_batch(input: &FuzzInput) {
+    let items: Vec<&str> = input.text.split(ITEM_SEPARATOR).collect();
+    let json = serde_json::to_string(&items).expect("string items always serialize");
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line,
+        ..JustifyOptions::default()
+    };
+
+    for use_cache in [false, true] {
+        let output = match batch_justify_text(&json, input.max_chars_per_line, use_cache) {
+            Ok(output) => output,
+            Err(e) => {
+                assert!(
+                    json.len() > max_batch_size()
+                        || input.text.len() > max_input_size()
+                        || items.len() > max_batch_items(),
+                    "batch_justify_text rejected valid input: {e}"
+                );
+                return;
+            }
+        };
+        let results: Vec<String> =
+            serde_json::from_str(&output).expect("batch_justify_text returned invalid JSON");
+        assert_eq!(
+            results.len(),
+            items.len(),
+            "batch_justify_text dropped items"
+        );
+        for (item, result) in items.iter().zip(&results) {
+            assert_eq!(result, &options.justify(item), "batch result for {item:?}");
+        }
+    }
+    check_batch_ids(input);
+    check_batch_json(input);
+}
+
+/// Every item twice, in an order shuffled by chunk_size, each copy with an id of its own
+/// (strings and numbers mixed): justify_batch_items hands every id back with its own item's
+/// text, in input order, with or without the cache
+pub fn check_batch_ids(input: &FuzzInput) {
+    let options = JustifyOptions {
+        max_chars_per_line: input.max_chars_per_line,
+        ..JustifyOptions::default()
+    };
+    let texts: Vec<&str> = input.text.split(ITEM_SEPARATOR).collect();
+    let mut items: Vec<BatchItem> = texts
+        .iter()
+        .chain(&texts)
+        .enumerate()
+        .map(|(index, text)| BatchItem {
+            id: Some(match index % 3 {
+                0 => BatchId::Text(format!("item-{index}")),
+                1 => BatchId::Integer(index as i64),
+                _ => BatchId::Float(index as f64 + 0.5),
+            }),
+            text: text.to_string(),
+        })
+        .collect();
+    let mut seed = u64::from(input.chunk_size) | 1;
+    for index in (1..items.len()).rev() {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        items.swap(index, (seed % (index as u64 + 1)) as usize);
+    }
+
+    for use_cache in [false, true] {
+        let results = justify_batch_items(&items, &options, use_cache);
+        assert_eq!(
+            results.len(),
+            items.len(),
+            "justify_batch_items dropped items"
+        );
+        for (item, result) in items.iter().zip(&results) {
+            assert_eq!(result.id, item.id, "result out of input order");
+            assert_eq!(
+                result.text,
+                options.justify(&item.text),
+                "result for {:?}",
+                item.id
+            );
+        }
+    }
+}
+
+/// parse_batch_json accepts exactly the arrays of strings serde_json does and fails them as
+/// check_batch_size would
+pub fn check_batch_json(input: &FuzzInput) {
+    for json in [
+        input.text.clone(),
+        serde_json::to_string(&input.text.split(ITEM_SEPARATOR).collect::<Vec<_>>()).unwrap(),
+    ] {
+        match serde_json::from_str::<Vec<String>>(&json) {
+            Ok(texts) => assert_eq!(
+                parse_batch_json(&json),
+                check_batch_size(&texts).map(|()| texts),
+                "{json:?}"
+            ),
+            Err(_) => assert!(parse_batch_json(&json).is_err(), "{json:?}"),
+        }
+    }
+}
+
+/// A text of BLANK_PIECES, picked by each byte in turn
+pub fn blank_text(data: &[u8]) -> String {
+    data.iter()
+        .map(|&byte| BLANK_PIECES[byte as usize % BLANK_PIECES.len()])
+        .collect()
+}
+
+/// A text of WIDTH_PIECES, picked by each byte in turn
+pub fn width_text(data: &[u8]) -> String {
+    data.iter()
+        .map(|&byte| WIDTH_PIECES[byte as usize % WIDTH_PIECES.len()])
+        .collect()
+}
+
+/// Every API measures a line the way the wrapper did (see core/src/width.rs): each wrapped line
+/// fits max_chars_per_line by get_text_stats' displayWidth, layout_text draws it at width ×
+/// font size / 2, and aligning or truncating it keeps the width accounted for
+pub fn check_width(text: &str, max_chars_per_line: u32) {
+    let font_size_px = 16.0;
+    let wrapped = justify_text(text, max_chars_per_line);
+    assert_line_widths("justify_text", &wrapped, max_chars_per_line);
+    let lines: Vec<&str> = match wrapped.is_empty() {
+        true => Vec::new(),
+        false => wrapped.split("\r\n").collect(),
+    };
+
+    let layout: serde_json::Value = serde_json::from_str(&layout_text(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        1.5,
+        0.0,
+        "left",
+    ))
+    .unwrap();
+    let layout = layout.as_array().expect("layout_text returns an array");
+    assert_eq!(layout.len(), lines.len(), "layout lines of {text:?}");
+    assert_eq!(
+        line_widths(&wrapped).len(),
+        lines.len(),
+        "line_widths of {text:?}"
+    );
+
+    for ((&line, entry), line_width) in lines.iter().zip(layout).zip(line_widths(&wrapped)) {
+        let width = calculate_text_width(line);
+        let chars: u64 = line.chars().map(|c| u64::from(get_char_width(c))).sum();
+        let stats: serde_json::Value = serde_json::from_str(&get_text_stats(line)).unwrap();
+        assert_eq!(chars, width, "get_char_width summed over {line:?}");
+        assert_eq!(line_width, width, "line_widths of {line:?}");
+        assert_eq!(stats["displayWidth"], width, "displayWidth of {line:?}");
+        assert_eq!(entry["text"], line, "layout text of {text:?}");
+        assert_eq!(
+            entry["widthPx"].as_f64(),
+            Some(width as f64 * font_size_px / 2.0),
+            "layout widthPx of {line:?}"
+        );
+
+        let columns = u64::from(max_chars_per_line);
+        for align in [Align::Left, Align::Center, Align::Right] {
+            let aligned = align_to_width(line, columns, align);
+            let padding = aligned.len() - line.len();
+            assert!(
+                aligned.ends_with(line) && aligned[..padding].bytes().all(|b| b == b' '),
+                "align_to_width {align:?} of {line:?} changed the line"
+            );
+            let free = columns.saturating_sub(width);
+            let expected = match align {
+                Align::Left => 0,
+                Align::Center => free / 2,
+                Align::Right => free,
+            };
+            assert_eq!(
+                padding as u64, expected,
+                "align_to_width {align:?} of {line:?}"
+            );
+        }
+        for max_columns in 0..=width {
+            let truncated = truncate_to_width(line, max_columns);
+            let next = line[truncated.len()..].chars().next();
+            assert!(
+                line.starts_with(truncated)
+                    && calculate_text_width(truncated) <= max_columns
+                    && next.is_none_or(|c| {
+                        calculate_text_width(truncated) + u64::from(get_char_width(c)) > max_columns
+                    }),
+                "truncate_to_width({line:?}, {max_columns}) gave {truncated:?}"
+            );
+        }
+        assert_eq!(truncate_to_width(line, width), line);
+        assert_eq!(slice_columns(line, 0, width as u32, false), line);
+    }
+}
+
+/// Whitespace-only text gets the same answer from every API (see is_empty_input): empty input
+/// wraps to "" with no lines and no error, and line breaks otherwise become blank lines
+pub fn check_blank(text: &str) {
+    let width = 20;
+    let empty = is_empty_input(text);
+    let breaks = text.matches('\n').count();
+    let (expected, lines) = match empty {
+        true => (String::new(), 0),
+        false => ("\r\n".repeat(breaks), breaks + 1),
+    };
+
+    let units: Vec<u16> = text.encode_utf16().collect();
+    let document = Document::new(text.to_string(), JustifyOptions::default());
+    let locale = justify_text_cjk_locale(text, width, "ja", "", "");
+    for (what, output) in [
+        ("justify_text", justify_text(text, width)),
+        ("justify_text_cjk", justify_text_cjk(text, width)),
+        (
+            "justify_text_fractional",
+            justify_text_fractional(text, 10.0),
+        ),
+        (
+            "justify_text_cjk_locale",
+            locale.expect("blank text is no error"),
+        ),
+        (
+            "JustifyOptions::justify",
+            JustifyOptions::default().justify(text),
+        ),
+        (
+            "justify_text_for_image",
+            justify_text_for_image(text, 400.0, 20.0, 0.0),
+        ),
+        (
+            "justify_text_utf16",
+            String::from_utf16_lossy(&justify_text_utf16(&units, width)),
+        ),
+        (
+            "Document::wrapped_text",
+            document.wrapped_text().to_string(),
+        ),
+    ] {
+        assert_eq!(output, expected, "{what} of {text:?}");
+    }
+    for chunk_size in 0..=text.len().max(1) as u32 {
+        let output = process_text_chunks(text, width, chunk_size).expect("blank text is no error");
+        assert_eq!(
+            output, expected,
+            "process_text_chunks of {text:?} in {chunk_size}s"
+        );
+    }
+    let json = serde_json::to_string(&[text]).expect("string items always serialize");
+    let batch = batch_justify_text(&json, width, false).expect("blank text is no error");
+    assert_eq!(
+        batch,
+        serde_json::to_string(&[&expected]).unwrap(),
+        "batch of {text:?}"
+    );
+    assert_eq!(
+        justify_text_english(text, width),
+        "",
+        "justify_text_english of {text:?}"
+    );
+    assert_eq!(
+        justify_text_html(text, width),
+        "<br>".repeat(expected.len() / 2)
+    );
+    if empty {
+        assert_eq!(
+            justify_text_numbered(text, width, 1),
+            "",
+            "numbered {text:?}"
+        );
+        assert!(
+            paginate_lines(text, width, 5, false).is_empty(),
+            "pages of {text:?}"
+        );
+        assert_eq!(
+            preview_snippet(text, width, 3, "…"),
+            "",
+            "preview of {text:?}"
+        );
+    }
+
+    for (what, count) in [
+        (
+            "count_wrapped_lines",
+            count_wrapped_lines(text, width) as usize,
+        ),
+        (
+            "count_wrapped_lines_of_output",
+            count_wrapped_lines_of_output(&expected) as usize,
+        ),
+        ("wrapped_lines", wrapped_lines(text, width).len()),
+        ("Document::line_count", document.line_count() as usize),
+        ("line_widths", line_widths(&expected).len()),
+    ] {
+        assert_eq!(count, lines, "{what} of {text:?}");
+    }
+    let layout: serde_json::Value =
+        serde_json::from_str(&layout_text(text, width, 16.0, 1.5, 0.0, "left")).unwrap();
+    assert_eq!(
+        layout.as_array().map(Vec::len),
+        Some(lines),
+        "layout of {text:?}"
+    );
+    let size: serde_json::Value =
+        serde_json::from_str(&estimate_image_size(text, width, 16.0, 1.5, 0.0)).unwrap();
+    assert_eq!(size["lineCount"], lines, "estimate_image_size of {text:?}");
+
+    let stats: serde_json::Value = serde_json::from_str(&get_text_stats(text)).unwrap();
+    assert_eq!(
+        stats["charCount"],
+        text.chars().count() - text.matches('\u{FEFF}').count()
+    );
+    assert_eq!(stats["wordCount"], 0);
+    if empty {
+        assert_eq!(stats["lineCount"], 0, "lineCount of {text:?}");
+        assert_eq!(
+            line_widths(text),
+            Vec::<u64>::new(),
+            "line_widths of {text:?}"
+        );
+        assert_eq!(validate_text(text), Err(WebtoolsError::EmptyInput));
+    } else {
+        assert_eq!(validate_text(text), Ok(()), "validate_text of {text:?}");
+    }
+}
@@ -0,0 +1,162 @@
+//! Golden outputs of the core API over a fixed corpus, to review before changing an algorithm
+//! Every document in corpus/ has a golden file in expected/ holding its get_text_stats JSON,
+//! its count_wrapped_lines and its justify_text output at each of WIDTHS. Checking compares
+//! what the core produces now with the committed goldens; a change that moves any output has
+//! to regenerate them with --update, and the golden diff shows what moved.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use text_processor_core::{count_wrapped_lines, get_text_stats, justify_text};
+
+const USAGE: &str = "\
+Usage: golden [--update]
+
+Compare the core API's output over corpus/ with the goldens in expected/.
+
+Options:
+      --update  Regenerate every golden instead, removing any without a document
+  -h, --help    Print this help";
+
+/// Line widths justify_text and count_wrapped_lines are run at, in half-width columns
+const WIDTHS: [u32; 3] = [20, 40, 80];
+
+/// Starts every section header of a golden
+const SECTION: &str = "=== ";
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf()
+}
+
+/// The golden for one document
+/// Wrapped lines are written one per line: the "\r\n" between them becomes "\n", and lone
+/// '\r' and '\n' left inside a line show as ␍ and ␊, so no two outputs give the same golden.
+fn golden(text: &str) -> String {
+    let mut golden = format!("{SECTION}get_text_stats\n{}\n", get_text_stats(text));
+    golden.push_str(&format!("{SECTION}count_wrapped_lines\n"));
+    for width in WIDTHS {
+        golden.push_str(&format!("{width}: {}\n", count_wrapped_lines(text, width)));
+    }
+    for width in WIDTHS {
+        golden.push_str(&format!("{SECTION}justify_text {width}\n"));
+        for line in justify_text(text, width).split("\r\n") {
+            golden.push_str(&line.replace('\r', "␍").replace('\n', "␊"));
+            golden.push('\n');
+        }
+    }
+    golden
+}
+
+/// File names in dir, sorted
+fn file_names(dir: &Path) -> Result<BTreeSet<String>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("{}: {}", dir.display(), e))?;
+        names.insert(entry.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+/// Where two goldens first differ, as "line N: expected .. / actual .."
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for number in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (e, a) => {
+                return format!(
+                    "line {}: expected {:?} / actual {:?}",
+                    number,
+                    e.unwrap_or("<end>"),
+                    a.unwrap_or("<end>")
+                )
+            }
+        }
+    }
+    unreachable!("two different goldens differ on some line")
+}
+
+/// Check (or with update, rewrite) every golden; Ok(false) when one is out of date
+fn run(update: bool) -> Result<bool, String> {
+    let corpus = golden_dir().join("corpus");
+    let expected = golden_dir().join("expected");
+    let documents = file_names(&corpus)?;
+    let goldens = file_names(&expected)?;
+    let mut up_to_date = true;
+
+    for name in &documents {
+        let path = corpus.join(name);
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let actual = golden(&text);
+        let golden_path = expected.join(name);
+        if update {
+            fs::write(&golden_path, &actual)
+                .map_err(|e| format!("{}: {}", golden_path.display(), e))?;
+            continue;
+        }
+        match fs::read_to_string(&golden_path) {
+            Ok(golden) if golden == actual => {}
+            Ok(golden) => {
+                println!("{}: {}", name, first_difference(&golden, &actual));
+                up_to_date = false;
+            }
+            Err(_) => {
+                println!("{}: no golden", name);
+                up_to_date = false;
+            }
+        }
+    }
+
+    for name in goldens.difference(&documents) {
+        let golden_path = expected.join(name);
+        if update {
+            fs::remove_file(&golden_path)
+                .map_err(|e| format!("{}: {}", golden_path.display(), e))?;
+        } else {
+            println!("{}: golden without a document", name);
+            up_to_date = false;
+        }
+    }
+
+    if update {
+        println!("{} goldens written", documents.len());
+    } else if up_to_date {
+        println!("{} goldens up to date", documents.len());
+    }
+    Ok(up_to_date)
+}
+
+fn main() -> ExitCode {
+    let mut update = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--update" => update = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("error: Unknown option: {}\n\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    match run(update) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!(
+                "Outputs changed; if that is intended, run with --update and commit the goldens"
+            );
+            ExitCode::FAILURE
+        }
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
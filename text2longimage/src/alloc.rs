@@ -0,0 +1,154 @@
+//! Global allocator selection and wasm memory introspection
+//! wee_alloc is opt-in (smaller .wasm, slower on allocation-heavy batch paths); the default is
+//! the standard library allocator (dlmalloc on wasm32). Debug builds wrap either one in a
+//! counting allocator so memory_stats can report allocation counters.
+
+use wasm_bindgen::prelude::*;
+
+#[cfg(debug_assertions)]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub(crate) static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    pub(crate) static DEALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    pub(crate) static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+    pub(crate) static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Forwards to the inner allocator and keeps running totals
+    pub(crate) struct Counting<A>(pub(crate) A);
+
+    fn record_alloc(size: usize) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(size: usize) {
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    unsafe impl<A: GlobalAlloc> GlobalAlloc for Counting<A> {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = self.0.alloc(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.dealloc(ptr, layout);
+            record_dealloc(layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = self.0.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                record_dealloc(layout.size());
+                record_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+}
+
+#[cfg(all(feature = "wee_alloc", debug_assertions))]
+#[global_allocator]
+static ALLOC: counting::Counting<wee_alloc::WeeAlloc> =
+    counting::Counting(wee_alloc::WeeAlloc::INIT);
+
+#[cfg(all(feature = "wee_alloc", not(debug_assertions)))]
+#[global_allocator]
+static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+
+#[cfg(all(not(feature = "wee_alloc"), debug_assertions))]
+#[global_allocator]
+static ALLOC: counting::Counting<std::alloc::System> = counting::Counting(std::alloc::System);
+
+const ALLOCATOR_NAME: &str = if cfg!(feature = "wee_alloc") {
+    "wee_alloc"
+} else {
+    "dlmalloc"
+};
+
+/// Size of one wasm memory page
+const WASM_PAGE_BYTES: usize = 65_536;
+
+/// Current size of wasm memory in bytes (0 off wasm)
+pub(crate) fn memory_bytes() -> usize {
+    memory_pages() * WASM_PAGE_BYTES
+}
+
+fn memory_pages() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// Current wasm memory usage
+/// Returns JSON {allocator, pages, bytes}; debug builds add allocations, deallocations,
+/// liveBytes and peakBytes counted since the module was instantiated
+#[wasm_bindgen]
+pub fn memory_stats() -> String {
+    let pages = memory_pages();
+    let mut json = format!(
+        r#"{{"allocator":"{}","pages":{},"bytes":{}"#,
+        ALLOCATOR_NAME,
+        pages,
+        pages * WASM_PAGE_BYTES
+    );
+
+    #[cfg(debug_assertions)]
+    {
+        use std::sync::atomic::Ordering;
+        json.push_str(&format!(
+            r#","allocations":{},"deallocations":{},"liveBytes":{},"peakBytes":{}"#,
+            counting::ALLOCATIONS.load(Ordering::Relaxed),
+            counting::DEALLOCATIONS.load(Ordering::Relaxed),
+            counting::LIVE_BYTES.load(Ordering::Relaxed),
+            counting::PEAK_BYTES.load(Ordering::Relaxed)
+        ));
+    }
+
+    json.push('}');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> serde_json::Value {
+        serde_json::from_str(&memory_stats()).unwrap()
+    }
+
+    #[test]
+    fn memory_stats_names_the_allocator() {
+        let stats = stats();
+        assert_eq!(stats["allocator"], ALLOCATOR_NAME);
+        // Off wasm there is no linear memory to measure
+        assert_eq!(stats["pages"], 0);
+        assert_eq!(stats["bytes"], 0);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn debug_builds_count_allocations() {
+        let before = stats();
+        let buffer = vec![1u8; 1 << 20];
+        let after = stats();
+        // Other tests allocate at the same time, so only lower bounds hold
+        let count = |stats: &serde_json::Value, field: &str| stats[field].as_u64().unwrap();
+        assert!(count(&after, "allocations") > count(&before, "allocations"));
+        assert!(count(&after, "peakBytes") >= 1 << 20);
+        drop(buffer);
+        assert!(count(&stats(), "deallocations") > count(&before, "deallocations"));
+    }
+}
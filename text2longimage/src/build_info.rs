@@ -0,0 +1,171 @@
+use wasm_bindgen::prelude::*;
+
+/// Cargo features this build was compiled with
+/// Keep in sync with [features] in Cargo.toml
+const FEATURES: &[(&str, bool)] = &[
+    ("json", cfg!(feature = "json")),
+    ("panic-hook", cfg!(feature = "panic-hook")),
+    ("encodings", cfg!(feature = "encodings")),
+    ("no-console", cfg!(feature = "no-console")),
+    ("normalization", cfg!(feature = "normalization")),
+    ("profiling", cfg!(feature = "profiling")),
+    ("raster", cfg!(feature = "raster")),
+    ("schema", cfg!(feature = "schema")),
+    ("simd", cfg!(feature = "simd")),
+    ("strict-checks", cfg!(feature = "strict-checks")),
+    ("wee_alloc", cfg!(feature = "wee_alloc")),
+];
+
+/// Size of the buffer self_test's memory check writes and reads back
+#[cfg(feature = "json")]
+const MEMORY_CHECK_BYTES: usize = 1 << 20;
+
+/// Names of the features in FEATURES that are enabled
+fn enabled_features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Cargo features this build was compiled with, comma-separated ("json,panic-hook,simd")
+/// A loader that ships a SIMD and a scalar build can check it picked the one it meant to.
+#[wasm_bindgen]
+pub fn features_compiled() -> String {
+    enabled_features().join(",")
+}
+
+/// Crate version of this wasm build
+#[wasm_bindgen]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+/// Newest wrapping algorithm version, the default for JustifyOptions.algorithmVersion
+/// Pin a lower one to reproduce output from earlier releases exactly
+#[wasm_bindgen]
+pub fn latest_algorithm_version() -> u32 {
+    text_processor_core::latest_algorithm_version()
+}
+
+/// Build details for bug reports
+/// Returns JSON {version, features, gitHash, buildTimestamp, unicodeVersion}; the timestamp is
+/// unix seconds, and unicodeVersion that of the character tables.
+/// Written by hand so it is available without the json feature (no value needs escaping)
+#[wasm_bindgen]
+pub fn build_info() -> String {
+    let features: Vec<String> = enabled_features()
+        .iter()
+        .map(|name| format!("\"{}\"", name))
+        .collect();
+    let build_timestamp: u64 = env!("TEXT_PROCESSOR_BUILD_TIMESTAMP").parse().unwrap_or(0);
+
+    format!(
+        r#"{{"version":"{}","features":[{}],"gitHash":"{}","buildTimestamp":{},"unicodeVersion":"{}"}}"#,
+        env!("CARGO_PKG_VERSION"),
+        features.join(","),
+        env!("TEXT_PROCESSOR_GIT_HASH"),
+        build_timestamp,
+        text_processor_core::unicode_tables_version()
+    )
+}
+
+#[cfg(feature = "json")]
+/// What this build supports, for settings panels and feature checks
+/// Returns JSON {features, options: [{kind, usedBy, values: [{name, description}]}],
+/// algorithmVersions: [{number, description}], latestAlgorithmVersion,
+/// unicode: {tables, normalization}, limits: {maxInputSize, maxBatchSize, maxBatchItems,
+/// maxOutputRatio, minMaxCharsPerLine, maxMaxCharsPerLine, canvas}, schemas: [{name, description}]}. Every
+/// option value listed is accepted where usedBy says, and every accepted one is listed;
+/// features are this module's, and schemas the kinds json_schema takes.
+#[wasm_bindgen]
+pub fn capabilities() -> String {
+    text_processor_core::capabilities()
+        .with_features(enabled_features())
+        .to_json()
+}
+
+#[cfg(feature = "json")]
+/// Run a small suite embedded in this module, for bug reports
+/// Returns JSON {passed, checks: [{name, passed, expected?, actual?}]}: a dozen fixture wraps,
+/// the width model measured every way, chunked against whole-text wrapping, and a memory
+/// check; a failed check carries what it expected and what it got. A stale or miscompiled
+/// build fails here with the same source that passes everywhere else. strict-checks builds
+/// run larger fixtures as well.
+#[wasm_bindgen]
+pub fn self_test() -> String {
+    let mut report = text_processor_core::self_test();
+    // A buffer written and read back, in memory at least that large
+    let buffer: Vec<u8> = (0..MEMORY_CHECK_BYTES).map(|i| (i % 251) as u8).collect();
+    let intact = buffer
+        .iter()
+        .enumerate()
+        .all(|(i, &b)| b == (i % 251) as u8);
+    let memory = crate::alloc::memory_bytes();
+    let big_enough = memory == 0 || memory >= MEMORY_CHECK_BYTES;
+    report.check(
+        "memory",
+        format!("intact {MEMORY_CHECK_BYTES} bytes"),
+        match (intact, big_enough) {
+            (true, true) => format!("intact {MEMORY_CHECK_BYTES} bytes"),
+            (false, _) => format!("corrupted {MEMORY_CHECK_BYTES} bytes"),
+            (true, false) => {
+                format!("intact {MEMORY_CHECK_BYTES} bytes in {memory} bytes of memory")
+            }
+        },
+    );
+    report.to_json()
+}
+
+#[cfg(feature = "schema")]
+/// JSON Schema (2020-12) document for one of the JSON outputs, to validate or generate
+/// types from; kind is one of capabilities().schemas, e.g. "layoutLines" or "error"
+#[wasm_bindgen]
+pub fn json_schema(kind: &str) -> Result<String, JsValue> {
+    text_processor_core::json_schema(kind).map_err(|e| crate::to_js_error("json_schema", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_match_the_manifest() {
+        let manifest = include_str!("../Cargo.toml");
+        let table = &manifest[manifest.find("\n[features]\n").unwrap() + 12..];
+        let table = &table[..table.find("\n[").unwrap_or(table.len())];
+        let mut declared: Vec<&str> = table
+            .lines()
+            .filter(|line| !line.starts_with('#') && line.contains(" = "))
+            .map(|line| &line[..line.find(" = ").unwrap()])
+            .filter(|&name| name != "default")
+            .collect();
+        declared.sort_unstable();
+        let mut listed: Vec<&str> = FEATURES.iter().map(|(name, _)| *name).collect();
+        listed.sort_unstable();
+        assert_eq!(listed, declared);
+    }
+
+    #[test]
+    fn build_info_is_json_naming_the_features() {
+        let info: serde_json::Value = serde_json::from_str(&build_info()).unwrap();
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        let features: Vec<&str> = info["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect();
+        assert_eq!(features.join(","), features_compiled());
+        assert_eq!(features.contains(&"json"), cfg!(feature = "json"));
+        assert!(info["gitHash"]
+            .as_str()
+            .is_some_and(|hash| !hash.is_empty()));
+        assert!(info["buildTimestamp"].is_u64());
+        assert_eq!(
+            info["unicodeVersion"],
+            text_processor_core::unicode_tables_version()
+        );
+    }
+}
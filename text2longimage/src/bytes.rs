@@ -0,0 +1,258 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+use text_processor_core::{decode_utf8, justify_text, WebtoolsError};
+
+use crate::objects::BlockSink;
+#[cfg(feature = "json")]
+use crate::objects::StatsBlockSink;
+use crate::to_js_error;
+#[cfg(feature = "json")]
+use crate::transfer::batch_justify_text_with_transfer;
+
+/// justify_text over UTF-8 bytes (e.g. a fetch() ArrayBuffer), returning UTF-8 bytes
+/// Skips the JS string round trip; with lossy = false invalid input is an error naming the byte offset
+#[wasm_bindgen]
+pub fn justify_text_bytes(
+    bytes: &[u8],
+    max_chars_per_line: u32,
+    lossy: bool,
+) -> Result<Vec<u8>, JsValue> {
+    core_api::justify_text_bytes(bytes, max_chars_per_line, lossy)
+        .map_err(|e| to_js_error("justify_text_bytes", e))
+}
+
+/// get_text_stats over UTF-8 bytes
+#[wasm_bindgen]
+pub fn get_text_stats_bytes(bytes: &[u8], lossy: bool) -> Result<String, JsValue> {
+    core_api::get_text_stats_bytes(bytes, lossy).map_err(|e| to_js_error("get_text_stats_bytes", e))
+}
+
+#[cfg(feature = "json")]
+/// batch_justify_text over a UTF-8 encoded JSON array, returning the JSON result as bytes
+/// report_transfer (default false) as for batch_justify_text
+#[wasm_bindgen]
+pub fn batch_justify_text_bytes(
+    texts_json: &[u8],
+    max_chars_per_line: u32,
+    use_cache: Option<bool>,
+    report_transfer: Option<bool>,
+) -> Result<Vec<u8>, JsValue> {
+    let use_cache = use_cache.unwrap_or(false);
+    if report_transfer.unwrap_or(false) {
+        decode_utf8(texts_json, false)
+            .and_then(|texts_json| {
+                batch_justify_text_with_transfer(&texts_json, max_chars_per_line, use_cache)
+            })
+            .map(String::into_bytes)
+    } else {
+        core_api::batch_justify_text_bytes(texts_json, max_chars_per_line, use_cache)
+    }
+    .map_err(|e| to_js_error("batch_justify_text_bytes", e))
+}
+
+#[cfg(feature = "encodings")]
+/// Decode file bytes in a legacy encoding, so they never pass through a TextDecoder with the
+/// wrong label; encoding_label is a TextDecoder label of UTF-8, GBK, Big5, Shift_JIS, EUC-KR
+/// or Windows-1252 ("utf-8", "gb2312", "sjis", "latin1", ...). Throws INVALID_ENCODING (or
+/// INVALID_UTF8) with the byte offset of the first sequence the encoding can't decode.
+#[wasm_bindgen]
+pub fn decode_bytes(bytes: &[u8], encoding_label: &str) -> Result<String, JsValue> {
+    core_api::decode_bytes(bytes, encoding_label).map_err(|e| to_js_error("decode_bytes", e))
+}
+
+#[cfg(feature = "encodings")]
+/// Best guess at the encoding of file bytes, as JSON {encoding, confidence}
+/// encoding is one of the labels decode_bytes takes; confidence is 0 to 1, 1 for valid UTF-8.
+/// Only the first 16 KB are looked at.
+#[wasm_bindgen]
+pub fn detect_encoding(bytes: &[u8]) -> String {
+    core_api::detect_encoding_json(bytes)
+}
+
+/// Why justify_text_large stopped: an error of its own, or one thrown by the sink
+enum LargeError {
+    Core(WebtoolsError),
+    Sink(JsValue),
+}
+
+impl From<WebtoolsError> for LargeError {
+    fn from(e: WebtoolsError) -> Self {
+        LargeError::Core(e)
+    }
+}
+
+/// Append the bytes range of bytes, a JS array, to buffer
+fn copy_range(bytes: &js_sys::Uint8Array, range: Range<usize>, buffer: &mut Vec<u8>) {
+    let start = buffer.len();
+    buffer.resize(start + range.len(), 0);
+    bytes
+        .subarray(range.start as u32, range.end as u32)
+        .copy_to(&mut buffer[start..]);
+}
+
+/// justify_text for UTF-8 input past maxInputSize (e.g. a 20 MB log), window_bytes at a time
+/// text_bytes stays in JS: each window is copied in, cut after its last line break and wrapped,
+/// and sink is called with the wrapped block, so wasm memory stays around a few window_bytes
+/// whatever the input size. The blocks, concatenated in the order sink gets them, are
+/// justify_text of the whole input (each ends with the line break before the next); joining
+/// them is up to the caller. window_bytes is 1 to maxInputSize, and a line longer than the
+/// window is read whole, up to maxInputSize. Throws on invalid UTF-8 (naming the offset) after
+/// the blocks before it, and rethrows whatever sink throws.
+#[wasm_bindgen]
+pub fn justify_text_large(
+    text_bytes: &js_sys::Uint8Array,
+    max_chars_per_line: u32,
+    window_bytes: u32,
+    sink: &BlockSink,
+) -> Result<(), JsValue> {
+    let sink: &js_sys::Function = sink.unchecked_ref();
+    core_api::justify_windows(
+        text_bytes.length() as usize,
+        max_chars_per_line,
+        window_bytes as usize,
+        |range, buffer| copy_range(text_bytes, range, buffer),
+        |block| {
+            sink.call1(&JsValue::NULL, &JsValue::from_str(block))
+                .map(drop)
+                .map_err(LargeError::Sink)
+        },
+    )
+    .map_err(|e| match e {
+        LargeError::Core(e) => to_js_error("justify_text_large", e),
+        LargeError::Sink(thrown) => thrown,
+    })
+}
+
+#[cfg(feature = "json")]
+/// justify_text_large, also calling sink with the stats so far as JSON and returning the
+/// stats of the whole input
+/// The stats are get_text_stats' char, byte, line, CJK, ASCII, astral, mark and BOM counts and
+/// display width of the input wrapped so far, and widestOutputLine, the widest wrapped line, in
+/// columns: JSON {charCount, byteCount, lineCount, ..., widestOutputLine}.
+#[wasm_bindgen]
+pub fn justify_text_large_with_stats(
+    text_bytes: &js_sys::Uint8Array,
+    max_chars_per_line: u32,
+    window_bytes: u32,
+    sink: &StatsBlockSink,
+) -> Result<String, JsValue> {
+    let sink: &js_sys::Function = sink.unchecked_ref();
+    core_api::justify_windows_with_stats(
+        text_bytes.length() as usize,
+        max_chars_per_line,
+        window_bytes as usize,
+        |range, buffer| copy_range(text_bytes, range, buffer),
+        |block, stats| {
+            let stats = JsValue::from_str(&core_api::streamed_stats_json(stats));
+            sink.call2(&JsValue::NULL, &JsValue::from_str(block), &stats)
+                .map(drop)
+                .map_err(LargeError::Sink)
+        },
+    )
+    .map(|stats| core_api::streamed_stats_json(&stats))
+    .map_err(|e| match e {
+        LargeError::Core(e) => to_js_error("justify_text_large_with_stats", e),
+        LargeError::Sink(thrown) => thrown,
+    })
+}
+
+thread_local! {
+    static LAST_RESULT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Justify text and keep the UTF-8 result in wasm memory instead of returning a JS string
+/// Returns the byte length; read it with last_result_view() or last_result_ptr()/last_result_len().
+///
+/// Invalidation: the stored bytes are replaced by the next *_to_buffer call and released by
+/// free_last_result(). Any call into the module may grow wasm memory, which detaches existing
+/// views, so read or copy the view before calling anything else.
+#[wasm_bindgen]
+pub fn justify_text_to_buffer(text: &str, max_chars_per_line: u32) -> usize {
+    store_last_result(justify_text(text, max_chars_per_line).into_bytes())
+}
+
+/// justify_text_bytes variant of justify_text_to_buffer
+#[wasm_bindgen]
+pub fn justify_text_bytes_to_buffer(
+    bytes: &[u8],
+    max_chars_per_line: u32,
+    lossy: bool,
+) -> Result<usize, JsValue> {
+    let text =
+        decode_utf8(bytes, lossy).map_err(|e| to_js_error("justify_text_bytes_to_buffer", e))?;
+    Ok(store_last_result(
+        justify_text(&text, max_chars_per_line).into_bytes(),
+    ))
+}
+
+fn store_last_result(bytes: Vec<u8>) -> usize {
+    let len = bytes.len();
+    LAST_RESULT.with(|cell| *cell.borrow_mut() = bytes);
+    len
+}
+
+/// Address of the stored result inside wasm memory (see justify_text_to_buffer)
+#[wasm_bindgen]
+pub fn last_result_ptr() -> *const u8 {
+    LAST_RESULT.with(|cell| cell.borrow().as_ptr())
+}
+
+/// Byte length of the stored result
+#[wasm_bindgen]
+pub fn last_result_len() -> usize {
+    LAST_RESULT.with(|cell| cell.borrow().len())
+}
+
+/// Uint8Array view over the stored result without copying
+/// Only valid until the next call into the module (see justify_text_to_buffer)
+#[wasm_bindgen]
+pub fn last_result_view() -> js_sys::Uint8Array {
+    LAST_RESULT.with(|cell| {
+        let bytes = cell.borrow();
+        // Safety: the view aliases the thread-local buffer, which is only replaced or freed by
+        // later calls into the module; callers must drop the view before making one.
+        unsafe { js_sys::Uint8Array::view(&bytes) }
+    })
+}
+
+/// Release the stored result
+#[wasm_bindgen]
+pub fn free_last_result() {
+    LAST_RESULT.with(|cell| *cell.borrow_mut() = Vec::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The stored result as last_result_ptr and last_result_len describe it, which is what
+    /// last_result_view wraps (js_sys views can't be made outside wasm)
+    fn stored() -> Vec<u8> {
+        let (ptr, len) = (last_result_ptr(), last_result_len());
+        // Safety: nothing replaces the buffer between the two calls and the copy
+        unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+    }
+
+    #[test]
+    fn stored_result_matches_the_copying_api() {
+        let text = "漢字かな交じりの文 with English words in it";
+        let len = justify_text_to_buffer(text, 12);
+        assert_eq!(len, justify_text(text, 12).len());
+        assert_eq!(stored(), justify_text(text, 12).into_bytes());
+
+        let len = justify_text_bytes_to_buffer(text.as_bytes(), 7, false).unwrap();
+        assert_eq!(len, last_result_len());
+        assert_eq!(
+            stored(),
+            core_api::justify_text_bytes(text.as_bytes(), 7, false).unwrap()
+        );
+
+        free_last_result();
+        assert_eq!(last_result_len(), 0);
+        assert!(stored().is_empty());
+    }
+}
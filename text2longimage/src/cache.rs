@@ -0,0 +1,21 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Drop every result kept by the batch APIs' use_cache option
+#[wasm_bindgen]
+pub fn clear_cache() {
+    core_api::clear_cache();
+}
+
+/// Set how many results the batch cache keeps (default 256); 0 turns off reuse across calls
+#[wasm_bindgen]
+pub fn set_cache_capacity(entries: u32) {
+    core_api::set_cache_capacity(entries as usize);
+}
+
+/// Current batch cache capacity in entries
+#[wasm_bindgen]
+pub fn get_cache_capacity() -> u32 {
+    u32::try_from(core_api::cache_capacity()).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,34 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::to_js_error;
+
+/// Strip a leading BOM, normalize newlines and remove zero-width characters, stray variation
+/// selectors and directional marks
+/// options_json: {stripBom, normalizeNewlinesTo: "keep"|"lf"|"crlf", stripZeroWidth,
+/// stripStrayVariationSelectors, stripDirectionalMarks, stripAnsi, normalizeNfc}, all
+/// optional; "" uses the defaults (every strip on but ANSI, LF newlines).
+/// A ZWJ joining two emoji is kept, and so is U+FE0E or U+FE0F after an emoji-capable
+/// character, where it picks text or emoji presentation.
+#[wasm_bindgen]
+pub fn clean_text(text: &str, options_json: &str) -> Result<String, JsValue> {
+    core_api::clean_text(text, options_json).map_err(|e| to_js_error("clean_text", e))
+}
+
+/// clean_text plus counts of what was removed
+/// Returns JSON {text, bomRemoved, zeroWidthRemoved, variationSelectorsRemoved,
+/// directionalMarksRemoved, newlinesNormalized, ansiSequencesRemoved, invisibleRemoved}
+#[wasm_bindgen]
+pub fn clean_text_report(text: &str, options_json: &str) -> Result<String, JsValue> {
+    core_api::clean_text_report(text, options_json).map_err(|e| to_js_error("clean_text_report", e))
+}
+
+/// clean_text as edits of text, so an editor can apply the change in place and keep cursors
+/// and undo history
+/// Returns JSON [{start, end, replacement}] in char indices of text, ascending and not
+/// touching; options_json as for clean_text.
+#[wasm_bindgen]
+pub fn clean_text_edits(text: &str, options_json: &str) -> Result<String, JsValue> {
+    core_api::clean_text_edits(text, options_json).map_err(|e| to_js_error("clean_text_edits", e))
+}
@@ -0,0 +1,41 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::logging::get_log_level;
+use crate::metrics::{register_font_metrics, with_font_metrics};
+use crate::{panic_hook, set_log_level, set_panic_hook, to_js_error};
+
+/// Apply every global setting at once, replacing whatever earlier calls set
+/// JSON shape: {"panicHook": true, "logLevel": 0, "maxInputSize": 500000, "maxBatchSize": 5000000,
+/// "maxBatchItems": 100000, "cacheCapacity": 256, "fontMetrics": null, "strictOptions": false, "calibrateChunkSize": true,
+/// "cjkLineHeightFactor": 1.15, "emojiLineHeightFactor": 1.25}; missing fields take these
+/// defaults, unknown ones are rejected. An invalid setting throws before anything is changed.
+/// Calling it again with the same JSON changes nothing. Returns the effective configuration as
+/// JSON (panicHook is false on builds without the panic-hook feature), ready to log.
+#[wasm_bindgen]
+pub fn configure(config_json: &str) -> Result<String, JsValue> {
+    let mut config =
+        core_api::RuntimeConfig::from_json(config_json).map_err(|e| to_js_error("configure", e))?;
+
+    // Validated in full above; nothing from here on can fail
+    config.panic_hook = set_panic_hook(config.panic_hook);
+    set_log_level(config.log_level);
+    config.apply_limits();
+    register_font_metrics(config.take_font_metrics());
+    Ok(config.to_json())
+}
+
+/// The settings in effect, as JSON in the shape configure takes and returns
+/// These are all the module keeps between calls, whether set with configure or with the
+/// single-setting exports (set_max_input_size, set_font_metrics, set_log_level, ...). Each wasm
+/// instance, so each Web Worker, has its own, and configure(current_config()) changes nothing.
+/// Per call, maxOutputRatio and strictOptions of an options object override the module's, and
+/// a Document keeps the line heights it was built with (see Document.set_line_heights).
+#[wasm_bindgen]
+pub fn current_config() -> String {
+    let config = with_font_metrics(|metrics| {
+        core_api::RuntimeConfig::current(panic_hook(), get_log_level(), metrics)
+    });
+    config.to_json()
+}
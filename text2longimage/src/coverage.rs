@@ -0,0 +1,12 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Count characters the selected font may not have glyphs for, with examples
+/// Returns JSON {riskyCount, astralCount, unassigned, privateUse, supplementaryIdeographs,
+/// combining, nonBmp}, each bucket {count, examples}. Each character is counted in one bucket
+/// only, the first that matches in that order; astralCount counts everything above U+FFFF
+#[wasm_bindgen]
+pub fn font_coverage_report(text: &str) -> String {
+    core_api::font_coverage_report(text)
+}
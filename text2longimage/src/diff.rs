@@ -0,0 +1,35 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::to_js_error;
+
+/// Wrap text under two JustifyOptions objects (as JSON) and compare the results line by line
+/// Returns JSON {lineCountA, lineCountB, firstDifference, differenceCount, differences}, where
+/// differences lists the first 50 {lineIndex, lineA, lineB}; lineA/lineB are null past the
+/// end of the shorter output and firstDifference is null when nothing changes.
+/// "" for either options object uses the defaults
+#[wasm_bindgen]
+pub fn diff_wrap(
+    text: &str,
+    options_a_json: &str,
+    options_b_json: &str,
+) -> Result<String, JsValue> {
+    core_api::diff_wrap(text, options_a_json, options_b_json)
+        .map_err(|e| to_js_error("diff_wrap", e))
+}
+
+/// Compare two versions of a text and wrap the merged result, for "what changed" images
+/// Returns JSON {lineCount, segments}, each segment {lineIndex, startCol, endCol, change,
+/// text} with change "unchanged", "inserted" or "deleted", in display columns of the wrapped
+/// line; a change that wraps is a segment on each line. Changed lines are compared by word,
+/// CJK text by character, and deleted text comes before the text inserted in its place.
+#[wasm_bindgen]
+pub fn diff_texts(
+    old_text: &str,
+    new_text: &str,
+    max_chars_per_line: u32,
+) -> Result<String, JsValue> {
+    core_api::diff_texts(old_text, new_text, max_chars_per_line)
+        .map_err(|e| to_js_error("diff_texts", e))
+}
@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::logging::Operation;
+use crate::objects::{options_from_js, to_js, JustifyOptionsArg, LayoutLineArray, TextStatsObject};
+use crate::to_js_error;
+
+/// A text and its options, wrapped once and queried many times
+/// Options are parsed in the constructor; the first accessor that needs lines wraps the text
+/// and the others reuse those lines until set_text or set_width. Each accessor returns what the
+/// matching standalone export returns for the same text and options. Nothing configure changes
+/// afterwards reaches a Document: its layouts keep the line heights set when it was built.
+#[wasm_bindgen]
+pub struct Document {
+    inner: core_api::Document,
+}
+
+#[wasm_bindgen]
+impl Document {
+    /// options as justify_text_with_options takes them; invalid options throw
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: String, options: JustifyOptionsArg) -> Result<Document, JsValue> {
+        let options = options_from_js("Document", options.into())?;
+        Ok(Document {
+            inner: core_api::Document::new(text, options),
+        })
+    }
+
+    /// Lay out with these heightFactors for lines with CJK and with emoji from now on, instead
+    /// of configure's cjkLineHeightFactor and emojiLineHeightFactor as they were when the
+    /// Document was built; each must be a positive number
+    pub fn set_line_heights(&mut self, cjk: f64, emoji: f64) -> Result<(), JsValue> {
+        let heights = core_api::ScriptLineHeights::checked(cjk, emoji)
+            .map_err(|e| to_js_error("Document", e))?;
+        self.inner.set_line_heights(heights);
+        Ok(())
+    }
+
+    /// Replace the text, keeping the options
+    pub fn set_text(&mut self, text: String) {
+        self.inner.set_text(text);
+    }
+
+    /// Change maxCharsPerLine; the text is wrapped again when next needed
+    pub fn set_width(&mut self, max_chars_per_line: u32) {
+        self.inner.set_width(max_chars_per_line);
+    }
+
+    /// justify_text_with_options(text, options)
+    pub fn wrapped_text(&self) -> String {
+        self.wrap();
+        self.inner.wrapped_text().to_string()
+    }
+
+    /// Number of lines in wrapped_text()
+    pub fn line_count(&self) -> u32 {
+        self.wrap();
+        self.inner.line_count()
+    }
+
+    /// layout_text_lines(text, options, font_size_px, line_height_factor, padding_px, align)
+    pub fn layout(
+        &self,
+        font_size_px: f64,
+        line_height_factor: f64,
+        padding_px: f64,
+        align: &str,
+    ) -> Result<LayoutLineArray, JsValue> {
+        self.wrap();
+        let lines = self.inner.layout(
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            core_api::Align::parse(align),
+        );
+        Ok(to_js(&lines)?.unchecked_into())
+    }
+
+    #[cfg(feature = "json")]
+    /// layout's entries for wrapped lines first_line through last_line (both included), for
+    /// laying out only what is near the viewport
+    /// Returns JSON {firstLine, lastLine, lineCount, lines}, lines being layout_text's entries;
+    /// a range past the end is cut to the lines there are, lastLine null when none is left.
+    /// Each entry is the one layout() gives for its line, so ranges stitch into the full layout.
+    pub fn layout_range(
+        &self,
+        first_line: u32,
+        last_line: u32,
+        font_size_px: f64,
+        line_height_factor: f64,
+        padding_px: f64,
+        align: &str,
+    ) -> String {
+        self.wrap();
+        self.inner.layout_range_json(
+            first_line,
+            last_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            core_api::Align::parse(align),
+        )
+    }
+
+    /// text_stats(text)
+    pub fn stats(&self) -> Result<TextStatsObject, JsValue> {
+        Ok(to_js(self.inner.stats())?.unchecked_into())
+    }
+
+    /// Pages of at most lines_per_page wrapped lines, as paginate_lines pages them
+    /// avoid_widows defaults to false
+    pub fn paginate(&self, lines_per_page: u32, avoid_widows: Option<bool>) -> js_sys::Array {
+        self.wrap();
+        self.inner
+            .paginate(lines_per_page, avoid_widows.unwrap_or(false))
+            .into_iter()
+            .map(JsValue::from)
+            .collect()
+    }
+}
+
+impl Document {
+    /// Log the wrap under its own operation when this call is the one that does it
+    fn wrap(&self) {
+        if !self.inner.is_wrapped() {
+            let _op = Operation::start("Document", self.inner.text().len());
+            self.inner.wrapped_text();
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Pre-summarized document that answers "how many lines at this width?" without rewrapping
+/// Results are exact (they always equal count_wrapped_lines), which keeps a width slider
+/// responsive on large documents
+#[wasm_bindgen]
+pub struct HeightEstimator {
+    inner: core_api::HeightEstimator,
+}
+
+#[wasm_bindgen]
+impl HeightEstimator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> HeightEstimator {
+        HeightEstimator {
+            inner: core_api::HeightEstimator::new(text),
+        }
+    }
+
+    /// Number of wrapped lines at max_chars_per_line
+    pub fn estimate(&self, max_chars_per_line: u32) -> u32 {
+        self.inner.estimate(max_chars_per_line)
+    }
+}
@@ -0,0 +1,14 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::to_js_error;
+
+/// Convert text pasted from a web page to plain text: tags removed (<br> and </p> become
+/// line breaks, <li> "- "), entities decoded, whitespace collapsed
+/// options_json: {keepLinkUrls} to follow link text with "(url)"; "" uses the defaults.
+/// Malformed markup is kept as text rather than rejected.
+#[wasm_bindgen]
+pub fn strip_html(text: &str, options_json: &str) -> Result<String, JsValue> {
+    core_api::strip_html(text, options_json).map_err(|e| to_js_error("strip_html", e))
+}
@@ -0,0 +1,25 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Default for line_hygiene_report's short_line_chars: single-character lines count as short
+#[cfg(feature = "json")]
+const DEFAULT_SHORT_LINE_CHARS: u32 = 2;
+
+#[cfg(feature = "json")]
+/// Duplicate, blank and short lines, for a "clean up input?" prompt
+/// Returns JSON {lineCount, duplicateLineCount, duplicates: [{text, occurrences, firstIndex}],
+/// blankLineCount, shortLineCount, shortLineIndices}. Lines are compared after trimming;
+/// short lines are non-blank lines under short_line_chars characters (default 2)
+#[wasm_bindgen]
+pub fn line_hygiene_report(text: &str, short_line_chars: Option<u32>) -> String {
+    core_api::line_hygiene_report(text, short_line_chars.unwrap_or(DEFAULT_SHORT_LINE_CHARS))
+}
+
+/// Remove repeated lines (compared after trimming); blank lines are always kept
+/// keep_first removes every repeat and keeps the first occurrence; otherwise only runs of
+/// consecutive equal lines collapse to one
+#[wasm_bindgen]
+pub fn dedupe_lines(text: &str, keep_first: bool) -> String {
+    core_api::dedupe_lines(text, keep_first)
+}
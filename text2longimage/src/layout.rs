@@ -0,0 +1,363 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+use text_processor_core::ColumnRounding;
+
+#[cfg(feature = "json")]
+use crate::logging::Operation;
+#[cfg(feature = "json")]
+use crate::profiling::{self, Profile};
+use crate::to_js_error;
+
+/// Number of half-width columns that fit in an image of the given pixel width
+/// (target_width_px - 2 × padding_px) / (font_size_px / 2), never below MIN_CHARS_PER_LINE,
+/// rounded as rounding says: "floor" (default) never overflows the pixel width, "round" is the
+/// closest fit (up to half a column over), "ceil" fills it (up to a column over)
+#[wasm_bindgen]
+pub fn chars_per_line_for_width(
+    target_width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+    rounding: Option<String>,
+) -> Result<u32, JsValue> {
+    let rounding = column_rounding("chars_per_line_for_width", rounding, ColumnRounding::Floor)?;
+    Ok(core_api::chars_per_line_for_width_with(
+        target_width_px,
+        font_size_px,
+        padding_px,
+        rounding,
+    ))
+}
+
+/// Inverse of chars_per_line_for_width: image width needed for `chars` columns
+#[wasm_bindgen]
+pub fn pixel_width_for_chars(chars: u32, font_size_px: f64, padding_px: f64) -> f64 {
+    core_api::pixel_width_for_chars(chars, font_size_px, padding_px)
+}
+
+/// Justify text for an image of the given pixel width in one call, rounding it to columns
+/// as chars_per_line_for_width does
+#[wasm_bindgen]
+pub fn justify_text_for_image(
+    text: &str,
+    width_px: f64,
+    font_size_px: f64,
+    padding_px: f64,
+    rounding: Option<String>,
+) -> Result<String, JsValue> {
+    let rounding = column_rounding("justify_text_for_image", rounding, ColumnRounding::Floor)?;
+    Ok(core_api::justify_text_for_image_with(
+        text,
+        width_px,
+        font_size_px,
+        padding_px,
+        rounding,
+    ))
+}
+
+/// rounding parsed, or default when it's not given
+fn column_rounding(
+    what: &str,
+    rounding: Option<String>,
+    default: ColumnRounding,
+) -> Result<ColumnRounding, JsValue> {
+    rounding.map_or(Ok(default), |rounding| {
+        ColumnRounding::parse(&rounding).map_err(|e| to_js_error(what, e))
+    })
+}
+
+#[cfg(feature = "json")]
+/// Estimate the rendered image dimensions for the wrapped text
+/// Returns JSON {widthPx, heightPx, lineCount, exceedsSafeCanvasHeight, exceedsMaxCanvasHeight,
+/// warnings: [{code, message, actual, limit, suggestion: {maxCharsPerLine?, imageCount?,
+/// splitHeightPx?}}]}. A warning comes from 80% of a canvas limit on: limits_json
+/// {maxHeightPx, maxWidthPx, maxTotalPixels} overrides the Chrome and Firefox defaults
+/// (32767, 32767, 268435456) field by field.
+#[wasm_bindgen]
+pub fn estimate_image_size(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    limits_json: Option<String>,
+) -> Result<String, JsValue> {
+    let limits = core_api::CanvasLimits::from_json(limits_json.as_deref().unwrap_or(""))
+        .map_err(|e| to_js_error("estimate_image_size", e))?;
+    Ok(core_api::estimate_image_size_with_limits(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        &limits,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// estimate_image_size for an image of the given pixel width, rounded to columns as
+/// chars_per_line_for_width does
+/// Returns the same JSON plus columns, the column count, and slackPx, the pixels of width_px
+/// left over to centre the text block in (negative when "round" or "ceil" overflows it)
+#[wasm_bindgen]
+pub fn estimate_image_size_for_width(
+    text: &str,
+    width_px: f64,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    rounding: Option<String>,
+    limits_json: Option<String>,
+) -> Result<String, JsValue> {
+    let rounding = column_rounding(
+        "estimate_image_size_for_width",
+        rounding,
+        ColumnRounding::Floor,
+    )?;
+    let limits = core_api::CanvasLimits::from_json(limits_json.as_deref().unwrap_or(""))
+        .map_err(|e| to_js_error("estimate_image_size_for_width", e))?;
+    Ok(core_api::estimate_image_size_for_width(
+        text,
+        width_px,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        rounding,
+        &limits,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// Wrap text and compute canvas positions for every line
+/// Returns JSON array of {text, xPx, yPx, widthPx}; yPx is the top of the line box
+/// align is "left", "center" or "right" (unknown values fall back to left).
+/// heading_scale (default 1) draws Markdown headings larger: they wrap at
+/// floor(max_chars_per_line / heading_scale) columns, take heading_scale times the line height
+/// and carry a fontScale field. letter_spacing_em and word_spacing_em (default 0, the canvas
+/// letterSpacing and wordSpacing in em) are counted when wrapping and in widthPx, so spaced
+/// lines still fit max_chars_per_line columns.
+/// debug_guides (default false) returns {lines, guides: {leftMarginPx, rightMarginPx,
+/// columnGuidesPx, slack}} instead, lines being the same array: the margins' x, a guide's x
+/// every guide_columns columns (default 10, 0 for none) and each line's unused columns
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn layout_text(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    heading_scale: Option<f64>,
+    letter_spacing_em: Option<f64>,
+    word_spacing_em: Option<f64>,
+    debug_guides: Option<bool>,
+    guide_columns: Option<u32>,
+) -> Result<String, JsValue> {
+    let _op = Operation::start("layout_text", text.len());
+    let _profile = Profile::start("layout_text");
+    let spacing = spacing_arg(letter_spacing_em, word_spacing_em)
+        .map_err(|e| to_js_error("layout_text", e))?;
+    profiling::mark(core_api::Stage::Decode);
+    if debug_guides.unwrap_or(false) {
+        return Ok(core_api::layout_text_with_guides(
+            text,
+            max_chars_per_line,
+            font_size_px,
+            line_height_factor,
+            padding_px,
+            align,
+            heading_scale.unwrap_or(1.0),
+            spacing,
+            guide_columns.unwrap_or(core_api::DEFAULT_GUIDE_COLUMNS),
+        ));
+    }
+    Ok(core_api::layout_text_with_spacing(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        heading_scale.unwrap_or(1.0),
+        spacing,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// layout_text with the image size and the canvas limit warnings of estimate_image_size
+/// Returns JSON {lines: [{text, xPx, yPx, widthPx}], widthPx, heightPx, warnings}
+#[wasm_bindgen]
+pub fn layout_text_with_limits(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+    limits_json: Option<String>,
+) -> Result<String, JsValue> {
+    let _op = Operation::start("layout_text_with_limits", text.len());
+    let limits = core_api::CanvasLimits::from_json(limits_json.as_deref().unwrap_or(""))
+        .map_err(|e| to_js_error("layout_text_with_limits", e))?;
+    Ok(core_api::layout_text_with_limits(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        align,
+        &limits,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// Vertical layout: columns of max_chars_per_column half-width units, the first at the right
+/// Returns JSON {widthPx, heightPx, columnCount, cells: [{colIndex, rowIndex, xPx, yPx, char,
+/// rotationGroup?}]}; half-width runs share a rotationGroup and are drawn rotated together.
+/// Columns break where justify_text_cjk_locale(text, max_chars_per_column, "auto") breaks lines
+#[wasm_bindgen]
+pub fn layout_vertical(
+    text: &str,
+    max_chars_per_column: u32,
+    font_size_px: f64,
+    column_gap_px: f64,
+    padding_px: f64,
+) -> String {
+    let _op = Operation::start("layout_vertical", text.len());
+    core_api::layout_vertical(
+        text,
+        max_chars_per_column,
+        font_size_px,
+        column_gap_px,
+        padding_px,
+    )
+}
+
+/// X offset of every character in a single line under the half-width/full-width model
+/// Offsets are relative to the line start; the last offset plus that character's width
+/// equals the line's widthPx from layout_text. Pass layout_text's letter_spacing_em and
+/// word_spacing_em (default 0) to get the offsets of the spaced line
+#[wasm_bindgen]
+pub fn char_advances(
+    line: &str,
+    font_size_px: f64,
+    letter_spacing_em: Option<f64>,
+    word_spacing_em: Option<f64>,
+) -> Result<Vec<f32>, JsValue> {
+    let spacing = spacing_arg(letter_spacing_em, word_spacing_em)
+        .map_err(|e| to_js_error("char_advances", e))?;
+    Ok(core_api::char_advances_with_spacing(
+        line,
+        font_size_px,
+        spacing,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// char_advances for every line of already wrapped text (CRLF or LF separated)
+/// Returns JSON array with one array of offsets per line
+#[wasm_bindgen]
+pub fn char_advances_lines(
+    wrapped_text: &str,
+    font_size_px: f64,
+    letter_spacing_em: Option<f64>,
+    word_spacing_em: Option<f64>,
+) -> Result<String, JsValue> {
+    let spacing = spacing_arg(letter_spacing_em, word_spacing_em)
+        .map_err(|e| to_js_error("char_advances_lines", e))?;
+    Ok(core_api::char_advances_lines_with_spacing(
+        wrapped_text,
+        font_size_px,
+        spacing,
+    ))
+}
+
+/// Spacing from optional letter and word spacing arguments, 0 when left out
+pub(crate) fn spacing_arg(
+    letter_spacing_em: Option<f64>,
+    word_spacing_em: Option<f64>,
+) -> Result<core_api::Spacing, core_api::WebtoolsError> {
+    core_api::Spacing::new(
+        letter_spacing_em.unwrap_or(0.0),
+        word_spacing_em.unwrap_or(0.0),
+    )
+}
+
+#[cfg(feature = "json")]
+/// Where to draw each emoji of already wrapped text (CRLF or LF separated) as an image
+/// Returns JSON array of {lineIndex, xPx, widthPx, codePoints}; xPx matches char_advances for
+/// the emoji's first character, and a ZWJ sequence, flag pair or keycap is one entry
+#[wasm_bindgen]
+pub fn emoji_positions(wrapped_text: &str, font_size_px: f64) -> String {
+    core_api::emoji_positions(wrapped_text, font_size_px)
+}
+
+#[cfg(feature = "json")]
+/// Character classes of text for coloring, run-length encoded
+/// Returns JSON array of [class, length] pairs, class being "cjk", "latin", "digit", "punct",
+/// "space", "emoji" or "other" and length in UTF-16 code units (the lengths add up to
+/// text.length); an emoji grapheme is one run, and combining marks take the class before them.
+#[wasm_bindgen]
+pub fn classify_runs(text: &str) -> String {
+    core_api::classify_runs(text)
+}
+
+#[cfg(feature = "json")]
+/// classify_runs of each line of already wrapped text (CRLF or LF separated)
+/// Returns JSON array with an array of [class, length] pairs per line; line breaks are left
+/// out, so no run crosses a line boundary
+#[wasm_bindgen]
+pub fn classify_line_runs(wrapped_text: &str) -> String {
+    core_api::classify_line_runs(wrapped_text)
+}
+
+/// Find the max_chars_per_line in [min_chars, max_chars] whose image is closest to target_ratio
+/// target_ratio is width / height (0.75 for a 3:4 card); line height is taken as font_size_px
+/// rounding picks between the widths either side of the ratio: "round" (default) the closer,
+/// "floor" the widest block no wider than the ratio, "ceil" the narrowest that reaches it
+#[wasm_bindgen]
+pub fn fit_aspect_ratio(
+    text: &str,
+    target_ratio: f64,
+    font_size_px: f64,
+    min_chars: u32,
+    max_chars: u32,
+    rounding: Option<String>,
+) -> Result<u32, JsValue> {
+    let rounding = column_rounding("fit_aspect_ratio", rounding, ColumnRounding::Round)?;
+    Ok(core_api::fit_aspect_ratio_with(
+        text,
+        target_ratio,
+        font_size_px,
+        min_chars,
+        max_chars,
+        rounding,
+    ))
+}
+
+#[cfg(feature = "json")]
+/// Find the narrowest width in [min_chars, max_chars] that wraps into at most target_lines lines
+/// Returns JSON {width, lineCount, fits, text}. When even max_chars overflows, the wrap at
+/// max_chars is returned with fits = false.
+#[wasm_bindgen]
+pub fn wrap_to_line_count(text: &str, target_lines: u32, min_chars: u32, max_chars: u32) -> String {
+    core_api::wrap_to_line_count(text, target_lines, min_chars, max_chars)
+}
+
+#[cfg(feature = "json")]
+/// Find query in text as justify_text(text, max_chars_per_line) wraps it
+/// Returns JSON array of {lineIndex, startCol, endCol, continuesOnNextLine} in display columns;
+/// a match over an inserted line break is two entries, the first with continuesOnNextLine.
+/// The wrapped output is what gets searched, so whitespace runs the wrapper collapsed only
+/// match as a single space. case_insensitive folds case per character, full-width Latin included
+#[wasm_bindgen]
+pub fn find_in_wrapped(
+    text: &str,
+    max_chars_per_line: u32,
+    query: &str,
+    case_insensitive: bool,
+) -> String {
+    let _op = Operation::start("find_in_wrapped", text.len());
+    core_api::find_in_wrapped(text, max_chars_per_line, query, case_insensitive)
+}
@@ -1,281 +1,832 @@
-use serde_json;
+//! wasm-bindgen bindings for text_processor_core
+//! Every export here forwards to the core crate; this layer only converts errors and
+//! JS-specific types and adds logging
+
 use wasm_bindgen::prelude::*;
-use wee_alloc::WeeAlloc;
 
-// Use `wee_alloc` as the global allocator. Reduces compiled .wasm size by 3KB
-#[global_allocator]
-static ALLOC: WeeAlloc = WeeAlloc::INIT;
+use text_processor_core as core_api;
+use text_processor_core::WebtoolsError;
+
+mod alloc;
+mod build_info;
+mod bytes;
+mod cache;
+#[cfg(feature = "json")]
+mod clean;
+#[cfg(feature = "json")]
+mod config;
+#[cfg(feature = "json")]
+mod coverage;
+#[cfg(feature = "json")]
+mod diff;
+mod document;
+mod estimate;
+#[cfg(feature = "json")]
+mod html;
+mod hygiene;
+mod layout;
+mod limits;
+mod lines;
+mod logging;
+mod metrics;
+mod numbering;
+mod objects;
+mod paginate;
+mod profiling;
+#[cfg(feature = "raster")]
+mod raster;
+mod svg;
+mod transfer;
+mod utf16;
+
+pub use alloc::memory_stats;
+pub use build_info::*;
+pub use bytes::*;
+pub use cache::*;
+#[cfg(feature = "json")]
+pub use clean::*;
+#[cfg(feature = "json")]
+pub use config::*;
+#[cfg(feature = "json")]
+pub use coverage::*;
+#[cfg(feature = "json")]
+pub use diff::*;
+pub use document::*;
+pub use estimate::*;
+#[cfg(feature = "json")]
+pub use html::*;
+pub use hygiene::*;
+pub use layout::*;
+pub use limits::*;
+pub use lines::*;
+pub use logging::{get_log_level, set_log_level};
+pub use metrics::*;
+pub use numbering::*;
+pub use objects::*;
+pub use paginate::*;
+#[cfg(feature = "raster")]
+pub use raster::*;
+pub use svg::*;
+pub use utf16::*;
+
+use logging::{performance_now, Operation};
+use profiling::Profile;
+use text_processor_core::Stage;
+
+/// Convert a core error into the JS exception value, logging it first
+/// The thrown value is a {code, message, details} object (see the WebtoolsError interface)
+pub(crate) fn to_js_error(operation: &str, error: WebtoolsError) -> JsValue {
+    let message = error.to_string();
+    logging::error(operation, &message);
+    serde_wasm_bindgen::to_value(&error).unwrap_or_else(|_| JsValue::from_str(&message))
+}
 
-// Bind console.log for debugging
+/// One-time module setup, call once after the wasm module is loaded
+/// Installs the console panic hook when built with the `panic-hook` feature, so a panic
+/// reports its message and location instead of only "unreachable executed"
 #[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
+pub fn init() {
+    set_panic_hook(true);
+}
 
-    #[wasm_bindgen(js_namespace = console, js_name = log)]
-    fn log_u32(a: u32);
+thread_local! {
+    static PANIC_HOOK: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
 }
 
-// Macro for console.log! debugging
-macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+#[cfg(feature = "json")]
+/// Whether the console panic hook is installed
+pub(crate) fn panic_hook() -> bool {
+    PANIC_HOOK.with(std::cell::Cell::get)
+}
+
+/// Install or remove the console panic hook; returns whether it is now installed
+/// Builds without the `panic-hook` feature never install it and leave the std hook alone
+pub(crate) fn set_panic_hook(enabled: bool) -> bool {
+    #[cfg(feature = "panic-hook")]
+    {
+        if enabled {
+            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        } else {
+            drop(std::panic::take_hook());
+        }
+        PANIC_HOOK.with(|hook| hook.set(enabled));
+        enabled
+    }
+    #[cfg(not(feature = "panic-hook"))]
+    {
+        let _ = enabled;
+        false
+    }
 }
 
 /// Check if a character is CJK (Chinese, Japanese, Korean)
 /// This is a critical performance function called for every character
 #[wasm_bindgen]
 pub fn is_cjk_char(c: char) -> bool {
-    let code_point = c as u32;
-
-    // Han characters (Chinese): U+4E00-U+9FFF
-    if code_point >= 0x4E00 && code_point <= 0x9FFF {
-        return true;
-    }
-
-    // Hiragana: U+3040-U+309F
-    if code_point >= 0x3040 && code_point <= 0x309F {
-        return true;
-    }
-
-    // Katakana: U+30A0-U+30FF
-    if code_point >= 0x30A0 && code_point <= 0x30FF {
-        return true;
-    }
-
-    // Extended Han characters
-    // CJK Extension A: U+3400-U+4DBF
-    if code_point >= 0x3400 && code_point <= 0x4DBF {
-        return true;
-    }
-
-    // CJK Extension B: U+20000-U+2A6DF
-    if code_point >= 0x20000 && code_point <= 0x2A6DF {
-        return true;
-    }
-
-    false
+    core_api::is_cjk_char(c)
 }
 
 /// Fast check if string contains any CJK characters
 /// Optimized to return early on first match
 #[wasm_bindgen]
 pub fn is_cjk(text: &str) -> bool {
-    text.chars().any(is_cjk_char)
+    core_api::is_cjk(text)
 }
 
 /// Get character width for text justification
 /// ASCII chars = 1, CJK chars = 2
 #[wasm_bindgen]
 pub fn get_char_width(c: char) -> u32 {
-    let code_point = c as u32;
+    core_api::get_char_width(c)
+}
 
-    // ASCII range (0x00-0xFF) = width 1
-    if code_point <= 0xFF {
-        1
-    } else {
-        // Non-ASCII (including CJK) = width 2
-        2
-    }
+/// get_char_width as a platform draws c before next (omitted at the end of the text): U+FE0F
+/// after an emoji-capable character makes it 2 columns, U+FE0E 1, and the selectors take 0
+/// The wrappers measure with get_char_width, a selector counting as a character of its own.
+#[wasm_bindgen]
+pub fn get_char_width_presented(c: char, next: Option<char>) -> u32 {
+    core_api::get_char_width_presented(c, next)
+}
+
+/// Fractional character width in em units
+/// ASCII chars = 0.5, CJK chars = 1.0 (get_char_width / 2)
+#[wasm_bindgen]
+pub fn get_char_width_f(c: char) -> f64 {
+    core_api::get_char_width_f(c)
 }
 
 /// High-performance CJK text justification
 /// Processes character-by-character with optimized width calculations
 #[wasm_bindgen]
 pub fn justify_text_cjk(text: &str, max_chars_per_line: u32) -> String {
-    let mut result = String::with_capacity(text.len() + text.len() / 20); // Pre-allocate with buffer
-    let mut current_line_width = 0u32;
-
-    for c in text.chars() {
-        match c {
-            '\r' | '\n' => {
-                // Handle existing line breaks
-                current_line_width = 0; // Reset width after line break
-                if c == '\r' {
-                    result.push_str("\r\n");
-                } else {
-                    result.push_str("\r\n");
-                }
-                continue;
-            }
-            _ => {
-                let char_width = get_char_width(c);
-
-                // Check if adding this character would exceed the line limit
-                if current_line_width + char_width > max_chars_per_line {
-                    result.push_str("\r\n");
-                    current_line_width = char_width;
-                } else {
-                    current_line_width += char_width;
-                }
-
-                result.push(c);
-            }
-        }
-    }
+    core_api::justify_text_cjk(text, max_chars_per_line)
+}
+
+/// justify_text_cjk with line-breaking rules: closing punctuation, and in Japanese small kana
+/// and ー, don't start a line; opening brackets don't end one
+/// locale is "ja", "zh-Hans", "zh-Hant", "ko" or "auto" (detected from the text);
+/// extra_no_start / extra_no_end add characters to the locale's tables
+#[wasm_bindgen]
+pub fn justify_text_cjk_locale(
+    text: &str,
+    max_chars_per_line: u32,
+    locale: &str,
+    extra_no_start: Option<String>,
+    extra_no_end: Option<String>,
+) -> Result<String, JsValue> {
+    let _op = Operation::start("justify_text_cjk_locale", text.len());
+    core_api::justify_text_cjk_locale(
+        text,
+        max_chars_per_line,
+        locale,
+        extra_no_start.as_deref().unwrap_or(""),
+        extra_no_end.as_deref().unwrap_or(""),
+    )
+    .map_err(|e| to_js_error("justify_text_cjk_locale", e))
+}
 
-    result
+#[cfg(feature = "json")]
+/// The characters locale's kinsoku rules keep from starting and from ending a line, before
+/// extra_no_start / extra_no_end add to them
+/// locale is "ja", "zh-Hans", "zh-Hant" or "ko". Returns JSON {locale, noStart, noEnd}, each
+/// table a string of characters.
+#[wasm_bindgen]
+pub fn kinsoku_sets(locale: &str) -> Result<String, JsValue> {
+    core_api::kinsoku_sets(locale).map_err(|e| to_js_error("kinsoku_sets", e))
+}
+
+#[cfg(feature = "json")]
+/// Which characters start and end the lines text wraps into at max_chars_per_line, at each
+/// break the wrapper made, for judging kinsoku settings against a real document
+/// options_json: {locale, extraNoStart, extraNoEnd}, all optional. A locale wraps with its
+/// kinsoku rules; without one the text wraps as justify_text wraps it, checked against the
+/// rules of the locale detected from it. Returns JSON {locale, kinsoku, softBreaks,
+/// forbiddenStarts, forbiddenEnds, lineStarts, lineEnds}, each histogram a list of
+/// {character, count, forbidden}, most frequent first.
+#[wasm_bindgen]
+pub fn edge_char_report(
+    text: &str,
+    max_chars_per_line: u32,
+    options_json: &str,
+) -> Result<String, JsValue> {
+    core_api::edge_char_report(text, max_chars_per_line, options_json)
+        .map_err(|e| to_js_error("edge_char_report", e))
+}
+
+/// Guess whether CJK text is "ja", "zh-Hans", "zh-Hant" or "ko"
+#[wasm_bindgen]
+pub fn detect_cjk_locale(text: &str) -> String {
+    core_api::detect_cjk_locale(text).tag().to_string()
 }
 
 /// High-performance English text justification
 /// Word-based wrapping with optimized string operations
 #[wasm_bindgen]
 pub fn justify_text_english(text: &str, max_chars_per_line: u32) -> String {
-    let words: Vec<&str> = text.split_whitespace().collect();
-    let mut lines = Vec::with_capacity(words.len() / 8); // Estimate lines needed
-    let mut current_line = String::with_capacity(max_chars_per_line as usize);
-
-    for word in words {
-        let word_len = word.len() as u32;
-        let space_needed = if current_line.is_empty() { 0 } else { 1 }; // Space before word
-
-        if current_line.len() as u32 + space_needed + word_len <= max_chars_per_line {
-            // Word fits on current line
-            if !current_line.is_empty() {
-                current_line.push(' ');
-            }
-            current_line.push_str(word);
-        } else {
-            // Word doesn't fit, start new line
-            if !current_line.is_empty() {
-                lines.push(current_line);
-                current_line = String::with_capacity(max_chars_per_line as usize);
-            }
-            current_line.push_str(word);
-        }
-    }
-
-    // Add the last line if it has content
-    if !current_line.is_empty() {
-        lines.push(current_line);
-    }
+    core_api::justify_text_english(text, max_chars_per_line)
+}
 
-    lines.join("\r\n")
+/// Fractional-width justification
+/// max_width is in em units: ASCII chars are 0.5 wide, CJK chars 1.0. letter_spacing_em and
+/// word_spacing_em (default 0) are added to each character and each space, as the canvas
+/// letterSpacing and wordSpacing draw them
+#[wasm_bindgen]
+pub fn justify_text_fractional(
+    text: &str,
+    max_width: f64,
+    letter_spacing_em: Option<f64>,
+    word_spacing_em: Option<f64>,
+) -> Result<String, JsValue> {
+    let spacing = layout::spacing_arg(letter_spacing_em, word_spacing_em)
+        .map_err(|e| to_js_error("justify_text_fractional", e))?;
+    Ok(core_api::justify_text_fractional_with_spacing(
+        text, max_width, spacing,
+    ))
 }
 
 /// Main text justification function
 /// Automatically detects CJK content and uses appropriate algorithm
 #[wasm_bindgen]
 pub fn justify_text(text: &str, max_chars_per_line: u32) -> String {
-    let lines: Vec<&str> = text.split('\n').collect();
-    let mut justified_lines = Vec::with_capacity(lines.len());
-
-    for line in lines {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() {
-            justified_lines.push(String::new());
-            continue;
-        }
+    let _op = Operation::start("justify_text", text.len());
+    let _profile = Profile::start("justify_text");
+    let justified = core_api::justify_text(text, max_chars_per_line);
+    profiling::mark(Stage::Wrap);
+    justified
+}
 
-        let justified_line = if is_cjk(trimmed_line) {
-            justify_text_cjk(trimmed_line, max_chars_per_line)
-        } else {
-            justify_text_english(trimmed_line, max_chars_per_line)
-        };
+/// justify_text wrapped and HTML-escaped in one pass, for an HTML preview
+/// use_br (default true) writes line breaks as <br>, otherwise as "\n" for white-space: pre.
+/// preserve_spaces (default false) writes repeated and line-leading spaces as &nbsp;.
+/// trim_trailing (default true) drops spaces left at the end of wrapped lines.
+#[wasm_bindgen]
+pub fn justify_text_html(
+    text: &str,
+    max_chars_per_line: u32,
+    use_br: Option<bool>,
+    preserve_spaces: Option<bool>,
+    trim_trailing: Option<bool>,
+) -> String {
+    let _op = Operation::start("justify_text_html", text.len());
+    let defaults = core_api::HtmlOutput::default();
+    let options = core_api::HtmlOutput {
+        use_br: use_br.unwrap_or(defaults.use_br),
+        preserve_spaces: preserve_spaces.unwrap_or(defaults.preserve_spaces),
+        trim_trailing: trim_trailing.unwrap_or(defaults.trim_trailing),
+    };
+    core_api::justify_text_html_with(text, max_chars_per_line, &options)
+}
 
-        justified_lines.push(justified_line);
-    }
+/// Join the lines that justify options' markSoftBreaks marked, recovering the text as it was
+/// before wrapping (at unlimited width). CJK breaks rejoin with no space, word breaks with one
+#[wasm_bindgen]
+pub fn remove_soft_breaks(text: &str, marker: &str) -> String {
+    core_api::remove_soft_breaks(text, marker)
+}
+
+/// Whitespace shown as symbols: · space, → tab, □ U+3000, ⍽ no-break space, ∅ zero-width
+/// characters, ¶ before each line break. For display only; widths aren't kept
+#[wasm_bindgen]
+pub fn visualize_whitespace(text: &str) -> String {
+    core_api::visualize_whitespace(text)
+}
+
+/// Count the lines `justify_text` would produce without building the output
+/// An empty result (empty or whitespace-only single-line input) has zero lines
+#[wasm_bindgen]
+pub fn count_wrapped_lines(text: &str, max_chars_per_line: u32) -> u32 {
+    core_api::count_wrapped_lines(text, max_chars_per_line)
+}
 
-    justified_lines.join("\r\n")
+/// How ragged the right edge of justify_text(text, max_chars_per_line) is, from 0 (every
+/// broken line full) towards 1: mean squared slack of the broken lines over the width squared
+/// Lines that end a paragraph don't count; nothing is wrapped into a string to measure it.
+#[wasm_bindgen]
+pub fn raggedness(text: &str, max_chars_per_line: u32) -> f64 {
+    core_api::raggedness(text, max_chars_per_line)
 }
 
+/// The width in [min_chars, max_chars] with the most even right edge (least raggedness),
+/// among those giving at most 10% more lines than max_chars does; ties go to the wider width
+#[wasm_bindgen]
+pub fn suggest_width(text: &str, min_chars: u32, max_chars: u32) -> u32 {
+    core_api::suggest_width(text, min_chars, max_chars)
+}
+
+/// Char indices after which justify_text would break a line, as a Uint32Array, for drawing
+/// break marks over the source without wrapping it into a string
+/// The text's own line breaks aren't included; whitespace the break drops comes after the index.
+#[wasm_bindgen]
+pub fn break_positions(text: &str, max_chars_per_line: u32) -> Vec<u32> {
+    core_api::break_positions(text, max_chars_per_line)
+}
+
+/// Whether text fits on one line of max_chars_per_line columns, as justify_text wraps it;
+/// stops at the first line instead of wrapping everything
+#[wasm_bindgen]
+pub fn fits_in_width(text: &str, max_chars_per_line: u32) -> bool {
+    core_api::fits_in_width(text, max_chars_per_line)
+}
+
+/// Char index of the first character that doesn't fit on one line of max_chars_per_line
+/// columns, or -1 when text fits; for highlighting the part that overflows
+/// That is where justify_text's first line stops (or passes the width, for a word too wide
+/// for any line), moved back to the start of an emoji sequence it falls inside.
+#[wasm_bindgen]
+pub fn first_overflow_index(text: &str, max_chars_per_line: u32) -> i32 {
+    core_api::first_overflow_index(text, max_chars_per_line).map_or(-1, |index| index as i32)
+}
+
+/// The first max_lines lines of justify_text, ending in ellipsis (default "…") when text is cut
+/// Only the lines shown are wrapped, so a preview of a long document costs about as much as
+/// the preview; leading blank lines are skipped
+#[wasm_bindgen]
+pub fn preview_snippet(
+    text: &str,
+    max_chars_per_line: u32,
+    max_lines: u32,
+    ellipsis: Option<String>,
+) -> String {
+    core_api::preview_snippet(
+        text,
+        max_chars_per_line,
+        max_lines,
+        ellipsis.as_deref().unwrap_or("…"),
+    )
+}
+
+#[cfg(feature = "json")]
 /// Batch process multiple text justification operations
 /// Takes JSON string array, returns JSON string array
+/// Items are capped at get_max_input_size() bytes each, get_max_batch_items() items and
+/// get_max_batch_size() bytes in total, checked while the JSON is read, so an oversized batch
+/// throws before it is copied; the array's elements must all be strings.
+/// With use_cache duplicates are wrapped once and results are kept across calls (see clear_cache)
+/// With report_transfer (default false) returns JSON {results, transfer} instead, results
+/// being that array and transfer the TransferReport of the call
 #[wasm_bindgen]
-pub fn batch_justify_text(texts_json: &str, max_chars_per_line: u32) -> Result<String, JsValue> {
-    let text_array: Vec<String> = serde_json::from_str(texts_json)
-        .map_err(|e| JsValue::from_str(&format!("Invalid JSON: {}", e)))?;
+pub fn batch_justify_text(
+    texts_json: &str,
+    max_chars_per_line: u32,
+    use_cache: Option<bool>,
+    report_transfer: Option<bool>,
+) -> Result<String, JsValue> {
+    let _op = Operation::start("batch_justify_text", texts_json.len());
+    let _profile = Profile::start("batch_justify_text");
+    let use_cache = use_cache.unwrap_or(false);
+    if report_transfer.unwrap_or(false) {
+        transfer::batch_justify_text_with_transfer(texts_json, max_chars_per_line, use_cache)
+    } else {
+        core_api::batch_justify_text(texts_json, max_chars_per_line, use_cache)
+    }
+    .map_err(|e| to_js_error("batch_justify_text", e))
+}
+
+/// Process text in chunks for large text handling
+/// Reduces memory pressure and enables progress tracking
+/// chunk_size is in bytes; chunk ends are moved back to the nearest character boundary.
+/// chunk_size 0 uses recommended_chunk_size and ends chunks at line breaks, so the output is
+/// justify_text's
+#[wasm_bindgen]
+pub fn process_text_chunks(
+    text: &str,
+    max_chars_per_line: u32,
+    chunk_size: u32,
+) -> Result<String, JsValue> {
+    let _op = Operation::start("process_text_chunks", text.len());
+    if chunk_size == 0 {
+        core_api::calibrate_chunk_cost(performance_now);
+    }
+    core_api::process_text_chunks(text, max_chars_per_line, chunk_size)
+        .map_err(|e| to_js_error("process_text_chunks", e))
+}
+
+/// chunk_size for process_text_chunks over text_len UTF-8 bytes: about 75 ms of wrapping per
+/// chunk, at most 64 chunks, and the whole text when it takes less
+/// The cost is measured by wrapping a small sample for a few ms the first time a chunk size
+/// is picked; configure({calibrateChunkSize: false}) beforehand skips that for a fixed estimate.
+#[wasm_bindgen]
+pub fn recommended_chunk_size(text_len: u32, max_chars_per_line: u32) -> u32 {
+    core_api::calibrate_chunk_cost(performance_now);
+    core_api::recommended_chunk_size(text_len as usize, max_chars_per_line)
+}
+
+/// Calculate character count with CJK width consideration
+/// Used for accurate text measurements; saturates at u32::MAX
+#[wasm_bindgen]
+pub fn calculate_text_width(text: &str) -> u32 {
+    u32::try_from(core_api::calculate_text_width(text)).unwrap_or(u32::MAX)
+}
 
-    let results: Vec<String> = text_array
+/// The longest prefix of text at most max_width display columns wide
+/// Never cuts through a grapheme (an emoji sequence, a character and its marks), leaves out a
+/// wide character that would go past max_width, and counts zero-width characters as nothing.
+#[wasm_bindgen]
+pub fn width_prefix(text: &str, max_width: u32) -> String {
+    core_api::width_prefix(text, max_width).to_string()
+}
+
+/// Length of width_prefix(text, max_width) in chars (code points, as [...text] counts them)
+#[wasm_bindgen]
+pub fn width_prefix_len(text: &str, max_width: u32) -> u32 {
+    core_api::width_prefix_len(text, max_width)
+}
+
+/// The longest suffix of text at most max_width display columns wide, cut as width_prefix
+/// cuts, for truncating from the left
+#[wasm_bindgen]
+pub fn width_suffix(text: &str, max_width: u32) -> String {
+    core_api::width_suffix(text, max_width).to_string()
+}
+
+/// The part of a line covering display columns start_col..end_col
+/// A wide character cut by either edge becomes a space per covered column, or is left out
+/// when pad_partial is false (default true); combining marks go with their base character
+#[wasm_bindgen]
+pub fn slice_columns(
+    line: &str,
+    start_col: u32,
+    end_col: u32,
+    pad_partial: Option<bool>,
+) -> String {
+    core_api::slice_columns(line, start_col, end_col, pad_partial.unwrap_or(true))
+}
+
+/// Index, in code points, of the character drawn at display column col
+/// Saturates at u32::MAX; a column past the end gives the line's length
+#[wasm_bindgen]
+pub fn column_to_char_index(line: &str, col: u32) -> u32 {
+    u32::try_from(core_api::column_to_char_index(line, col)).unwrap_or(u32::MAX)
+}
+
+/// Display width of the widest '\n'-separated line; line breaks count as zero
+/// Saturates at u32::MAX
+#[wasm_bindgen]
+pub fn max_line_width(text: &str) -> u32 {
+    u32::try_from(core_api::max_line_width(text)).unwrap_or(u32::MAX)
+}
+
+/// Display width of every '\n'-separated line as a JSON array of numbers
+#[wasm_bindgen]
+pub fn line_widths(text: &str) -> String {
+    let widths: Vec<String> = core_api::line_widths(text)
         .iter()
-        .map(|text| justify_text(text, max_chars_per_line))
+        .map(u64::to_string)
         .collect();
+    format!("[{}]", widths.join(","))
+}
 
-    serde_json::to_string(&results)
-        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+#[cfg(feature = "normalization")]
+/// Normalize text to "NFC" or "NFKC" (any case) before measuring or wrapping
+/// NFKC also folds fullwidth ASCII and compatibility ideographs to their plain forms
+#[wasm_bindgen]
+pub fn normalize_unicode(text: &str, form: &str) -> Result<String, JsValue> {
+    let form = core_api::NormalizationForm::parse(form)
+        .map_err(|e| to_js_error("normalize_unicode", e))?;
+    Ok(core_api::normalize_unicode(text, form))
 }
 
-/// Process text in chunks for large text handling
-/// Reduces memory pressure and enables progress tracking
+/// Make punctuation consistent: target "cjk" turns ASCII marks next to CJK text into
+/// ，。！？：；（）“”, "ascii" does the reverse
+/// Decimal points, digit separators and URLs are never converted.
 #[wasm_bindgen]
-pub fn process_text_chunks(text: &str, max_chars_per_line: u32, chunk_size: u32) -> String {
-    let chunk_size = chunk_size as usize;
-    let text_len = text.len();
+pub fn convert_punctuation(text: &str, target: &str) -> Result<String, JsValue> {
+    core_api::convert_punctuation(text, target).map_err(|e| to_js_error("convert_punctuation", e))
+}
 
-    if text_len <= chunk_size {
-        // Small text, process directly
-        return justify_text(text, max_chars_per_line);
-    }
+#[cfg(feature = "json")]
+/// convert_punctuation plus counts
+/// Returns JSON {text, substitutions, details: [{from, to, count}]}
+#[wasm_bindgen]
+pub fn convert_punctuation_report(text: &str, target: &str) -> Result<String, JsValue> {
+    core_api::convert_punctuation_report(text, target)
+        .map_err(|e| to_js_error("convert_punctuation_report", e))
+}
 
-    let mut result = String::with_capacity(text_len + text_len / 20);
-    let mut start = 0;
+#[cfg(feature = "json")]
+/// convert_punctuation as edits of text, so an editor can apply the change in place and keep
+/// cursors and undo history
+/// Returns JSON [{start, end, replacement}] in char indices of text, ascending and not touching
+#[wasm_bindgen]
+pub fn convert_punctuation_edits(text: &str, target: &str) -> Result<String, JsValue> {
+    core_api::convert_punctuation_edits(text, target)
+        .map_err(|e| to_js_error("convert_punctuation_edits", e))
+}
 
-    while start < text_len {
-        let end = std::cmp::min(start + chunk_size, text_len);
-        let chunk = &text[start..end];
+#[cfg(feature = "json")]
+/// Characters that look like another script's in the same word: fullwidth ０ in "2０24",
+/// Cyrillic а in "dаta", 〇 among digits, katakana カ between kanji
+/// Returns JSON {count, groups: [{class, start, word, chars: [{charIndex, char, script,
+/// suggestion}]}]}
+#[wasm_bindgen]
+pub fn confusables_report(text: &str) -> String {
+    core_api::confusables_report(text)
+}
 
-        let justified_chunk = justify_text(chunk, max_chars_per_line);
-        result.push_str(&justified_chunk);
+/// Rewrite what confusables_report finds; policy "latin-preferred" writes mixed words in
+/// ASCII, "cjk-preferred" in fullwidth forms or 〇
+/// Cyrillic, Greek and kana look-alikes take their suggestion under either policy.
+#[wasm_bindgen]
+pub fn apply_confusable_fixes(text: &str, policy: &str) -> Result<String, JsValue> {
+    core_api::apply_confusable_fixes(text, policy)
+        .map_err(|e| to_js_error("apply_confusable_fixes", e))
+}
 
-        // Add separator between chunks if not at the end
-        if end < text_len && !justified_chunk.ends_with("\r\n") {
-            result.push_str("\r\n");
-        }
+#[cfg(feature = "json")]
+/// Uppercase, lowercase or title-case text for headings; mode is "upper", "lower" or "title"
+/// Fullwidth letters stay fullwidth and CJK is untouched. Returns JSON {text, widthChanged};
+/// widthChanged means a mapping like ß → SS changed widths and the text needs wrapping again.
+#[wasm_bindgen]
+pub fn transform_case(text: &str, mode: &str) -> Result<String, JsValue> {
+    core_api::transform_case_json(text, mode).map_err(|e| to_js_error("transform_case", e))
+}
 
-        start = end;
-    }
+/// Join consecutive short lines of a chat log, then wrap like justify_text
+/// Lines of one message block are joined while the result is at most max_chars_per_line ×
+/// min_fill_ratio columns (0 to 1; 0 joins nothing). A blank line, or a line starting with a
+/// timestamp or "Name:", starts a new block. separator is "auto" (a space, nothing between
+/// CJK), "space" or "dot" (" · ").
+#[wasm_bindgen]
+pub fn merge_short_lines(
+    text: &str,
+    max_chars_per_line: u32,
+    min_fill_ratio: f64,
+    separator: &str,
+) -> Result<String, JsValue> {
+    core_api::MergeSeparator::parse(separator)
+        .and_then(|separator| {
+            core_api::merge_short_lines(text, max_chars_per_line, min_fill_ratio, separator)
+        })
+        .map_err(|e| to_js_error("merge_short_lines", e))
+}
 
-    result
+/// Unwrap hard-wrapped text into paragraphs, then wrap like justify_text
+/// Lines of a paragraph or list item join with a space (nothing between CJK); headings, code
+/// blocks and blank lines stay. A word hyphenated at a line end ("justifi-\ncation") loses the
+/// hyphen when the next line starts lowercase; keep_hyphens (default false) keeps every hyphen.
+#[wasm_bindgen]
+pub fn reflow_text(
+    text: &str,
+    max_chars_per_line: u32,
+    keep_hyphens: Option<bool>,
+) -> Result<String, JsValue> {
+    core_api::reflow_text(text, max_chars_per_line, keep_hyphens.unwrap_or(false))
+        .map_err(|e| to_js_error("reflow_text", e))
 }
 
-/// Calculate character count with CJK width consideration
-/// Used for accurate text measurements
+#[cfg(feature = "json")]
+/// What is left of a caption limit after text, negative when text is over it
+/// counting_mode is "chars" (code points), "utf16" (JavaScript's length), "displayWidth"
+/// (columns) or "weightedTwitter" (CJK and emoji 2, URLs 23); weights_json overrides the
+/// weighted count's {light, heavy, emoji, url}, "" for Twitter's own
 #[wasm_bindgen]
-pub fn calculate_text_width(text: &str) -> u32 {
-    text.chars().map(get_char_width).sum()
+pub fn remaining_budget(
+    text: &str,
+    limit: u32,
+    counting_mode: &str,
+    weights_json: &str,
+) -> Result<i32, JsValue> {
+    let mode = core_api::CountingMode::parse_with_weights(counting_mode, weights_json)
+        .map_err(|e| to_js_error("remaining_budget", e))?;
+    Ok(core_api::remaining_budget(text, limit, mode))
+}
+
+#[cfg(feature = "json")]
+/// text cut to fit a caption limit, ellipsis appended when it had to be cut
+/// Counts as remaining_budget does, the ellipsis included, and never cuts through a grapheme
+/// (or, in the weighted count, a URL); text that fits comes back as it is.
+#[wasm_bindgen]
+pub fn truncate_to_budget(
+    text: &str,
+    limit: u32,
+    counting_mode: &str,
+    weights_json: &str,
+    ellipsis: &str,
+) -> Result<String, JsValue> {
+    let mode = core_api::CountingMode::parse_with_weights(counting_mode, weights_json)
+        .map_err(|e| to_js_error("truncate_to_budget", e))?;
+    Ok(core_api::truncate_to_budget(text, limit, mode, ellipsis))
+}
+
+#[cfg(feature = "json")]
+/// Align tab-separated rows into columns that fit max_chars_per_line, for pasted TSV
+/// options_json: {maxColumnWidth, columnGap, header, overflow: "drop"|"wrap"}, all optional;
+/// "" uses the defaults (24-column cap, 2-space gap, no header rule, columns that don't fit
+/// dropped). Cells too wide for their column end in "…"; "wrap" moves the columns that don't
+/// fit to another table below instead of dropping them.
+#[wasm_bindgen]
+pub fn format_tsv(
+    text: &str,
+    max_chars_per_line: u32,
+    options_json: &str,
+) -> Result<String, JsValue> {
+    core_api::format_tsv_json(text, max_chars_per_line, options_json)
+        .map_err(|e| to_js_error("format_tsv", e))
+}
+
+#[cfg(feature = "json")]
+/// Char indices a line may break before, as a JSON array, whatever the width; for drawing
+/// soft-wrap guides without wrapping
+/// options_json: {locale, extraNoStart, extraNoEnd}, all optional. "" gives justify_text's
+/// rules (spaces on word-wrapped lines, any boundary on CJK lines, never inside an RTL run); a
+/// locale gives justify_text_cjk_locale's kinsoku rules. Number tokens never break inside.
+#[wasm_bindgen]
+pub fn break_opportunities(line: &str, options_json: &str) -> Result<String, JsValue> {
+    core_api::break_opportunities_json(line, options_json)
+        .map_err(|e| to_js_error("break_opportunities", e))
+}
+
+/// Remove the longest common leading indentation (blank lines don't count)
+/// tab_width (default 4) is how many columns a tab advances to
+#[wasm_bindgen]
+pub fn dedent(text: &str, tab_width: Option<u32>) -> String {
+    core_api::dedent(text, tab_width.unwrap_or(core_api::DEFAULT_TAB_WIDTH))
+}
+
+/// Prefix every non-blank line (every line with include_empty) with prefix
+#[wasm_bindgen]
+pub fn indent(text: &str, prefix: &str, include_empty: Option<bool>) -> String {
+    core_api::indent(text, prefix, include_empty.unwrap_or(false))
+}
+
+/// Whether text is indented with tabs or spaces, and by how much per level
+/// Returns JSON {style: "none"|"tabs"|"spaces", size}
+#[wasm_bindgen]
+pub fn detect_indentation(text: &str) -> String {
+    core_api::detect_indentation_json(text)
+}
+
+/// Rewrite leading whitespace with mode "tabs_to_spaces" or "spaces_to_tabs"
+/// tab_width defaults to 4; spaces after a line's first non-blank character are left alone
+#[wasm_bindgen]
+pub fn convert_indentation(
+    text: &str,
+    mode: &str,
+    tab_width: Option<u32>,
+) -> Result<String, JsValue> {
+    core_api::convert_indentation(text, mode, tab_width.unwrap_or(core_api::DEFAULT_TAB_WIDTH))
+        .map_err(|e| to_js_error("convert_indentation", e))
+}
+
+/// Set the indent at the start of every prose paragraph: mode "add" (exactly two U+3000,
+/// the Chinese paragraph style), "remove" (none) or "leave"
+/// Whatever mix of U+3000, spaces and tabs was there is replaced; list items, headings and
+/// code blocks keep theirs. Running a mode again changes nothing.
+#[wasm_bindgen]
+pub fn normalize_cjk_indent(text: &str, mode: &str) -> Result<String, JsValue> {
+    core_api::normalize_cjk_indent(text, mode).map_err(|e| to_js_error("normalize_cjk_indent", e))
+}
+
+/// Lines whose indentation mixes tabs and spaces, as JSON [{line, tabs, spaces}] (1-based)
+#[wasm_bindgen]
+pub fn lint_indentation(text: &str) -> String {
+    core_api::lint_indentation_json(text)
+}
+
+/// Split text into sentences for one-sentence-per-line layouts
+/// Handles ASCII and CJK terminators, keeps closing quotes with their sentence and doesn't
+/// split on decimals, abbreviations (Mr., e.g., U.S.) or inside quotes
+#[wasm_bindgen]
+pub fn split_sentences(text: &str) -> js_sys::Array {
+    core_api::split_sentences(text)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
+
+/// split_sentences joined with "\n", ready for justify_text
+#[wasm_bindgen]
+pub fn one_sentence_per_line(text: &str) -> String {
+    core_api::one_sentence_per_line(text)
+}
+
+/// Split text into words, the same ones get_text_stats counts
+/// Keeps "don't", "state-of-the-art" and "3.14" whole; each Han character and each kana run
+/// is a word of its own; punctuation and whitespace are skipped
+#[wasm_bindgen]
+pub fn split_words(text: &str) -> js_sys::Array {
+    core_api::split_words(text)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
+
+/// Remove email quote markers (">", "> >", ">>") from the start of every line
+#[wasm_bindgen]
+pub fn strip_quote_prefix(text: &str) -> String {
+    core_api::strip_quote_prefix(text)
+}
+
+#[cfg(feature = "json")]
+/// strip_quote_prefix with each line's quote depth
+/// Returns JSON array [{depth, text}], one entry per line
+#[wasm_bindgen]
+pub fn strip_quote_prefix_detailed(text: &str) -> String {
+    core_api::strip_quote_prefix_detailed(text)
+}
+
+/// Quote every line depth levels deeper (">> text"); existing markers are rewritten
+#[wasm_bindgen]
+pub fn add_quote_prefix(text: &str, depth: u32) -> String {
+    core_api::add_quote_prefix(text, depth)
+}
+
+/// Rewrap each run of equally quoted lines to max_chars_per_line, prefix included
+/// Runs that already fit are left unchanged
+#[wasm_bindgen]
+pub fn rewrap_quoted(text: &str, max_chars_per_line: u32) -> String {
+    core_api::rewrap_quoted(text, max_chars_per_line)
+}
+
+/// Remove ANSI escape sequences (terminal colors, OSC titles and links) from pasted text
+/// Truncated sequences at the end are dropped; a stray ESC is removed on its own
+#[wasm_bindgen]
+pub fn strip_ansi(text: &str) -> String {
+    core_api::strip_ansi(text)
+}
+
+/// Remove Markdown syntax (emphasis, headings, links, images, code backticks, quote and
+/// list markers) so stats and the image reflect the prose; fenced code is kept verbatim
+#[wasm_bindgen]
+pub fn strip_markdown(text: &str) -> String {
+    core_api::strip_markdown(text)
 }
 
 /// Validate text input for processing
 /// Returns error message if invalid, empty string if valid
 #[wasm_bindgen]
 pub fn validate_text_input(text: &str) -> String {
-    if text.is_empty() {
-        return "Text cannot be empty".to_string();
-    }
-
-    if text.len() > 500_000 {
-        return "Text too large: maximum 500,000 characters supported".to_string();
-    }
+    core_api::validate_text_input(text)
+}
 
-    String::new() // Empty string means valid
+#[cfg(feature = "json")]
+/// Run every validation rule against text at the given line width
+/// Returns JSON {valid, errors, warnings}; each entry is {code, message, details}.
+/// Errors should block processing, warnings can be shown inline.
+#[wasm_bindgen]
+pub fn validate_input(text: &str, max_chars_per_line: u32) -> String {
+    core_api::validate_input(text, max_chars_per_line)
 }
 
 /// Get text processing statistics
 /// Returns JSON string with analysis data
 #[wasm_bindgen]
 pub fn get_text_stats(text: &str) -> String {
-    let char_count = text.chars().count();
-    let byte_count = text.len();
-    let line_count = text.lines().count();
-    let cjk_count = text.chars().filter(|&c| is_cjk_char(c)).count();
-    let ascii_count = text.chars().filter(|&c| (c as u32) <= 0xFF).count();
-    let display_width = calculate_text_width(text);
-
-    format!(
-        r#"{{
-            "charCount": {},
-            "byteCount": {},
-            "lineCount": {},
-            "cjkCount": {},
-            "asciiCount": {},
-            "displayWidth": {},
-            "hasCjk": {}
-        }}"#,
-        char_count,
-        byte_count,
-        line_count,
-        cjk_count,
-        ascii_count,
-        display_width,
-        is_cjk(text)
-    )
+    let _profile = Profile::start("get_text_stats");
+    core_api::get_text_stats(text)
+}
+
+/// get_text_stats of the text before its signature: the first line equal to
+/// signature_delimiter and everything after it aren't counted ("" counts the whole text)
+#[wasm_bindgen]
+pub fn get_text_stats_excluding_signature(text: &str, signature_delimiter: &str) -> String {
+    let _profile = Profile::start("get_text_stats_excluding_signature");
+    core_api::get_text_stats_excluding_signature(text, signature_delimiter)
+}
+
+#[cfg(feature = "json")]
+/// Character, word and line counts, display width and reading time, each as a number and as
+/// display text for locale: "en" (32,415; "3 min"), "zh" (3.2万; "3分钟") or "ja" (3.2万;
+/// "3分"), a region ("zh-CN") being ignored. Returns JSON {locale, charCountRaw,
+/// charCountDisplay, wordCountRaw, wordCountDisplay, lineCountRaw, lineCountDisplay,
+/// displayWidthRaw, displayWidthDisplay, readingMinutesRaw, readingMinutesDisplay}
+#[wasm_bindgen]
+pub fn format_stats(text: &str, locale: &str) -> Result<String, JsValue> {
+    core_api::format_stats(text, locale).map_err(|e| to_js_error("format_stats", e))
+}
+
+#[cfg(feature = "json")]
+/// get_text_stats for live typing: when text is the previous text with more typed at the end,
+/// only the end is gone over
+/// prev_stats_json is get_text_stats' JSON for the previous text and old_text_len that text's
+/// length. Any other edit (old_text_len not matching the stats, text not longer) falls back
+/// to get_text_stats over the whole text, so the result is always get_text_stats(text) as
+/// long as text starts with the text the stats are for.
+#[wasm_bindgen]
+pub fn stats_incremental(
+    prev_stats_json: &str,
+    old_text_len: u32,
+    text: &str,
+) -> Result<String, JsValue> {
+    let _profile = Profile::start("stats_incremental");
+    core_api::stats_incremental_json(prev_stats_json, old_text_len as usize, text)
+        .map_err(|e| to_js_error("stats_incremental", e))
+}
+
+#[cfg(feature = "json")]
+/// Per-paragraph statistics for document outline views
+/// Returns JSON array with one object per blank-line-separated paragraph
+#[wasm_bindgen]
+pub fn paragraph_stats(text: &str, max_chars_per_line: u32) -> String {
+    core_api::paragraph_stats(text, max_chars_per_line)
+}
+
+#[cfg(feature = "json")]
+/// Split text into prose paragraphs, list items, fenced code blocks, headings and blank runs
+/// Returns JSON array of {text, srcStartLine, srcEndLine, blankLinesBefore, kind}, kind being
+/// "prose", "listItem", "codeBlock", "heading", "blank" or "signature" (from a "-- " line to
+/// the end); source lines are 0-based, the end line included. These are the paragraphs paragraph_stats and split_even_sections use.
+#[wasm_bindgen]
+pub fn split_paragraphs(text: &str) -> String {
+    core_api::split_paragraphs(text)
 }
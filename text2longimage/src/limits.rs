@@ -0,0 +1,54 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Set the per-input size cap in UTF-8 bytes (default 500,000)
+/// Consulted by validate_text_input, the batch APIs (per item) and process_text_chunks
+#[wasm_bindgen]
+pub fn set_max_input_size(bytes: u32) {
+    core_api::set_max_input_size(bytes as usize);
+}
+
+/// Current per-input size cap in bytes
+#[wasm_bindgen]
+pub fn get_max_input_size() -> u32 {
+    u32::try_from(core_api::max_input_size()).unwrap_or(u32::MAX)
+}
+
+/// Set the cap on a batch's combined size in UTF-8 bytes (default 5,000,000)
+#[wasm_bindgen]
+pub fn set_max_batch_size(bytes: u32) {
+    core_api::set_max_batch_size(bytes as usize);
+}
+
+/// Current combined batch size cap in bytes
+#[wasm_bindgen]
+pub fn get_max_batch_size() -> u32 {
+    u32::try_from(core_api::max_batch_size()).unwrap_or(u32::MAX)
+}
+
+/// Set the cap on the number of items in a batch (default 100,000)
+#[wasm_bindgen]
+pub fn set_max_batch_items(items: u32) {
+    core_api::set_max_batch_items(items as usize);
+}
+
+/// Current cap on the number of items in a batch
+#[wasm_bindgen]
+pub fn get_max_batch_items() -> u32 {
+    u32::try_from(core_api::max_batch_items()).unwrap_or(u32::MAX)
+}
+
+/// Set the cap on output bytes per input byte (default 10, 0 for none)
+/// justify_text_with_options, layout_text_lines and process_text_chunks throw
+/// OUTPUT_TOO_LARGE past it instead of running out of memory; output up to 64 KiB always fits
+#[wasm_bindgen]
+pub fn set_max_output_ratio(ratio: u32) {
+    core_api::set_max_output_ratio(ratio);
+}
+
+/// Current cap on output bytes per input byte, 0 for none
+#[wasm_bindgen]
+pub fn get_max_output_ratio() -> u32 {
+    core_api::max_output_ratio()
+}
@@ -0,0 +1,44 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WrappedLine<'a> {
+    text: &'a str,
+    width: u32,
+    hard_break: bool,
+}
+
+/// The lines of justify_text(text, max_chars_per_line), produced one at a time
+/// Lets a caller render or stop early on a large document without building the whole output
+#[wasm_bindgen]
+pub struct WrapLines {
+    inner: core_api::WrapIter<String>,
+}
+
+#[wasm_bindgen]
+impl WrapLines {
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: String, max_chars_per_line: u32) -> WrapLines {
+        WrapLines {
+            inner: core_api::WrapIter::new(text, max_chars_per_line),
+        }
+    }
+
+    /// The next line as {text, width, hardBreak}, or undefined after the last one
+    /// width is in half-width columns; hardBreak is true where the source line ended
+    pub fn next_line(&mut self) -> JsValue {
+        let Some(line) = self.inner.next() else {
+            return JsValue::UNDEFINED;
+        };
+        let text = line.text(self.inner.source());
+        serde_wasm_bindgen::to_value(&WrappedLine {
+            text: &text,
+            width: line.width,
+            hard_break: line.hard_break,
+        })
+        .unwrap_or(JsValue::UNDEFINED)
+    }
+}
@@ -0,0 +1,170 @@
+//! Runtime-switchable diagnostics routed to the devtools console
+//! Logging is off until set_log_level is called; the `no-console` feature removes the
+//! console bindings entirely and turns every call here into a no-op
+
+use std::cell::Cell;
+
+use wasm_bindgen::prelude::*;
+
+pub(crate) const LOG_OFF: u32 = 0;
+pub(crate) const LOG_ERROR: u32 = 1;
+pub(crate) const LOG_INFO: u32 = 2;
+pub(crate) const LOG_TRACE: u32 = 3;
+
+thread_local! {
+    static LOG_LEVEL: Cell<u32> = const { Cell::new(LOG_OFF) };
+    static OPERATION_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+#[cfg(not(feature = "no-console"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = log)]
+    fn console_log(s: &str);
+
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(s: &str);
+}
+
+/// Set the console log level: 0 off, 1 error, 2 info, 3 trace (higher values mean trace)
+/// Info logs one line per top-level call with its input size and elapsed time;
+/// trace also logs the calls made internally by other entry points
+#[wasm_bindgen]
+pub fn set_log_level(level: u32) {
+    LOG_LEVEL.with(|l| l.set(level.min(LOG_TRACE)));
+}
+
+/// Current console log level
+#[wasm_bindgen]
+pub fn get_log_level() -> u32 {
+    LOG_LEVEL.with(Cell::get)
+}
+
+#[cfg(not(feature = "no-console"))]
+pub(crate) fn enabled(level: u32) -> bool {
+    level != LOG_OFF && level <= get_log_level()
+}
+
+#[cfg(feature = "no-console")]
+pub(crate) fn enabled(_level: u32) -> bool {
+    false
+}
+
+#[cfg(not(feature = "no-console"))]
+fn emit(level: u32, message: &str) {
+    if level == LOG_ERROR {
+        console_error(message);
+    } else {
+        console_log(message);
+    }
+}
+
+#[cfg(feature = "no-console")]
+fn emit(_level: u32, _message: &str) {}
+
+/// Log a failure that is about to be returned to JS
+pub(crate) fn error(operation: &str, message: &str) {
+    if enabled(LOG_ERROR) {
+        emit(
+            LOG_ERROR,
+            &format!("[text_processor] op={} error={}", operation, message),
+        );
+    }
+}
+
+pub(crate) fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// Sub-millisecond clock, for timing work shorter than Date.now's tick
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    pub(crate) fn performance_now() -> f64;
+}
+
+/// Timing guard for an entry point: logs operation, input size and elapsed ms when dropped
+/// Only the outermost operation logs at info; nested ones log at trace
+pub(crate) struct Operation {
+    name: &'static str,
+    input_bytes: usize,
+    level: u32,
+    started_ms: f64,
+}
+
+impl Operation {
+    pub(crate) fn start(name: &'static str, input_bytes: usize) -> Self {
+        let depth = OPERATION_DEPTH.with(|d| {
+            let depth = d.get();
+            d.set(depth + 1);
+            depth
+        });
+        let level = if depth == 0 { LOG_INFO } else { LOG_TRACE };
+        let started_ms = if enabled(level) { now_ms() } else { 0.0 };
+
+        Operation {
+            name,
+            input_bytes,
+            level,
+            started_ms,
+        }
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        OPERATION_DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+        if enabled(self.level) {
+            emit(
+                self.level,
+                &format!(
+                    "[text_processor] op={} input_bytes={} elapsed_ms={:.2}",
+                    self.name,
+                    self.input_bytes,
+                    now_ms() - self.started_ms
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test runs on a thread of its own, so the thread-local level starts off every time
+
+    #[test]
+    fn levels_above_trace_mean_trace() {
+        assert_eq!(get_log_level(), LOG_OFF);
+        set_log_level(99);
+        assert_eq!(get_log_level(), LOG_TRACE);
+        set_log_level(LOG_ERROR);
+        assert_eq!(get_log_level(), LOG_ERROR);
+    }
+
+    #[cfg(not(feature = "no-console"))]
+    #[test]
+    fn a_level_enables_itself_and_the_ones_below() {
+        assert!(!enabled(LOG_ERROR));
+        set_log_level(LOG_INFO);
+        assert!(enabled(LOG_ERROR) && enabled(LOG_INFO) && !enabled(LOG_TRACE));
+        // Off is never logged, whatever the level
+        set_log_level(LOG_TRACE);
+        assert!(!enabled(LOG_OFF));
+    }
+
+    #[test]
+    fn nested_operations_log_at_trace() {
+        // Logging off, so nothing is emitted and no clock is read
+        let outer = Operation::start("batch_justify", 10);
+        {
+            let inner = Operation::start("justify_text", 5);
+            assert_eq!(inner.level, LOG_TRACE);
+        }
+        assert_eq!(outer.level, LOG_INFO);
+        drop(outer);
+        assert_eq!(OPERATION_DEPTH.with(Cell::get), 0);
+        assert_eq!(Operation::start("justify_text", 5).level, LOG_INFO);
+    }
+}
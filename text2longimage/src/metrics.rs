@@ -0,0 +1,130 @@
+use std::cell::RefCell;
+
+use wasm_bindgen::prelude::*;
+
+use text_processor_core::{
+    justify_text_with_metrics, justify_text_with_provider, FontMetrics, MemoizedWidths,
+    WidthProvider,
+};
+
+use crate::objects::MeasureCallback;
+#[cfg(feature = "json")]
+use crate::to_js_error;
+
+thread_local! {
+    static FONT_METRICS: RefCell<FontMetrics> = RefCell::new(FontMetrics::default());
+}
+
+#[cfg(feature = "json")]
+/// Register character widths measured from the rendering font
+/// Parsed once and kept for subsequent justify_text_measured calls
+/// JSON shape: {"narrow": 0.5, "wide": 1.0, "chars": {"i": 0.3}, "ranges": [[0x4E00, 0x9FFF, 1.0]]}
+#[wasm_bindgen]
+pub fn set_font_metrics(metrics_json: &str) -> Result<(), JsValue> {
+    let metrics =
+        FontMetrics::from_json(metrics_json).map_err(|e| to_js_error("set_font_metrics", e))?;
+    register_font_metrics(metrics);
+    Ok(())
+}
+
+/// Replace the metrics justify_text_measured uses
+pub(crate) fn register_font_metrics(metrics: FontMetrics) {
+    FONT_METRICS.with(|cell| *cell.borrow_mut() = metrics);
+}
+
+#[cfg(feature = "json")]
+/// f of the metrics justify_text_measured uses
+pub(crate) fn with_font_metrics<R>(f: impl FnOnce(&FontMetrics) -> R) -> R {
+    FONT_METRICS.with(|cell| f(&cell.borrow()))
+}
+
+/// Restore the default 0.5 em / 1.0 em metrics
+#[wasm_bindgen]
+pub fn clear_font_metrics() {
+    register_font_metrics(FontMetrics::default());
+}
+
+/// Justify text against the registered font metrics
+/// max_width_em is the line width in em units
+#[wasm_bindgen]
+pub fn justify_text_measured(text: &str, max_width_em: f64) -> String {
+    FONT_METRICS.with(|cell| justify_text_with_metrics(text, max_width_em, &cell.borrow()))
+}
+
+/// The measure callback behind MeasuredWidths, holding on to the first thing it threw
+struct JsMeasure {
+    measure: js_sys::Function,
+    error: Option<JsValue>,
+}
+
+impl WidthProvider for JsMeasure {
+    fn width(&mut self, c: char) -> f64 {
+        let result = self
+            .measure
+            .call1(&JsValue::NULL, &JsValue::from(c as u32))
+            .and_then(|width| {
+                width.as_f64().ok_or_else(|| {
+                    JsValue::from_str(&format!(
+                        "measure must return a number for U+{:04X}",
+                        c as u32
+                    ))
+                })
+            });
+        match result {
+            Ok(width) => width,
+            Err(error) => {
+                self.error.get_or_insert(error);
+                f64::NAN
+            }
+        }
+    }
+}
+
+/// Character widths measured by a JS callback (e.g. canvas measureText), each character once
+/// measure gets a code point and returns its advance in em; results are kept for the life of
+/// the object, so build one per font and call clear() if the font changes. NaN, negative or
+/// infinite widths fall back to the built-in 0.5 em / 1.0 em.
+/// Performance: the first sight of every character is a call across the wasm boundary, and
+/// lines are measured a character at a time; a FontMetrics table via set_font_metrics is
+/// faster wherever the widths can be measured up front.
+#[wasm_bindgen]
+pub struct MeasuredWidths {
+    inner: MemoizedWidths<JsMeasure>,
+}
+
+#[wasm_bindgen]
+impl MeasuredWidths {
+    #[wasm_bindgen(constructor)]
+    pub fn new(measure: &MeasureCallback) -> MeasuredWidths {
+        MeasuredWidths {
+            inner: MemoizedWidths::new(JsMeasure {
+                measure: measure.unchecked_ref::<js_sys::Function>().clone(),
+                error: None,
+            }),
+        }
+    }
+
+    /// justify_text_measured with these widths; max_width_em is in measure's unit
+    /// Rethrows whatever measure throws (or a non-number it returns), forgetting every width
+    /// measured so far so the next call asks again.
+    pub fn justify(&mut self, text: &str, max_width_em: f64) -> Result<String, JsValue> {
+        let justified = justify_text_with_provider(text, max_width_em, &self.inner);
+        match self.inner.provider_mut().error.take() {
+            Some(error) => {
+                self.inner.clear();
+                Err(error)
+            }
+            None => Ok(justified),
+        }
+    }
+
+    /// Number of distinct characters measure has been called for since the last clear()
+    pub fn measured_count(&self) -> u32 {
+        self.inner.measured_count() as u32
+    }
+
+    /// Forget every measured width
+    pub fn clear(&self) {
+        self.inner.clear();
+    }
+}
@@ -0,0 +1,63 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Justify text with a right-aligned line-number gutter in front of every source line
+/// The gutter width comes out of max_chars_per_line, so numbered lines keep the same total width;
+/// wrapped continuation lines get an empty gutter
+#[wasm_bindgen]
+pub fn justify_text_numbered(text: &str, max_chars_per_line: u32, number_start: u32) -> String {
+    core_api::justify_text_numbered(text, max_chars_per_line, number_start)
+}
+
+#[cfg(feature = "json")]
+/// layout_text for the numbered output of justify_text_numbered (left aligned)
+#[wasm_bindgen]
+pub fn layout_text_numbered(
+    text: &str,
+    max_chars_per_line: u32,
+    number_start: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+) -> String {
+    core_api::layout_text_numbered(
+        text,
+        max_chars_per_line,
+        number_start,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+    )
+}
+
+/// Prefix every line with a right-aligned line number and separator (e.g. "│ " or ": ")
+/// pad_char fills the number column by display width, so a fullwidth pad takes two columns.
+/// include_blank (default true) numbers whitespace-only lines too; unset, they stay bare.
+#[wasm_bindgen]
+pub fn number_lines(
+    text: &str,
+    start: u32,
+    separator: &str,
+    pad_char: char,
+    include_blank: Option<bool>,
+) -> String {
+    core_api::number_lines(
+        text,
+        start,
+        separator,
+        pad_char,
+        include_blank.unwrap_or(true),
+    )
+}
+
+/// Display width of the prefix number_lines adds, to subtract from max_chars_per_line
+#[wasm_bindgen]
+pub fn number_lines_gutter_width(
+    text: &str,
+    start: u32,
+    separator: &str,
+    include_blank: Option<bool>,
+) -> u32 {
+    core_api::number_lines_gutter_width(text, start, separator, include_blank.unwrap_or(true))
+}
@@ -0,0 +1,624 @@
+//! Object-returning variants of the JSON-string APIs
+//! Values cross the boundary via serde-wasm-bindgen instead of JSON.stringify/parse
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+
+#[cfg(feature = "json")]
+use text_processor_core::OptionsReport;
+use text_processor_core::{
+    batch_results, check_batch_item_count, check_batch_items, check_option_names,
+    justify_batch_items, justify_batch_with_report, layout_lines_with_ruby, strict_for,
+    validate_text, Align, BatchItem, BatchResult, JustifyOptions, Stage, TextStats, TransferReport,
+    WebtoolsError,
+};
+
+use crate::logging::{now_ms, Operation};
+use crate::profiling::{self, Profile};
+use crate::to_js_error;
+use crate::transfer::TransferMeter;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = TS_DEFINITIONS;
+
+/// The TypeScript added to the generated .d.ts, in a const of its own so tests can read it
+/// (only the wasm32 build emits the section, so natively nothing else uses it)
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+const TS_DEFINITIONS: &str = r#"
+export type WebtoolsErrorCode =
+    | "INVALID_JSON"
+    | "INVALID_UTF8"
+    | "INVALID_ENCODING"
+    | "EMPTY_INPUT"
+    | "INPUT_TOO_LARGE"
+    | "TOO_MANY_CHARACTERS"
+    | "BATCH_ITEM_TOO_LARGE"
+    | "BATCH_TOO_LARGE"
+    | "TOO_MANY_BATCH_ITEMS"
+    | "OUTPUT_TOO_LARGE"
+    | "INVALID_WIDTH"
+    | "WIDTH_TOO_LARGE"
+    | "INVALID_RANGE"
+    | "INVALID_ARGUMENT"
+    | "CONFLICTING_OPTIONS"
+    | "CANCELLED"
+    | "INTERNAL_ERROR";
+
+/**
+ * Thrown by every fallible export; details names the two fields for CONFLICTING_OPTIONS, the
+ * encoding for INVALID_ENCODING, the item's id for BATCH_ITEM_TOO_LARGE when it has one, and
+ * the offending output line for INTERNAL_ERROR (strict-checks builds only); OUTPUT_TOO_LARGE
+ * has {ratio, limit, lineIndex}
+ */
+export type WebtoolsError =
+    | {
+          code: Exclude<
+              WebtoolsErrorCode,
+              | "INVALID_ENCODING"
+              | "BATCH_ITEM_TOO_LARGE"
+              | "CONFLICTING_OPTIONS"
+              | "INTERNAL_ERROR"
+          >;
+          message: string;
+          details: Record<string, number>;
+      }
+    | {
+          code: "INVALID_ENCODING";
+          message: string;
+          details: { encoding: string; offset: number };
+      }
+    | {
+          code: "BATCH_ITEM_TOO_LARGE";
+          message: string;
+          details: { index: number; limit: number; actual: number; id?: BatchId };
+      }
+    | {
+          code: "CONFLICTING_OPTIONS";
+          message: string;
+          details: { field: string; other: string };
+      }
+    | {
+          code: "INTERNAL_ERROR";
+          message: string;
+          details: { lineIndex: number; line: string };
+      };
+
+/** An option that was set but has no effect because another one overrides it */
+export interface OptionWarning {
+    field: string;
+    overriddenBy: string;
+    message: string;
+}
+
+/** A fallback justify took instead of failing; lineIndex and charIndex locate it in the input */
+export interface Warning {
+    code:
+        | "OPTION_IGNORED"
+        | "TOKEN_OVERFLOW"
+        | "RUN_CUT"
+        | "REPEAT_TRUNCATED"
+        | "MARKS_DROPPED"
+        | "INVISIBLE_LINE_EMPTIED"
+        | "RUBY_UNCLOSED"
+        | "FOOTER_TRUNCATED";
+    message: string;
+    /** Input line, split at "\n" */
+    lineIndex?: number;
+    /** Char offset within that line */
+    charIndex?: number;
+    /** OPTION_IGNORED: the ignored option and the one overriding it */
+    field?: string;
+    overriddenBy?: string;
+}
+
+export interface JustifiedText {
+    text: string;
+    warnings: Warning[];
+}
+
+export function justify_text_with_options(
+    text: string,
+    options: JustifyOptions | undefined,
+    collect_warnings?: false | null,
+): string;
+export function justify_text_with_options(
+    text: string,
+    options: JustifyOptions | undefined,
+    collect_warnings: true,
+): JustifiedText;
+
+export interface JustifyOptions {
+    /** Half-width columns, 1 to 100,000 */
+    maxCharsPerLine?: number;
+    lineNumbers?: boolean;
+    numberStart?: number;
+    stripAnsi?: boolean;
+    normalizeNfc?: boolean;
+    trimTrailing?: boolean;
+    markdown?: boolean;
+    hardBreakMarker?: string;
+    softBreakMarker?: string;
+    algorithmVersion?: number;
+    rubySyntax?: "none" | "kanji" | "aozora";
+    footerText?: string;
+    footerAlign?: "left" | "center" | "right";
+    footerBlankLines?: number;
+    markSoftBreaks?: string;
+    breakMarkerEnd?: string;
+    breakMarkerStart?: string;
+    punctuationBreakWindow?: number;
+    /** Whitespace in the output shown as symbols (·, →, □, ⍽, ∅, ¶); for display only */
+    visualizeWhitespace?: boolean;
+    /** & < > " ' written as HTML entities, after wrapping; lines break as they would unescaped */
+    escapeHtml?: boolean;
+    /** Words wider than this many columns are cut into lines (default 10000, 0 for no limit) */
+    maxUnbreakableRun?: number;
+    /** Keep the whitespace between words on a line (e.g. U+3000) instead of one space */
+    preserveSeparatorChars?: boolean;
+    /** Characters a word may break after (default "—–-/・·"); algorithmVersion 4 and later */
+    breakAfterChars?: string;
+    /** Combining marks kept on one character, the rest dropped (default 8, 0 for no limit) */
+    maxMarksPerCluster?: number;
+    /** Repeats of one character or emoji kept in a row, the rest dropped (default 0, no limit) */
+    maxCharRepeat?: number;
+    /** Continuation lines of a list item ("(a) ", "iv. ", "第1条 ", "①") indented under its text */
+    hangingIndent?: boolean;
+    /** Whitespace hangingIndent writes after a list marker */
+    listMarkerSpacing?: "keep" | "space" | "ideographic";
+    /** Line opening a signature kept as written, unwrapped (default "-- ", "" for none); algorithmVersion 7 and later */
+    signatureDelimiter?: string;
+    /** Output bytes per input byte before OUTPUT_TOO_LARGE, 0 for no limit; null for configure's maxOutputRatio */
+    maxOutputRatio?: number | null;
+    /** Throw on unknown fields of this object (true) or ignore them (false); null for configure's strictOptions */
+    strictOptions?: boolean | null;
+}
+
+/** configure's input, and the effective configuration it returns */
+export interface RuntimeConfig {
+    panicHook?: boolean;
+    logLevel?: number;
+    maxInputSize?: number;
+    maxBatchSize?: number;
+    maxBatchItems?: number;
+    /** Output bytes per input byte before wrapping throws OUTPUT_TOO_LARGE (0 for no limit) */
+    maxOutputRatio?: number;
+    cacheCapacity?: number;
+    fontMetrics?: object | null;
+    strictOptions?: boolean;
+    calibrateChunkSize?: boolean;
+    /** heightFactor of layout lines with CJK */
+    cjkLineHeightFactor?: number;
+    /** heightFactor of layout lines with emoji */
+    emojiLineHeightFactor?: number;
+}
+
+export interface TextStats {
+    charCount: number;
+    byteCount: number;
+    lineCount: number;
+    cjkCount: number;
+    asciiCount: number;
+    displayWidth: number;
+    hasCjk: boolean;
+    wordCount: number;
+    rtlRunCount: number;
+    /** Characters above U+FFFF (two UTF-16 code units each) */
+    astralCount: number;
+    /** Widest word the word wrapper can't break, in columns (compare maxUnbreakableRun) */
+    longestUnbreakableRun: number;
+    /** Most times one character or emoji repeats in a row, whitespace aside (compare maxCharRepeat) */
+    longestCharRepeat: number;
+    combiningMarkCount: number;
+    /** Combining marks outnumber the other characters: likely "Zalgo" text */
+    zalgoSuspect: boolean;
+    /** U+FEFF byte order marks, which charCount leaves out */
+    bomCount: number;
+}
+
+/** A batch item's own id, echoed back in its result and in a BATCH_ITEM_TOO_LARGE error */
+export type BatchId = string | number;
+
+/** A batch item that carries an id; a bare string is an item without one */
+export interface BatchItem {
+    id?: BatchId;
+    text: string;
+}
+
+export interface BatchResult {
+    /** The item's id, when it had one */
+    id?: BatchId;
+    text: string;
+    lineCount: number;
+}
+
+/** What one batch call moved across the JS/wasm boundary (reportTransfer) */
+export interface TransferReport {
+    itemCount: number;
+    /** UTF-8 bytes of the item texts */
+    inputBytes: number;
+    /** UTF-8 bytes of the item results */
+    outputBytes: number;
+    /** Strings and arrays converted on the way in and out */
+    boundaryCrossings: number;
+    /** Wall-clock ms per item, in input order (Date.now resolution) */
+    itemMs: number[];
+    totalMs: number;
+    /** How much wasm memory grew during the call */
+    memoryGrowthBytes: number;
+}
+
+export interface BatchResultsWithTransfer {
+    results: BatchResult[];
+    transfer: TransferReport;
+}
+
+export function batch_justify(
+    texts: (string | BatchItem)[],
+    options: JustifyOptions | undefined,
+    use_cache?: boolean | null,
+    report_transfer?: false | null,
+): BatchResult[];
+export function batch_justify(
+    texts: (string | BatchItem)[],
+    options: JustifyOptions | undefined,
+    use_cache: boolean | null | undefined,
+    report_transfer: true,
+): BatchResultsWithTransfer;
+
+export interface RubyAnnotation {
+    base: string;
+    reading: string;
+    xPx: number;
+    widthPx: number;
+}
+
+export interface LayoutLine {
+    text: string;
+    xPx: number;
+    yPx: number;
+    widthPx: number;
+    /** Present when rubySyntax found annotations on the line */
+    ruby?: RubyAnnotation[];
+    /** Font size multiplier, present on heading lines laid out with a headingScale */
+    fontScale?: number;
+    /** Line height multiplier: 1 without CJK or emoji, cjkLineHeightFactor or
+     * emojiLineHeightFactor (see configure) when the line has them */
+    heightFactor: number;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "JustifyOptions | undefined")]
+    pub type JustifyOptionsArg;
+
+    #[wasm_bindgen(typescript_type = "Required<JustifyOptions>")]
+    pub type FullJustifyOptions;
+
+    #[wasm_bindgen(typescript_type = "(string | BatchItem)[]")]
+    pub type BatchItemArray;
+
+    #[wasm_bindgen(typescript_type = "TextStats")]
+    pub type TextStatsObject;
+
+    #[wasm_bindgen(typescript_type = "LayoutLine[]")]
+    pub type LayoutLineArray;
+
+    #[wasm_bindgen(typescript_type = "WebtoolsError | null")]
+    pub type WebtoolsErrorOrNull;
+
+    #[wasm_bindgen(typescript_type = "(block: string) => void")]
+    pub type BlockSink;
+
+    #[wasm_bindgen(typescript_type = "(block: string, statsSoFar: string) => void")]
+    pub type StatsBlockSink;
+
+    #[wasm_bindgen(typescript_type = "(codePoint: number) => number")]
+    pub type MeasureCallback;
+}
+
+pub(crate) fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+fn invalid_options(error: serde_wasm_bindgen::Error) -> WebtoolsError {
+    WebtoolsError::InvalidArgument {
+        message: format!("Invalid options: {}", error),
+    }
+}
+
+/// Field names of an options object, for the unknown-field checks
+fn option_fields(value: &JsValue) -> Result<Vec<String>, WebtoolsError> {
+    let fields: BTreeMap<String, IgnoredAny> =
+        serde_wasm_bindgen::from_value(value.clone()).map_err(invalid_options)?;
+    Ok(fields.into_keys().collect())
+}
+
+/// Read options from JS; undefined or null means all defaults
+/// Unknown fields are ignored unless the object's strictOptions, or without it configure's, is on
+pub(crate) fn options_from_js(operation: &str, value: JsValue) -> Result<JustifyOptions, JsValue> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(JustifyOptions::default());
+    }
+    let strict_field = js_sys::Reflect::get(&value, &JsValue::from_str("strictOptions"))
+        .ok()
+        .and_then(|strict| strict.as_bool());
+    if strict_for(strict_field) {
+        let fields = option_fields(&value).map_err(|e| to_js_error(operation, e))?;
+        check_option_names(fields.iter().map(String::as_str), true)
+            .map_err(|e| to_js_error(operation, e))?;
+    }
+    let options: JustifyOptions = serde_wasm_bindgen::from_value(value)
+        .map_err(|e| to_js_error(operation, invalid_options(e)))?;
+    profiling::mark(Stage::Decode);
+    options.validate().map_err(|e| to_js_error(operation, e))?;
+    profiling::mark(Stage::Classify);
+    Ok(options)
+}
+
+/// Every option with its default value: the knobs the options-taking exports understand
+#[wasm_bindgen]
+pub fn default_options() -> Result<FullJustifyOptions, JsValue> {
+    Ok(to_js(&JustifyOptions::default())?.unchecked_into())
+}
+
+#[cfg(feature = "json")]
+/// Check an options object without using it
+/// Returns JSON {valid, errors: [{code, message, details}], unknownFields, warnings}: every
+/// problem instead of only the first one the options-taking exports throw. A value that doesn't
+/// parse (such as an unknown rubySyntax) is the single error; unknown fields are listed, and
+/// are errors too when strict options are on. warnings ([{field, overriddenBy, message}]) are
+/// the options another one overrides, which don't make the options invalid
+#[wasm_bindgen]
+pub fn validate_options(options: JustifyOptionsArg) -> String {
+    let value: JsValue = options.into();
+    if value.is_undefined() || value.is_null() {
+        return OptionsReport::new(Ok(JustifyOptions::default()), &[]).to_json();
+    }
+    let (fields, parsed) = match option_fields(&value) {
+        Ok(fields) => {
+            let parsed = serde_wasm_bindgen::from_value(value).map_err(invalid_options);
+            (fields, parsed)
+        }
+        Err(error) => (Vec::new(), Err(error)),
+    };
+    OptionsReport::new(parsed, &fields).to_json()
+}
+
+#[derive(Serialize)]
+struct BatchResultsWithTransfer {
+    results: Vec<BatchResult>,
+    transfer: TransferReport,
+}
+
+/// Structured sibling of validate_text_input
+/// Returns null when the text is valid, otherwise the {code, message, details} error object
+#[wasm_bindgen]
+pub fn validate_text_input_detailed(text: &str) -> Result<WebtoolsErrorOrNull, JsValue> {
+    let value = match validate_text(text) {
+        Ok(()) => JsValue::NULL,
+        Err(error) => to_js(&error)?,
+    };
+    Ok(value.unchecked_into())
+}
+
+/// justify_text driven by an options object ({maxCharsPerLine, lineNumbers, numberStart, stripAnsi})
+/// Options that contradict each other throw CONFLICTING_OPTIONS. With collect_warnings
+/// (default false) returns {text, warnings} instead, text unchanged and warnings listing every
+/// fallback taken: options another one overrode, overflowing or cut words, dropped marks,
+/// unclosed ruby, a cut footer (typed as overloads in TS_TYPES)
+#[wasm_bindgen(skip_typescript)]
+pub fn justify_text_with_options(
+    text: &str,
+    options: JustifyOptionsArg,
+    collect_warnings: Option<bool>,
+) -> Result<JsValue, JsValue> {
+    let _profile = Profile::start("justify_text_with_options");
+    let options = options_from_js("justify_text_with_options", options.into())?;
+    let value = if collect_warnings.unwrap_or(false) {
+        let justified = options.justify_with_warnings(text);
+        profiling::mark(Stage::Wrap);
+        to_js(&justified)?
+    } else {
+        let justified = options
+            .justify_checked(text)
+            .map_err(|e| to_js_error("justify_text_with_options", e))?;
+        profiling::mark(Stage::Wrap);
+        JsValue::from_str(&justified)
+    };
+    profiling::mark(Stage::Serialize);
+    Ok(value)
+}
+
+/// get_text_stats as a JS object
+#[wasm_bindgen]
+pub fn text_stats(text: &str) -> Result<TextStatsObject, JsValue> {
+    Ok(to_js(&TextStats::of(text))?.unchecked_into())
+}
+
+/// Batch justification over a JS array of strings or {id, text} items
+/// Returns an array of {id, text, lineCount} objects in input order, id repeating the item's
+/// own (left out for items without one); size caps and use_cache as batch_justify_text. With report_transfer (default false) returns {results, transfer}
+/// instead, transfer being the TransferReport of the call (typed as overloads in TS_TYPES)
+#[wasm_bindgen(skip_typescript)]
+pub fn batch_justify(
+    texts: BatchItemArray,
+    options: JustifyOptionsArg,
+    use_cache: Option<bool>,
+    report_transfer: Option<bool>,
+) -> Result<JsValue, JsValue> {
+    let _profile = Profile::start("batch_justify");
+    let meter = report_transfer.unwrap_or(false).then(TransferMeter::start);
+    let texts: JsValue = texts.into();
+    // Counted before anything is copied out of the array; sizes are checked once it is
+    if let Some(array) = texts.dyn_ref::<js_sys::Array>() {
+        check_batch_item_count(array.length() as usize)
+            .map_err(|e| to_js_error("batch_justify", e))?;
+    }
+    let items: Vec<BatchItem> = serde_wasm_bindgen::from_value(texts).map_err(|e| {
+        let error = WebtoolsError::InvalidArgument {
+            message: format!("Invalid batch input: {}", e),
+        };
+        to_js_error("batch_justify", error)
+    })?;
+    let options = options_from_js("batch_justify", options.into())?;
+    check_batch_items(&items).map_err(|e| to_js_error("batch_justify", e))?;
+    profiling::mark(Stage::Classify);
+    let _op = Operation::start("batch_justify", items.iter().map(|i| i.text.len()).sum());
+
+    let use_cache = use_cache.unwrap_or(false);
+    let Some(meter) = meter else {
+        let results = justify_batch_items(&items, &options, use_cache);
+        profiling::mark(Stage::Wrap);
+        let value = to_js(&results);
+        profiling::mark(Stage::Serialize);
+        return value;
+    };
+    let (results, mut transfer) = justify_batch_with_report(&items, &options, use_cache, now_ms);
+    profiling::mark(Stage::Wrap);
+    // The array and each string, both ways
+    meter.finish(&mut transfer, 2 * (items.len() + 1));
+    let value = BatchResultsWithTransfer {
+        results: batch_results(&items, results),
+        transfer,
+    };
+    let value = to_js(&value);
+    profiling::mark(Stage::Serialize);
+    value
+}
+
+/// layout_text returning LayoutLine objects instead of a JSON string
+/// With options.rubySyntax each line also lists its readings for the canvas to draw
+#[wasm_bindgen]
+pub fn layout_text_lines(
+    text: &str,
+    options: JustifyOptionsArg,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    align: &str,
+) -> Result<LayoutLineArray, JsValue> {
+    let _profile = Profile::start("layout_text_lines");
+    let options = options_from_js("layout_text_lines", options.into())?;
+    let justified = options
+        .justify_checked(text)
+        .map_err(|e| to_js_error("layout_text_lines", e))?;
+    let lines = layout_lines_with_ruby(
+        &justified,
+        options.max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        Align::parse(align),
+        options.ruby_syntax,
+    );
+    profiling::mark(Stage::Wrap);
+
+    let value = to_js(&lines)?.unchecked_into();
+    profiling::mark(Stage::Serialize);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text_processor_core::{option_names, BatchId, RubySyntax};
+
+    /// Fields of `export interface name` in TS_DEFINITIONS, each with whether it is optional
+    fn interface_fields(name: &str) -> Vec<(&'static str, bool)> {
+        let start = TS_DEFINITIONS
+            .find(&format!("export interface {} {{\n", name))
+            .unwrap_or_else(|| panic!("no interface {}", name));
+        let body = &TS_DEFINITIONS[start..];
+        let body = &body[body.find('\n').unwrap() + 1..body.find("\n}").unwrap()];
+        body.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with("/**") && !line.starts_with('*'))
+            .map(|line| {
+                let name = &line[..line.find(':').unwrap()];
+                match name.strip_suffix('?') {
+                    Some(name) => (name, true),
+                    None => (name, false),
+                }
+            })
+            .collect()
+    }
+
+    /// Check the keys value serializes with against the interface: every key is declared, and
+    /// every field that isn't optional is there
+    fn assert_matches_interface(name: &str, value: &impl Serialize) {
+        let value = serde_json::to_value(value).unwrap();
+        let keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let fields = interface_fields(name);
+        for key in &keys {
+            assert!(
+                fields.iter().any(|(field, _)| field == key),
+                "{} has no {}",
+                name,
+                key
+            );
+        }
+        for (field, optional) in &fields {
+            assert!(
+                *optional || keys.contains(field),
+                "{} without {}",
+                name,
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn justify_options_interface_has_every_option() {
+        let fields: Vec<&str> = interface_fields("JustifyOptions")
+            .into_iter()
+            .map(|(field, optional)| {
+                assert!(optional, "{} isn't optional", field);
+                field
+            })
+            .collect();
+        assert_eq!(fields, option_names());
+        assert_matches_interface("JustifyOptions", &JustifyOptions::default());
+    }
+
+    #[test]
+    fn structured_returns_match_their_interfaces() {
+        assert_matches_interface("TextStats", &TextStats::of("漢字 and text"));
+        for id in [
+            None,
+            Some(BatchId::Integer(3)),
+            Some(BatchId::Text("a".into())),
+        ] {
+            let result = BatchResult {
+                id,
+                text: "wrapped".to_string(),
+                line_count: 1,
+            };
+            assert_matches_interface("BatchResult", &result);
+        }
+        let lines = layout_lines_with_ruby(
+            "漢字《かんじ》\nplain",
+            36,
+            16.0,
+            1.5,
+            20.0,
+            Align::Left,
+            RubySyntax::Kanji,
+        );
+        assert!(!lines[0].ruby.is_empty());
+        for line in &lines {
+            assert_matches_interface("LayoutLine", line);
+        }
+        assert_matches_interface("RubyAnnotation", &lines[0].ruby[0]);
+    }
+}
@@ -0,0 +1,95 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+#[cfg(feature = "json")]
+use crate::logging::Operation;
+
+#[cfg(feature = "json")]
+/// Split wrapped text into several images that each stay under max_height_px
+/// Returns JSON array of {text, lineCount, heightPx}; blocks prefer ending at paragraph breaks.
+/// avoid_widows (default false) keeps a paragraph's last line off the top of an image
+#[wasm_bindgen]
+pub fn paginate_for_images(
+    text: &str,
+    max_chars_per_line: u32,
+    font_size_px: f64,
+    line_height_factor: f64,
+    max_height_px: f64,
+    avoid_widows: Option<bool>,
+) -> String {
+    let _op = Operation::start("paginate_for_images", text.len());
+    core_api::paginate_for_images(
+        text,
+        max_chars_per_line,
+        font_size_px,
+        line_height_factor,
+        max_height_px,
+        avoid_widows.unwrap_or(false),
+    )
+}
+
+/// Paginate wrapped text into pages of at most lines_per_page lines
+/// Wrapped lines are never split; avoid_widows keeps a paragraph's last line off the top of a page
+#[wasm_bindgen]
+pub fn paginate_lines(
+    text: &str,
+    max_chars_per_line: u32,
+    lines_per_page: u32,
+    avoid_widows: bool,
+) -> js_sys::Array {
+    core_api::paginate_lines(text, max_chars_per_line, lines_per_page, avoid_widows)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
+
+#[cfg(feature = "json")]
+/// Distribute wrapped lines into columns, filling each top to bottom
+/// Returns JSON array of columns, each an array of lines. With balance the per-column
+/// height shrinks so the last column isn't nearly empty. Content that needs more than
+/// `columns` columns at lines_per_column spills into extra columns rather than being dropped.
+#[wasm_bindgen]
+pub fn layout_columns(
+    text: &str,
+    max_chars_per_line: u32,
+    columns: u32,
+    lines_per_column: u32,
+    balance: bool,
+) -> String {
+    core_api::layout_columns(text, max_chars_per_line, columns, lines_per_column, balance)
+}
+
+#[cfg(feature = "json")]
+/// Paginate with a header band and a footer band on each page
+/// Returns JSON array of {header, body, footer, lineCount}. The header appears on the first page
+/// (every page with header_on_all_pages); footer_template may use {page} and {pages}.
+/// Both bands are wrapped at max_chars_per_line and their lines are taken out of lines_per_page.
+#[wasm_bindgen]
+pub fn paginate_with_bands(
+    text: &str,
+    max_chars_per_line: u32,
+    lines_per_page: u32,
+    header_text: &str,
+    footer_template: &str,
+    header_on_all_pages: bool,
+) -> String {
+    core_api::paginate_with_bands(
+        text,
+        max_chars_per_line,
+        lines_per_page,
+        header_text,
+        footer_template,
+        header_on_all_pages,
+    )
+}
+
+/// Split a document into n similarly sized sections for an image thread
+/// Cuts only at blank-line paragraph boundaries; returns the section texts in order
+#[wasm_bindgen]
+pub fn split_even_sections(text: &str, n: u32) -> js_sys::Array {
+    core_api::split_even_sections(text, n)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
@@ -0,0 +1,301 @@
+//! Per-stage timings of the major entry points, for telling where a slow call goes
+//! With the `profiling` feature, set_profiling(true) starts collecting: every top-level call
+//! to an instrumented entry point records how long it took and how that splits into stages
+//! (decode, classify, wrap, serialize; see Stage), and take_profile hands the calls over as
+//! JSON. Without the feature Profile is empty and every mark compiles away.
+
+#[cfg(feature = "profiling")]
+pub(crate) use enabled::*;
+
+#[cfg(not(feature = "profiling"))]
+pub(crate) use disabled::*;
+
+#[cfg(not(feature = "profiling"))]
+mod disabled {
+    use text_processor_core::Stage;
+
+    /// Stand-in for the profiler's call guard
+    pub(crate) struct Profile;
+
+    impl Profile {
+        #[inline(always)]
+        pub(crate) fn start(_operation: &'static str) -> Self {
+            Profile
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn mark(_stage: Stage) {}
+}
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use std::cell::{Cell, RefCell};
+
+    use serde::Serialize;
+    use text_processor_core::{set_stage_marker, Stage};
+    use wasm_bindgen::prelude::*;
+
+    use crate::objects::to_js;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+
+    /// Milliseconds spent in each Stage
+    #[derive(Clone, Copy, Default, Serialize)]
+    struct StagesMs {
+        decode: f64,
+        classify: f64,
+        wrap: f64,
+        serialize: f64,
+    }
+
+    impl StagesMs {
+        fn add(&mut self, stage: Stage, ms: f64) {
+            match stage {
+                Stage::Decode => self.decode += ms,
+                Stage::Classify => self.classify += ms,
+                Stage::Wrap => self.wrap += ms,
+                Stage::Serialize => self.serialize += ms,
+            }
+        }
+
+        fn get(&self, stage: Stage) -> f64 {
+            match stage {
+                Stage::Decode => self.decode,
+                Stage::Classify => self.classify,
+                Stage::Wrap => self.wrap,
+                Stage::Serialize => self.serialize,
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct CallProfile {
+        operation: &'static str,
+        /// Whole call, from entering the export to leaving it
+        total_ms: f64,
+        stages_ms: StagesMs,
+    }
+
+    /// The call being profiled
+    struct Running {
+        operation: &'static str,
+        started_ms: f64,
+        last_mark_ms: f64,
+        stages_ms: StagesMs,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ProfileReport {
+        calls: Vec<CallProfile>,
+        total_ms: f64,
+        stages_ms: StagesMs,
+    }
+
+    thread_local! {
+        static COLLECTING: Cell<bool> = const { Cell::new(false) };
+        static RUNNING: RefCell<Option<Running>> = const { RefCell::new(None) };
+        static CALLS: RefCell<Vec<CallProfile>> = const { RefCell::new(Vec::new()) };
+        static CLOCK: Cell<fn() -> f64> = const { Cell::new(performance_now as fn() -> f64) };
+    }
+
+    /// performance.now(), or the clock a test put in its place
+    fn now() -> f64 {
+        CLOCK.with(Cell::get)()
+    }
+
+    #[cfg(test)]
+    fn set_clock(clock: fn() -> f64) {
+        CLOCK.with(|c| c.set(clock));
+    }
+
+    /// Start or stop recording stage timings; stopping keeps what was recorded for take_profile
+    #[wasm_bindgen]
+    pub fn set_profiling(enabled: bool) {
+        COLLECTING.with(|c| c.set(enabled));
+    }
+
+    /// The calls recorded since the last take_profile, which starts over
+    /// Returns JSON {calls: [{operation, totalMs, stagesMs: {decode, classify, wrap,
+    /// serialize}}], totalMs, stagesMs} in call order, the top-level totals summed. A stage a
+    /// call doesn't have is 0, and the stages of a call add up to its totalMs less the time
+    /// spent entering and leaving it. Calls made inside another count towards the outer one.
+    #[wasm_bindgen]
+    pub fn take_profile() -> Result<String, JsValue> {
+        let json = js_sys::JSON::stringify(&to_js(&take_report())?)?;
+        Ok(json.into())
+    }
+
+    fn take_report() -> ProfileReport {
+        let calls = CALLS.with(|calls| std::mem::take(&mut *calls.borrow_mut()));
+        let mut stages_ms = StagesMs::default();
+        for call in &calls {
+            for stage in Stage::ALL {
+                stages_ms.add(stage, call.stages_ms.get(stage));
+            }
+        }
+        ProfileReport {
+            total_ms: calls.iter().map(|call| call.total_ms).sum(),
+            calls,
+            stages_ms,
+        }
+    }
+
+    /// Guard for one call to an instrumented export; the call is recorded when it drops
+    /// Inert unless profiling is on and no other call is being profiled
+    pub(crate) struct Profile {
+        recording: bool,
+    }
+
+    impl Profile {
+        pub(crate) fn start(operation: &'static str) -> Self {
+            if !COLLECTING.with(Cell::get) {
+                return Profile { recording: false };
+            }
+            let recording = RUNNING.with(|running| {
+                let mut running = running.borrow_mut();
+                if running.is_some() {
+                    return false;
+                }
+                let now = now();
+                *running = Some(Running {
+                    operation,
+                    started_ms: now,
+                    last_mark_ms: now,
+                    stages_ms: StagesMs::default(),
+                });
+                true
+            });
+            if recording {
+                set_stage_marker(Some(mark));
+            }
+            Profile { recording }
+        }
+    }
+
+    impl Drop for Profile {
+        fn drop(&mut self) {
+            if !self.recording {
+                return;
+            }
+            set_stage_marker(None);
+            let Some(running) = RUNNING.with(|running| running.borrow_mut().take()) else {
+                return;
+            };
+            let call = CallProfile {
+                operation: running.operation,
+                total_ms: now() - running.started_ms,
+                stages_ms: running.stages_ms,
+            };
+            CALLS.with(|calls| calls.borrow_mut().push(call));
+        }
+    }
+
+    /// The work since the previous mark (or the start of the call) was stage
+    pub(crate) fn mark(stage: Stage) {
+        RUNNING.with(|running| {
+            if let Some(running) = running.borrow_mut().as_mut() {
+                let now = now();
+                running.stages_ms.add(stage, now - running.last_mark_ms);
+                running.last_mark_ms = now;
+            }
+        });
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        thread_local! {
+            static FAKE_NOW: Cell<f64> = const { Cell::new(0.0) };
+        }
+
+        fn fake_now() -> f64 {
+            FAKE_NOW.with(Cell::get)
+        }
+
+        fn at(ms: f64) {
+            FAKE_NOW.with(|now| now.set(ms));
+        }
+
+        fn keys(value: &serde_json::Value) -> Vec<&str> {
+            let mut keys: Vec<&str> = value
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(String::as_str)
+                .collect();
+            keys.sort_unstable();
+            keys
+        }
+
+        #[test]
+        fn the_report_has_the_documented_keys_and_stages_add_up() {
+            set_clock(fake_now);
+            set_profiling(true);
+            at(10.0);
+            {
+                let _call = Profile::start("justifyText");
+                at(12.0);
+                mark(Stage::Decode);
+                // A call made inside another is the outer one's
+                drop(Profile::start("inner"));
+                at(15.0);
+                mark(Stage::Wrap);
+                at(15.5);
+                mark(Stage::Serialize);
+            }
+            {
+                let _call = Profile::start("getTextStats");
+                at(16.5);
+                // Marks from the core go through the stage marker
+                text_processor_core::get_text_stats("text");
+                mark(Stage::Classify);
+            }
+            set_profiling(false);
+            drop(Profile::start("not recorded"));
+
+            let report = serde_json::to_value(take_report()).unwrap();
+            assert_eq!(keys(&report), ["calls", "stagesMs", "totalMs"]);
+            let calls = report["calls"].as_array().unwrap();
+            let operations: Vec<&str> = calls
+                .iter()
+                .map(|c| c["operation"].as_str().unwrap())
+                .collect();
+            assert_eq!(operations, ["justifyText", "getTextStats"]);
+
+            let stages_sum = |stages: &serde_json::Value| -> f64 {
+                assert_eq!(keys(stages), ["classify", "decode", "serialize", "wrap"]);
+                stages
+                    .as_object()
+                    .unwrap()
+                    .values()
+                    .map(|ms| ms.as_f64().unwrap())
+                    .sum()
+            };
+            for call in calls {
+                assert_eq!(keys(call), ["operation", "stagesMs", "totalMs"]);
+                assert_eq!(
+                    stages_sum(&call["stagesMs"]),
+                    call["totalMs"].as_f64().unwrap()
+                );
+            }
+            assert_eq!(calls[0]["totalMs"], 5.5);
+            assert_eq!(calls[0]["stagesMs"]["wrap"], 3.0);
+            assert_eq!(calls[1]["stagesMs"]["classify"], 1.0);
+            assert_eq!(report["totalMs"], 6.5);
+            assert_eq!(stages_sum(&report["stagesMs"]), 6.5);
+
+            assert_eq!(
+                serde_json::to_value(take_report()).unwrap()["calls"],
+                serde_json::json!([])
+            );
+        }
+    }
+}
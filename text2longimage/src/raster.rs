@@ -0,0 +1,24 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+/// Pixel dimensions of the bitmap for the wrapped text: [width, height]
+/// One half-width column is 8 × scale pixels wide, one line 16 × scale pixels tall
+#[wasm_bindgen]
+pub fn bitmap_size(text: &str, max_chars_per_line: u32, scale: u32) -> Vec<u32> {
+    core_api::bitmap_size(text, max_chars_per_line, scale)
+}
+
+/// Rasterize the wrapped text into an RGBA buffer (row-major, 4 bytes per pixel)
+/// ASCII is drawn with the embedded 8×16 font; other characters get an outlined box
+/// of their column width. Colors are 0xRRGGBBAA. Oversized canvases return an empty buffer.
+#[wasm_bindgen]
+pub fn render_bitmap(
+    text: &str,
+    max_chars_per_line: u32,
+    scale: u32,
+    fg_rgba: u32,
+    bg_rgba: u32,
+) -> Vec<u8> {
+    core_api::render_bitmap(text, max_chars_per_line, scale, fg_rgba, bg_rgba)
+}
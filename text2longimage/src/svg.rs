@@ -0,0 +1,33 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::logging::Operation;
+
+/// Render wrapped text as a standalone SVG document
+/// Uses the layout_text positions (top-aligned like canvas textBaseline = "top");
+/// width/height match estimate_image_size
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn render_svg(
+    text: &str,
+    max_chars_per_line: u32,
+    font_family: &str,
+    font_size_px: f64,
+    line_height_factor: f64,
+    padding_px: f64,
+    fg: &str,
+    bg: &str,
+) -> String {
+    let _op = Operation::start("render_svg", text.len());
+    core_api::render_svg(
+        text,
+        max_chars_per_line,
+        font_family,
+        font_size_px,
+        line_height_factor,
+        padding_px,
+        fg,
+        bg,
+    )
+}
@@ -0,0 +1,49 @@
+use text_processor_core::TransferReport;
+#[cfg(feature = "json")]
+use text_processor_core::{self as core_api, WebtoolsError};
+
+use crate::alloc::memory_bytes;
+#[cfg(feature = "json")]
+use crate::logging::now_ms;
+
+/// Measures one batch call for its TransferReport
+pub(crate) struct TransferMeter {
+    memory_bytes: usize,
+}
+
+impl TransferMeter {
+    pub(crate) fn start() -> Self {
+        TransferMeter {
+            memory_bytes: memory_bytes(),
+        }
+    }
+
+    /// Fill in the parts of the report only the wasm side knows
+    pub(crate) fn finish(self, report: &mut TransferReport, boundary_crossings: usize) {
+        report.boundary_crossings = boundary_crossings;
+        report.memory_growth_bytes = memory_bytes().saturating_sub(self.memory_bytes);
+    }
+}
+
+#[cfg(feature = "json")]
+/// batch_justify_text with report_transfer: JSON {results, transfer}
+pub(crate) fn batch_justify_text_with_transfer(
+    texts_json: &str,
+    max_chars_per_line: u32,
+    use_cache: bool,
+) -> Result<String, WebtoolsError> {
+    let meter = TransferMeter::start();
+    let (results, mut transfer) = core_api::batch_justify_text_with_report(
+        texts_json,
+        max_chars_per_line,
+        use_cache,
+        now_ms,
+    )?;
+    // One JSON string in, one out
+    meter.finish(&mut transfer, 2);
+    Ok(format!(
+        r#"{{"results":{},"transfer":{}}}"#,
+        results,
+        transfer.to_json()
+    ))
+}
@@ -0,0 +1,20 @@
+use wasm_bindgen::prelude::*;
+
+use text_processor_core as core_api;
+
+use crate::logging::Operation;
+
+/// justify_text over a Uint16Array of UTF-16 code units, returning a Uint16Array
+/// Skips the UTF-8 encode of a JS string argument; decode the result with
+/// new TextDecoder('utf-16le'). Lone surrogates become U+FFFD (see count_lone_surrogates).
+#[wasm_bindgen]
+pub fn justify_text_utf16(units: &[u16], max_chars_per_line: u32) -> Vec<u16> {
+    let _op = Operation::start("justify_text_utf16", units.len() * 2);
+    core_api::justify_text_utf16(units, max_chars_per_line)
+}
+
+/// Number of unpaired surrogates justify_text_utf16 would replace with U+FFFD
+#[wasm_bindgen]
+pub fn count_lone_surrogates(units: &[u16]) -> u32 {
+    u32::try_from(core_api::count_lone_surrogates(units)).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,398 @@
+//! The core's block-based range tables, generated from the Unicode Character Database
+//! data/Blocks.txt is vendored from the UCD; TABLES says which blocks make up each table.
+//! Checking compares what the generator produces now with the committed core/src/ucd.rs;
+//! after replacing Blocks.txt with a newer one (or changing TABLES), regenerate it with
+//! --update, and the diff shows which ranges moved. Normal builds never run this.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: ucd [--update]
+
+Compare core/src/ucd.rs with the tables generated from data/Blocks.txt.
+
+Options:
+      --update  Regenerate core/src/ucd.rs instead
+  -h, --help    Print this help";
+
+/// Blocks a table takes in
+enum Part {
+    /// One block
+    Block(&'static str),
+    /// From the start of the first block to the end of the second, gaps included, with a
+    /// name for the comment
+    Span(&'static str, &'static str, &'static str),
+}
+
+/// One generated table
+struct Table {
+    name: &'static str,
+    doc: &'static str,
+    parts: &'static [Part],
+}
+
+/// What the generator emits, in this order
+const TABLES: &[Table] = &[
+    Table {
+        name: "CJK_RANGES",
+        doc: "CJK ranges (inclusive)",
+        parts: &[
+            Part::Block("Hiragana"),
+            Part::Block("Katakana"),
+            Part::Block("CJK Unified Ideographs Extension A"),
+            Part::Block("CJK Unified Ideographs"),
+            Part::Block("CJK Unified Ideographs Extension B"),
+        ],
+    },
+    Table {
+        name: "SUPPLEMENTARY_IDEOGRAPH_RANGES",
+        doc: "Ideographs outside the BMP: CJK Extensions B through I and the compatibility \
+              supplement\n(only Extension B counts as CJK for wrapping)",
+        parts: &[
+            Part::Span(
+                "CJK Unified Ideographs Extension B",
+                "CJK Compatibility Ideographs Supplement",
+                "CJK Extensions B to F and I, compatibility supplement",
+            ),
+            Part::Span(
+                "CJK Unified Ideographs Extension G",
+                "CJK Unified Ideographs Extension H",
+                "CJK Extensions G and H",
+            ),
+        ],
+    },
+    Table {
+        name: "NARROW_ASTRAL_RANGES",
+        doc: "Astral-plane letters that fonts draw about as wide as Latin ones: Old Italic, \
+              Gothic,\nDeseret and Mathematical Alphanumeric Symbols. Everything else above \
+              U+FFFF (emoji,\nideographs, other symbols) stays two columns wide.",
+        parts: &[
+            Part::Block("Old Italic"),
+            Part::Block("Gothic"),
+            Part::Block("Deseret"),
+            Part::Block("Mathematical Alphanumeric Symbols"),
+        ],
+    },
+    Table {
+        name: "COMBINING_RANGES",
+        doc: "Combining marks: diacritics plus their extended, supplement, symbol and half-mark \
+              blocks",
+        parts: &[
+            Part::Block("Combining Diacritical Marks"),
+            Part::Block("Combining Diacritical Marks Extended"),
+            Part::Block("Combining Diacritical Marks Supplement"),
+            Part::Block("Combining Diacritical Marks for Symbols"),
+            Part::Block("Combining Half Marks"),
+        ],
+    },
+];
+
+/// Blocks whose ranges are checked before anything is generated, so a parser regression
+/// can't quietly move a table
+const SAMPLES: &[(&str, u32, u32)] = &[
+    ("Basic Latin", 0x0000, 0x007F),
+    ("Combining Diacritical Marks", 0x0300, 0x036F),
+    ("Hiragana", 0x3040, 0x309F),
+    ("CJK Unified Ideographs", 0x4E00, 0x9FFF),
+    ("Combining Half Marks", 0xFE20, 0xFE2F),
+    ("Mathematical Alphanumeric Symbols", 0x1D400, 0x1D7FF),
+    ("CJK Unified Ideographs Extension I", 0x2EBF0, 0x2EE5F),
+    ("Supplementary Private Use Area-B", 0x100000, 0x10FFFF),
+];
+
+/// One line of Blocks.txt
+struct Block {
+    start: u32,
+    end: u32,
+    name: String,
+}
+
+fn ucd_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf()
+}
+
+fn output_path() -> PathBuf {
+    ucd_dir().join("../core/src/ucd.rs")
+}
+
+/// The Unicode version ("15.1.0") and blocks of a Blocks.txt
+/// The version comes from the "# Blocks-15.1.0.txt" line at the top.
+fn parse_blocks(text: &str) -> Result<(String, Vec<Block>), String> {
+    let version = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# Blocks-")?.strip_suffix(".txt"))
+        .ok_or("Blocks.txt: no \"# Blocks-<version>.txt\" line")?
+        .to_string();
+
+    let mut blocks = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let malformed = || format!("Blocks.txt line {}: malformed: {}", index + 1, line);
+        let (range, name) = line.split_once(';').ok_or_else(malformed)?;
+        let (start, end) = range.trim().split_once("..").ok_or_else(malformed)?;
+        let hex = |digits: &str| u32::from_str_radix(digits, 16).map_err(|_| malformed());
+        let block = Block {
+            start: hex(start)?,
+            end: hex(end)?,
+            name: name.trim().to_string(),
+        };
+        if block.start > block.end || blocks.last().is_some_and(|b: &Block| b.end >= block.start) {
+            return Err(format!(
+                "Blocks.txt line {}: {} is out of order",
+                index + 1,
+                block.name
+            ));
+        }
+        blocks.push(block);
+    }
+    Ok((version, blocks))
+}
+
+fn find<'a>(blocks: &'a [Block], name: &str) -> Result<&'a Block, String> {
+    blocks
+        .iter()
+        .find(|block| block.name == name)
+        .ok_or_else(|| format!("Blocks.txt has no block {:?}", name))
+}
+
+/// Check every SAMPLES block has the range it should
+fn check_samples(blocks: &[Block]) -> Result<(), String> {
+    for &(name, start, end) in SAMPLES {
+        let block = find(blocks, name)?;
+        if (block.start, block.end) != (start, end) {
+            return Err(format!(
+                "{} parsed as {:04X}..{:04X}, expected {:04X}..{:04X}",
+                name, block.start, block.end, start, end
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A table's ranges, sorted, with touching ones merged, and the block names in each
+fn ranges(table: &Table, blocks: &[Block]) -> Result<Vec<(u32, u32, Vec<String>)>, String> {
+    let mut parts = Vec::new();
+    for part in table.parts {
+        let (first, last, label) = match *part {
+            Part::Block(name) => (name, name, name),
+            Part::Span(first, last, label) => (first, last, label),
+        };
+        let (first, last) = (find(blocks, first)?, find(blocks, last)?);
+        parts.push((first.start, last.end, vec![label.to_string()]));
+    }
+    parts.sort_by_key(|&(start, _, _)| start);
+
+    let mut merged: Vec<(u32, u32, Vec<String>)> = Vec::new();
+    for (start, end, labels) in parts {
+        match merged.last_mut() {
+            Some(last) if last.1 + 1 >= start => {
+                last.1 = last.1.max(end);
+                last.2.extend(labels);
+            }
+            _ => merged.push((start, end, labels)),
+        }
+    }
+    Ok(merged)
+}
+
+/// The whole of core/src/ucd.rs
+fn generate(version: &str, blocks: &[Block]) -> Result<String, String> {
+    let mut out = format!(
+        "//! Range tables generated from the Unicode Character Database's Blocks.txt\n\
+         //! Written by `cargo run -p text_processor_ucd -- --update`; don't edit by hand.\n\
+         \n\
+         /// Unicode version of the Blocks.txt the tables were generated from\n\
+         pub(crate) const UNICODE_VERSION: &str = \"{}\";\n",
+        version
+    );
+    for table in TABLES {
+        out.push('\n');
+        for line in table.doc.lines() {
+            out.push_str(&format!("/// {}\n", line.trim()));
+        }
+        out.push_str(&format!(
+            "pub(crate) const {}: &[(u32, u32)] = &[\n",
+            table.name
+        ));
+        // Comments lined up as rustfmt lines them up
+        let entries: Vec<(String, String)> = ranges(table, blocks)?
+            .into_iter()
+            .map(|(start, end, labels)| {
+                (
+                    format!("(0x{:04X}, 0x{:04X}),", start, end),
+                    labels.join(", "),
+                )
+            })
+            .collect();
+        let column = entries
+            .iter()
+            .map(|(entry, _)| entry.len())
+            .max()
+            .unwrap_or(0);
+        for (entry, comment) in entries {
+            out.push_str(&format!("    {:<column$} // {}\n", entry, comment));
+        }
+        out.push_str("];\n");
+    }
+    Ok(out)
+}
+
+/// Check (or with update, rewrite) core/src/ucd.rs; Ok(false) when it is out of date
+fn run(update: bool) -> Result<bool, String> {
+    let data = ucd_dir().join("data/Blocks.txt");
+    let text = fs::read_to_string(&data).map_err(|e| format!("{}: {}", data.display(), e))?;
+    let (version, blocks) = parse_blocks(&text)?;
+    check_samples(&blocks)?;
+    let generated = generate(&version, &blocks)?;
+
+    let path = output_path();
+    if update {
+        fs::write(&path, &generated).map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!("core/src/ucd.rs written from Unicode {}", version);
+        return Ok(true);
+    }
+    let up_to_date = fs::read_to_string(&path).is_ok_and(|current| current == generated);
+    if up_to_date {
+        println!("core/src/ucd.rs up to date with Unicode {}", version);
+    }
+    Ok(up_to_date)
+}
+
+fn main() -> ExitCode {
+    let mut update = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--update" => update = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("error: Unknown option: {}\n\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    match run(update) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!(
+                "core/src/ucd.rs doesn't match data/Blocks.txt; run with --update and commit it"
+            );
+            ExitCode::FAILURE
+        }
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+# Blocks-15.1.0.txt
+# Date: 2023-07-28
+
+# Property:\tBlock
+
+0000..007F; Basic Latin
+0080..00FF; Latin-1 Supplement # trailing comment
+
+  0300..036F ;  Combining Diacritical Marks \t
+#1AB0..1AFF; Commented Out
+10000..1007F; Linear B Syllabary
+";
+
+    fn ranges_of(blocks: &[Block]) -> Vec<(u32, u32, &str)> {
+        blocks
+            .iter()
+            .map(|block| (block.start, block.end, block.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let (version, blocks) = parse_blocks(FIXTURE).unwrap();
+        assert_eq!(version, "15.1.0");
+        assert_eq!(
+            ranges_of(&blocks),
+            [
+                (0x0000, 0x007F, "Basic Latin"),
+                (0x0080, 0x00FF, "Latin-1 Supplement"),
+                (0x0300, 0x036F, "Combining Diacritical Marks"),
+                (0x10000, 0x1007F, "Linear B Syllabary"),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_errors_naming_the_line() {
+        for (line, error) in [
+            ("0000..007F Basic Latin", "line 2: malformed"),
+            ("0000-007F; Basic Latin", "line 2: malformed"),
+            ("0000..00G0; Basic Latin", "line 2: malformed"),
+            ("..007F; Basic Latin", "line 2: malformed"),
+            (
+                "007F..0000; Basic Latin",
+                "line 2: Basic Latin is out of order",
+            ),
+        ] {
+            let text = format!("# Blocks-15.1.0.txt\n{}\n", line);
+            let message = parse_blocks(&text).err().unwrap();
+            assert!(message.contains(error), "{line:?}: {message}");
+        }
+    }
+
+    #[test]
+    fn overlapping_blocks_and_a_missing_version_are_errors() {
+        let overlapping = "# Blocks-15.1.0.txt\n0000..007F; A\n0070..00FF; B\n";
+        assert_eq!(
+            parse_blocks(overlapping).err().unwrap(),
+            "Blocks.txt line 3: B is out of order"
+        );
+        assert!(parse_blocks("0000..007F; Basic Latin\n").is_err());
+    }
+
+    #[test]
+    fn touching_parts_merge_into_one_range() {
+        let (_, blocks) = parse_blocks(FIXTURE).unwrap();
+        let table = Table {
+            name: "TEST",
+            doc: "",
+            parts: &[
+                Part::Block("Combining Diacritical Marks"),
+                Part::Block("Latin-1 Supplement"),
+                Part::Block("Basic Latin"),
+            ],
+        };
+        assert_eq!(
+            ranges(&table, &blocks).unwrap(),
+            [
+                (
+                    0x0000,
+                    0x00FF,
+                    vec!["Basic Latin".to_string(), "Latin-1 Supplement".to_string()]
+                ),
+                (
+                    0x0300,
+                    0x036F,
+                    vec!["Combining Diacritical Marks".to_string()]
+                ),
+            ]
+        );
+        let missing = Table {
+            parts: &[Part::Block("Gothic")],
+            ..table
+        };
+        assert!(ranges(&missing, &blocks).is_err());
+    }
+}